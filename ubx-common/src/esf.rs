@@ -0,0 +1,202 @@
+//! Sensor fusion status of the dead reckoning receivers (ESF-STATUS), like the NEO-M8U: the
+//! fusion mode, and the calibration of each sensor in the repeated blocks of the message
+
+/// Sensor fusion status
+pub const ESF_STATUS: (u8, u8) = (0x10, 0x10);
+
+/// Length of the ESF-STATUS header, offset of its sensor count, and length of its blocks
+const HEADER_LEN: usize = 16;
+const COUNT_OFFSET: usize = 15;
+const SENSOR_LEN: usize = 4;
+
+/// Offset of fusionMode
+const FUSION_MODE_OFFSET: usize = 12;
+
+/// sensStatus1 bits: type, used and ready
+const TYPE_MASK: u8 = 0x3f;
+const USED: u8 = 0x40;
+const READY: u8 = 0x80;
+
+/// sensStatus2 bits: calibStatus
+const CALIBRATION_MASK: u8 = 0x03;
+
+/// Faults of the faults bits
+const FAULTS: [(u8, &str); 4] = [
+    (0x01, "bad measurements"),
+    (0x02, "bad time tags"),
+    (0x04, "missing measurements"),
+    (0x08, "noisy measurements"),
+];
+
+/// Name of a sensor data type, like "gyro_z" or "speed"
+pub fn sensor_name(data_type: u8) -> String {
+    match data_type {
+        5 => "gyro_z".to_string(),
+        6 => "front_left_wheel_ticks".to_string(),
+        7 => "front_right_wheel_ticks".to_string(),
+        8 => "rear_left_wheel_ticks".to_string(),
+        9 => "rear_right_wheel_ticks".to_string(),
+        10 => "single_tick".to_string(),
+        11 => "speed".to_string(),
+        12 => "gyro_temperature".to_string(),
+        13 => "gyro_y".to_string(),
+        14 => "gyro_x".to_string(),
+        16 => "accel_x".to_string(),
+        17 => "accel_y".to_string(),
+        18 => "accel_z".to_string(),
+        _ => format!("sensor type {}", data_type),
+    }
+}
+
+/// Fusion mode of the navigation solutions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FusionMode {
+    /// Initializing the sensors, the solutions being GNSS only
+    Initializing,
+    /// Sensors fused into the solutions
+    Fusion,
+    /// Fusion suspended, like without enough satellites
+    Suspended,
+    Disabled,
+    Unknown(u8),
+}
+
+impl FusionMode {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => FusionMode::Initializing,
+            1 => FusionMode::Fusion,
+            2 => FusionMode::Suspended,
+            3 => FusionMode::Disabled,
+            _ => FusionMode::Unknown(byte),
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            FusionMode::Initializing => "initializing".to_string(),
+            FusionMode::Fusion => "fusion".to_string(),
+            FusionMode::Suspended => "suspended".to_string(),
+            FusionMode::Disabled => "disabled".to_string(),
+            FusionMode::Unknown(byte) => format!("mode {}", byte),
+        }
+    }
+}
+
+/// Calibration of a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Calibration {
+    NotCalibrated,
+    /// Being calibrated, not yet usable
+    Calibrating,
+    /// Calibrated, coarsely or finely
+    Calibrated,
+}
+
+impl Calibration {
+    pub fn name(self) -> &'static str {
+        match self {
+            Calibration::NotCalibrated => "not calibrated",
+            Calibration::Calibrating => "calibrating",
+            Calibration::Calibrated => "calibrated",
+        }
+    }
+}
+
+/// Sensor of an ESF-STATUS block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sensor {
+    pub data_type: u8,
+    /// Used in the fusion
+    pub used: bool,
+    /// Configured and available
+    pub ready: bool,
+    pub calibration: Calibration,
+    /// Observation frequency (Hz)
+    pub frequency: u8,
+    /// Faults bits
+    pub faults: u8,
+}
+
+impl Sensor {
+    fn parse(block: &[u8]) -> Self {
+        Self {
+            data_type: block[0] & TYPE_MASK,
+            used: block[0] & USED != 0,
+            ready: block[0] & READY != 0,
+            calibration: match block[1] & CALIBRATION_MASK {
+                0 => Calibration::NotCalibrated,
+                1 => Calibration::Calibrating,
+                _ => Calibration::Calibrated,
+            },
+            frequency: block[2],
+            faults: block[3],
+        }
+    }
+
+    pub fn name(&self) -> String {
+        sensor_name(self.data_type)
+    }
+
+    /// Names of the faults, like ["missing measurements"]
+    pub fn faults(&self) -> Vec<&'static str> {
+        FAULTS
+            .iter()
+            .filter(|(bit, _)| self.faults & bit != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+/// Sensor fusion status, from an ESF-STATUS payload
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub itow: u32,
+    pub fusion_mode: FusionMode,
+    pub sensors: Vec<Sensor>,
+}
+
+impl Status {
+    pub fn parse(payload: &[u8]) -> Result<Self, String> {
+        let blocks = crate::blocks(payload, HEADER_LEN, COUNT_OFFSET, SENSOR_LEN).ok_or_else(|| {
+            format!(
+                "Malformed ESF-STATUS of {} bytes, not holding the {} sensors of its header",
+                payload.len(),
+                payload.get(COUNT_OFFSET).copied().unwrap_or_default()
+            )
+        })?;
+        Ok(Self {
+            itow: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            fusion_mode: FusionMode::from_byte(payload[FUSION_MODE_OFFSET]),
+            sensors: blocks.map(Sensor::parse).collect(),
+        })
+    }
+
+    /// Initializing, or with sensors used while not calibrated yet
+    pub fn initializing(&self) -> bool {
+        self.fusion_mode == FusionMode::Initializing
+            || self
+                .sensors
+                .iter()
+                .any(|sensor| sensor.used && sensor.calibration != Calibration::Calibrated)
+    }
+
+    /// Like "fusion, gyro_z calibrated, speed calibrating, accel_x calibrated (unused),
+    /// single_tick not calibrated (not ready, missing measurements)"
+    pub fn describe(&self) -> String {
+        let mut description = self.fusion_mode.name();
+        for sensor in &self.sensors {
+            description.push_str(&format!(", {} {}", sensor.name(), sensor.calibration.name()));
+            let mut notes = sensor.faults();
+            if !sensor.ready {
+                notes.insert(0, "not ready");
+            } else if !sensor.used {
+                notes.insert(0, "unused");
+            }
+            if !notes.is_empty() {
+                description.push_str(&format!(" ({})", notes.join(", ")));
+            }
+        }
+        description
+    }
+}
@@ -0,0 +1,65 @@
+//! UBX framing, and the checksums of the UBX and RTCM3 frames, for the tools to build and
+//! validate the frames they write and scan
+
+/// Sync characters of the UBX frames
+pub const SYNC: [u8; 2] = [0xb5, 0x62];
+
+/// Generator polynomial of the CRC-24Q of the RTCM3 frames
+const CRC24Q_POLY: u32 = 0x0186_4cfb;
+
+/// UBX frame: `SYNC`, class, id, little endian payload length, payload and the 8-bit
+/// Fletcher checksum of everything following the sync characters
+pub fn frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 8);
+    frame.extend_from_slice(&SYNC);
+    frame.extend_from_slice(&[class, id]);
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+    let (ck_a, ck_b) = checksum(&frame[2..]);
+    frame.extend_from_slice(&[ck_a, ck_b]);
+    frame
+}
+
+/// 8-bit Fletcher checksum of a UBX frame, over class, id, length and payload
+pub fn checksum(data: &[u8]) -> (u8, u8) {
+    data.iter().fold((0u8, 0u8), |(ck_a, ck_b), byte| {
+        let ck_a = ck_a.wrapping_add(*byte);
+        (ck_a, ck_b.wrapping_add(ck_a))
+    })
+}
+
+/// CRC-24Q of an RTCM3 frame, over the preamble, the length and the message
+pub fn crc24q(data: &[u8]) -> u32 {
+    let mut crc = 0_u32;
+    for byte in data {
+        crc ^= u32::from(*byte) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x0100_0000 != 0 {
+                crc ^= CRC24Q_POLY;
+            }
+        }
+    }
+    crc & 0x00ff_ffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_bytes() {
+        // UBX-MON-VER poll
+        assert_eq!(frame(0x0a, 0x04, &[]), [0xb5, 0x62, 0x0a, 0x04, 0x00, 0x00, 0x0e, 0x34]);
+        // UBX-ACK-ACK of UBX-CFG-PRT
+        assert_eq!(
+            frame(0x05, 0x01, &[0x06, 0x00]),
+            [0xb5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x00, 0x0e, 0x37]
+        );
+    }
+
+    #[test]
+    fn crc24q_check_value() {
+        assert_eq!(crc24q(b"123456789"), 0xcd_e703);
+    }
+}
@@ -0,0 +1,29 @@
+//! Informational text messages of the receiver: INF-ERROR, INF-WARNING, INF-NOTICE, INF-TEST
+//! and INF-DEBUG, their payload being the text, without terminator
+
+/// Class of the INF messages
+pub const INF: u8 = 0x04;
+
+/// Severities: name, id of their INF message, and bit of the CFG-INF masks
+pub const SEVERITIES: [(&str, u8, u8); 5] = [
+    ("error", 0x00, 0x01),
+    ("warning", 0x01, 0x02),
+    ("notice", 0x02, 0x04),
+    ("test", 0x03, 0x08),
+    ("debug", 0x04, 0x10),
+];
+
+/// Severity of an INF message id, like "warning"
+pub fn severity(id: u8) -> Option<&'static str> {
+    SEVERITIES
+        .iter()
+        .find(|(_, known, _)| *known == id)
+        .map(|(name, ..)| *name)
+}
+
+/// Text of an INF payload, the bytes not being ASCII replaced, the padding trimmed
+pub fn text(payload: &[u8]) -> String {
+    String::from_utf8_lossy(payload)
+        .trim_end_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
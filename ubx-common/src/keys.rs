@@ -0,0 +1,811 @@
+//! Configuration key database of the generation 9 receivers and later, like the ZED-F9P,
+//! whose configuration is set with UBX-CFG-VALSET and read with UBX-CFG-VALGET.
+//!
+//! Keys are named like CFG-RATE-MEAS, from the table below, or given by their hex id like
+//! 0x30210001. The size of a value is bits 28 to 30 of its key id, so that unknown keys can
+//! still be read and written as raw values: their type comes from a suffix like
+//! 0x40030009:I4, unsigned (or L for 1 bit) without one.
+
+/// Value types: L (boolean), unsigned, signed, float, enumeration and bitfield of a size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    L,
+    U1,
+    U2,
+    U4,
+    U8,
+    I1,
+    I2,
+    I4,
+    I8,
+    R4,
+    R8,
+    E1,
+    E2,
+    E4,
+    X1,
+    X2,
+    X4,
+    X8,
+}
+
+use Type::*;
+
+/// Types by name, for the key ids given with a type
+const TYPES: [(&str, Type); 18] = [
+    ("L", L),
+    ("U1", U1),
+    ("U2", U2),
+    ("U4", U4),
+    ("U8", U8),
+    ("I1", I1),
+    ("I2", I2),
+    ("I4", I4),
+    ("I8", I8),
+    ("R4", R4),
+    ("R8", R8),
+    ("E1", E1),
+    ("E2", E2),
+    ("E4", E4),
+    ("X1", X1),
+    ("X2", X2),
+    ("X4", X4),
+    ("X8", X8),
+];
+
+impl Type {
+    /// Size of the values, in bytes
+    pub fn size(self) -> usize {
+        match self {
+            L | U1 | I1 | E1 | X1 => 1,
+            U2 | I2 | E2 | X2 => 2,
+            U4 | I4 | R4 | E4 | X4 => 4,
+            U8 | I8 | R8 | X8 => 8,
+        }
+    }
+
+    /// Type of the keys of unknown type: L for a bit, unsigned of the size bits of the id
+    fn of_id(id: u32) -> Option<Self> {
+        match (id >> 28) & 0x07 {
+            1 => Some(L),
+            2 => Some(U1),
+            3 => Some(U2),
+            4 => Some(U4),
+            5 => Some(U8),
+            _ => None,
+        }
+    }
+}
+
+/// Known keys, by u-blox name: name, id and type
+pub const KEYS: [(&str, u32, Type); 479] = [
+    ("CFG-UART1-BAUDRATE", 0x40520001, U4),
+    ("CFG-UART1-STOPBITS", 0x20520002, E1),
+    ("CFG-UART1-DATABITS", 0x20520003, E1),
+    ("CFG-UART1-PARITY", 0x20520004, E1),
+    ("CFG-UART1-ENABLED", 0x10520005, L),
+    ("CFG-UART1INPROT-UBX", 0x10730001, L),
+    ("CFG-UART1INPROT-NMEA", 0x10730002, L),
+    ("CFG-UART1INPROT-RTCM3X", 0x10730004, L),
+    ("CFG-UART1OUTPROT-UBX", 0x10740001, L),
+    ("CFG-UART1OUTPROT-NMEA", 0x10740002, L),
+    ("CFG-UART1OUTPROT-RTCM3X", 0x10740004, L),
+    ("CFG-UART2-BAUDRATE", 0x40530001, U4),
+    ("CFG-UART2-STOPBITS", 0x20530002, E1),
+    ("CFG-UART2-DATABITS", 0x20530003, E1),
+    ("CFG-UART2-PARITY", 0x20530004, E1),
+    ("CFG-UART2-ENABLED", 0x10530005, L),
+    ("CFG-UART2-REMAP", 0x10530006, L),
+    ("CFG-UART2INPROT-UBX", 0x10750001, L),
+    ("CFG-UART2INPROT-NMEA", 0x10750002, L),
+    ("CFG-UART2INPROT-RTCM3X", 0x10750004, L),
+    ("CFG-UART2OUTPROT-UBX", 0x10760001, L),
+    ("CFG-UART2OUTPROT-NMEA", 0x10760002, L),
+    ("CFG-UART2OUTPROT-RTCM3X", 0x10760004, L),
+    ("CFG-USB-ENABLED", 0x10650001, L),
+    ("CFG-USB-SELFPOW", 0x10650002, L),
+    ("CFG-USB-VENDOR_ID", 0x3065000a, U2),
+    ("CFG-USB-PRODUCT_ID", 0x3065000b, U2),
+    ("CFG-USB-POWER", 0x3065000c, U2),
+    ("CFG-USB-VENDOR_STR0", 0x5065000d, X8),
+    ("CFG-USB-VENDOR_STR1", 0x5065000e, X8),
+    ("CFG-USB-VENDOR_STR2", 0x5065000f, X8),
+    ("CFG-USB-VENDOR_STR3", 0x50650010, X8),
+    ("CFG-USB-PRODUCT_STR0", 0x50650011, X8),
+    ("CFG-USB-PRODUCT_STR1", 0x50650012, X8),
+    ("CFG-USB-PRODUCT_STR2", 0x50650013, X8),
+    ("CFG-USB-PRODUCT_STR3", 0x50650014, X8),
+    ("CFG-USB-SERIAL_NO_STR0", 0x50650015, X8),
+    ("CFG-USB-SERIAL_NO_STR1", 0x50650016, X8),
+    ("CFG-USB-SERIAL_NO_STR2", 0x50650017, X8),
+    ("CFG-USB-SERIAL_NO_STR3", 0x50650018, X8),
+    ("CFG-USBINPROT-UBX", 0x10770001, L),
+    ("CFG-USBINPROT-NMEA", 0x10770002, L),
+    ("CFG-USBINPROT-RTCM3X", 0x10770004, L),
+    ("CFG-USBOUTPROT-UBX", 0x10780001, L),
+    ("CFG-USBOUTPROT-NMEA", 0x10780002, L),
+    ("CFG-USBOUTPROT-RTCM3X", 0x10780004, L),
+    ("CFG-INFMSG-UBX_I2C", 0x20920001, X1),
+    ("CFG-INFMSG-UBX_UART1", 0x20920002, X1),
+    ("CFG-INFMSG-UBX_UART2", 0x20920003, X1),
+    ("CFG-INFMSG-UBX_USB", 0x20920004, X1),
+    ("CFG-INFMSG-UBX_SPI", 0x20920005, X1),
+    ("CFG-INFMSG-NMEA_I2C", 0x20920006, X1),
+    ("CFG-INFMSG-NMEA_UART1", 0x20920007, X1),
+    ("CFG-INFMSG-NMEA_UART2", 0x20920008, X1),
+    ("CFG-INFMSG-NMEA_USB", 0x20920009, X1),
+    ("CFG-INFMSG-NMEA_SPI", 0x2092000a, X1),
+    ("CFG-RATE-MEAS", 0x30210001, U2),
+    ("CFG-RATE-NAV", 0x30210002, U2),
+    ("CFG-RATE-TIMEREF", 0x20210003, E1),
+    ("CFG-NAVSPG-FIXMODE", 0x20110011, E1),
+    ("CFG-NAVSPG-INIFIX3D", 0x10110013, L),
+    ("CFG-NAVSPG-WKNROLLOVER", 0x30110017, U2),
+    ("CFG-NAVSPG-UTCSTANDARD", 0x2011001c, E1),
+    ("CFG-NAVSPG-DYNMODEL", 0x20110021, E1),
+    ("CFG-NAVSPG-ACKAIDING", 0x10110025, L),
+    ("CFG-NAVSPG-INFIL_MINSVS", 0x201100a1, U1),
+    ("CFG-NAVSPG-INFIL_MAXSVS", 0x201100a2, U1),
+    ("CFG-NAVSPG-INFIL_MINCNO", 0x201100a3, U1),
+    ("CFG-NAVSPG-INFIL_MINELEV", 0x201100a4, I1),
+    ("CFG-NAVSPG-INFIL_NCNOTHRS", 0x201100aa, U1),
+    ("CFG-NAVSPG-INFIL_CNOTHRS", 0x201100ab, U1),
+    ("CFG-NAVSPG-OUTFIL_PDOP", 0x301100b1, U2),
+    ("CFG-NAVSPG-OUTFIL_TDOP", 0x301100b2, U2),
+    ("CFG-NAVSPG-OUTFIL_PACC", 0x301100b3, U2),
+    ("CFG-NAVSPG-OUTFIL_TACC", 0x301100b4, U2),
+    ("CFG-NAVSPG-OUTFIL_FACC", 0x301100b5, U2),
+    ("CFG-NAVSPG-CONSTR_ALT", 0x401100c1, I4),
+    ("CFG-NAVSPG-CONSTR_ALTVAR", 0x401100c2, U4),
+    ("CFG-NAVSPG-CONSTR_DGNSSTO", 0x201100c4, U1),
+    ("CFG-SIGNAL-GPS_ENA", 0x1031001f, L),
+    ("CFG-SIGNAL-GPS_L1CA_ENA", 0x10310001, L),
+    ("CFG-SIGNAL-GPS_L2C_ENA", 0x10310003, L),
+    ("CFG-SIGNAL-SBAS_ENA", 0x10310020, L),
+    ("CFG-SIGNAL-SBAS_L1CA_ENA", 0x10310005, L),
+    ("CFG-SIGNAL-GAL_ENA", 0x10310021, L),
+    ("CFG-SIGNAL-GAL_E1_ENA", 0x10310007, L),
+    ("CFG-SIGNAL-GAL_E5B_ENA", 0x1031000a, L),
+    ("CFG-SIGNAL-BDS_ENA", 0x10310022, L),
+    ("CFG-SIGNAL-BDS_B1_ENA", 0x1031000d, L),
+    ("CFG-SIGNAL-BDS_B2_ENA", 0x1031000e, L),
+    ("CFG-SIGNAL-QZSS_ENA", 0x10310024, L),
+    ("CFG-SIGNAL-QZSS_L1CA_ENA", 0x10310012, L),
+    ("CFG-SIGNAL-QZSS_L1S_ENA", 0x10310014, L),
+    ("CFG-SIGNAL-QZSS_L2C_ENA", 0x10310015, L),
+    ("CFG-SIGNAL-GLO_ENA", 0x10310025, L),
+    ("CFG-SIGNAL-GLO_L1_ENA", 0x10310018, L),
+    ("CFG-SIGNAL-GLO_L2_ENA", 0x1031001a, L),
+    ("CFG-TMODE-MODE", 0x20030001, E1),
+    ("CFG-TMODE-POS_TYPE", 0x20030002, E1),
+    ("CFG-TMODE-ECEF_X", 0x40030003, I4),
+    ("CFG-TMODE-ECEF_Y", 0x40030004, I4),
+    ("CFG-TMODE-ECEF_Z", 0x40030005, I4),
+    ("CFG-TMODE-ECEF_X_HP", 0x20030006, I1),
+    ("CFG-TMODE-ECEF_Y_HP", 0x20030007, I1),
+    ("CFG-TMODE-ECEF_Z_HP", 0x20030008, I1),
+    ("CFG-TMODE-LAT", 0x40030009, I4),
+    ("CFG-TMODE-LON", 0x4003000a, I4),
+    ("CFG-TMODE-HEIGHT", 0x4003000b, I4),
+    ("CFG-TMODE-LAT_HP", 0x2003000c, I1),
+    ("CFG-TMODE-LON_HP", 0x2003000d, I1),
+    ("CFG-TMODE-HEIGHT_HP", 0x2003000e, I1),
+    ("CFG-TMODE-FIXED_POS_ACC", 0x4003000f, U4),
+    ("CFG-TMODE-SVIN_MIN_DUR", 0x40030010, U4),
+    ("CFG-TMODE-SVIN_ACC_LIMIT", 0x40030011, U4),
+    ("CFG-TP-PULSE_DEF", 0x20050023, E1),
+    ("CFG-TP-PULSE_LENGTH_DEF", 0x20050030, E1),
+    ("CFG-TP-ANT_CABLEDELAY", 0x30050001, I2),
+    ("CFG-TP-PERIOD_TP1", 0x40050002, U4),
+    ("CFG-TP-PERIOD_LOCK_TP1", 0x40050003, U4),
+    ("CFG-TP-FREQ_TP1", 0x40050024, U4),
+    ("CFG-TP-FREQ_LOCK_TP1", 0x40050025, U4),
+    ("CFG-TP-LEN_TP1", 0x40050004, U4),
+    ("CFG-TP-LEN_LOCK_TP1", 0x40050005, U4),
+    ("CFG-TP-TP1_ENA", 0x10050007, L),
+    ("CFG-TP-SYNC_GNSS_TP1", 0x10050008, L),
+    ("CFG-TP-USE_LOCKED_TP1", 0x10050009, L),
+    ("CFG-TP-ALIGN_TO_TOW_TP1", 0x1005000a, L),
+    ("CFG-TP-POL_TP1", 0x1005000b, L),
+    ("CFG-TP-TIMEGRID_TP1", 0x2005000c, E1),
+    ("CFG-ITFM-BBTHRESHOLD", 0x20410001, U1),
+    ("CFG-ITFM-CWTHRESHOLD", 0x20410002, U1),
+    ("CFG-ITFM-ENABLE", 0x1041000d, L),
+    ("CFG-ITFM-ANTSETTING", 0x20410010, E1),
+    ("CFG-ITFM-ENABLE_AUX", 0x10410013, L),
+    ("CFG-MSGOUT-NMEA_ID_DTM_I2C", 0x209100a6, U1),
+    ("CFG-MSGOUT-NMEA_ID_DTM_SPI", 0x209100aa, U1),
+    ("CFG-MSGOUT-NMEA_ID_DTM_UART1", 0x209100a7, U1),
+    ("CFG-MSGOUT-NMEA_ID_DTM_UART2", 0x209100a8, U1),
+    ("CFG-MSGOUT-NMEA_ID_DTM_USB", 0x209100a9, U1),
+    ("CFG-MSGOUT-NMEA_ID_GBS_I2C", 0x209100dd, U1),
+    ("CFG-MSGOUT-NMEA_ID_GBS_SPI", 0x209100e1, U1),
+    ("CFG-MSGOUT-NMEA_ID_GBS_UART1", 0x209100de, U1),
+    ("CFG-MSGOUT-NMEA_ID_GBS_UART2", 0x209100df, U1),
+    ("CFG-MSGOUT-NMEA_ID_GBS_USB", 0x209100e0, U1),
+    ("CFG-MSGOUT-NMEA_ID_GGA_I2C", 0x209100ba, U1),
+    ("CFG-MSGOUT-NMEA_ID_GGA_SPI", 0x209100be, U1),
+    ("CFG-MSGOUT-NMEA_ID_GGA_UART1", 0x209100bb, U1),
+    ("CFG-MSGOUT-NMEA_ID_GGA_UART2", 0x209100bc, U1),
+    ("CFG-MSGOUT-NMEA_ID_GGA_USB", 0x209100bd, U1),
+    ("CFG-MSGOUT-NMEA_ID_GLL_I2C", 0x209100c9, U1),
+    ("CFG-MSGOUT-NMEA_ID_GLL_SPI", 0x209100cd, U1),
+    ("CFG-MSGOUT-NMEA_ID_GLL_UART1", 0x209100ca, U1),
+    ("CFG-MSGOUT-NMEA_ID_GLL_UART2", 0x209100cb, U1),
+    ("CFG-MSGOUT-NMEA_ID_GLL_USB", 0x209100cc, U1),
+    ("CFG-MSGOUT-NMEA_ID_GNS_I2C", 0x209100b5, U1),
+    ("CFG-MSGOUT-NMEA_ID_GNS_SPI", 0x209100b9, U1),
+    ("CFG-MSGOUT-NMEA_ID_GNS_UART1", 0x209100b6, U1),
+    ("CFG-MSGOUT-NMEA_ID_GNS_UART2", 0x209100b7, U1),
+    ("CFG-MSGOUT-NMEA_ID_GNS_USB", 0x209100b8, U1),
+    ("CFG-MSGOUT-NMEA_ID_GRS_I2C", 0x209100ce, U1),
+    ("CFG-MSGOUT-NMEA_ID_GRS_SPI", 0x209100d2, U1),
+    ("CFG-MSGOUT-NMEA_ID_GRS_UART1", 0x209100cf, U1),
+    ("CFG-MSGOUT-NMEA_ID_GRS_UART2", 0x209100d0, U1),
+    ("CFG-MSGOUT-NMEA_ID_GRS_USB", 0x209100d1, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSA_I2C", 0x209100bf, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSA_SPI", 0x209100c3, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSA_UART1", 0x209100c0, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSA_UART2", 0x209100c1, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSA_USB", 0x209100c2, U1),
+    ("CFG-MSGOUT-NMEA_ID_GST_I2C", 0x209100d3, U1),
+    ("CFG-MSGOUT-NMEA_ID_GST_SPI", 0x209100d7, U1),
+    ("CFG-MSGOUT-NMEA_ID_GST_UART1", 0x209100d4, U1),
+    ("CFG-MSGOUT-NMEA_ID_GST_UART2", 0x209100d5, U1),
+    ("CFG-MSGOUT-NMEA_ID_GST_USB", 0x209100d6, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSV_I2C", 0x209100c4, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSV_SPI", 0x209100c8, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSV_UART1", 0x209100c5, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSV_UART2", 0x209100c6, U1),
+    ("CFG-MSGOUT-NMEA_ID_GSV_USB", 0x209100c7, U1),
+    ("CFG-MSGOUT-NMEA_ID_RMC_I2C", 0x209100ab, U1),
+    ("CFG-MSGOUT-NMEA_ID_RMC_SPI", 0x209100af, U1),
+    ("CFG-MSGOUT-NMEA_ID_RMC_UART1", 0x209100ac, U1),
+    ("CFG-MSGOUT-NMEA_ID_RMC_UART2", 0x209100ad, U1),
+    ("CFG-MSGOUT-NMEA_ID_RMC_USB", 0x209100ae, U1),
+    ("CFG-MSGOUT-NMEA_ID_VLW_I2C", 0x209100e7, U1),
+    ("CFG-MSGOUT-NMEA_ID_VLW_SPI", 0x209100eb, U1),
+    ("CFG-MSGOUT-NMEA_ID_VLW_UART1", 0x209100e8, U1),
+    ("CFG-MSGOUT-NMEA_ID_VLW_UART2", 0x209100e9, U1),
+    ("CFG-MSGOUT-NMEA_ID_VLW_USB", 0x209100ea, U1),
+    ("CFG-MSGOUT-NMEA_ID_VTG_I2C", 0x209100b0, U1),
+    ("CFG-MSGOUT-NMEA_ID_VTG_SPI", 0x209100b4, U1),
+    ("CFG-MSGOUT-NMEA_ID_VTG_UART1", 0x209100b1, U1),
+    ("CFG-MSGOUT-NMEA_ID_VTG_UART2", 0x209100b2, U1),
+    ("CFG-MSGOUT-NMEA_ID_VTG_USB", 0x209100b3, U1),
+    ("CFG-MSGOUT-NMEA_ID_ZDA_I2C", 0x209100d8, U1),
+    ("CFG-MSGOUT-NMEA_ID_ZDA_SPI", 0x209100dc, U1),
+    ("CFG-MSGOUT-NMEA_ID_ZDA_UART1", 0x209100d9, U1),
+    ("CFG-MSGOUT-NMEA_ID_ZDA_UART2", 0x209100da, U1),
+    ("CFG-MSGOUT-NMEA_ID_ZDA_USB", 0x209100db, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYP_I2C", 0x209100ec, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYP_SPI", 0x209100f0, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYP_UART1", 0x209100ed, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYP_UART2", 0x209100ee, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYP_USB", 0x209100ef, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYS_I2C", 0x209100f1, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYS_SPI", 0x209100f5, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYS_UART1", 0x209100f2, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYS_UART2", 0x209100f3, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYS_USB", 0x209100f4, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYT_I2C", 0x209100f6, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYT_SPI", 0x209100fa, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYT_UART1", 0x209100f7, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYT_UART2", 0x209100f8, U1),
+    ("CFG-MSGOUT-PUBX_ID_POLYT_USB", 0x209100f9, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1005_I2C", 0x209102bd, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1005_SPI", 0x209102c1, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1005_UART1", 0x209102be, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1005_UART2", 0x209102bf, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1005_USB", 0x209102c0, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1074_I2C", 0x2091035e, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1074_SPI", 0x20910362, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1074_UART1", 0x2091035f, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1074_UART2", 0x20910360, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1074_USB", 0x20910361, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1077_I2C", 0x209102cc, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1077_SPI", 0x209102d0, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1077_UART1", 0x209102cd, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1077_UART2", 0x209102ce, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1077_USB", 0x209102cf, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1084_I2C", 0x20910363, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1084_SPI", 0x20910367, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1084_UART1", 0x20910364, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1084_UART2", 0x20910365, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1084_USB", 0x20910366, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1087_I2C", 0x209102d1, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1087_SPI", 0x209102d5, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1087_UART1", 0x209102d2, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1087_UART2", 0x209102d3, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1087_USB", 0x209102d4, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1094_I2C", 0x20910368, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1094_SPI", 0x2091036c, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1094_UART1", 0x20910369, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1094_UART2", 0x2091036a, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1094_USB", 0x2091036b, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1097_I2C", 0x20910318, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1097_SPI", 0x2091031c, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1097_UART1", 0x20910319, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1097_UART2", 0x2091031a, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1097_USB", 0x2091031b, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1124_I2C", 0x2091036d, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1124_SPI", 0x20910371, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1124_UART1", 0x2091036e, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1124_UART2", 0x2091036f, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1124_USB", 0x20910370, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1127_I2C", 0x209102d6, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1127_SPI", 0x209102da, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1127_UART1", 0x209102d7, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1127_UART2", 0x209102d8, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1127_USB", 0x209102d9, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1230_I2C", 0x20910303, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1230_SPI", 0x20910307, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1230_UART1", 0x20910304, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1230_UART2", 0x20910305, U1),
+    ("CFG-MSGOUT-RTCM_3X_TYPE1230_USB", 0x20910306, U1),
+    ("CFG-MSGOUT-UBX_LOG_INFO_I2C", 0x20910259, U1),
+    ("CFG-MSGOUT-UBX_LOG_INFO_SPI", 0x2091025d, U1),
+    ("CFG-MSGOUT-UBX_LOG_INFO_UART1", 0x2091025a, U1),
+    ("CFG-MSGOUT-UBX_LOG_INFO_UART2", 0x2091025b, U1),
+    ("CFG-MSGOUT-UBX_LOG_INFO_USB", 0x2091025c, U1),
+    ("CFG-MSGOUT-UBX_MON_COMMS_I2C", 0x2091034f, U1),
+    ("CFG-MSGOUT-UBX_MON_COMMS_SPI", 0x20910353, U1),
+    ("CFG-MSGOUT-UBX_MON_COMMS_UART1", 0x20910350, U1),
+    ("CFG-MSGOUT-UBX_MON_COMMS_UART2", 0x20910351, U1),
+    ("CFG-MSGOUT-UBX_MON_COMMS_USB", 0x20910352, U1),
+    ("CFG-MSGOUT-UBX_MON_HW2_I2C", 0x209101b9, U1),
+    ("CFG-MSGOUT-UBX_MON_HW2_SPI", 0x209101bd, U1),
+    ("CFG-MSGOUT-UBX_MON_HW2_UART1", 0x209101ba, U1),
+    ("CFG-MSGOUT-UBX_MON_HW2_UART2", 0x209101bb, U1),
+    ("CFG-MSGOUT-UBX_MON_HW2_USB", 0x209101bc, U1),
+    ("CFG-MSGOUT-UBX_MON_HW3_I2C", 0x20910354, U1),
+    ("CFG-MSGOUT-UBX_MON_HW3_SPI", 0x20910358, U1),
+    ("CFG-MSGOUT-UBX_MON_HW3_UART1", 0x20910355, U1),
+    ("CFG-MSGOUT-UBX_MON_HW3_UART2", 0x20910356, U1),
+    ("CFG-MSGOUT-UBX_MON_HW3_USB", 0x20910357, U1),
+    ("CFG-MSGOUT-UBX_MON_HW_I2C", 0x209101b4, U1),
+    ("CFG-MSGOUT-UBX_MON_HW_SPI", 0x209101b8, U1),
+    ("CFG-MSGOUT-UBX_MON_HW_UART1", 0x209101b5, U1),
+    ("CFG-MSGOUT-UBX_MON_HW_UART2", 0x209101b6, U1),
+    ("CFG-MSGOUT-UBX_MON_HW_USB", 0x209101b7, U1),
+    ("CFG-MSGOUT-UBX_MON_IO_I2C", 0x209101a5, U1),
+    ("CFG-MSGOUT-UBX_MON_IO_SPI", 0x209101a9, U1),
+    ("CFG-MSGOUT-UBX_MON_IO_UART1", 0x209101a6, U1),
+    ("CFG-MSGOUT-UBX_MON_IO_UART2", 0x209101a7, U1),
+    ("CFG-MSGOUT-UBX_MON_IO_USB", 0x209101a8, U1),
+    ("CFG-MSGOUT-UBX_MON_MSGPP_I2C", 0x20910196, U1),
+    ("CFG-MSGOUT-UBX_MON_MSGPP_SPI", 0x2091019a, U1),
+    ("CFG-MSGOUT-UBX_MON_MSGPP_UART1", 0x20910197, U1),
+    ("CFG-MSGOUT-UBX_MON_MSGPP_UART2", 0x20910198, U1),
+    ("CFG-MSGOUT-UBX_MON_MSGPP_USB", 0x20910199, U1),
+    ("CFG-MSGOUT-UBX_MON_RF_I2C", 0x20910359, U1),
+    ("CFG-MSGOUT-UBX_MON_RF_SPI", 0x2091035d, U1),
+    ("CFG-MSGOUT-UBX_MON_RF_UART1", 0x2091035a, U1),
+    ("CFG-MSGOUT-UBX_MON_RF_UART2", 0x2091035b, U1),
+    ("CFG-MSGOUT-UBX_MON_RF_USB", 0x2091035c, U1),
+    ("CFG-MSGOUT-UBX_MON_RXBUF_I2C", 0x209101a0, U1),
+    ("CFG-MSGOUT-UBX_MON_RXBUF_SPI", 0x209101a4, U1),
+    ("CFG-MSGOUT-UBX_MON_RXBUF_UART1", 0x209101a1, U1),
+    ("CFG-MSGOUT-UBX_MON_RXBUF_UART2", 0x209101a2, U1),
+    ("CFG-MSGOUT-UBX_MON_RXBUF_USB", 0x209101a3, U1),
+    ("CFG-MSGOUT-UBX_MON_RXR_I2C", 0x20910187, U1),
+    ("CFG-MSGOUT-UBX_MON_RXR_SPI", 0x2091018b, U1),
+    ("CFG-MSGOUT-UBX_MON_RXR_UART1", 0x20910188, U1),
+    ("CFG-MSGOUT-UBX_MON_RXR_UART2", 0x20910189, U1),
+    ("CFG-MSGOUT-UBX_MON_RXR_USB", 0x2091018a, U1),
+    ("CFG-MSGOUT-UBX_MON_TXBUF_I2C", 0x2091019b, U1),
+    ("CFG-MSGOUT-UBX_MON_TXBUF_SPI", 0x2091019f, U1),
+    ("CFG-MSGOUT-UBX_MON_TXBUF_UART1", 0x2091019c, U1),
+    ("CFG-MSGOUT-UBX_MON_TXBUF_UART2", 0x2091019d, U1),
+    ("CFG-MSGOUT-UBX_MON_TXBUF_USB", 0x2091019e, U1),
+    ("CFG-MSGOUT-UBX_NAV_CLOCK_I2C", 0x20910065, U1),
+    ("CFG-MSGOUT-UBX_NAV_CLOCK_SPI", 0x20910069, U1),
+    ("CFG-MSGOUT-UBX_NAV_CLOCK_UART1", 0x20910066, U1),
+    ("CFG-MSGOUT-UBX_NAV_CLOCK_UART2", 0x20910067, U1),
+    ("CFG-MSGOUT-UBX_NAV_CLOCK_USB", 0x20910068, U1),
+    ("CFG-MSGOUT-UBX_NAV_DOP_I2C", 0x20910038, U1),
+    ("CFG-MSGOUT-UBX_NAV_DOP_SPI", 0x2091003c, U1),
+    ("CFG-MSGOUT-UBX_NAV_DOP_UART1", 0x20910039, U1),
+    ("CFG-MSGOUT-UBX_NAV_DOP_UART2", 0x2091003a, U1),
+    ("CFG-MSGOUT-UBX_NAV_DOP_USB", 0x2091003b, U1),
+    ("CFG-MSGOUT-UBX_NAV_EOE_I2C", 0x2091015f, U1),
+    ("CFG-MSGOUT-UBX_NAV_EOE_SPI", 0x20910163, U1),
+    ("CFG-MSGOUT-UBX_NAV_EOE_UART1", 0x20910160, U1),
+    ("CFG-MSGOUT-UBX_NAV_EOE_UART2", 0x20910161, U1),
+    ("CFG-MSGOUT-UBX_NAV_EOE_USB", 0x20910162, U1),
+    ("CFG-MSGOUT-UBX_NAV_GEOFENCE_I2C", 0x209100a1, U1),
+    ("CFG-MSGOUT-UBX_NAV_GEOFENCE_SPI", 0x209100a5, U1),
+    ("CFG-MSGOUT-UBX_NAV_GEOFENCE_UART1", 0x209100a2, U1),
+    ("CFG-MSGOUT-UBX_NAV_GEOFENCE_UART2", 0x209100a3, U1),
+    ("CFG-MSGOUT-UBX_NAV_GEOFENCE_USB", 0x209100a4, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSECEF_I2C", 0x2091002e, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSECEF_SPI", 0x20910032, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSECEF_UART1", 0x2091002f, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSECEF_UART2", 0x20910030, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSECEF_USB", 0x20910031, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSLLH_I2C", 0x20910033, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSLLH_SPI", 0x20910037, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSLLH_UART1", 0x20910034, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSLLH_UART2", 0x20910035, U1),
+    ("CFG-MSGOUT-UBX_NAV_HPPOSLLH_USB", 0x20910036, U1),
+    ("CFG-MSGOUT-UBX_NAV_ODO_I2C", 0x2091007e, U1),
+    ("CFG-MSGOUT-UBX_NAV_ODO_SPI", 0x20910082, U1),
+    ("CFG-MSGOUT-UBX_NAV_ODO_UART1", 0x2091007f, U1),
+    ("CFG-MSGOUT-UBX_NAV_ODO_UART2", 0x20910080, U1),
+    ("CFG-MSGOUT-UBX_NAV_ODO_USB", 0x20910081, U1),
+    ("CFG-MSGOUT-UBX_NAV_ORB_I2C", 0x20910010, U1),
+    ("CFG-MSGOUT-UBX_NAV_ORB_SPI", 0x20910014, U1),
+    ("CFG-MSGOUT-UBX_NAV_ORB_UART1", 0x20910011, U1),
+    ("CFG-MSGOUT-UBX_NAV_ORB_UART2", 0x20910012, U1),
+    ("CFG-MSGOUT-UBX_NAV_ORB_USB", 0x20910013, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSECEF_I2C", 0x20910024, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSECEF_SPI", 0x20910028, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSECEF_UART1", 0x20910025, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSECEF_UART2", 0x20910026, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSECEF_USB", 0x20910027, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSLLH_I2C", 0x20910029, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSLLH_SPI", 0x2091002d, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSLLH_UART1", 0x2091002a, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSLLH_UART2", 0x2091002b, U1),
+    ("CFG-MSGOUT-UBX_NAV_POSLLH_USB", 0x2091002c, U1),
+    ("CFG-MSGOUT-UBX_NAV_PVT_I2C", 0x20910006, U1),
+    ("CFG-MSGOUT-UBX_NAV_PVT_SPI", 0x2091000a, U1),
+    ("CFG-MSGOUT-UBX_NAV_PVT_UART1", 0x20910007, U1),
+    ("CFG-MSGOUT-UBX_NAV_PVT_UART2", 0x20910008, U1),
+    ("CFG-MSGOUT-UBX_NAV_PVT_USB", 0x20910009, U1),
+    ("CFG-MSGOUT-UBX_NAV_RELPOSNED_I2C", 0x2091008d, U1),
+    ("CFG-MSGOUT-UBX_NAV_RELPOSNED_SPI", 0x20910091, U1),
+    ("CFG-MSGOUT-UBX_NAV_RELPOSNED_UART1", 0x2091008e, U1),
+    ("CFG-MSGOUT-UBX_NAV_RELPOSNED_UART2", 0x2091008f, U1),
+    ("CFG-MSGOUT-UBX_NAV_RELPOSNED_USB", 0x20910090, U1),
+    ("CFG-MSGOUT-UBX_NAV_SAT_I2C", 0x20910015, U1),
+    ("CFG-MSGOUT-UBX_NAV_SAT_SPI", 0x20910019, U1),
+    ("CFG-MSGOUT-UBX_NAV_SAT_UART1", 0x20910016, U1),
+    ("CFG-MSGOUT-UBX_NAV_SAT_UART2", 0x20910017, U1),
+    ("CFG-MSGOUT-UBX_NAV_SAT_USB", 0x20910018, U1),
+    ("CFG-MSGOUT-UBX_NAV_SIG_I2C", 0x20910345, U1),
+    ("CFG-MSGOUT-UBX_NAV_SIG_SPI", 0x20910349, U1),
+    ("CFG-MSGOUT-UBX_NAV_SIG_UART1", 0x20910346, U1),
+    ("CFG-MSGOUT-UBX_NAV_SIG_UART2", 0x20910347, U1),
+    ("CFG-MSGOUT-UBX_NAV_SIG_USB", 0x20910348, U1),
+    ("CFG-MSGOUT-UBX_NAV_STATUS_I2C", 0x2091001a, U1),
+    ("CFG-MSGOUT-UBX_NAV_STATUS_SPI", 0x2091001e, U1),
+    ("CFG-MSGOUT-UBX_NAV_STATUS_UART1", 0x2091001b, U1),
+    ("CFG-MSGOUT-UBX_NAV_STATUS_UART2", 0x2091001c, U1),
+    ("CFG-MSGOUT-UBX_NAV_STATUS_USB", 0x2091001d, U1),
+    ("CFG-MSGOUT-UBX_NAV_SVIN_I2C", 0x20910088, U1),
+    ("CFG-MSGOUT-UBX_NAV_SVIN_SPI", 0x2091008c, U1),
+    ("CFG-MSGOUT-UBX_NAV_SVIN_UART1", 0x20910089, U1),
+    ("CFG-MSGOUT-UBX_NAV_SVIN_UART2", 0x2091008a, U1),
+    ("CFG-MSGOUT-UBX_NAV_SVIN_USB", 0x2091008b, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEBDS_I2C", 0x20910051, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEBDS_SPI", 0x20910055, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEBDS_UART1", 0x20910052, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEBDS_UART2", 0x20910053, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEBDS_USB", 0x20910054, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGAL_I2C", 0x20910056, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGAL_SPI", 0x2091005a, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGAL_UART1", 0x20910057, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGAL_UART2", 0x20910058, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGAL_USB", 0x20910059, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGLO_I2C", 0x2091004c, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGLO_SPI", 0x20910050, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGLO_UART1", 0x2091004d, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGLO_UART2", 0x2091004e, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGLO_USB", 0x2091004f, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGPS_I2C", 0x20910047, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGPS_SPI", 0x2091004b, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGPS_UART1", 0x20910048, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGPS_UART2", 0x20910049, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEGPS_USB", 0x2091004a, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMELS_I2C", 0x20910060, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMELS_SPI", 0x20910064, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMELS_UART1", 0x20910061, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMELS_UART2", 0x20910062, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMELS_USB", 0x20910063, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEUTC_I2C", 0x2091005b, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEUTC_SPI", 0x2091005f, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEUTC_UART1", 0x2091005c, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEUTC_UART2", 0x2091005d, U1),
+    ("CFG-MSGOUT-UBX_NAV_TIMEUTC_USB", 0x2091005e, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELECEF_I2C", 0x2091003d, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELECEF_SPI", 0x20910041, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELECEF_UART1", 0x2091003e, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELECEF_UART2", 0x2091003f, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELECEF_USB", 0x20910040, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELNED_I2C", 0x20910042, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELNED_SPI", 0x20910046, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELNED_UART1", 0x20910043, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELNED_UART2", 0x20910044, U1),
+    ("CFG-MSGOUT-UBX_NAV_VELNED_USB", 0x20910045, U1),
+    ("CFG-MSGOUT-UBX_RXM_MEASX_I2C", 0x20910204, U1),
+    ("CFG-MSGOUT-UBX_RXM_MEASX_SPI", 0x20910208, U1),
+    ("CFG-MSGOUT-UBX_RXM_MEASX_UART1", 0x20910205, U1),
+    ("CFG-MSGOUT-UBX_RXM_MEASX_UART2", 0x20910206, U1),
+    ("CFG-MSGOUT-UBX_RXM_MEASX_USB", 0x20910207, U1),
+    ("CFG-MSGOUT-UBX_RXM_RAWX_I2C", 0x209102a4, U1),
+    ("CFG-MSGOUT-UBX_RXM_RAWX_SPI", 0x209102a8, U1),
+    ("CFG-MSGOUT-UBX_RXM_RAWX_UART1", 0x209102a5, U1),
+    ("CFG-MSGOUT-UBX_RXM_RAWX_UART2", 0x209102a6, U1),
+    ("CFG-MSGOUT-UBX_RXM_RAWX_USB", 0x209102a7, U1),
+    ("CFG-MSGOUT-UBX_RXM_RLM_I2C", 0x2091025e, U1),
+    ("CFG-MSGOUT-UBX_RXM_RLM_SPI", 0x20910262, U1),
+    ("CFG-MSGOUT-UBX_RXM_RLM_UART1", 0x2091025f, U1),
+    ("CFG-MSGOUT-UBX_RXM_RLM_UART2", 0x20910260, U1),
+    ("CFG-MSGOUT-UBX_RXM_RLM_USB", 0x20910261, U1),
+    ("CFG-MSGOUT-UBX_RXM_RTCM_I2C", 0x20910268, U1),
+    ("CFG-MSGOUT-UBX_RXM_RTCM_SPI", 0x2091026c, U1),
+    ("CFG-MSGOUT-UBX_RXM_RTCM_UART1", 0x20910269, U1),
+    ("CFG-MSGOUT-UBX_RXM_RTCM_UART2", 0x2091026a, U1),
+    ("CFG-MSGOUT-UBX_RXM_RTCM_USB", 0x2091026b, U1),
+    ("CFG-MSGOUT-UBX_RXM_SFRBX_I2C", 0x20910231, U1),
+    ("CFG-MSGOUT-UBX_RXM_SFRBX_SPI", 0x20910235, U1),
+    ("CFG-MSGOUT-UBX_RXM_SFRBX_UART1", 0x20910232, U1),
+    ("CFG-MSGOUT-UBX_RXM_SFRBX_UART2", 0x20910233, U1),
+    ("CFG-MSGOUT-UBX_RXM_SFRBX_USB", 0x20910234, U1),
+    ("CFG-MSGOUT-UBX_TIM_TM2_I2C", 0x20910178, U1),
+    ("CFG-MSGOUT-UBX_TIM_TM2_SPI", 0x2091017c, U1),
+    ("CFG-MSGOUT-UBX_TIM_TM2_UART1", 0x20910179, U1),
+    ("CFG-MSGOUT-UBX_TIM_TM2_UART2", 0x2091017a, U1),
+    ("CFG-MSGOUT-UBX_TIM_TM2_USB", 0x2091017b, U1),
+    ("CFG-MSGOUT-UBX_TIM_TP_I2C", 0x2091017d, U1),
+    ("CFG-MSGOUT-UBX_TIM_TP_SPI", 0x20910181, U1),
+    ("CFG-MSGOUT-UBX_TIM_TP_UART1", 0x2091017e, U1),
+    ("CFG-MSGOUT-UBX_TIM_TP_UART2", 0x2091017f, U1),
+    ("CFG-MSGOUT-UBX_TIM_TP_USB", 0x20910180, U1),
+    ("CFG-MSGOUT-UBX_TIM_VRFY_I2C", 0x20910092, U1),
+    ("CFG-MSGOUT-UBX_TIM_VRFY_SPI", 0x20910096, U1),
+    ("CFG-MSGOUT-UBX_TIM_VRFY_UART1", 0x20910093, U1),
+    ("CFG-MSGOUT-UBX_TIM_VRFY_UART2", 0x20910094, U1),
+    ("CFG-MSGOUT-UBX_TIM_VRFY_USB", 0x20910095, U1),
+];
+
+/// Returns the id of a known key
+pub fn id(name: &str) -> Option<u32> {
+    KEYS.iter()
+        .find(|(key, _, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, id, _)| *id)
+}
+
+/// Returns the name of a known key
+pub fn name(id: u32) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(_, key, _)| *key == id)
+        .map(|(name, _, _)| *name)
+}
+
+/// Size of the value of given key in the messages, in bytes, a bit taking a byte
+pub fn value_size(id: u32) -> Option<usize> {
+    Type::of_id(id).map(Type::size)
+}
+
+/// Configuration key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub id: u32,
+    pub ty: Type,
+    name: Option<&'static str>,
+}
+
+impl Key {
+    /// Key of an id, typed from the table when known, from its size bits otherwise
+    pub fn of_id(id: u32) -> Option<Self> {
+        if let Some((name, _, ty)) = KEYS.iter().find(|(_, known, _)| *known == id) {
+            return Some(Self {
+                id,
+                ty: *ty,
+                name: Some(name),
+            });
+        }
+        Some(Self {
+            id,
+            ty: Type::of_id(id)?,
+            name: None,
+        })
+    }
+
+    /// Name from the table, like CFG-RATE-MEAS
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Value size in the messages, a bit taking a byte
+    pub fn size(&self) -> usize {
+        self.ty.size()
+    }
+
+    /// Like "CFG-RATE-MEAS (0x30210001, U2)" or "0x30210001 (U2)"
+    pub fn describe(&self) -> String {
+        match self.name {
+            Some(name) => format!("{} (0x{:08x}, {:?})", name, self.id, self.ty),
+            None => format!("0x{:08x} ({:?})", self.id, self.ty),
+        }
+    }
+}
+
+/// Keys of the table, in order
+pub fn known_keys() -> Vec<Key> {
+    KEYS.iter()
+        .map(|(name, id, ty)| Key {
+            id: *id,
+            ty: *ty,
+            name: Some(name),
+        })
+        .collect()
+}
+
+/// Key named like CFG-RATE-MEAS or given by its hex id like 0x30210001, with its type like
+/// 0x40030009:I4
+pub fn parse_key(s: &str) -> Result<Key, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        let (hex, ty) = match hex.split_once(':') {
+            Some((hex, ty)) => (hex, Some(ty)),
+            None => (hex, None),
+        };
+        let id = u32::from_str_radix(hex, 16).map_err(|e| format!("\"{}\": {}", s, e))?;
+        let mut key = Key::of_id(id).ok_or_else(|| {
+            format!("\"{}\": invalid key id, its size bits 28 to 30 are not 1 to 5", s)
+        })?;
+        if let Some(ty) = ty {
+            let (_, ty) = TYPES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(ty))
+                .ok_or_else(|| {
+                    format!(
+                        "\"{}\": unknown type \"{}\", expecting one of {}",
+                        s,
+                        ty,
+                        TYPES.map(|(name, _)| name).join(", ")
+                    )
+                })?;
+            if ty.size() != key.size() {
+                return Err(format!(
+                    "\"{}\": {:?} values are {} bytes, the size bits of the key id telling {}",
+                    s,
+                    ty,
+                    ty.size(),
+                    key.size()
+                ));
+            }
+            key.ty = *ty;
+        }
+        return Ok(key);
+    }
+    KEYS.iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(s))
+        .map(|(name, id, ty)| Key {
+            id: *id,
+            ty: *ty,
+            name: Some(name),
+        })
+        .ok_or_else(|| {
+            format!(
+                "unknown key \"{}\", give its hex id like 0x30210001 from the interface \
+description of the receiver",
+                s
+            )
+        })
+}
+
+fn parse_unsigned(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Value of a key, in the bytes of the messages
+pub fn encode(ty: Type, s: &str) -> Result<Vec<u8>, String> {
+    let size = ty.size();
+    let bytes = match ty {
+        L => match s {
+            "1" | "true" => vec![1],
+            "0" | "false" => vec![0],
+            _ => return Err("expecting 0, 1, true or false".to_string()),
+        },
+        I1 | I2 | I4 | I8 => {
+            let value = s.parse::<i64>().map_err(|e| e.to_string())?;
+            let bits = 8 * size as u32;
+            if bits < 64 && !(-(1i64 << (bits - 1))..1i64 << (bits - 1)).contains(&value) {
+                return Err(format!("{} does not fit in {:?}", value, ty));
+            }
+            value.to_le_bytes()[..size].to_vec()
+        },
+        R4 => s
+            .parse::<f32>()
+            .map_err(|e| e.to_string())?
+            .to_le_bytes()
+            .to_vec(),
+        R8 => s
+            .parse::<f64>()
+            .map_err(|e| e.to_string())?
+            .to_le_bytes()
+            .to_vec(),
+        _ => {
+            let value = parse_unsigned(s)?;
+            if size < 8 && value >> (8 * size) != 0 {
+                return Err(format!("{} does not fit in {:?}", value, ty));
+            }
+            value.to_le_bytes()[..size].to_vec()
+        },
+    };
+    Ok(bytes)
+}
+
+/// Value of a key, decoded by type
+pub fn decode(ty: Type, bytes: &[u8]) -> String {
+    let mut raw = [0; 8];
+    raw[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+    let unsigned = u64::from_le_bytes(raw);
+    match ty {
+        L => (unsigned != 0).to_string(),
+        I1 => (unsigned as i8).to_string(),
+        I2 => (unsigned as i16).to_string(),
+        I4 => (unsigned as i32).to_string(),
+        I8 => (unsigned as i64).to_string(),
+        R4 => f32::from_bits(unsigned as u32).to_string(),
+        R8 => f64::from_bits(unsigned).to_string(),
+        X1 | X2 | X4 | X8 => format!("0x{:0width$x}", unsigned, width = 2 * bytes.len()),
+        _ => unsigned.to_string(),
+    }
+}
+
+/// Key and value of an assignment like CFG-RATE-MEAS=100
+pub fn parse_assignment(s: &str) -> Result<(Key, Vec<u8>), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("\"{}\": expecting KEY=VALUE like CFG-RATE-MEAS=100", s))?;
+    let key = parse_key(key.trim())?;
+    let value = encode(key.ty, value.trim())
+        .map_err(|e| format!("\"{}\": invalid {:?} value: {}", s, key.ty, e))?;
+    Ok((key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_of_the_size_bits() {
+        // unknown keys of each size
+        let ty = |s| parse_key(s).unwrap().ty;
+        assert_eq!(ty("0x10ff0001"), L);
+        assert_eq!(ty("0x20ff0001"), U1);
+        assert_eq!(ty("0x30ff0001"), U2);
+        assert_eq!(ty("0x40ff0001"), U4);
+        assert_eq!(ty("0x50ff0001"), U8);
+        assert_eq!(value_size(0x10ff0001), Some(1));
+        assert_eq!(value_size(0x50ff0001), Some(8));
+        assert!(parse_key("0x00ff0001").is_err());
+        assert!(parse_key("0x60ff0001").is_err());
+        // the type suffix must agree with the size bits
+        assert_eq!(ty("0x40030009:I4"), I4);
+        assert_eq!(
+            parse_key("0x40030009:I2").unwrap_err(),
+            "\"0x40030009:I2\": I2 values are 2 bytes, the size bits of the key id telling 4"
+        );
+        // known keys keep the type of the table
+        assert_eq!(ty("0x40030009"), I4);
+        assert_eq!(parse_key("0x40030009").unwrap().name(), Some("CFG-TMODE-LAT"));
+    }
+
+    #[test]
+    fn table_sizes() {
+        for (name, id, ty) in KEYS {
+            assert_eq!(Some(ty.size()), value_size(id), "{}", name);
+        }
+    }
+
+    #[test]
+    fn values() {
+        let (key, value) = parse_assignment("CFG-RATE-MEAS=100").unwrap();
+        assert_eq!((key.id, value.as_slice()), (0x30210001, &[100, 0][..]));
+        let (key, value) = parse_assignment("cfg-navspg-infil_minelev = -5").unwrap();
+        assert_eq!(decode(key.ty, &value), "-5");
+        assert_eq!(decode(X1, &[0x07]), "0x07");
+        assert_eq!(decode(L, &[1]), "true");
+        assert!(parse_assignment("CFG-RATE-MEAS=70000").is_err());
+        assert!(parse_assignment("CFG-ITFM-ENABLE=2").is_err());
+    }
+}
@@ -0,0 +1,542 @@
+//! Legacy configuration, made of the CFG messages used by M8 and older receivers, and its
+//! dumps (`ubx-config dump --legacy`, `ubx-record configure --dump`).
+//!
+//! Each poll item decodes into `key = value` settings, stored under the
+//! section of the item. Raw integer values are kept as they are on the wire,
+//! bitfields are written in hexadecimal, reserved fields are omitted. The
+//! payloads themselves end the dump, in hexadecimal, for restoring them.
+
+use std::io::Write;
+
+use crate::valcfg::Response;
+
+/// CFG class
+const CFG: u8 = 0x06;
+
+/// First line of legacy dumps
+pub const DUMP_HEADER: &str = "# ubx-config legacy configuration dump";
+
+/// Last section of the dumps, the payloads by item name
+const PAYLOADS_SECTION: &str = "payloads";
+
+/// Settings names and values, in the order of the dump
+pub type Settings = Vec<(String, String)>;
+
+/// Periodic messages known to the ublox crate, polled through CFG-MSG
+pub const MESSAGES: &[(&str, u8, u8)] = &[
+    ("NAV-POSLLH", 0x01, 0x02),
+    ("NAV-STATUS", 0x01, 0x03),
+    ("NAV-DOP", 0x01, 0x04),
+    ("NAV-ATT", 0x01, 0x05),
+    ("NAV-SOL", 0x01, 0x06),
+    ("NAV-PVT", 0x01, 0x07),
+    ("NAV-ODO", 0x01, 0x09),
+    ("NAV-VELECEF", 0x01, 0x11),
+    ("NAV-VELNED", 0x01, 0x12),
+    ("NAV-HPPOSECEF", 0x01, 0x13),
+    ("NAV-HPPOSLLH", 0x01, 0x14),
+    ("NAV-TIMEUTC", 0x01, 0x21),
+    ("NAV-CLOCK", 0x01, 0x22),
+    ("NAV-TIMELS", 0x01, 0x26),
+    ("NAV-SAT", 0x01, 0x35),
+    ("NAV-EOE", 0x01, 0x61),
+    ("RXM-SFRBX", 0x02, 0x13),
+    ("RXM-RAWX", 0x02, 0x15),
+    ("RXM-RTCM", 0x02, 0x32),
+    ("MON-HW", 0x0a, 0x09),
+    ("MON-GNSS", 0x0a, 0x28),
+    ("TIM-TP", 0x0d, 0x01),
+    ("TIM-TM2", 0x0d, 0x03),
+    ("TIM-SVIN", 0x0d, 0x04),
+    ("ESF-MEAS", 0x10, 0x02),
+    ("ESF-RAW", 0x10, 0x03),
+    ("ESF-INS", 0x10, 0x15),
+    ("HNR-PVT", 0x28, 0x00),
+    ("HNR-ATT", 0x28, 0x01),
+    ("HNR-INS", 0x28, 0x02),
+];
+
+/// Receiver ports, by CFG-PRT port id
+const PORTS: &[(&str, u8)] = &[
+    ("I2C", 0),
+    ("UART1", 1),
+    ("UART2", 2),
+    ("USB", 3),
+    ("SPI", 4),
+];
+
+/// Wire type of a field
+#[derive(Debug, Clone, Copy)]
+enum Type {
+    U1,
+    U2,
+    U4,
+    I1,
+    I2,
+    I4,
+    X1,
+    X2,
+    X4,
+}
+
+/// Field name, payload offset and type
+struct Field(&'static str, usize, Type);
+
+const CFG_PRT: &[Field] = &[
+    Field("port_id", 0, Type::U1),
+    Field("tx_ready", 2, Type::X2),
+    Field("mode", 4, Type::X4),
+    Field("baud_rate", 8, Type::U4),
+    Field("in_proto_mask", 12, Type::X2),
+    Field("out_proto_mask", 14, Type::X2),
+    Field("flags", 16, Type::X2),
+];
+
+const CFG_RATE: &[Field] = &[
+    Field("meas_rate", 0, Type::U2),
+    Field("nav_rate", 2, Type::U2),
+    Field("time_ref", 4, Type::U2),
+];
+
+const CFG_NAV5: &[Field] = &[
+    Field("mask", 0, Type::X2),
+    Field("dyn_model", 2, Type::U1),
+    Field("fix_mode", 3, Type::U1),
+    Field("fixed_alt", 4, Type::I4),
+    Field("fixed_alt_var", 8, Type::U4),
+    Field("min_elev", 12, Type::I1),
+    Field("dr_limit", 13, Type::U1),
+    Field("p_dop", 14, Type::U2),
+    Field("t_dop", 16, Type::U2),
+    Field("p_acc", 18, Type::U2),
+    Field("t_acc", 20, Type::U2),
+    Field("static_hold_thresh", 22, Type::U1),
+    Field("dgnss_timeout", 23, Type::U1),
+    Field("cno_thresh_num_svs", 24, Type::U1),
+    Field("cno_thresh", 25, Type::U1),
+    Field("static_hold_max_dist", 28, Type::U2),
+    Field("utc_standard", 30, Type::U1),
+];
+
+const CFG_NAVX5: &[Field] = &[
+    Field("version", 0, Type::U2),
+    Field("mask1", 2, Type::X2),
+    Field("mask2", 4, Type::X4),
+    Field("min_svs", 10, Type::U1),
+    Field("max_svs", 11, Type::U1),
+    Field("min_cno", 12, Type::U1),
+    Field("ini_fix_3d", 14, Type::U1),
+    Field("ack_aiding", 17, Type::U1),
+    Field("wkn_rollover", 18, Type::U2),
+    Field("sig_atten_comp_mode", 20, Type::U1),
+    Field("use_ppp", 26, Type::U1),
+    Field("aop_cfg", 27, Type::U1),
+    Field("aop_orb_max_err", 30, Type::U2),
+    Field("use_adr", 39, Type::U1),
+];
+
+const CFG_SBAS: &[Field] = &[
+    Field("mode", 0, Type::X1),
+    Field("usage", 1, Type::X1),
+    Field("max_sbas", 2, Type::U1),
+    Field("scanmode2", 3, Type::X1),
+    Field("scanmode1", 4, Type::X4),
+];
+
+/// Fields beyond the payload of older protocol versions are skipped
+const CFG_NMEA: &[Field] = &[
+    Field("filter", 0, Type::X1),
+    Field("nmea_version", 1, Type::X1),
+    Field("num_sv", 2, Type::U1),
+    Field("flags", 3, Type::X1),
+    Field("gnss_to_filter", 4, Type::X4),
+    Field("sv_numbering", 8, Type::U1),
+    Field("main_talker_id", 9, Type::U1),
+    Field("gsv_talker_id", 10, Type::U1),
+    Field("version", 11, Type::U1),
+    Field("bds_talker_id", 12, Type::X2),
+];
+
+const CFG_TP5: &[Field] = &[
+    Field("tp_idx", 0, Type::U1),
+    Field("version", 1, Type::U1),
+    Field("ant_cable_delay", 4, Type::I2),
+    Field("rf_group_delay", 6, Type::I2),
+    Field("freq_period", 8, Type::U4),
+    Field("freq_period_lock", 12, Type::U4),
+    Field("pulse_len_ratio", 16, Type::U4),
+    Field("pulse_len_ratio_lock", 20, Type::U4),
+    Field("user_config_delay", 24, Type::I4),
+    Field("flags", 28, Type::X4),
+];
+
+const CFG_ANT: &[Field] = &[Field("flags", 0, Type::X2), Field("pins", 2, Type::X2)];
+
+const CFG_ITFM: &[Field] = &[Field("config", 0, Type::X4), Field("config2", 4, Type::X4)];
+
+const CFG_ODO: &[Field] = &[
+    Field("version", 0, Type::U1),
+    Field("flags", 4, Type::X1),
+    Field("odo_cfg", 5, Type::X1),
+    Field("cog_max_speed", 12, Type::U1),
+    Field("cog_max_pos_acc", 13, Type::U1),
+    Field("vel_lp_gain", 16, Type::U1),
+    Field("cog_lp_gain", 17, Type::U1),
+];
+
+const CFG_PM2: &[Field] = &[
+    Field("version", 0, Type::U1),
+    Field("max_startup_state_dur", 2, Type::U1),
+    Field("flags", 4, Type::X4),
+    Field("update_period", 8, Type::U4),
+    Field("search_period", 12, Type::U4),
+    Field("grid_offset", 16, Type::U4),
+    Field("on_time", 20, Type::U2),
+    Field("min_acq_time", 22, Type::U2),
+    Field("extint_inactivity_ms", 44, Type::U4),
+];
+
+const CFG_RXM: &[Field] = &[Field("lp_mode", 1, Type::U1)];
+
+/// How the response of an item decodes
+enum Layout {
+    Fields(&'static [Field]),
+    /// CFG-MSG output rates of the named message
+    Msg(&'static str),
+    /// CFG-GNSS, made of one block per constellation
+    Gnss,
+}
+
+/// One poll of the legacy configuration
+pub struct Item {
+    /// Section the settings are stored under
+    pub section: String,
+    /// Name used in reports
+    pub name: String,
+    pub class: u8,
+    pub id: u8,
+    pub poll_payload: Vec<u8>,
+    layout: Layout,
+}
+
+impl Item {
+    fn new(section: &str, id: u8, poll_payload: Vec<u8>, layout: Layout) -> Self {
+        Self {
+            section: section.to_string(),
+            name: section.to_string(),
+            class: CFG,
+            id,
+            poll_payload,
+            layout,
+        }
+    }
+
+    /// Decodes the poll response into settings
+    pub fn decode(&self, payload: &[u8]) -> Settings {
+        match self.layout {
+            Layout::Fields(fields) => fields
+                .iter()
+                .filter_map(|Field(name, offset, ty)| {
+                    read_field(payload, *offset, *ty).map(|value| (name.to_string(), value))
+                })
+                .collect(),
+            Layout::Msg(name) => {
+                let rates = payload
+                    .iter()
+                    .skip(2)
+                    .map(|rate| rate.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                vec![(name.to_string(), rates)]
+            },
+            Layout::Gnss => {
+                let mut settings = vec![];
+                for (name, offset) in [("num_trk_ch_hw", 1), ("num_trk_ch_use", 2)] {
+                    if let Some(value) = read_field(payload, offset, Type::U1) {
+                        settings.push((name.to_string(), value));
+                    }
+                }
+                for block in payload.get(4..).unwrap_or_default().chunks_exact(8) {
+                    let gnss = gnss_name(block[0]);
+                    settings.push((format!("{}.res_trk_ch", gnss), block[1].to_string()));
+                    settings.push((format!("{}.max_trk_ch", gnss), block[2].to_string()));
+                    settings.push((
+                        format!("{}.flags", gnss),
+                        read_field(block, 4, Type::X4).unwrap_or_default(),
+                    ));
+                }
+                settings
+            },
+        }
+    }
+}
+
+/// Complete set of legacy configuration items
+pub fn items() -> Vec<Item> {
+    let mut items = vec![];
+    for (port, id) in PORTS {
+        let section = format!("CFG-PRT.{}", port);
+        items.push(Item::new(
+            &section,
+            0x00,
+            vec![*id],
+            Layout::Fields(CFG_PRT),
+        ));
+    }
+    for (name, class, id) in MESSAGES {
+        let mut item = Item::new("CFG-MSG", 0x01, vec![*class, *id], Layout::Msg(name));
+        item.name = format!("CFG-MSG.{}", name);
+        items.push(item);
+    }
+    items.push(Item::new(
+        "CFG-RATE",
+        0x08,
+        vec![],
+        Layout::Fields(CFG_RATE),
+    ));
+    items.push(Item::new(
+        "CFG-NAV5",
+        0x24,
+        vec![],
+        Layout::Fields(CFG_NAV5),
+    ));
+    items.push(Item::new(
+        "CFG-NAVX5",
+        0x23,
+        vec![],
+        Layout::Fields(CFG_NAVX5),
+    ));
+    items.push(Item::new("CFG-GNSS", 0x3e, vec![], Layout::Gnss));
+    items.push(Item::new(
+        "CFG-SBAS",
+        0x16,
+        vec![],
+        Layout::Fields(CFG_SBAS),
+    ));
+    items.push(Item::new(
+        "CFG-NMEA",
+        0x17,
+        vec![],
+        Layout::Fields(CFG_NMEA),
+    ));
+    items.push(Item::new(
+        "CFG-TP5.TP1",
+        0x31,
+        vec![0],
+        Layout::Fields(CFG_TP5),
+    ));
+    items.push(Item::new(
+        "CFG-TP5.TP2",
+        0x31,
+        vec![1],
+        Layout::Fields(CFG_TP5),
+    ));
+    items.push(Item::new("CFG-ANT", 0x13, vec![], Layout::Fields(CFG_ANT)));
+    items.push(Item::new(
+        "CFG-ITFM",
+        0x39,
+        vec![],
+        Layout::Fields(CFG_ITFM),
+    ));
+    items.push(Item::new("CFG-ODO", 0x1e, vec![], Layout::Fields(CFG_ODO)));
+    items.push(Item::new("CFG-PM2", 0x3b, vec![], Layout::Fields(CFG_PM2)));
+    items.push(Item::new("CFG-RXM", 0x11, vec![], Layout::Fields(CFG_RXM)));
+    items
+}
+
+/// Writes the dump, one section per message, unavailable items being commented out, then
+/// the payloads
+pub fn write_dump<W: Write>(
+    mut writer: W,
+    header: &[String],
+    items: &[Item],
+    responses: &[Response],
+) -> std::io::Result<()> {
+    writeln!(writer, "{}", DUMP_HEADER)?;
+    for line in header {
+        writeln!(writer, "# {}", line)?;
+    }
+    let mut section = "";
+    for (item, response) in items.iter().zip(responses) {
+        if item.section != section {
+            section = &item.section;
+            writeln!(writer)?;
+            writeln!(writer, "[{}]", section)?;
+            if section == "CFG-MSG" {
+                writeln!(writer, "# rates per port: I2C,UART1,UART2,USB,SPI,reserved")?;
+            }
+        }
+        match response {
+            Response::Payload(payload) => {
+                for (key, value) in item.decode(payload) {
+                    writeln!(writer, "{} = {}", key, value)?;
+                }
+            },
+            _ => writeln!(writer, "# {}: {}", item.name, response)?,
+        }
+    }
+    writeln!(writer)?;
+    writeln!(writer, "[{}]", PAYLOADS_SECTION)?;
+    writeln!(writer, "# as polled, in hexadecimal")?;
+    for (item, response) in items.iter().zip(responses) {
+        if let Response::Payload(payload) = response {
+            writeln!(writer, "{} = {}", item.name, hex(payload))?;
+        }
+    }
+    writer.flush()
+}
+
+/// Contents of a dump
+#[derive(Debug, Default)]
+pub struct Dump {
+    /// Header lines, without their `#`
+    pub header: Vec<String>,
+    /// Sections of the settings
+    pub sections: Vec<String>,
+    /// Full setting names and values, in the format of [settings]
+    pub settings: Settings,
+    /// Payloads by item name
+    pub payloads: Vec<(String, Vec<u8>)>,
+}
+
+/// Full setting names, like "CFG-NAV5.dyn_model", and values of polled items
+pub fn settings(items: &[Item], responses: &[Response]) -> Settings {
+    let mut settings = vec![];
+    for (item, response) in items.iter().zip(responses) {
+        if let Response::Payload(payload) = response {
+            for (key, value) in item.decode(payload) {
+                settings.push((format!("{}.{}", item.section, key), value));
+            }
+        }
+    }
+    settings
+}
+
+/// Parses a dump: its header, the sections it contains, the settings and the payloads
+pub fn parse_dump(content: &str) -> Result<Dump, String> {
+    if content.lines().next() != Some(DUMP_HEADER) {
+        return Err("not a legacy configuration dump".to_string());
+    }
+    let mut dump = Dump::default();
+    let mut payloads = false;
+    for (index, line) in content.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if dump.sections.is_empty() && !payloads {
+            if let Some(header) = line.strip_prefix("# ") {
+                dump.header.push(header.to_string());
+            }
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            payloads = section == PAYLOADS_SECTION;
+            if !payloads {
+                dump.sections.push(section.to_string());
+            }
+            continue;
+        }
+        let invalid = || format!("line {}: invalid setting \"{}\"", index + 1, line);
+        match (line.split_once('='), dump.sections.last()) {
+            (Some((name, value)), _) if payloads => {
+                let payload = unhex(value.trim()).ok_or_else(invalid)?;
+                dump.payloads.push((name.trim().to_string(), payload));
+            },
+            (Some((key, value)), Some(section)) => {
+                dump.settings.push((
+                    format!("{}.{}", section, key.trim()),
+                    value.trim().to_string(),
+                ));
+            },
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(dump)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+        .collect()
+}
+
+fn read_field(payload: &[u8], offset: usize, ty: Type) -> Option<String> {
+    let size = match ty {
+        Type::U1 | Type::I1 | Type::X1 => 1,
+        Type::U2 | Type::I2 | Type::X2 => 2,
+        Type::U4 | Type::I4 | Type::X4 => 4,
+    };
+    let bytes = payload.get(offset..offset + size)?;
+    let mut raw = [0; 4];
+    raw[..size].copy_from_slice(bytes);
+    let value = u32::from_le_bytes(raw);
+    Some(match ty {
+        Type::U1 | Type::U2 | Type::U4 => value.to_string(),
+        Type::I1 => (value as u8 as i8).to_string(),
+        Type::I2 => (value as u16 as i16).to_string(),
+        Type::I4 => (value as i32).to_string(),
+        Type::X1 | Type::X2 | Type::X4 => format!("0x{:0width$x}", value, width = 2 * size),
+    })
+}
+
+fn gnss_name(gnss_id: u8) -> String {
+    match gnss_id {
+        0 => "gps".to_string(),
+        1 => "sbas".to_string(),
+        2 => "galileo".to_string(),
+        3 => "beidou".to_string(),
+        4 => "imes".to_string(),
+        5 => "qzss".to_string(),
+        6 => "glonass".to_string(),
+        id => format!("gnss{}", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_round_trip() {
+        let items = items()
+            .into_iter()
+            .filter(|item| ["CFG-RATE", "CFG-MSG.NAV-PVT", "CFG-ANT"].contains(&item.name.as_str()))
+            .collect::<Vec<_>>();
+        let responses = [
+            Response::Payload(vec![0x01, 0x07, 0, 1, 0, 1, 0, 0]),
+            Response::Payload(vec![0x64, 0x00, 0x01, 0x00, 0x01, 0x00]),
+            Response::Nak,
+        ];
+        let header = ["firmware: SPG 3.01".to_string()];
+        let mut written = vec![];
+        write_dump(&mut written, &header, &items, &responses).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.contains("[CFG-RATE]\nmeas_rate = 100\n"), "{}", written);
+        assert!(written.contains("# CFG-ANT: NAK\n"), "{}", written);
+        let dump = parse_dump(&written).unwrap();
+        assert_eq!(dump.header, header);
+        assert_eq!(dump.sections, ["CFG-MSG", "CFG-RATE", "CFG-ANT"]);
+        assert_eq!(dump.settings, settings(&items, &responses));
+        assert_eq!(
+            dump.payloads,
+            [
+                ("CFG-MSG.NAV-PVT".to_string(), vec![0x01, 0x07, 0, 1, 0, 1, 0, 0]),
+                ("CFG-RATE".to_string(), vec![0x64, 0x00, 0x01, 0x00, 0x01, 0x00]),
+            ]
+        );
+        // odd number of digits
+        let line = written.lines().position(|line| line.starts_with("CFG-MSG.NAV-PVT")).unwrap();
+        assert_eq!(
+            parse_dump(&written.replace("= 0107", "= 017")).unwrap_err(),
+            format!("line {}: invalid setting \"CFG-MSG.NAV-PVT = 017000100010000\"", line + 1)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! Code shared by the U-Blox tools: decoding of the messages the ublox crate
+//! does not decode, or not entirely, and the GNSS time scales of their times,
+//! the configuration of the receivers and its dumps, and the logger
+
+pub mod esf;
+pub mod frame;
+pub mod inf;
+pub mod keys;
+pub mod legacy;
+pub mod logging;
+pub mod satellites;
+pub mod time;
+pub mod timemark;
+#[cfg(feature = "tokio")]
+pub mod transport;
+pub mod ucenter;
+pub mod valcfg;
+
+/// Repeated blocks of `len` bytes following the `header_len` bytes of a payload, their count
+/// being the byte at `count_offset`, none when the payload does not hold exactly that many
+pub fn blocks(
+    payload: &[u8],
+    header_len: usize,
+    count_offset: usize,
+    len: usize,
+) -> Option<std::slice::ChunksExact<'_, u8>> {
+    let count = usize::from(*payload.get(count_offset)?);
+    (payload.len() == header_len + count * len).then(|| payload[header_len..].chunks_exact(len))
+}
@@ -0,0 +1,112 @@
+//! Logger of the tools: the messages of the `log` macros printed to the standard error and
+//! copied to `--log-file`.
+//!
+//! Errors, warnings and info are printed by default, `-q` keeps the errors and warnings
+//! only, `-v` adds debug, `-vv` trace. The log file gets the same messages, each with its
+//! UTC time and level. Only the messages of the tools and of this crate (`ubx_*` targets)
+//! are kept, not those of the dependencies.
+
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+
+use chrono::{SecondsFormat, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+
+struct Logger {
+    file: Mutex<Option<File>>,
+}
+
+static LOGGER: Logger = Logger {
+    file: Mutex::new(None),
+};
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with("ubx_")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        eprintln!("{}", record.args());
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            // the log file failing is no reason to stop the tool
+            let _ = writeln!(file, "{}", line(record));
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Line of the log file
+fn line(record: &Record) -> String {
+    format!(
+        "{} {:<5} {}",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        record.level(),
+        record.args()
+    )
+}
+
+/// Level of the `-q` and `-v` flags, `-v` counted
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Prints the messages up to `level`, copying them to the file at `path` when given
+pub fn init(level: LevelFilter, path: Option<&str>) -> std::io::Result<()> {
+    if let Some(path) = path {
+        let file = File::options().create(true).append(true).open(path)?;
+        *LOGGER.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    }
+    // only fails when already set, the level being updated anyway
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_levels() {
+        assert_eq!(level(true, 2), LevelFilter::Warn);
+        assert_eq!(level(false, 0), LevelFilter::Info);
+        assert_eq!(level(false, 1), LevelFilter::Debug);
+        assert_eq!(level(false, 3), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn dependencies_filtered() {
+        log::set_max_level(LevelFilter::Trace);
+        let metadata = |target| Metadata::builder().level(log::Level::Trace).target(target).build();
+        assert!(LOGGER.enabled(&metadata("ubx_record::session")));
+        assert!(LOGGER.enabled(&metadata("ubx_common::transport")));
+        assert!(!LOGGER.enabled(&metadata("mio::poll")));
+    }
+
+    #[test]
+    fn file_line() {
+        let line = line(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .args(format_args!("NAK of CFG-VALSET"))
+                .build(),
+        );
+        // 2024-05-01T12:00:00.125Z WARN  NAK of CFG-VALSET
+        assert_eq!(line.len(), 24 + 1 + 5 + 1 + 17, "{}", line);
+        assert!(line.ends_with("Z WARN  NAK of CFG-VALSET"), "{}", line);
+    }
+}
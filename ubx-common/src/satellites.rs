@@ -0,0 +1,201 @@
+//! Satellites of the NAV-SAT, NAV-SIG and NAV-SVINFO messages. They are variable-length, a
+//! header counting the repeated blocks following it, one per satellite or signal: the count
+//! is checked against the length of the payload before the blocks are decoded.
+
+/// Satellite information of the firmware older than protocol 15, superseded by NAV-SAT
+pub const NAV_SVINFO: (u8, u8) = (0x01, 0x30);
+/// Satellite information
+pub const NAV_SAT: (u8, u8) = (0x01, 0x35);
+/// Signal information, of protocol 27 and later
+pub const NAV_SIG: (u8, u8) = (0x01, 0x43);
+
+/// Length of the headers of the three messages, ahead of their blocks
+const HEADER_LEN: usize = 8;
+
+/// Block lengths
+const SAT_BLOCK_LEN: usize = 12;
+const SIG_BLOCK_LEN: usize = 16;
+const SVINFO_BLOCK_LEN: usize = 12;
+
+/// NAV-SAT flags: svUsed
+const SAT_SV_USED: u32 = 0x08;
+/// NAV-SIG sigFlags: prUsed, crUsed and doUsed
+const SIG_USED: u16 = 0x08 | 0x10 | 0x20;
+/// NAV-SVINFO flags: svUsed
+const SVINFO_SV_USED: u8 = 0x01;
+
+pub fn message_name(message: (u8, u8)) -> &'static str {
+    match message {
+        NAV_SVINFO => "NAV-SVINFO",
+        NAV_SAT => "NAV-SAT",
+        NAV_SIG => "NAV-SIG",
+        _ => "unknown",
+    }
+}
+
+/// GNSS identifier and satellite number of the NAV-SVINFO numbering, like the NAV-SAT ones
+fn svinfo_id(svid: u8) -> (u8, u8) {
+    match svid {
+        1..=32 => (0, svid),
+        33..=64 => (3, svid - 27),
+        65..=96 => (6, svid - 64),
+        120..=158 => (1, svid),
+        159..=163 => (3, svid - 158),
+        173..=182 => (4, svid - 172),
+        193..=202 => (5, svid - 192),
+        211..=246 => (2, svid - 210),
+        // GLONASS satellite not identified yet
+        255 => (6, 255),
+        _ => (0xff, svid),
+    }
+}
+
+/// Satellite, or signal of a satellite for NAV-SIG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Satellite {
+    /// GNSS identifier of NAV-SAT: 0 GPS, 1 SBAS, 2 Galileo, 3 BeiDou, 4 IMES, 5 QZSS,
+    /// 6 GLONASS, 7 NavIC
+    pub gnss_id: u8,
+    pub sv_id: u8,
+    /// Signal identifier, NAV-SIG only
+    pub sig_id: Option<u8>,
+    /// Carrier to noise ratio (dBHz), 0 when not tracked
+    pub cno: u8,
+    /// Used for navigation
+    pub used: bool,
+    /// Elevation (degrees), none when unknown or not reported (NAV-SIG)
+    pub elev: Option<i8>,
+    /// Azimuth (degrees), none when unknown or not reported (NAV-SIG)
+    pub azim: Option<i16>,
+}
+
+impl Satellite {
+    /// Tracked, with a carrier to noise ratio
+    pub fn tracked(&self) -> bool {
+        self.cno > 0
+    }
+
+    fn position(elev: u8, azim: &[u8]) -> (Option<i8>, Option<i16>) {
+        let elev = elev as i8;
+        // unknown when the elevation is out of range
+        if !(-90..=90).contains(&elev) {
+            return (None, None);
+        }
+        (Some(elev), Some(i16::from_le_bytes([azim[0], azim[1]])))
+    }
+
+    fn from_nav_sat(block: &[u8]) -> Self {
+        let (elev, azim) = Self::position(block[3], &block[4..6]);
+        Self {
+            gnss_id: block[0],
+            sv_id: block[1],
+            sig_id: None,
+            cno: block[2],
+            used: u32::from_le_bytes([block[8], block[9], block[10], block[11]]) & SAT_SV_USED
+                != 0,
+            elev,
+            azim,
+        }
+    }
+
+    fn from_nav_sig(block: &[u8]) -> Self {
+        Self {
+            gnss_id: block[0],
+            sv_id: block[1],
+            sig_id: Some(block[2]),
+            cno: block[6],
+            used: u16::from_le_bytes([block[10], block[11]]) & SIG_USED != 0,
+            elev: None,
+            azim: None,
+        }
+    }
+
+    fn from_nav_svinfo(block: &[u8]) -> Self {
+        let (gnss_id, sv_id) = svinfo_id(block[1]);
+        let (elev, azim) = Self::position(block[5], &block[6..8]);
+        Self {
+            gnss_id,
+            sv_id,
+            sig_id: None,
+            cno: block[4],
+            used: block[2] & SVINFO_SV_USED != 0,
+            elev,
+            azim,
+        }
+    }
+}
+
+/// Decoder of the blocks of a message
+type Decode = fn(&[u8]) -> Satellite;
+
+/// Satellites of a NAV-SAT, NAV-SIG or NAV-SVINFO message
+#[derive(Debug, Clone)]
+pub struct Epoch {
+    pub message: (u8, u8),
+    pub itow: u32,
+    pub satellites: Vec<Satellite>,
+}
+
+impl Epoch {
+    /// Decodes the payload of one of the three messages, failing on another message or a
+    /// malformed payload
+    pub fn parse(message: (u8, u8), payload: &[u8]) -> Result<Self, String> {
+        let (count_offset, len, decode): (usize, usize, Decode) = match message {
+            NAV_SAT => (5, SAT_BLOCK_LEN, Satellite::from_nav_sat),
+            NAV_SIG => (5, SIG_BLOCK_LEN, Satellite::from_nav_sig),
+            NAV_SVINFO => (4, SVINFO_BLOCK_LEN, Satellite::from_nav_svinfo),
+            (class, id) => {
+                return Err(format!(
+                    "0x{:02x},0x{:02x} is not a satellite message",
+                    class, id
+                ))
+            },
+        };
+        let blocks = crate::blocks(payload, HEADER_LEN, count_offset, len).ok_or_else(|| {
+            format!(
+                "Malformed {} of {} bytes, not holding the {} blocks of {} bytes of its header",
+                message_name(message),
+                payload.len(),
+                payload.get(count_offset).copied().unwrap_or_default(),
+                len
+            )
+        })?;
+        Ok(Self {
+            message,
+            itow: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            satellites: blocks.map(decode).collect(),
+        })
+    }
+}
+
+/// Epochs of the most detailed of the messages given, in order of preference: a message
+/// is ignored once one preferred to it was received, like the NAV-SVINFO still output
+/// besides NAV-SAT by some firmware
+pub struct Source {
+    messages: Vec<(u8, u8)>,
+    /// Index of the preferred message received
+    best: Option<usize>,
+}
+
+impl Source {
+    pub fn new(messages: &[(u8, u8)]) -> Self {
+        Self {
+            messages: messages.to_vec(),
+            best: None,
+        }
+    }
+
+    /// Epoch of a message, none for other messages and the ones less preferred than one
+    /// already received
+    pub fn epoch(&mut self, message: (u8, u8), payload: &[u8]) -> Result<Option<Epoch>, String> {
+        let Some(rank) = self.messages.iter().position(|known| *known == message) else {
+            return Ok(None);
+        };
+        if self.best.is_some_and(|best| best < rank) {
+            return Ok(None);
+        }
+        let epoch = Epoch::parse(message, payload)?;
+        self.best = Some(rank);
+        Ok(Some(epoch))
+    }
+}
@@ -0,0 +1,195 @@
+//! GNSS time scales: week and time of week pairs into absolute GPS and UTC times.
+//!
+//! GPS time is continuous and runs ahead of UTC by the leap seconds inserted
+//! since the GPS epoch (1980-01-06T00:00:00Z).
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// Leap seconds introduced since the GPS epoch: (first GPS time they apply to,
+/// as seconds since the GPS epoch, GPS - UTC offset)
+const LEAP_SECONDS: &[(i64, i64)] = &[
+    (46_828_800, 1),
+    (78_364_801, 2),
+    (109_900_802, 3),
+    (173_059_203, 4),
+    (252_028_804, 5),
+    (315_187_205, 6),
+    (346_723_206, 7),
+    (393_984_007, 8),
+    (425_520_008, 9),
+    (457_056_009, 10),
+    (504_489_610, 11),
+    (551_750_411, 12),
+    (599_184_012, 13),
+    (820_108_813, 14),
+    (914_803_214, 15),
+    (1_025_136_015, 16),
+    (1_119_744_016, 17),
+    (1_167_264_017, 18),
+];
+
+const SECONDS_PER_WEEK: i64 = 7 * 86_400;
+
+/// Time scale of a week and time of week pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeScale {
+    Gps,
+    Utc,
+}
+
+/// Instant given in a GNSS time scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekTime {
+    pub scale: TimeScale,
+    pub week: u16,
+    /// Milliseconds of week
+    pub tow_ms: u32,
+    /// Nanoseconds within the millisecond
+    pub sub_ms_ns: u32,
+}
+
+impl WeekTime {
+    /// UTC week and time of week of a UTC time
+    pub fn from_utc(time: DateTime<Utc>) -> Self {
+        let ns = (time - gps_epoch()).num_nanoseconds().unwrap_or(0);
+        let ms = ns.div_euclid(1_000_000);
+        Self {
+            scale: TimeScale::Utc,
+            week: (ms / (SECONDS_PER_WEEK * 1000)) as u16,
+            tow_ms: (ms % (SECONDS_PER_WEEK * 1000)) as u32,
+            sub_ms_ns: ns.rem_euclid(1_000_000) as u32,
+        }
+    }
+
+    /// Time of week in seconds, with nanosecond digits
+    pub fn tow(&self) -> String {
+        format!(
+            "{}.{:03}{:06}",
+            self.tow_ms / 1000,
+            self.tow_ms % 1000,
+            self.sub_ms_ns
+        )
+    }
+
+    /// Nanoseconds since the GPS epoch, counted in the pair's own time scale
+    fn nanoseconds(&self) -> i64 {
+        (i64::from(self.week) * SECONDS_PER_WEEK * 1000 + i64::from(self.tow_ms)) * 1_000_000
+            + i64::from(self.sub_ms_ns)
+    }
+
+    /// GPS time, as a date in the GPS time scale (no leap seconds)
+    pub fn gps(&self) -> DateTime<Utc> {
+        let ns = match self.scale {
+            TimeScale::Gps => self.nanoseconds(),
+            TimeScale::Utc => {
+                let utc_seconds = self.nanoseconds().div_euclid(1_000_000_000);
+                self.nanoseconds() + utc_to_gps_offset(utc_seconds) * 1_000_000_000
+            },
+        };
+        gps_epoch() + Duration::nanoseconds(ns)
+    }
+
+    /// UTC time
+    pub fn utc(&self) -> DateTime<Utc> {
+        let ns = match self.scale {
+            TimeScale::Utc => self.nanoseconds(),
+            TimeScale::Gps => {
+                let gps_seconds = self.nanoseconds().div_euclid(1_000_000_000);
+                self.nanoseconds() - leap_seconds(gps_seconds) * 1_000_000_000
+            },
+        };
+        gps_epoch() + Duration::nanoseconds(ns)
+    }
+}
+
+pub fn gps_epoch() -> DateTime<Utc> {
+    NaiveDate::from_ymd_opt(1980, 1, 6)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap()
+        .and_utc()
+}
+
+/// GPS - UTC offset at a GPS time, in seconds since the GPS epoch
+pub fn leap_seconds(gps_seconds: i64) -> i64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(start, _)| gps_seconds >= *start)
+        .map(|(_, offset)| *offset)
+        .unwrap_or(0)
+}
+
+/// GPS - UTC offset at a UTC time, counted like GPS time from the GPS epoch
+pub fn utc_to_gps_offset(utc_seconds: i64) -> i64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(start, offset)| utc_seconds >= *start - *offset)
+        .map(|(_, offset)| *offset)
+        .unwrap_or(0)
+}
+
+/// Formats times like "2024-05-01T12:00:00.000000000Z"
+pub fn format(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.9fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gps_time(week: u16, tow_ms: u32) -> WeekTime {
+        WeekTime {
+            scale: TimeScale::Gps,
+            week,
+            tow_ms,
+            sub_ms_ns: 0,
+        }
+    }
+
+    #[test]
+    fn week_1024_rollover() {
+        // 1999-08-22 GPS, 13 leap seconds
+        let last = gps_time(1023, 604_799_999);
+        let first = gps_time(1024, 0);
+        assert_eq!(format(last.utc()), "1999-08-21T23:59:46.999000000Z");
+        assert_eq!(format(first.utc()), "1999-08-21T23:59:47.000000000Z");
+        assert_eq!(format(first.gps()), "1999-08-22T00:00:00.000000000Z");
+        assert_eq!((first.utc() - last.utc()).num_milliseconds(), 1);
+    }
+
+    #[test]
+    fn week_2048_rollover() {
+        // 2019-04-07 GPS, 18 leap seconds
+        let last = gps_time(2047, 604_799_999);
+        let first = gps_time(2048, 0);
+        assert_eq!(format(last.utc()), "2019-04-06T23:59:41.999000000Z");
+        assert_eq!(format(first.utc()), "2019-04-06T23:59:42.000000000Z");
+        assert_eq!((first.utc() - last.utc()).num_milliseconds(), 1);
+        // the last seconds of UTC week 2047 are in GPS week 2048
+        let utc = WeekTime {
+            scale: TimeScale::Utc,
+            ..gps_time(2047, 604_790_000)
+        };
+        assert_eq!(format(utc.gps()), "2019-04-07T00:00:08.000000000Z");
+        assert_eq!(format(utc.utc()), "2019-04-06T23:59:50.000000000Z");
+    }
+
+    #[test]
+    fn utc_weeks() {
+        let rollover = gps_epoch() + Duration::weeks(2048);
+        assert_eq!(
+            WeekTime::from_utc(rollover - Duration::nanoseconds(1)),
+            WeekTime {
+                scale: TimeScale::Utc,
+                week: 2047,
+                tow_ms: 604_799_999,
+                sub_ms_ns: 999_999,
+            }
+        );
+        let first = WeekTime::from_utc(rollover);
+        assert_eq!((first.week, first.tow_ms, first.sub_ms_ns), (2048, 0, 0));
+        assert_eq!(first.utc(), rollover);
+        assert_eq!(first.tow(), "0.000000000");
+    }
+}
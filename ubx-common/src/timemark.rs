@@ -0,0 +1,27 @@
+//! Flags of the TIM-TM2 time marks, decoded here as the ublox crate does not shift their
+//! time base field
+
+use crate::time::TimeScale;
+
+/// Payload offset of TIM-TM2 `flags`
+pub const FLAGS_OFFSET: usize = 1;
+
+/// TIM-TM2 `flags` bits
+pub const MODE_RUNNING: u8 = 0x01;
+pub const RUN_STOPPED: u8 = 0x02;
+pub const NEW_FALLING_EDGE: u8 = 0x04;
+const TIME_BASE_MASK: u8 = 0x18;
+const TIME_BASE_SHIFT: u8 = 3;
+pub const UTC_AVAILABLE: u8 = 0x20;
+pub const TIME_VALID: u8 = 0x40;
+pub const NEW_RISING_EDGE: u8 = 0x80;
+
+/// Name and time scale of the time base of the flags, the receiver and GNSS times being
+/// counted as GPS time
+pub fn time_base(flags: u8) -> (&'static str, TimeScale) {
+    match (flags & TIME_BASE_MASK) >> TIME_BASE_SHIFT {
+        0 => ("receiver", TimeScale::Gps),
+        1 => ("gnss", TimeScale::Gps),
+        _ => ("utc", TimeScale::Utc),
+    }
+}
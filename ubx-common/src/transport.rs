@@ -0,0 +1,198 @@
+//! Asynchronous transport to the receiver, on tokio (`tokio` feature): the device reads and
+//! writes any `AsyncRead + AsyncWrite` stream, a serial port opened with tokio-serial, a TCP
+//! stream or an in-memory duplex, so that a tool can run the receiver, its network outputs
+//! and its timers as tasks of one runtime. The blocking devices of the tools remain for
+//! simple consumers
+
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::frame::{checksum, frame, SYNC};
+
+/// UBX-ACK class, and the ids of ACK-ACK and ACK-NAK
+const ACK_CLASS: u8 = 0x05;
+const ACK_ACK_ID: u8 = 0x01;
+const ACK_NAK_ID: u8 = 0x00;
+
+/// Longest payload accepted, longer lengths being corrupted headers
+const MAX_PAYLOAD_LEN: usize = 8192;
+
+/// Valid UBX frame received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Receiver behind an asynchronous stream. The bytes that are not part of a valid UBX
+/// frame, NMEA sentences among them, are skipped and counted
+pub struct AsyncDevice<T> {
+    stream: T,
+    buffer: Vec<u8>,
+    discarded: u64,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncDevice<T> {
+    pub fn new(stream: T) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            discarded: 0,
+        }
+    }
+
+    /// Bytes skipped so far, outside valid UBX frames
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Stream of the device, like the port for its settings
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+
+    /// Bytes received, those left by the frame scans first: the raw stream of a recording.
+    /// Cancel safe, like the reads of tokio, 0 being the end of the stream
+    pub async fn read(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() {
+            return self.stream.read(output).await;
+        }
+        let size = self.buffer.len().min(output.len());
+        output[..size].copy_from_slice(&self.buffer[..size]);
+        self.buffer.drain(..size);
+        Ok(size)
+    }
+
+    pub async fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(data).await?;
+        self.stream.flush().await
+    }
+
+    /// Next valid frame, None at the end of the stream
+    pub async fn next_frame(&mut self) -> std::io::Result<Option<Frame>> {
+        let mut chunk = [0; 1024];
+        loop {
+            if let Some(frame) = self.scan() {
+                return Ok(Some(frame));
+            }
+            let size = self.stream.read(&mut chunk).await?;
+            if size == 0 {
+                self.discarded += self.buffer.len() as u64;
+                self.buffer.clear();
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&chunk[..size]);
+        }
+    }
+
+    /// Takes the first valid frame of the buffer, dropping the bytes preceding it
+    fn scan(&mut self) -> Option<Frame> {
+        loop {
+            let start = self
+                .buffer
+                .windows(2)
+                .position(|pair| pair == SYNC)
+                .unwrap_or(self.buffer.len().saturating_sub(1));
+            self.discard(start);
+            if self.buffer.len() < 6 {
+                return None;
+            }
+            let len = usize::from(u16::from_le_bytes([self.buffer[4], self.buffer[5]]));
+            if len > MAX_PAYLOAD_LEN {
+                self.discard(1);
+                continue;
+            }
+            if self.buffer.len() < len + 8 {
+                return None;
+            }
+            let (ck_a, ck_b) = checksum(&self.buffer[2..len + 6]);
+            if [ck_a, ck_b] != self.buffer[len + 6..len + 8] {
+                self.discard(1);
+                continue;
+            }
+            let frame = Frame {
+                class: self.buffer[2],
+                id: self.buffer[3],
+                payload: self.buffer[6..len + 6].to_vec(),
+            };
+            self.buffer.drain(..len + 8);
+            return Some(frame);
+        }
+    }
+
+    fn discard(&mut self, count: usize) {
+        self.buffer.drain(..count);
+        self.discarded += count as u64;
+    }
+
+    /// Waits up to `timeout` for the acknowledgment of a message, the other frames received
+    /// meanwhile being dropped. False when the receiver rejected it (UBX-ACK-NAK)
+    pub async fn wait_for_ack_of(
+        &mut self,
+        class: u8,
+        id: u8,
+        timeout: Duration,
+    ) -> std::io::Result<bool> {
+        let wait = async {
+            while let Some(frame) = self.next_frame().await? {
+                if frame.class == ACK_CLASS && frame.payload == [class, id] {
+                    match frame.id {
+                        ACK_ACK_ID => return Ok(true),
+                        ACK_NAK_ID => return Ok(false),
+                        _ => {},
+                    }
+                }
+            }
+            Err(std::io::ErrorKind::UnexpectedEof.into())
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into()))
+    }
+
+    /// Polls a message, returning the payload of the response, starting like the `request`
+    /// payload. An UBX-ACK-NAK of the class and id is an error
+    pub async fn poll_payload(
+        &mut self,
+        class: u8,
+        id: u8,
+        request: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Vec<u8>> {
+        self.write_all(&frame(class, id, request)).await?;
+        let wait = async {
+            while let Some(frame) = self.next_frame().await? {
+                if frame.class == class
+                    && frame.id == id
+                    && frame.payload.len() > request.len()
+                    && frame.payload.starts_with(request)
+                {
+                    return Ok(frame.payload);
+                }
+                if (frame.class, frame.id) == (ACK_CLASS, ACK_NAK_ID)
+                    && frame.payload == [class, id]
+                {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "rejected (UBX-ACK-NAK)",
+                    ));
+                }
+            }
+            Err(std::io::ErrorKind::UnexpectedEof.into())
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into()))
+    }
+}
+
+/// Opens the serial port of a receiver, at `baud`, 8N1 without flow control
+pub fn open_serial(
+    path: &str,
+    baud: u32,
+) -> std::io::Result<AsyncDevice<tokio_serial::SerialStream>> {
+    let stream = tokio_serial::SerialStream::open(&tokio_serial::new(path, baud))?;
+    Ok(AsyncDevice::new(stream))
+}
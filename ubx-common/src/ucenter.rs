@@ -0,0 +1,272 @@
+//! u-center configuration files, as saved from the generation 9 configuration view.
+//!
+//! Settings come one per line, made of the layer, the key and the value,
+//! like `RAM CFG-RATE-MEAS 0x3e8`. Layers are given by name (RAM, BBR, Flash)
+//! or by VALGET layer number in hexadecimal (0x00, 0x01, 0x02) and may be omitted.
+//! Keys are given by name, possibly followed by their id, or by id alone
+//! (`0x30210001`). Everything following `#` is a comment. Settings of `[del]`
+//! sections are skipped, as only value updates are supported.
+//!
+//! The VALGET dumps (`ubx-config dump`, `ubx-record configure --dump`) are written in
+//! this format, one layer per file.
+
+use std::io::Write;
+
+use crate::keys;
+
+/// VALSET layer bits
+pub const LAYER_RAM: u8 = 0x01;
+pub const LAYER_BBR: u8 = 0x02;
+pub const LAYER_FLASH: u8 = 0x04;
+
+/// One setting of the file
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Line number, for reports
+    pub line: usize,
+    /// VALSET layer mask, if given by the file
+    pub layers: Option<u8>,
+    pub key: u32,
+    /// Key name, as given by the file or found in the key database
+    pub name: Option<String>,
+    /// Value, as written in the file
+    pub text: String,
+    /// Value, little endian encoded on the size of the key
+    pub value: Vec<u8>,
+}
+
+impl Entry {
+    /// Key name, or id when unknown
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("0x{:08x}", self.key),
+        }
+    }
+}
+
+/// Parses a u-center configuration file, returns its settings
+/// and the warnings raised on the way
+pub fn parse(content: &str) -> Result<(Vec<Entry>, Vec<String>), String> {
+    let mut entries = vec![];
+    let mut warnings = vec![];
+    let mut deleting = false;
+
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            deleting = line.eq_ignore_ascii_case("[del]");
+            continue;
+        }
+        if deleting {
+            warnings.push(format!(
+                "line {}: key deletion not supported, skipped",
+                number
+            ));
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace().collect::<Vec<_>>();
+        let layers = match tokens.first().and_then(|token| parse_layer(token)) {
+            Some(Some(layers)) => {
+                tokens.remove(0);
+                Some(layers)
+            },
+            Some(None) => {
+                warnings.push(format!(
+                    "line {}: default layer is read only, skipped",
+                    number
+                ));
+                continue;
+            },
+            None => None,
+        };
+
+        let (name, raw_id, text) = match tokens[..] {
+            [key, value] if parse_key_id(key).is_some() => (None, parse_key_id(key), value),
+            [key, value] => (Some(key), None, value),
+            [key, id, value] => match parse_key_id(id) {
+                Some(id) => (Some(key), Some(id), value),
+                None => return Err(format!("line {}: invalid key id \"{}\"", number, id)),
+            },
+            _ => return Err(format!("line {}: invalid setting \"{}\"", number, line)),
+        };
+
+        let name = name
+            .map(|name| name.to_string())
+            .or_else(|| raw_id.and_then(keys::name).map(|name| name.to_string()));
+        let key = match (name.as_deref().and_then(keys::id), raw_id) {
+            (Some(known), Some(raw)) if known != raw => {
+                warnings.push(format!(
+                    "line {}: key database has 0x{:08x} for {}, applying 0x{:08x} from file",
+                    number,
+                    known,
+                    name.as_deref().unwrap_or_default(),
+                    raw
+                ));
+                raw
+            },
+            (Some(known), _) => known,
+            (None, Some(raw)) => {
+                warnings.push(format!(
+                    "line {}: unknown key {}, applied as raw key id 0x{:08x}",
+                    number,
+                    name.as_deref().unwrap_or("id"),
+                    raw
+                ));
+                raw
+            },
+            (None, None) => {
+                warnings.push(format!(
+                    "line {}: unknown key {} without key id, skipped",
+                    number,
+                    name.as_deref().unwrap_or_default()
+                ));
+                continue;
+            },
+        };
+
+        let Some(size) = keys::value_size(key) else {
+            warnings.push(format!(
+                "line {}: invalid size in key id 0x{:08x}, skipped",
+                number, key
+            ));
+            continue;
+        };
+        let value = parse_value(text, size, key).map_err(|e| format!("line {}: {}", number, e))?;
+
+        entries.push(Entry {
+            line: number,
+            layers,
+            key,
+            name,
+            text: text.to_string(),
+            value,
+        });
+    }
+    Ok((entries, warnings))
+}
+
+/// Returns the VALSET layer mask of a layer token, None for the read only
+/// default layer, or nothing when the token is not a layer
+fn parse_layer(token: &str) -> Option<Option<u8>> {
+    match token.to_ascii_lowercase().as_str() {
+        "ram" | "0x00" | "0x0" => Some(Some(LAYER_RAM)),
+        "bbr" | "0x01" | "0x1" => Some(Some(LAYER_BBR)),
+        "flash" | "0x02" | "0x2" => Some(Some(LAYER_FLASH)),
+        "default" | "0x07" | "0x7" => Some(None),
+        _ => None,
+    }
+}
+
+/// Parses key ids, written as 8 hexadecimal digits
+fn parse_key_id(token: &str) -> Option<u32> {
+    let digits = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))?;
+    if digits.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Parses hexadecimal or decimal values, checking they fit the key size
+fn parse_value(text: &str, size: usize, key: u32) -> Result<Vec<u8>, String> {
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u64::from_str_radix(digits, 16).ok(),
+        None => text
+            .parse::<u64>()
+            .ok()
+            .or_else(|| text.parse::<i64>().ok().map(|v| v as u64)),
+    }
+    .ok_or_else(|| format!("invalid value \"{}\"", text))?;
+
+    let bits = if (key >> 28) & 0x07 == 1 { 1 } else { size * 8 };
+    let fits = bits == 64
+        || value < 1 << bits
+        || (text.starts_with('-') && (value as i64) >= -(1 << (bits - 1)));
+    if !fits {
+        return Err(format!(
+            "value \"{}\" does not fit key 0x{:08x} ({} bits)",
+            text, key, bits
+        ));
+    }
+    Ok(value.to_le_bytes()[..size].to_vec())
+}
+
+/// Formats a little endian value, in hexadecimal like the u-center files or in decimal
+pub fn format(value: &[u8], hex: bool) -> String {
+    let mut raw = [0; 8];
+    raw[..value.len().min(8)].copy_from_slice(&value[..value.len().min(8)]);
+    let value = u64::from_le_bytes(raw);
+    if hex {
+        format!("0x{:x}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the `values` (key id and value) of the `layer`, named like `RAM`, with the
+/// `header` lines as comments. Keys are written by name, by id when unknown.
+pub fn write<W: Write>(
+    mut writer: W,
+    header: &[String],
+    layer: &str,
+    values: &[(u32, Vec<u8>)],
+) -> std::io::Result<()> {
+    for line in header {
+        writeln!(writer, "# {}", line)?;
+    }
+    for (key, value) in values {
+        match keys::name(*key) {
+            Some(name) => writeln!(writer, "{} {} {}", layer, name, format(value, true))?,
+            None => writeln!(writer, "{} 0x{:08x} {}", layer, key, format(value, true))?,
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format(&[0xe8, 0x03], true), "0x3e8");
+        assert_eq!(format(&[0xe8, 0x03], false), "1000");
+        assert_eq!(format(&[0x00, 0xc2, 0x01, 0x00], true), "0x1c200");
+        assert_eq!(format(&[1], false), "1");
+    }
+
+    #[test]
+    fn written_file_parses() {
+        let values = [
+            (0x30210001, vec![0xe8, 0x03]),
+            (0x10310025, vec![0]),
+            (0x40ff0001, 7_u32.to_le_bytes().to_vec()),
+        ];
+        let mut written = vec![];
+        write(&mut written, &["firmware: HPG 1.32".to_string()], "RAM", &values).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(
+            written,
+            "# firmware: HPG 1.32\nRAM CFG-RATE-MEAS 0x3e8\nRAM CFG-SIGNAL-GLO_ENA 0x0\n\
+RAM 0x40ff0001 0x7\n"
+        );
+        let (entries, warnings) = parse(&written).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.key, entry.value.clone()))
+                .collect::<Vec<_>>(),
+            values
+        );
+        assert!(entries.iter().all(|entry| entry.layers == Some(LAYER_RAM)));
+        // the unknown key applied by id
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,371 @@
+//! Configuration of the generation 9 receivers and later through UBX-CFG-VALSET and
+//! UBX-CFG-VALGET, whatever the link to the receiver.
+//!
+//! Messages carry 64 keys at most: more values are set in a transaction of several
+//! VALSET messages, applied together by the last one, and more keys polled with several
+//! VALGET requests.
+
+use std::time::Duration;
+
+use crate::keys;
+
+/// UBX-CFG-VALSET class and id
+pub const CFG_VALSET: (u8, u8) = (0x06, 0x8a);
+
+/// UBX-CFG-VALGET class and id
+pub const CFG_VALGET: (u8, u8) = (0x06, 0x8b);
+
+/// Most keys of a CFG-VALSET or CFG-VALGET message
+pub const MAX_KEYS: usize = 64;
+
+/// Length of the VALSET and VALGET headers: version, layers and transaction or position
+const HEADER_LEN: usize = 4;
+
+/// VALSET transaction actions
+const TRANSACTIONLESS: u8 = 0;
+const TRANSACTION_BEGIN: u8 = 1;
+const TRANSACTION_CONTINUE: u8 = 2;
+const TRANSACTION_END: u8 = 3;
+
+/// Acknowledgment of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ack {
+    Acked,
+    Nak,
+    Timeout,
+}
+
+impl std::fmt::Display for Ack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ack::Acked => write!(f, "ok"),
+            Ack::Nak => write!(f, "NAK"),
+            Ack::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// Outcome of a poll request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// Payload of the response frame
+    Payload(Vec<u8>),
+    /// Receiver rejected the request
+    Nak,
+    /// No answer within the timeout
+    Timeout,
+}
+
+impl std::fmt::Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Response::Payload(_) => write!(f, "ok"),
+            Response::Nak => write!(f, "NAK"),
+            Response::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// Outcome of one value set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Applied,
+    Rejected(Ack),
+    /// Another message of the transaction failed
+    Aborted,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Applied => write!(f, "ok"),
+            Status::Rejected(ack) => write!(f, "failed ({})", ack),
+            Status::Aborted => write!(f, "not applied (transaction aborted)"),
+        }
+    }
+}
+
+/// Link to the receiver
+pub trait Link {
+    /// Sends a command, then waits up to `timeout` for its acknowledgment
+    fn command(&mut self, class: u8, id: u8, payload: &[u8], timeout: Duration)
+        -> std::io::Result<Ack>;
+
+    /// Sends a poll request, then waits up to `timeout` for the response of same class and
+    /// id whose payload starts with `prefix`, or its UBX-ACK-NAK
+    fn poll(
+        &mut self,
+        class: u8,
+        id: u8,
+        request: &[u8],
+        prefix: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Response>;
+}
+
+/// VALSET messages setting the `values` in the `layers`: a single message, or a transaction
+/// of 64 values per message
+fn valset_payloads(values: &[(u32, Vec<u8>)], layers: u8) -> Vec<Vec<u8>> {
+    let chunks = values.chunks(MAX_KEYS).collect::<Vec<_>>();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            // version 1 for the transactions
+            let (version, transaction) = match (chunks.len(), index) {
+                (1, _) => (0, TRANSACTIONLESS),
+                (_, 0) => (1, TRANSACTION_BEGIN),
+                (count, index) if index + 1 == count => (1, TRANSACTION_END),
+                _ => (1, TRANSACTION_CONTINUE),
+            };
+            let mut payload = vec![version, layers, transaction, 0];
+            for (key, value) in *chunk {
+                payload.extend_from_slice(&key.to_le_bytes());
+                payload.extend_from_slice(value);
+            }
+            payload
+        })
+        .collect()
+}
+
+/// Sets the `values` (key id and value) in the `layers`, as a transaction when more than 64.
+/// A message of the transaction failing, the transaction is aborted, by an empty
+/// transactionless VALSET, rather than left open on the receiver. Returns the status of
+/// each value.
+pub fn valset<L: Link>(
+    link: &mut L,
+    values: &[(u32, Vec<u8>)],
+    layers: u8,
+    timeout: Duration,
+) -> std::io::Result<Vec<Status>> {
+    let payloads = valset_payloads(values, layers);
+    let mut statuses = Vec::with_capacity(values.len());
+    for (chunk, payload) in values.chunks(MAX_KEYS).zip(&payloads) {
+        let ack = link.command(CFG_VALSET.0, CFG_VALSET.1, payload, timeout)?;
+        if ack == Ack::Acked {
+            statuses.extend(chunk.iter().map(|_| Status::Applied));
+            continue;
+        }
+        if payloads.len() > 1 {
+            // discards what the transaction set so far
+            let abort = [0, layers, TRANSACTIONLESS, 0];
+            link.command(CFG_VALSET.0, CFG_VALSET.1, &abort, timeout)?;
+            for status in statuses.iter_mut() {
+                *status = Status::Aborted;
+            }
+        }
+        statuses.extend(chunk.iter().map(|_| Status::Rejected(ack)));
+        statuses.resize(values.len(), Status::Aborted);
+        break;
+    }
+    Ok(statuses)
+}
+
+/// Polls the values of the `keys` in the `layer`, 64 keys per request, returning the value
+/// of each key, or the response of the receiver when it could not be read. The receiver
+/// rejecting a whole request for a single unknown key, a rejected request is polled again
+/// key by key.
+pub fn valget<L: Link>(
+    link: &mut L,
+    keys: &[u32],
+    layer: u8,
+    timeout: Duration,
+) -> std::io::Result<Vec<Result<Vec<u8>, Response>>> {
+    let mut values = Vec::with_capacity(keys.len());
+    for chunk in keys.chunks(MAX_KEYS) {
+        match request(link, chunk, layer, timeout)? {
+            Response::Payload(payload) => {
+                values.extend(chunk.iter().map(|key| value(&payload, *key).ok_or(Response::Nak)))
+            },
+            Response::Nak if chunk.len() > 1 => {
+                for key in chunk {
+                    values.push(match request(link, &[*key], layer, timeout)? {
+                        Response::Payload(payload) => value(&payload, *key).ok_or(Response::Nak),
+                        response => Err(response),
+                    });
+                }
+            },
+            response => values.extend(chunk.iter().map(|_| Err(response.clone()))),
+        }
+    }
+    Ok(values)
+}
+
+/// Sends one VALGET request, answered by version 1 and the layer
+fn request<L: Link>(
+    link: &mut L,
+    keys: &[u32],
+    layer: u8,
+    timeout: Duration,
+) -> std::io::Result<Response> {
+    let mut payload = vec![0, layer, 0, 0];
+    for key in keys {
+        payload.extend_from_slice(&key.to_le_bytes());
+    }
+    link.poll(CFG_VALGET.0, CFG_VALGET.1, &payload, &[0x01, layer], timeout)
+}
+
+/// Value of `key` in a VALGET response, walking the key ids by the size of their values
+pub fn value(payload: &[u8], key: u32) -> Option<Vec<u8>> {
+    let mut data = payload.get(HEADER_LEN..)?;
+    while data.len() >= 4 {
+        let id = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let size = keys::value_size(id)?;
+        let value = data.get(4..4 + size)?;
+        if id == key {
+            return Some(value.to_vec());
+        }
+        data = &data[4 + size..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Receiver acknowledging the commands in turn, answering the polls with the value of
+    /// each key, its id
+    struct Receiver {
+        acks: Vec<Ack>,
+        commands: Vec<Vec<u8>>,
+        polls: Vec<Vec<u8>>,
+        /// Keys rejected, all of a request being rejected for one
+        unknown: Vec<u32>,
+    }
+
+    impl Receiver {
+        fn new(acks: &[Ack]) -> Self {
+            Self {
+                acks: acks.to_vec(),
+                commands: vec![],
+                polls: vec![],
+                unknown: vec![],
+            }
+        }
+    }
+
+    impl Link for Receiver {
+        fn command(
+            &mut self,
+            class: u8,
+            id: u8,
+            payload: &[u8],
+            _timeout: Duration,
+        ) -> std::io::Result<Ack> {
+            assert_eq!((class, id), CFG_VALSET);
+            self.commands.push(payload.to_vec());
+            Ok(if self.acks.is_empty() {
+                Ack::Acked
+            } else {
+                self.acks.remove(0)
+            })
+        }
+
+        fn poll(
+            &mut self,
+            class: u8,
+            id: u8,
+            request: &[u8],
+            prefix: &[u8],
+            _timeout: Duration,
+        ) -> std::io::Result<Response> {
+            assert_eq!((class, id), CFG_VALGET);
+            self.polls.push(request.to_vec());
+            let ids = request[HEADER_LEN..]
+                .chunks(4)
+                .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+                .collect::<Vec<_>>();
+            if ids.iter().any(|id| self.unknown.contains(id)) {
+                return Ok(Response::Nak);
+            }
+            let mut payload = [prefix, &[0, 0]].concat();
+            for id in ids {
+                payload.extend_from_slice(&id.to_le_bytes());
+                payload.extend_from_slice(&id.to_le_bytes()[..keys::value_size(id).unwrap()]);
+            }
+            Ok(Response::Payload(payload))
+        }
+    }
+
+    /// CFG-MSGOUT keys (U1) of the message rates
+    fn rates(count: u32) -> Vec<(u32, Vec<u8>)> {
+        (0..count).map(|index| (0x20910000 + index, vec![1])).collect()
+    }
+
+    #[test]
+    fn chunked_transaction() {
+        let mut receiver = Receiver::new(&[]);
+        let statuses = valset(&mut receiver, &rates(150), 0x01, Duration::ZERO).unwrap();
+        assert_eq!(statuses, vec![Status::Applied; 150]);
+        let headers = receiver
+            .commands
+            .iter()
+            .map(|payload| (payload[..HEADER_LEN].to_vec(), (payload.len() - HEADER_LEN) / 5))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                (vec![1, 0x01, TRANSACTION_BEGIN, 0], 64),
+                (vec![1, 0x01, TRANSACTION_CONTINUE, 0], 64),
+                (vec![1, 0x01, TRANSACTION_END, 0], 22),
+            ]
+        );
+        // 64 keys fit in a single message, without transaction
+        let mut receiver = Receiver::new(&[]);
+        valset(&mut receiver, &rates(64), 0x05, Duration::ZERO).unwrap();
+        assert_eq!(receiver.commands.len(), 1);
+        assert_eq!(receiver.commands[0][..HEADER_LEN], [0, 0x05, TRANSACTIONLESS, 0]);
+        assert_eq!(receiver.commands[0].len(), HEADER_LEN + 64 * 5);
+    }
+
+    #[test]
+    fn rejected_transaction_aborted() {
+        let mut receiver = Receiver::new(&[Ack::Acked, Ack::Nak]);
+        let statuses = valset(&mut receiver, &rates(150), 0x01, Duration::ZERO).unwrap();
+        assert_eq!(statuses[..64], [Status::Aborted; 64]);
+        assert_eq!(statuses[64..128], [Status::Rejected(Ack::Nak); 64]);
+        assert_eq!(statuses[128..], [Status::Aborted; 22]);
+        // begin, continue, then the abort instead of the end
+        assert_eq!(receiver.commands.len(), 3);
+        assert_eq!(receiver.commands[2], [0, 0x01, TRANSACTIONLESS, 0]);
+        // a single message is not a transaction
+        let mut receiver = Receiver::new(&[Ack::Timeout]);
+        let statuses = valset(&mut receiver, &rates(2), 0x01, Duration::ZERO).unwrap();
+        assert_eq!(statuses, [Status::Rejected(Ack::Timeout); 2]);
+        assert_eq!(receiver.commands.len(), 1);
+    }
+
+    #[test]
+    fn chunked_polls() {
+        let keys = (0..100).map(|index| 0x40520000 + index).collect::<Vec<_>>();
+        let mut receiver = Receiver::new(&[]);
+        receiver.unknown.push(0x40520050);
+        let values = valget(&mut receiver, &keys, 0, Duration::ZERO).unwrap();
+        assert_eq!(values[0], Ok(0x40520000_u32.to_le_bytes().to_vec()));
+        assert_eq!(values[99], Ok(0x40520063_u32.to_le_bytes().to_vec()));
+        assert_eq!(values[0x50], Err(Response::Nak));
+        assert_eq!(values.iter().filter(|value| value.is_ok()).count(), 99);
+        // 64 keys, then the 36 others key by key, the unknown one rejecting their request
+        assert_eq!(receiver.polls.len(), 1 + 1 + 36);
+        assert_eq!(receiver.polls[0].len(), HEADER_LEN + 64 * 4);
+    }
+
+    #[test]
+    fn values_of_a_response() {
+        // CFG-RATE-MEAS (U2) = 1000, CFG-NAVSPG-DYNMODEL (E1) = 4, CFG-UART1-BAUDRATE (U4)
+        let mut payload = vec![1, 0, 0, 0];
+        payload.extend_from_slice(&0x30210001_u32.to_le_bytes());
+        payload.extend_from_slice(&1000_u16.to_le_bytes());
+        payload.extend_from_slice(&0x20110021_u32.to_le_bytes());
+        payload.push(4);
+        payload.extend_from_slice(&0x40520001_u32.to_le_bytes());
+        payload.extend_from_slice(&115200_u32.to_le_bytes());
+        assert_eq!(value(&payload, 0x30210001), Some(vec![0xe8, 0x03]));
+        assert_eq!(value(&payload, 0x20110021), Some(vec![4]));
+        assert_eq!(value(&payload, 0x40520001), Some(115200_u32.to_le_bytes().to_vec()));
+        assert_eq!(value(&payload, 0x10310021), None);
+        // truncated value
+        assert_eq!(value(&payload[..payload.len() - 1], 0x40520001), None);
+    }
+}
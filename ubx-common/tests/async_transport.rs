@@ -0,0 +1,137 @@
+//! Asynchronous device against a fake receiver, served by a task of the same runtime
+//! over an in-memory duplex stream
+
+use std::time::Duration;
+
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use ubx_common::frame::frame;
+use ubx_common::transport::{AsyncDevice, Frame};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Sentence the fake sends ahead of its MON-VER responses
+const NMEA: &[u8] = b"$GNGGA,,,,,,0,00,99.99,,,,,,*56\r\n";
+
+/// MON-VER payload: software and hardware versions, one extension
+fn mon_ver() -> Vec<u8> {
+    let mut payload = vec![0; 40];
+    payload[..8].copy_from_slice(b"ROM CORE");
+    payload[30..38].copy_from_slice(b"00080000");
+    let mut extension = [0; 30];
+    extension[..13].copy_from_slice(b"PROTVER=18.00");
+    payload.extend_from_slice(&extension);
+    payload
+}
+
+/// Fake receiver: answers the MON-VER polls after some NMEA, acknowledges CFG-RATE, rejects
+/// CFG-NAV5, polled or written, and leaves CFG-MSG unanswered
+async fn receiver(stream: DuplexStream) {
+    let mut device = AsyncDevice::new(stream);
+    while let Ok(Some(Frame { class, id, payload })) = device.next_frame().await {
+        let response = match (class, id, payload.is_empty()) {
+            (0x0a, 0x04, true) => {
+                let mut response = NMEA.to_vec();
+                response.extend_from_slice(&frame(0x0a, 0x04, &mon_ver()));
+                response
+            },
+            (0x06, 0x08, false) => frame(0x05, 0x01, &[0x06, 0x08]),
+            (0x06, 0x24, _) => frame(0x05, 0x00, &[0x06, 0x24]),
+            _ => continue,
+        };
+        // split writes, frames arriving in pieces
+        for chunk in response.chunks(7) {
+            if device.write_all(chunk).await.is_err() {
+                return;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+fn connect() -> AsyncDevice<DuplexStream> {
+    let (host, fake) = tokio::io::duplex(64);
+    tokio::spawn(receiver(fake));
+    AsyncDevice::new(host)
+}
+
+#[tokio::test]
+async fn poll_skips_nmea() {
+    let mut device = connect();
+    let payload = device.poll_payload(0x0a, 0x04, &[], TIMEOUT).await.unwrap();
+    assert_eq!(payload, mon_ver());
+    assert_eq!(device.discarded(), NMEA.len() as u64);
+}
+
+#[tokio::test]
+async fn acknowledgments() {
+    let mut device = connect();
+    let rate = [0xe8, 0x03, 0x01, 0x00, 0x01, 0x00];
+    device.write_all(&frame(0x06, 0x08, &rate)).await.unwrap();
+    assert!(device.wait_for_ack_of(0x06, 0x08, TIMEOUT).await.unwrap());
+
+    device.write_all(&frame(0x06, 0x24, &[0; 36])).await.unwrap();
+    assert!(!device.wait_for_ack_of(0x06, 0x24, TIMEOUT).await.unwrap());
+
+    device.write_all(&frame(0x06, 0x01, &[0xf0, 0x00, 0x00])).await.unwrap();
+    let error = device.wait_for_ack_of(0x06, 0x01, TIMEOUT).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn rejected_poll() {
+    let mut device = connect();
+    let error = device.poll_payload(0x06, 0x24, &[], TIMEOUT).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    // unanswered
+    let error = device.poll_payload(0x06, 0x01, &[0xf0, 0x00], TIMEOUT).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn corrupted_frames() {
+    let (host, mut fake) = tokio::io::duplex(256);
+    let mut device = AsyncDevice::new(host);
+    let mut corrupted = frame(0x01, 0x07, &[1, 2, 3]);
+    corrupted[8] ^= 0xff;
+    fake.write_all(&corrupted).await.unwrap();
+    // header of an impossible length
+    fake.write_all(&[0xb5, 0x62, 0x01, 0x07, 0xff, 0xff]).await.unwrap();
+    fake.write_all(&frame(0x01, 0x07, &[4, 5, 6])).await.unwrap();
+    drop(fake);
+    let frame = device.next_frame().await.unwrap().unwrap();
+    assert_eq!((frame.class, frame.id, frame.payload), (0x01, 0x07, vec![4, 5, 6]));
+    assert_eq!(device.discarded(), 11 + 6);
+    assert!(device.next_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn tasks_share_the_runtime() {
+    // receiver, polls and a timer run concurrently on the single threaded test runtime
+    let mut device = connect();
+    let ticks = tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_millis(1));
+        for _ in 0..5 {
+            interval.tick().await;
+        }
+    });
+    for _ in 0..3 {
+        device.poll_payload(0x0a, 0x04, &[], TIMEOUT).await.unwrap();
+    }
+    ticks.await.unwrap();
+}
+
+#[tokio::test]
+async fn raw_reads_follow_the_frames() {
+    let (host, mut fake) = tokio::io::duplex(256);
+    let mut device = AsyncDevice::new(host);
+    let mut received = frame(0x01, 0x07, &[1, 2, 3]);
+    received.extend_from_slice(NMEA);
+    fake.write_all(&received).await.unwrap();
+    drop(fake);
+    assert_eq!(device.next_frame().await.unwrap().unwrap().payload, [1, 2, 3]);
+    // the sentence read by the frame scan comes first
+    let mut output = [0; 64];
+    let size = device.read(&mut output).await.unwrap();
+    assert_eq!(&output[..size], NMEA);
+    assert_eq!(device.read(&mut output).await.unwrap(), 0);
+}
@@ -0,0 +1,64 @@
+//! Configuration updates through UBX-CFG-VALSET
+
+use std::time::Duration;
+
+use ubx_common::ucenter::{Entry, LAYER_BBR, LAYER_FLASH, LAYER_RAM};
+use ubx_common::valcfg::{self, Status};
+
+use crate::device::Device;
+
+/// Applies the settings to the layers given by the file, or `layers` when
+/// set, RAM otherwise. Settings of a layer set are applied in a single
+/// transaction, split in as many VALSET messages as needed, aborted when one
+/// of them is rejected. Prints one report line per setting, returns true
+/// when all of them were applied.
+pub fn apply(
+    device: &mut Device,
+    entries: &[Entry],
+    layers: Option<u8>,
+    timeout: Duration,
+) -> std::io::Result<bool> {
+    // group by layers, preserving the file order
+    let mut groups: Vec<(u8, Vec<&Entry>)> = vec![];
+    for entry in entries {
+        let mask = layers.or(entry.layers).unwrap_or(LAYER_RAM);
+        match groups.iter_mut().find(|(m, _)| *m == mask) {
+            Some((_, group)) => group.push(entry),
+            None => groups.push((mask, vec![entry])),
+        }
+    }
+
+    let mut success = true;
+    for (mask, group) in groups {
+        let values = group
+            .iter()
+            .map(|entry| (entry.key, entry.value.clone()))
+            .collect::<Vec<_>>();
+        let statuses = valcfg::valset(device, &values, mask, timeout)?;
+        for (entry, status) in group.iter().zip(statuses) {
+            println!(
+                "line {}: {} = {} [{}]: {}",
+                entry.line,
+                entry.label(),
+                entry.text,
+                layer_names(mask),
+                status
+            );
+            success &= status == Status::Applied;
+        }
+    }
+    Ok(success)
+}
+
+fn layer_names(mask: u8) -> String {
+    [
+        (LAYER_RAM, "RAM"),
+        (LAYER_BBR, "BBR"),
+        (LAYER_FLASH, "Flash"),
+    ]
+    .iter()
+    .filter(|(bit, _)| mask & bit != 0)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>()
+    .join(",")
+}
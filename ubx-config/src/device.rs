@@ -0,0 +1,226 @@
+//! Serial link to the receiver, with request/response helpers
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use ubx_common::frame::{checksum, frame};
+use ubx_common::valcfg::Link;
+pub use ubx_common::valcfg::{Ack, Response};
+
+const SYNC_CHAR_1: u8 = 0xb5;
+const SYNC_CHAR_2: u8 = 0x62;
+
+/// Frames larger than this are considered corrupted
+const MAX_PAYLOAD_LEN: usize = 8192;
+
+/// UBX-ACK class, ACK-NAK and ACK-ACK ids
+const ACK_CLASS: u8 = 0x05;
+const ACK_NAK_ID: u8 = 0x00;
+const ACK_ACK_ID: u8 = 0x01;
+
+/// Validated UBX frame
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Poll request, answered by a frame of the same class and id
+#[derive(Debug, Clone)]
+pub struct Poll {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+pub struct Device {
+    port: Box<dyn serialport::SerialPort>,
+    /// Received bytes not framed yet
+    buf: Vec<u8>,
+}
+
+impl Device {
+    pub fn new(port: Box<dyn serialport::SerialPort>) -> Device {
+        Device {
+            port,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.port.write_all(data)
+    }
+
+    /// Returns the next valid frame, or None if none is received before `deadline`
+    pub fn next_frame(&mut self, deadline: Instant) -> std::io::Result<Option<Frame>> {
+        loop {
+            if let Some(frame) = self.extract_frame() {
+                return Ok(Some(frame));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            let mut local_buf = [0; 1024];
+            let nbytes = self.read_port(&mut local_buf)?;
+            self.buf.extend_from_slice(&local_buf[..nbytes]);
+        }
+    }
+
+    /// Sends all polls at once, then collects responses until every poll is
+    /// answered. A response matches the first pending poll of the same class
+    /// and id whose payload it starts with, like the port id of CFG-PRT.
+    /// Each poll gets `timeout` to be answered after the previous answer.
+    pub fn poll_batch(
+        &mut self,
+        polls: &[Poll],
+        timeout: Duration,
+    ) -> std::io::Result<Vec<Response>> {
+        for poll in polls {
+            self.write_all(&frame(poll.class, poll.id, &poll.payload))?;
+        }
+        let mut responses = vec![Response::Timeout; polls.len()];
+        let mut pending = polls.len();
+        let mut deadline = Instant::now() + timeout;
+        while pending > 0 {
+            let Some(frame) = self.next_frame(deadline)? else {
+                break;
+            };
+            let nak =
+                frame.class == ACK_CLASS && frame.id == ACK_NAK_ID && frame.payload.len() == 2;
+            let index = polls.iter().zip(&responses).position(|(poll, response)| {
+                let answers = if nak {
+                    frame.payload == [poll.class, poll.id]
+                } else {
+                    (frame.class, frame.id) == (poll.class, poll.id)
+                        && frame.payload.starts_with(&poll.payload)
+                };
+                answers && matches!(response, Response::Timeout)
+            });
+            if let Some(index) = index {
+                responses[index] = if nak {
+                    Response::Nak
+                } else {
+                    Response::Payload(frame.payload)
+                };
+                pending -= 1;
+                deadline = Instant::now() + timeout;
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Sends a command, then waits up to `timeout` for its acknowledgment
+    pub fn command(
+        &mut self,
+        class: u8,
+        id: u8,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Ack> {
+        self.write_all(&frame(class, id, payload))?;
+        let deadline = Instant::now() + timeout;
+        while let Some(frame) = self.next_frame(deadline)? {
+            if frame.class == ACK_CLASS && frame.payload == [class, id] {
+                match frame.id {
+                    ACK_ACK_ID => return Ok(Ack::Acked),
+                    ACK_NAK_ID => return Ok(Ack::Nak),
+                    _ => {},
+                }
+            }
+        }
+        Ok(Ack::Timeout)
+    }
+
+    /// Extracts the first valid frame from the receive buffer,
+    /// dropping garbage and corrupted frames on the way
+    fn extract_frame(&mut self) -> Option<Frame> {
+        loop {
+            let start = self
+                .buf
+                .windows(2)
+                .position(|w| w == [SYNC_CHAR_1, SYNC_CHAR_2]);
+            match start {
+                Some(start) => {
+                    self.buf.drain(..start);
+                },
+                None => {
+                    // keep a possible first sync char
+                    let keep = usize::from(self.buf.last() == Some(&SYNC_CHAR_1));
+                    self.buf.drain(..self.buf.len() - keep);
+                    return None;
+                },
+            }
+            if self.buf.len() < 6 {
+                return None;
+            }
+            let len = usize::from(u16::from_le_bytes([self.buf[4], self.buf[5]]));
+            if len > MAX_PAYLOAD_LEN {
+                self.buf.drain(..2);
+                continue;
+            }
+            if self.buf.len() < len + 8 {
+                return None;
+            }
+            let (ck_a, ck_b) = checksum(&self.buf[2..len + 6]);
+            if (ck_a, ck_b) != (self.buf[len + 6], self.buf[len + 7]) {
+                self.buf.drain(..2);
+                continue;
+            }
+            let frame = Frame {
+                class: self.buf[2],
+                id: self.buf[3],
+                payload: self.buf[6..len + 6].to_vec(),
+            };
+            self.buf.drain(..len + 8);
+            return Some(frame);
+        }
+    }
+
+    /// Reads the serial port, converting timeouts into "no data received"
+    fn read_port(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
+        match self.port.read(output) {
+            Ok(b) => Ok(b),
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::TimedOut {
+                    Ok(0)
+                } else {
+                    Err(e)
+                }
+            },
+        }
+    }
+}
+
+impl Link for Device {
+    fn command(
+        &mut self,
+        class: u8,
+        id: u8,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Ack> {
+        Device::command(self, class, id, payload, timeout)
+    }
+
+    fn poll(
+        &mut self,
+        class: u8,
+        id: u8,
+        request: &[u8],
+        prefix: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Response> {
+        self.write_all(&frame(class, id, request))?;
+        let deadline = Instant::now() + timeout;
+        while let Some(frame) = self.next_frame(deadline)? {
+            if (frame.class, frame.id) == (class, id) && frame.payload.starts_with(prefix) {
+                return Ok(Response::Payload(frame.payload));
+            }
+            if (frame.class, frame.id) == (ACK_CLASS, ACK_NAK_ID) && frame.payload == [class, id] {
+                return Ok(Response::Nak);
+            }
+        }
+        Ok(Response::Timeout)
+    }
+}
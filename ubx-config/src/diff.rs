@@ -0,0 +1,91 @@
+//! Comparison of a configuration file with the live receiver
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ubx_common::ucenter::{self, Entry, LAYER_BBR, LAYER_FLASH, LAYER_RAM};
+use ubx_common::valcfg;
+
+use crate::device::{Device, Response};
+use crate::valget;
+
+/// Settings of the file and of the device, with the settings the device did not answer
+pub type Comparison = (Vec<(String, String)>, Vec<(String, String)>, Vec<(String, Response)>);
+
+/// Prints settings that differ between file and device, one per line,
+/// returns true when both are identical. `unavailable` lists the items
+/// the device did not answer, by name, which explains missing settings.
+pub fn compare(
+    file: &[(String, String)],
+    device: &[(String, String)],
+    unavailable: &[(String, Response)],
+) -> bool {
+    let device_values = device.iter().cloned().collect::<HashMap<_, _>>();
+    let file_values = file.iter().cloned().collect::<HashMap<_, _>>();
+    let mut identical = true;
+
+    for (key, value) in file {
+        match device_values.get(key) {
+            Some(device_value) if device_value == value => {},
+            Some(device_value) => {
+                println!("{}: file={} device={}", key, value, device_value);
+                identical = false;
+            },
+            None => {
+                let reason = unavailable
+                    .iter()
+                    .find(|(name, _)| key == name || key.starts_with(&format!("{}.", name)));
+                match reason {
+                    Some((_, response)) => println!("{}: missing on device ({})", key, response),
+                    None => println!("{}: missing on device", key),
+                }
+                identical = false;
+            },
+        }
+    }
+    for (key, _) in device {
+        if !file_values.contains_key(key) {
+            println!("{}: missing in file", key);
+            identical = false;
+        }
+    }
+    identical
+}
+
+/// Polls the keys of a u-center configuration file with VALGET, in the layers given by the
+/// file, RAM otherwise. Settings are named like `CFG-RATE-MEAS [Flash]`, values formatted in
+/// hexadecimal when the file writes them so, in decimal otherwise.
+pub fn valget_settings(
+    device: &mut Device,
+    entries: &[Entry],
+    timeout: Duration,
+) -> std::io::Result<Comparison> {
+    let mut file = vec![];
+    let mut polled = vec![];
+    let mut unavailable = vec![];
+    for (mask, name, layer) in [
+        (LAYER_RAM, "RAM", valget::LAYER_RAM),
+        (LAYER_BBR, "BBR", valget::LAYER_BBR),
+        (LAYER_FLASH, "Flash", valget::LAYER_FLASH),
+    ] {
+        let entries = entries
+            .iter()
+            .filter(|entry| entry.layers.unwrap_or(LAYER_RAM) & mask != 0)
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            continue;
+        }
+        let keys = entries.iter().map(|entry| entry.key).collect::<Vec<_>>();
+        let values = valcfg::valget(device, &keys, layer, timeout)?;
+        for (entry, value) in entries.into_iter().zip(values) {
+            let label = format!("{} [{}]", entry.label(), name);
+            let hex = entry.text.starts_with("0x") || entry.text.starts_with("0X");
+            file.push((label.clone(), ucenter::format(&entry.value, hex)));
+            match value {
+                Ok(value) => polled.push((label, ucenter::format(&value, hex))),
+                Err(response) => unavailable.push((label, response)),
+            }
+        }
+    }
+    Ok((file, polled, unavailable))
+}
@@ -0,0 +1,41 @@
+//! Polls of the legacy configuration (M8 and older receivers), in batches. The items, their
+//! settings and the dumps are shared with ubx-record ([ubx_common::legacy]).
+
+use std::time::Duration;
+
+pub use ubx_common::legacy::{items, parse_dump, settings, write_dump, Item, DUMP_HEADER};
+
+use crate::device::{Device, Poll, Response};
+
+/// Polls sent at once
+const BATCH_SIZE: usize = 8;
+
+fn poll(item: &Item) -> Poll {
+    Poll {
+        class: item.class,
+        id: item.id,
+        payload: item.poll_payload.clone(),
+    }
+}
+
+/// Polls all items in batches, reporting progress on stderr
+pub fn poll_all(
+    device: &mut Device,
+    items: &[Item],
+    timeout: Duration,
+) -> std::io::Result<Vec<Response>> {
+    let mut responses = vec![Response::Timeout; items.len()];
+    for (batch, chunk) in items.chunks(BATCH_SIZE).enumerate() {
+        let polls = chunk.iter().map(poll).collect::<Vec<_>>();
+        for (index, response) in device.poll_batch(&polls, timeout)?.into_iter().enumerate() {
+            responses[batch * BATCH_SIZE + index] = response;
+        }
+        eprint!(
+            "\rpolled {}/{} items",
+            batch * BATCH_SIZE + chunk.len(),
+            items.len()
+        );
+    }
+    eprintln!();
+    Ok(responses)
+}
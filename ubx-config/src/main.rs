@@ -0,0 +1,458 @@
+use clap::{value_parser, Arg, ArgAction, Command};
+use serialport::{
+    DataBits as SerialDataBits, FlowControl as SerialFlowControl, Parity as SerialParity,
+    StopBits as SerialStopBits,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+mod apply;
+mod device;
+mod diff;
+mod legacy;
+mod rinv;
+mod valget;
+
+use device::Device;
+use rinv::Inventory;
+use ubx_common::frame::frame;
+use ubx_common::{keys, ucenter, valcfg};
+
+/// UBX-MON-VER class and id
+const MON_VER: (u8, u8) = (0x0a, 0x04);
+
+fn main() {
+    let matches = Command::new("ubx-config")
+        .author(clap::crate_authors!())
+        .about("Inspect and manage the configuration of your U-Blox receiver")
+        .arg_required_else_help(true)
+        .subcommand_required(true)
+        .next_help_heading("Serial configuration")
+        .arg(
+            Arg::new("port")
+                .value_name("PORT")
+                .short('p')
+                .long("port")
+                .global(true)
+                .help("Serial port to open"),
+        )
+        .arg(
+            Arg::new("baud")
+                .value_name("BAUD")
+                .short('s')
+                .long("baud")
+                .global(true)
+                .default_value("9600")
+                .value_parser(value_parser!(u32))
+                .help("Baud rate of the port to open"),
+        )
+        .arg(
+            Arg::new("stop-bits")
+                .long("stop-bits")
+                .help("Number of stop bits to use for open port")
+                .global(true)
+                .value_parser(["1", "2"])
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("data-bits")
+                .long("data-bits")
+                .help("Number of data bits to use for open port")
+                .global(true)
+                .value_parser(["7", "8"])
+                .default_value("8"),
+        )
+        .arg(
+            Arg::new("parity")
+                .long("parity")
+                .help("Parity to use for open port")
+                .global(true)
+                .value_parser(["even", "odd"]),
+        )
+        .subcommand(
+            Command::new("dump")
+                .about("Dump the receiver configuration to a text file")
+                .arg(
+                    Arg::new("legacy")
+                        .long("legacy")
+                        .action(ArgAction::SetTrue)
+                        .help(
+                            "Poll the legacy CFG messages (M8 and older receivers), instead of \
+the known keys with VALGET into a u-center configuration file",
+                        ),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output file name, standard output otherwise"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("MS")
+                        .default_value("1000")
+                        .value_parser(value_parser!(u64))
+                        .help("Time to wait for each poll response, in milliseconds"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare a configuration dump with the receiver configuration")
+                .long_about(
+                    "Compare a configuration dump with the receiver configuration.
+Legacy dumps are compared through the CFG polls, u-center configuration files
+(generation 9) through UBX-CFG-VALGET in the layers of the file.
+Prints the differing settings and exits with 1 when drift is found, 0 otherwise.",
+                )
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Configuration dump or u-center configuration file to compare against"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("MS")
+                        .default_value("1000")
+                        .value_parser(value_parser!(u64))
+                        .help("Time to wait for each poll response, in milliseconds"),
+                ),
+        )
+        .subcommand(
+            Command::new("apply")
+                .about("Apply a configuration file through VALSET (generation 9 receivers)")
+                .arg(
+                    Arg::new("ucenter")
+                        .long("ucenter")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("u-center configuration file to apply"),
+                )
+                .arg(
+                    Arg::new("layer")
+                        .long("layer")
+                        .value_name("LAYERS")
+                        .value_delimiter(',')
+                        .value_parser(["ram", "bbr", "flash"])
+                        .help("Layers to apply to, overriding the layers of the file"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("MS")
+                        .default_value("1000")
+                        .value_parser(value_parser!(u64))
+                        .help("Time to wait for each acknowledgment, in milliseconds"),
+                ),
+        )
+        .subcommand(
+            Command::new("rinv")
+                .about("Show or write the remote inventory (UBX-CFG-RINV), like an asset tag")
+                .group(
+                    clap::ArgGroup::new("action")
+                        .args(["show", "set", "set-hex"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("show")
+                        .long("show")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the stored data, as text unless flagged binary"),
+                )
+                .arg(
+                    Arg::new("set")
+                        .long("set")
+                        .value_name("TEXT")
+                        .help("Store printable ASCII data, 30 bytes at most"),
+                )
+                .arg(
+                    Arg::new("set-hex")
+                        .long("set-hex")
+                        .value_name("HEX")
+                        .help("Store binary data given in hexadecimal, 30 bytes at most"),
+                )
+                .arg(
+                    Arg::new("dump-at-startup")
+                        .long("dump-at-startup")
+                        .action(ArgAction::SetTrue)
+                        .help("Have the receiver output the stored data at startup"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("MS")
+                        .default_value("1000")
+                        .value_parser(value_parser!(u64))
+                        .help("Time to wait for each response, in milliseconds"),
+                ),
+        )
+        .get_matches();
+
+    // validated before opening the port
+    let inventory = match matches.subcommand() {
+        Some(("rinv", sub_matches)) => {
+            let dump = sub_matches.get_flag("dump-at-startup");
+            let inventory = match (
+                sub_matches.get_one::<String>("set"),
+                sub_matches.get_one::<String>("set-hex"),
+            ) {
+                (Some(text), _) => Some(Inventory::text(text, dump)),
+                (_, Some(hex)) => Some(Inventory::hex(hex, dump)),
+                _ => None,
+            };
+            inventory.map(|inventory| {
+                inventory.unwrap_or_else(|e| {
+                    eprintln!("Invalid remote inventory data: {}", e);
+                    std::process::exit(2);
+                })
+            })
+        },
+        _ => None,
+    };
+
+    let port = matches.get_one::<String>("port").unwrap_or_else(|| {
+        eprintln!("Serial port to open is required (-p, --port)");
+        std::process::exit(2);
+    });
+
+    let baud = matches.get_one::<u32>("baud").cloned().unwrap_or(9600);
+    let stop_bits = match matches.get_one::<String>("stop-bits").map(|s| s.as_str()) {
+        Some("2") => SerialStopBits::Two,
+        _ => SerialStopBits::One,
+    };
+
+    let data_bits = match matches.get_one::<String>("data-bits").map(|s| s.as_str()) {
+        Some("7") => SerialDataBits::Seven,
+        _ => SerialDataBits::Eight,
+    };
+
+    let parity = match matches.get_one::<String>("parity").map(|s| s.as_str()) {
+        Some("odd") => SerialParity::Odd,
+        Some("even") => SerialParity::Even,
+        _ => SerialParity::None,
+    };
+
+    let builder = serialport::new(port, baud)
+        .stop_bits(stop_bits)
+        .data_bits(data_bits)
+        .timeout(Duration::from_millis(10))
+        .parity(parity)
+        .flow_control(SerialFlowControl::None);
+
+    let port = builder.open().unwrap_or_else(|e| {
+        eprintln!("Failed to open \"{}\". Error: {}", port, e);
+        ::std::process::exit(2);
+    });
+
+    let mut device = Device::new(port);
+
+    if let Some(("dump", sub_matches)) = matches.subcommand() {
+        let timeout = Duration::from_millis(*sub_matches.get_one::<u64>("timeout").unwrap());
+
+        let mut header = vec![format!("date: {}", chrono::Utc::now().to_rfc3339())];
+        header.extend(receiver_version(&mut device, timeout));
+
+        let writer: Box<dyn Write> = match sub_matches.get_one::<String>("output") {
+            Some(path) => {
+                Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| {
+                    panic!("failed to create file \"{}\": {}", path, e)
+                })))
+            },
+            None => Box::new(std::io::stdout().lock()),
+        };
+
+        if sub_matches.get_flag("legacy") {
+            let items = legacy::items();
+            let responses = legacy::poll_all(&mut device, &items, timeout)
+                .unwrap_or_else(|e| panic!("failed to poll receiver configuration: {}", e));
+
+            for (item, response) in items.iter().zip(&responses) {
+                if !matches!(response, device::Response::Payload(_)) {
+                    eprintln!("{}: {}", item.name, response);
+                }
+            }
+            legacy::write_dump(writer, &header, &items, &responses)
+                .expect("failed to write configuration dump");
+        } else {
+            let keys = keys::known_keys().iter().map(|key| key.id).collect::<Vec<_>>();
+            let values = valcfg::valget(&mut device, &keys, valget::LAYER_RAM, timeout)
+                .unwrap_or_else(|e| panic!("failed to poll receiver configuration: {}", e));
+
+            let mut read = vec![];
+            let mut unknown = 0;
+            for (key, value) in keys.into_iter().zip(values) {
+                match value {
+                    Ok(value) => read.push((key, value)),
+                    Err(_) => unknown += 1,
+                }
+            }
+            if read.is_empty() {
+                eprintln!("No key read (UBX-CFG-VALGET), use --legacy for M8 and older receivers");
+                std::process::exit(1);
+            }
+            if unknown > 0 {
+                eprintln!("{} keys not read, unknown to the firmware", unknown);
+            }
+            ucenter::write(writer, &header, "RAM", &read)
+                .expect("failed to write configuration dump");
+        }
+    }
+
+    if let Some(("diff", sub_matches)) = matches.subcommand() {
+        let path = sub_matches.get_one::<String>("file").unwrap();
+        let timeout = Duration::from_millis(*sub_matches.get_one::<u64>("timeout").unwrap());
+
+        let content = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read \"{}\". Error: {}", path, e);
+            std::process::exit(2);
+        });
+        let (file_settings, device_settings, unavailable) =
+            if content.lines().next() == Some(legacy::DUMP_HEADER) {
+                let dump = legacy::parse_dump(&content).unwrap_or_else(|e| {
+                    eprintln!("Failed to parse \"{}\": {}", path, e);
+                    std::process::exit(2);
+                });
+
+                let items = legacy::items()
+                    .into_iter()
+                    .filter(|item| dump.sections.contains(&item.section))
+                    .collect::<Vec<_>>();
+                let responses = legacy::poll_all(&mut device, &items, timeout)
+                    .unwrap_or_else(|e| panic!("failed to poll receiver configuration: {}", e));
+
+                let unavailable = items
+                    .iter()
+                    .zip(&responses)
+                    .filter(|(_, response)| !matches!(response, device::Response::Payload(_)))
+                    .map(|(item, response)| (item.name.clone(), response.clone()))
+                    .collect::<Vec<_>>();
+                (dump.settings, legacy::settings(&items, &responses), unavailable)
+            } else {
+                let entries = match ucenter::parse(&content) {
+                    Ok((entries, warnings)) if !entries.is_empty() => {
+                        for warning in warnings {
+                            eprintln!("{}", warning);
+                        }
+                        entries
+                    },
+                    Ok(_) => {
+                        eprintln!(
+                            "Failed to parse \"{}\": neither a legacy dump nor a u-center \
+configuration file",
+                            path
+                        );
+                        std::process::exit(2);
+                    },
+                    Err(e) => {
+                        eprintln!(
+                            "Failed to parse \"{}\" as a u-center configuration file: {}",
+                            path, e
+                        );
+                        std::process::exit(2);
+                    },
+                };
+                diff::valget_settings(&mut device, &entries, timeout)
+                    .unwrap_or_else(|e| panic!("failed to poll receiver configuration: {}", e))
+            };
+
+        if !diff::compare(&file_settings, &device_settings, &unavailable) {
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(("rinv", sub_matches)) = matches.subcommand() {
+        let timeout = Duration::from_millis(*sub_matches.get_one::<u64>("timeout").unwrap());
+        match inventory {
+            Some(inventory) => {
+                let written = rinv::write(&mut device, &inventory, timeout)
+                    .unwrap_or_else(|e| panic!("failed to write remote inventory: {}", e));
+                match written {
+                    Ok(()) => println!("remote inventory written: {}", inventory.data()),
+                    Err(e) => {
+                        eprintln!("Failed to write the remote inventory: {}", e);
+                        std::process::exit(1);
+                    },
+                }
+            },
+            None => {
+                let stored = rinv::read(&mut device, timeout)
+                    .unwrap_or_else(|e| panic!("failed to read remote inventory: {}", e));
+                match stored {
+                    Ok(inventory) => println!("{}", inventory),
+                    Err(e) => {
+                        eprintln!("Failed to read the remote inventory: {}", e);
+                        std::process::exit(2);
+                    },
+                }
+            },
+        }
+    }
+
+    if let Some(("apply", sub_matches)) = matches.subcommand() {
+        let path = sub_matches.get_one::<String>("ucenter").unwrap();
+        let timeout = Duration::from_millis(*sub_matches.get_one::<u64>("timeout").unwrap());
+        let layers = sub_matches.get_many::<String>("layer").map(|layers| {
+            layers.fold(0, |mask, layer| {
+                mask | match layer.as_str() {
+                    "bbr" => ucenter::LAYER_BBR,
+                    "flash" => ucenter::LAYER_FLASH,
+                    _ => ucenter::LAYER_RAM,
+                }
+            })
+        });
+
+        let content = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read \"{}\". Error: {}", path, e);
+            std::process::exit(2);
+        });
+        let (entries, warnings) = ucenter::parse(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse \"{}\": {}", path, e);
+            std::process::exit(2);
+        });
+        for warning in warnings {
+            eprintln!("warning: {}", warning);
+        }
+
+        let applied = apply::apply(&mut device, &entries, layers, timeout)
+            .unwrap_or_else(|e| panic!("failed to apply configuration: {}", e));
+        if !applied {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Polls UBX-MON-VER, returns the software, hardware and extension strings
+fn receiver_version(device: &mut Device, timeout: Duration) -> Vec<String> {
+    device
+        .write_all(&frame(MON_VER.0, MON_VER.1, &[]))
+        .expect("Unable to write request/poll for UBX-MON-VER message");
+
+    let deadline = Instant::now() + timeout;
+    while let Ok(Some(frame)) = device.next_frame(deadline) {
+        if (frame.class, frame.id) != MON_VER || frame.payload.len() < 40 {
+            continue;
+        }
+        let mut lines = vec![
+            format!("software: {}", cstr(&frame.payload[..30])),
+            format!("hardware: {}", cstr(&frame.payload[30..40])),
+        ];
+        for extension in frame.payload[40..].chunks_exact(30) {
+            lines.push(format!("extension: {}", cstr(extension)));
+        }
+        return lines;
+    }
+    eprintln!("UBX-MON-VER: no response");
+    vec![]
+}
+
+/// Null terminated string
+fn cstr(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
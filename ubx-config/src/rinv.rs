@@ -0,0 +1,220 @@
+//! Remote inventory (UBX-CFG-RINV): up to 30 bytes of user data stored in the receiver,
+//! like an asset tag, optionally dumped at startup
+
+use std::time::Duration;
+
+use crate::device::{Ack, Device, Poll, Response};
+
+/// UBX-CFG-RINV class and id
+const CFG_RINV: (u8, u8) = (0x06, 0x34);
+
+/// Largest amount of data the remote inventory holds
+pub const MAX_DATA_LEN: usize = 30;
+
+/// CFG-RINV `flags` bits
+const FLAG_DUMP: u8 = 0x01;
+const FLAG_BINARY: u8 = 0x02;
+
+/// Remote inventory content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inventory {
+    /// Data dumped at startup (as a INF-NOTICE or binary message)
+    pub dump: bool,
+    /// Data flagged as binary, ASCII otherwise
+    pub binary: bool,
+    pub data: Vec<u8>,
+}
+
+impl Inventory {
+    /// ASCII data, like "STATION=BASE1;INSTALLED=2024-07-01"
+    pub fn text(text: &str, dump: bool) -> Result<Self, String> {
+        if let Some(c) = text.chars().find(|c| !(' '..='~').contains(c)) {
+            return Err(format!(
+                "{:?} is not printable ASCII, write binary data in hexadecimal instead",
+                c
+            ));
+        }
+        Self::new(text.as_bytes().to_vec(), dump, false)
+    }
+
+    /// Binary data given in hexadecimal, like "0a1b2c"
+    pub fn hex(hex: &str, dump: bool) -> Result<Self, String> {
+        if hex.len() % 2 != 0 {
+            return Err("odd number of hexadecimal digits".to_string());
+        }
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| format!("invalid hexadecimal byte at position {}", i))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(data, dump, true)
+    }
+
+    fn new(data: Vec<u8>, dump: bool, binary: bool) -> Result<Self, String> {
+        if data.is_empty() {
+            return Err("no data".to_string());
+        }
+        if data.len() > MAX_DATA_LEN {
+            return Err(format!(
+                "{} bytes, the remote inventory holds {} bytes at most",
+                data.len(),
+                MAX_DATA_LEN
+            ));
+        }
+        Ok(Self { dump, binary, data })
+    }
+
+    /// Decodes a CFG-RINV payload: flags, then the data
+    fn parse(payload: &[u8]) -> Result<Self, String> {
+        let Some((flags, data)) = payload.split_first() else {
+            return Err("empty UBX-CFG-RINV payload".to_string());
+        };
+        if data.len() > MAX_DATA_LEN {
+            return Err(format!("UBX-CFG-RINV of {} bytes of data", data.len()));
+        }
+        Ok(Self {
+            dump: flags & FLAG_DUMP != 0,
+            binary: flags & FLAG_BINARY != 0,
+            data: data.to_vec(),
+        })
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.dump {
+            flags |= FLAG_DUMP;
+        }
+        if self.binary {
+            flags |= FLAG_BINARY;
+        }
+        let mut payload = vec![flags];
+        payload.extend_from_slice(&self.data);
+        payload
+    }
+
+    /// Stored data, as text unless flagged binary or not printable ASCII
+    pub fn data(&self) -> String {
+        let printable = self.data.iter().all(|b| (0x20..=0x7e).contains(b));
+        if !self.binary && printable {
+            return format!("\"{}\"", String::from_utf8_lossy(&self.data));
+        }
+        let hex = self
+            .data
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        format!("0x{}", hex)
+    }
+}
+
+impl std::fmt::Display for Inventory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "data: {}", self.data())?;
+        writeln!(f, "length: {} bytes", self.data.len())?;
+        writeln!(f, "binary: {}", self.binary)?;
+        write!(f, "dump at startup: {}", self.dump)
+    }
+}
+
+/// Polls the remote inventory
+pub fn read(device: &mut Device, timeout: Duration) -> std::io::Result<Result<Inventory, String>> {
+    let poll = Poll {
+        class: CFG_RINV.0,
+        id: CFG_RINV.1,
+        payload: vec![],
+    };
+    let responses = device.poll_batch(&[poll], timeout)?;
+    Ok(match &responses[0] {
+        Response::Payload(payload) => Inventory::parse(payload),
+        response => Err(format!("UBX-CFG-RINV poll: {}", response)),
+    })
+}
+
+/// Writes the remote inventory, then reads it back. Returns the reason of a failure
+pub fn write(
+    device: &mut Device,
+    inventory: &Inventory,
+    timeout: Duration,
+) -> std::io::Result<Result<(), String>> {
+    let ack = device.command(CFG_RINV.0, CFG_RINV.1, &inventory.payload(), timeout)?;
+    if ack != Ack::Acked {
+        return Ok(Err(format!("UBX-CFG-RINV not acknowledged: {}", ack)));
+    }
+    Ok(match read(device, timeout)? {
+        Ok(stored) if stored == *inventory => Ok(()),
+        Ok(stored) => Err(format!("read back {} instead", stored.data())),
+        Err(e) => Err(format!("not read back: {}", e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags() {
+        let tag = Inventory::text("STATION=BASE1", true).unwrap();
+        assert_eq!(tag.payload(), [b"\x01".as_slice(), b"STATION=BASE1"].concat());
+        let binary = Inventory::hex("0a1B2c", false).unwrap();
+        assert_eq!(binary.payload(), [0x02, 0x0a, 0x1b, 0x2c]);
+        let both = Inventory::hex("ff", true).unwrap();
+        assert_eq!(both.payload(), [0x03, 0xff]);
+        for inventory in [tag, binary, both] {
+            assert_eq!(Inventory::parse(&inventory.payload()), Ok(inventory));
+        }
+        // reserved bits are ignored
+        let parsed = Inventory::parse(&[0xfc, b'A']).unwrap();
+        assert!(!parsed.dump && !parsed.binary);
+    }
+
+    #[test]
+    fn length_limits() {
+        assert_eq!(Inventory::text(&"A".repeat(MAX_DATA_LEN), false).unwrap().data.len(), 30);
+        assert_eq!(
+            Inventory::text(&"A".repeat(MAX_DATA_LEN + 1), false).unwrap_err(),
+            "31 bytes, the remote inventory holds 30 bytes at most"
+        );
+        assert!(Inventory::hex(&"00".repeat(MAX_DATA_LEN), false).is_ok());
+        assert!(Inventory::hex(&"00".repeat(MAX_DATA_LEN + 1), false).is_err());
+        assert_eq!(Inventory::text("", false).unwrap_err(), "no data");
+        assert_eq!(Inventory::hex("", false).unwrap_err(), "no data");
+        // received payloads
+        assert!(Inventory::parse(&[0; MAX_DATA_LEN + 1]).is_ok());
+        assert_eq!(
+            Inventory::parse(&[0; MAX_DATA_LEN + 2]).unwrap_err(),
+            "UBX-CFG-RINV of 31 bytes of data"
+        );
+        assert_eq!(Inventory::parse(&[]).unwrap_err(), "empty UBX-CFG-RINV payload");
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(
+            Inventory::text("café", false).unwrap_err(),
+            "'é' is not printable ASCII, write binary data in hexadecimal instead"
+        );
+        assert!(Inventory::text("tab\there", false).is_err());
+        assert_eq!(Inventory::hex("abc", false).unwrap_err(), "odd number of hexadecimal digits");
+        assert_eq!(
+            Inventory::hex("00zz", false).unwrap_err(),
+            "invalid hexadecimal byte at position 2"
+        );
+        // a multibyte character is no hexadecimal byte either
+        assert!(Inventory::hex("0é", false).is_err());
+    }
+
+    #[test]
+    fn display() {
+        let tag = Inventory::text("BASE1", false).unwrap();
+        assert_eq!(
+            tag.to_string(),
+            "data: \"BASE1\"\nlength: 5 bytes\nbinary: false\ndump at startup: false"
+        );
+        assert_eq!(Inventory::hex("4142", false).unwrap().data(), "0x4142");
+        // unflagged data that is not printable
+        assert_eq!(Inventory::parse(&[0x00, 0x41, 0x00]).unwrap().data(), "0x4100");
+    }
+}
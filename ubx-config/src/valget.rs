@@ -0,0 +1,6 @@
+//! Layer numbers of the configuration reads through UBX-CFG-VALGET
+
+/// VALGET layer numbers
+pub const LAYER_RAM: u8 = 0;
+pub const LAYER_BBR: u8 = 1;
+pub const LAYER_FLASH: u8 = 2;
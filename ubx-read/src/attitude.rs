@@ -0,0 +1,233 @@
+//! NAV-ATT vehicle attitude export and statistics
+
+use std::io::Write;
+
+use ublox::{NavAttRef, NavPvtFlags, NavPvtRef};
+
+/// Attitude of an epoch, and its accuracy estimates, in degrees
+#[derive(Debug, Clone, Copy)]
+pub struct Attitude {
+    pub itow: u32,
+    pub roll: f64,
+    pub pitch: f64,
+    pub heading: f64,
+    pub acc_roll: f64,
+    pub acc_pitch: f64,
+    pub acc_heading: f64,
+}
+
+impl From<&NavAttRef<'_>> for Attitude {
+    fn from(att: &NavAttRef) -> Self {
+        Self {
+            itow: att.itow(),
+            roll: att.vehicle_roll(),
+            pitch: att.vehicle_pitch(),
+            heading: att.vehicle_heading(),
+            acc_roll: att.vehicle_roll_accuracy(),
+            acc_pitch: att.vehicle_pitch_accuracy(),
+            acc_heading: att.vehicle_heading_accuracy(),
+        }
+    }
+}
+
+/// Exports one CSV row per NAV-ATT solution
+pub struct AttitudeExporter<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> AttitudeExporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+        }
+    }
+
+    pub fn export(&mut self, att: &NavAttRef) -> std::io::Result<()> {
+        if !self.header_written {
+            writeln!(
+                self.writer,
+                "itow,roll,pitch,heading,acc_roll,acc_pitch,acc_heading"
+            )?;
+            self.header_written = true;
+        }
+        let att = Attitude::from(att);
+        writeln!(
+            self.writer,
+            "{},{:.5},{:.5},{:.5},{:.5},{:.5},{:.5}",
+            att.itow,
+            att.roll,
+            att.pitch,
+            att.heading,
+            att.acc_roll,
+            att.acc_pitch,
+            att.acc_heading
+        )
+    }
+
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Running statistics of a linear quantity
+#[derive(Debug, Default, Clone, Copy)]
+struct Linear {
+    count: usize,
+    sum: f64,
+    sum_squares: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Linear {
+    fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.sum_squares += value * value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        (self.sum_squares / self.count as f64 - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Running statistics of an angle, in degrees
+#[derive(Debug, Default, Clone, Copy)]
+struct Circular {
+    count: usize,
+    sum_sin: f64,
+    sum_cos: f64,
+}
+
+impl Circular {
+    fn add(&mut self, degrees: f64) {
+        self.count += 1;
+        self.sum_sin += degrees.to_radians().sin();
+        self.sum_cos += degrees.to_radians().cos();
+    }
+
+    /// Circular mean, in [0, 360[
+    fn mean(&self) -> f64 {
+        self.sum_sin
+            .atan2(self.sum_cos)
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    /// Mean resultant length, 1 when all angles are equal
+    fn resultant_length(&self) -> f64 {
+        self.sum_sin.hypot(self.sum_cos) / self.count as f64
+    }
+
+    /// Circular standard deviation, sqrt(-2 ln R)
+    fn std_dev(&self) -> f64 {
+        (-2.0 * self.resultant_length().min(1.0).ln())
+            .sqrt()
+            .to_degrees()
+    }
+}
+
+/// Attitude statistics over a file. An epoch has a valid attitude when
+/// a NAV-ATT solution comes with its NAV-PVT solution flagging the vehicle
+/// heading as valid.
+#[derive(Debug, Default)]
+pub struct AttitudeReport {
+    /// Last NAV-ATT solution, until its NAV-PVT solution is received
+    pending: Option<Attitude>,
+    epochs: usize,
+    valid: usize,
+    attitudes: usize,
+    roll: Linear,
+    pitch: Linear,
+    heading: Circular,
+    acc_roll: Linear,
+    acc_pitch: Linear,
+    acc_heading: Linear,
+}
+
+impl AttitudeReport {
+    pub fn attitude(&mut self, att: &NavAttRef) {
+        self.attitudes += 1;
+        self.pending = Some(Attitude::from(att));
+    }
+
+    pub fn solution(&mut self, pvt: &NavPvtRef) {
+        self.epochs += 1;
+        let Some(att) = self.pending.filter(|att| att.itow == pvt.itow()) else {
+            return;
+        };
+        self.pending = None;
+        if !pvt.flags().contains(NavPvtFlags::HEAD_VEH_VALID) {
+            return;
+        }
+        self.valid += 1;
+        self.roll.add(att.roll);
+        self.pitch.add(att.pitch);
+        self.heading.add(att.heading);
+        self.acc_roll.add(att.acc_roll);
+        self.acc_pitch.add(att.acc_pitch);
+        self.acc_heading.add(att.acc_heading);
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "{} NAV-PVT epochs, {} NAV-ATT solutions",
+            self.epochs, self.attitudes
+        )?;
+        if self.epochs > 0 {
+            writeln!(
+                writer,
+                "valid attitude: {} epochs ({:.1}%)",
+                self.valid,
+                100.0 * self.valid as f64 / self.epochs as f64
+            )?;
+        }
+        if self.valid == 0 {
+            return Ok(());
+        }
+        for (name, stats, acc) in [
+            ("roll", &self.roll, &self.acc_roll),
+            ("pitch", &self.pitch, &self.acc_pitch),
+        ] {
+            writeln!(
+                writer,
+                "{}: mean {:.3}°, std dev {:.3}°, min {:.3}°, max {:.3}°, mean accuracy {:.3}°",
+                name,
+                stats.mean(),
+                stats.std_dev(),
+                stats.min,
+                stats.max,
+                acc.mean()
+            )?;
+        }
+        writeln!(
+            writer,
+            "heading: circular mean {:.3}°, circular std dev {:.3}°, mean resultant length {:.4}, mean accuracy {:.3}°",
+            self.heading.mean(),
+            self.heading.std_dev(),
+            self.heading.resultant_length(),
+            self.acc_heading.mean()
+        )
+    }
+}
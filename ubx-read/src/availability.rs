@@ -0,0 +1,213 @@
+//! Availability of the solutions: expected epochs present, with a 3D fix, outages.
+//!
+//! Epochs are placed on a grid of the nominal navigation rate in GPS time, derived from
+//! the NAV-PVT UTC date, so that leap seconds, iTOW rollovers or restarts do not
+//! disturb the count. Epochs without valid time cannot be placed, and are counted apart.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::{DateTime, Duration, Timelike, Utc};
+use ublox::{GpsFix, NavPvtFlags, NavPvtRef};
+
+use crate::export::{FULLY_RESOLVED, VALID_DATE, VALID_TIME};
+use crate::timebase::WeekTime;
+
+/// Slot of the epoch grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum State {
+    Missing,
+    Present,
+    Fix3D,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Count {
+    expected: usize,
+    present: usize,
+    fix_3d: usize,
+}
+
+/// Longest run of slots matching a condition
+#[derive(Debug, Default, Clone, Copy)]
+struct Run {
+    start: i64,
+    len: i64,
+}
+
+pub struct Availability {
+    /// Grid period (ns)
+    period: i64,
+    /// Slot of the first element of `slots`
+    first_slot: i64,
+    slots: Vec<State>,
+    /// GPS - UTC offset (s), labelling the hours in UTC
+    leap_seconds: i64,
+    epochs: usize,
+    undated: usize,
+}
+
+impl Availability {
+    /// Expecting `rate` solutions per second
+    pub fn new(rate: f64) -> Self {
+        Self {
+            period: (1e9 / rate) as i64,
+            first_slot: 0,
+            slots: Vec::new(),
+            leap_seconds: 0,
+            epochs: 0,
+            undated: 0,
+        }
+    }
+
+    pub fn solution(&mut self, pvt: &NavPvtRef) {
+        self.epochs += 1;
+        let dated = pvt.valid() & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+            == VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        let Some(utc) = DateTime::<Utc>::try_from(pvt).ok().filter(|_| dated) else {
+            self.undated += 1;
+            return;
+        };
+        let gps = WeekTime::from_utc(utc).gps();
+        let ns = gps.timestamp() * 1_000_000_000 + i64::from(gps.timestamp_subsec_nanos());
+        let slot = (ns + self.period / 2).div_euclid(self.period);
+
+        let state = if pvt.flags().contains(NavPvtFlags::GPS_FIX_OK)
+            && matches!(pvt.fix_type(), GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning)
+        {
+            State::Fix3D
+        } else {
+            State::Present
+        };
+
+        if self.slots.is_empty() {
+            self.first_slot = slot;
+            self.leap_seconds = (gps - utc).num_seconds();
+        } else if slot < self.first_slot {
+            // earlier than the first epoch, logs are not always in order
+            let missing = (self.first_slot - slot) as usize;
+            self.slots
+                .splice(0..0, std::iter::repeat(State::Missing).take(missing));
+            self.first_slot = slot;
+        }
+        let index = (slot - self.first_slot) as usize;
+        if index >= self.slots.len() {
+            self.slots.resize(index + 1, State::Missing);
+        }
+        self.slots[index] = self.slots[index].max(state);
+    }
+
+    /// UTC time of a slot
+    fn utc(&self, slot: i64) -> DateTime<Utc> {
+        let ns = slot * self.period - self.leap_seconds * 1_000_000_000;
+        DateTime::<Utc>::from_naive_utc_and_offset(Default::default(), Utc)
+            + Duration::nanoseconds(ns)
+    }
+
+    /// Longest run of slots below a state
+    fn longest_run(&self, below: State) -> Run {
+        let mut longest = Run::default();
+        let mut current = Run::default();
+        for (index, state) in self.slots.iter().enumerate() {
+            if *state < below {
+                if current.len == 0 {
+                    current.start = self.first_slot + index as i64;
+                }
+                current.len += 1;
+                if current.len > longest.len {
+                    longest = current;
+                }
+            } else {
+                current.len = 0;
+            }
+        }
+        longest
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let period = self.period as f64 * 1e-9;
+        if self.slots.is_empty() {
+            return writeln!(
+                writer,
+                "{} NAV-PVT epochs, none with valid time to place on the {:.3} s grid",
+                self.epochs, period
+            );
+        }
+        let last_slot = self.first_slot + self.slots.len() as i64 - 1;
+        let format = |time: DateTime<Utc>| time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        writeln!(
+            writer,
+            "{} NAV-PVT epochs, {} without valid time",
+            self.epochs, self.undated
+        )?;
+        writeln!(
+            writer,
+            "{} expected epochs every {:.3} s, from {} to {}",
+            self.slots.len(),
+            period,
+            format(self.utc(self.first_slot)),
+            format(self.utc(last_slot))
+        )?;
+
+        let mut total = Count::default();
+        let mut hours = BTreeMap::<DateTime<Utc>, Count>::new();
+        for (index, state) in self.slots.iter().enumerate() {
+            let utc = self.utc(self.first_slot + index as i64);
+            let hour = utc
+                .with_minute(0)
+                .and_then(|t| t.with_second(0))
+                .and_then(|t| t.with_nanosecond(0))
+                .unwrap_or(utc);
+            for count in [&mut total, hours.entry(hour).or_default()] {
+                count.expected += 1;
+                count.present += usize::from(*state >= State::Present);
+                count.fix_3d += usize::from(*state == State::Fix3D);
+            }
+        }
+        let share = |n: usize, count: &Count| 100.0 * n as f64 / count.expected as f64;
+        writeln!(
+            writer,
+            "present: {} ({:.2}%), with a 3D fix: {} ({:.2}%)",
+            total.present,
+            share(total.present, &total),
+            total.fix_3d,
+            share(total.fix_3d, &total)
+        )?;
+        for (label, run) in [
+            ("longest outage", self.longest_run(State::Present)),
+            (
+                "longest time without a 3D fix",
+                self.longest_run(State::Fix3D),
+            ),
+        ] {
+            if run.len == 0 {
+                writeln!(writer, "{}: none", label)?;
+            } else {
+                writeln!(
+                    writer,
+                    "{}: {:.1} s from {}",
+                    label,
+                    run.len as f64 * period,
+                    format(self.utc(run.start))
+                )?;
+            }
+        }
+
+        writeln!(
+            writer,
+            "{:<18} {:>9} {:>9} {:>9}",
+            "hour (UTC)", "expected", "present", "3D fix"
+        )?;
+        for (hour, count) in &hours {
+            writeln!(
+                writer,
+                "{:<18} {:>9} {:>8.2}% {:>8.2}%",
+                hour.format("%Y-%m-%dT%H:%MZ").to_string(),
+                count.expected,
+                share(count.present, count),
+                share(count.fix_3d, count)
+            )?;
+        }
+        Ok(())
+    }
+}
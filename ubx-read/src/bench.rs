@@ -0,0 +1,412 @@
+//! Parse throughput benchmark, and generation of synthetic recordings to run it on.
+//!
+//! The file is read fully through each code path, packets being parsed or frames split
+//! but nothing formatted: the frame splitting scanner of `--summary` and `repair`, and the
+//! `Parser::consume` path of every other mode, each of them reading the file or, on Unix
+//! and for uncompressed files, a memory mapping of it. Throughputs are the median of the
+//! iterations.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::time::{Duration, Instant};
+
+use ublox::Parser;
+use ubx_common::frame::{crc24q, frame};
+
+use crate::scan::{Kind, StreamScanner};
+
+/// Size of the reads, same as the other modes
+const READ_SIZE: usize = 2048;
+
+/// Messages of the synthetic recordings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Message {
+    NavPvt,
+    NavSat,
+    NavAtt,
+    Gga,
+    Rtcm,
+}
+
+/// Messages of each synthetic epoch, with their count
+#[derive(Debug, Clone)]
+pub struct Mix(Vec<(Message, usize)>);
+
+impl Mix {
+    /// Parses a comma separated list of messages like "pvt:2,sat,nmea"
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut mix = Vec::new();
+        for item in list.split(',') {
+            let (name, count) = match item.split_once(':') {
+                Some((name, count)) => {
+                    let count = count
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid count \"{}\"", count))?;
+                    (name, count)
+                },
+                None => (item, 1),
+            };
+            let message = match name.trim() {
+                "pvt" => Message::NavPvt,
+                "sat" => Message::NavSat,
+                "att" => Message::NavAtt,
+                "nmea" => Message::Gga,
+                "rtcm" => Message::Rtcm,
+                name => {
+                    return Err(format!(
+                        "unknown message \"{}\", expecting pvt, sat, att, nmea or rtcm",
+                        name
+                    ))
+                },
+            };
+            mix.push((message, count));
+        }
+        if mix.iter().all(|(_, count)| *count == 0) {
+            return Err("empty message mix".to_string());
+        }
+        Ok(Self(mix))
+    }
+}
+
+fn nav_pvt(itow: u32) -> Vec<u8> {
+    let mut payload = [0_u8; 92];
+    payload[0..4].copy_from_slice(&itow.to_le_bytes());
+    payload[4..6].copy_from_slice(&2024_u16.to_le_bytes());
+    payload[6] = 1;
+    payload[7] = 1;
+    payload[8] = (itow / 3_600_000 % 24) as u8;
+    payload[9] = (itow / 60_000 % 60) as u8;
+    payload[10] = (itow / 1000 % 60) as u8;
+    // validDate, validTime, fullyResolved, validMag
+    payload[11] = 0x0f;
+    // 3D fix, gnssFixOK
+    payload[20] = 3;
+    payload[21] = 0x01;
+    payload[23] = 20;
+    payload[24..28].copy_from_slice(&23_500_000_i32.to_le_bytes());
+    payload[28..32].copy_from_slice(&468_000_000_i32.to_le_bytes());
+    payload[32..36].copy_from_slice(&450_000_i32.to_le_bytes());
+    payload[36..40].copy_from_slice(&400_000_i32.to_le_bytes());
+    payload[40..44].copy_from_slice(&1500_u32.to_le_bytes());
+    payload[44..48].copy_from_slice(&2500_u32.to_le_bytes());
+    payload[76..78].copy_from_slice(&120_u16.to_le_bytes());
+    frame(0x01, 0x07, &payload)
+}
+
+fn nav_sat(itow: u32) -> Vec<u8> {
+    const SATELLITES: usize = 20;
+    let mut payload = vec![0_u8; 8 + 12 * SATELLITES];
+    payload[0..4].copy_from_slice(&itow.to_le_bytes());
+    payload[4] = 1;
+    payload[5] = SATELLITES as u8;
+    for (i, sv) in payload[8..].chunks_mut(12).enumerate() {
+        sv[0] = (i % 3 * 2) as u8;
+        sv[1] = i as u8 + 1;
+        sv[2] = 30 + (i % 15) as u8;
+        sv[3] = 10 + (i * 4) as u8;
+        sv[4..6].copy_from_slice(&((i * 17 % 360) as i16).to_le_bytes());
+        // svUsed, quality: code and carrier locked
+        sv[8..12].copy_from_slice(&0x0000_000f_u32.to_le_bytes());
+    }
+    frame(0x01, 0x35, &payload)
+}
+
+fn nav_att(itow: u32) -> Vec<u8> {
+    let mut payload = [0_u8; 32];
+    payload[0..4].copy_from_slice(&itow.to_le_bytes());
+    payload[8..12].copy_from_slice(&150_000_i32.to_le_bytes());
+    payload[12..16].copy_from_slice(&(-80_000_i32).to_le_bytes());
+    payload[16..20].copy_from_slice(&9_000_000_i32.to_le_bytes());
+    frame(0x01, 0x05, &payload)
+}
+
+fn gga(itow: u32) -> Vec<u8> {
+    let body = format!(
+        "GPGGA,{:02}{:02}{:02}.00,4648.00000,N,00221.00000,E,1,20,0.8,450.0,M,47.0,M,,",
+        itow / 3_600_000 % 24,
+        itow / 60_000 % 60,
+        itow / 1000 % 60
+    );
+    let checksum = body.bytes().fold(0, |acc, byte| acc ^ byte);
+    format!("${}*{:02X}\r\n", body, checksum).into_bytes()
+}
+
+/// RTCM3 1005, stationary antenna reference point
+fn rtcm() -> Vec<u8> {
+    let mut frame = vec![0xd3, 0x00, 19];
+    let mut payload = [0_u8; 19];
+    payload[0] = (1005 >> 4) as u8;
+    payload[1] = ((1005 & 0x0f) << 4) as u8;
+    frame.extend_from_slice(&payload);
+    let crc = crc24q(&frame);
+    frame.extend_from_slice(&crc.to_be_bytes()[1..]);
+    frame
+}
+
+/// Writes a synthetic recording of about `size` bytes, one epoch of the mix per second
+pub fn generate(path: &str, size: u64, mix: &Mix) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    let mut itow = 0_u32;
+    while written < size {
+        for (message, count) in &mix.0 {
+            for _ in 0..*count {
+                let frame = match message {
+                    Message::NavPvt => nav_pvt(itow),
+                    Message::NavSat => nav_sat(itow),
+                    Message::NavAtt => nav_att(itow),
+                    Message::Gga => gga(itow),
+                    Message::Rtcm => rtcm(),
+                };
+                writer.write_all(&frame)?;
+                written += frame.len() as u64;
+            }
+        }
+        itow = (itow + 1000) % 604_800_000;
+    }
+    writer.flush()
+}
+
+/// Bytes and frames seen by one pass over the file
+#[derive(Debug, Default, Clone, Copy)]
+struct Pass {
+    bytes: usize,
+    frames: usize,
+}
+
+fn parse_stream<R: Read>(mut reader: R) -> std::io::Result<Pass> {
+    let mut buf = [0; READ_SIZE];
+    let mut parser = Parser::default();
+    let mut pass = Pass::default();
+    loop {
+        let size = reader.read(&mut buf)?;
+        if size == 0 {
+            return Ok(pass);
+        }
+        pass.bytes += size;
+        pass.frames += parse(&mut parser, &buf[..size]);
+    }
+}
+
+fn scan_stream<R: Read>(mut reader: R) -> std::io::Result<Pass> {
+    let mut buf = [0; READ_SIZE];
+    let mut scanner = StreamScanner::default();
+    let mut pass = Pass::default();
+    loop {
+        let size = reader.read(&mut buf)?;
+        pass.bytes += size;
+        pass.frames += scan(&mut scanner, &buf[..size], size == 0);
+        if size == 0 {
+            return Ok(pass);
+        }
+    }
+}
+
+/// Packets parsed out of the data
+fn parse(parser: &mut Parser<Vec<u8>>, data: &[u8]) -> usize {
+    let mut frames = 0;
+    let mut it = parser.consume(data);
+    while let Some(packet) = it.next() {
+        if packet.is_ok() {
+            frames += 1;
+        }
+    }
+    frames
+}
+
+/// Valid frames split out of the data
+fn scan(scanner: &mut StreamScanner, data: &[u8], eof: bool) -> usize {
+    scanner.push(data);
+    let mut frames = 0;
+    while let Some((kind, _)) = scanner.next_frame(eof) {
+        if kind != Kind::Unknown {
+            frames += 1;
+        }
+    }
+    frames
+}
+
+/// Read only memory mapping of a file
+#[cfg(unix)]
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn open(path: &str) -> std::io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "empty file",
+            ));
+        }
+        // SAFETY: maps the whole file read only, the mapping outliving the descriptor
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn data(&self) -> &[u8] {
+        // SAFETY: the mapping is valid and readable for `len` bytes until dropped
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmaps the mapping created in `open`
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn parse_mapped(path: &str) -> std::io::Result<Pass> {
+    let mapping = Mapping::open(path)?;
+    let mut parser = Parser::default();
+    let frames = parse(&mut parser, mapping.data());
+    Ok(Pass {
+        bytes: mapping.len,
+        frames,
+    })
+}
+
+#[cfg(unix)]
+fn scan_mapped(path: &str) -> std::io::Result<Pass> {
+    let mapping = Mapping::open(path)?;
+    let mut scanner = StreamScanner::default();
+    let mut frames = 0;
+    let mut chunks = mapping.data().chunks(READ_SIZE).peekable();
+    while let Some(chunk) = chunks.next() {
+        frames += scan(&mut scanner, chunk, chunks.peek().is_none());
+    }
+    Ok(Pass {
+        bytes: mapping.len,
+        frames,
+    })
+}
+
+/// Median throughput of one code path
+pub struct Measure {
+    pub path: &'static str,
+    pub input: &'static str,
+    pub bytes: usize,
+    pub frames: usize,
+    pub mb_per_s: f64,
+    pub frames_per_s: f64,
+}
+
+fn measure<F: FnMut() -> std::io::Result<Pass>>(
+    path: &'static str,
+    input: &'static str,
+    iterations: usize,
+    mut run: F,
+) -> std::io::Result<Measure> {
+    let mut durations = Vec::with_capacity(iterations);
+    let mut pass = Pass::default();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        pass = run()?;
+        durations.push(start.elapsed());
+    }
+    durations.sort();
+    let median = durations[durations.len() / 2].max(Duration::from_nanos(1));
+    let seconds = median.as_secs_f64();
+    Ok(Measure {
+        path,
+        input,
+        bytes: pass.bytes,
+        frames: pass.frames,
+        mb_per_s: pass.bytes as f64 / 1e6 / seconds,
+        frames_per_s: pass.frames as f64 / seconds,
+    })
+}
+
+/// Runs every code path over the file, `open` opening it like the other modes
+pub fn run<R: Read, F: Fn() -> R>(
+    path: &str,
+    iterations: usize,
+    open: F,
+) -> std::io::Result<Vec<Measure>> {
+    let iterations = iterations.max(1);
+    let mut results = vec![
+        measure("parser", "read", iterations, || parse_stream(open()))?,
+        measure("scanner", "read", iterations, || scan_stream(open()))?,
+    ];
+    #[cfg(unix)]
+    if !path.ends_with(".gz") && !path.ends_with(".zst") {
+        results.push(measure("parser", "mmap", iterations, || {
+            parse_mapped(path)
+        })?);
+        results.push(measure("scanner", "mmap", iterations, || {
+            scan_mapped(path)
+        })?);
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(results)
+}
+
+pub fn write_table<W: Write>(writer: &mut W, results: &[Measure]) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "{:<8} {:<5} {:>10} {:>12} {:>10}",
+        "path", "input", "MB/s", "frames/s", "frames"
+    )?;
+    for result in results {
+        writeln!(
+            writer,
+            "{:<8} {:<5} {:>10.1} {:>12.0} {:>10}",
+            result.path, result.input, result.mb_per_s, result.frames_per_s, result.frames
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_json<W: Write>(
+    writer: &mut W,
+    file: &str,
+    iterations: usize,
+    results: &[Measure],
+) -> std::io::Result<()> {
+    let results = results
+        .iter()
+        .map(|result| {
+            format!(
+                "{{\"path\":\"{}\",\"input\":\"{}\",\"bytes\":{},\"frames\":{},\
+\"mb_per_s\":{:.3},\"frames_per_s\":{:.1}}}",
+                result.path,
+                result.input,
+                result.bytes,
+                result.frames,
+                result.mb_per_s,
+                result.frames_per_s
+            )
+        })
+        .collect::<Vec<_>>();
+    writeln!(
+        writer,
+        "{{\"file\":\"{}\",\"iterations\":{},\"results\":[{}]}}",
+        file.replace('\\', "\\\\").replace('"', "\\\""),
+        iterations,
+        results.join(",")
+    )
+}
@@ -0,0 +1,305 @@
+//! Time aligned comparison of the NAV-PVT solutions of two receivers
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use ublox::{GpsFix, NavPvtFlags, NavPvtRef, NavSatRef};
+
+use crate::export::{FULLY_RESOLVED, VALID_DATE, VALID_TIME};
+
+/// Earth radius, for the local position differences (m)
+const EARTH_RADIUS: f64 = 6_378_137.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Epoch {
+    time: DateTime<Utc>,
+    itow: u32,
+    gnss_fix_ok: bool,
+    fix_type: GpsFix,
+    num_sv: u8,
+    lat: f64,
+    lon: f64,
+    height: f64,
+    /// Mean CN0 of the tracked satellites, from the NAV-SAT of the epoch (dBHz)
+    cn0: Option<f64>,
+}
+
+/// Solutions of a log, dated by their UTC time
+#[derive(Debug, Default)]
+pub struct Log {
+    epochs: Vec<Epoch>,
+    /// Solutions without valid and fully resolved time, which cannot be aligned
+    undated: usize,
+    /// Mean CN0 per NAV-SAT iTOW
+    cn0: HashMap<u32, f64>,
+}
+
+impl Log {
+    pub fn solution(&mut self, pvt: &NavPvtRef) {
+        let dated = pvt.valid() & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+            == VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        let time = DateTime::<Utc>::try_from(pvt).ok().filter(|_| dated);
+        let Some(time) = time else {
+            self.undated += 1;
+            return;
+        };
+        self.epochs.push(Epoch {
+            time,
+            itow: pvt.itow(),
+            gnss_fix_ok: pvt.flags().contains(NavPvtFlags::GPS_FIX_OK),
+            fix_type: pvt.fix_type(),
+            num_sv: pvt.num_satellites(),
+            lat: pvt.lat_degrees(),
+            lon: pvt.lon_degrees(),
+            height: pvt.height_meters(),
+            cn0: None,
+        });
+    }
+
+    pub fn satellites(&mut self, sat: &NavSatRef) {
+        let cn0 = sat
+            .svs()
+            .filter(|sv| sv.cno() > 0)
+            .map(|sv| f64::from(sv.cno()))
+            .collect::<Vec<_>>();
+        if !cn0.is_empty() {
+            self.cn0
+                .insert(sat.itow(), cn0.iter().sum::<f64>() / cn0.len() as f64);
+        }
+    }
+
+    /// Sorts the epochs and attaches the CN0 of their NAV-SAT
+    fn finish(&mut self) {
+        for epoch in &mut self.epochs {
+            epoch.cn0 = self.cn0.get(&epoch.itow).copied();
+        }
+        self.epochs.sort_by_key(|epoch| epoch.time);
+    }
+}
+
+/// Epochs of both logs matched in time
+struct Match {
+    a: Epoch,
+    b: Epoch,
+    /// North, east and up differences B - A, when both have a valid fix (m)
+    position: Option<(f64, f64, f64)>,
+}
+
+impl Match {
+    fn new(a: Epoch, b: Epoch) -> Self {
+        let position = (a.gnss_fix_ok && b.gnss_fix_ok).then(|| {
+            (
+                (b.lat - a.lat).to_radians() * EARTH_RADIUS,
+                (b.lon - a.lon).to_radians() * EARTH_RADIUS * a.lat.to_radians().cos(),
+                b.height - a.height,
+            )
+        });
+        Self { a, b, position }
+    }
+
+    fn horizontal(&self) -> Option<f64> {
+        self.position.map(|(north, east, _)| north.hypot(east))
+    }
+}
+
+pub struct Comparison {
+    matches: Vec<Match>,
+    epochs: (usize, usize),
+    undated: (usize, usize),
+    unmatched: (usize, usize),
+}
+
+impl Comparison {
+    /// Matches each epoch of A with the nearest epoch of B, within `tolerance` seconds,
+    /// every epoch being matched once at most
+    pub fn new(mut a: Log, mut b: Log, tolerance: f64) -> Self {
+        a.finish();
+        b.finish();
+        let tolerance = (tolerance * 1e9) as i64;
+        let mut used = vec![false; b.epochs.len()];
+        let mut matches = Vec::new();
+        for epoch in &a.epochs {
+            let index = b.epochs.partition_point(|e| e.time < epoch.time);
+            // nearest of the neighbours not matched yet
+            let nearest = [index.checked_sub(1), Some(index)]
+                .into_iter()
+                .flatten()
+                .filter(|i| *i < b.epochs.len() && !used[*i])
+                .min_by_key(|i| offset(epoch, &b.epochs[*i]).abs());
+            if let Some(i) = nearest {
+                if offset(epoch, &b.epochs[i]).abs() <= tolerance {
+                    used[i] = true;
+                    matches.push(Match::new(*epoch, b.epochs[i]));
+                }
+            }
+        }
+        Self {
+            unmatched: (
+                a.epochs.len() - matches.len(),
+                b.epochs.len() - matches.len(),
+            ),
+            epochs: (a.epochs.len() + a.undated, b.epochs.len() + b.undated),
+            undated: (a.undated, b.undated),
+            matches,
+        }
+    }
+
+    /// One CSV row per matched epoch
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "utc,itow_a,itow_b,dt,d_north,d_east,d_up,d_horizontal,fix_a,fix_b,num_sv_a,num_sv_b,cn0_a,cn0_b"
+        )?;
+        let optional = |value: Option<f64>, precision: usize| {
+            value
+                .map(|v| format!("{:.*}", precision, v))
+                .unwrap_or_default()
+        };
+        for m in &self.matches {
+            writeln!(
+                writer,
+                "{},{},{},{:.3},{},{},{},{},{:?},{:?},{},{},{},{}",
+                m.a.time.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                m.a.itow,
+                m.b.itow,
+                offset(&m.a, &m.b) as f64 * 1e-9,
+                optional(m.position.map(|p| p.0), 3),
+                optional(m.position.map(|p| p.1), 3),
+                optional(m.position.map(|p| p.2), 3),
+                optional(m.horizontal(), 3),
+                m.a.fix_type,
+                m.b.fix_type,
+                m.a.num_sv,
+                m.b.num_sv,
+                optional(m.a.cn0, 1),
+                optional(m.b.cn0, 1),
+            )?;
+        }
+        writer.flush()
+    }
+
+    pub fn write_summary<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for (name, epochs, undated) in [
+            ("A", self.epochs.0, self.undated.0),
+            ("B", self.epochs.1, self.undated.1),
+        ] {
+            writeln!(
+                writer,
+                "{}: {} epochs, {} without valid time",
+                name, epochs, undated
+            )?;
+        }
+        writeln!(
+            writer,
+            "matched: {} epochs, unmatched: {} of A, {} of B",
+            self.matches.len(),
+            self.unmatched.0,
+            self.unmatched.1
+        )?;
+        if self.matches.is_empty() {
+            return Ok(());
+        }
+
+        let offsets = self
+            .matches
+            .iter()
+            .map(|m| offset(&m.a, &m.b) as f64 * 1e-9)
+            .collect::<Vec<_>>();
+        writeln!(
+            writer,
+            "time offset B - A (s): median {:.3}",
+            percentile(offsets, 0.5)
+        )?;
+
+        let horizontal = self
+            .matches
+            .iter()
+            .filter_map(Match::horizontal)
+            .collect::<Vec<_>>();
+        let up = self
+            .matches
+            .iter()
+            .filter_map(|m| m.position.map(|p| p.2))
+            .collect::<Vec<_>>();
+        if horizontal.is_empty() {
+            writeln!(writer, "position: no epoch with both fixes valid")?;
+        } else {
+            writeln!(
+                writer,
+                "horizontal difference (m): mean {:.3}, median {:.3}, 95% {:.3}, max {:.3} ({} epochs with both fixes valid)",
+                mean(&horizontal),
+                percentile(horizontal.clone(), 0.5),
+                percentile(horizontal.clone(), 0.95),
+                horizontal.iter().copied().fold(0.0, f64::max),
+                horizontal.len()
+            )?;
+            let up_mean = mean(&up);
+            let up_std =
+                (up.iter().map(|v| (v - up_mean).powi(2)).sum::<f64>() / up.len() as f64).sqrt();
+            writeln!(
+                writer,
+                "vertical difference B - A (m): mean {:.3}, std {:.3}, 95% {:.3} absolute",
+                up_mean,
+                up_std,
+                percentile(up.iter().map(|v| v.abs()).collect(), 0.95)
+            )?;
+        }
+
+        let agreeing = self
+            .matches
+            .iter()
+            .filter(|m| m.a.fix_type == m.b.fix_type && m.a.gnss_fix_ok == m.b.gnss_fix_ok)
+            .count();
+        writeln!(
+            writer,
+            "fix agreement: {:.1}% of the matched epochs",
+            100.0 * agreeing as f64 / self.matches.len() as f64
+        )?;
+
+        let num_sv = self
+            .matches
+            .iter()
+            .map(|m| f64::from(m.b.num_sv) - f64::from(m.a.num_sv))
+            .collect::<Vec<_>>();
+        writeln!(
+            writer,
+            "numSV difference B - A: mean {:+.2}, min {:+}, max {:+}",
+            mean(&num_sv),
+            num_sv.iter().copied().fold(f64::INFINITY, f64::min),
+            num_sv.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+        )?;
+
+        let cn0 = self
+            .matches
+            .iter()
+            .filter_map(|m| Some(m.b.cn0? - m.a.cn0?))
+            .collect::<Vec<_>>();
+        if cn0.is_empty() {
+            writeln!(writer, "CN0 difference: no NAV-SAT in both logs")
+        } else {
+            writeln!(
+                writer,
+                "CN0 difference B - A (dBHz, mean of the tracked satellites): mean {:+.2} ({} epochs)",
+                mean(&cn0),
+                cn0.len()
+            )
+        }
+    }
+}
+
+/// Time of B - time of A (ns)
+fn offset(a: &Epoch, b: &Epoch) -> i64 {
+    (b.time - a.time).num_nanoseconds().unwrap_or(i64::MAX)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Nearest rank percentile of non empty values
+fn percentile(mut values: Vec<f64>, p: f64) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let rank = ((p * values.len() as f64).ceil() as usize).clamp(1, values.len());
+    values[rank - 1]
+}
@@ -0,0 +1,117 @@
+//! Timestamped recordings, written by `ubx-record --timestamps`.
+//!
+//! The file starts with the 8 bytes magic `UBXTS`, version 1, two reserved bytes, followed
+//! by one record per frame: its reception time, monotonic since the start of the recording
+//! (u64) and UTC since the Unix epoch (i64), both in nanoseconds, its length (u32), all
+//! little endian, then the frame itself. The records are read as the raw stream of frames,
+//! the reception time of the frame being read being available besides.
+
+use std::io::{BufRead, Read};
+
+pub const MAGIC: [u8; 8] = *b"UBXTS\x01\x00\x00";
+
+/// Reception time, in nanoseconds
+#[derive(Debug, Clone, Copy)]
+pub struct Reception {
+    /// Since the start of the recording
+    pub monotonic: u64,
+    /// Since the Unix epoch
+    pub utc: i64,
+}
+
+impl Reception {
+    /// UTC time, like "2024-05-03T14:02:11.482913000Z"
+    pub fn utc(&self) -> String {
+        chrono::DateTime::from_timestamp_nanos(self.utc)
+            .format("%Y-%m-%dT%H:%M:%S%.9fZ")
+            .to_string()
+    }
+}
+
+const HEADER_LEN: usize = 20;
+
+/// Frames of a timestamped recording, read a record at a time
+pub struct Records<R: BufRead> {
+    input: R,
+    /// Bytes of the current frame not read yet
+    remaining: usize,
+    reception: Option<Reception>,
+}
+
+impl<R: BufRead> Records<R> {
+    /// Reads the records following the magic, already checked
+    pub fn new(mut input: R) -> Self {
+        input.consume(MAGIC.len());
+        Self {
+            input,
+            remaining: 0,
+            reception: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// Reception time of the frame being read
+    pub fn reception(&self) -> Option<Reception> {
+        self.reception
+    }
+
+    /// Reads the next record header, false at the end of the recording
+    fn next_record(&mut self) -> std::io::Result<bool> {
+        let mut header = [0; HEADER_LEN];
+        let mut read = 0;
+        while read < HEADER_LEN {
+            match self.input.read(&mut header[read..])? {
+                0 if read == 0 => return Ok(false),
+                0 => {
+                    warn!("truncated record header at the end of the recording");
+                    return Ok(false);
+                },
+                size => read += size,
+            }
+        }
+        self.reception = Some(Reception {
+            monotonic: u64::from_le_bytes(header[0..8].try_into().unwrap()),
+            utc: i64::from_le_bytes(header[8..16].try_into().unwrap()),
+        });
+        self.remaining = u32::from_le_bytes(header[16..20].try_into().unwrap()) as usize;
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for Records<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let data = self.fill_buf()?;
+        let size = data.len().min(buf.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        self.consume(size);
+        Ok(size)
+    }
+}
+
+impl<R: BufRead> BufRead for Records<R> {
+    /// Data of the current frame only, so that the frame of a read is known
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        // skips empty records
+        while self.remaining == 0 {
+            if !self.next_record()? {
+                return Ok(&[]);
+            }
+        }
+        let data = self.input.fill_buf()?;
+        if data.is_empty() {
+            warn!("truncated frame at the end of the recording");
+            self.remaining = 0;
+            return Ok(&[]);
+        }
+        let size = data.len().min(self.remaining);
+        Ok(&data[..size])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.input.consume(amt);
+        self.remaining -= amt;
+    }
+}
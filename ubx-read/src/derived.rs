@@ -0,0 +1,243 @@
+//! Speed and acceleration derived from position differences, for logs without
+//! (or besides) the Doppler based velocity of the receiver.
+//!
+//! Each value is a backward difference over the actual spacing of the epochs,
+//! optionally smoothed with a trailing moving average. Gaps (and invalid positions)
+//! restart the derivation, nothing being derived across them.
+
+use std::collections::VecDeque;
+
+use crate::geodesy::local_offset;
+
+/// iTOW rollover (ms)
+const MS_PER_WEEK: u32 = 7 * 86_400 * 1000;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    itow: u32,
+    lat: f64,
+    lon: f64,
+    height: f64,
+}
+
+/// Values derived at an epoch
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Derived {
+    /// Horizontal speed (m/s)
+    pub speed: Option<f64>,
+    /// Vertical speed, upwards (m/s)
+    pub vertical_speed: Option<f64>,
+    /// Horizontal acceleration (m/s²)
+    pub acceleration: Option<f64>,
+}
+
+/// Trailing moving average
+struct Average {
+    window: usize,
+    values: VecDeque<f64>,
+}
+
+impl Average {
+    fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            values: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, value: f64) -> f64 {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+        self.values.iter().sum::<f64>() / self.values.len() as f64
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+pub struct Derivation {
+    /// Largest spacing of epochs derived from (ms)
+    max_gap_ms: u32,
+    last: Option<Sample>,
+    /// Last raw horizontal speed, and the first epoch of its interval
+    last_speed: Option<(u32, f64)>,
+    speed: Average,
+    vertical_speed: Average,
+    acceleration: Average,
+}
+
+impl Derivation {
+    /// Smoothing over `window` epochs (1: none), nothing derived over more than `max_gap` seconds
+    pub fn new(window: usize, max_gap: f64) -> Self {
+        Self {
+            max_gap_ms: (max_gap * 1000.0) as u32,
+            last: None,
+            last_speed: None,
+            speed: Average::new(window),
+            vertical_speed: Average::new(window),
+            acceleration: Average::new(window),
+        }
+    }
+
+    /// Derives the values at an epoch, its position being valid or not
+    pub fn update(&mut self, itow: u32, position: Option<(f64, f64, f64)>) -> Derived {
+        let Some((lat, lon, height)) = position else {
+            self.restart(None);
+            return Derived::default();
+        };
+        let sample = Sample {
+            itow,
+            lat,
+            lon,
+            height,
+        };
+        let Some(last) = self.last.filter(|last| {
+            let dt = spacing(last.itow, itow);
+            dt > 0 && dt <= self.max_gap_ms
+        }) else {
+            self.restart(Some(sample));
+            return Derived::default();
+        };
+        self.last = Some(sample);
+
+        let dt = f64::from(spacing(last.itow, itow)) / 1000.0;
+        let (north, east) = local_offset(last.lat, last.lon, lat, lon);
+        let speed = north.hypot(east) / dt;
+        let vertical_speed = (height - last.height) / dt;
+        let acceleration = self.last_speed.map(|(start, last_speed)| {
+            // speeds are means over their interval, which middles are
+            // half the spacing of the first and last epochs apart
+            let dt = f64::from(spacing(start, itow)) / 2000.0;
+            (speed - last_speed) / dt
+        });
+        self.last_speed = Some((last.itow, speed));
+
+        Derived {
+            speed: Some(self.speed.push(speed)),
+            vertical_speed: Some(self.vertical_speed.push(vertical_speed)),
+            acceleration: acceleration.map(|a| self.acceleration.push(a)),
+        }
+    }
+
+    fn restart(&mut self, sample: Option<Sample>) {
+        self.last = sample;
+        self.last_speed = None;
+        self.speed.clear();
+        self.vertical_speed.clear();
+        self.acceleration.clear();
+    }
+}
+
+/// Time from one iTOW to the next, across week rollovers (ms)
+fn spacing(from: u32, to: u32) -> u32 {
+    (to + MS_PER_WEEK - from % MS_PER_WEEK) % MS_PER_WEEK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions of a vehicle starting at 47°N 8°E, moving `north` and `east` (m) in each
+    /// epoch of `spacing` ms and `up` (m/s) vertically, from iTOW `start`
+    fn track(
+        start: u32,
+        spacing: u32,
+        moves: impl Iterator<Item = (f64, f64)>,
+        up: f64,
+    ) -> Vec<(u32, (f64, f64, f64))> {
+        let (mut lat, mut lon, mut height) = (47.0, 8.0, 500.0);
+        let mut epochs = vec![(start, (lat, lon, height))];
+        for (index, (north, east)) in moves.enumerate() {
+            // metres per degree at the current position
+            let (per_lat, _) = local_offset(lat, lon, lat + 1e-4, lon);
+            let (_, per_lon) = local_offset(lat, lon, lat, lon + 1e-4);
+            lat += north / per_lat * 1e-4;
+            lon += east / per_lon * 1e-4;
+            height += up * f64::from(spacing) / 1000.0;
+            let itow = (start + (index as u32 + 1) * spacing) % MS_PER_WEEK;
+            epochs.push((itow, (lat, lon, height)));
+        }
+        epochs
+    }
+
+    fn derive(derivation: &mut Derivation, epochs: &[(u32, (f64, f64, f64))]) -> Vec<Derived> {
+        epochs
+            .iter()
+            .map(|(itow, position)| derivation.update(*itow, Some(*position)))
+            .collect()
+    }
+
+    fn assert_close(value: Option<f64>, expected: f64) {
+        let value = value.expect("derived value");
+        assert!((value - expected).abs() < 1e-3, "{} instead of {}", value, expected);
+    }
+
+    #[test]
+    fn constant_velocity() {
+        // 5 m/s north, 10 m/s east, climbing 1 m/s, at 1 Hz
+        let epochs = track(100_000, 1000, std::iter::repeat((5.0, 10.0)).take(20), 1.0);
+        let derived = derive(&mut Derivation::new(1, 2.0), &epochs);
+        assert!(derived[0].speed.is_none());
+        assert!(derived[1].acceleration.is_none());
+        for epoch in &derived[1..] {
+            assert_close(epoch.speed, 125_f64.sqrt());
+            assert_close(epoch.vertical_speed, 1.0);
+        }
+        for epoch in &derived[2..] {
+            assert_close(epoch.acceleration, 0.0);
+        }
+    }
+
+    #[test]
+    fn constant_acceleration() {
+        // 2 m/s² east at 5 Hz: the speeds are means over their 200 ms interval
+        let epochs = track(0, 200, (0..20).map(|i| (0.0, 0.04 * f64::from(2 * i + 1))), 0.0);
+        let derived = derive(&mut Derivation::new(1, 1.0), &epochs);
+        for (index, epoch) in derived.iter().enumerate().skip(1) {
+            assert_close(epoch.speed, 0.4 * (index as f64 - 0.5));
+        }
+        for epoch in &derived[2..] {
+            assert_close(epoch.acceleration, 2.0);
+        }
+    }
+
+    #[test]
+    fn smoothing() {
+        // alternating 8 and 12 m/s, averaged over 2 epochs
+        let epochs = track(0, 1000, (0..10).map(|i| (0.0, [8.0, 12.0][i % 2])), 0.0);
+        let derived = derive(&mut Derivation::new(2, 2.0), &epochs);
+        assert_close(derived[1].speed, 8.0);
+        for epoch in &derived[2..] {
+            assert_close(epoch.speed, 10.0);
+        }
+    }
+
+    #[test]
+    fn week_rollover() {
+        let epochs = track(MS_PER_WEEK - 2000, 1000, std::iter::repeat((0.0, 3.0)).take(4), 0.0);
+        assert_eq!(epochs[2].0, 0);
+        let derived = derive(&mut Derivation::new(1, 2.0), &epochs);
+        for epoch in &derived[1..] {
+            assert_close(epoch.speed, 3.0);
+        }
+    }
+
+    #[test]
+    fn gaps_restart() {
+        let epochs = track(0, 1000, std::iter::repeat((0.0, 3.0)).take(6), 0.0);
+        let mut derivation = Derivation::new(1, 2.0);
+        derive(&mut derivation, &epochs[..3]);
+        // invalid position
+        assert!(derivation.update(3000, None).speed.is_none());
+        assert!(derivation.update(epochs[4].0, Some(epochs[4].1)).speed.is_none());
+        assert_close(derivation.update(epochs[5].0, Some(epochs[5].1)).speed, 3.0);
+        // longer than the largest spacing
+        let (itow, (lat, lon, height)) = epochs[6];
+        assert!(derivation.update(itow + 2500, Some((lat, lon, height))).speed.is_none());
+        // repeated epoch
+        assert!(derivation.update(itow + 2500, Some((lat, lon, height))).speed.is_none());
+    }
+}
@@ -0,0 +1,103 @@
+//! ESF-RAW sensor sample export
+
+use std::io::Write;
+
+use ublox::EsfRawRef;
+
+/// Wheel tick samples: direction bit, and tick count
+const TICKS_BACKWARD: u32 = 0x80_0000;
+const TICKS_MASK: u32 = 0x7f_ffff;
+
+/// Name, unit and scale of the sensor data types
+fn sensor(data_type: u8) -> Option<(&'static str, &'static str, f64)> {
+    let gyro = 1.0 / 4096.0;
+    let accel = 1.0 / 1024.0;
+    match data_type {
+        5 => Some(("gyro_z", "deg/s", gyro)),
+        6 => Some(("front_left_wheel_ticks", "ticks", 1.0)),
+        7 => Some(("front_right_wheel_ticks", "ticks", 1.0)),
+        8 => Some(("rear_left_wheel_ticks", "ticks", 1.0)),
+        9 => Some(("rear_right_wheel_ticks", "ticks", 1.0)),
+        10 => Some(("single_tick", "ticks", 1.0)),
+        11 => Some(("speed", "m/s", 1e-3)),
+        12 => Some(("gyro_temperature", "degC", 1e-2)),
+        13 => Some(("gyro_y", "deg/s", gyro)),
+        14 => Some(("gyro_x", "deg/s", gyro)),
+        16 => Some(("accel_x", "m/s^2", accel)),
+        17 => Some(("accel_y", "m/s^2", accel)),
+        18 => Some(("accel_z", "m/s^2", accel)),
+        _ => None,
+    }
+}
+
+/// Exports one CSV row per ESF-RAW sample, messages packing several samples
+pub struct EsfRawExporter<W: Write> {
+    writer: W,
+    messages: usize,
+    samples: usize,
+    header_written: bool,
+}
+
+impl<W: Write> EsfRawExporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            messages: 0,
+            samples: 0,
+            header_written: false,
+        }
+    }
+
+    pub fn export(&mut self, raw: &EsfRawRef) -> std::io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "s_ttag,data_type,sensor,value,unit")?;
+            self.header_written = true;
+        }
+        self.messages += 1;
+        for sample in raw.data() {
+            self.samples += 1;
+            let value = match (sample.data_type, sensor(sample.data_type)) {
+                // wheel ticks: sign and magnitude
+                (6..=10, _) => {
+                    let ticks = f64::from(sample.data_field & TICKS_MASK);
+                    if sample.data_field & TICKS_BACKWARD != 0 {
+                        -ticks
+                    } else {
+                        ticks
+                    }
+                },
+                (_, Some((_, _, scale))) => f64::from(sign_extend(sample.data_field)) * scale,
+                (_, None) => f64::from(sample.data_field),
+            };
+            let (name, unit) = sensor(sample.data_type)
+                .map(|(name, unit, _)| (name, unit))
+                .unwrap_or(("unknown", ""));
+            writeln!(
+                self.writer,
+                "{},{},{},{},{}",
+                sample.sensor_time_tag, sample.data_type, name, value, unit
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the output, reports the number of samples on stderr
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        info!(
+            "{} ESF-RAW messages, {} samples",
+            self.messages, self.samples
+        );
+        Ok(())
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Sign extends 24 bit two's complement values
+fn sign_extend(value: u32) -> i32 {
+    ((value << 8) as i32) >> 8
+}
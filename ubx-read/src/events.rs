@@ -0,0 +1,331 @@
+//! State change events of the navigation solution.
+//!
+//! Each tracked state (fix, carrier solution, numSV and hAcc threshold bands)
+//! has to hold for a number of consecutive epochs before its change is
+//! reported, so short flickers are ignored. Events are dated at the first
+//! epoch of the new state.
+
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use ublox::{GpsFix, NavPvtFlags, NavPvtRef, NavTimeLsRef};
+
+use crate::container::Reception;
+use crate::export::{FLAGS3_OFFSET, FULLY_RESOLVED, INVALID_LLH, VALID_DATE, VALID_TIME};
+use crate::timebase::Timestamps;
+
+/// Event output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One line per event
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Date and position of an epoch
+#[derive(Debug, Clone)]
+struct Epoch {
+    itow: u32,
+    /// Timestamp, when the time is valid
+    time: Option<String>,
+    /// Latitude, longitude, when valid
+    position: Option<(f64, f64)>,
+    /// Reception time, from timestamped recordings
+    reception: Option<Reception>,
+}
+
+/// Debounced state
+struct Tracker<T> {
+    /// Reported state and the description of its last epoch
+    stable: Option<(T, String)>,
+    /// Differing state, its description, first epoch and number of epochs
+    candidate: Option<(T, String, Epoch, usize)>,
+}
+
+impl<T: Copy + PartialEq> Tracker<T> {
+    fn new() -> Self {
+        Self {
+            stable: None,
+            candidate: None,
+        }
+    }
+
+    /// Returns the previous state and the new one, with their descriptions,
+    /// and the first epoch of the new state, once it held for `debounce` epochs
+    fn update(
+        &mut self,
+        value: T,
+        detail: String,
+        epoch: &Epoch,
+        debounce: usize,
+    ) -> Option<(T, String, T, String, Epoch)> {
+        let Some((stable, stable_detail)) = &mut self.stable else {
+            self.stable = Some((value, detail));
+            return None;
+        };
+        if *stable == value {
+            *stable_detail = detail;
+            self.candidate = None;
+            return None;
+        }
+        match &mut self.candidate {
+            Some((candidate, candidate_detail, _, count)) if *candidate == value => {
+                *candidate_detail = detail;
+                *count += 1;
+            },
+            _ => self.candidate = Some((value, detail, epoch.clone(), 1)),
+        }
+        match self.candidate.take() {
+            Some((value, detail, first, count)) if count >= debounce => {
+                let (before, before_detail) = self.stable.replace((value, detail.clone()))?;
+                Some((before, before_detail, value, detail, first))
+            },
+            candidate => {
+                self.candidate = candidate;
+                None
+            },
+        }
+    }
+}
+
+/// Carrier phase solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Carrier {
+    None,
+    Float,
+    Fixed,
+}
+
+impl Carrier {
+    fn name(&self) -> &'static str {
+        match self {
+            Carrier::None => "none",
+            Carrier::Float => "float",
+            Carrier::Fixed => "fixed",
+        }
+    }
+}
+
+/// Scans NAV-PVT solutions, writes one event per state change
+pub struct EventLogger<W: Write> {
+    writer: W,
+    format: Format,
+    timestamps: Timestamps,
+    /// Number of consecutive epochs a new state must hold
+    debounce: usize,
+    /// Ascending numSV thresholds
+    numsv_thresholds: Vec<u8>,
+    /// Ascending hAcc thresholds (m)
+    hacc_thresholds: Vec<f64>,
+    fix: Tracker<bool>,
+    carrier: Tracker<Carrier>,
+    numsv: Tracker<usize>,
+    hacc: Tracker<usize>,
+    epochs: usize,
+    events: usize,
+    /// Reception time of the solution being processed
+    reception: Option<Reception>,
+}
+
+impl<W: Write> EventLogger<W> {
+    pub fn new(
+        writer: W,
+        format: Format,
+        timestamps: Timestamps,
+        debounce: usize,
+        mut numsv_thresholds: Vec<u8>,
+        mut hacc_thresholds: Vec<f64>,
+    ) -> Self {
+        numsv_thresholds.sort();
+        numsv_thresholds.dedup();
+        hacc_thresholds.sort_by(f64::total_cmp);
+        hacc_thresholds.dedup();
+        Self {
+            writer,
+            format,
+            timestamps,
+            debounce: debounce.max(1),
+            numsv_thresholds,
+            hacc_thresholds,
+            fix: Tracker::new(),
+            carrier: Tracker::new(),
+            numsv: Tracker::new(),
+            hacc: Tracker::new(),
+            epochs: 0,
+            events: 0,
+            reception: None,
+        }
+    }
+
+    /// Reception time of the next solutions, from timestamped recordings
+    pub fn received(&mut self, reception: Option<Reception>) {
+        self.reception = reception;
+    }
+
+    pub fn leap_seconds(&mut self, ls: &NavTimeLsRef) {
+        self.timestamps.leap_seconds(ls);
+    }
+
+    pub fn process(&mut self, pvt: &NavPvtRef) -> std::io::Result<()> {
+        let flags = pvt.flags();
+        let gnss_fix_ok = flags.contains(NavPvtFlags::GPS_FIX_OK);
+        let invalid_llh = pvt.as_bytes()[FLAGS3_OFFSET] & INVALID_LLH != 0;
+        let fix_ok = gnss_fix_ok && !matches!(pvt.fix_type(), GpsFix::NoFix | GpsFix::TimeOnlyFix);
+        let time_ok = pvt.valid() & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+            == VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+
+        let epoch = Epoch {
+            itow: pvt.itow(),
+            time: DateTime::<Utc>::try_from(pvt)
+                .ok()
+                .filter(|_| time_ok)
+                .map(|t| self.timestamps.utc(t)),
+            position: (fix_ok && !invalid_llh).then(|| (pvt.lat_degrees(), pvt.lon_degrees())),
+            reception: self.reception,
+        };
+        self.epochs += 1;
+
+        let fix_type = format!("{:?}", pvt.fix_type());
+        if let Some((_, before, gained, after, first)) =
+            self.fix.update(fix_ok, fix_type, &epoch, self.debounce)
+        {
+            let name = if gained { "fix-gained" } else { "fix-lost" };
+            self.write_event(&first, name, None, &before, &after)?;
+        }
+
+        let carrier = if !fix_ok {
+            Carrier::None
+        } else if flags.contains(NavPvtFlags::CARR_SOLN_FIXED) {
+            Carrier::Fixed
+        } else if flags.contains(NavPvtFlags::CARR_SOLN_FLOAT) {
+            Carrier::Float
+        } else {
+            Carrier::None
+        };
+        let detail = carrier.name().to_string();
+        if let Some((_, before, carrier, after, first)) =
+            self.carrier.update(carrier, detail, &epoch, self.debounce)
+        {
+            let name = match carrier {
+                Carrier::None => "rtk-lost",
+                Carrier::Float => "rtk-float",
+                Carrier::Fixed => "rtk-fixed",
+            };
+            self.write_event(&first, name, None, &before, &after)?;
+        }
+
+        if !self.numsv_thresholds.is_empty() {
+            let numsv = pvt.num_satellites();
+            let band = self
+                .numsv_thresholds
+                .iter()
+                .filter(|t| **t <= numsv)
+                .count();
+            if let Some((before_band, before, band, after, first)) =
+                self.numsv
+                    .update(band, numsv.to_string(), &epoch, self.debounce)
+            {
+                let (name, threshold) = if band > before_band {
+                    ("numsv-above", self.numsv_thresholds[band - 1])
+                } else {
+                    ("numsv-below", self.numsv_thresholds[band])
+                };
+                let threshold = threshold.to_string();
+                self.write_event(&first, name, Some(&threshold), &before, &after)?;
+            }
+        }
+
+        if !self.hacc_thresholds.is_empty() {
+            let hacc = f64::from(pvt.horiz_accuracy()) * 1e-3;
+            let band = self.hacc_thresholds.iter().filter(|t| **t <= hacc).count();
+            if let Some((before_band, before, band, after, first)) =
+                self.hacc
+                    .update(band, format!("{:.3}", hacc), &epoch, self.debounce)
+            {
+                let (name, threshold) = if band > before_band {
+                    ("hacc-above", self.hacc_thresholds[band - 1])
+                } else {
+                    ("hacc-below", self.hacc_thresholds[band])
+                };
+                let threshold = threshold.to_string();
+                self.write_event(&first, name, Some(&threshold), &before, &after)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the output, reports the number of events on stderr
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        info!("{} epochs, {} events", self.epochs, self.events);
+        Ok(())
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_event(
+        &mut self,
+        epoch: &Epoch,
+        name: &str,
+        threshold: Option<&str>,
+        before: &str,
+        after: &str,
+    ) -> std::io::Result<()> {
+        self.events += 1;
+        match self.format {
+            Format::Text => {
+                let position = match epoch.position {
+                    Some((lat, lon)) => format!("{:.9},{:.9}", lat, lon),
+                    None => "-".to_string(),
+                };
+                let name = match threshold {
+                    Some(threshold) => format!("{} {}", name, threshold),
+                    None => name.to_string(),
+                };
+                writeln!(
+                    self.writer,
+                    "{} itow={} {} {}: {} -> {}",
+                    epoch.time.as_deref().unwrap_or("-"),
+                    epoch.itow,
+                    position,
+                    name,
+                    before,
+                    after
+                )
+            },
+            Format::Json => {
+                let mut object = format!("{{\"event\":\"{}\",\"itow\":{}", name, epoch.itow);
+                let scale = self.timestamps.scale.name();
+                match &epoch.time {
+                    Some(time) => object.push_str(&format!(",\"{}\":\"{}\"", scale, time)),
+                    None => object.push_str(&format!(",\"{}\":null", scale)),
+                }
+                match epoch.position {
+                    Some((lat, lon)) => {
+                        object.push_str(&format!(",\"lat\":{:.9},\"lon\":{:.9}", lat, lon))
+                    },
+                    None => object.push_str(",\"lat\":null,\"lon\":null"),
+                }
+                if let Some(threshold) = threshold {
+                    object.push_str(&format!(",\"threshold\":{}", threshold));
+                }
+                if let Some(reception) = epoch.reception {
+                    object.push_str(&format!(
+                        ",\"rx_utc\":\"{}\",\"rx_monotonic_ns\":{}",
+                        reception.utc(),
+                        reception.monotonic
+                    ));
+                }
+                writeln!(
+                    self.writer,
+                    "{},\"before\":\"{}\",\"after\":\"{}\"}}",
+                    object, before, after
+                )
+            },
+        }
+    }
+}
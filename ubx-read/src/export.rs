@@ -0,0 +1,437 @@
+//! Navigation solution exporters
+
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use ublox::{GpsFix, NavHpPosLlhRef, NavPvtFlags, NavPvtRef, NavTimeLsRef};
+
+use crate::container::Reception;
+use crate::derived::Derivation;
+use crate::geodesy::{mgrs, Utm, UtmZone};
+use crate::heading::{HeadingFilter, HeadingOptions};
+use crate::timebase::Timestamps;
+
+/// NAV-PVT `valid` bits
+pub const VALID_DATE: u8 = 0x01;
+pub const VALID_TIME: u8 = 0x02;
+pub const FULLY_RESOLVED: u8 = 0x04;
+
+/// NAV-PVT `flags3` invalidLlh bit
+pub const INVALID_LLH: u8 = 0x01;
+
+/// Payload offset of NAV-PVT `flags3`, not decoded by the ublox crate
+pub const FLAGS3_OFFSET: usize = 78;
+
+/// Payload offset of NAV-HPPOSLLH `flags`, reserved before protocol 27.11, with the same
+/// invalidLlh bit
+const HP_FLAGS_OFFSET: usize = 3;
+
+/// High-precision position of an epoch, from NAV-HPPOSLLH
+#[derive(Debug, Clone, Copy)]
+pub struct HpPosition {
+    itow: u32,
+    lat: f64,
+    lon: f64,
+    height_msl: f64,
+}
+
+impl HpPosition {
+    /// The position with its high-precision components added, none when flagged invalid
+    pub fn from_packet(hp: &NavHpPosLlhRef) -> Option<Self> {
+        (hp.as_bytes()[HP_FLAGS_OFFSET] & INVALID_LLH == 0).then(|| Self {
+            itow: hp.itow(),
+            lat: hp.lat_degrees() + hp.lat_hp_degrees(),
+            lon: hp.lon_degrees() + hp.lon_hp_degrees(),
+            // heightHp being scaled to millimeters by the ublox crate
+            height_msl: hp.height_msl() + hp.height_hp_msl() * 1e-3,
+        })
+    }
+}
+
+/// Values of a NAV-PVT solution exported, held until the NAV-HPPOSLLH of its epoch
+/// following it
+#[derive(Debug, Clone)]
+struct Solution {
+    itow: u32,
+    time: String,
+    fix_type: GpsFix,
+    num_sv: u8,
+    position_ok: bool,
+    lat: f64,
+    lon: f64,
+    height_msl: f64,
+    ground_speed: f64,
+    heading_motion: f64,
+    heading_vehicle: Option<f64>,
+    heading_accuracy: f64,
+    reception: Option<Reception>,
+}
+
+/// Coordinate representation of exported positions
+#[derive(Debug, Clone, Copy)]
+pub enum Frame {
+    /// Latitude, longitude in decimal degrees
+    Geodetic,
+    /// UTM easting/northing, in the forced zone
+    /// or the zone of the first fix otherwise
+    Utm(Option<UtmZone>),
+    /// MGRS grid reference, with given number of digits per axis
+    Mgrs(u8),
+}
+
+/// Number of epochs excluded, per validity flag
+#[derive(Debug, Default, Clone, Copy)]
+struct Excluded {
+    invalid_llh: usize,
+    no_fix_ok: usize,
+    invalid_date: usize,
+    invalid_time: usize,
+    not_fully_resolved: usize,
+}
+
+/// Exports one CSV row per NAV-PVT solution. Unless invalid epochs are
+/// included, positions are left blank when invalidLlh is set or gnssFixOK is
+/// not, timestamps are left blank when date or time are not valid and fully resolved.
+/// The positions of the NAV-HPPOSLLH messages are preferred when present, with their
+/// high-precision components.
+pub struct PvtExporter<W: Write> {
+    writer: W,
+    frame: Frame,
+    timestamps: Timestamps,
+    /// Speeds derived from positions, exported besides the receiver ground speed
+    derivation: Option<Derivation>,
+    /// Heading of motion and of vehicle processing
+    headings: Option<(HeadingOptions, [HeadingFilter; 2])>,
+    include_invalid: bool,
+    epochs: usize,
+    excluded: Excluded,
+    /// Active UTM zone, once known
+    utm_zone: Option<UtmZone>,
+    /// Rows received before the UTM zone could be selected
+    pending: Vec<String>,
+    header_written: bool,
+    zone_crossing_warned: bool,
+    /// Reception time of the solution being exported, from timestamped recordings,
+    /// exported once the first solution has one
+    reception: Option<Reception>,
+    receptions: bool,
+    /// Last solution, until the high-precision position of its epoch or the next solution
+    held: Option<Solution>,
+    /// High-precision position received ahead of the solution of its epoch
+    hp: Option<HpPosition>,
+}
+
+impl Solution {
+    fn fold(&mut self, hp: HpPosition) {
+        self.lat = hp.lat;
+        self.lon = hp.lon;
+        self.height_msl = hp.height_msl;
+    }
+}
+
+impl<W: Write> PvtExporter<W> {
+    pub fn new(
+        writer: W,
+        frame: Frame,
+        timestamps: Timestamps,
+        derivation: Option<Derivation>,
+        headings: Option<HeadingOptions>,
+        include_invalid: bool,
+    ) -> Self {
+        let utm_zone = match frame {
+            Frame::Utm(zone) => zone,
+            _ => None,
+        };
+        Self {
+            writer,
+            frame,
+            timestamps,
+            derivation,
+            headings: headings.map(|options| {
+                (
+                    options,
+                    [HeadingFilter::new(options), HeadingFilter::new(options)],
+                )
+            }),
+            include_invalid,
+            epochs: 0,
+            excluded: Excluded::default(),
+            utm_zone,
+            pending: Vec::new(),
+            header_written: false,
+            zone_crossing_warned: false,
+            reception: None,
+            receptions: false,
+            held: None,
+            hp: None,
+        }
+    }
+
+    /// Reception time of the next solutions, from timestamped recordings
+    pub fn received(&mut self, reception: Option<Reception>) {
+        if reception.is_some() && !self.header_written && self.pending.is_empty() {
+            self.receptions = true;
+        }
+        self.reception = reception;
+    }
+
+    pub fn leap_seconds(&mut self, ls: &NavTimeLsRef) {
+        self.timestamps.leap_seconds(ls);
+    }
+
+    pub fn export(&mut self, pvt: &NavPvtRef) -> std::io::Result<()> {
+        let gnss_fix_ok = pvt.flags().contains(NavPvtFlags::GPS_FIX_OK);
+        let invalid_llh = pvt.as_bytes()[FLAGS3_OFFSET] & INVALID_LLH != 0;
+        let valid = pvt.valid();
+
+        self.epochs += 1;
+        self.excluded.invalid_llh += usize::from(invalid_llh);
+        self.excluded.no_fix_ok += usize::from(!gnss_fix_ok);
+        self.excluded.invalid_date += usize::from(valid & VALID_DATE == 0);
+        self.excluded.invalid_time += usize::from(valid & VALID_TIME == 0);
+        self.excluded.not_fully_resolved += usize::from(valid & FULLY_RESOLVED == 0);
+
+        let time_ok = self.include_invalid
+            || valid & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+                == VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        let time = DateTime::<Utc>::try_from(pvt)
+            .ok()
+            .filter(|_| time_ok)
+            .map(|t| self.timestamps.utc(t))
+            .unwrap_or_default();
+
+        let mut solution = Solution {
+            itow: pvt.itow(),
+            time,
+            fix_type: pvt.fix_type(),
+            num_sv: pvt.num_satellites(),
+            position_ok: self.include_invalid || (gnss_fix_ok && !invalid_llh),
+            lat: pvt.lat_degrees(),
+            lon: pvt.lon_degrees(),
+            height_msl: pvt.height_msl(),
+            ground_speed: pvt.ground_speed(),
+            heading_motion: pvt.heading_degrees(),
+            heading_vehicle: pvt
+                .flags()
+                .contains(NavPvtFlags::HEAD_VEH_VALID)
+                .then(|| pvt.heading_of_vehicle_degrees()),
+            heading_accuracy: pvt.heading_accuracy_estimate(),
+            reception: self.reception,
+        };
+        if let Some(previous) = self.held.take() {
+            self.write(previous)?;
+        }
+        match self.hp.take().filter(|hp| hp.itow == solution.itow) {
+            Some(hp) => {
+                solution.fold(hp);
+                self.write(solution)
+            },
+            None => {
+                self.held = Some(solution);
+                Ok(())
+            },
+        }
+    }
+
+    /// Replaces the position of the solution of the same epoch with a high-precision one
+    pub fn high_precision(&mut self, hp: &NavHpPosLlhRef) -> std::io::Result<()> {
+        let Some(hp) = HpPosition::from_packet(hp) else {
+            return Ok(());
+        };
+        match self.held.take() {
+            Some(mut solution) if solution.itow == hp.itow => {
+                solution.fold(hp);
+                self.write(solution)
+            },
+            held => {
+                self.held = held;
+                self.hp = Some(hp);
+                Ok(())
+            },
+        }
+    }
+
+    fn write(&mut self, solution: Solution) -> std::io::Result<()> {
+        let (position_ok, lat, lon) = (solution.position_ok, solution.lat, solution.lon);
+        let mut row = format!(
+            "{},{},{:?},{},",
+            solution.itow, solution.time, solution.fix_type, solution.num_sv
+        );
+
+        match self.frame {
+            Frame::Geodetic => {
+                if position_ok {
+                    row.push_str(&format!("{:.9},{:.9}", lat, lon));
+                } else {
+                    row.push(',');
+                }
+                row.push(',');
+            },
+            Frame::Utm(forced) => {
+                if self.utm_zone.is_none() && position_ok {
+                    self.utm_zone = Some(UtmZone::from_position(lat, lon));
+                }
+                match self.utm_zone {
+                    Some(zone) if position_ok => {
+                        if forced.is_none()
+                            && !self.zone_crossing_warned
+                            && UtmZone::from_position(lat, lon) != zone
+                        {
+                            warn!(
+                                "warning: track leaves UTM zone {} at itow {}, coordinates remain projected in zone {}",
+                                zone, solution.itow, zone
+                            );
+                            self.zone_crossing_warned = true;
+                        }
+                        let utm = Utm::from_geodetic(lat, lon, zone);
+                        row.push_str(&format!("{},{:.3},{:.3},", zone, utm.easting, utm.northing));
+                    },
+                    _ => row.push_str(",,,"),
+                }
+            },
+            Frame::Mgrs(precision) => {
+                if position_ok {
+                    row.push_str(&mgrs(lat, lon, precision));
+                }
+                row.push(',');
+            },
+        }
+        if position_ok {
+            row.push_str(&format!("{:.3}", solution.height_msl));
+        }
+        if let Some(derivation) = &mut self.derivation {
+            let derived = derivation.update(
+                solution.itow,
+                position_ok.then_some((lat, lon, solution.height_msl)),
+            );
+            let optional =
+                |value: Option<f64>| value.map(|v| format!("{:.3}", v)).unwrap_or_default();
+            row.push_str(&format!(
+                ",{:.3},{},{},{}",
+                solution.ground_speed,
+                optional(derived.speed),
+                optional(derived.vertical_speed),
+                optional(derived.acceleration)
+            ));
+        }
+
+        if let Some((_, [motion, vehicle])) = &mut self.headings {
+            let (speed, accuracy) = (solution.ground_speed, solution.heading_accuracy);
+            let head_mot =
+                motion.update(position_ok.then_some(solution.heading_motion), speed, accuracy);
+            let head_veh = vehicle.update(
+                solution.heading_vehicle.filter(|_| position_ok),
+                speed,
+                accuracy,
+            );
+            let optional =
+                |value: Option<f64>| value.map(|v| format!("{:.5}", v)).unwrap_or_default();
+            row.push_str(&format!(",{},{}", optional(head_mot), optional(head_veh)));
+        }
+
+        if self.receptions {
+            match solution.reception {
+                Some(reception) => {
+                    row.push_str(&format!(",{},{}", reception.utc(), reception.monotonic))
+                },
+                None => row.push_str(",,"),
+            }
+        }
+
+        if let Frame::Utm(_) = self.frame {
+            if self.utm_zone.is_none() {
+                // header depends on the zone: hold on until first fix
+                self.pending.push(row);
+                return Ok(());
+            }
+        }
+        self.write_header()?;
+        writeln!(self.writer, "{}", row)
+    }
+
+    /// Writes rows still held back and flushes the output,
+    /// then reports the epochs excluded per validity flag on stderr
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(solution) = self.held.take() {
+            self.write(solution)?;
+        }
+        self.write_header()?;
+        self.writer.flush()?;
+
+        let excluded = self.excluded;
+        let action = if self.include_invalid {
+            "included anyway"
+        } else {
+            "excluded"
+        };
+        info!("{} epochs", self.epochs);
+        info!(
+            "  position {}: {} invalidLlh, {} without gnssFixOK",
+            action, excluded.invalid_llh, excluded.no_fix_ok
+        );
+        info!(
+            "  timestamp {}: {} without validDate, {} without validTime, {} not fullyResolved",
+            action, excluded.invalid_date, excluded.invalid_time, excluded.not_fully_resolved
+        );
+        Ok(())
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        let time = self.timestamps.scale.name();
+        let derived = if self.derivation.is_some() {
+            ",ground_speed,speed_derived,vertical_speed_derived,acceleration_derived"
+        } else {
+            ""
+        }
+        .to_string();
+        let derived = match &self.headings {
+            Some((options, _)) => format!("{0},head_mot{1},head_veh{1}", derived, options.suffix()),
+            None => derived,
+        };
+        let derived = if self.receptions {
+            format!("{},rx_utc,rx_monotonic_ns", derived)
+        } else {
+            derived
+        };
+        match self.frame {
+            Frame::Geodetic => {
+                writeln!(
+                    self.writer,
+                    "itow,{},fix_type,num_sv,lat,lon,height_msl{}",
+                    time, derived
+                )?;
+            },
+            Frame::Utm(_) => {
+                match self.utm_zone {
+                    Some(zone) => writeln!(self.writer, "# utm_zone={}", zone)?,
+                    None => writeln!(self.writer, "# utm_zone=unknown")?,
+                }
+                writeln!(
+                    self.writer,
+                    "itow,{},fix_type,num_sv,zone,easting,northing,height_msl{}",
+                    time, derived
+                )?;
+            },
+            Frame::Mgrs(_) => {
+                writeln!(
+                    self.writer,
+                    "itow,{},fix_type,num_sv,mgrs,height_msl{}",
+                    time, derived
+                )?;
+            },
+        }
+        for row in std::mem::take(&mut self.pending) {
+            writeln!(self.writer, "{}", row)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+//! Coordinate conversions for exported positions.
+//!
+//! The transverse Mercator projection uses the Krüger series to sixth order
+//! in the third flattening (Karney, "Transverse Mercator with an accuracy of a
+//! few nanometers", 2011), which keeps the error far below a millimeter over
+//! the whole extent of a UTM zone.
+//!
+//! MGRS references follow the NGA "AA" lettering scheme used with WGS84,
+//! UPS being used beyond the UTM limits (north of 84°N, south of 80°S).
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// WGS84 semi-major axis (m)
+const WGS84_A: f64 = 6_378_137.0;
+
+/// WGS84 flattening
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// UTM central meridian scale factor
+const UTM_K0: f64 = 0.9996;
+
+/// UTM false easting (m)
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+
+/// UTM false northing applied in the southern hemisphere (m)
+const UTM_FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// UPS pole scale factor
+const UPS_K0: f64 = 0.994;
+
+/// UPS false easting and northing (m)
+const UPS_FALSE_ORIGIN: f64 = 2_000_000.0;
+
+/// MGRS latitude bands, 8° each from 80°S, X being extended to 84°N
+const MGRS_LATITUDE_BANDS: &[u8] = b"CDEFGHJKLMNPQRSTUVWX";
+
+/// MGRS 100 km column letters, by zone set
+const MGRS_UTM_COLUMNS: [&[u8]; 3] = [b"ABCDEFGH", b"JKLMNPQR", b"STUVWXYZ"];
+
+/// MGRS 100 km row letters
+const MGRS_UTM_ROWS: &[u8] = b"ABCDEFGHJKLMNPQRSTUV";
+
+/// MGRS polar bands: south/west, south/east, north/west, north/east
+const MGRS_UPS_BANDS: &[u8] = b"ABYZ";
+
+/// MGRS polar 100 km column letters, by polar band
+const MGRS_UPS_COLUMNS: [&[u8]; 4] = [b"JKLPQRSTUXYZ", b"ABCFGHJKLPQR", b"RSTUXYZ", b"ABCFGHJ"];
+
+/// Index of the first 100 km column, by polar band
+const MGRS_UPS_MIN_COLUMN: [i64; 4] = [8, 20, 13, 20];
+
+/// MGRS polar 100 km row letters, south then north
+const MGRS_UPS_ROWS: [&[u8]; 2] = [b"ABCDEFGHJKLMNPQRSTUVWXYZ", b"ABCDEFGHJKLMNP"];
+
+/// Index of the first 100 km row, south then north
+const MGRS_UPS_MIN_ROW: [i64; 2] = [8, 13];
+
+/// UTM zone, made of the zone number and hemisphere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtmZone {
+    /// Zone number, 1..=60
+    pub number: u8,
+    /// True for the northern hemisphere
+    pub north: bool,
+}
+
+impl UtmZone {
+    /// Returns the standard UTM zone containing given position,
+    /// including the Norway and Svalbard exceptions.
+    pub fn from_position(lat: f64, lon: f64) -> Self {
+        let lon = normalize_longitude(lon);
+        let mut number = (((lon + 180.0) / 6.0).floor() as i32 + 1).clamp(1, 60) as u8;
+        if (56.0..64.0).contains(&lat) && (3.0..12.0).contains(&lon) {
+            number = 32;
+        } else if (72.0..=84.0).contains(&lat) && (0.0..42.0).contains(&lon) {
+            number = match lon {
+                lon if lon < 9.0 => 31,
+                lon if lon < 21.0 => 33,
+                lon if lon < 33.0 => 35,
+                _ => 37,
+            };
+        }
+        Self {
+            number,
+            north: lat >= 0.0,
+        }
+    }
+
+    /// Central meridian of this zone, in degrees
+    pub fn central_meridian(&self) -> f64 {
+        f64::from(self.number) * 6.0 - 183.0
+    }
+}
+
+impl fmt::Display for UtmZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.number, if self.north { 'N' } else { 'S' })
+    }
+}
+
+impl FromStr for UtmZone {
+    type Err = String;
+    /// Parses zones like "34N" or "7S", the letter being the hemisphere
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, hemisphere) = s.split_at(s.len().saturating_sub(1));
+        let north = match hemisphere {
+            "N" | "n" => true,
+            "S" | "s" => false,
+            _ => return Err(format!("invalid UTM zone \"{}\": expecting <1-60><N|S>", s)),
+        };
+        let number = number
+            .parse::<u8>()
+            .ok()
+            .filter(|n| (1..=60).contains(n))
+            .ok_or_else(|| format!("invalid UTM zone number in \"{}\"", s))?;
+        Ok(Self { number, north })
+    }
+}
+
+/// Projected UTM coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct Utm {
+    /// Easting (m)
+    pub easting: f64,
+    /// Northing (m)
+    pub northing: f64,
+}
+
+impl Utm {
+    /// Projects given geodetic position (degrees) in the requested zone.
+    /// The zone does not need to contain the position, which lets a track be
+    /// exported in a single zone even if it crosses a zone boundary.
+    pub fn from_geodetic(lat: f64, lon: f64, zone: UtmZone) -> Self {
+        let (x, y) = transverse_mercator(lat, lon, zone.central_meridian());
+        let false_northing = if zone.north {
+            0.0
+        } else {
+            UTM_FALSE_NORTHING_SOUTH
+        };
+        Self {
+            easting: UTM_FALSE_EASTING + UTM_K0 * x,
+            northing: false_northing + UTM_K0 * y,
+        }
+    }
+}
+
+/// Returns the MGRS grid reference of given geodetic position (degrees),
+/// like "34UDC1234567890", with `precision` digits (0 to 5) per axis.
+pub fn mgrs(lat: f64, lon: f64, precision: u8) -> String {
+    let precision = precision.min(5);
+    let lon = normalize_longitude(lon);
+
+    let (prefix, easting, northing) = if (-80.0..84.0).contains(&lat) {
+        let zone = UtmZone::from_position(lat, lon);
+        let utm = Utm::from_geodetic(lat, lon, zone);
+        let band = ((lat + 80.0) / 8.0).floor().clamp(0.0, 19.0) as usize;
+        let set = usize::from((zone.number - 1) % 3);
+        let column = (utm.easting / 100_000.0).floor() as usize;
+        let mut row = (utm.northing / 100_000.0).floor() as usize;
+        if zone.number % 2 == 0 {
+            row += 5;
+        }
+        let prefix = format!(
+            "{:02}{}{}{}",
+            zone.number,
+            MGRS_LATITUDE_BANDS[band] as char,
+            MGRS_UTM_COLUMNS[set][column.clamp(1, 8) - 1] as char,
+            MGRS_UTM_ROWS[row % MGRS_UTM_ROWS.len()] as char,
+        );
+        (prefix, utm.easting, utm.northing)
+    } else {
+        let north = lat > 0.0;
+        let (easting, northing) = polar_stereographic(lat, lon);
+        let column = (easting / 100_000.0).floor() as i64;
+        let row = (northing / 100_000.0).floor() as i64;
+        let band = if north { 2 } else { 0 } + if column >= 20 { 1 } else { 0 };
+        let columns = MGRS_UPS_COLUMNS[band];
+        let rows = MGRS_UPS_ROWS[north as usize];
+        let column = (column - MGRS_UPS_MIN_COLUMN[band]).clamp(0, columns.len() as i64 - 1);
+        let row = (row - MGRS_UPS_MIN_ROW[north as usize]).clamp(0, rows.len() as i64 - 1);
+        let prefix = format!(
+            "{}{}{}",
+            MGRS_UPS_BANDS[band] as char,
+            columns[column as usize] as char,
+            rows[row as usize] as char,
+        );
+        (prefix, easting, northing)
+    };
+
+    if precision == 0 {
+        return prefix;
+    }
+    let divisor = 10_f64.powi(5 - i32::from(precision));
+    let e = (easting.rem_euclid(100_000.0) / divisor).floor() as u64;
+    let n = (northing.rem_euclid(100_000.0) / divisor).floor() as u64;
+    format!(
+        "{}{:0width$}{:0width$}",
+        prefix,
+        e,
+        n,
+        width = usize::from(precision)
+    )
+}
+
+/// UPS projection of given geodetic position (degrees), returns (easting, northing)
+fn polar_stereographic(lat: f64, lon: f64) -> (f64, f64) {
+    let e = (WGS84_F * (2.0 - WGS84_F)).sqrt();
+    let north = lat > 0.0;
+    // work in the northern aspect, mirrored for the south pole
+    let phi = lat.abs() * PI / 180.0;
+    let lambda = lon * PI / 180.0;
+    let sin_phi = phi.sin();
+    let t =
+        (PI / 4.0 - phi / 2.0).tan() / ((1.0 - e * sin_phi) / (1.0 + e * sin_phi)).powf(e / 2.0);
+    let c = ((1.0 + e).powf(1.0 + e) * (1.0 - e).powf(1.0 - e)).sqrt();
+    let rho = 2.0 * WGS84_A * UPS_K0 * t / c;
+    let easting = UPS_FALSE_ORIGIN + rho * lambda.sin();
+    let northing = if north {
+        UPS_FALSE_ORIGIN - rho * lambda.cos()
+    } else {
+        UPS_FALSE_ORIGIN + rho * lambda.cos()
+    };
+    (easting, northing)
+}
+
+/// North and east offsets (m) of a position from a nearby reference position,
+/// using the WGS84 radii of curvature at the reference
+pub fn local_offset(lat0: f64, lon0: f64, lat: f64, lon: f64) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let sin = lat0.to_radians().sin();
+    let w2 = 1.0 - e2 * sin * sin;
+    let meridian = WGS84_A * (1.0 - e2) / (w2 * w2.sqrt());
+    let prime_vertical = WGS84_A / w2.sqrt();
+    (
+        (lat - lat0).to_radians() * meridian,
+        normalize_longitude(lon - lon0).to_radians() * prime_vertical * lat0.to_radians().cos(),
+    )
+}
+
+/// Wraps longitude into [-180, 180[
+fn normalize_longitude(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Unscaled transverse Mercator projection on the WGS84 ellipsoid, returns
+/// (x, y) in meters relative to the central meridian and the equator.
+fn transverse_mercator(lat: f64, lon: f64, lon0: f64) -> (f64, f64) {
+    let n = WGS84_F / (2.0 - WGS84_F);
+    let (n2, n3) = (n * n, n * n * n);
+    let (n4, n5, n6) = (n2 * n2, n2 * n3, n3 * n3);
+
+    // rectifying radius
+    let big_a = WGS84_A / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0 + n6 / 256.0);
+
+    let alpha = [
+        n / 2.0 - 2.0 / 3.0 * n2 + 5.0 / 16.0 * n3 + 41.0 / 180.0 * n4 - 127.0 / 288.0 * n5
+            + 7891.0 / 37800.0 * n6,
+        13.0 / 48.0 * n2 - 3.0 / 5.0 * n3 + 557.0 / 1440.0 * n4 + 281.0 / 630.0 * n5
+            - 1983433.0 / 1935360.0 * n6,
+        61.0 / 240.0 * n3 - 103.0 / 140.0 * n4 + 15061.0 / 26880.0 * n5 + 167603.0 / 181440.0 * n6,
+        49561.0 / 161280.0 * n4 - 179.0 / 168.0 * n5 + 6601661.0 / 7257600.0 * n6,
+        34729.0 / 80640.0 * n5 - 3418889.0 / 1995840.0 * n6,
+        212378941.0 / 319334400.0 * n6,
+    ];
+
+    let phi = lat * PI / 180.0;
+    let dlambda = normalize_longitude(lon - lon0) * PI / 180.0;
+
+    // conformal latitude
+    let e = 2.0 * n.sqrt() / (1.0 + n);
+    let sin_phi = phi.sin();
+    let t = (sin_phi.atanh() - e * (e * sin_phi).atanh()).sinh();
+
+    let xi_p = t.atan2(dlambda.cos());
+    let eta_p = (dlambda.sin() / (1.0 + t * t).sqrt()).atanh();
+
+    let mut xi = xi_p;
+    let mut eta = eta_p;
+    for (j, alpha_j) in alpha.iter().enumerate() {
+        let k = 2.0 * (j as f64 + 1.0);
+        xi += alpha_j * (k * xi_p).sin() * (k * eta_p).cosh();
+        eta += alpha_j * (k * xi_p).cos() * (k * eta_p).sinh();
+    }
+
+    (big_a * eta, big_a * xi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference conversions of GeographicLib (GeoConvert), which follows the NGA UTM/UPS and
+    /// MGRS specifications: latitude, longitude, zone, easting, northing and MGRS reference
+    const UTM_POINTS: [(f64, f64, &str, f64, f64, &str); 16] = [
+        (38.8895, -77.0352, "18N", 323486.737, 4306483.048, "18SUJ2348606483"),
+        (-33.8568, 151.2153, "56S", 334900.570, 6252288.753, "56HLH3490052288"),
+        (-79.9, -60.0, "21S", 441292.553, 1128062.171, "21CVM4129228062"),
+        // zone 32V widened over 3°E to 12°E, between 56°N and 64°N
+        (60.0, 5.0, "32N", 276979.926, 6658157.202, "32VKM7697958157"),
+        (56.0, 3.0, "32N", 126049.971, 6222336.335, "32VJH2604922336"),
+        (63.99, 11.99, "32N", 646258.785, 7099330.675, "32VPR4625899330"),
+        (56.0, 2.99, "31N", 499376.322, 6206079.632, "31VDC9937606079"),
+        // zones 31X, 33X, 35X and 37X of Svalbard, 32X, 34X and 36X not being used
+        (78.0, 8.9, "31N", 636716.846, 8665261.550, "31XFG3671665261"),
+        (78.0, 9.0, "33N", 360973.604, 8665496.996, "33XUG6097365496"),
+        (78.0, 20.9, "33N", 636716.846, 8665261.550, "33XXG3671665261"),
+        (78.0, 21.0, "35N", 360973.604, 8665496.996, "35XLG6097365496"),
+        (78.0, 33.0, "37N", 360973.604, 8665496.996, "37XCG6097365496"),
+        (78.0, 41.9, "37N", 567282.209, 8660035.493, "37XEG6728260035"),
+        // band X extended to 84°N
+        (83.9, 10.0, "33N", 440754.278, 9319502.269, "33XVP4075419502"),
+        // central meridian on the equator
+        (0.0, 3.0, "31N", 500000.0, 0.0, "31NEA0000000000"),
+        (0.0, -171.0, "2N", 500000.0, 0.0, "02NNF0000000000"),
+    ];
+
+    /// UPS references of GeographicLib: latitude, longitude, easting, northing and MGRS
+    const UPS_POINTS: [(f64, f64, f64, f64, &str); 9] = [
+        (84.0, 10.0, 2115776.051, 1343401.388, "ZBA1577643401"),
+        (85.0, 45.0, 2392767.688, 1607232.312, "ZFD9276707232"),
+        (85.0, -45.0, 1607232.312, 1607232.312, "YUD0723207232"),
+        (-85.0, 45.0, 2392767.688, 2392767.688, "BFR9276792767"),
+        (-85.0, -45.0, 1607232.312, 2392767.688, "AUR0723292767"),
+        (-80.5, 170.0, 2183555.078, 959007.421, "BBB8355559007"),
+        (89.9, -120.0, 1990385.053, 2005551.192, "YZH9038505551"),
+        (90.0, 0.0, 2000000.0, 2000000.0, "ZAH0000000000"),
+        (-90.0, 0.0, 2000000.0, 2000000.0, "BAN0000000000"),
+    ];
+
+    #[test]
+    fn utm_reference_points() {
+        for (lat, lon, zone, easting, northing, _) in UTM_POINTS {
+            let found = UtmZone::from_position(lat, lon);
+            assert_eq!(found.to_string(), zone, "zone of {}, {}", lat, lon);
+            let utm = Utm::from_geodetic(lat, lon, found);
+            assert!((utm.easting - easting).abs() < 1e-3, "easting of {}, {}", lat, lon);
+            assert!((utm.northing - northing).abs() < 1e-3, "northing of {}, {}", lat, lon);
+        }
+    }
+
+    #[test]
+    fn mgrs_reference_points() {
+        for (lat, lon, _, _, _, reference) in UTM_POINTS {
+            assert_eq!(mgrs(lat, lon, 5), reference, "MGRS of {}, {}", lat, lon);
+        }
+        for (lat, lon, _, _, reference) in UPS_POINTS {
+            assert_eq!(mgrs(lat, lon, 5), reference, "MGRS of {}, {}", lat, lon);
+        }
+    }
+
+    #[test]
+    fn ups_reference_points() {
+        for (lat, lon, easting, northing, _) in UPS_POINTS {
+            let (e, n) = polar_stereographic(lat, lon);
+            assert!((e - easting).abs() < 1e-3, "easting of {}, {}", lat, lon);
+            assert!((n - northing).abs() < 1e-3, "northing of {}, {}", lat, lon);
+        }
+    }
+
+    #[test]
+    fn mgrs_precision() {
+        assert_eq!(mgrs(38.8895, -77.0352, 0), "18SUJ");
+        assert_eq!(mgrs(38.8895, -77.0352, 1), "18SUJ20");
+        assert_eq!(mgrs(38.8895, -77.0352, 3), "18SUJ234064");
+        assert_eq!(mgrs(90.0, 0.0, 2), "ZAH0000");
+    }
+
+    #[test]
+    fn utm_zone_parsing() {
+        let zone = "34N".parse::<UtmZone>().unwrap();
+        assert_eq!((zone.number, zone.north), (34, true));
+        assert_eq!("7s".parse::<UtmZone>().unwrap().to_string(), "7S");
+        assert!("61N".parse::<UtmZone>().is_err());
+        assert!("34".parse::<UtmZone>().is_err());
+    }
+}
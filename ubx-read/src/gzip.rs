@@ -0,0 +1,151 @@
+//! Gzip decompression across concatenated members.
+//!
+//! Appended recordings, or files concatenated with `cat`, hold several gzip members
+//! back to back: decompression carries on with the next member instead of stopping
+//! at the end of the first one, counting them.
+//!
+//! Recordings with sync points (`ubx-record --sync-interval`) can also be decompressed
+//! from any of the sync points listed in their `<path>.sync` index.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+
+use flate2::bufread::{DeflateDecoder, GzDecoder};
+
+pub struct Members<R: Read> {
+    /// Decoder of the current member, None once the input is exhausted
+    decoder: Option<GzDecoder<BufReader<R>>>,
+    members: usize,
+}
+
+impl<R: Read> Members<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            decoder: Some(GzDecoder::new(BufReader::new(input))),
+            members: 1,
+        }
+    }
+
+    /// Number of members encountered so far
+    pub fn members(&self) -> usize {
+        self.members
+    }
+}
+
+impl<R: Read> Read for Members<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(decoder) = self.decoder.as_mut() {
+            let size = decoder.read(buf)?;
+            if size > 0 {
+                return Ok(size);
+            }
+            // end of the member, another one possibly following
+            let mut input = self.decoder.take().unwrap().into_inner();
+            if !input.fill_buf()?.is_empty() {
+                self.decoder = Some(GzDecoder::new(input));
+                self.members += 1;
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Decompresses a recording from one of its sync points, counted from 0 (the start)
+pub fn from_sync_point(
+    path: &str,
+    sync_point: usize,
+) -> std::io::Result<DeflateDecoder<BufReader<File>>> {
+    let index = std::fs::read_to_string(format!("{}.sync", path))?;
+    let offsets = index
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next()?.parse::<u64>().ok())
+        .collect::<Vec<_>>();
+    let offset = *offsets.get(sync_point).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "sync point {} out of range, {} listed",
+                sync_point,
+                offsets.len()
+            ),
+        )
+    })?;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    // the data following a sync point is a raw deflate stream
+    Ok(DeflateDecoder::new(BufReader::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(input: Vec<u8>) -> (std::io::Result<Vec<u8>>, usize) {
+        let mut members = Members::new(Cursor::new(input));
+        let mut output = vec![];
+        let result = members.read_to_end(&mut output).map(|_| output);
+        (result, members.members())
+    }
+
+    #[test]
+    fn single_member() {
+        let (output, members) = decompress(gzip(b"first recording"));
+        assert_eq!(output.unwrap(), b"first recording");
+        assert_eq!(members, 1);
+    }
+
+    #[test]
+    fn concatenated_members() {
+        let input = [gzip(b"first, "), gzip(b""), gzip(b"appended, "), gzip(b"last")].concat();
+        let (output, members) = decompress(input);
+        assert_eq!(output.unwrap(), b"first, appended, last");
+        assert_eq!(members, 4);
+    }
+
+    #[test]
+    fn truncated_member() {
+        let second = gzip(&[0x55; 4096]);
+        let input = [gzip(b"complete"), second[..second.len() / 2].to_vec()].concat();
+        let mut members = Members::new(Cursor::new(input));
+        let mut output = vec![];
+        let error = members.read_to_end(&mut output).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(output.starts_with(b"complete"));
+        assert_eq!(members.members(), 2);
+    }
+
+    #[test]
+    fn sync_points() {
+        let name = format!("ubx-read-{}-sync.ubx.gz", std::process::id());
+        let path = std::env::temp_dir().join(name).to_string_lossy().to_string();
+        // a raw deflate stream follows the second sync point
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder.write_all(b"from the sync point").unwrap();
+        let prefix = b"data preceding the sync point";
+        std::fs::write(&path, [prefix.as_slice(), &encoder.finish().unwrap()].concat()).unwrap();
+        std::fs::write(format!("{}.sync", path), format!("# offsets\n0\n{} 12\n", prefix.len()))
+            .unwrap();
+
+        let mut output = vec![];
+        from_sync_point(&path, 1).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"from the sync point");
+        let error = from_sync_point(&path, 2).unwrap_err();
+        assert_eq!(error.to_string(), "sync point 2 out of range, 2 listed");
+
+        std::fs::remove_file(format!("{}.sync", path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+//! Heading post-processing for export: blanking when meaningless, circular smoothing
+//! and unwrapping into a continuous angle.
+//!
+//! At low speed, the heading of motion is noise: epochs slower than the speed threshold,
+//! or with a heading accuracy worse than the threshold, are blanked and restart the smoothing.
+
+use std::collections::VecDeque;
+
+/// Heading processing settings
+#[derive(Debug, Clone, Copy)]
+pub struct HeadingOptions {
+    /// Accumulate full turns instead of wrapping into [0, 360[
+    pub unwrap: bool,
+    /// Circular moving average window (epochs), 1 for none
+    pub window: usize,
+    /// Slowest ground speed of a meaningful heading (m/s)
+    pub min_speed: f64,
+    /// Largest heading accuracy estimate of a meaningful heading (degrees)
+    pub max_accuracy: f64,
+}
+
+impl HeadingOptions {
+    /// Suffix of the heading column names
+    pub fn suffix(&self) -> String {
+        let mut suffix = String::new();
+        if self.window > 1 {
+            suffix.push_str("_smoothed");
+        }
+        if self.unwrap {
+            suffix.push_str("_unwrapped");
+        }
+        suffix
+    }
+}
+
+/// Processes one heading series
+pub struct HeadingFilter {
+    options: HeadingOptions,
+    /// Unit vectors of the smoothing window
+    window: VecDeque<(f64, f64)>,
+    /// Last heading output, unwrapped
+    last: Option<f64>,
+}
+
+impl HeadingFilter {
+    pub fn new(options: HeadingOptions) -> Self {
+        Self {
+            options,
+            window: VecDeque::new(),
+            last: None,
+        }
+    }
+
+    /// Processed heading of an epoch, None when meaningless
+    pub fn update(&mut self, heading: Option<f64>, speed: f64, accuracy: f64) -> Option<f64> {
+        let Some(heading) = heading
+            .filter(|_| speed >= self.options.min_speed && accuracy <= self.options.max_accuracy)
+        else {
+            self.window.clear();
+            return None;
+        };
+
+        let radians = heading.to_radians();
+        if self.window.len() == self.options.window.max(1) {
+            self.window.pop_front();
+        }
+        self.window.push_back((radians.sin(), radians.cos()));
+        let (sin, cos) = self
+            .window
+            .iter()
+            .fold((0.0, 0.0), |(s, c), (sin, cos)| (s + sin, c + cos));
+        let mean = sin.atan2(cos).to_degrees().rem_euclid(360.0);
+
+        if !self.options.unwrap {
+            return Some(mean);
+        }
+        // nearest turn to the last output, blanked epochs keeping the count
+        let unwrapped = match self.last {
+            Some(last) => last + (mean - last + 180.0).rem_euclid(360.0) - 180.0,
+            None => mean,
+        };
+        self.last = Some(unwrapped);
+        Some(unwrapped)
+    }
+}
@@ -1,23 +1,119 @@
 use ublox::*;
-use clap::{Arg, Command};
+use clap::{value_parser, Arg, ArgAction, Command};
+use ubx_common::logging;
+
+// the error!, warn!, info!, debug! and trace! macros, for every module
+#[macro_use]
+extern crate log;
+
+mod attitude;
+mod availability;
+mod bench;
+mod compare;
+mod container;
+mod derived;
+mod esf;
+mod events;
+mod export;
+mod geodesy;
+mod gzip;
+mod heading;
+mod metadata;
+mod navsat;
+mod output;
+mod repair;
+mod scan;
+mod summary;
+mod template;
+mod timebase;
+mod timemark;
+mod timing;
+mod ttff;
+
+use attitude::{AttitudeExporter, AttitudeReport};
+use availability::Availability;
+use bench::Mix;
+use compare::Comparison;
+use container::{Reception, Records};
+use derived::Derivation;
+use esf::EsfRawExporter;
+use events::EventLogger;
+use export::{Frame, PvtExporter};
+use geodesy::UtmZone;
+use gzip::{from_sync_point, Members};
+use heading::HeadingOptions;
+use metadata::Info;
+use navsat::{Cn0Matrix, Satellites, SkyplotExporter, SkyplotSummary};
+use output::Output;
+use repair::Repair;
+use scan::{StreamScanner, Validation};
+use summary::Summary;
+use template::{Template, TemplateExporter};
+use timebase::{Timescale, Timestamps};
+use timemark::TimeMarkExporter;
+use timing::{TimeExporter, TimeGps, TimeReport, NAV_TIMEGPS};
+use ttff::TtffReport;
 
 use std::fs::File;
-use flate2::read::GzDecoder;
-use std::io::{BufReader, Read};
+use flate2::bufread::DeflateDecoder;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 
 enum BufferedReader {
     Plain(BufReader<File>),
-    Gzip(BufReader<GzDecoder<File>>),
+    Gzip(Box<BufReader<Members<File>>>),
+    Zstd(Box<BufReader<zstd::Decoder<'static, BufReader<File>>>>),
+    Resumed(Box<BufReader<DeflateDecoder<BufReader<File>>>>),
+    /// Recording of `ubx-record --timestamps`, compressed or not
+    Timestamped(Box<Records<BufferedReader>>),
 }
 
 impl BufferedReader {
-    fn new(path: &str) -> Self {
+    /// Opens the file, compressed files being possibly read from one of their sync points
+    fn new(path: &str, sync_point: Option<usize>) -> Self {
+        if let Some(sync_point) = sync_point {
+            let decoder = from_sync_point(path, sync_point).unwrap_or_else(|e| {
+                panic!("failed to open \"{}\" at sync point {}: {}", path, sync_point, e)
+            });
+            debug!("reading \"{}\" from gzip sync point {}", path, sync_point);
+            return Self::Resumed(Box::new(BufReader::new(decoder)));
+        }
         let fd = File::open(path)
-            .expect(&format!("failed to open \"{}\"", path));
-        if path.ends_with(".gz") {
-            Self::Gzip(BufReader::new(GzDecoder::new(fd))) 
+            .unwrap_or_else(|e| panic!("failed to open \"{}\": {}", path, e));
+        let (mut reader, compressed) = if path.ends_with(".gz") {
+            (Self::Gzip(Box::new(BufReader::new(Members::new(fd)))), ", gzip compressed")
+        } else if path.ends_with(".zst") {
+            let decoder = zstd::Decoder::new(fd)
+                .unwrap_or_else(|e| panic!("failed to open \"{}\": {}", path, e));
+            (Self::Zstd(Box::new(BufReader::new(decoder))), ", zstd compressed")
         } else {
-            Self::Plain(BufReader::new(fd))
+            (Self::Plain(BufReader::new(fd)), "")
+        };
+        let timestamped = reader
+            .fill_buf()
+            .is_ok_and(|data| data.starts_with(&container::MAGIC));
+        if timestamped {
+            debug!("reading \"{}\", timestamped recording{}", path, compressed);
+            Self::Timestamped(Box::new(Records::new(reader)))
+        } else {
+            debug!("reading \"{}\"{}", path, compressed);
+            reader
+        }
+    }
+
+    /// Gzip members decompressed so far, None when not compressed
+    fn members(&self) -> Option<usize> {
+        match self {
+            Self::Plain(_) | Self::Zstd(_) | Self::Resumed(_) => None,
+            Self::Gzip(bufreader) => Some(bufreader.get_ref().members()),
+            Self::Timestamped(records) => records.get_ref().members(),
+        }
+    }
+
+    /// Reception time of the frame last read, from timestamped recordings
+    fn reception(&self) -> Option<Reception> {
+        match self {
+            Self::Timestamped(records) => records.reception(),
+            _ => None,
         }
     }
 }
@@ -27,6 +123,9 @@ impl std::io::Read for BufferedReader {
         match self {
             Self::Plain(ref mut h) => h.read(buf),
             Self::Gzip(ref mut h) => h.read(buf),
+            Self::Zstd(ref mut h) => h.read(buf),
+            Self::Resumed(ref mut h) => h.read(buf),
+            Self::Timestamped(ref mut h) => h.read(buf),
         }
     }
 }
@@ -36,12 +135,18 @@ impl std::io::BufRead for BufferedReader {
         match self {
             Self::Plain(ref mut bufreader) => bufreader.fill_buf(),
             Self::Gzip(ref mut bufreader) => bufreader.fill_buf(),
+            Self::Zstd(ref mut bufreader) => bufreader.fill_buf(),
+            Self::Resumed(ref mut bufreader) => bufreader.fill_buf(),
+            Self::Timestamped(ref mut records) => records.fill_buf(),
         }
     }
     fn consume(&mut self, s: usize) {
         match self {
             Self::Plain(ref mut bufreader) => bufreader.consume(s),
             Self::Gzip(ref mut bufreader) => bufreader.consume(s),
+            Self::Zstd(ref mut bufreader) => bufreader.consume(s),
+            Self::Resumed(ref mut bufreader) => bufreader.consume(s),
+            Self::Timestamped(ref mut records) => records.consume(s),
         }
     }
 }
@@ -51,30 +156,1106 @@ fn main() {
         .author(clap::crate_authors!())
         .about("Read and parse UBX files")
         .arg_required_else_help(true)
+        .subcommand_negates_reqs(true)
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .help("Only print warnings and errors on stderr")
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Print more on stderr: -v adds the files read, -vv every packet")
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .global(true)
+                .help("Append the stderr messages to PATH too, with their time and level")
+        )
         .arg(
             Arg::new("file")
                 .value_name("FILE")
                 .short('f')
                 .long("fp")
                 .required(true)
-                .help("Local .ubx file path, can be gzip compressed.")
+                .help("Local .ubx file path, can be gzip (*.gz) or zstd (*.zst) compressed.")
+        )
+        .arg(
+            Arg::new("sync-point")
+                .long("sync-point")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Start reading a gzip recording at its sync point N (0 being the start), \
+as listed in its .sync index by ubx-record --sync-interval")
+        )
+        .arg(
+            Arg::new("output")
+                .value_name("FILE")
+                .short('o')
+                .long("output")
+                .help("Write the export or report to this file instead of the standard output, \
+gzip compressed when named *.gz")
+        )
+        .arg(
+            Arg::new("compress-level")
+                .long("compress-level")
+                .value_name("LEVEL")
+                .global(true)
+                .value_parser(value_parser!(u32).range(0..=22))
+                .help("Compression level of the compressed outputs: *.gz from 0 (none) to 9 \
+(smallest), 6 by default, *.zst from 1 (fastest) to 22 (smallest), 3 by default")
+        )
+        .next_help_heading("Export")
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["debug", "csv", "tim-tm2", "nav-time", "esf-raw", "nav-att", "template"])
+                .default_value("debug")
+                .help("Output format. \"debug\" prints every packet, \"csv\" exports one row per NAV-PVT solution, \
+\"tim-tm2\" exports one row per TIM-TM2 time mark, \"nav-time\" exports one row per NAV-TIMEGPS and NAV-TIMEUTC message, \"esf-raw\" exports one row per ESF-RAW sensor sample, \"nav-att\" exports one row per NAV-ATT attitude, \
+\"template\" writes the --template line of each navigation epoch.")
+        )
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .value_name("TEMPLATE")
+                .value_parser(Template::parse)
+                .help("Line written by --format template, like \"{utc} {lat:.7} {lon:.7} {fix_type} {num_sv}\". \
+Fields: itow, utc, gps, tai, unix, fix_type, num_sv, lat, lon, height, height_msl, h_acc, v_acc, \
+vel_n, vel_e, vel_d, ground_speed, head_mot, pdop (NAV-PVT) and roll, pitch, heading (NAV-ATT). \
+{{ and }} write braces, \\n and \\t new lines and tabs")
+        )
+        .arg(
+            Arg::new("per-packet")
+                .long("per-packet")
+                .action(ArgAction::SetTrue)
+                .help("Write the template line at every NAV-PVT or NAV-ATT packet it uses a field of, \
+instead of once per epoch")
+        )
+        .arg(
+            Arg::new("frame")
+                .long("frame")
+                .value_parser(["geodetic", "utm", "mgrs"])
+                .default_value("geodetic")
+                .help("Coordinate representation of exported positions")
+        )
+        .arg(
+            Arg::new("utm-zone")
+                .long("utm-zone")
+                .value_name("ZONE")
+                .value_parser(|s: &str| s.parse::<UtmZone>())
+                .help("Force UTM zone (for example \"34N\"), instead of the zone of the first fix")
+        )
+        .arg(
+            Arg::new("mgrs-precision")
+                .long("mgrs-precision")
+                .value_name("DIGITS")
+                .value_parser(value_parser!(u8).range(0..=5))
+                .default_value("5")
+                .help("Number of digits per axis of MGRS grid references: 5 is 1 m, 0 is the 100 km square")
+        )
+        .arg(
+            Arg::new("timescale")
+                .long("timescale")
+                .value_name("SCALE")
+                .value_parser(Timescale::parse)
+                .help("Time scale of the exported timestamps: utc, gps, tai or unix (seconds). \
+The column is named after the scale. Time marks are dated in both GPS and UTC times by default")
+        )
+        .arg(
+            Arg::new("derived-speed")
+                .long("derived-speed")
+                .action(ArgAction::SetTrue)
+                .help("Add the receiver ground speed, and the horizontal speed, vertical speed \
+and horizontal acceleration derived from the positions to the CSV export")
+        )
+        .arg(
+            Arg::new("derived-window")
+                .long("derived-window")
+                .value_name("EPOCHS")
+                .value_parser(value_parser!(usize))
+                .default_value("1")
+                .help("Moving average window of the derived values, 1 for none")
+        )
+        .arg(
+            Arg::new("derived-max-gap")
+                .long("derived-max-gap")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(f64))
+                .default_value("2")
+                .help("Largest spacing of the epochs values are derived from")
+        )
+        .arg(
+            Arg::new("heading")
+                .long("heading")
+                .action(ArgAction::SetTrue)
+                .help("Add the heading of motion and heading of vehicle to the CSV export, \
+blank when slower than --heading-min-speed or less accurate than --heading-max-acc")
+        )
+        .arg(
+            Arg::new("unwrap-heading")
+                .long("unwrap-heading")
+                .action(ArgAction::SetTrue)
+                .help("Export continuous headings, accumulating full turns instead of wrapping \
+into [0, 360[. Implies --heading")
+        )
+        .arg(
+            Arg::new("heading-window")
+                .long("heading-window")
+                .value_name("EPOCHS")
+                .value_parser(value_parser!(usize))
+                .help("Smooth the headings with a circular moving average over EPOCHS. Implies --heading")
+        )
+        .arg(
+            Arg::new("heading-min-speed")
+                .long("heading-min-speed")
+                .value_name("M/S")
+                .value_parser(value_parser!(f64))
+                .default_value("0.5")
+                .help("Ground speed below which headings are blanked")
+        )
+        .arg(
+            Arg::new("heading-max-acc")
+                .long("heading-max-acc")
+                .value_name("DEGREES")
+                .value_parser(value_parser!(f64))
+                .default_value("30")
+                .help("Heading accuracy estimate (headAcc) above which headings are blanked")
+        )
+        .arg(
+            Arg::new("include-invalid")
+                .long("include-invalid")
+                .action(ArgAction::SetTrue)
+                .help("Export positions and timestamps even when NAV-PVT flags them as invalid")
+        )
+        .arg(
+            Arg::new("attitude-report")
+                .long("attitude-report")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events"])
+                .help("Print roll, pitch and heading statistics of the NAV-ATT solutions instead")
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "ttff-report"])
+                .help("Print the frames, bytes, share of the stream and bandwidth \
+per UBX message, NMEA sentence and RTCM message instead")
+        )
+        .arg(
+            Arg::new("info")
+                .long("info")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "ttff-report"])
+                .help("Print the session metadata blocks of the recording instead \
+(ubx-record --embed-metadata): tool version, command line, host, receiver and start time")
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "ttff-report", "info"])
+                .help("Validate the recording instead: exit with an error unless every byte \
+belongs to a valid UBX, NMEA or RTCM3 frame (see repair)")
+        )
+        .arg(
+            Arg::new("cn0-matrix")
+                .long("cn0-matrix")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "ttff-report"])
+                .help("Export the NAV-SAT CN0 as a CSV matrix instead: one row per epoch, \
+one column per satellite (like G05, E12, R14), empty when not tracked")
+        )
+        .arg(
+            Arg::new("skyplot")
+                .long("skyplot")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "ttff-report", "cn0-matrix"])
+                .help("Export the NAV-SAT azimuth, elevation, CN0 and used flag instead, \
+one CSV row per satellite and epoch")
+        )
+        .arg(
+            Arg::new("skyplot-decimate")
+                .long("skyplot-decimate")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(f64))
+                .default_value("0")
+                .help("Export one skyplot sample per satellite every SECONDS at most")
+        )
+        .arg(
+            Arg::new("skyplot-summary")
+                .long("skyplot-summary")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "ttff-report", "cn0-matrix", "skyplot"])
+                .help("Print the maximum elevation and time above the elevation mask of each satellite instead")
+        )
+        .arg(
+            Arg::new("elevation-mask")
+                .long("elevation-mask")
+                .value_name("DEGREES")
+                .value_parser(value_parser!(i8).range(-90..=90))
+                .default_value("10")
+                .help("Elevation mask of the skyplot summary")
+        )
+        .arg(
+            Arg::new("availability")
+                .long("availability")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "summary", "cn0-matrix", "skyplot", "skyplot-summary"])
+                .help("Print the share of expected epochs present and with a 3D fix, the longest outages, \
+and a per hour breakdown instead")
+        )
+        .arg(
+            Arg::new("expected-rate")
+                .long("expected-rate")
+                .value_name("HZ")
+                .value_parser(value_parser!(f64))
+                .default_value("1")
+                .help("Nominal navigation rate of the availability report")
+        )
+        .arg(
+            Arg::new("ttff-report")
+                .long("ttff-report")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "availability"])
+                .help("Print the time to first 2D fix, 3D fix and fully resolved time \
+of each receiver startup (log start, iTOW restart or data after a gap) instead")
+        )
+        .arg(
+            Arg::new("ttff-gap")
+                .long("ttff-gap")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(f64))
+                .default_value("5")
+                .help("Gap between NAV-PVT solutions considered as a receiver restart")
+        )
+        .arg(
+            Arg::new("time-report")
+                .long("time-report")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["format", "events", "attitude-report", "availability", "ttff-report"])
+                .help("Compare the NAV-TIMEGPS, NAV-TIMEUTC and NAV-PVT times of each epoch instead, \
+reporting inconsistencies and the time accuracy estimate (tAcc) trend over the session")
+        )
+        .arg(
+            Arg::new("time-tolerance")
+                .long("time-tolerance")
+                .value_name("NS")
+                .value_parser(value_parser!(u64))
+                .default_value("1")
+                .help("Largest difference between the times of an epoch reported as consistent")
+        )
+        .next_help_heading("Events")
+        .arg(
+            Arg::new("events")
+                .long("events")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .num_args(0..=1)
+                .default_missing_value("text")
+                .conflicts_with("format")
+                .help("Print one line (or JSON object) per state change of the NAV-PVT solution \
+instead: fix gained/lost, RTK float/fixed transitions, numSV and hAcc threshold crossings")
+        )
+        .arg(
+            Arg::new("debounce")
+                .long("debounce")
+                .value_name("EPOCHS")
+                .value_parser(value_parser!(usize))
+                .default_value("3")
+                .help("Number of consecutive epochs a new state must hold to be reported")
+        )
+        .arg(
+            Arg::new("numsv-thresholds")
+                .long("numsv-thresholds")
+                .value_name("LIST")
+                .value_parser(value_parser!(u8))
+                .value_delimiter(',')
+                .help("numSV thresholds, comma separated, like \"6,10\"")
+        )
+        .arg(
+            Arg::new("hacc-thresholds")
+                .long("hacc-thresholds")
+                .value_name("LIST")
+                .value_parser(value_parser!(f64))
+                .value_delimiter(',')
+                .help("hAcc thresholds in meters, comma separated, like \"0.05,1\"")
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compare the NAV-PVT solutions of two receivers recorded simultaneously, \
+epochs being aligned by UTC time")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .short('f')
+                        .long("fp")
+                        .action(ArgAction::Append)
+                        .required(true)
+                        .help("Log of receiver A, then log of receiver B: -f a.ubx -f b.ubx")
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .long("tolerance")
+                        .value_name("SECONDS")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1")
+                        .help("Largest time difference between matched epochs")
+                )
+                .arg(
+                    Arg::new("csv")
+                        .long("csv")
+                        .value_name("FILE")
+                        .help("Write one CSV row per matched epoch to FILE")
+                )
+        )
+        .subcommand(
+            Command::new("repair")
+                .about("Rewrite a recording keeping its valid UBX, NMEA and RTCM3 frames only, \
+garbage bytes and corrupted frames being dropped")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .short('f')
+                        .long("fp")
+                        .required(true)
+                        .help("Recording to repair, can be gzip compressed")
+                )
+                .arg(
+                    Arg::new("output")
+                        .value_name("FILE")
+                        .short('o')
+                        .long("output")
+                        .required(true)
+                        .help("Repaired recording, gzip compressed when named *.gz, zstd when \
+*.zst")
+                )
+                .arg(
+                    Arg::new("dedup")
+                        .long("dedup")
+                        .action(ArgAction::SetTrue)
+                        .help("Drop NAV frames identical to one of the last 256 NAV frames, \
+retransmits of their epoch")
+                )
+                .arg(
+                    Arg::new("trim-prelude")
+                        .long("trim-prelude")
+                        .action(ArgAction::SetTrue)
+                        .help("Drop the frames before the first NAV-PVT solution with a valid, \
+fully resolved date and time")
+                )
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measure the parse throughput of each code path, reading the file fully \
+without formatting any output")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .short('f')
+                        .long("fp")
+                        .help("Sample recording, can be gzip compressed. \
+A synthetic recording is generated when not provided")
+                )
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .default_value("5")
+                        .help("Passes over the file per code path, the median being reported")
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .value_name("MB")
+                        .value_parser(value_parser!(u64))
+                        .default_value("64")
+                        .help("Size of the synthetic recording")
+                )
+                .arg(
+                    Arg::new("mix")
+                        .long("mix")
+                        .value_name("LIST")
+                        .value_parser(Mix::parse)
+                        .default_value("pvt,sat,nmea")
+                        .help("Messages of each epoch of the synthetic recording, comma separated, \
+among pvt, sat, att, nmea and rtcm, with an optional count like \"pvt:5,sat\"")
+                )
+                .arg(
+                    Arg::new("save")
+                        .long("save")
+                        .value_name("FILE")
+                        .help("Keep the synthetic recording as FILE, instead of a temporary file")
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .value_name("FILE")
+                        .help("Also write the results as JSON to FILE, \"-\" for stdout")
+                )
         )
         .get_matches();
 
+    let level = logging::level(matches.get_flag("quiet"), matches.get_count("verbose"));
+    if let Err(e) = logging::init(level, matches.get_one::<String>("log-file").map(|s| s.as_str())) {
+        eprintln!("Failed to open the log file: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(("bench", sub_matches)) = matches.subcommand() {
+        let iterations = *sub_matches.get_one::<usize>("iterations").unwrap();
+        let (path, temporary) = match sub_matches.get_one::<String>("file") {
+            Some(path) => (path.clone(), false),
+            None => {
+                let (path, temporary) = match sub_matches.get_one::<String>("save") {
+                    Some(path) => (path.clone(), false),
+                    None => {
+                        let path = std::env::temp_dir()
+                            .join(format!("ubx-read-bench-{}.ubx", std::process::id()));
+                        (path.to_string_lossy().into_owned(), true)
+                    },
+                };
+                let size = *sub_matches.get_one::<u64>("size").unwrap() * 1_000_000;
+                bench::generate(&path, size, sub_matches.get_one::<Mix>("mix").unwrap())
+                    .unwrap_or_else(|e| panic!("failed to generate \"{}\": {}", path, e));
+                (path, temporary)
+            },
+        };
+        let results = bench::run(&path, iterations, || BufferedReader::new(&path, None));
+        if temporary {
+            let _ = std::fs::remove_file(&path);
+        }
+        let results = results.unwrap_or_else(|e| panic!("failed to read \"{}\": {}", path, e));
+        bench::write_table(&mut std::io::stdout().lock(), &results)
+            .expect("failed to write benchmark results");
+        if let Some(json) = sub_matches.get_one::<String>("json") {
+            let mut writer: Box<dyn Write> = if json == "-" {
+                Box::new(std::io::stdout())
+            } else {
+                let fd = File::create(json)
+                    .unwrap_or_else(|e| panic!("failed to create \"{}\": {}", json, e));
+                Box::new(BufWriter::new(fd))
+            };
+            bench::write_json(&mut writer, &path, iterations, &results)
+                .expect("failed to write benchmark results");
+        }
+        return;
+    }
+
+    if let Some(("repair", sub_matches)) = matches.subcommand() {
+        let path = sub_matches.get_one::<String>("output").unwrap();
+        let level = sub_matches.get_one::<u32>("compress-level").copied();
+        let mut writer = create(path, level);
+        let mut repair = Repair::new(sub_matches.get_flag("dedup"), sub_matches.get_flag("trim-prelude"));
+        let mut buf = [0; 2048];
+        let mut reader = BufferedReader::new(sub_matches.get_one::<String>("file").unwrap(), None);
+        let mut scanner = StreamScanner::default();
+        let mut error = None;
+        loop {
+            let size = reader.read(&mut buf).unwrap_or_else(|e| {
+                error = Some(e.to_string());
+                0
+            });
+            scanner.push(&buf[..size]);
+            while let Some((kind, frame)) = scanner.next_frame(size == 0) {
+                repair
+                    .push(&mut writer, kind, &frame)
+                    .expect("failed to write repaired recording");
+            }
+            if size == 0 {
+                break;
+            }
+        }
+        complete(writer);
+        repair
+            .write_report(&mut std::io::stdout().lock(), error.as_deref())
+            .expect("failed to write repair report");
+        return;
+    }
+
+    if let Some(("compare", sub_matches)) = matches.subcommand() {
+        let files = sub_matches
+            .get_many::<String>("file")
+            .unwrap()
+            .collect::<Vec<_>>();
+        if files.len() != 2 {
+            error!("Two logs are compared: -f a.ubx -f b.ubx");
+            std::process::exit(1);
+        }
+        let logs = files.iter().map(|fp| {
+            let mut log = compare::Log::default();
+            read_packets(fp, None, |packet| match packet {
+                PacketRef::NavPvt(pvt) => log.solution(&pvt),
+                PacketRef::NavSat(sat) => log.satellites(&sat),
+                _ => {},
+            });
+            log
+        });
+        let [a, b]: [compare::Log; 2] = logs.collect::<Vec<_>>().try_into().unwrap();
+        let comparison = Comparison::new(a, b, *sub_matches.get_one::<f64>("tolerance").unwrap());
+        if let Some(path) = sub_matches.get_one::<String>("csv") {
+            let fd = File::create(path)
+                .unwrap_or_else(|e| panic!("failed to create \"{}\": {}", path, e));
+            comparison
+                .write_csv(&mut BufWriter::new(fd))
+                .expect("failed to write comparison");
+        }
+        comparison
+            .write_summary(&mut std::io::stdout().lock())
+            .expect("failed to write comparison");
+        return;
+    }
+
     let fp = matches
         .get_one::<String>("file")
         .unwrap();
+    let sync_point = matches.get_one::<usize>("sync-point").copied();
+
+    if matches.get_flag("info") {
+        let mut buf = [0; 2048];
+        let mut reader = BufferedReader::new(fp, sync_point);
+        let mut scanner = StreamScanner::default();
+        let mut info = Info::default();
+        loop {
+            let size = reader.read(&mut buf).unwrap_or(0);
+            scanner.push(&buf[..size]);
+            while let Some((kind, frame)) = scanner.next_frame(size == 0) {
+                info.add(&kind, &frame);
+            }
+            if size == 0 {
+                break;
+            }
+        }
+        let mut output = output(&matches);
+        info.write(&mut output)
+            .expect("failed to write metadata");
+        complete(output);
+        return;
+    }
+
+    if matches.get_flag("strict") {
+        let mut buf = [0; 2048];
+        let mut reader = BufferedReader::new(fp, sync_point);
+        let mut scanner = StreamScanner::default();
+        let mut validation = Validation::default();
+        let mut error = None;
+        loop {
+            let size = reader.read(&mut buf).unwrap_or_else(|e| {
+                error = Some(e.to_string());
+                0
+            });
+            scanner.push(&buf[..size]);
+            while let Some((kind, frame)) = scanner.next_frame(size == 0) {
+                validation.add(&kind, &frame);
+            }
+            if size == 0 {
+                break;
+            }
+        }
+        if let Some(e) = error {
+            error!("\"{}\" truncated after {} bytes: {}", fp, validation.bytes, e);
+            std::process::exit(1);
+        }
+        if let Some(offset) = validation.first_invalid {
+            error!(
+                "\"{}\": {} bytes in {} chunks of invalid data, the first at offset {}",
+                fp, validation.invalid_bytes, validation.invalid, offset
+            );
+            std::process::exit(1);
+        }
+        println!("{} frames, {} bytes, all valid", validation.frames, validation.bytes);
+        return;
+    }
+
+    if matches.get_flag("summary") {
+        let mut buf = [0; 2048];
+        let mut reader = BufferedReader::new(fp, sync_point);
+        let mut scanner = StreamScanner::default();
+        let mut summary = Summary::default();
+        let mut error = None;
+        loop {
+            let size = reader.read(&mut buf).unwrap_or_else(|e| {
+                error = Some(e.to_string());
+                0
+            });
+            scanner.push(&buf[..size]);
+            while let Some((kind, frame)) = scanner.next_frame(size == 0) {
+                summary.add(kind, &frame);
+            }
+            if size == 0 {
+                break;
+            }
+        }
+        summary.input(reader.members(), error);
+        let mut output = output(&matches);
+        summary
+            .write(&mut output)
+            .expect("failed to write summary");
+        complete(output);
+        return;
+    }
+
+    if matches.get_flag("cn0-matrix") {
+        // two passes: columns first, rows then
+        let mut matrix = Cn0Matrix::new(output(&matches));
+        let mut satellites = Satellites::default();
+        read_packets(fp, sync_point, |packet| {
+            if let Some(epoch) = satellites.epoch(&packet) {
+                matrix.discover(&epoch);
+            }
+        });
+        let mut satellites = Satellites::default();
+        read_packets(fp, sync_point, |packet| {
+            if let Some(epoch) = satellites.epoch(&packet) {
+                matrix.export(&epoch).expect("failed to export NAV-SAT CN0");
+            }
+        });
+        matrix.finish().expect("failed to export NAV-SAT CN0");
+        complete(matrix.into_inner());
+        return;
+    }
+
+    let frame = match matches.get_one::<String>("frame").map(|s| s.as_str()) {
+        Some("utm") => Frame::Utm(matches.get_one::<UtmZone>("utm-zone").copied()),
+        Some("mgrs") => Frame::Mgrs(*matches.get_one::<u8>("mgrs-precision").unwrap()),
+        _ => Frame::Geodetic,
+    };
+
+    let headings = (matches.get_flag("heading")
+        || matches.get_flag("unwrap-heading")
+        || matches.contains_id("heading-window"))
+    .then(|| HeadingOptions {
+        unwrap: matches.get_flag("unwrap-heading"),
+        window: matches.get_one::<usize>("heading-window").copied().unwrap_or(1),
+        min_speed: *matches.get_one::<f64>("heading-min-speed").unwrap(),
+        max_accuracy: *matches.get_one::<f64>("heading-max-acc").unwrap(),
+    });
+
+    let timescale = matches.get_one::<Timescale>("timescale").copied();
+    let timestamps = Timestamps::new(timescale.unwrap_or(Timescale::Utc), 3);
+
+    let mut exporter = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("csv") => Some(PvtExporter::new(
+            output(&matches),
+            frame,
+            timestamps,
+            matches.get_flag("derived-speed").then(|| {
+                Derivation::new(
+                    *matches.get_one::<usize>("derived-window").unwrap(),
+                    *matches.get_one::<f64>("derived-max-gap").unwrap(),
+                )
+            }),
+            headings,
+            matches.get_flag("include-invalid"),
+        )),
+        _ => None,
+    };
+
+    let mut timemarks = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("tim-tm2") => Some(TimeMarkExporter::new(
+            output(&matches),
+            timescale.map(|scale| Timestamps::new(scale, 9)),
+        )),
+        _ => None,
+    };
+
+    let mut templated = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("template") => {
+            let Some(template) = matches.get_one::<Template>("template").cloned() else {
+                error!("--format template needs a --template");
+                std::process::exit(1);
+            };
+            Some(TemplateExporter::new(
+                output(&matches),
+                template,
+                matches.get_flag("per-packet"),
+                matches.get_flag("include-invalid"),
+            ))
+        },
+        _ => None,
+    };
+
+    let mut samples = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("esf-raw") => Some(EsfRawExporter::new(output(&matches))),
+        _ => None,
+    };
+
+    let mut attitudes = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("nav-att") => Some(AttitudeExporter::new(output(&matches))),
+        _ => None,
+    };
+
+    let mut attitude_report = matches
+        .get_flag("attitude-report")
+        .then(AttitudeReport::default);
+
+    let mut skyplot = matches.get_flag("skyplot").then(|| {
+        SkyplotExporter::new(
+            output(&matches),
+            *matches.get_one::<f64>("skyplot-decimate").unwrap(),
+        )
+    });
+
+    let mut sky_summary = matches
+        .get_flag("skyplot-summary")
+        .then(|| SkyplotSummary::new(*matches.get_one::<i8>("elevation-mask").unwrap()));
+
+    let mut availability = matches
+        .get_flag("availability")
+        .then(|| Availability::new(*matches.get_one::<f64>("expected-rate").unwrap()));
+
+    let mut ttff_report = matches
+        .get_flag("ttff-report")
+        .then(|| TtffReport::new(*matches.get_one::<f64>("ttff-gap").unwrap()));
+
+    let mut time_report = matches
+        .get_flag("time-report")
+        .then(|| TimeReport::new(*matches.get_one::<u64>("time-tolerance").unwrap()));
+
+    let mut times = match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("nav-time") => Some(TimeExporter::new(output(&matches))),
+        _ => None,
+    };
+
+    let mut events = matches.get_one::<String>("events").map(|format| {
+        EventLogger::new(
+            output(&matches),
+            match format.as_str() {
+                "json" => events::Format::Json,
+                _ => events::Format::Text,
+            },
+            timestamps,
+            *matches.get_one::<usize>("debounce").unwrap(),
+            matches
+                .get_many::<u8>("numsv-thresholds")
+                .map(|t| t.copied().collect())
+                .unwrap_or_default(),
+            matches
+                .get_many::<f64>("hacc-thresholds")
+                .map(|t| t.copied().collect())
+                .unwrap_or_default(),
+        )
+    });
+    if events.is_some() {
+        exporter = None;
+    }
 
     let mut buf = [0; 2048];
     let mut parser = Parser::default();
-    let mut reader = BufferedReader::new(fp);
+    let mut reader = BufferedReader::new(fp, sync_point);
+    let mut satellites = Satellites::default();
 
     while let Ok(size) = reader.read(&mut buf) {
-        if size > 0 {
-            let mut it = parser.consume(&buf[..size]);
-            while let Some(packet) = it.next() {
-                println!("{:?}", packet);
+        if size == 0 {
+            break;
+        }
+        // a timestamped recording is read a frame at a time
+        let reception = reader.reception();
+        if let Some(ref mut events) = events {
+            events.received(reception);
+        }
+        if let Some(ref mut exporter) = exporter {
+            exporter.received(reception);
+        }
+        let mut it = parser.consume(&buf[..size]);
+        while let Some(packet) = it.next() {
+            log_packet(&packet);
+            if let Some(ref mut events) = events {
+                match packet {
+                    Ok(PacketRef::NavPvt(pvt)) => events.process(&pvt).expect("failed to write event"),
+                    Ok(PacketRef::NavTimeLs(ls)) => events.leap_seconds(&ls),
+                    _ => {},
+                }
+                continue;
+            }
+            if let Some(ref mut timemarks) = timemarks {
+                match packet {
+                    Ok(PacketRef::TimTm2(tm)) => {
+                        timemarks.export(&tm).expect("failed to export TIM-TM2 time mark")
+                    },
+                    Ok(PacketRef::NavTimeLs(ls)) => timemarks.leap_seconds(&ls),
+                    _ => {},
+                }
+                continue;
+            }
+            if let Some(ref mut times) = times {
+                match packet {
+                    Ok(PacketRef::Unknown(unknown)) if (unknown.class, unknown.msg_id) == NAV_TIMEGPS => {
+                        if let Some(gps) = TimeGps::parse(unknown.payload) {
+                            times.gps(&gps).expect("failed to export NAV-TIMEGPS");
+                        }
+                    },
+                    Ok(PacketRef::NavTimeUTC(utc)) => times.utc(&utc).expect("failed to export NAV-TIMEUTC"),
+                    _ => {},
+                }
+                continue;
+            }
+            if let Some(ref mut report) = time_report {
+                match packet {
+                    Ok(PacketRef::Unknown(unknown)) if (unknown.class, unknown.msg_id) == NAV_TIMEGPS => {
+                        if let Some(gps) = TimeGps::parse(unknown.payload) {
+                            report.gps(&gps);
+                        }
+                    },
+                    Ok(PacketRef::NavTimeUTC(utc)) => report.utc(&utc),
+                    Ok(PacketRef::NavPvt(pvt)) => report.solution(&pvt),
+                    _ => {},
+                }
+                continue;
+            }
+            if let Some(ref mut skyplot) = skyplot {
+                if let Some(epoch) = packet.as_ref().ok().and_then(|p| satellites.epoch(p)) {
+                    skyplot.export(&epoch).expect("failed to export NAV-SAT skyplot");
+                }
+                continue;
+            }
+            if let Some(ref mut summary) = sky_summary {
+                if let Some(epoch) = packet.as_ref().ok().and_then(|p| satellites.epoch(p)) {
+                    summary.add(&epoch);
+                }
+                continue;
+            }
+            if let Some(ref mut report) = availability {
+                if let Ok(PacketRef::NavPvt(pvt)) = packet {
+                    report.solution(&pvt);
+                }
+                continue;
+            }
+            if let Some(ref mut report) = ttff_report {
+                if let Ok(PacketRef::NavPvt(pvt)) = packet {
+                    report.solution(&pvt);
+                }
+                continue;
+            }
+            if let Some(ref mut report) = attitude_report {
+                match packet {
+                    Ok(PacketRef::NavAtt(att)) => report.attitude(&att),
+                    Ok(PacketRef::NavPvt(pvt)) => report.solution(&pvt),
+                    _ => {},
+                }
+                continue;
             }
+            if let Some(ref mut attitudes) = attitudes {
+                if let Ok(PacketRef::NavAtt(att)) = packet {
+                    attitudes.export(&att).expect("failed to export NAV-ATT solution");
+                }
+                continue;
+            }
+            if let Some(ref mut templated) = templated {
+                match packet {
+                    Ok(PacketRef::NavPvt(pvt)) => {
+                        templated.pvt(&pvt).expect("failed to write templated line")
+                    },
+                    Ok(PacketRef::NavAtt(att)) => {
+                        templated.att(&att).expect("failed to write templated line")
+                    },
+                    Ok(PacketRef::NavTimeLs(ls)) => templated.leap_seconds(&ls),
+                    _ => {},
+                }
+                continue;
+            }
+            if let Some(ref mut samples) = samples {
+                if let Ok(PacketRef::EsfRaw(raw)) = packet {
+                    samples.export(&raw).expect("failed to export ESF-RAW samples");
+                }
+                continue;
+            }
+            match exporter {
+                Some(ref mut exporter) => match packet {
+                    Ok(PacketRef::NavPvt(pvt)) => {
+                        exporter.export(&pvt).expect("failed to export NAV-PVT solution")
+                    },
+                    Ok(PacketRef::NavHpPosLlh(hp)) => exporter
+                        .high_precision(&hp)
+                        .expect("failed to export NAV-PVT solution"),
+                    Ok(PacketRef::NavTimeLs(ls)) => exporter.leap_seconds(&ls),
+                    _ => {},
+                },
+                None => match reception {
+                    Some(reception) => println!("{} {:?}", reception.utc(), packet),
+                    None => println!("{:?}", packet),
+                },
+            }
+        }
+    }
+
+    if let Some(mut events) = events {
+        events.finish().expect("failed to write event");
+        complete(events.into_inner());
+    }
+
+    if let Some(mut timemarks) = timemarks {
+        timemarks.finish().expect("failed to export TIM-TM2 time mark");
+        complete(timemarks.into_inner());
+    }
+
+    if let Some(mut skyplot) = skyplot {
+        skyplot.finish().expect("failed to export NAV-SAT skyplot");
+        complete(skyplot.into_inner());
+    }
+
+    if let Some(ref summary) = sky_summary {
+        let mut output = output(&matches);
+        summary
+            .write(&mut output)
+            .expect("failed to write skyplot summary");
+        complete(output);
+    }
+
+    if let Some(ref report) = availability {
+        let mut output = output(&matches);
+        report
+            .write(&mut output)
+            .expect("failed to write availability report");
+        complete(output);
+    }
+
+    if let Some(mut times) = times {
+        times.finish().expect("failed to export NAV-TIMEGPS / NAV-TIMEUTC");
+        complete(times.into_inner());
+    }
+
+    if let Some(ref mut report) = time_report {
+        let mut output = output(&matches);
+        report
+            .write(&mut output)
+            .expect("failed to write time report");
+        complete(output);
+    }
+
+    if let Some(ref report) = ttff_report {
+        let mut output = output(&matches);
+        report
+            .write(&mut output)
+            .expect("failed to write TTFF report");
+        complete(output);
+    }
+
+    if let Some(ref report) = attitude_report {
+        let mut output = output(&matches);
+        report
+            .write(&mut output)
+            .expect("failed to write attitude report");
+        complete(output);
+    }
+
+    if let Some(mut attitudes) = attitudes {
+        attitudes.finish().expect("failed to export NAV-ATT solution");
+        complete(attitudes.into_inner());
+    }
+
+    if let Some(mut templated) = templated {
+        templated.finish().expect("failed to write templated line");
+        complete(templated.into_inner());
+    }
+
+    if let Some(mut samples) = samples {
+        samples.finish().expect("failed to export ESF-RAW samples");
+        complete(samples.into_inner());
+    }
+
+    if let Some(mut exporter) = exporter {
+        exporter.finish().expect("failed to export NAV-PVT solution");
+        complete(exporter.into_inner());
+    }
+}
+
+/// Destination of exports and reports: the output file, compressed according to its
+/// name, standard output otherwise
+fn output(matches: &clap::ArgMatches) -> Output {
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            create(path, matches.get_one::<u32>("compress-level").copied())
+        },
+        None => Output::stdout(),
+    }
+}
+
+/// Creates the output file, exiting on failure
+fn create(path: &str, level: Option<u32>) -> Output {
+    output::create(path, level).unwrap_or_else(|e| {
+        error!("failed to create \"{}\": {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+/// Finishes the output, exiting on failure: a compressed output left incomplete does not
+/// decompress
+fn complete(output: Output) {
+    if let Err(e) = output.finish() {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Parses every packet of the file, from a sync point if any
+fn read_packets<F: FnMut(PacketRef)>(fp: &str, sync_point: Option<usize>, mut f: F) {
+    let mut buf = [0; 2048];
+    let mut parser = Parser::default();
+    let mut reader = BufferedReader::new(fp, sync_point);
+    while let Ok(size) = reader.read(&mut buf) {
+        if size == 0 {
+            break;
         }
+        let mut it = parser.consume(&buf[..size]);
+        while let Some(packet) = it.next() {
+            log_packet(&packet);
+            if let Ok(packet) = packet {
+                f(packet);
+            }
+        }
+    }
+}
+
+/// Reports invalid packets, and every packet at the trace level
+fn log_packet(packet: &Result<PacketRef, ParserError>) {
+    match packet {
+        Ok(packet) => {
+            let (class, id) = packet.class_and_msg_id();
+            trace!("packet class 0x{:02x} id 0x{:02x}", class, id);
+        },
+        Err(e) => warn!("invalid UBX packet: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ubx_common::frame::frame;
+
+    #[test]
+    fn zstd_recording() {
+        let path = std::env::temp_dir()
+            .join(format!("ubx-read-{}-recording.ubx.zst", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let first = frame(0x01, 0x07, &[0x5a; 92]).repeat(20);
+        let second = frame(0x0a, 0x04, &[0x30; 40]);
+        // two zstd frames, read as one stream
+        let compressed = [
+            zstd::encode_all(first.as_slice(), 3).unwrap(),
+            zstd::encode_all(second.as_slice(), 19).unwrap(),
+        ]
+        .concat();
+        std::fs::write(&path, compressed).unwrap();
+        let mut data = vec![];
+        BufferedReader::new(&path, None).read_to_end(&mut data).unwrap();
+        let mut packets = vec![];
+        read_packets(&path, None, |packet| packets.push(packet.class_and_msg_id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(data, [first, second].concat());
+        assert_eq!(packets.len(), 21);
+        assert_eq!(packets[20], (0x0a, 0x04));
     }
 }
@@ -0,0 +1,91 @@
+//! Session metadata blocks embedded at the start of the recordings by
+//! `ubx-record --embed-metadata`: UBX frames of a class u-blox does not use,
+//! their payload a JSON object describing the session.
+
+use std::io::Write;
+
+use crate::scan::Kind;
+
+/// UBX class and id of the metadata block
+const METADATA: (u8, u8) = (0x7f, 0x01);
+
+pub fn is_metadata(kind: &Kind) -> bool {
+    *kind
+        == Kind::Ubx {
+            class: METADATA.0,
+            id: METADATA.1,
+        }
+}
+
+/// Metadata blocks of a recording, and where they were found
+#[derive(Debug, Default)]
+pub struct Info {
+    blocks: Vec<(usize, String)>,
+    offset: usize,
+}
+
+impl Info {
+    /// Accounts for a frame found by the stream scanner
+    pub fn add(&mut self, kind: &Kind, frame: &[u8]) {
+        if is_metadata(kind) {
+            let payload = &frame[6..frame.len() - 2];
+            self.blocks
+                .push((self.offset, String::from_utf8_lossy(payload).into_owned()));
+        }
+        self.offset += frame.len();
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        if self.blocks.is_empty() {
+            return writeln!(writer, "no metadata block (ubx-record --embed-metadata)");
+        }
+        for (offset, json) in &self.blocks {
+            writeln!(writer, "metadata block at offset {}: {}", offset, json)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::StreamScanner;
+    use ubx_common::frame::frame;
+
+    fn info(data: &[u8]) -> String {
+        let mut scanner = StreamScanner::default();
+        scanner.push(data);
+        let mut info = Info::default();
+        while let Some((kind, frame)) = scanner.next_frame(true) {
+            info.add(&kind, &frame);
+        }
+        let mut output = vec![];
+        info.write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn blocks_and_offsets() {
+        let block = frame(0x7f, 0x01, b"{\"tool\":\"ubx-record\"}");
+        let pvt = frame(0x01, 0x07, &[0; 92]);
+        let data = [block.as_slice(), &pvt, b"junk", &block].concat();
+        assert_eq!(
+            info(&data),
+            "metadata block at offset 0: {\"tool\":\"ubx-record\"}
+metadata block at offset 133: {\"tool\":\"ubx-record\"}
+"
+        );
+        assert_eq!(info(&pvt), "no metadata block (ubx-record --embed-metadata)\n");
+        // other ids of the class are not metadata
+        assert!(!is_metadata(&Kind::Ubx { class: 0x7f, id: 0x02 }));
+    }
+
+    #[test]
+    fn largest_block() {
+        // 4096 bytes of JSON, the limit of ubx-record
+        let json = format!("{{\"command_line\":\"{}\"}}", "a".repeat(4096 - 19));
+        assert_eq!(json.len(), 4096);
+        let block = frame(0x7f, 0x01, json.as_bytes());
+        assert_eq!(info(&block), format!("metadata block at offset 0: {}\n", json));
+    }
+}
@@ -0,0 +1,302 @@
+//! NAV-SAT satellite tracking export and sky coverage, from the NAV-SVINFO of older
+//! firmware in recordings without NAV-SAT
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+use ubx_common::satellites::{Epoch, Source, NAV_SAT, NAV_SVINFO};
+use ublox::PacketRef;
+
+/// Satellite name, RINEX style like "G05", "E12" or "R14"
+pub fn sv_name(gnss_id: u8, sv_id: u8) -> String {
+    match gnss_id {
+        0 => format!("G{:02}", sv_id),
+        // SBAS PRNs 120 to 158
+        1 => format!("S{:02}", sv_id.saturating_sub(100)),
+        2 => format!("E{:02}", sv_id),
+        3 => format!("C{:02}", sv_id),
+        4 => format!("M{:02}", sv_id),
+        5 => format!("J{:02}", sv_id),
+        6 => format!("R{:02}", sv_id),
+        7 => format!("I{:02}", sv_id),
+        _ => format!("X{}-{:02}", gnss_id, sv_id),
+    }
+}
+
+/// Satellites of the NAV-SAT messages of a recording, or of its NAV-SVINFO messages until a
+/// NAV-SAT one is read
+pub struct Satellites {
+    source: Source,
+}
+
+impl Default for Satellites {
+    fn default() -> Self {
+        Self {
+            source: Source::new(&[NAV_SAT, NAV_SVINFO]),
+        }
+    }
+}
+
+impl Satellites {
+    /// Satellites of a packet, none for other packets, the malformed ones being reported
+    pub fn epoch(&mut self, packet: &PacketRef) -> Option<Epoch> {
+        // NAV-SVINFO is not decoded by the ublox crate
+        let (message, payload) = match packet {
+            PacketRef::NavSat(sat) => (NAV_SAT, sat.as_bytes()),
+            PacketRef::Unknown(unknown) => ((unknown.class, unknown.msg_id), unknown.payload),
+            _ => return None,
+        };
+        self.source.epoch(message, payload).unwrap_or_else(|e| {
+            warn!("{}", e);
+            None
+        })
+    }
+}
+
+/// Exports the CN0 of each satellite as a wide CSV, one row per NAV-SAT epoch
+/// and one column per satellite.
+///
+/// The columns are discovered on a first pass over the log ([Cn0Matrix::discover]),
+/// the rows being written on a second one ([Cn0Matrix::export]): memory only depends on
+/// the number of satellites, whatever the length of the log.
+pub struct Cn0Matrix<W: Write> {
+    writer: W,
+    satellites: BTreeSet<(u8, u8)>,
+    /// Column of each satellite, once discovery is over
+    columns: Option<HashMap<(u8, u8), usize>>,
+    row: Vec<Option<u8>>,
+}
+
+impl<W: Write> Cn0Matrix<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            satellites: BTreeSet::new(),
+            columns: None,
+            row: Vec::new(),
+        }
+    }
+
+    /// First pass: collects the satellites
+    pub fn discover(&mut self, epoch: &Epoch) {
+        for sv in &epoch.satellites {
+            self.satellites.insert((sv.gnss_id, sv.sv_id));
+        }
+    }
+
+    /// Second pass: writes the CN0 of the epoch, empty for satellites not tracked
+    pub fn export(&mut self, epoch: &Epoch) -> std::io::Result<()> {
+        if self.columns.is_none() {
+            let mut names = self
+                .satellites
+                .iter()
+                .map(|(gnss_id, sv_id)| ((*gnss_id, *sv_id), sv_name(*gnss_id, *sv_id)))
+                .collect::<Vec<_>>();
+            names.sort_by(|(_, a), (_, b)| a.cmp(b));
+            write!(self.writer, "itow")?;
+            for (_, name) in &names {
+                write!(self.writer, ",{}", name)?;
+            }
+            writeln!(self.writer)?;
+            self.row = vec![None; names.len()];
+            self.columns = Some(
+                names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(column, (sv, _))| (sv, column))
+                    .collect(),
+            );
+        }
+        let columns = self.columns.as_ref().unwrap();
+
+        self.row.iter_mut().for_each(|cell| *cell = None);
+        for sv in &epoch.satellites {
+            // discovered on the first pass
+            if let Some(column) = columns.get(&(sv.gnss_id, sv.sv_id)) {
+                if sv.tracked() {
+                    self.row[*column] = Some(sv.cno);
+                }
+            }
+        }
+        write!(self.writer, "{}", epoch.itow)?;
+        for cell in &self.row {
+            match cell {
+                Some(cno) => write!(self.writer, ",{}", cno)?,
+                None => write!(self.writer, ",")?,
+            }
+        }
+        writeln!(self.writer)
+    }
+
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Exports one CSV row per satellite and NAV-SAT epoch, for polar plots
+pub struct SkyplotExporter<W: Write> {
+    writer: W,
+    /// Minimum interval between samples of a satellite (ms)
+    decimation_ms: u32,
+    /// iTOW of the last sample of each satellite
+    last: HashMap<(u8, u8), u32>,
+    header_written: bool,
+}
+
+impl<W: Write> SkyplotExporter<W> {
+    /// Exports one sample per satellite every `decimation` seconds at most
+    pub fn new(writer: W, decimation: f64) -> Self {
+        Self {
+            writer,
+            decimation_ms: (decimation * 1000.0) as u32,
+            last: HashMap::new(),
+            header_written: false,
+        }
+    }
+
+    pub fn export(&mut self, epoch: &Epoch) -> std::io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "itow,sv,azimuth,elevation,cn0,used")?;
+            self.header_written = true;
+        }
+        let itow = epoch.itow;
+        for sv in &epoch.satellites {
+            let (Some(elev), Some(azim)) = (sv.elev, sv.azim) else {
+                continue;
+            };
+            let key = (sv.gnss_id, sv.sv_id);
+            // iTOW going backwards: week rollover or restart
+            if let Some(last) = self.last.get(&key) {
+                if itow >= *last && itow - last < self.decimation_ms {
+                    continue;
+                }
+            }
+            self.last.insert(key, itow);
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                itow,
+                sv_name(sv.gnss_id, sv.sv_id),
+                azim,
+                elev,
+                sv.cno,
+                u8::from(sv.used)
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Coverage {
+    epochs: usize,
+    max_elevation: i8,
+    epochs_above_mask: usize,
+}
+
+/// Elevation coverage of each satellite
+pub struct SkyplotSummary {
+    /// Elevation mask (degrees)
+    mask: i8,
+    satellites: HashMap<(u8, u8), Coverage>,
+    epochs: usize,
+    last_itow: Option<u32>,
+    /// Positive iTOW steps, for the nominal epoch period
+    steps: Vec<u32>,
+}
+
+impl SkyplotSummary {
+    pub fn new(mask: i8) -> Self {
+        Self {
+            mask,
+            satellites: HashMap::new(),
+            epochs: 0,
+            last_itow: None,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, epoch: &Epoch) {
+        let itow = epoch.itow;
+        if let Some(last) = self.last_itow {
+            if itow > last {
+                self.steps.push(itow - last);
+            }
+        }
+        self.last_itow = Some(itow);
+        self.epochs += 1;
+        for sv in &epoch.satellites {
+            let Some(elev) = sv.elev else {
+                continue;
+            };
+            let coverage = self
+                .satellites
+                .entry((sv.gnss_id, sv.sv_id))
+                .or_insert(Coverage {
+                    max_elevation: elev,
+                    ..Default::default()
+                });
+            coverage.epochs += 1;
+            coverage.max_elevation = coverage.max_elevation.max(elev);
+            if elev >= self.mask {
+                coverage.epochs_above_mask += 1;
+            }
+        }
+    }
+
+    /// Median positive iTOW step (s), 1 s when unknown
+    fn period(&self) -> f64 {
+        let mut steps = self.steps.clone();
+        steps.sort_unstable();
+        steps
+            .get(steps.len() / 2)
+            .map(|step| f64::from(*step) / 1000.0)
+            .unwrap_or(1.0)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let period = self.period();
+        writeln!(
+            writer,
+            "{} NAV-SAT epochs, {:.1} s epoch period, {} deg elevation mask",
+            self.epochs, period, self.mask
+        )?;
+        writeln!(
+            writer,
+            "{:<6} {:>8} {:>9} {:>12} {:>8}",
+            "sv", "epochs", "max elev", "above mask", "share"
+        )?;
+        let mut satellites = self
+            .satellites
+            .iter()
+            .map(|((gnss_id, sv_id), coverage)| (sv_name(*gnss_id, *sv_id), coverage))
+            .collect::<Vec<_>>();
+        satellites.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, coverage) in satellites {
+            writeln!(
+                writer,
+                "{:<6} {:>8} {:>9} {:>10.0} s {:>7.1}%",
+                name,
+                coverage.epochs,
+                coverage.max_elevation,
+                coverage.epochs_above_mask as f64 * period,
+                100.0 * coverage.epochs_above_mask as f64 / self.epochs as f64
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! Output files of the exports and reports, compressed according to their name.
+//!
+//! Exports of long recordings are far larger than the recordings themselves: an output
+//! named `*.gz` is gzip compressed while it is written, one named `*.zst` zstd compressed.
+//! The compressed stream is completed by [Output::finish], once everything is written: a
+//! stream left incomplete does not decompress.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// Gzip level of the outputs, unless set
+const GZIP_DEFAULT_LEVEL: u32 = 6;
+/// zstd level of the outputs, unless set
+const ZSTD_DEFAULT_LEVEL: u32 = 3;
+/// Highest gzip level, zstd going up to 22
+const GZIP_MAX_LEVEL: u32 = 9;
+
+enum Stream {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Stdout(std::io::StdoutLock<'static>),
+}
+
+/// Output file, or the standard output
+pub struct Output {
+    /// Name of the file, for the errors
+    path: String,
+    stream: Stream,
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Self {
+            path: "standard output".to_string(),
+            stream: Stream::Stdout(std::io::stdout().lock()),
+        }
+    }
+
+    /// Completes the compressed stream, and writes everything out
+    pub fn finish(self) -> std::io::Result<()> {
+        let finished = match self.stream {
+            Stream::Plain(mut writer) => writer.flush(),
+            Stream::Gzip(encoder) => encoder.finish().and_then(|mut writer| writer.flush()),
+            Stream::Zstd(encoder) => encoder.finish().and_then(|mut writer| writer.flush()),
+            Stream::Stdout(mut writer) => writer.flush(),
+        };
+        finished.map_err(|e| {
+            std::io::Error::new(e.kind(), format!("failed to finish \"{}\": {}", self.path, e))
+        })
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.stream {
+            Stream::Plain(writer) => writer.write(buf),
+            Stream::Gzip(encoder) => encoder.write(buf),
+            Stream::Zstd(encoder) => encoder.write(buf),
+            Stream::Stdout(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.stream {
+            Stream::Plain(writer) => writer.flush(),
+            Stream::Gzip(encoder) => encoder.flush(),
+            Stream::Zstd(encoder) => encoder.flush(),
+            Stream::Stdout(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Creates the output file, gzip compressed when named `*.gz` and zstd compressed when named
+/// `*.zst`, at `level` or else the default level of the codec
+pub fn create(path: &str, level: Option<u32>) -> std::io::Result<Output> {
+    let gzip = path.ends_with(".gz");
+    let zstd = path.ends_with(".zst");
+    if let (true, Some(level)) = (gzip, level.filter(|level| *level > GZIP_MAX_LEVEL)) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("gzip compression levels go from 0 to {}, not {}", GZIP_MAX_LEVEL, level),
+        ));
+    }
+    let fd = BufWriter::new(File::create(path)?);
+    let stream = if gzip {
+        let level = level.unwrap_or(GZIP_DEFAULT_LEVEL);
+        Stream::Gzip(GzEncoder::new(fd, Compression::new(level)))
+    } else if zstd {
+        let level = level.unwrap_or(ZSTD_DEFAULT_LEVEL);
+        Stream::Zstd(zstd::Encoder::new(fd, level as i32)?)
+    } else {
+        Stream::Plain(fd)
+    };
+    Ok(Output {
+        path: path.to_string(),
+        stream,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Written to a temporary output of given extension, then read back
+    fn written(extension: &str, level: Option<u32>, data: &[u8]) -> Vec<u8> {
+        let path = std::env::temp_dir()
+            .join(format!("ubx-read-{}-output.csv{}", std::process::id(), extension))
+            .to_string_lossy()
+            .into_owned();
+        let mut output = create(&path, level).unwrap();
+        output.write_all(data).unwrap();
+        output.finish().unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        written
+    }
+
+    #[test]
+    fn compressed_outputs() {
+        let data = b"itow,lat,lon\n0,47.28,8.56\n".repeat(100);
+        assert_eq!(written("", None, &data), data);
+        let mut decoded = vec![];
+        let compressed = written(".gz", Some(9), &data);
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+        // the level of zstd goes beyond the gzip levels
+        let compressed = written(".zst", Some(19), &data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), data);
+    }
+
+    #[test]
+    fn gzip_level_range() {
+        let path = std::env::temp_dir().join("ubx-read-level.csv.gz");
+        let error = create(path.to_str().unwrap(), Some(19)).err().unwrap();
+        assert_eq!(error.to_string(), "gzip compression levels go from 0 to 9, not 19");
+        // rejected before the file is created
+        assert!(!path.exists());
+    }
+}
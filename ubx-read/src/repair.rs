@@ -0,0 +1,282 @@
+//! Rewriting of a recording keeping its valid frames only.
+//!
+//! Bytes belonging to no valid UBX, NMEA or RTCM3 frame (garbage, corrupted or truncated
+//! frames) are dropped. Optionally dropped as well: repeated NAV frames, identical to one
+//! of the last ones (NAV messages carry the iTOW of their epoch, so these are retransmits,
+//! like replayed buffers), and everything before the first NAV-PVT solution with a valid
+//! time. Other messages legitimately repeat (MON-VER replies, RXM-SFRBX subframes
+//! broadcast again) and are kept.
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+use ubx_common::frame::SYNC;
+
+use crate::export::{FULLY_RESOLVED, VALID_DATE, VALID_TIME};
+use crate::scan::{Kind, UBX_MAX_PAYLOAD_LEN};
+
+/// UBX NAV class, the only one deduplicated
+const NAV: u8 = 0x01;
+
+/// UBX NAV-PVT class and id
+const NAV_PVT: (u8, u8) = (0x01, 0x07);
+
+/// Frame offset of NAV-PVT `valid`: header, then payload offset 11
+const NAV_PVT_VALID_OFFSET: usize = 6 + 11;
+
+/// Recent NAV frames a repeat is looked for in
+const DUPLICATE_WINDOW: usize = 256;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Count {
+    frames: usize,
+    bytes: usize,
+}
+
+impl Count {
+    fn add(&mut self, bytes: usize) {
+        self.frames += 1;
+        self.bytes += bytes;
+    }
+}
+
+pub struct Repair {
+    dedup: bool,
+    trim_prelude: bool,
+    /// Still in the prelude, frames being dropped until a valid time
+    prelude: bool,
+    recent: VecDeque<Vec<u8>>,
+    input: usize,
+    kept: Count,
+    /// Chunks of bytes belonging to no valid frame
+    unknown: Count,
+    /// UBX frames among the unknown bytes, corrupted or truncated
+    corrupted: usize,
+    duplicates: Count,
+    trimmed: Count,
+}
+
+impl Repair {
+    pub fn new(dedup: bool, trim_prelude: bool) -> Self {
+        Self {
+            dedup,
+            trim_prelude,
+            prelude: trim_prelude,
+            recent: VecDeque::new(),
+            input: 0,
+            kept: Count::default(),
+            unknown: Count::default(),
+            corrupted: 0,
+            duplicates: Count::default(),
+            trimmed: Count::default(),
+        }
+    }
+
+    /// Copies a frame found by the stream scanner, unless dropped
+    pub fn push<W: Write>(
+        &mut self,
+        writer: &mut W,
+        kind: Kind,
+        frame: &[u8],
+    ) -> std::io::Result<()> {
+        self.input += frame.len();
+        let ubx = match kind {
+            Kind::Unknown => {
+                self.unknown.add(frame.len());
+                self.corrupted += corrupted_frames(frame);
+                return Ok(());
+            },
+            Kind::Ubx { class, id } => Some((class, id)),
+            _ => None,
+        };
+        if self.prelude {
+            let valid = VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+            let time_valid = ubx == Some(NAV_PVT)
+                && frame
+                    .get(NAV_PVT_VALID_OFFSET)
+                    .is_some_and(|flags| flags & valid == valid);
+            if !time_valid {
+                self.trimmed.add(frame.len());
+                return Ok(());
+            }
+            self.prelude = false;
+        }
+        if self.dedup && ubx.is_some_and(|(class, _)| class == NAV) {
+            if self.recent.iter().any(|recent| recent == frame) {
+                self.duplicates.add(frame.len());
+                return Ok(());
+            }
+            if self.recent.len() == DUPLICATE_WINDOW {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(frame.to_vec());
+        }
+        self.kept.add(frame.len());
+        writer.write_all(frame)
+    }
+
+    pub fn write_report<W: Write>(
+        &self,
+        writer: &mut W,
+        read_error: Option<&str>,
+    ) -> std::io::Result<()> {
+        writeln!(writer, "bytes in: {}", self.input)?;
+        writeln!(
+            writer,
+            "bytes out: {} ({} frames)",
+            self.kept.bytes, self.kept.frames
+        )?;
+        writeln!(
+            writer,
+            "dropped: {} bytes in {} chunks of invalid data, {} corrupted or truncated UBX \
+frames among them",
+            self.unknown.bytes, self.unknown.frames, self.corrupted
+        )?;
+        if self.dedup {
+            writeln!(
+                writer,
+                "dropped: {} duplicate frames ({} bytes)",
+                self.duplicates.frames, self.duplicates.bytes
+            )?;
+        }
+        if self.trim_prelude {
+            writeln!(
+                writer,
+                "dropped: {} frames ({} bytes) before the first valid time{}",
+                self.trimmed.frames,
+                self.trimmed.bytes,
+                if self.prelude { ", none found" } else { "" }
+            )?;
+        }
+        if let Some(e) = read_error {
+            writeln!(writer, "input truncated: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the UBX frames of invalid data: headers, each one covering its declared length
+/// (up to the end of the data for a truncated frame), so that a sync pair within a
+/// corrupted payload is not counted again
+fn corrupted_frames(data: &[u8]) -> usize {
+    let mut frames = 0;
+    let mut offset = 0;
+    while offset + 1 < data.len() {
+        if data[offset..offset + 2] != SYNC {
+            offset += 1;
+            continue;
+        }
+        frames += 1;
+        let len = data
+            .get(offset + 4..offset + 6)
+            .map(|len| usize::from(u16::from_le_bytes([len[0], len[1]])));
+        offset += match len {
+            Some(len) if len <= UBX_MAX_PAYLOAD_LEN => len + 8,
+            // header truncated or length corrupted: the payload can't be skipped
+            _ => 2,
+        };
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::{StreamScanner, Validation};
+    use ubx_common::frame::frame;
+
+    /// NAV-PVT of given iTOW, time valid or not
+    fn nav_pvt(itow: u32, time_valid: bool) -> Vec<u8> {
+        let mut payload = vec![0; 92];
+        payload[..4].copy_from_slice(&itow.to_le_bytes());
+        if time_valid {
+            payload[11] = VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        }
+        frame(NAV_PVT.0, NAV_PVT.1, &payload)
+    }
+
+    /// Frames of the stream, scanned in small chunks
+    fn scanned(data: &[u8]) -> Vec<(Kind, Vec<u8>)> {
+        let mut scanner = StreamScanner::default();
+        let mut frames = vec![];
+        for chunk in data.chunks(7) {
+            scanner.push(chunk);
+            while let Some(frame) = scanner.next_frame(false) {
+                frames.push(frame);
+            }
+        }
+        while let Some(frame) = scanner.next_frame(true) {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn repaired(repair: &mut Repair, data: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        for (kind, frame) in scanned(data) {
+            repair.push(&mut output, kind, &frame).unwrap();
+        }
+        output
+    }
+
+    fn validated(data: &[u8]) -> Validation {
+        let mut validation = Validation::default();
+        for (kind, frame) in scanned(data) {
+            validation.add(&kind, &frame);
+        }
+        validation
+    }
+
+    /// Recording of 4 epochs with garbage, a corrupted checksum, a retransmitted NAV-PVT,
+    /// twice the same MON-VER reply and a truncated frame
+    fn corrupted() -> Vec<u8> {
+        let mon_ver = frame(0x0a, 0x04, b"ROM CORE 3.01");
+        let mut corrupted = nav_pvt(1000, true);
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        [
+            nav_pvt(0, false),
+            b"\x00\xff garbage".to_vec(),
+            nav_pvt(1000, true),
+            mon_ver.clone(),
+            nav_pvt(1000, true),
+            corrupted,
+            b"$GNGGA,120000.00,,,,,0,00,99.99,,,,,,\r\n".to_vec(),
+            mon_ver,
+            nav_pvt(2000, true),
+            nav_pvt(3000, true)[..40].to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn repaired_output_validates() {
+        let data = corrupted();
+        let validation = validated(&data);
+        assert_eq!(validation.first_invalid, Some(100));
+        let mut repair = Repair::new(true, true);
+        let output = repaired(&mut repair, &data);
+        let validation = validated(&output);
+        assert_eq!(validation.invalid, 0);
+        assert_eq!(validation.bytes, output.len());
+        // PVT 1000, MON-VER twice, GGA and PVT 2000
+        assert_eq!(validation.frames, 5);
+        assert_eq!(repair.duplicates.frames, 1);
+        assert_eq!(repair.trimmed.frames, 1);
+        assert_eq!(repair.corrupted, 2);
+    }
+
+    #[test]
+    fn corrupted_frame_count() {
+        let pvt = nav_pvt(1000, true);
+        // a sync pair within a corrupted payload is not another frame
+        let mut payload = vec![0; 20];
+        payload[4..6].copy_from_slice(&SYNC);
+        let mut corrupted = frame(0x01, 0x07, &payload);
+        corrupted[10] ^= 0xff;
+        assert_eq!(corrupted_frames(&corrupted), 1);
+        assert_eq!(corrupted_frames(&[corrupted.as_slice(), &pvt[..50]].concat()), 2);
+        assert_eq!(corrupted_frames(b"\x00\xb5\x62\x01"), 1);
+        assert_eq!(corrupted_frames(b"no frame"), 0);
+    }
+}
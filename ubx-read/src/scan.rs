@@ -0,0 +1,197 @@
+//! Splitting of the recorded byte stream into UBX, NMEA and RTCM3 frames
+
+use ubx_common::frame::{checksum, crc24q};
+
+const UBX_SYNC_CHAR_1: u8 = 0xb5;
+const UBX_SYNC_CHAR_2: u8 = 0x62;
+const NMEA_START: u8 = b'$';
+const RTCM_PREAMBLE: u8 = 0xd3;
+
+/// NMEA sentences are at most 82 characters long, some receivers exceeding it slightly
+const NMEA_MAX_LEN: usize = 128;
+
+/// UBX frames larger than this are considered corrupted
+pub const UBX_MAX_PAYLOAD_LEN: usize = 8192;
+
+/// Frame found in the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Ubx {
+        class: u8,
+        id: u8,
+    },
+    /// Sentence type, talker excluded
+    Nmea(String),
+    /// Message number
+    Rtcm(u16),
+    /// Bytes belonging to no valid frame
+    Unknown,
+}
+
+/// Splits the stream into frames, whatever their protocol,
+/// invalid data being returned as [Kind::Unknown] chunks
+#[derive(Default)]
+pub struct StreamScanner {
+    buf: Vec<u8>,
+}
+
+enum Found {
+    /// Frame of given length
+    Frame(Kind, usize),
+    /// Not a frame start
+    Invalid,
+    /// More data needed
+    Incomplete,
+}
+
+impl StreamScanner {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the next frame and its bytes. At the end of the stream
+    /// (`eof`), incomplete frames are returned as unknown data.
+    pub fn next_frame(&mut self, eof: bool) -> Option<(Kind, Vec<u8>)> {
+        let mut unknown = 0;
+        while unknown < self.buf.len() {
+            match self.frame_at(unknown) {
+                Found::Frame(kind, len) => {
+                    if unknown > 0 {
+                        return Some((Kind::Unknown, self.buf.drain(..unknown).collect()));
+                    }
+                    return Some((kind, self.buf.drain(..len).collect()));
+                },
+                Found::Incomplete if !eof => break,
+                _ => unknown += 1,
+            }
+        }
+        if unknown > 0 {
+            return Some((Kind::Unknown, self.buf.drain(..unknown).collect()));
+        }
+        None
+    }
+
+    fn frame_at(&self, start: usize) -> Found {
+        let data = &self.buf[start..];
+        match data[0] {
+            UBX_SYNC_CHAR_1 => ubx_frame(data),
+            NMEA_START => nmea_frame(data),
+            RTCM_PREAMBLE => rtcm_frame(data),
+            _ => Found::Invalid,
+        }
+    }
+}
+
+fn ubx_frame(data: &[u8]) -> Found {
+    if data.len() < 2 {
+        return Found::Incomplete;
+    }
+    if data[1] != UBX_SYNC_CHAR_2 {
+        return Found::Invalid;
+    }
+    if data.len() < 6 {
+        return Found::Incomplete;
+    }
+    let len = usize::from(u16::from_le_bytes([data[4], data[5]]));
+    if len > UBX_MAX_PAYLOAD_LEN {
+        return Found::Invalid;
+    }
+    if data.len() < len + 8 {
+        return Found::Incomplete;
+    }
+    if checksum(&data[2..len + 6]) != (data[len + 6], data[len + 7]) {
+        return Found::Invalid;
+    }
+    Found::Frame(
+        Kind::Ubx {
+            class: data[2],
+            id: data[3],
+        },
+        len + 8,
+    )
+}
+
+fn nmea_frame(data: &[u8]) -> Found {
+    let end = data
+        .iter()
+        .take(NMEA_MAX_LEN)
+        .position(|byte| *byte == b'\n');
+    let Some(end) = end else {
+        return if data.len() < NMEA_MAX_LEN {
+            Found::Incomplete
+        } else {
+            Found::Invalid
+        };
+    };
+    let Ok(sentence) = std::str::from_utf8(&data[1..end]) else {
+        return Found::Invalid;
+    };
+    let sentence = sentence.trim_end_matches('\r');
+    let (body, checksum) = match sentence.split_once('*') {
+        Some((body, checksum)) => (body, Some(checksum)),
+        None => (sentence, None),
+    };
+    if let Some(checksum) = checksum {
+        let expected = body.bytes().fold(0, |acc, byte| acc ^ byte);
+        if u8::from_str_radix(checksum, 16) != Ok(expected) {
+            return Found::Invalid;
+        }
+    }
+    let address = body.split(',').next().unwrap_or_default();
+    if address.len() < 3 || !address.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Found::Invalid;
+    }
+    // proprietary sentences ("PUBX") have no talker
+    let sentence_type = if address.starts_with('P') {
+        address
+    } else {
+        &address[2..]
+    };
+    Found::Frame(Kind::Nmea(sentence_type.to_string()), end + 1)
+}
+
+fn rtcm_frame(data: &[u8]) -> Found {
+    if data.len() < 3 {
+        return Found::Incomplete;
+    }
+    // 6 reserved bits, then a 10 bit length
+    if data[1] & 0xfc != 0 {
+        return Found::Invalid;
+    }
+    let len = usize::from(data[1] & 0x03) << 8 | usize::from(data[2]);
+    if data.len() < len + 6 {
+        return Found::Incomplete;
+    }
+    let crc =
+        u32::from(data[len + 3]) << 16 | u32::from(data[len + 4]) << 8 | u32::from(data[len + 5]);
+    if crc24q(&data[..len + 3]) != crc || len < 2 {
+        return Found::Invalid;
+    }
+    let number = u16::from(data[3]) << 4 | u16::from(data[4]) >> 4;
+    Found::Frame(Kind::Rtcm(number), len + 6)
+}
+
+/// Strict validation of a stream: every byte must belong to a valid frame
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub frames: usize,
+    pub bytes: usize,
+    /// Chunks of bytes belonging to no valid frame
+    pub invalid: usize,
+    pub invalid_bytes: usize,
+    /// Offset of the first invalid chunk
+    pub first_invalid: Option<usize>,
+}
+
+impl Validation {
+    pub fn add(&mut self, kind: &Kind, frame: &[u8]) {
+        if *kind == Kind::Unknown {
+            self.first_invalid.get_or_insert(self.bytes);
+            self.invalid += 1;
+            self.invalid_bytes += frame.len();
+        } else {
+            self.frames += 1;
+        }
+        self.bytes += frame.len();
+    }
+}
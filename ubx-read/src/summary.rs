@@ -0,0 +1,347 @@
+//! Stream composition: frames, bytes and bandwidth per message type, the sensor fusion
+//! of the dead reckoning receivers (ESF-STATUS), and the text messages of the receiver (INF)
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use ubx_common::esf::{FusionMode, Status, ESF_STATUS};
+use ubx_common::inf::{self, INF};
+
+use crate::metadata::is_metadata;
+use crate::scan::Kind;
+
+/// Names of the common UBX messages
+const UBX_MESSAGES: &[(u8, u8, &str)] = &[
+    (0x01, 0x01, "NAV-POSECEF"),
+    (0x01, 0x02, "NAV-POSLLH"),
+    (0x01, 0x03, "NAV-STATUS"),
+    (0x01, 0x04, "NAV-DOP"),
+    (0x01, 0x05, "NAV-ATT"),
+    (0x01, 0x06, "NAV-SOL"),
+    (0x01, 0x07, "NAV-PVT"),
+    (0x01, 0x09, "NAV-ODO"),
+    (0x01, 0x11, "NAV-VELECEF"),
+    (0x01, 0x12, "NAV-VELNED"),
+    (0x01, 0x13, "NAV-HPPOSECEF"),
+    (0x01, 0x14, "NAV-HPPOSLLH"),
+    (0x01, 0x20, "NAV-TIMEGPS"),
+    (0x01, 0x21, "NAV-TIMEUTC"),
+    (0x01, 0x22, "NAV-CLOCK"),
+    (0x01, 0x26, "NAV-TIMELS"),
+    (0x01, 0x30, "NAV-SVINFO"),
+    (0x01, 0x32, "NAV-SBAS"),
+    (0x01, 0x35, "NAV-SAT"),
+    (0x01, 0x39, "NAV-GEOFENCE"),
+    (0x01, 0x3b, "NAV-SVIN"),
+    (0x01, 0x3c, "NAV-RELPOSNED"),
+    (0x01, 0x43, "NAV-SIG"),
+    (0x01, 0x60, "NAV-AOPSTATUS"),
+    (0x01, 0x61, "NAV-EOE"),
+    (0x02, 0x13, "RXM-SFRBX"),
+    (0x02, 0x14, "RXM-MEASX"),
+    (0x02, 0x15, "RXM-RAWX"),
+    (0x02, 0x32, "RXM-RTCM"),
+    (0x02, 0x41, "RXM-PMREQ"),
+    (0x04, 0x00, "INF-ERROR"),
+    (0x04, 0x01, "INF-WARNING"),
+    (0x04, 0x02, "INF-NOTICE"),
+    (0x04, 0x03, "INF-TEST"),
+    (0x04, 0x04, "INF-DEBUG"),
+    (0x05, 0x00, "ACK-NAK"),
+    (0x05, 0x01, "ACK-ACK"),
+    (0x06, 0x00, "CFG-PRT"),
+    (0x06, 0x01, "CFG-MSG"),
+    (0x06, 0x08, "CFG-RATE"),
+    (0x06, 0x24, "CFG-NAV5"),
+    (0x06, 0x8a, "CFG-VALSET"),
+    (0x06, 0x8b, "CFG-VALGET"),
+    (0x0a, 0x04, "MON-VER"),
+    (0x0a, 0x09, "MON-HW"),
+    (0x0a, 0x0b, "MON-HW2"),
+    (0x0a, 0x28, "MON-GNSS"),
+    (0x0a, 0x36, "MON-COMMS"),
+    (0x0a, 0x38, "MON-RF"),
+    (0x0d, 0x01, "TIM-TP"),
+    (0x0d, 0x03, "TIM-TM2"),
+    (0x0d, 0x06, "TIM-VRFY"),
+    (0x10, 0x02, "ESF-MEAS"),
+    (0x10, 0x03, "ESF-RAW"),
+    (0x10, 0x10, "ESF-STATUS"),
+    (0x10, 0x14, "ESF-ALG"),
+    (0x10, 0x15, "ESF-INS"),
+    (0x27, 0x03, "SEC-UNIQID"),
+    (0x28, 0x00, "HNR-PVT"),
+    (0x28, 0x01, "HNR-ATT"),
+    (0x28, 0x02, "HNR-INS"),
+];
+
+/// UBX NAV-PVT class and id, dating the stream
+const NAV_PVT: (u8, u8) = (0x01, 0x07);
+
+/// Milliseconds per GPS week, for iTOW rollovers
+const WEEK_MS: u64 = 7 * 86_400 * 1000;
+
+/// Largest gap across a week rollover, larger steps back being stream restarts (ms)
+const ROLLOVER_MAX_GAP_MS: u64 = 60_000;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Usage {
+    frames: usize,
+    bytes: usize,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: usize) {
+        self.frames += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Bandwidth usage per protocol and message type
+#[derive(Debug, Default)]
+pub struct Summary {
+    ubx: HashMap<(u8, u8), Usage>,
+    nmea: HashMap<String, Usage>,
+    rtcm: HashMap<u16, Usage>,
+    unknown: Usage,
+    /// Metadata blocks of ubx-record, not part of the stream
+    metadata: Usage,
+    /// First NAV-PVT iTOW of the current segment, and the last one
+    segment_start: Option<u64>,
+    last_itow: u64,
+    /// Time covered by previous segments, the stream restarting
+    /// on receiver resets or concatenated logs (ms)
+    previous_segments_ms: u64,
+    /// Gzip members of a compressed input
+    members: Option<usize>,
+    /// Error ending the input early, like a truncated member
+    read_error: Option<String>,
+    /// ESF-STATUS messages per fusion mode, and the last one
+    fusion: BTreeMap<FusionMode, usize>,
+    last_esf_status: Option<Status>,
+    malformed_esf_status: usize,
+    /// Distinct INF messages, in order of appearance: severity, text and count
+    inf: Vec<(&'static str, String, usize)>,
+}
+
+impl Summary {
+    /// Accounts for a frame found by the stream scanner
+    pub fn add(&mut self, kind: Kind, frame: &[u8]) {
+        if is_metadata(&kind) {
+            self.metadata.add(frame.len());
+            return;
+        }
+        match kind {
+            Kind::Ubx { class, id } => {
+                self.ubx.entry((class, id)).or_default().add(frame.len());
+                if (class, id) == NAV_PVT && frame.len() >= 10 {
+                    let itow = u32::from_le_bytes([frame[6], frame[7], frame[8], frame[9]]);
+                    self.date(itow);
+                }
+                if (class, id) == ESF_STATUS && frame.len() >= 8 {
+                    self.esf_status(&frame[6..frame.len() - 2]);
+                }
+                if class == INF && frame.len() >= 8 {
+                    self.inf(id, &frame[6..frame.len() - 2]);
+                }
+            },
+            Kind::Nmea(sentence) => self.nmea.entry(sentence).or_default().add(frame.len()),
+            Kind::Rtcm(number) => self.rtcm.entry(number).or_default().add(frame.len()),
+            Kind::Unknown => {
+                self.unknown.frames += 1;
+                self.unknown.bytes += frame.len();
+            },
+        }
+    }
+
+    /// Accounts for the input decompression, once read
+    pub fn input(&mut self, members: Option<usize>, read_error: Option<String>) {
+        self.members = members;
+        self.read_error = read_error;
+    }
+
+    fn esf_status(&mut self, payload: &[u8]) {
+        match Status::parse(payload) {
+            Ok(status) => {
+                *self.fusion.entry(status.fusion_mode).or_default() += 1;
+                self.last_esf_status = Some(status);
+            },
+            Err(_) => self.malformed_esf_status += 1,
+        }
+    }
+
+    fn inf(&mut self, id: u8, payload: &[u8]) {
+        let Some(severity) = inf::severity(id) else {
+            return;
+        };
+        let text = inf::text(payload);
+        match self
+            .inf
+            .iter_mut()
+            .find(|(known, known_text, _)| *known == severity && *known_text == text)
+        {
+            Some((.., count)) => *count += 1,
+            None => self.inf.push((severity, text, 1)),
+        }
+    }
+
+    fn date(&mut self, itow: u32) {
+        let mut itow = u64::from(itow);
+        match self.segment_start {
+            None => self.segment_start = Some(itow),
+            Some(start) if itow < self.last_itow % WEEK_MS => {
+                if itow + WEEK_MS - self.last_itow % WEEK_MS <= ROLLOVER_MAX_GAP_MS {
+                    // keep counting from the start of the segment
+                    itow += self.last_itow - self.last_itow % WEEK_MS + WEEK_MS;
+                } else {
+                    self.previous_segments_ms += self.last_itow - start;
+                    self.segment_start = Some(itow);
+                }
+            },
+            Some(_) => itow += self.last_itow - self.last_itow % WEEK_MS,
+        }
+        self.last_itow = itow;
+    }
+
+    /// Stream duration in seconds, as dated by NAV-PVT
+    fn duration(&self) -> Option<f64> {
+        let ms = self.previous_segments_ms + self.last_itow - self.segment_start?;
+        (ms > 0).then(|| ms as f64 / 1000.0)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let total = self.ubx.values().map(|u| u.bytes).sum::<usize>()
+            + self.nmea.values().map(|u| u.bytes).sum::<usize>()
+            + self.rtcm.values().map(|u| u.bytes).sum::<usize>()
+            + self.unknown.bytes;
+        let duration = self.duration();
+        let row = |writer: &mut W, name: &str, usage: &Usage| {
+            let share = if total > 0 {
+                100.0 * usage.bytes as f64 / total as f64
+            } else {
+                0.0
+            };
+            let rate = match duration {
+                Some(duration) => format!("{:.1}", usage.bytes as f64 / duration),
+                None => "-".to_string(),
+            };
+            writeln!(
+                writer,
+                "{:<16} {:>10} {:>12} {:>7.2}% {:>10}",
+                name, usage.frames, usage.bytes, share, rate
+            )
+        };
+
+        writeln!(
+            writer,
+            "{:<16} {:>10} {:>12} {:>8} {:>10}",
+            "message", "frames", "bytes", "share", "bytes/s"
+        )?;
+        let ubx = self
+            .ubx
+            .iter()
+            .map(|((class, id), usage)| (ubx_name(*class, *id), *usage))
+            .collect::<Vec<_>>();
+        let nmea = self
+            .nmea
+            .iter()
+            .map(|(sentence, usage)| (format!("NMEA {}", sentence), *usage))
+            .collect::<Vec<_>>();
+        let rtcm = self
+            .rtcm
+            .iter()
+            .map(|(number, usage)| (format!("RTCM {}", number), *usage))
+            .collect::<Vec<_>>();
+        for (protocol, mut messages) in [("UBX", ubx), ("NMEA", nmea), ("RTCM", rtcm)] {
+            if messages.is_empty() {
+                continue;
+            }
+            // largest first
+            messages.sort_by(|(a_name, a), (b_name, b)| {
+                b.bytes.cmp(&a.bytes).then_with(|| a_name.cmp(b_name))
+            });
+            let mut subtotal = Usage::default();
+            for (name, usage) in &messages {
+                row(writer, name, usage)?;
+                subtotal.frames += usage.frames;
+                subtotal.bytes += usage.bytes;
+            }
+            row(writer, &format!("{} total", protocol), &subtotal)?;
+        }
+        if self.unknown.bytes > 0 {
+            row(writer, "unknown", &self.unknown)?;
+        }
+        let frames = self.ubx.values().map(|u| u.frames).sum::<usize>()
+            + self.nmea.values().map(|u| u.frames).sum::<usize>()
+            + self.rtcm.values().map(|u| u.frames).sum::<usize>()
+            + self.unknown.frames;
+        row(
+            writer,
+            "total",
+            &Usage {
+                frames,
+                bytes: total,
+            },
+        )?;
+        match duration {
+            Some(duration) => writeln!(writer, "duration: {:.1} s, dated by NAV-PVT", duration)?,
+            None => writeln!(
+                writer,
+                "duration: unknown, no NAV-PVT solutions to date the stream"
+            )?,
+        }
+        let statuses = self.fusion.values().sum::<usize>();
+        if statuses > 0 {
+            let modes = self
+                .fusion
+                .iter()
+                .map(|(mode, count)| {
+                    format!("{} {:.1}%", mode.name(), 100.0 * *count as f64 / statuses as f64)
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                writer,
+                "sensor fusion: {} of {} ESF-STATUS",
+                modes.join(", "),
+                statuses
+            )?;
+        }
+        if let Some(ref status) = self.last_esf_status {
+            writeln!(writer, "sensor fusion at the end: {}", status.describe())?;
+        }
+        if self.malformed_esf_status > 0 {
+            writeln!(writer, "malformed ESF-STATUS: {}", self.malformed_esf_status)?;
+        }
+        if !self.inf.is_empty() {
+            writeln!(writer, "receiver messages (INF):")?;
+        }
+        for (severity, text, count) in &self.inf {
+            match count {
+                1 => writeln!(writer, "  {}: {}", severity, text)?,
+                _ => writeln!(writer, "  {}: {} ({} times)", severity, text, count)?,
+            }
+        }
+        if self.metadata.frames > 0 {
+            writeln!(
+                writer,
+                "metadata: {} block(s), {} bytes, not counted",
+                self.metadata.frames, self.metadata.bytes
+            )?;
+        }
+        if let Some(members) = self.members {
+            writeln!(writer, "compression: {} gzip member(s)", members)?;
+        }
+        if let Some(ref e) = self.read_error {
+            writeln!(writer, "input truncated: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+fn ubx_name(class: u8, id: u8) -> String {
+    UBX_MESSAGES
+        .iter()
+        .find(|(c, i, _)| (*c, *i) == (class, id))
+        .map(|(_, _, name)| name.to_string())
+        .unwrap_or_else(|| format!("UBX 0x{:02x}-0x{:02x}", class, id))
+}
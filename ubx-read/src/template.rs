@@ -0,0 +1,547 @@
+//! Free-form text export, one line per navigation epoch, from a template like
+//! `"{utc} {lat:.7} {lon:.7} {fix_type} {num_sv}"`.
+//!
+//! Fields are written `{name}` or `{name:.N}`, N being the number of decimals,
+//! or of fraction digits of the seconds for times. Anything else is literal text,
+//! `{{` and `}}` writing braces, `\n`, `\t` and `\\` the usual characters.
+//! Fields come from NAV-PVT and NAV-ATT: the values of an epoch are gathered
+//! by iTOW, any field having no value being left empty. Like in CSV exports,
+//! invalid positions and times are left empty unless invalid epochs are included.
+
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use ublox::{NavAttRef, NavPvtFlags, NavPvtRef, NavTimeLsRef};
+
+use crate::export::{FLAGS3_OFFSET, FULLY_RESOLVED, INVALID_LLH, VALID_DATE, VALID_TIME};
+use crate::timebase::{Timescale, Timestamps};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Pvt,
+    Att,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Integer,
+    /// Decimals by default
+    Float(usize),
+    Time(Timescale),
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Itow,
+    Time,
+    FixType,
+    NumSv,
+    Lat,
+    Lon,
+    Height,
+    HeightMsl,
+    HAcc,
+    VAcc,
+    VelN,
+    VelE,
+    VelD,
+    GroundSpeed,
+    HeadMot,
+    Pdop,
+    Roll,
+    Pitch,
+    Heading,
+}
+
+/// Field names, with their source, kind and value
+const FIELDS: &[(&str, Source, Kind, Field)] = &[
+    ("itow", Source::Pvt, Kind::Integer, Field::Itow),
+    ("utc", Source::Pvt, Kind::Time(Timescale::Utc), Field::Time),
+    ("gps", Source::Pvt, Kind::Time(Timescale::Gps), Field::Time),
+    ("tai", Source::Pvt, Kind::Time(Timescale::Tai), Field::Time),
+    (
+        "unix",
+        Source::Pvt,
+        Kind::Time(Timescale::Unix),
+        Field::Time,
+    ),
+    ("fix_type", Source::Pvt, Kind::Text, Field::FixType),
+    ("num_sv", Source::Pvt, Kind::Integer, Field::NumSv),
+    ("lat", Source::Pvt, Kind::Float(9), Field::Lat),
+    ("lon", Source::Pvt, Kind::Float(9), Field::Lon),
+    ("height", Source::Pvt, Kind::Float(3), Field::Height),
+    ("height_msl", Source::Pvt, Kind::Float(3), Field::HeightMsl),
+    ("h_acc", Source::Pvt, Kind::Float(3), Field::HAcc),
+    ("v_acc", Source::Pvt, Kind::Float(3), Field::VAcc),
+    ("vel_n", Source::Pvt, Kind::Float(3), Field::VelN),
+    ("vel_e", Source::Pvt, Kind::Float(3), Field::VelE),
+    ("vel_d", Source::Pvt, Kind::Float(3), Field::VelD),
+    (
+        "ground_speed",
+        Source::Pvt,
+        Kind::Float(3),
+        Field::GroundSpeed,
+    ),
+    ("head_mot", Source::Pvt, Kind::Float(5), Field::HeadMot),
+    ("pdop", Source::Pvt, Kind::Float(2), Field::Pdop),
+    ("roll", Source::Att, Kind::Float(5), Field::Roll),
+    ("pitch", Source::Att, Kind::Float(5), Field::Pitch),
+    ("heading", Source::Att, Kind::Float(5), Field::Heading),
+];
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Field {
+        source: Source,
+        field: Field,
+        kind: Kind,
+        /// Decimals
+        precision: usize,
+    },
+}
+
+/// Parsed template
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses a template, errors giving their position (in characters, starting at 1)
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let chars = template.chars().collect::<Vec<_>>();
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match (chars[i], chars.get(i + 1)) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    literal.push(chars[i]);
+                    i += 2;
+                },
+                ('\\', next) => {
+                    literal.push(match next {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('\\') => '\\',
+                        Some(c) => {
+                            return Err(format!("unknown escape \"\\{}\" at position {}", c, i + 1))
+                        },
+                        None => return Err(format!("dangling \"\\\" at position {}", i + 1)),
+                    });
+                    i += 2;
+                },
+                ('}', _) => return Err(format!("unmatched \"}}\" at position {}", i + 1)),
+                ('{', _) => {
+                    let Some(len) = chars[i + 1..].iter().position(|c| *c == '}') else {
+                        return Err(format!("unterminated field at position {}", i + 1));
+                    };
+                    let field = chars[i + 1..i + 1 + len].iter().collect::<String>();
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_field(&field, i + 2)?);
+                    i += len + 2;
+                },
+                (c, _) => {
+                    literal.push(c);
+                    i += 1;
+                },
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    fn uses(&self, source: Source) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field { source: s, .. } if *s == source))
+    }
+}
+
+/// Parses the content of a field, starting at `position` in the template
+fn parse_field(content: &str, position: usize) -> Result<Segment, String> {
+    let (name, precision) = match content.split_once(':') {
+        Some((name, spec)) => {
+            let spec_position = position + name.chars().count() + 1;
+            let precision = spec
+                .strip_prefix('.')
+                .and_then(|digits| digits.parse::<usize>().ok())
+                .ok_or_else(|| {
+                    format!(
+                        "invalid format \"{}\" at position {}, expecting a precision like \".3\"",
+                        spec, spec_position
+                    )
+                })?;
+            (name, Some((precision, spec_position)))
+        },
+        None => (content, None),
+    };
+    let (_, source, kind, field) = FIELDS
+        .iter()
+        .find(|(n, ..)| *n == name)
+        .ok_or_else(|| format!("unknown field \"{}\" at position {}", name, position))?;
+    let precision = match (kind, precision) {
+        (Kind::Float(default), None) => *default,
+        (Kind::Time(_), None) => 3,
+        (Kind::Integer | Kind::Text, None) => 0,
+        (Kind::Float(_), Some((precision, _))) => precision,
+        // fraction digits supported by the date formatting
+        (Kind::Time(Timescale::Unix), Some((precision, _))) if precision <= 9 => precision,
+        (Kind::Time(_), Some((precision, _))) if [3, 6, 9].contains(&precision) => precision,
+        (Kind::Time(Timescale::Unix), Some((_, position))) => {
+            return Err(format!(
+                "invalid precision of \"{}\" at position {}, expecting at most 9 digits",
+                name, position
+            ))
+        },
+        (Kind::Time(_), Some((_, position))) => {
+            return Err(format!(
+                "invalid precision of \"{}\" at position {}, expecting 3, 6 or 9 digits",
+                name, position
+            ))
+        },
+        (Kind::Integer | Kind::Text, Some((_, position))) => {
+            return Err(format!(
+                "field \"{}\" at position {} takes no precision",
+                name, position
+            ))
+        },
+    };
+    Ok(Segment::Field {
+        source: *source,
+        field: *field,
+        kind: *kind,
+        precision,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Pvt {
+    itow: u32,
+    time: Option<DateTime<Utc>>,
+    fix_type: String,
+    num_sv: u8,
+    /// Position valid, or invalid positions included (like times)
+    position_ok: bool,
+    lat: f64,
+    lon: f64,
+    height: f64,
+    height_msl: f64,
+    h_acc: f64,
+    v_acc: f64,
+    vel_n: f64,
+    vel_e: f64,
+    vel_d: f64,
+    ground_speed: f64,
+    head_mot: f64,
+    pdop: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Att {
+    roll: f64,
+    pitch: f64,
+    heading: f64,
+}
+
+/// Writes the template of each epoch, or of each packet with `per_packet`
+pub struct TemplateExporter<W: Write> {
+    writer: W,
+    template: Template,
+    per_packet: bool,
+    include_invalid: bool,
+    timestamps: Timestamps,
+    /// iTOW of the epoch being gathered
+    itow: Option<u32>,
+    pvt: Option<Pvt>,
+    att: Option<Att>,
+}
+
+impl<W: Write> TemplateExporter<W> {
+    pub fn new(writer: W, template: Template, per_packet: bool, include_invalid: bool) -> Self {
+        Self {
+            writer,
+            template,
+            per_packet,
+            include_invalid,
+            timestamps: Timestamps::new(Timescale::Utc, 3),
+            itow: None,
+            pvt: None,
+            att: None,
+        }
+    }
+
+    pub fn leap_seconds(&mut self, ls: &NavTimeLsRef) {
+        self.timestamps.leap_seconds(ls);
+    }
+
+    pub fn pvt(&mut self, pvt: &NavPvtRef) -> std::io::Result<()> {
+        self.epoch(pvt.itow(), Source::Pvt)?;
+        let gnss_fix_ok = pvt.flags().contains(NavPvtFlags::GPS_FIX_OK);
+        let invalid_llh = pvt.as_bytes()[FLAGS3_OFFSET] & INVALID_LLH != 0;
+        let time_ok = self.include_invalid
+            || pvt.valid() & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+                == VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        self.pvt = Some(Pvt {
+            itow: pvt.itow(),
+            time: DateTime::<Utc>::try_from(pvt).ok().filter(|_| time_ok),
+            fix_type: format!("{:?}", pvt.fix_type()),
+            num_sv: pvt.num_satellites(),
+            position_ok: self.include_invalid || (gnss_fix_ok && !invalid_llh),
+            lat: pvt.lat_degrees(),
+            lon: pvt.lon_degrees(),
+            height: pvt.height_meters(),
+            height_msl: pvt.height_msl(),
+            h_acc: f64::from(pvt.horiz_accuracy()) / 1000.0,
+            v_acc: f64::from(pvt.vert_accuracy()) / 1000.0,
+            vel_n: pvt.vel_north(),
+            vel_e: pvt.vel_east(),
+            vel_d: pvt.vel_down(),
+            ground_speed: pvt.ground_speed(),
+            head_mot: pvt.heading_degrees(),
+            pdop: f64::from(pvt.pdop()) / 100.0,
+        });
+        self.packet(Source::Pvt)
+    }
+
+    pub fn att(&mut self, att: &NavAttRef) -> std::io::Result<()> {
+        self.epoch(att.itow(), Source::Att)?;
+        self.att = Some(Att {
+            roll: att.vehicle_roll(),
+            pitch: att.vehicle_pitch(),
+            heading: att.vehicle_heading(),
+        });
+        self.packet(Source::Att)
+    }
+
+    /// Writes the last epoch, and flushes the output
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.per_packet {
+            self.write_line()?;
+        }
+        self.writer.flush()
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes the gathered epoch when a packet of another epoch arrives
+    fn epoch(&mut self, itow: u32, source: Source) -> std::io::Result<()> {
+        if self.per_packet || !self.template.uses(source) || self.itow == Some(itow) {
+            return Ok(());
+        }
+        self.write_line()?;
+        self.itow = Some(itow);
+        self.pvt = None;
+        self.att = None;
+        Ok(())
+    }
+
+    fn packet(&mut self, source: Source) -> std::io::Result<()> {
+        if self.per_packet && self.template.uses(source) {
+            self.write_line()?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self) -> std::io::Result<()> {
+        if self.pvt.is_none() && self.att.is_none() {
+            return Ok(());
+        }
+        let mut line = String::new();
+        for segment in &self.template.segments {
+            match segment {
+                Segment::Literal(text) => line.push_str(text),
+                Segment::Field {
+                    source,
+                    field,
+                    kind,
+                    precision,
+                } => line.push_str(&self.value(*source, *field, *kind, *precision)),
+            }
+        }
+        writeln!(self.writer, "{}", line)
+    }
+
+    /// Formatted value of a field, empty when unknown
+    fn value(&self, source: Source, field: Field, kind: Kind, precision: usize) -> String {
+        let value = match source {
+            Source::Pvt => self.pvt.as_ref().and_then(|pvt| pvt_value(pvt, field)),
+            Source::Att => self.att.and_then(|att| att_value(&att, field)),
+        };
+        match (value, kind) {
+            (None, _) => String::new(),
+            (Some(Value::Number(v)), Kind::Integer) => format!("{}", v),
+            (Some(Value::Number(v)), _) => format!("{:.*}", precision, v),
+            (Some(Value::Text(text)), _) => text,
+            (Some(Value::Time(time)), Kind::Time(scale)) => {
+                self.timestamps.rescaled(scale, precision).utc(time)
+            },
+            (Some(Value::Time(time)), _) => time.to_string(),
+        }
+    }
+}
+
+enum Value {
+    Number(f64),
+    Text(String),
+    Time(DateTime<Utc>),
+}
+
+fn pvt_value(pvt: &Pvt, field: Field) -> Option<Value> {
+    let position = |v: f64| pvt.position_ok.then_some(Value::Number(v));
+    match field {
+        Field::Itow => Some(Value::Number(f64::from(pvt.itow))),
+        Field::Time => pvt.time.map(Value::Time),
+        Field::FixType => Some(Value::Text(pvt.fix_type.clone())),
+        Field::NumSv => Some(Value::Number(f64::from(pvt.num_sv))),
+        Field::Lat => position(pvt.lat),
+        Field::Lon => position(pvt.lon),
+        Field::Height => position(pvt.height),
+        Field::HeightMsl => position(pvt.height_msl),
+        Field::HAcc => Some(Value::Number(pvt.h_acc)),
+        Field::VAcc => Some(Value::Number(pvt.v_acc)),
+        Field::VelN => Some(Value::Number(pvt.vel_n)),
+        Field::VelE => Some(Value::Number(pvt.vel_e)),
+        Field::VelD => Some(Value::Number(pvt.vel_d)),
+        Field::GroundSpeed => Some(Value::Number(pvt.ground_speed)),
+        Field::HeadMot => Some(Value::Number(pvt.head_mot)),
+        Field::Pdop => Some(Value::Number(pvt.pdop)),
+        _ => None,
+    }
+}
+
+fn att_value(att: &Att, field: Field) -> Option<Value> {
+    match field {
+        Field::Roll => Some(Value::Number(att.roll)),
+        Field::Pitch => Some(Value::Number(att.pitch)),
+        Field::Heading => Some(Value::Number(att.heading)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ublox::{PacketRef, Parser};
+    use ubx_common::frame::frame;
+
+    /// NAV-PVT of 2024-05-01 12:00:00.25 UTC, 3D fix at 47.3 N 8.5 E, 10 satellites
+    fn nav_pvt(itow: u32, fix_ok: bool) -> Vec<u8> {
+        let mut payload = [0_u8; 92];
+        payload[0..4].copy_from_slice(&itow.to_le_bytes());
+        payload[4..6].copy_from_slice(&2024_u16.to_le_bytes());
+        payload[6..11].copy_from_slice(&[5, 1, 12, 0, 0]);
+        payload[11] = VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+        payload[16..20].copy_from_slice(&250_000_000_i32.to_le_bytes());
+        payload[20] = 3;
+        payload[21] = u8::from(fix_ok);
+        payload[23] = 10;
+        payload[24..28].copy_from_slice(&85_000_000_i32.to_le_bytes());
+        payload[28..32].copy_from_slice(&473_000_000_i32.to_le_bytes());
+        payload[32..36].copy_from_slice(&500_250_i32.to_le_bytes());
+        payload[76..78].copy_from_slice(&125_u16.to_le_bytes());
+        frame(0x01, 0x07, &payload)
+    }
+
+    /// NAV-ATT, rolled 1.5 degrees
+    fn nav_att(itow: u32) -> Vec<u8> {
+        let mut payload = [0_u8; 32];
+        payload[0..4].copy_from_slice(&itow.to_le_bytes());
+        payload[8..12].copy_from_slice(&150_000_i32.to_le_bytes());
+        frame(0x01, 0x05, &payload)
+    }
+
+    /// Lines written for the frames
+    fn export(
+        template: &str,
+        per_packet: bool,
+        include_invalid: bool,
+        frames: &[Vec<u8>],
+    ) -> String {
+        let template = Template::parse(template).unwrap();
+        let mut exporter = TemplateExporter::new(vec![], template, per_packet, include_invalid);
+        let data = frames.concat();
+        let mut parser = Parser::default();
+        let mut packets = parser.consume(&data);
+        while let Some(packet) = packets.next() {
+            match packet.unwrap() {
+                PacketRef::NavPvt(pvt) => exporter.pvt(&pvt).unwrap(),
+                PacketRef::NavAtt(att) => exporter.att(&att).unwrap(),
+                _ => {},
+            }
+        }
+        exporter.finish().unwrap();
+        String::from_utf8(exporter.writer).unwrap()
+    }
+
+    #[test]
+    fn literals_and_escapes() {
+        let template = Template::parse("a{{b}}\\t\\n\\\\").unwrap();
+        assert!(matches!(&template.segments[..], [Segment::Literal(text)] if text == "a{b}\t\n\\"));
+        let template = Template::parse("{itow}, {lat:.2}!").unwrap();
+        assert_eq!(template.segments.len(), 4);
+        assert!(template.uses(Source::Pvt));
+        assert!(!template.uses(Source::Att));
+    }
+
+    #[test]
+    fn error_positions() {
+        for (template, error) in [
+            ("{itow} }", "unmatched \"}\" at position 8"),
+            ("ok {lat", "unterminated field at position 4"),
+            ("\\x", "unknown escape \"\\x\" at position 1"),
+            ("end\\", "dangling \"\\\" at position 4"),
+            ("{itow} {speed}", "unknown field \"speed\" at position 9"),
+            (
+                "{lat:3}",
+                "invalid format \"3\" at position 6, expecting a precision like \".3\"",
+            ),
+            (
+                "é{utc:.4}",
+                "invalid precision of \"utc\" at position 7, expecting 3, 6 or 9 digits",
+            ),
+            (
+                "{unix:.10}",
+                "invalid precision of \"unix\" at position 7, expecting at most 9 digits",
+            ),
+            ("{num_sv:.1}", "field \"num_sv\" at position 9 takes no precision"),
+        ] {
+            assert_eq!(Template::parse(template).unwrap_err(), error, "{}", template);
+        }
+    }
+
+    #[test]
+    fn epochs() {
+        let frames = [nav_pvt(1000, true), nav_att(1000), nav_att(2000), nav_pvt(3000, true)];
+        let template = "{itow} {utc} {lat:.4} {lon:.1} {height} {fix_type} {num_sv} {roll:.2}";
+        // the epoch of 2000 has no NAV-PVT, the one of 3000 no NAV-ATT
+        assert_eq!(
+            export(template, false, false, &frames),
+            [
+                "1000 2024-05-01T12:00:00.250Z 47.3000 8.5 500.250 Fix3D 10 1.50\n",
+                "       1.50\n",
+                "3000 2024-05-01T12:00:00.250Z 47.3000 8.5 500.250 Fix3D 10 \n",
+            ]
+            .concat()
+        );
+        assert_eq!(export("{pdop}", false, false, &frames[..1]), "1.25\n");
+    }
+
+    #[test]
+    fn per_packet_and_invalid() {
+        let frames = [nav_pvt(1000, false), nav_att(1000)];
+        // invalid positions are left empty, unless included
+        let lines = export("{itow};{lat:.1};{roll:.1}", true, false, &frames);
+        assert_eq!(lines, "1000;;\n1000;;1.5\n");
+        assert_eq!(export("{itow};{lat:.1}", false, true, &frames), "1000;47.3\n");
+    }
+}
@@ -0,0 +1,140 @@
+//! Timestamps of the exports, in the GPS, UTC, TAI or Unix time scale. TAI runs ahead
+//! of GPS time by 19 s.
+
+use chrono::{DateTime, Duration, Utc};
+use ublox::{NavTimeLsFlags, NavTimeLsRef};
+
+pub use ubx_common::time::{format, gps_epoch, TimeScale, WeekTime};
+use ubx_common::time::{leap_seconds, utc_to_gps_offset};
+
+/// TAI - GPS offset (s)
+const TAI_GPS_OFFSET: i64 = 19;
+
+/// Time scale of exported timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timescale {
+    Utc,
+    Gps,
+    Tai,
+    /// Seconds since 1970-01-01T00:00:00Z, leap seconds excluded
+    Unix,
+}
+
+impl Timescale {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "utc" => Ok(Self::Utc),
+            "gps" => Ok(Self::Gps),
+            "tai" => Ok(Self::Tai),
+            "unix" => Ok(Self::Unix),
+            _ => Err(format!(
+                "unknown time scale \"{}\", expecting utc, gps, tai or unix",
+                s
+            )),
+        }
+    }
+
+    /// Name of the timestamp columns and fields
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utc => "utc",
+            Self::Gps => "gps",
+            Self::Tai => "tai",
+            Self::Unix => "unix",
+        }
+    }
+}
+
+/// Formats timestamps in the selected time scale. UTC and GPS times are converted
+/// with the leap seconds reported by the receiver (NAV-TIMELS) once known,
+/// the leap seconds table otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamps {
+    pub scale: Timescale,
+    /// Fraction digits of the seconds
+    digits: usize,
+    /// Receiver reported GPS - UTC offset (s)
+    leap_seconds: Option<i64>,
+}
+
+impl Timestamps {
+    pub fn new(scale: Timescale, digits: usize) -> Self {
+        Self {
+            scale,
+            digits,
+            leap_seconds: None,
+        }
+    }
+
+    /// Same conversions, formatted in another scale
+    pub fn rescaled(&self, scale: Timescale, digits: usize) -> Self {
+        Self {
+            scale,
+            digits,
+            leap_seconds: self.leap_seconds,
+        }
+    }
+
+    pub fn leap_seconds(&mut self, ls: &NavTimeLsRef) {
+        if ls.valid().contains(NavTimeLsFlags::VALID_CURR_LS) {
+            self.leap_seconds = Some(i64::from(ls.current_ls()));
+        }
+    }
+
+    /// Timestamp of a UTC time
+    pub fn utc(&self, utc: DateTime<Utc>) -> String {
+        let offset = self.leap_seconds.unwrap_or_else(|| {
+            let utc_seconds = (utc - gps_epoch()).num_seconds();
+            utc_to_gps_offset(utc_seconds)
+        });
+        match self.scale {
+            Timescale::Utc | Timescale::Unix => self.format(utc),
+            Timescale::Gps | Timescale::Tai => self.format(utc + Duration::seconds(offset)),
+        }
+    }
+
+    /// Timestamp of a GPS time, given as a date in the GPS time scale
+    pub fn gps(&self, gps: DateTime<Utc>) -> String {
+        let offset = self.leap_seconds.unwrap_or_else(|| {
+            let gps_seconds = (gps - gps_epoch()).num_seconds();
+            leap_seconds(gps_seconds)
+        });
+        match self.scale {
+            Timescale::Gps | Timescale::Tai => self.format(gps),
+            Timescale::Utc | Timescale::Unix => self.format(gps - Duration::seconds(offset)),
+        }
+    }
+
+    /// Timestamp of a week and time of week pair, in its own time scale
+    pub fn week_time(&self, time: &WeekTime) -> String {
+        match time.scale {
+            TimeScale::Gps => self.gps(time.gps()),
+            TimeScale::Utc => self.utc(time.utc()),
+        }
+    }
+
+    /// Formats a UTC (Utc, Unix scales) or GPS (Gps, Tai scales) time
+    fn format(&self, time: DateTime<Utc>) -> String {
+        match self.scale {
+            Timescale::Utc => format!(
+                "{}Z",
+                time.format(&format!("%Y-%m-%dT%H:%M:%S%.{}f", self.digits))
+            ),
+            Timescale::Gps => time
+                .format(&format!("%Y-%m-%dT%H:%M:%S%.{}f", self.digits))
+                .to_string(),
+            Timescale::Tai => (time + Duration::seconds(TAI_GPS_OFFSET))
+                .format(&format!("%Y-%m-%dT%H:%M:%S%.{}f", self.digits))
+                .to_string(),
+            Timescale::Unix => {
+                let ns = time.timestamp_subsec_nanos();
+                let fraction = format!("{:09}", ns);
+                if self.digits == 0 {
+                    time.timestamp().to_string()
+                } else {
+                    format!("{}.{}", time.timestamp(), &fraction[..self.digits.min(9)])
+                }
+            },
+        }
+    }
+}
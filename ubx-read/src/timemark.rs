@@ -0,0 +1,162 @@
+//! TIM-TM2 time mark export
+
+use std::io::Write;
+
+use ubx_common::timemark::{
+    self, FLAGS_OFFSET, MODE_RUNNING, NEW_FALLING_EDGE, NEW_RISING_EDGE, RUN_STOPPED,
+    TIME_VALID, UTC_AVAILABLE,
+};
+use ublox::{NavTimeLsRef, TimTm2Ref};
+
+use crate::timebase::{self, Timestamps, WeekTime};
+
+/// Number of EXTINT channels tracked for missed events
+const CHANNELS: usize = 2;
+
+/// Exports one CSV row per TIM-TM2 time mark. Edges not flagged as new are
+/// left blank. Missed events are detected from gaps of the rising edge counter.
+/// Edges are dated in GPS and UTC times, or in the selected time scale only.
+pub struct TimeMarkExporter<W: Write> {
+    writer: W,
+    timestamps: Option<Timestamps>,
+    /// Last rising edge count, per channel
+    counts: [Option<u16>; CHANNELS],
+    marks: usize,
+    missed: usize,
+    header_written: bool,
+}
+
+impl<W: Write> TimeMarkExporter<W> {
+    pub fn new(writer: W, timestamps: Option<Timestamps>) -> Self {
+        Self {
+            writer,
+            timestamps,
+            counts: [None; CHANNELS],
+            marks: 0,
+            missed: 0,
+            header_written: false,
+        }
+    }
+
+    pub fn leap_seconds(&mut self, ls: &NavTimeLsRef) {
+        if let Some(timestamps) = &mut self.timestamps {
+            timestamps.leap_seconds(ls);
+        }
+    }
+
+    pub fn export(&mut self, tm: &TimTm2Ref) -> std::io::Result<()> {
+        if !self.header_written {
+            match &self.timestamps {
+                Some(timestamps) => writeln!(
+                    self.writer,
+                    "ch,count,missed,time_base,time_valid,utc_available,mode,run,\
+rising_week,rising_tow,rising_{0},falling_week,falling_tow,falling_{0},acc_ns",
+                    timestamps.scale.name()
+                )?,
+                None => writeln!(
+                    self.writer,
+                    "ch,count,missed,time_base,time_valid,utc_available,mode,run,\
+rising_week,rising_tow,rising_gps,rising_utc,falling_week,falling_tow,falling_gps,falling_utc,acc_ns"
+                )?,
+            }
+            self.header_written = true;
+        }
+        self.marks += 1;
+
+        let flags = tm.as_bytes()[FLAGS_OFFSET];
+        let channel = tm.ch();
+        let count = tm.count();
+        let missed = match self.counts.get_mut(usize::from(channel)) {
+            Some(last) => {
+                let missed = match *last {
+                    // counter is incremented on rising edges only
+                    Some(last) if flags & NEW_RISING_EDGE != 0 => {
+                        count.wrapping_sub(last).wrapping_sub(1)
+                    },
+                    _ => 0,
+                };
+                *last = Some(count);
+                missed
+            },
+            None => 0,
+        };
+        self.missed += usize::from(missed);
+
+        let (time_base, scale) = timemark::time_base(flags);
+        let rising = WeekTime {
+            scale,
+            week: tm.wn_r(),
+            tow_ms: tm.tow_ms_r(),
+            sub_ms_ns: tm.tow_sub_ms_r(),
+        };
+        let falling = WeekTime {
+            scale,
+            week: tm.wn_f(),
+            tow_ms: tm.tow_ms_f(),
+            sub_ms_ns: tm.tow_sub_ms_f(),
+        };
+
+        let timestamps = self.timestamps.as_ref();
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            channel,
+            count,
+            missed,
+            time_base,
+            u8::from(flags & TIME_VALID != 0),
+            u8::from(flags & UTC_AVAILABLE != 0),
+            if flags & MODE_RUNNING != 0 {
+                "running"
+            } else {
+                "single"
+            },
+            if flags & RUN_STOPPED != 0 {
+                "stopped"
+            } else {
+                "armed"
+            },
+            edge(&rising, flags & NEW_RISING_EDGE != 0, timestamps),
+            edge(&falling, flags & NEW_FALLING_EDGE != 0, timestamps),
+            tm.acc_est()
+        )
+    }
+
+    /// Flushes the output, reports the number of missed events on stderr
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        info!("{} time marks, {} missed events", self.marks, self.missed);
+        Ok(())
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Week, time of week, GPS and UTC times (or the timestamp in the selected scale)
+/// of an edge, blank unless new
+fn edge(time: &WeekTime, new: bool, timestamps: Option<&Timestamps>) -> String {
+    if let Some(timestamps) = timestamps {
+        if !new {
+            return ",,".to_string();
+        }
+        return format!(
+            "{},{},{}",
+            time.week,
+            time.tow(),
+            timestamps.week_time(time)
+        );
+    }
+    if !new {
+        return ",,,".to_string();
+    }
+    format!(
+        "{},{},{},{}",
+        time.week,
+        time.tow(),
+        timebase::format(time.gps()),
+        timebase::format(time.utc())
+    )
+}
@@ -0,0 +1,468 @@
+//! NAV-TIMEGPS and NAV-TIMEUTC export, and consistency report of the time messages.
+//!
+//! Every time is kept as integer nanoseconds (chrono durations): a timing receiver is
+//! validated at the nanosecond level, which floats would not hold over a GPS week.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use ublox::{NavPvtRef, NavTimeUtcFlags, NavTimeUTCRef};
+
+use crate::export::{FULLY_RESOLVED, VALID_DATE, VALID_TIME};
+use crate::timebase::{self, gps_epoch};
+
+/// UBX NAV-TIMEGPS class and id, not decoded by the ublox crate
+pub const NAV_TIMEGPS: (u8, u8) = (0x01, 0x20);
+const NAV_TIMEGPS_LEN: usize = 16;
+
+/// NAV-TIMEGPS `valid` bits
+const TOW_VALID: u8 = 0x01;
+const WEEK_VALID: u8 = 0x02;
+const LEAP_S_VALID: u8 = 0x04;
+
+const MS_PER_WEEK: u32 = 604_800_000;
+const NS_PER_MS: i64 = 1_000_000;
+const NS_PER_S: i64 = 1_000_000_000;
+const SECONDS_PER_WEEK: i64 = 604_800;
+
+/// Duration of the tAcc trend buckets (ms of receiver time)
+const TREND_BUCKET_MS: u32 = 600_000;
+
+/// Inconsistencies listed in the report, the others being counted only
+const MAX_LISTED: usize = 20;
+
+/// NAV-TIMEGPS solution
+#[derive(Debug, Clone, Copy)]
+pub struct TimeGps {
+    pub itow: u32,
+    /// Fraction of the millisecond, range -500000..500000 (ns)
+    pub ftow: i32,
+    pub week: i16,
+    /// GPS - UTC leap seconds
+    pub leap_s: i8,
+    pub valid: u8,
+    pub t_acc: u32,
+}
+
+impl TimeGps {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.len() != NAV_TIMEGPS_LEN {
+            return None;
+        }
+        Some(Self {
+            itow: u32::from_le_bytes(payload[0..4].try_into().unwrap()),
+            ftow: i32::from_le_bytes(payload[4..8].try_into().unwrap()),
+            week: i16::from_le_bytes(payload[8..10].try_into().unwrap()),
+            leap_s: payload[10] as i8,
+            valid: payload[11],
+            t_acc: u32::from_le_bytes(payload[12..16].try_into().unwrap()),
+        })
+    }
+
+    /// Time of week (ns)
+    fn tow_ns(&self) -> i64 {
+        i64::from(self.itow) * NS_PER_MS + i64::from(self.ftow)
+    }
+
+    /// Time of week in seconds, nanosecond digits
+    fn tow(&self) -> String {
+        let ns = self.tow_ns();
+        format!("{}.{:09}", ns.div_euclid(NS_PER_S), ns.rem_euclid(NS_PER_S))
+    }
+
+    /// UTC time, from the week, time of week and leap seconds of the receiver
+    fn utc(&self) -> Option<DateTime<Utc>> {
+        let valid = TOW_VALID | WEEK_VALID | LEAP_S_VALID;
+        if self.valid & valid != valid {
+            return None;
+        }
+        let ns = i64::from(self.week) * SECONDS_PER_WEEK * NS_PER_S + self.tow_ns()
+            - i64::from(self.leap_s) * NS_PER_S;
+        Some(gps_epoch() + Duration::nanoseconds(ns))
+    }
+}
+
+/// UTC time of calendar fields and a signed nanosecond correction,
+/// a leap second (sec = 60) running into the next minute
+fn calendar(
+    (year, month, day): (u16, u8, u8),
+    (hour, min, sec): (u8, u8, u8),
+    nanos: i32,
+) -> Option<DateTime<Utc>> {
+    let time = NaiveDate::from_ymd_opt(i32::from(year), u32::from(month), u32::from(day))?
+        .and_hms_opt(u32::from(hour), u32::from(min), 0)?
+        .and_utc();
+    Some(time + Duration::seconds(i64::from(sec)) + Duration::nanoseconds(i64::from(nanos)))
+}
+
+fn time_utc(utc: &NavTimeUTCRef) -> Option<DateTime<Utc>> {
+    if !utc.valid().contains(NavTimeUtcFlags::VALID_UTC) {
+        return None;
+    }
+    calendar(
+        (utc.year(), utc.month(), utc.day()),
+        (utc.hour(), utc.min(), utc.sec()),
+        utc.nanos(),
+    )
+}
+
+fn time_pvt(pvt: &NavPvtRef) -> Option<DateTime<Utc>> {
+    let valid = VALID_DATE | VALID_TIME | FULLY_RESOLVED;
+    if pvt.valid() & valid != valid {
+        return None;
+    }
+    calendar(
+        (pvt.year(), pvt.month(), pvt.day()),
+        (pvt.hour(), pvt.min(), pvt.sec()),
+        pvt.nanosecond(),
+    )
+}
+
+/// Exports one CSV row per NAV-TIMEGPS and NAV-TIMEUTC message
+pub struct TimeExporter<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> TimeExporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+        }
+    }
+
+    fn header(&mut self) -> std::io::Result<()> {
+        if !self.header_written {
+            writeln!(
+                self.writer,
+                "message,itow,ftow_ns,week,tow,leap_s,utc,t_acc_ns,\
+tow_valid,week_valid,leap_s_valid,utc_valid"
+            )?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    pub fn gps(&mut self, gps: &TimeGps) -> std::io::Result<()> {
+        self.header()?;
+        writeln!(
+            self.writer,
+            "NAV-TIMEGPS,{},{},{},{},{},{},{},{},{},{},",
+            gps.itow,
+            gps.ftow,
+            gps.week,
+            gps.tow(),
+            gps.leap_s,
+            gps.utc().map(timebase::format).unwrap_or_default(),
+            gps.t_acc,
+            u8::from(gps.valid & TOW_VALID != 0),
+            u8::from(gps.valid & WEEK_VALID != 0),
+            u8::from(gps.valid & LEAP_S_VALID != 0),
+        )
+    }
+
+    pub fn utc(&mut self, utc: &NavTimeUTCRef) -> std::io::Result<()> {
+        self.header()?;
+        let valid = utc.valid();
+        writeln!(
+            self.writer,
+            "NAV-TIMEUTC,{},,,,,{},{},{},{},,{}",
+            utc.itow(),
+            time_utc(utc).map(timebase::format).unwrap_or_default(),
+            utc.time_accuracy_estimate_ns(),
+            u8::from(valid.contains(NavTimeUtcFlags::VALID_TOW)),
+            u8::from(valid.contains(NavTimeUtcFlags::VALID_WKN)),
+            u8::from(valid.contains(NavTimeUtcFlags::VALID_UTC)),
+        )
+    }
+
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The output, once finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Time messages of one navigation epoch
+#[derive(Debug, Default)]
+struct Epoch {
+    itow: u32,
+    gps: Option<TimeGps>,
+    /// NAV-TIMEUTC time when valid, tAcc, validTOW / validWKN / validUTC
+    utc: Option<(Option<DateTime<Utc>>, u32, [bool; 3])>,
+    /// NAV-PVT time when valid, tAcc
+    pvt: Option<(Option<DateTime<Utc>>, u32)>,
+}
+
+/// Agreement of two time messages over the session
+#[derive(Debug, Default)]
+struct Agreement {
+    compared: usize,
+    /// Not compared, one of the times being invalid
+    invalid: usize,
+    max_abs_ns: i64,
+    inconsistent: usize,
+}
+
+impl Agreement {
+    fn compare(
+        &mut self,
+        a: Option<DateTime<Utc>>,
+        b: Option<DateTime<Utc>>,
+        tolerance: i64,
+    ) -> Option<i64> {
+        let (Some(a), Some(b)) = (a, b) else {
+            self.invalid += 1;
+            return None;
+        };
+        self.compared += 1;
+        // both years apart at most: nanoseconds fit
+        let diff = (a - b).num_nanoseconds().unwrap_or(i64::MAX);
+        self.max_abs_ns = self.max_abs_ns.max(diff.saturating_abs());
+        if diff.saturating_abs() > tolerance {
+            self.inconsistent += 1;
+            return Some(diff);
+        }
+        None
+    }
+}
+
+/// Compares NAV-TIMEGPS, NAV-TIMEUTC and NAV-PVT times within each epoch,
+/// and follows the time accuracy estimate over the session
+pub struct TimeReport {
+    tolerance: i64,
+    epoch: Option<Epoch>,
+    epochs: usize,
+    counts: [usize; 3],
+    gps_utc: Agreement,
+    pvt_utc: Agreement,
+    gps_pvt: Agreement,
+    /// Epochs whose messages disagree on validity, or on tAcc
+    validity: usize,
+    t_acc: usize,
+    listed: Vec<String>,
+    unlisted: usize,
+    first_itow: Option<u32>,
+    /// tAcc (ns) of the epochs, per trend bucket
+    trend: BTreeMap<u32, Vec<u32>>,
+}
+
+impl TimeReport {
+    pub fn new(tolerance_ns: u64) -> Self {
+        Self {
+            tolerance: i64::try_from(tolerance_ns).unwrap_or(i64::MAX),
+            epoch: None,
+            epochs: 0,
+            counts: [0; 3],
+            gps_utc: Agreement::default(),
+            pvt_utc: Agreement::default(),
+            gps_pvt: Agreement::default(),
+            validity: 0,
+            t_acc: 0,
+            listed: Vec::new(),
+            unlisted: 0,
+            first_itow: None,
+            trend: BTreeMap::new(),
+        }
+    }
+
+    /// Epoch of the message, the previous one being closed on a new iTOW
+    fn epoch(&mut self, itow: u32) -> &mut Epoch {
+        if self.epoch.as_ref().is_some_and(|epoch| epoch.itow != itow) {
+            self.close();
+        }
+        self.epoch.get_or_insert_with(|| Epoch {
+            itow,
+            ..Default::default()
+        })
+    }
+
+    pub fn gps(&mut self, gps: &TimeGps) {
+        self.counts[0] += 1;
+        self.epoch(gps.itow).gps = Some(*gps);
+    }
+
+    pub fn utc(&mut self, utc: &NavTimeUTCRef) {
+        self.counts[1] += 1;
+        let valid = utc.valid();
+        let flags = [
+            valid.contains(NavTimeUtcFlags::VALID_TOW),
+            valid.contains(NavTimeUtcFlags::VALID_WKN),
+            valid.contains(NavTimeUtcFlags::VALID_UTC),
+        ];
+        self.epoch(utc.itow()).utc = Some((time_utc(utc), utc.time_accuracy_estimate_ns(), flags));
+    }
+
+    pub fn solution(&mut self, pvt: &NavPvtRef) {
+        self.counts[2] += 1;
+        self.epoch(pvt.itow()).pvt = Some((time_pvt(pvt), pvt.time_accuracy()));
+    }
+
+    fn list(&mut self, itow: u32, what: String) {
+        if self.listed.len() < MAX_LISTED {
+            self.listed.push(format!("itow={} {}", itow, what));
+        } else {
+            self.unlisted += 1;
+        }
+    }
+
+    fn close(&mut self) {
+        let Some(epoch) = self.epoch.take() else {
+            return;
+        };
+        self.epochs += 1;
+        let itow = epoch.itow;
+        let gps = epoch.gps.as_ref().and_then(TimeGps::utc);
+        let utc = epoch.utc.and_then(|(time, _, _)| time);
+        let pvt = epoch.pvt.and_then(|(time, _)| time);
+        let tolerance = self.tolerance;
+        if epoch.gps.is_some() && epoch.utc.is_some() {
+            if let Some(diff) = self.gps_utc.compare(gps, utc, tolerance) {
+                self.list(itow, format!("NAV-TIMEGPS - NAV-TIMEUTC: {:+} ns", diff));
+            }
+        }
+        if epoch.pvt.is_some() && epoch.utc.is_some() {
+            if let Some(diff) = self.pvt_utc.compare(pvt, utc, tolerance) {
+                self.list(itow, format!("NAV-PVT - NAV-TIMEUTC: {:+} ns", diff));
+            }
+        }
+        if epoch.gps.is_some() && epoch.pvt.is_some() {
+            if let Some(diff) = self.gps_pvt.compare(gps, pvt, tolerance) {
+                self.list(itow, format!("NAV-TIMEGPS - NAV-PVT: {:+} ns", diff));
+            }
+        }
+
+        if let (Some(gps), Some((_, _, [tow, week, utc]))) = (&epoch.gps, &epoch.utc) {
+            let flags = [
+                gps.valid & TOW_VALID != 0,
+                gps.valid & WEEK_VALID != 0,
+                gps.valid & LEAP_S_VALID != 0,
+            ];
+            if flags != [*tow, *week, *utc] {
+                self.validity += 1;
+                self.list(
+                    itow,
+                    format!(
+                        "validity: NAV-TIMEGPS tow/week/leapS {}/{}/{}, \
+NAV-TIMEUTC validTOW/validWKN/validUTC {}/{}/{}",
+                        u8::from(flags[0]),
+                        u8::from(flags[1]),
+                        u8::from(flags[2]),
+                        u8::from(*tow),
+                        u8::from(*week),
+                        u8::from(*utc)
+                    ),
+                );
+            }
+        }
+
+        let accuracies = [
+            epoch.gps.map(|gps| ("NAV-TIMEGPS", gps.t_acc)),
+            epoch.utc.map(|(_, t_acc, _)| ("NAV-TIMEUTC", t_acc)),
+            epoch.pvt.map(|(_, t_acc)| ("NAV-PVT", t_acc)),
+        ];
+        let accuracies = accuracies.iter().flatten().collect::<Vec<_>>();
+        if accuracies.windows(2).any(|pair| pair[0].1 != pair[1].1) {
+            self.t_acc += 1;
+            let values = accuracies
+                .iter()
+                .map(|(name, t_acc)| format!("{} {} ns", name, t_acc))
+                .collect::<Vec<_>>();
+            self.list(itow, format!("tAcc: {}", values.join(", ")));
+        }
+        if let Some((_, t_acc)) = accuracies.first() {
+            let first = *self.first_itow.get_or_insert(itow);
+            // receiver time elapsed since the first epoch, across week rollovers
+            let elapsed = (itow + MS_PER_WEEK - first) % MS_PER_WEEK;
+            self.trend
+                .entry(elapsed / TREND_BUCKET_MS)
+                .or_default()
+                .push(*t_acc);
+        }
+    }
+
+    pub fn write<W: Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.close();
+        writeln!(
+            writer,
+            "epochs: {} (NAV-TIMEGPS {}, NAV-TIMEUTC {}, NAV-PVT {})",
+            self.epochs, self.counts[0], self.counts[1], self.counts[2]
+        )?;
+        for (name, agreement) in [
+            ("NAV-TIMEGPS vs NAV-TIMEUTC", &self.gps_utc),
+            ("NAV-PVT vs NAV-TIMEUTC", &self.pvt_utc),
+            ("NAV-TIMEGPS vs NAV-PVT", &self.gps_pvt),
+        ] {
+            if agreement.compared + agreement.invalid == 0 {
+                writeln!(writer, "{}: no common epochs", name)?;
+                continue;
+            }
+            writeln!(
+                writer,
+                "{}: {} epochs compared, max |diff| {} ns, {} above {} ns, {} not compared (invalid time)",
+                name,
+                agreement.compared,
+                agreement.max_abs_ns,
+                agreement.inconsistent,
+                self.tolerance,
+                agreement.invalid
+            )?;
+        }
+        writeln!(
+            writer,
+            "validity flags disagreeing: {} epochs, tAcc disagreeing: {} epochs",
+            self.validity, self.t_acc
+        )?;
+        if !self.listed.is_empty() {
+            writeln!(writer, "inconsistencies:")?;
+            for line in &self.listed {
+                writeln!(writer, "  {}", line)?;
+            }
+            if self.unlisted > 0 {
+                writeln!(writer, "  ... {} more", self.unlisted)?;
+            }
+        }
+        if self.trend.is_empty() {
+            return writeln!(writer, "tAcc trend: no epochs");
+        }
+        writeln!(
+            writer,
+            "tAcc trend (ns), per {} min:",
+            TREND_BUCKET_MS / 60_000
+        )?;
+        writeln!(
+            writer,
+            "  {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "start", "epochs", "min", "median", "max"
+        )?;
+        let mut medians = Vec::new();
+        for (bucket, values) in self.trend.iter_mut() {
+            values.sort_unstable();
+            let median = values[values.len() / 2];
+            medians.push(median);
+            let start_s = bucket * TREND_BUCKET_MS / 1000;
+            writeln!(
+                writer,
+                "  {:>8} {:>8} {:>8} {:>8} {:>8}",
+                format!("+{}:{:02}", start_s / 3600, start_s / 60 % 60),
+                values.len(),
+                values[0],
+                median,
+                values[values.len() - 1]
+            )?;
+        }
+        let (first, last) = (medians[0], medians[medians.len() - 1]);
+        writeln!(
+            writer,
+            "tAcc median {} ns at the start, {} ns at the end ({:+} ns)",
+            first,
+            last,
+            i64::from(last) - i64::from(first)
+        )
+    }
+}
@@ -0,0 +1,209 @@
+//! Time to first fix of each receiver startup found in a log.
+//!
+//! Startups are detected from the NAV-PVT epochs: the start of the log,
+//! iTOW going backwards (receiver reset), or the first epoch following a data gap.
+//! Receiver time is not reliable until the first fix and may jump once resolved,
+//! so time is accumulated over the epochs, iTOW jumps while the time is not valid
+//! counting as one nominal epoch period.
+
+use std::io::Write;
+
+use ublox::{GpsFix, NavPvtFlags, NavPvtRef};
+
+use crate::export::{FULLY_RESOLVED, VALID_DATE, VALID_TIME};
+
+/// Milliseconds per GPS week, for iTOW rollovers
+const WEEK_MS: u32 = 7 * 86_400 * 1000;
+
+/// Largest gap across a week rollover, larger steps back being resets (ms)
+const ROLLOVER_MAX_GAP_MS: u32 = 60_000;
+
+/// What started the measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cause {
+    LogStart,
+    /// iTOW restarted (reset)
+    Restart,
+    /// First epoch after a gap (s)
+    Gap(f64),
+}
+
+/// State of the solution at an epoch
+#[derive(Debug, Clone, Copy, Default)]
+struct Solution {
+    fix_2d: bool,
+    fix_3d: bool,
+    time_resolved: bool,
+}
+
+/// One receiver startup, times since startup (s)
+#[derive(Debug, Clone)]
+struct Startup {
+    cause: Cause,
+    itow: u32,
+    /// Epoch count at startup
+    epoch: usize,
+    /// The first epoch already had a 3D fix, at log start
+    already_fixed: bool,
+    elapsed: f64,
+    fix_2d: Option<f64>,
+    fix_3d: Option<f64>,
+    time_resolved: Option<f64>,
+}
+
+impl Startup {
+    fn update(&mut self, solution: Solution) {
+        if solution.fix_2d && self.fix_2d.is_none() {
+            self.fix_2d = Some(self.elapsed);
+        }
+        if solution.fix_3d && self.fix_3d.is_none() {
+            self.fix_3d = Some(self.elapsed);
+        }
+        if solution.time_resolved && self.time_resolved.is_none() {
+            self.time_resolved = Some(self.elapsed);
+        }
+    }
+}
+
+pub struct TtffReport {
+    /// Data gap starting a new measurement (ms)
+    gap_ms: u32,
+    startups: Vec<Startup>,
+    last_itow: Option<u32>,
+    /// Time of the last epoch was valid, iTOW jumps being time corrections otherwise
+    last_time_valid: bool,
+    epochs: usize,
+    /// Positive iTOW steps, for the nominal epoch period
+    steps: Vec<u32>,
+}
+
+impl TtffReport {
+    /// Data gaps longer than `gap` seconds start a new measurement
+    pub fn new(gap: f64) -> Self {
+        Self {
+            gap_ms: (gap * 1000.0) as u32,
+            startups: Vec::new(),
+            last_itow: None,
+            last_time_valid: false,
+            epochs: 0,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn solution(&mut self, pvt: &NavPvtRef) {
+        let itow = pvt.itow();
+        let gnss_fix_ok = pvt.flags().contains(NavPvtFlags::GPS_FIX_OK);
+        let solution = Solution {
+            fix_2d: gnss_fix_ok
+                && matches!(
+                    pvt.fix_type(),
+                    GpsFix::Fix2D | GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning
+                ),
+            fix_3d: gnss_fix_ok
+                && matches!(pvt.fix_type(), GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning),
+            time_resolved: pvt.valid() & (VALID_DATE | VALID_TIME | FULLY_RESOLVED)
+                == VALID_DATE | VALID_TIME | FULLY_RESOLVED,
+        };
+        self.epochs += 1;
+
+        let cause = match self.last_itow {
+            None => Some(Cause::LogStart),
+            Some(last) => {
+                let step = if itow < last {
+                    itow + WEEK_MS - last
+                } else {
+                    itow - last
+                };
+                let restart = itow < last && step > ROLLOVER_MAX_GAP_MS;
+                if (restart || step > self.gap_ms) && !self.last_time_valid {
+                    // time being resolved: the step counts as one epoch period
+                    let period = self.nominal_period();
+                    if let Some(startup) = self.startups.last_mut() {
+                        startup.elapsed += period;
+                    }
+                    None
+                } else if restart {
+                    Some(Cause::Restart)
+                } else if step > self.gap_ms {
+                    Some(Cause::Gap(f64::from(step) / 1000.0))
+                } else {
+                    if step > 0 {
+                        self.steps.push(step);
+                    }
+                    if let Some(startup) = self.startups.last_mut() {
+                        startup.elapsed += f64::from(step) / 1000.0;
+                    }
+                    None
+                }
+            },
+        };
+        self.last_itow = Some(itow);
+        self.last_time_valid = pvt.valid() & VALID_TIME != 0;
+
+        if let Some(cause) = cause {
+            self.startups.push(Startup {
+                cause,
+                itow,
+                epoch: self.epochs,
+                already_fixed: cause == Cause::LogStart && solution.fix_3d,
+                elapsed: 0.0,
+                fix_2d: None,
+                fix_3d: None,
+                time_resolved: None,
+            });
+        }
+        if let Some(startup) = self.startups.last_mut() {
+            startup.update(solution);
+        }
+    }
+
+    /// Median positive iTOW step (s), 1 s when unknown
+    fn nominal_period(&self) -> f64 {
+        let mut steps = self.steps.clone();
+        steps.sort_unstable();
+        steps
+            .get(steps.len() / 2)
+            .map(|step| f64::from(*step) / 1000.0)
+            .unwrap_or(1.0)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "{} NAV-PVT epochs, {} startups",
+            self.epochs,
+            self.startups.len()
+        )?;
+        let time = |t: Option<f64>| match t {
+            Some(t) => format!("{:.1} s", t),
+            None => "never".to_string(),
+        };
+        for (index, startup) in self.startups.iter().enumerate() {
+            let cause = match startup.cause {
+                Cause::LogStart => "log start".to_string(),
+                Cause::Restart => "iTOW restart".to_string(),
+                Cause::Gap(gap) => format!("data after a {:.1} s gap", gap),
+            };
+            write!(
+                writer,
+                "startup {}: {} at itow {} (epoch {}): ",
+                index + 1,
+                cause,
+                startup.itow,
+                startup.epoch
+            )?;
+            if startup.already_fixed {
+                writeln!(writer, "already fixed at log start")?;
+                continue;
+            }
+            writeln!(
+                writer,
+                "2D fix {}, 3D fix {}, fully resolved time {}",
+                time(startup.fix_2d),
+                time(startup.fix_3d),
+                time(startup.time_resolved)
+            )?;
+        }
+        Ok(())
+    }
+}
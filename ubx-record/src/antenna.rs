@@ -0,0 +1,171 @@
+//! Active antenna supervisor: its settings (UBX-CFG-ANT) for the configure antenna
+//! subcommand, and the antenna status of the UBX-MON-HW messages during the recording
+//! (`--monitor-antenna`), a short or open antenna being reported as it is detected rather
+//! than found later from bad data
+
+use crate::frame;
+use crate::Device;
+
+/// Length of a CFG-ANT payload: flags and pins
+const ANT_LEN: usize = 4;
+
+/// CFG-ANT flags: supply voltage control, short and open circuit detection
+pub const SUPERVISOR: u16 = 0x01;
+pub const SHORT_DETECT: u16 = 0x02;
+pub const OPEN_DETECT: u16 = 0x04;
+
+/// Offsets of aStatus and aPower in a MON-HW payload
+const MON_HW_STATUS: usize = 20;
+const MON_HW_POWER: usize = 21;
+
+/// Antenna supervisor settings, from a UBX-CFG-ANT payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ant {
+    payload: [u8; ANT_LEN],
+}
+
+impl Ant {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..ANT_LEN)?.try_into().ok()?,
+        })
+    }
+
+    fn flags(&self) -> u16 {
+        u16::from_le_bytes([self.payload[0], self.payload[1]])
+    }
+
+    fn set_flag(&mut self, flag: u16, on: bool) {
+        let flags = if on { self.flags() | flag } else { self.flags() & !flag };
+        self.payload[..2].copy_from_slice(&flags.to_le_bytes());
+    }
+
+    /// Like "supervisor on, short detection on, open detection off"
+    pub fn describe(&self) -> String {
+        let state = |flag: u16| if self.flags() & flag != 0 { "on" } else { "off" };
+        format!(
+            "supervisor {}, short detection {}, open detection {}",
+            state(SUPERVISOR),
+            state(SHORT_DETECT),
+            state(OPEN_DETECT)
+        )
+    }
+}
+
+/// Reads the antenna supervisor settings
+pub fn poll(device: &mut Device) -> Result<Ant, String> {
+    let payload = device
+        .poll_payload(0x06, 0x13, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the antenna settings (UBX-CFG-ANT): {}", e))?;
+    Ant::parse(&payload).ok_or_else(|| format!("Unexpected UBX-CFG-ANT of {} bytes", payload.len()))
+}
+
+/// Turns the CFG-ANT `flags` given on or off, keeping the other settings and the pins,
+/// returning the settings polled back
+pub fn set(device: &mut Device, flags: &[(u16, bool)]) -> Result<Option<Ant>, String> {
+    let mut ant = poll(device)?;
+    for (flag, on) in flags {
+        ant.set_flag(*flag, *on);
+    }
+    info!("Setting the antenna supervisor: {} ...", ant.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x13, &ant.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-ANT: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x13, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the antenna settings (UBX-ACK-NAK of UBX-CFG-ANT), \
+generation 9 receivers taking the CFG-HW-ANT_CFG keys instead: {}",
+                ant.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-ANT not acknowledged: {}", e)),
+    }
+    Ok(poll(device).ok())
+}
+
+/// Antenna status of the supervisor, the aStatus of UBX-MON-HW
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Init,
+    DontKnow,
+    Ok,
+    Short,
+    Open,
+    Unknown(u8),
+}
+
+impl Status {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Status::Init,
+            1 => Status::DontKnow,
+            2 => Status::Ok,
+            3 => Status::Short,
+            4 => Status::Open,
+            _ => Status::Unknown(byte),
+        }
+    }
+
+    /// Short or open antenna
+    pub fn is_fault(self) -> bool {
+        matches!(self, Status::Short | Status::Open)
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Status::Init => "INIT".to_string(),
+            Status::DontKnow => "DONTKNOW".to_string(),
+            Status::Ok => "OK".to_string(),
+            Status::Short => "SHORT".to_string(),
+            Status::Open => "OPEN".to_string(),
+            Status::Unknown(byte) => format!("0x{:02x}", byte),
+        }
+    }
+}
+
+fn power_name(byte: u8) -> &'static str {
+    match byte {
+        0 => "off",
+        1 => "on",
+        _ => "unknown",
+    }
+}
+
+/// Antenna status of the UBX-MON-HW messages, reporting its changes
+#[derive(Default)]
+pub struct AntennaWatch {
+    last: Option<Status>,
+}
+
+impl AntennaWatch {
+    /// Reads the antenna status of a MON-HW frame, returning a description of its change
+    /// and whether the antenna is now faulty
+    pub fn frame(&mut self, frame: &[u8]) -> Option<(String, bool)> {
+        if frame[2..4] != [0x0a, 0x09] {
+            return None;
+        }
+        let payload = &frame[6..frame.len() - 2];
+        let status = Status::from_byte(*payload.get(MON_HW_STATUS)?);
+        let power = power_name(*payload.get(MON_HW_POWER)?);
+        let previous = self.last.replace(status);
+        let description = match previous {
+            Some(previous) if previous == status => return None,
+            Some(previous) => format!(
+                "antenna status {} -> {}, power {}",
+                previous.name(),
+                status.name(),
+                power
+            ),
+            None => format!("antenna status {}, power {}", status.name(), power),
+        };
+        if !status.is_fault() {
+            info!("{}", description);
+            // the first status is only an event when faulty
+            return previous.map(|_| (description, false));
+        }
+        warn!("{}: ANTENNA FAULT, {}", chrono::Utc::now().to_rfc3339(), description);
+        Some((description, true))
+    }
+}
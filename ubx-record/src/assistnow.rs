@@ -0,0 +1,288 @@
+//! AssistNow assistance data (`--assistnow mga.ubx`): the UBX-MGA messages of an AssistNow
+//! Offline or Online file, or downloaded from AssistNow Online with the token of the account
+//! (`--assistnow-online TOKEN`), streamed to the receiver before it is configured, to shorten
+//! the time to first fix. The receiver is asked to acknowledge them (ackAiding of UBX-CFG-NAVX5),
+//! each message being sent once the previous one is acknowledged by a UBX-MGA-ACK, or after a
+//! pause when the receiver does not acknowledge them. The upload gives up at the
+//! `--assistnow-timeout`, recording then starting without the messages left.
+
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use crate::frame::{self, FrameScanner};
+use crate::Device;
+
+const CLASS_MGA: u8 = 0x13;
+const MGA_ACK: u8 = 0x60;
+
+/// ackAid bit of the mask1 of CFG-NAVX5, and offset of its ackAiding field
+const NAVX5_ACK_AID: u16 = 0x0400;
+const NAVX5_ACK_AIDING: usize = 17;
+
+/// Pause between the messages the receiver does not acknowledge, for its buffer not to
+/// overflow
+const PACE: Duration = Duration::from_millis(20);
+
+/// AssistNow Online servers, the second one tried when the first fails
+const ONLINE_SERVERS: [&str; 2] = [
+    "online-live1.services.u-blox.com",
+    "online-live2.services.u-blox.com",
+];
+
+/// Constellations and data requested from AssistNow Online: ephemerides, almanacs, and the
+/// ionosphere and time parameters
+const ONLINE_GNSS: &str = "gps,glo,gal,bds,qzss";
+const ONLINE_DATA_TYPES: &str = "eph,alm,aux";
+
+/// Longest wait for an AssistNow Online server
+const ONLINE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Largest AssistNow Online response, a few kB usually
+const ONLINE_MAX_LEN: u64 = 1 << 20;
+
+/// Reasons of a UBX-MGA-ACK rejecting a message, by infoCode
+const REJECTIONS: [&str; 7] = [
+    "accepted",
+    "no time known yet",
+    "message version unsupported",
+    "message size mismatch",
+    "database storage failure",
+    "receiver not ready",
+    "unknown message type",
+];
+
+/// UBX-MGA frames of a file, the other messages being dropped
+pub fn parse(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut scanner = FrameScanner::default();
+    scanner.push(data);
+    let mut frames = Vec::new();
+    let mut others = 0;
+    while let Some(frame) = scanner.next_frame() {
+        if frame[2] == CLASS_MGA {
+            frames.push(frame);
+        } else {
+            others += 1;
+        }
+    }
+    if others > 0 {
+        warn!("{} messages of the AssistNow file are not UBX-MGA, they are not sent", others);
+    }
+    if scanner.checksum_failures() > 0 {
+        warn!(
+            "{} frames of the AssistNow file have an invalid checksum, they are not sent",
+            scanner.checksum_failures()
+        );
+    }
+    if frames.is_empty() {
+        return Err("no UBX-MGA message, expecting an AssistNow Offline or Online file".to_string());
+    }
+    Ok(frames)
+}
+
+/// Query of the AssistNow Online data, on `server`
+fn online_url(server: &str, token: &str) -> String {
+    format!(
+        "https://{}/GetOnlineData.ashx?token={};gnss={};datatype={}",
+        server, token, ONLINE_GNSS, ONLINE_DATA_TYPES
+    )
+}
+
+/// Body of a GET of `url`, the reason of a failure given by the server being reported
+fn fetch(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, String> {
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => {
+            // like an unknown token, said in the body
+            let reason = response.into_string().unwrap_or_default();
+            return Err(format!("HTTP {} {}", status, reason.lines().next().unwrap_or_default())
+                .trim_end()
+                .to_string());
+        },
+        Err(ureq::Error::Transport(e)) => return Err(e.to_string()),
+    };
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(ONLINE_MAX_LEN)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// Downloads the current AssistNow Online data, the file of UBX-MGA messages, trying the
+/// servers in turn
+pub fn download(token: &str) -> Result<Vec<u8>, String> {
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid AssistNow token \"{}\"", token));
+    }
+    let agent = ureq::AgentBuilder::new().timeout(ONLINE_TIMEOUT).build();
+    let mut errors = Vec::new();
+    for server in ONLINE_SERVERS {
+        match fetch(&agent, &online_url(server, token)) {
+            Ok(data) => return Ok(data),
+            Err(e) => errors.push(format!("{}: {}", server, e)),
+        }
+    }
+    Err(format!("AssistNow Online unavailable: {}", errors.join(", ")))
+}
+
+/// Messages of the upload, by outcome
+#[derive(Debug, Default)]
+pub struct Upload {
+    accepted: usize,
+    rejected: usize,
+    /// Sent without a UBX-MGA-ACK
+    unacknowledged: usize,
+    /// Left at the timeout
+    not_sent: usize,
+}
+
+impl Upload {
+    /// Like "120 accepted, 2 rejected, 0 unacknowledged, 0 not sent"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} accepted, {} rejected, {} unacknowledged, {} not sent",
+            self.accepted, self.rejected, self.unacknowledged, self.not_sent
+        )
+    }
+}
+
+/// Asks for the UBX-MGA-ACK of the assistance messages, polling UBX-CFG-NAVX5 first for the
+/// other settings to be kept
+fn enable_acks(device: &mut Device, timeout: Duration) -> Result<(), String> {
+    let mut payload = device
+        .poll_payload(0x06, 0x23, &[], timeout)
+        .map_err(|e| format!("UBX-CFG-NAVX5 unavailable: {}", e))?;
+    if payload.len() <= NAVX5_ACK_AIDING {
+        return Err(format!("unexpected UBX-CFG-NAVX5 of {} bytes", payload.len()));
+    }
+    let mask1 = u16::from_le_bytes([payload[2], payload[3]]) | NAVX5_ACK_AID;
+    payload[2..4].copy_from_slice(&mask1.to_le_bytes());
+    payload[NAVX5_ACK_AIDING] = 1;
+    device
+        .write_all(&frame::frame(0x06, 0x23, &payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-NAVX5: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x23, timeout) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("UBX-CFG-NAVX5 rejected (UBX-ACK-NAK)".to_string()),
+        Err(e) => Err(format!("UBX-CFG-NAVX5 not acknowledged: {}", e)),
+    }
+}
+
+/// Waits up to `timeout` for the UBX-MGA-ACK of a frame, telling whether it was accepted,
+/// None without one
+fn wait_for_mga_ack(device: &mut Device, frame: &[u8], timeout: Duration) -> Option<bool> {
+    let deadline = Instant::now() + timeout;
+    // the acknowledgment repeats the message id and the start of its payload
+    let mut start = [0; 4];
+    for (byte, sent) in start.iter_mut().zip(&frame[6..frame.len() - 2]) {
+        *byte = *sent;
+    }
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let ack = device.response(CLASS_MGA, MGA_ACK, &[], left).ok()?;
+        if ack.len() < 8 || ack[3] != frame[3] || ack[4..8] != start {
+            continue;
+        }
+        if ack[0] == 1 {
+            return Some(true);
+        }
+        debug!(
+            "UBX-MGA message 0x{:02x} rejected: {}",
+            frame[3],
+            REJECTIONS.get(usize::from(ack[2])).unwrap_or(&"unknown reason")
+        );
+        return Some(false);
+    }
+}
+
+/// Sends the frames, each waiting up to `ack_timeout` for its UBX-MGA-ACK unless the
+/// receiver does not send them, giving up at `timeout`
+pub fn upload(
+    device: &mut Device,
+    frames: &[Vec<u8>],
+    timeout: Duration,
+    ack_timeout: Duration,
+) -> std::io::Result<Upload> {
+    let deadline = Instant::now() + timeout;
+    let mut acks = match enable_acks(device, ack_timeout) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("{}, the AssistNow messages are sent without flow control", e);
+            false
+        },
+    };
+    let mut upload = Upload::default();
+    for (index, frame) in frames.iter().enumerate() {
+        if Instant::now() >= deadline {
+            upload.not_sent = frames.len() - index;
+            warn!(
+                "AssistNow upload timed out after {:?}, {} messages not sent",
+                timeout, upload.not_sent
+            );
+            break;
+        }
+        device.write_all(frame)?;
+        if !acks {
+            upload.unacknowledged += 1;
+            std::thread::sleep(PACE);
+            continue;
+        }
+        let wait = ack_timeout.min(deadline.saturating_duration_since(Instant::now()));
+        match wait_for_mga_ack(device, frame, wait) {
+            Some(true) => upload.accepted += 1,
+            Some(false) => upload.rejected += 1,
+            // older firmware ignoring ackAiding
+            None if index == 0 => {
+                warn!("No UBX-MGA-ACK, the AssistNow messages are sent without flow control");
+                upload.unacknowledged += 1;
+                acks = false;
+            },
+            None => upload.unacknowledged += 1,
+        }
+    }
+    Ok(upload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn online_query() {
+        assert_eq!(
+            online_url(ONLINE_SERVERS[0], "AbC-12_x"),
+            "https://online-live1.services.u-blox.com/GetOnlineData.ashx?token=AbC-12_x;\
+gnss=gps,glo,gal,bds,qzss;datatype=eph,alm,aux"
+        );
+        // not sent to the servers
+        assert_eq!(download("a;b").unwrap_err(), "invalid AssistNow token \"a;b\"");
+        assert_eq!(download("").unwrap_err(), "invalid AssistNow token \"\"");
+    }
+
+    /// Body of a GET to a local server answering `response`
+    fn fetched(response: Vec<u8>) -> Result<Vec<u8>, String> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/GetOnlineData.ashx", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            use std::io::Write;
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(&response).unwrap();
+        });
+        let fetched = fetch(&ureq::AgentBuilder::new().timeout(ONLINE_TIMEOUT).build(), &url);
+        server.join().unwrap();
+        fetched
+    }
+
+    #[test]
+    fn online_responses() {
+        let mga = frame::frame(CLASS_MGA, 0x00, &[1, 0, 0, 0]);
+        let header = b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\n";
+        assert_eq!(fetched([header.as_slice(), &mga].concat()), Ok(mga));
+        let rejected = b"HTTP/1.1 403 Forbidden\r\nContent-Length: 18\r\n\r\nInvalid token\r\n...";
+        assert_eq!(fetched(rejected.to_vec()), Err("HTTP 403 Invalid token".to_string()));
+    }
+}
@@ -0,0 +1,285 @@
+//! RTK base station mode (UBX-CFG-TMODE3): survey-in until a mean position is accurate
+//! enough, or a known fixed position, and the survey-in progress of the NAV-SVIN messages
+//! received while recording (`--rtcm-after-survey` enabling the RTCM3 output once done)
+
+use crate::frame;
+use crate::Device;
+
+/// Length of a CFG-TMODE3 payload
+const TMODE3_LEN: usize = 40;
+
+const MODE_DISABLED: u16 = 0;
+const MODE_SURVEY_IN: u16 = 1;
+const MODE_FIXED: u16 = 2;
+/// The position is latitude, longitude and height rather than ECEF
+const LLA: u16 = 0x100;
+
+/// Survey-in progress printed every this much survey time (s)
+const PROGRESS_INTERVAL_S: u32 = 10;
+
+/// Base messages enabled by `--rtcm-after-survey`: the reference station position, the MSM7
+/// observations of GPS, GLONASS, Galileo and BeiDou, and the GLONASS code-phase biases
+pub const RTCM_MESSAGES: [&str; 6] = [
+    "RTCM3-1005",
+    "RTCM3-1077",
+    "RTCM3-1087",
+    "RTCM3-1097",
+    "RTCM3-1127",
+    "RTCM3-1230",
+];
+
+pub fn parse_latitude(s: &str) -> Result<f64, String> {
+    let degrees = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if !(-90.0..=90.0).contains(&degrees) {
+        return Err("the latitude is from -90 to 90 degrees".to_string());
+    }
+    Ok(degrees)
+}
+
+pub fn parse_longitude(s: &str) -> Result<f64, String> {
+    let degrees = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if !(-180.0..=180.0).contains(&degrees) {
+        return Err("the longitude is from -180 to 180 degrees".to_string());
+    }
+    Ok(degrees)
+}
+
+/// Standard and high precision parts of a value in `unit`, the high precision one being
+/// in hundredths of `unit`, like 1e-7 and 1e-9 degree
+fn split_precision(value: f64, unit: f64) -> (i32, i8) {
+    let hundredths = (value / unit * 100.0).round() as i64;
+    ((hundredths / 100) as i32, (hundredths % 100) as i8)
+}
+
+/// Base station mode requested
+#[derive(Debug, Clone, Copy)]
+pub enum BaseMode {
+    /// Minimum duration (s) and accuracy limit (m), the current ones kept when not given
+    SurveyIn {
+        min_duration_s: Option<u32>,
+        accuracy_limit_m: Option<f64>,
+    },
+    /// Latitude and longitude (degrees), height above the ellipsoid (m)
+    Fixed { lat: f64, lon: f64, height: f64 },
+}
+
+/// Base station settings, from a UBX-CFG-TMODE3 payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tmode3 {
+    payload: [u8; TMODE3_LEN],
+}
+
+impl Tmode3 {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..TMODE3_LEN)?.try_into().ok()?,
+        })
+    }
+
+    fn flags(&self) -> u16 {
+        u16::from_le_bytes([self.payload[2], self.payload[3]])
+    }
+
+    fn field(&self, offset: usize) -> u32 {
+        let bytes = &self.payload[offset..offset + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn set_field(&mut self, offset: usize, value: u32) {
+        self.payload[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Position coordinate at `offset`, with its high precision part at `hp_offset`
+    fn coordinate(&self, offset: usize, hp_offset: usize, unit: f64) -> f64 {
+        let hp = self.payload[hp_offset] as i8;
+        (f64::from(self.field(offset) as i32) + f64::from(hp) / 100.0) * unit
+    }
+
+    fn set_coordinate(&mut self, offset: usize, hp_offset: usize, value: f64, unit: f64) {
+        let (standard, hp) = split_precision(value, unit);
+        self.set_field(offset, standard as u32);
+        self.payload[hp_offset] = hp as u8;
+    }
+
+    pub fn apply(&mut self, mode: &BaseMode) {
+        match *mode {
+            BaseMode::SurveyIn {
+                min_duration_s,
+                accuracy_limit_m,
+            } => {
+                self.payload[2..4].copy_from_slice(&MODE_SURVEY_IN.to_le_bytes());
+                if let Some(duration) = min_duration_s {
+                    self.set_field(32, duration);
+                }
+                if let Some(limit) = accuracy_limit_m {
+                    // 0.1 mm
+                    self.set_field(36, (limit * 1e4).round() as u32);
+                }
+            },
+            BaseMode::Fixed { lat, lon, height } => {
+                self.payload[2..4].copy_from_slice(&(MODE_FIXED | LLA).to_le_bytes());
+                self.set_coordinate(4, 16, lat, 1e-7);
+                self.set_coordinate(8, 17, lon, 1e-7);
+                self.set_coordinate(12, 18, height, 1e-2);
+            },
+        }
+    }
+
+    /// Like "survey-in for 300 s at least, down to 2.000 m" or
+    /// "fixed at 47.366521876°, 8.541723501°, 459.2312 m"
+    pub fn describe(&self) -> String {
+        let flags = self.flags();
+        match flags & 0xff {
+            MODE_DISABLED => "disabled".to_string(),
+            MODE_SURVEY_IN => format!(
+                "survey-in for {} s at least, down to {:.3} m",
+                self.field(32),
+                f64::from(self.field(36)) / 1e4
+            ),
+            MODE_FIXED if flags & LLA != 0 => format!(
+                "fixed at {:.9}°, {:.9}°, {:.4} m",
+                self.coordinate(4, 16, 1e-7),
+                self.coordinate(8, 17, 1e-7),
+                self.coordinate(12, 18, 1e-2)
+            ),
+            MODE_FIXED => format!(
+                "fixed at ECEF {:.4}, {:.4}, {:.4} m",
+                self.coordinate(4, 16, 1e-2),
+                self.coordinate(8, 17, 1e-2),
+                self.coordinate(12, 18, 1e-2)
+            ),
+            mode => format!("mode {}", mode),
+        }
+    }
+}
+
+/// Sets the base station mode, keeping the settings not given, returning the settings
+/// polled back
+pub fn set_base(device: &mut Device, mode: &BaseMode) -> Result<Option<Tmode3>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x71, &[], device.ack_timeout())
+        .map_err(|e| {
+            format!(
+                "Failed to read the base station mode (UBX-CFG-TMODE3), only on high \
+precision receivers like the ZED-F9P: {}",
+                e
+            )
+        })?;
+    let mut tmode3 = Tmode3::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-TMODE3 of {} bytes", payload.len()))?;
+    tmode3.apply(mode);
+    info!("Setting the base station mode: {} ...", tmode3.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x71, &tmode3.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-TMODE3: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x71, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the base station mode (UBX-ACK-NAK of UBX-CFG-TMODE3): {}",
+                tmode3.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-TMODE3 not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x71, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Tmode3::parse(&payload));
+    Ok(applied)
+}
+
+/// Survey-in progress, from a NAV-SVIN payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Survey {
+    /// Survey time (s)
+    pub duration_s: u32,
+    /// Mean position accuracy (0.1 mm)
+    pub mean_accuracy: u32,
+    pub observations: u32,
+    pub valid: bool,
+    pub active: bool,
+}
+
+impl Survey {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let field = |offset: usize| {
+            payload
+                .get(offset..offset + 4)
+                .map(|field| u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+        };
+        Some(Self {
+            duration_s: field(8)?,
+            mean_accuracy: field(28)?,
+            observations: field(32)?,
+            valid: *payload.get(36)? != 0,
+            active: *payload.get(37)? != 0,
+        })
+    }
+
+    pub fn accuracy_m(&self) -> f64 {
+        f64::from(self.mean_accuracy) / 1e4
+    }
+
+    /// Like "312 s, 1.987 m mean accuracy, 305 observations"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} s, {:.3} m mean accuracy, {} observations",
+            self.duration_s,
+            self.accuracy_m(),
+            self.observations
+        )
+    }
+}
+
+/// Follows the survey-in of the NAV-SVIN messages received
+#[derive(Default)]
+pub struct SurveyWatch {
+    last: Option<Survey>,
+    /// Survey time of the last progress line (s)
+    reported_s: Option<u32>,
+    completed: bool,
+}
+
+impl SurveyWatch {
+    /// Last survey-in status received
+    pub fn last(&self) -> Option<Survey> {
+        self.last
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    /// Prints the progress of a NAV-SVIN frame, returning the survey once it completes
+    pub fn frame(&mut self, frame: &[u8]) -> Option<Survey> {
+        if frame[2..4] != [0x01, 0x3b] {
+            return None;
+        }
+        let survey = Survey::parse(&frame[6..frame.len() - 2])?;
+        self.last = Some(survey);
+        if survey.valid && !survey.active {
+            if self.completed {
+                return None;
+            }
+            info!("survey-in completed: {}", survey.describe());
+            self.completed = true;
+            return Some(survey);
+        }
+        if !survey.active {
+            return None;
+        }
+        let due = self
+            .reported_s
+            .map_or(true, |reported| survey.duration_s >= reported + PROGRESS_INTERVAL_S);
+        if due {
+            info!(
+                "survey-in: {}, {}",
+                survey.describe(),
+                if survey.valid { "valid" } else { "not valid yet" }
+            );
+            self.reported_s = Some(survey.duration_s);
+        }
+        None
+    }
+}
@@ -0,0 +1,261 @@
+//! Recording start conditions, evaluated on the NAV-PVT solutions of the live stream.
+//!
+//! Conditions are either a keyword (`rtk-float`, `rtk-fixed`, `geofence-exit`)
+//! or a comparison like `speed>5`, `hacc<0.5`, `numsv>=8` or `fix>=3d`.
+//! Speed is in m/s, accuracies in meters. Fix levels are ordered
+//! `none < dr < 2d < 3d < rtk-float < rtk-fixed`.
+
+use std::str::FromStr;
+
+use ublox::{GpsFix, NavPvtFlags, NavPvtRef, PacketRef, Parser};
+
+/// Mean Earth radius, for geofence distances (m)
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operator {
+    fn compare(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Operator::Less => lhs < rhs,
+            Operator::LessOrEqual => lhs <= rhs,
+            Operator::Greater => lhs > rhs,
+            Operator::GreaterOrEqual => lhs >= rhs,
+            Operator::Equal => lhs == rhs,
+            Operator::NotEqual => lhs != rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Speed,
+    HAcc,
+    VAcc,
+    NumSv,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    /// Fix level comparison
+    Fix(Operator, u8),
+    Value(Quantity, Operator, f64),
+    GeofenceExit,
+}
+
+/// Circular geofence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geofence {
+    pub lat: f64,
+    pub lon: f64,
+    /// Radius (m)
+    pub radius: f64,
+}
+
+impl FromStr for Geofence {
+    type Err = String;
+    /// Parses "LAT,LON,RADIUS", in degrees and meters
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid geofence \"{}\": expecting LAT,LON,RADIUS", s))?;
+        match values[..] {
+            [lat, lon, radius] if radius > 0.0 => Ok(Self { lat, lon, radius }),
+            _ => Err(format!(
+                "invalid geofence \"{}\": expecting LAT,LON,RADIUS",
+                s
+            )),
+        }
+    }
+}
+
+/// Start condition, with the state needed to evaluate it on raw frames
+pub struct StartCondition {
+    text: String,
+    condition: Condition,
+    geofence: Option<Geofence>,
+    parser: Parser<Vec<u8>>,
+}
+
+impl StartCondition {
+    /// Parses a condition, `geofence` being required by `geofence-exit`
+    pub fn new(text: &str, geofence: Option<Geofence>) -> Result<Self, String> {
+        let condition = parse_condition(text)?;
+        if condition == Condition::GeofenceExit && geofence.is_none() {
+            return Err("\"geofence-exit\" requires a --geofence".to_string());
+        }
+        Ok(Self {
+            text: text.to_string(),
+            condition,
+            geofence,
+            parser: Parser::default(),
+        })
+    }
+
+    /// Evaluates the condition on a validated frame, returns a description
+    /// of the epoch when the frame is a NAV-PVT solution meeting the condition
+    pub fn check(&mut self, frame: &[u8]) -> Option<String> {
+        let mut it = self.parser.consume(frame);
+        let mut epoch = None;
+        while let Some(packet) = it.next() {
+            if let Ok(PacketRef::NavPvt(pvt)) = packet {
+                if is_met(&self.condition, self.geofence.as_ref(), &pvt) {
+                    epoch = Some(format!(
+                        "start condition \"{}\" met at itow {} ({:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z), \
+{:?}, {} SV, speed {:.2} m/s, hAcc {:.3} m",
+                        self.text,
+                        pvt.itow(),
+                        pvt.year(),
+                        pvt.month(),
+                        pvt.day(),
+                        pvt.hour(),
+                        pvt.min(),
+                        pvt.sec(),
+                        pvt.fix_type(),
+                        pvt.num_satellites(),
+                        pvt.ground_speed(),
+                        f64::from(pvt.horiz_accuracy()) * 1e-3,
+                    ));
+                }
+            }
+        }
+        epoch
+    }
+}
+
+fn is_met(condition: &Condition, geofence: Option<&Geofence>, pvt: &NavPvtRef) -> bool {
+    let fix_ok = pvt.flags().contains(NavPvtFlags::GPS_FIX_OK);
+    match condition {
+        Condition::Fix(op, level) => op.compare(f64::from(fix_level(pvt)), f64::from(*level)),
+        Condition::Value(Quantity::NumSv, op, value) => {
+            op.compare(f64::from(pvt.num_satellites()), *value)
+        },
+        Condition::Value(quantity, op, value) => {
+            let lhs = match quantity {
+                Quantity::Speed => pvt.ground_speed(),
+                Quantity::HAcc => f64::from(pvt.horiz_accuracy()) * 1e-3,
+                _ => f64::from(pvt.vert_accuracy()) * 1e-3,
+            };
+            fix_ok && op.compare(lhs, *value)
+        },
+        Condition::GeofenceExit => match geofence {
+            Some(fence) => {
+                fix_ok
+                    && distance(fence.lat, fence.lon, pvt.lat_degrees(), pvt.lon_degrees())
+                        > fence.radius
+            },
+            None => false,
+        },
+    }
+}
+
+/// Fix level, see [fix_level_from_str] for the order
+fn fix_level(pvt: &NavPvtRef) -> u8 {
+    let flags = pvt.flags();
+    if !flags.contains(NavPvtFlags::GPS_FIX_OK) {
+        return 0;
+    }
+    match pvt.fix_type() {
+        GpsFix::DeadReckoningOnly => 1,
+        GpsFix::Fix2D => 2,
+        GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning => {
+            if flags.contains(NavPvtFlags::CARR_SOLN_FIXED) {
+                5
+            } else if flags.contains(NavPvtFlags::CARR_SOLN_FLOAT) {
+                4
+            } else {
+                3
+            }
+        },
+        _ => 0,
+    }
+}
+
+fn fix_level_from_str(s: &str) -> Option<u8> {
+    match s {
+        "none" => Some(0),
+        "dr" => Some(1),
+        "2d" => Some(2),
+        "3d" => Some(3),
+        "rtk-float" => Some(4),
+        "rtk-fixed" => Some(5),
+        _ => None,
+    }
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    let text = text.trim().to_ascii_lowercase();
+    match text.as_str() {
+        "rtk-float" => return Ok(Condition::Fix(Operator::GreaterOrEqual, 4)),
+        "rtk-fixed" => return Ok(Condition::Fix(Operator::GreaterOrEqual, 5)),
+        "geofence-exit" => return Ok(Condition::GeofenceExit),
+        _ => {},
+    }
+
+    let start = text.find(['<', '>', '=', '!']).ok_or_else(|| {
+        format!(
+            "invalid condition \"{}\": expecting rtk-float, rtk-fixed, geofence-exit \
+or a comparison like \"speed>5\"",
+            text
+        )
+    })?;
+    let (name, rest) = text.split_at(start);
+    let (op, value) = [
+        ("<=", Operator::LessOrEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+        ("=", Operator::Equal),
+    ]
+    .iter()
+    .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (*op, value.trim())))
+    .ok_or_else(|| format!("invalid comparison in condition \"{}\"", text))?;
+
+    let name = name.trim();
+    if name == "fix" {
+        let level = fix_level_from_str(value).ok_or_else(|| {
+            format!(
+                "invalid fix level \"{}\": expecting none, dr, 2d, 3d, rtk-float or rtk-fixed",
+                value
+            )
+        })?;
+        return Ok(Condition::Fix(op, level));
+    }
+    let quantity = match name {
+        "speed" => Quantity::Speed,
+        "hacc" => Quantity::HAcc,
+        "vacc" => Quantity::VAcc,
+        "numsv" => Quantity::NumSv,
+        _ => {
+            return Err(format!(
+                "unknown quantity \"{}\": expecting fix, speed, hacc, vacc or numsv",
+                name
+            ))
+        },
+    };
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| format!("invalid value \"{}\" in condition \"{}\"", value, text))?;
+    Ok(Condition::Value(quantity, op, value))
+}
+
+/// Great circle distance between two positions in degrees (m)
+fn distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = phi2 - phi1;
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
@@ -0,0 +1,90 @@
+//! Timestamped recordings (`--timestamps`), read back by ubx-read.
+//!
+//! The file starts with the 8 bytes magic `UBXTS`, version 1, two reserved bytes, followed
+//! by one record per frame: its reception time, monotonic since the start of the recording
+//! (u64) and UTC since the Unix epoch (i64), both in nanoseconds, its length (u32), all
+//! little endian, then the frame itself. Only valid UBX, NMEA and RTCM3 frames are recorded.
+
+use std::time::Instant;
+
+use chrono::Utc;
+
+use crate::protocol::ProtocolScanner;
+use crate::sink::Sink;
+
+pub const MAGIC: [u8; 8] = *b"UBXTS\x01\x00\x00";
+
+/// Output file receiving every frame in a record, with its reception time
+pub struct Timestamped<W: Sink> {
+    output: W,
+    start: Instant,
+    scanner: ProtocolScanner,
+    /// Bytes belonging to no frame
+    dropped: usize,
+}
+
+impl<W: Sink> Timestamped<W> {
+    /// Starts the recording, with the metadata block as its first record when given.
+    /// A resumed recording already starts with the magic
+    pub fn new(mut output: W, metadata: Option<&[u8]>, resumed: bool) -> std::io::Result<Self> {
+        if !resumed {
+            output.raw(&MAGIC)?;
+        }
+        let mut timestamped = Self {
+            output,
+            start: Instant::now(),
+            scanner: ProtocolScanner::default(),
+            dropped: 0,
+        };
+        if let Some(block) = metadata {
+            let mut record = Vec::new();
+            timestamped.record(&mut record, block);
+            timestamped.output.raw(&record)?;
+        }
+        Ok(timestamped)
+    }
+
+    /// Appends the record of a frame received now
+    fn record(&self, records: &mut Vec<u8>, frame: &[u8]) {
+        let monotonic = self.start.elapsed().as_nanos() as u64;
+        // out of range past 2262
+        let utc = Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX);
+        records.extend_from_slice(&monotonic.to_le_bytes());
+        records.extend_from_slice(&utc.to_le_bytes());
+        records.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        records.extend_from_slice(frame);
+    }
+}
+
+impl<W: Sink> Sink for Timestamped<W> {
+    fn name(&self) -> String {
+        self.output.name()
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut frames = Vec::new();
+        self.scanner.scan(data, |protocol, frame| {
+            if protocol.is_some() {
+                frames.push(frame.to_vec());
+            } else {
+                self.dropped += frame.len();
+            }
+        });
+        let mut records = Vec::new();
+        for frame in frames {
+            self.record(&mut records, &frame);
+        }
+        self.output.raw(&records)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        // an incomplete frame is not recorded
+        self.dropped += self.scanner.take().len();
+        info!(
+            "{}: {} bytes of invalid data dropped",
+            self.output.name(),
+            self.dropped
+        );
+        self.output.finish()
+    }
+}
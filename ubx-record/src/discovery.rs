@@ -0,0 +1,343 @@
+//! Serial port discovery, by USB vendor id or device strings, and listing of the ports
+
+use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+use crate::metadata::{json_or_null, json_string, Receiver};
+
+/// u-blox USB vendor id
+pub const UBLOX_VID: u16 = 0x1546;
+
+/// Port selection criteria. Without strings to match, u-blox USB devices are selected.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    /// Part of the USB serial number
+    pub serial: Option<String>,
+    /// Part of the USB product string, case insensitive
+    pub product: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        let SerialPortType::UsbPort(usb) = &port.port_type else {
+            return false;
+        };
+        if self.serial.is_none() && self.product.is_none() {
+            return usb.vid == UBLOX_VID;
+        }
+        let serial_ok = match &self.serial {
+            Some(serial) => usb
+                .serial_number
+                .as_deref()
+                .is_some_and(|s| s.contains(serial.as_str())),
+            None => true,
+        };
+        let product_ok = match &self.product {
+            Some(product) => usb
+                .product
+                .as_deref()
+                .is_some_and(|p| p.to_lowercase().contains(&product.to_lowercase())),
+            None => true,
+        };
+        serial_ok && product_ok
+    }
+}
+
+/// Returns the name of the single port matching the filter, or an error
+/// listing the candidates
+pub fn select(ports: &[SerialPortInfo], filter: &Filter) -> Result<String, String> {
+    let matching = ports
+        .iter()
+        .filter(|port| filter.matches(port))
+        .collect::<Vec<_>>();
+    match matching[..] {
+        [port] => Ok(port.port_name.clone()),
+        [] => {
+            let mut message = "no matching serial port".to_string();
+            if ports.is_empty() {
+                message.push_str(", no serial port found");
+            }
+            for port in ports {
+                message.push_str(&format!("\n  {}", describe(port)));
+            }
+            Err(message)
+        },
+        _ => {
+            let mut message = format!(
+                "{} matching serial ports, use --match-serial or --match-product to select one",
+                matching.len()
+            );
+            for port in matching {
+                message.push_str(&format!("\n  {}", describe(port)));
+            }
+            Err(message)
+        },
+    }
+}
+
+/// Describes a port, like "/dev/ttyACM0: USB 1546:01a9 u-blox GNSS receiver (serial 1234)"
+pub fn describe(port: &SerialPortInfo) -> String {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => format!("{}: {}", port.port_name, describe_usb(usb)),
+        SerialPortType::PciPort => format!("{}: PCI", port.port_name),
+        SerialPortType::BluetoothPort => format!("{}: Bluetooth", port.port_name),
+        SerialPortType::Unknown => format!("{}: unknown", port.port_name),
+    }
+}
+
+fn describe_usb(usb: &UsbPortInfo) -> String {
+    let mut description = format!("USB {:04x}:{:04x}", usb.vid, usb.pid);
+    for text in [&usb.manufacturer, &usb.product].into_iter().flatten() {
+        description.push(' ');
+        description.push_str(text);
+    }
+    if let Some(serial) = &usb.serial_number {
+        description.push_str(&format!(" (serial {})", serial));
+    }
+    description
+}
+
+/// Port of the `list-ports` listing
+pub struct Listed {
+    pub info: SerialPortInfo,
+    /// Version of the receiver answering the probe, None when not probed
+    pub probe: Option<Result<Receiver, String>>,
+}
+
+impl Listed {
+    fn ublox(&self) -> bool {
+        matches!(&self.info.port_type, SerialPortType::UsbPort(usb) if usb.vid == UBLOX_VID)
+    }
+
+    fn usb(&self) -> Option<&UsbPortInfo> {
+        match &self.info.port_type {
+            SerialPortType::UsbPort(usb) => Some(usb),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match &self.info.port_type {
+            SerialPortType::UsbPort(_) => "usb",
+            SerialPortType::PciPort => "pci",
+            SerialPortType::BluetoothPort => "bluetooth",
+            SerialPortType::Unknown => "unknown",
+        }
+    }
+}
+
+/// Lists the ports, u-blox receivers first
+pub fn listing(mut ports: Vec<SerialPortInfo>) -> Vec<Listed> {
+    ports.sort_by_key(|port| !Filter::default().matches(port));
+    ports
+        .into_iter()
+        .map(|info| Listed { info, probe: None })
+        .collect()
+}
+
+/// Table of the ports, with the probe results when probed
+pub fn table(ports: &[Listed]) -> String {
+    let probed = ports.iter().any(|port| port.probe.is_some());
+    let mut rows = vec![["port", "usb id", "manufacturer", "product", "serial", "u-blox"]
+        .iter()
+        .map(|title| title.to_string())
+        .collect::<Vec<_>>()];
+    if probed {
+        rows[0].extend(["firmware".to_string(), "protocol".to_string()]);
+    }
+    for port in ports {
+        let usb = port.usb();
+        let text = |value: Option<&String>| value.cloned().unwrap_or_else(|| "-".to_string());
+        let mut row = vec![
+            port.info.port_name.clone(),
+            usb.map_or_else(
+                || port.kind().to_string(),
+                |usb| format!("{:04x}:{:04x}", usb.vid, usb.pid),
+            ),
+            text(usb.and_then(|usb| usb.manufacturer.as_ref())),
+            text(usb.and_then(|usb| usb.product.as_ref())),
+            text(usb.and_then(|usb| usb.serial_number.as_ref())),
+            if port.ublox() { "yes" } else { "no" }.to_string(),
+        ];
+        match &port.probe {
+            Some(Ok(receiver)) => {
+                row.push(text(receiver.firmware().as_ref()));
+                row.push(text(receiver.protocol().as_ref()));
+            },
+            Some(Err(e)) => row.push(format!("no answer: {}", e)),
+            None if probed => row.push("-".to_string()),
+            None => {},
+        }
+        rows.push(row);
+    }
+    let mut widths = vec![0; rows[0].len()];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// JSON array of the ports, "probe" being null when not probed
+pub fn json(ports: &[Listed]) -> String {
+    let ports = ports
+        .iter()
+        .map(|port| {
+            let usb = port.usb();
+            let number = |value: Option<u16>| value.map_or("null".to_string(), |v| v.to_string());
+            let probe = match &port.probe {
+                Some(Ok(receiver)) => format!(
+                    "{{\"firmware\":{},\"protocol\":{}}}",
+                    json_or_null(receiver.firmware()),
+                    json_or_null(receiver.protocol())
+                ),
+                Some(Err(e)) => format!("{{\"error\":{}}}", json_string(e)),
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"port\":{},\"type\":\"{}\",\"vid\":{},\"pid\":{},\"manufacturer\":{},\
+\"product\":{},\"serial_number\":{},\"ublox\":{},\"probe\":{}}}",
+                json_string(&port.info.port_name),
+                port.kind(),
+                number(usb.map(|usb| usb.vid)),
+                number(usb.map(|usb| usb.pid)),
+                json_or_null(usb.and_then(|usb| usb.manufacturer.clone())),
+                json_or_null(usb.and_then(|usb| usb.product.clone())),
+                json_or_null(usb.and_then(|usb| usb.serial_number.clone())),
+                port.ublox(),
+                probe
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", ports.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usb(name: &str, vid: u16, product: Option<&str>, serial: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid: 0x01a9,
+                serial_number: serial.map(str::to_string),
+                manufacturer: Some("u-blox AG".to_string()),
+                product: product.map(str::to_string),
+            }),
+        }
+    }
+
+    fn ports() -> Vec<SerialPortInfo> {
+        vec![
+            SerialPortInfo {
+                port_name: "/dev/ttyS0".to_string(),
+                port_type: SerialPortType::Unknown,
+            },
+            usb("/dev/ttyUSB0", 0x0403, Some("FT232R USB UART"), Some("A50285BI")),
+            usb("/dev/ttyACM0", UBLOX_VID, Some("u-blox GNSS receiver"), Some("1234")),
+            usb("/dev/ttyACM1", UBLOX_VID, Some("u-blox 9 GNSS"), Some("5678")),
+        ]
+    }
+
+    fn filter(serial: Option<&str>, product: Option<&str>) -> Filter {
+        Filter {
+            serial: serial.map(str::to_string),
+            product: product.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn selection() {
+        let ports = ports();
+        assert_eq!(select(&ports, &filter(Some("56"), None)).unwrap(), "/dev/ttyACM1");
+        // case insensitive product, any vendor once strings are given
+        assert_eq!(select(&ports, &filter(None, Some("ft232r"))).unwrap(), "/dev/ttyUSB0");
+        assert_eq!(
+            select(&ports, &filter(Some("1234"), Some("GNSS"))).unwrap(),
+            "/dev/ttyACM0"
+        );
+        // serial numbers are case sensitive
+        assert!(select(&ports, &filter(Some("a50285bi"), None)).is_err());
+    }
+
+    #[test]
+    fn ambiguous_selection() {
+        let error = select(&ports(), &Filter::default()).unwrap_err();
+        assert_eq!(
+            error,
+            "2 matching serial ports, use --match-serial or --match-product to select one
+  /dev/ttyACM0: USB 1546:01a9 u-blox AG u-blox GNSS receiver (serial 1234)
+  /dev/ttyACM1: USB 1546:01a9 u-blox AG u-blox 9 GNSS (serial 5678)"
+        );
+    }
+
+    #[test]
+    fn no_selection() {
+        let error = select(&ports(), &filter(Some("9999"), None)).unwrap_err();
+        assert!(error.starts_with("no matching serial port\n  /dev/ttyS0: unknown\n"));
+        assert_eq!(error.lines().count(), 5);
+        assert_eq!(
+            select(&[], &Filter::default()).unwrap_err(),
+            "no matching serial port, no serial port found"
+        );
+    }
+
+    #[test]
+    fn listing_order() {
+        let listed = listing(ports());
+        let names = listed
+            .iter()
+            .map(|port| port.info.port_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["/dev/ttyACM0", "/dev/ttyACM1", "/dev/ttyS0", "/dev/ttyUSB0"]);
+    }
+
+    #[test]
+    fn table_and_json() {
+        let ublox = usb("/dev/ttyACM0", UBLOX_VID, None, None);
+        let mut listed = listing(vec![ports().remove(0), ublox]);
+        assert_eq!(
+            table(&listed),
+            "port          usb id     manufacturer  product  serial  u-blox
+/dev/ttyACM0  1546:01a9  u-blox AG     -        -       yes
+/dev/ttyS0    unknown    -             -        -       no"
+        );
+        listed[1].probe = Some(Err("timeout".to_string()));
+        assert_eq!(
+            json(&listed),
+            "[{\"port\":\"/dev/ttyACM0\",\"type\":\"usb\",\"vid\":5446,\"pid\":425,\
+\"manufacturer\":\"u-blox AG\",\"product\":null,\"serial_number\":null,\"ublox\":true,\
+\"probe\":null},{\"port\":\"/dev/ttyS0\",\"type\":\"unknown\",\"vid\":null,\"pid\":null,\
+\"manufacturer\":null,\"product\":null,\"serial_number\":null,\"ublox\":false,\
+\"probe\":{\"error\":\"timeout\"}}]"
+        );
+    }
+
+    #[test]
+    fn probed_table() {
+        let mut version = vec![0; 40];
+        version[..8].copy_from_slice(b"ROM CORE");
+        let mut extension = [0; 30];
+        extension[..13].copy_from_slice(b"PROTVER=18.00");
+        version.extend_from_slice(&extension);
+        let mut listed = listing(vec![usb("/dev/ttyACM0", UBLOX_VID, None, None)]);
+        listed[0].probe = Some(Ok(Receiver::from_version(version)));
+        assert_eq!(
+            table(&listed).lines().last().unwrap(),
+            "/dev/ttyACM0  1546:01a9  u-blox AG     -        -       yes     ROM CORE  18.00"
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Sensor fusion of the dead reckoning receivers, like the NEO-M8U: the fusion mode and the
+//! calibration of the sensors of the UBX-ESF-STATUS messages, printed at each epoch with
+//! `--print-esf-status` so that the calibration can be checked before driving off
+
+use ubx_common::esf::{Status, ESF_STATUS};
+use ublox::PacketRef;
+
+use crate::sink::Sink;
+
+/// Why the ESF messages are rejected
+pub const NO_FUSION: &str = "the receiver has no sensor fusion, only the dead reckoning (ADR or \
+UDR) receivers like the NEO-M8U or ZED-F9R output the ESF messages";
+
+/// Prints the fusion mode and the calibration of each sensor of the ESF-STATUS messages,
+/// as warnings while the sensors are initializing
+#[derive(Default)]
+pub struct EsfStatusPrinter {
+    /// Malformed messages, reported once and counted
+    malformed: usize,
+}
+
+impl Sink for EsfStatusPrinter {
+    fn name(&self) -> String {
+        "ESF-STATUS printer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        // not decoded by the ublox crate
+        let PacketRef::Unknown(unknown) = packet else {
+            return Ok(());
+        };
+        if (unknown.class, unknown.msg_id) != ESF_STATUS {
+            return Ok(());
+        }
+        match Status::parse(unknown.payload) {
+            Ok(status) if status.initializing() => warn!(
+                "sensor fusion: {}, iTOW {} ms: sensors still initializing",
+                status.describe(),
+                status.itow
+            ),
+            Ok(status) => info!("sensor fusion: {}, iTOW {} ms", status.describe(), status.itow),
+            Err(e) => {
+                if self.malformed == 0 {
+                    warn!("{}", e);
+                }
+                self.malformed += 1;
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if self.malformed > 1 {
+            warn!("{} malformed ESF-STATUS messages ignored", self.malformed);
+        }
+        Ok(())
+    }
+}
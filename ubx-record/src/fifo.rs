@@ -0,0 +1,241 @@
+//! Named pipe (FIFO) output, for tools reading the live stream like gpsd or RTKLIB.
+//!
+//! The pipe is opened without blocking: as long as no reader is attached,
+//! or once the reader went away, data is buffered up to a limit and the
+//! pipe is opened again periodically, so recording never stalls.
+
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Delay between attempts to open the pipe
+const RETRY_PERIOD: Duration = Duration::from_millis(500);
+
+/// Returns true when the path is an existing named pipe
+pub fn is_fifo(path: &str) -> bool {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+/// Creates the named pipe, unless it exists already
+pub fn create(path: &str) -> std::io::Result<()> {
+    if is_fifo(path) {
+        return Ok(());
+    }
+    if Path::new(path).exists() {
+        return Err(std::io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("\"{}\" exists and is not a named pipe", path),
+        ));
+    }
+    let c_path = CString::new(Path::new(path).as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, e))?;
+    // SAFETY: c_path is a valid NUL terminated string
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub struct FifoWriter {
+    path: String,
+    /// Pipe, while a reader is attached
+    pipe: Option<File>,
+    /// Data not written yet, oldest first
+    pending: VecDeque<u8>,
+    max_pending: usize,
+    /// Bytes dropped since the last report
+    dropped: usize,
+    last_attempt: Option<Instant>,
+}
+
+impl FifoWriter {
+    /// Buffers up to `max_pending` bytes while no reader is attached
+    pub fn new(path: &str, max_pending: usize) -> Self {
+        info!("waiting for a reader on \"{}\"..", path);
+        Self {
+            path: path.to_string(),
+            pipe: None,
+            pending: VecDeque::new(),
+            max_pending,
+            dropped: 0,
+            last_attempt: None,
+        }
+    }
+
+    /// Opens the pipe when a reader is attached, without blocking
+    fn connect(&mut self) -> std::io::Result<()> {
+        let now = Instant::now();
+        if self
+            .last_attempt
+            .is_some_and(|last| now.duration_since(last) < RETRY_PERIOD)
+        {
+            return Ok(());
+        }
+        self.last_attempt = Some(now);
+        match OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)
+        {
+            Ok(pipe) => {
+                info!("reader attached to \"{}\"", self.path);
+                if self.dropped > 0 {
+                    info!(
+                        "{} bytes dropped while the pipe was not read",
+                        self.dropped
+                    );
+                    self.dropped = 0;
+                }
+                self.pipe = Some(pipe);
+                Ok(())
+            },
+            // no reader yet
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes as much pending data as the pipe accepts
+    fn drain(&mut self) -> std::io::Result<()> {
+        while let Some(pipe) = &mut self.pipe {
+            let (data, _) = self.pending.as_slices();
+            if data.is_empty() {
+                return Ok(());
+            }
+            match pipe.write(data) {
+                Ok(size) => {
+                    self.pending.drain(..size);
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    info!(
+                        "reader detached from \"{}\", waiting for a new one..",
+                        self.path
+                    );
+                    self.pipe = None;
+                    self.last_attempt = None;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for FifoWriter {
+    /// Never blocks: data is buffered, the oldest being dropped
+    /// beyond the buffer limit
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend(buf);
+        if self.pipe.is_none() {
+            self.connect()?;
+        }
+        self.drain()?;
+        if self.pending.len() > self.max_pending {
+            let excess = self.pending.len() - self.max_pending;
+            if self.dropped == 0 {
+                warn!("\"{}\" buffer full, dropping the oldest data", self.path);
+            }
+            self.pending.drain(..excess);
+            self.dropped += excess;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Path in the temporary directory, removed first
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ubx-record-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    /// Reading end, opened without blocking like the writer
+    fn reader(path: &str) -> File {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn creation() {
+        let path = temp_path("create.fifo");
+        assert!(!is_fifo(&path));
+        create(&path).unwrap();
+        assert!(is_fifo(&path));
+        // existing pipes are reused
+        create(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_path("create.ubx");
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(create(&path).unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert!(!is_fifo(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn buffered_without_reader() {
+        let path = temp_path("buffered.fifo");
+        create(&path).unwrap();
+        let mut writer = FifoWriter::new(&path, 8);
+        // never blocks, the oldest data being dropped
+        assert_eq!(writer.write(b"0123456789").unwrap(), 10);
+        assert_eq!(writer.write(b"abc").unwrap(), 3);
+        assert_eq!(writer.dropped, 5);
+        assert!(writer.pipe.is_none());
+
+        let mut reader = reader(&path);
+        writer.last_attempt = None;
+        writer.write_all(b"de").unwrap();
+        assert!(writer.pipe.is_some());
+        assert_eq!(writer.dropped, 0);
+        let mut received = vec![];
+        reader.read_to_end(&mut received).unwrap_or_default();
+        // the pending data is drained ahead of the limit
+        assert_eq!(received, b"56789abcde");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reader_going_away() {
+        let path = temp_path("detach.fifo");
+        create(&path).unwrap();
+        let mut writer = FifoWriter::new(&path, 1024);
+        let mut first = reader(&path);
+        writer.write_all(b"first").unwrap();
+        let mut received = [0; 16];
+        assert_eq!(first.read(&mut received).unwrap(), 5);
+        drop(first);
+
+        // buffered once the reader is gone, written to the next one
+        writer.write_all(b"second").unwrap();
+        assert!(writer.pipe.is_none());
+        let mut next = reader(&path);
+        writer.last_attempt = None;
+        writer.flush().unwrap();
+        writer.write_all(b"!").unwrap();
+        assert_eq!(next.read(&mut received).unwrap(), 7);
+        assert_eq!(&received[..7], b"second!");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! End of the recording a given time after the first 3D fix (`--stop-after-fix`), like on
+//! production test stations proving each unit gets a fix, with a short window of data after it.
+
+use std::time::{Duration, Instant};
+
+use ublox::{GpsFix, NavPvtFlags, PacketRef, Parser};
+
+pub struct FixWatch {
+    start: Instant,
+    /// Recording time after the first fix
+    after: Duration,
+    /// Longest wait for the first fix
+    timeout: Option<Duration>,
+    parser: Parser<Vec<u8>>,
+    /// Time to first fix, since the start of the recording
+    ttff: Option<Duration>,
+}
+
+impl FixWatch {
+    pub fn new(start: Instant, after: Duration, timeout: Option<Duration>) -> Self {
+        Self {
+            start,
+            after,
+            timeout,
+            parser: Parser::default(),
+            ttff: None,
+        }
+    }
+
+    pub fn ttff(&self) -> Option<Duration> {
+        self.ttff
+    }
+
+    /// Looks for the first 3D fix in a UBX frame
+    pub fn frame(&mut self, frame: &[u8]) {
+        // NAV-PVT only
+        if self.ttff.is_some() || frame[2..4] != [0x01, 0x07] {
+            return;
+        }
+        let mut it = self.parser.consume(frame);
+        let mut fixed = false;
+        while let Some(packet) = it.next() {
+            if let Ok(PacketRef::NavPvt(pvt)) = packet {
+                fixed |= pvt.flags().contains(NavPvtFlags::GPS_FIX_OK)
+                    && matches!(pvt.fix_type(), GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning);
+            }
+        }
+        if fixed {
+            let ttff = self.start.elapsed();
+            info!(
+                "first 3D fix {:.1} s after the start of the recording, recording {:?} more",
+                ttff.as_secs_f64(),
+                self.after
+            );
+            self.ttff = Some(ttff);
+        }
+    }
+
+    /// True once recorded long enough after the fix
+    pub fn is_done(&self) -> bool {
+        self.ttff
+            .is_some_and(|ttff| self.start.elapsed() >= ttff + self.after)
+    }
+
+    /// True when the fix timeout elapsed without a fix
+    pub fn timed_out(&self) -> bool {
+        self.ttff.is_none()
+            && self
+                .timeout
+                .is_some_and(|timeout| self.start.elapsed() >= timeout)
+    }
+}
@@ -0,0 +1,136 @@
+//! UBX framing of the received byte stream
+
+pub use ubx_common::frame::{checksum, frame};
+
+const SYNC_CHAR_1: u8 = 0xb5;
+const SYNC_CHAR_2: u8 = 0x62;
+
+/// Frames larger than this are considered corrupted
+const MAX_PAYLOAD_LEN: usize = 8192;
+
+/// UBX message classes and their names
+pub const CLASSES: [(u8, &str); 14] = [
+    (0x01, "NAV"),
+    (0x02, "RXM"),
+    (0x04, "INF"),
+    (0x05, "ACK"),
+    (0x06, "CFG"),
+    (0x09, "UPD"),
+    (0x0a, "MON"),
+    (0x0b, "AID"),
+    (0x0d, "TIM"),
+    (0x10, "ESF"),
+    (0x13, "MGA"),
+    (0x21, "LOG"),
+    (0x27, "SEC"),
+    (0x28, "HNR"),
+];
+
+/// Name of the UBX message class, like "NAV", None when unknown
+pub fn class_name(class: u8) -> Option<&'static str> {
+    CLASSES
+        .iter()
+        .find(|(known, _)| *known == class)
+        .map(|(_, name)| *name)
+}
+
+/// UBX message class of a name like "NAV", or a value like "0x01"
+pub fn parse_class(s: &str) -> Result<u8, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        return u8::from_str_radix(hex, 16).map_err(|e| format!("invalid class \"{}\": {}", s, e));
+    }
+    CLASSES
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(s))
+        .map(|(class, _)| *class)
+        .ok_or_else(|| format!("unknown class \"{}\", expecting a name like NAV or a value like 0x01", s))
+}
+
+/// Splits the received bytes into complete UBX frames with a valid checksum,
+/// dropping anything else
+#[derive(Default)]
+pub struct FrameScanner {
+    buf: Vec<u8>,
+    /// Frames dropped for a checksum mismatch
+    checksum_failures: usize,
+}
+
+impl FrameScanner {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    pub fn checksum_failures(&self) -> usize {
+        self.checksum_failures
+    }
+
+    /// Bytes kept for the next frames
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the next validated frame, sync chars and checksum included
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let start = self
+                .buf
+                .windows(2)
+                .position(|w| w == [SYNC_CHAR_1, SYNC_CHAR_2]);
+            match start {
+                Some(start) => {
+                    self.buf.drain(..start);
+                },
+                None => {
+                    // keep a possible first sync char
+                    let keep = usize::from(self.buf.last() == Some(&SYNC_CHAR_1));
+                    self.buf.drain(..self.buf.len() - keep);
+                    return None;
+                },
+            }
+            if self.buf.len() < 6 {
+                return None;
+            }
+            let len = usize::from(u16::from_le_bytes([self.buf[4], self.buf[5]]));
+            if len > MAX_PAYLOAD_LEN {
+                self.buf.drain(..2);
+                continue;
+            }
+            if self.buf.len() < len + 8 {
+                return None;
+            }
+            let (ck_a, ck_b) = checksum(&self.buf[2..len + 6]);
+            if (ck_a, ck_b) != (self.buf[len + 6], self.buf[len + 7]) {
+                self.checksum_failures += 1;
+                self.buf.drain(..2);
+                continue;
+            }
+            return Some(self.buf.drain(..len + 8).collect());
+        }
+    }
+}
+
+/// Description of a frame on a few lines: class and id, the message as ublox decodes it,
+/// like ubx-read prints it, and the frame in hexadecimal
+pub fn describe(frame: &[u8]) -> String {
+    let (class, id) = (frame[2], frame[3]);
+    let mut parser = ublox::Parser::default();
+    let decoded = match parser.consume(frame).next() {
+        Some(Ok(packet)) => format!("{:?}", packet),
+        Some(Err(e)) => format!("not decoded: {}", e),
+        None => "not decoded".to_string(),
+    };
+    let hex = frame
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "class {} (0x{:02x}) id 0x{:02x}, {} bytes payload\n  {}\n  {}",
+        class_name(class).unwrap_or("?"),
+        class,
+        id,
+        frame.len().saturating_sub(8),
+        decoded,
+        hex
+    )
+}
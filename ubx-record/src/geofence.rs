@@ -0,0 +1,213 @@
+//! Geofences of the receiver (UBX-CFG-GEOFENCE) for the configure geofence subcommand: up to
+//! four circles, evaluated by the receiver at a confidence level, optionally driving a PIO pin,
+//! and their states of the UBX-NAV-GEOFENCE messages during the recording
+//! (`--monitor-geofence`), each change being printed
+
+use crate::condition::Geofence;
+use crate::frame;
+use crate::Device;
+
+/// Geofences supported by the firmware
+pub const MAX_FENCES: usize = 4;
+
+/// Sane radii (m), smaller fences flipping with the noise of the solutions
+const MIN_RADIUS: f64 = 1.0;
+const MAX_RADIUS: f64 = 10_000_000.0;
+
+/// Confidence levels of the fence states, by name, with their confLvl
+pub const CONFIDENCES: [(&str, u8); 6] = [
+    ("none", 0),
+    ("1sigma", 1),
+    ("2sigma", 2),
+    ("3sigma", 3),
+    ("4sigma", 4),
+    ("5sigma", 5),
+];
+
+/// Length of the CFG-GEOFENCE header, and of each fence following it
+const HEADER_LEN: usize = 8;
+const FENCE_LEN: usize = 12;
+
+/// Geofence settings, from a UBX-CFG-GEOFENCE payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fences {
+    payload: Vec<u8>,
+}
+
+impl Fences {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let fences = usize::from(*payload.get(1)?);
+        (payload.len() >= HEADER_LEN + fences * FENCE_LEN).then(|| Self {
+            payload: payload[..HEADER_LEN + fences * FENCE_LEN].to_vec(),
+        })
+    }
+
+    /// Encodes the `fences`, none clearing them, after checking them
+    pub fn new(fences: &[Geofence], confidence: u8, pin: Option<u8>) -> Result<Self, String> {
+        if fences.len() > MAX_FENCES {
+            return Err(format!(
+                "{} geofences given, the receiver supporting {} at most",
+                fences.len(),
+                MAX_FENCES
+            ));
+        }
+        let mut payload = vec![
+            0,
+            fences.len() as u8,
+            confidence,
+            0,
+            u8::from(pin.is_some()),
+            // the pin is low inside the fences
+            0,
+            pin.unwrap_or_default(),
+            0,
+        ];
+        for fence in fences {
+            if !(-90.0..=90.0).contains(&fence.lat) || !(-180.0..=180.0).contains(&fence.lon) {
+                return Err(format!(
+                    "Geofence center {:.7},{:.7} out of range",
+                    fence.lat, fence.lon
+                ));
+            }
+            if !(MIN_RADIUS..=MAX_RADIUS).contains(&fence.radius) {
+                return Err(format!(
+                    "Geofence radius of {} m out of range, from {} m to {} km",
+                    fence.radius,
+                    MIN_RADIUS,
+                    MAX_RADIUS / 1000.0
+                ));
+            }
+            payload.extend_from_slice(&((fence.lat * 1e7).round() as i32).to_le_bytes());
+            payload.extend_from_slice(&((fence.lon * 1e7).round() as i32).to_le_bytes());
+            payload.extend_from_slice(&((fence.radius * 100.0).round() as u32).to_le_bytes());
+        }
+        Ok(Self { payload })
+    }
+
+    fn value(&self, offset: usize) -> [u8; 4] {
+        [
+            self.payload[offset],
+            self.payload[offset + 1],
+            self.payload[offset + 2],
+            self.payload[offset + 3],
+        ]
+    }
+
+    /// Like "2 fences at 2sigma, PIO 3 low inside: 48.8583701,2.2944813 within 50.00 m, ..."
+    pub fn describe(&self) -> String {
+        let count = usize::from(self.payload[1]);
+        if count == 0 {
+            return "no geofence".to_string();
+        }
+        let confidence = CONFIDENCES
+            .iter()
+            .find(|(_, level)| *level == self.payload[2])
+            .map_or_else(|| format!("level {}", self.payload[2]), |(name, _)| name.to_string());
+        let pin = match self.payload[4] {
+            0 => String::new(),
+            _ => format!(
+                ", PIO {} {} inside",
+                self.payload[6],
+                if self.payload[5] == 0 { "low" } else { "high" }
+            ),
+        };
+        let fences = (0..count)
+            .map(|index| {
+                let offset = HEADER_LEN + index * FENCE_LEN;
+                format!(
+                    "{:.7},{:.7} within {:.2} m",
+                    f64::from(i32::from_le_bytes(self.value(offset))) * 1e-7,
+                    f64::from(i32::from_le_bytes(self.value(offset + 4))) * 1e-7,
+                    f64::from(u32::from_le_bytes(self.value(offset + 8))) * 1e-2
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{} fence{} at {}{}: {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            confidence,
+            pin,
+            fences.join(", ")
+        )
+    }
+}
+
+/// Sets the geofences, replacing the ones of the receiver, returning the ones polled back
+pub fn set(device: &mut Device, fences: &Fences) -> Result<Option<Fences>, String> {
+    info!("Setting the geofences: {} ...", fences.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x69, &fences.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-GEOFENCE: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x69, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the geofences (UBX-ACK-NAK of UBX-CFG-GEOFENCE): {}",
+                fences.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-GEOFENCE not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x69, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Fences::parse(&payload));
+    Ok(applied)
+}
+
+fn state_name(state: u8) -> &'static str {
+    match state {
+        0 => "unknown",
+        1 => "inside",
+        2 => "outside",
+        _ => "invalid",
+    }
+}
+
+/// Fence states of the UBX-NAV-GEOFENCE messages, printing their changes
+#[derive(Default)]
+pub struct GeofenceWatch {
+    /// Combined state, then the state of each fence
+    last: Vec<u8>,
+}
+
+impl GeofenceWatch {
+    /// Prints the changes of the states of a NAV-GEOFENCE frame
+    pub fn frame(&mut self, frame: &[u8]) {
+        if frame[2..4] != [0x01, 0x39] {
+            return;
+        }
+        let payload = &frame[6..frame.len() - 2];
+        // not available, like without a fence
+        if payload.len() < 8 || payload[5] == 0 {
+            return;
+        }
+        let (count, combined) = (payload[6], payload[7]);
+        let mut states = vec![combined];
+        states.extend(
+            payload[8..]
+                .chunks_exact(2)
+                .take(usize::from(count))
+                .map(|fence| fence[0]),
+        );
+        let changes = (0..states.len())
+            .filter(|index| self.last.get(*index) != states.get(*index))
+            .map(|index| {
+                let name = match index {
+                    0 => "combined".to_string(),
+                    _ => format!("fence {}", index),
+                };
+                let state = state_name(states[index]);
+                match self.last.get(index) {
+                    Some(last) => format!("{} {} -> {}", name, state_name(*last), state),
+                    None => format!("{} {}", name, state),
+                }
+            })
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            info!("geofence: {}", changes.join(", "));
+        }
+        self.last = states;
+    }
+}
@@ -0,0 +1,286 @@
+//! Gzip output with periodic sync points, decompression being able to start at any of them.
+//!
+//! Every sync interval, at a frame boundary, the deflate stream is fully flushed:
+//! the compression dictionary is reset, so the data following a sync point decompresses
+//! on its own, as a raw deflate stream. The sync points are listed in a sidecar index,
+//! `<output>.sync`, one `compressed_offset uncompressed_offset` line each.
+//!
+//! Resetting the dictionary costs compression: the output is also compressed without
+//! sync points, only counting bytes, to measure it.
+
+use std::fs::File;
+use std::io::{BufWriter, LineWriter, Write};
+use std::time::{Duration, Instant};
+
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+
+use crate::protocol::ProtocolScanner;
+
+/// Gzip header: deflate, no flags nor modification time, unknown OS
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 0x08, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// Room made for each deflate call output
+const OUTPUT_CHUNK: usize = 16 * 1024;
+
+/// Period of the compression cost reports
+const REPORT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Deflate stream and its output
+struct Deflate {
+    compress: Compress,
+    output: Vec<u8>,
+}
+
+impl Deflate {
+    fn new(level: u32) -> Self {
+        Self {
+            compress: Compress::new(Compression::new(level), false),
+            output: Vec::with_capacity(OUTPUT_CHUNK),
+        }
+    }
+
+    /// Compresses the input, calling `f` with the output
+    fn run<F: FnMut(&[u8]) -> std::io::Result<()>>(
+        &mut self,
+        mut input: &[u8],
+        flush: FlushCompress,
+        mut f: F,
+    ) -> std::io::Result<()> {
+        loop {
+            self.output.clear();
+            let total_in = self.compress.total_in();
+            let status = self
+                .compress
+                .compress_vec(input, &mut self.output, flush)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            input = &input[(self.compress.total_in() - total_in) as usize..];
+            f(&self.output)?;
+            let done = match flush {
+                FlushCompress::Finish => status == Status::StreamEnd,
+                // all pending output was produced when some room is left
+                _ => input.is_empty() && self.output.len() < self.output.capacity(),
+            };
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub struct SyncedGzip {
+    output: BufWriter<File>,
+    index: LineWriter<File>,
+    deflate: Deflate,
+    /// Same stream compressed without sync points
+    reference: Deflate,
+    crc: Crc,
+    scanner: ProtocolScanner,
+    interval: Duration,
+    last_sync: Instant,
+    last_report: Instant,
+    sync_points: usize,
+    finished: bool,
+}
+
+impl SyncedGzip {
+    /// Creates the file, compressed at `level`, with a sync point every `interval`
+    pub fn create(path: &str, interval: Duration, level: u32) -> std::io::Result<Self> {
+        let mut output = BufWriter::new(File::create(path)?);
+        output.write_all(&GZIP_HEADER)?;
+        let mut index = LineWriter::new(File::create(format!("{}.sync", path))?);
+        writeln!(index, "# compressed_offset uncompressed_offset")?;
+        // the stream start is the first sync point
+        writeln!(index, "{} 0", GZIP_HEADER.len())?;
+        let now = Instant::now();
+        Ok(Self {
+            output,
+            index,
+            deflate: Deflate::new(level),
+            reference: Deflate::new(level),
+            crc: Crc::new(),
+            scanner: ProtocolScanner::default(),
+            interval,
+            last_sync: now,
+            last_report: now,
+            sync_points: 1,
+            finished: false,
+        })
+    }
+
+    fn compress(&mut self, data: &[u8], flush: FlushCompress) -> std::io::Result<()> {
+        self.crc.update(data);
+        let output = &mut self.output;
+        self.deflate
+            .run(data, flush, |bytes| output.write_all(bytes))?;
+        // the reference keeps its dictionary, only flushing its output when reporting
+        let reference_flush = match flush {
+            FlushCompress::Full => FlushCompress::None,
+            flush => flush,
+        };
+        self.reference.run(data, reference_flush, |_| Ok(()))
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.compress(&[], FlushCompress::Full)?;
+        writeln!(
+            self.index,
+            "{} {}",
+            GZIP_HEADER.len() as u64 + self.deflate.compress.total_out(),
+            self.deflate.compress.total_in()
+        )?;
+        self.sync_points += 1;
+        // the data up to the sync point reaches the file
+        self.output.flush()
+    }
+
+    /// Compression cost of the sync points so far, in percents of the size without them
+    fn cost(&mut self) -> std::io::Result<f64> {
+        if !self.finished {
+            self.reference.run(&[], FlushCompress::Sync, |_| Ok(()))?;
+        }
+        let compressed = self.deflate.compress.total_out();
+        let reference = self.reference.compress.total_out();
+        Ok(if reference > 0 {
+            100.0 * (compressed as f64 / reference as f64 - 1.0)
+        } else {
+            0.0
+        })
+    }
+
+    /// Sync points so far, and the compression cost of the sync points
+    fn report(&mut self) -> std::io::Result<()> {
+        let cost = self.cost()?;
+        info!(
+            "gzip sync points: {}, {} bytes compressed, {:+.1}% compared to no sync points",
+            self.sync_points,
+            self.deflate.compress.total_out(),
+            cost
+        );
+        Ok(())
+    }
+
+    /// Completes the gzip stream, once
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        // incomplete frames are kept as they are
+        let pending = self.scanner.take();
+        self.compress(&pending, FlushCompress::Finish)?;
+        self.finished = true;
+        self.output.write_all(&self.crc.sum().to_le_bytes())?;
+        self.output.write_all(&self.crc.amount().to_le_bytes())?;
+        self.output.flush()?;
+        self.report()
+    }
+}
+
+impl Write for SyncedGzip {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // only complete frames are compressed, sync points falling between frames
+        let mut aligned = Vec::with_capacity(buf.len());
+        self.scanner
+            .scan(buf, |_, bytes| aligned.extend_from_slice(bytes));
+        self.compress(&aligned, FlushCompress::None)?;
+        let now = Instant::now();
+        if now.duration_since(self.last_sync) >= self.interval {
+            self.sync()?;
+            self.last_sync = now;
+        }
+        if now.duration_since(self.last_report) >= REPORT_INTERVAL {
+            self.report()?;
+            self.last_report = now;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.deflate.run(&[], FlushCompress::Sync, |bytes| {
+            self.output.write_all(bytes)
+        })?;
+        self.output.flush()
+    }
+}
+
+impl Drop for SyncedGzip {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!("failed to complete the gzip output: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame;
+
+    /// One second of a rover at 1 Hz: NAV-PVT, NAV-SAT and RXM-RAWX of 24 satellites, their
+    /// measurements noisy
+    fn epoch(second: u32, noise: &mut u32) -> Vec<u8> {
+        let mut next = || {
+            *noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (*noise >> 16) as u8
+        };
+        let mut pvt = [0; 92];
+        pvt[..4].copy_from_slice(&(second * 1000).to_le_bytes());
+        pvt[20] = 3;
+        pvt[23] = 24;
+        for byte in &mut pvt[24..40] {
+            *byte = next() & 0x0f;
+        }
+        let mut sat = vec![0; 8 + 24 * 12];
+        sat[..4].copy_from_slice(&(second * 1000).to_le_bytes());
+        sat[5] = 24;
+        for (n, block) in sat[8..].chunks_mut(12).enumerate() {
+            block[1] = n as u8 + 1;
+            block[2] = 35 + (next() & 0x07);
+        }
+        let mut rawx = vec![0; 16 + 24 * 32];
+        rawx[11] = 24;
+        for (n, block) in rawx[16..].chunks_mut(32).enumerate() {
+            // pseudorange, carrier phase and doppler
+            for byte in &mut block[..20] {
+                *byte = next();
+            }
+            block[21] = n as u8 + 1;
+            block[26] = 40;
+        }
+        [frame(0x01, 0x07, &pvt), frame(0x01, 0x35, &sat), frame(0x02, 0x15, &rawx)].concat()
+    }
+
+    /// Cost of a sync point every `epochs` seconds of an hour of data
+    fn cost(epochs: usize) -> f64 {
+        let path = std::env::temp_dir()
+            .join(format!("ubx-record-{}-sync-{}.ubx.gz", std::process::id(), epochs))
+            .to_string_lossy()
+            .into_owned();
+        // a sync point after every write
+        let mut gzip = SyncedGzip::create(&path, Duration::ZERO, 6).unwrap();
+        let mut noise = 1;
+        let data: Vec<_> = (0..3600).map(|second| epoch(second, &mut noise)).collect();
+        for seconds in data.chunks(epochs) {
+            gzip.write_all(&seconds.concat()).unwrap();
+        }
+        gzip.finish().unwrap();
+        let cost = gzip.cost().unwrap();
+        let mut decoded = vec![];
+        let file = File::open(&path).unwrap();
+        std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(file), &mut decoded).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(format!("{}.sync", path));
+        assert_eq!(decoded, data.concat());
+        cost
+    }
+
+    #[test]
+    fn sync_point_cost() {
+        let (every_second, every_10s, every_minute) = (cost(1), cost(10), cost(60));
+        println!(
+            "sync point cost: {:+.1}% every second, {:+.1}% every 10 s, {:+.1}% every minute",
+            every_second, every_10s, every_minute
+        );
+        assert!(every_second > every_10s && every_10s > every_minute);
+        assert!(every_minute > 0.0);
+    }
+}
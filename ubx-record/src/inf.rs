@@ -0,0 +1,131 @@
+//! Informational text messages of the receiver (`--inf`): the severities of the UBX INF
+//! messages output on the ports (UBX-CFG-INF), and their text printed at the level of their
+//! severity while recording
+
+use ubx_common::inf::{self, SEVERITIES};
+use ublox::PacketRef;
+
+use crate::frame;
+use crate::messages::PORTS;
+use crate::sink::Sink;
+use crate::Device;
+
+/// Length of a CFG-INF block: protocolID, reserved, and the masks of the 6 ports
+const INF_BLOCK_LEN: usize = 10;
+const MASKS_OFFSET: usize = 4;
+
+/// protocolID of the UBX INF messages
+const PROTOCOL_UBX: u8 = 0;
+
+/// Severities of the `--inf` option, the INF-TEST messages being left out
+pub const NAMES: [&str; 4] = ["error", "warning", "notice", "debug"];
+
+/// CFG-INF mask of severities named like "warning"
+pub fn mask(names: &[String]) -> u8 {
+    SEVERITIES
+        .iter()
+        .filter(|(name, ..)| names.iter().any(|known| known == name))
+        .fold(0, |mask, (_, _, bit)| mask | bit)
+}
+
+/// UBX INF output of the ports, from a UBX-CFG-INF block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfOutput {
+    block: [u8; INF_BLOCK_LEN],
+}
+
+impl InfOutput {
+    /// UBX block of a CFG-INF payload, which holds a block per protocol
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let block = payload
+            .chunks_exact(INF_BLOCK_LEN)
+            .find(|block| block[0] == PROTOCOL_UBX)?;
+        Some(Self {
+            block: block.try_into().ok()?,
+        })
+    }
+
+    /// Like "uart1 error, warning; usb error, warning", or "none"
+    pub fn describe(&self) -> String {
+        let ports = PORTS
+            .iter()
+            .zip(&self.block[MASKS_OFFSET..])
+            .filter(|(_, mask)| **mask != 0)
+            .map(|(port, mask)| {
+                let severities = SEVERITIES
+                    .iter()
+                    .filter(|(_, _, bit)| mask & bit != 0)
+                    .map(|(name, ..)| *name)
+                    .collect::<Vec<_>>();
+                format!("{} {}", port, severities.join(", "))
+            })
+            .collect::<Vec<_>>();
+        if ports.is_empty() {
+            return "none".to_string();
+        }
+        ports.join("; ")
+    }
+}
+
+/// Outputs the severities of `mask` on the selected ports, and only them, the other ports
+/// being kept, returning the output polled back
+pub fn set(
+    device: &mut Device,
+    mask: u8,
+    ports: &[bool; 5],
+) -> Result<Option<InfOutput>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x02, &[PROTOCOL_UBX], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the INF output (UBX-CFG-INF): {}", e))?;
+    let mut output = InfOutput::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-INF of {} bytes", payload.len()))?;
+    for (port, selected) in output.block[MASKS_OFFSET..].iter_mut().zip(ports) {
+        if *selected {
+            *port = mask;
+        }
+    }
+    info!("Setting the UBX INF output: {} ...", output.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x02, &output.block))
+        .map_err(|e| format!("Failed to write UBX-CFG-INF: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x02, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the INF output (UBX-ACK-NAK of UBX-CFG-INF): {}",
+                output.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-INF not acknowledged: {}", e)),
+    }
+    Ok(device
+        .poll_payload(0x06, 0x02, &[PROTOCOL_UBX], device.ack_timeout())
+        .ok()
+        .and_then(|payload| InfOutput::parse(&payload)))
+}
+
+/// Prints the text of the INF messages, INF-ERROR as an error, INF-WARNING as a warning,
+/// INF-NOTICE and INF-TEST as information and INF-DEBUG at the debug level
+pub struct InfPrinter;
+
+impl Sink for InfPrinter {
+    fn name(&self) -> String {
+        "INF printer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        match packet {
+            PacketRef::InfError(m) => error!("receiver: {}", inf::text(m.as_bytes())),
+            PacketRef::InfWarning(m) => warn!("receiver: {}", inf::text(m.as_bytes())),
+            PacketRef::InfNotice(m) => info!("receiver: {}", inf::text(m.as_bytes())),
+            PacketRef::InfTest(m) => info!("receiver: {}", inf::text(m.as_bytes())),
+            PacketRef::InfDebug(m) => debug!("receiver: {}", inf::text(m.as_bytes())),
+            _ => {},
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Raw configuration messages sent at startup, for the settings without typed support.
+//!
+//! Each line of the file is a hex encoded message, bytes possibly separated by spaces:
+//! either a full frame (sync chars and checksum included), or its class, id and payload,
+//! the tool framing it. A trailing `!ack` waits for the receiver acknowledgment.
+//! Empty lines and comments, starting with `#`, are ignored:
+//!
+//! ```text
+//! # CFG-MSG: NAV-SAT at every solution
+//! 06 01 01 35 01 !ack
+//! B5 62 06 01 03 00 01 35 01 41 AD
+//! ```
+
+use crate::frame;
+
+const SYNC_CHARS: [u8; 2] = [0xb5, 0x62];
+
+const ACK_SUFFIX: &str = "!ack";
+
+pub struct InitPacket {
+    /// Line of the file (starting at 1)
+    pub line: usize,
+    pub frame: Vec<u8>,
+    /// Wait for the acknowledgment of the receiver
+    pub ack: bool,
+}
+
+impl InitPacket {
+    pub fn class(&self) -> u8 {
+        self.frame[2]
+    }
+
+    pub fn id(&self) -> u8 {
+        self.frame[3]
+    }
+}
+
+/// Parses the file content, returning every invalid line with its error
+pub fn parse(text: &str) -> Result<Vec<InitPacket>, Vec<String>> {
+    let mut packets = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Ok((frame, ack)) => packets.push(InitPacket {
+                line: index + 1,
+                frame,
+                ack,
+            }),
+            Err(e) => errors.push(format!("line {}: {}", index + 1, e)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(packets)
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Vec<u8>, bool), String> {
+    let (hex, ack) = match line.strip_suffix(ACK_SUFFIX) {
+        Some(hex) => (hex, true),
+        None => (line, false),
+    };
+    let digits = hex.split_whitespace().collect::<String>();
+    if digits.len() % 2 != 0 {
+        return Err("odd number of hex digits".to_string());
+    }
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex byte \"{}\"", &digits[i..i + 2]))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    if !bytes.starts_with(&SYNC_CHARS) {
+        // class, id and payload
+        if bytes.len() < 2 {
+            return Err("expecting at least a class and an id".to_string());
+        }
+        return Ok((frame::frame(bytes[0], bytes[1], &bytes[2..]), ack));
+    }
+    if bytes.len() < 8 {
+        return Err(format!("frame of {} bytes, too short", bytes.len()));
+    }
+    let len = usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+    if bytes.len() != len + 8 {
+        return Err(format!(
+            "frame of {} bytes, its header announcing a {} bytes payload ({} bytes frame)",
+            bytes.len(),
+            len,
+            len + 8
+        ));
+    }
+    let expected = frame::frame(bytes[2], bytes[3], &bytes[6..len + 6]);
+    if expected != bytes {
+        return Err(format!(
+            "invalid checksum {:02X} {:02X}, expecting {:02X} {:02X}",
+            bytes[len + 6],
+            bytes[len + 7],
+            expected[len + 6],
+            expected[len + 7]
+        ));
+    }
+    Ok((bytes, ack))
+}
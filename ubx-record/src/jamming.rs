@@ -0,0 +1,276 @@
+//! Interference monitoring: the jamming and interference detector (UBX-CFG-ITFM), enabled
+//! with `--monitor-jamming`, and the jamming state of the UBX-MON-RF messages, or of the
+//! UBX-MON-HW ones of the firmware older than MON-RF, reported during the recording
+
+use crate::frame;
+use crate::Device;
+
+/// Length of a CFG-ITFM payload: config and config2
+const ITFM_LEN: usize = 8;
+
+/// CFG-ITFM config fields: thresholds (dB), algorithm bits and enable
+const BB_THRESHOLD_MASK: u32 = 0x0f;
+const CW_THRESHOLD_SHIFT: u32 = 4;
+const CW_THRESHOLD_MASK: u32 = 0x1f << CW_THRESHOLD_SHIFT;
+const ALGORITHM_SHIFT: u32 = 9;
+const ALGORITHM_MASK: u32 = 0x3f_ffff << ALGORITHM_SHIFT;
+const ENABLE: u32 = 0x8000_0000;
+/// CFG-ITFM config2 fields: general bits and antenna setting
+const GENERAL_MASK: u32 = 0x0fff;
+const ANT_SETTING_SHIFT: u32 = 12;
+
+/// Values of the algorithm and general bits the receiver description requires
+const ALGORITHM_BITS: u32 = 0x16b156;
+const GENERAL_BITS: u32 = 0x31e;
+
+/// MON-RF: version, block count, reserved, then blocks of 24 bytes
+const MON_RF_HEADER_LEN: usize = 4;
+const MON_RF_BLOCK_LEN: usize = 24;
+/// Offsets in a MON-RF block: blockId, flags, agcCnt and jamInd
+const RF_BLOCK_ID: usize = 0;
+const RF_FLAGS: usize = 1;
+const RF_AGC_COUNT: usize = 14;
+const RF_JAM_INDICATOR: usize = 16;
+
+/// Offsets in a MON-HW payload: agcCnt, flags and jamInd
+const MON_HW_AGC_COUNT: usize = 18;
+const MON_HW_FLAGS: usize = 22;
+const MON_HW_JAM_INDICATOR: usize = 45;
+/// jammingState bits of the MON-HW flags
+const MON_HW_JAMMING_SHIFT: u8 = 2;
+
+/// Full scale of the AGC count
+const AGC_MAX: u16 = 8191;
+
+/// Interference detector settings, from a UBX-CFG-ITFM payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Itfm {
+    payload: [u8; ITFM_LEN],
+}
+
+impl Itfm {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..ITFM_LEN)?.try_into().ok()?,
+        })
+    }
+
+    fn config(&self) -> u32 {
+        u32::from_le_bytes([self.payload[0], self.payload[1], self.payload[2], self.payload[3]])
+    }
+
+    fn config2(&self) -> u32 {
+        u32::from_le_bytes([self.payload[4], self.payload[5], self.payload[6], self.payload[7]])
+    }
+
+    /// Like "interference detection on, broadband threshold 3 dB, CW threshold 15 dB,
+    /// active antenna"
+    pub fn describe(&self) -> String {
+        let config = self.config();
+        format!(
+            "interference detection {}, broadband threshold {} dB, CW threshold {} dB, {} antenna",
+            if config & ENABLE != 0 { "on" } else { "off" },
+            config & BB_THRESHOLD_MASK,
+            (config & CW_THRESHOLD_MASK) >> CW_THRESHOLD_SHIFT,
+            match (self.config2() >> ANT_SETTING_SHIFT) & 0x03 {
+                1 => "passive",
+                2 => "active",
+                _ => "unknown",
+            }
+        )
+    }
+}
+
+/// Enables the interference detector with the broadband and continuous wave thresholds
+/// given (dB), keeping the antenna setting, returning the settings polled back
+pub fn set(
+    device: &mut Device,
+    bb_threshold: u8,
+    cw_threshold: u8,
+) -> Result<Option<Itfm>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x39, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the interference detector (UBX-CFG-ITFM): {}", e))?;
+    let mut itfm = Itfm::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-ITFM of {} bytes", payload.len()))?;
+    let config = itfm.config() & !(BB_THRESHOLD_MASK | CW_THRESHOLD_MASK | ALGORITHM_MASK)
+        | u32::from(bb_threshold)
+        | u32::from(cw_threshold) << CW_THRESHOLD_SHIFT
+        | ALGORITHM_BITS << ALGORITHM_SHIFT
+        | ENABLE;
+    let config2 = itfm.config2() & !GENERAL_MASK | GENERAL_BITS;
+    itfm.payload[..4].copy_from_slice(&config.to_le_bytes());
+    itfm.payload[4..].copy_from_slice(&config2.to_le_bytes());
+    info!("Setting the interference detector: {} ...", itfm.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x39, &itfm.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-ITFM: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x39, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the interference detector settings (UBX-ACK-NAK of \
+UBX-CFG-ITFM), generation 9 receivers taking the CFG-ITFM keys instead: {}",
+                itfm.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-ITFM not acknowledged: {}", e)),
+    }
+    Ok(device
+        .poll_payload(0x06, 0x39, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Itfm::parse(&payload)))
+}
+
+/// Jamming state of the interference detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Unknown,
+    Ok,
+    Warning,
+    Critical,
+}
+
+impl State {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            1 => State::Ok,
+            2 => State::Warning,
+            3 => State::Critical,
+            _ => State::Unknown,
+        }
+    }
+
+    /// Interference warning or critical
+    pub fn is_jammed(self) -> bool {
+        matches!(self, State::Warning | State::Critical)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Unknown => "unknown",
+            State::Ok => "ok",
+            State::Warning => "warning",
+            State::Critical => "critical",
+        }
+    }
+}
+
+/// Jamming state, CW jamming indicator and AGC count of an RF block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    id: u8,
+    state: State,
+    /// CW jamming indicator, 0 (no CW jamming) to 255 (strong CW jamming)
+    jam_indicator: u8,
+    agc_count: u16,
+}
+
+impl Block {
+    /// Like "CW jamming indicator 120, AGC count 3200 (39%)"
+    fn describe(&self) -> String {
+        format!(
+            "CW jamming indicator {}, AGC count {} ({:.0}%)",
+            self.jam_indicator,
+            self.agc_count,
+            100.0 * f64::from(self.agc_count) / f64::from(AGC_MAX)
+        )
+    }
+}
+
+/// Jamming states of the UBX-MON-RF messages, or of the UBX-MON-HW ones until a MON-RF is
+/// received, reporting their changes
+#[derive(Default)]
+pub struct JammingWatch {
+    /// Last state of each RF block
+    last: Vec<(u8, State)>,
+    /// MON-RF received, MON-HW being ignored since
+    mon_rf: bool,
+    /// iTOW of the last NAV-PVT (ms), dating the changes in the recording
+    itow: Option<u32>,
+}
+
+impl JammingWatch {
+    fn blocks(&mut self, frame: &[u8]) -> Option<Vec<Block>> {
+        let payload = &frame[6..frame.len() - 2];
+        match (frame[2], frame[3]) {
+            (0x01, 0x07) => {
+                self.itow = Some(u32::from_le_bytes(payload.get(..4)?.try_into().ok()?));
+                None
+            },
+            (0x0a, 0x38) => {
+                let count = usize::from(*payload.get(1)?);
+                if payload.len() != MON_RF_HEADER_LEN + count * MON_RF_BLOCK_LEN {
+                    return None;
+                }
+                self.mon_rf = true;
+                let blocks = payload[MON_RF_HEADER_LEN..]
+                    .chunks_exact(MON_RF_BLOCK_LEN)
+                    .map(|block| Block {
+                        id: block[RF_BLOCK_ID],
+                        state: State::from_bits(block[RF_FLAGS]),
+                        jam_indicator: block[RF_JAM_INDICATOR],
+                        agc_count: u16::from_le_bytes([
+                            block[RF_AGC_COUNT],
+                            block[RF_AGC_COUNT + 1],
+                        ]),
+                    });
+                Some(blocks.collect())
+            },
+            (0x0a, 0x09) if !self.mon_rf => Some(vec![Block {
+                id: 0,
+                state: State::from_bits(*payload.get(MON_HW_FLAGS)? >> MON_HW_JAMMING_SHIFT),
+                jam_indicator: *payload.get(MON_HW_JAM_INDICATOR)?,
+                agc_count: u16::from_le_bytes(
+                    payload.get(MON_HW_AGC_COUNT..MON_HW_AGC_COUNT + 2)?.try_into().ok()?,
+                ),
+            }]),
+            _ => None,
+        }
+    }
+
+    /// Reads the jamming states of a MON-RF or MON-HW frame, returning the descriptions of
+    /// their changes, warning when the interference now is a warning or critical
+    pub fn frame(&mut self, frame: &[u8]) -> Vec<String> {
+        let mut changes = Vec::new();
+        for block in self.blocks(frame).unwrap_or_default() {
+            let previous = match self.last.iter_mut().find(|(id, _)| *id == block.id) {
+                Some((_, last)) => Some(std::mem::replace(last, block.state)),
+                None => {
+                    self.last.push((block.id, block.state));
+                    None
+                },
+            };
+            let mut description = match previous {
+                Some(previous) if previous == block.state => continue,
+                Some(previous) => format!(
+                    "jamming state {} -> {}, RF block {}, {}",
+                    previous.name(),
+                    block.state.name(),
+                    block.id,
+                    block.describe()
+                ),
+                None => format!(
+                    "jamming state {}, RF block {}, {}",
+                    block.state.name(),
+                    block.id,
+                    block.describe()
+                ),
+            };
+            if let Some(itow) = self.itow {
+                description.push_str(&format!(", iTOW {} ms", itow));
+            }
+            if !block.state.is_jammed() {
+                info!("{}", description);
+                // the first state is only an event when jammed
+                if previous.is_some() {
+                    changes.push(description);
+                }
+                continue;
+            }
+            warn!("{}: INTERFERENCE, {}", chrono::Utc::now().to_rfc3339(), description);
+            changes.push(description);
+        }
+        changes
+    }
+}
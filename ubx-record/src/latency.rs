@@ -0,0 +1,137 @@
+//! Low latency serial tuning, and measurement of the latency it achieves.
+//!
+//! The latency is measured as the host time a NAV-PVT solution is received at,
+//! minus the time of its epoch: it includes the receiver processing and transmission
+//! delays, and the offset of the host clock, which should be synchronized (NTP, PTP).
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use ublox::{NavPvtRef, PacketRef};
+
+use crate::sink::Sink;
+
+/// NAV-PVT `valid`: validDate, validTime and fullyResolved
+const VALID_TIME: u8 = 0x07;
+
+/// Linux TIOCGSERIAL/TIOCSSERIAL `flags` bit, asking the driver (like ftdi_sio)
+/// to push the received bytes right away
+#[cfg(target_os = "linux")]
+const ASYNC_LOW_LATENCY: libc::c_int = 1 << 13;
+
+/// `struct serial_struct` of linux/serial.h
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct SerialStruct {
+    kind: libc::c_int,
+    line: libc::c_int,
+    port: libc::c_uint,
+    irq: libc::c_int,
+    flags: libc::c_int,
+    xmit_fifo_size: libc::c_int,
+    custom_divisor: libc::c_int,
+    baud_base: libc::c_int,
+    close_delay: libc::c_ushort,
+    io_type: libc::c_char,
+    reserved_char: [libc::c_char; 1],
+    hub6: libc::c_int,
+    closing_wait: libc::c_ushort,
+    closing_wait2: libc::c_ushort,
+    iomem_base: *mut libc::c_uchar,
+    iomem_reg_shift: libc::c_ushort,
+    port_high: libc::c_uint,
+    iomap_base: libc::c_ulong,
+}
+
+/// Sets the low latency flag of the serial driver
+#[cfg(target_os = "linux")]
+pub fn set_low_latency(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    let mut serial = std::mem::MaybeUninit::<SerialStruct>::zeroed();
+    // SAFETY: TIOCGSERIAL fills the serial_struct, TIOCSSERIAL reads it
+    unsafe {
+        if libc::ioctl(fd, libc::TIOCGSERIAL, serial.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut serial = serial.assume_init();
+        serial.flags |= ASYNC_LOW_LATENCY;
+        if libc::ioctl(fd, libc::TIOCSSERIAL, &serial) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Reports the latency of the NAV-PVT solutions every `interval`
+pub struct LatencyMonitor {
+    interval: Duration,
+    last_report: Instant,
+    /// Latencies since the last report (ms)
+    latencies: Vec<f64>,
+}
+
+impl LatencyMonitor {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_report: Instant::now(),
+            latencies: Vec::new(),
+        }
+    }
+
+    fn solution(&mut self, pvt: &NavPvtRef) {
+        if pvt.valid() & VALID_TIME != VALID_TIME {
+            return;
+        }
+        let Ok(epoch) = DateTime::<Utc>::try_from(pvt) else {
+            return;
+        };
+        let latency = Utc::now() - epoch;
+        if let Some(us) = latency.num_microseconds() {
+            self.latencies.push(us as f64 / 1000.0);
+        }
+    }
+
+    fn report(&mut self) {
+        if self.latencies.is_empty() {
+            warn!("latency: no NAV-PVT solution with a valid time received");
+            return;
+        }
+        let n = self.latencies.len() as f64;
+        let mean = self.latencies.iter().sum::<f64>() / n;
+        let min = self.latencies.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self
+            .latencies
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        info!(
+            "latency (receive time - epoch time): mean {:.1} ms, min {:.1} ms, max {:.1} ms, {} epochs",
+            mean,
+            min,
+            max,
+            self.latencies.len()
+        );
+        self.latencies.clear();
+    }
+}
+
+impl Sink for LatencyMonitor {
+    fn name(&self) -> String {
+        "latency monitor".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        if let PacketRef::NavPvt(pvt) = packet {
+            self.solution(pvt);
+        }
+        if self.last_report.elapsed() >= self.interval {
+            self.report();
+            self.last_report = Instant::now();
+        }
+        Ok(())
+    }
+}
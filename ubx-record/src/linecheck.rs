@@ -0,0 +1,232 @@
+//! Startup check of the serial line, detecting a wrong baud rate.
+//!
+//! At a wrong baud rate, bytes keep arriving but almost none of them belongs
+//! to a valid UBX, NMEA or RTCM3 frame. Framing errors also show up as many
+//! 0x00 and 0xFF bytes (breaks and idle line sampled as data), and the bytes
+//! look random: their entropy is high, unlike the ASCII of NMEA sentences.
+
+use crate::protocol::ProtocolScanner;
+
+/// Bytes needed before judging the line
+const MIN_BYTES: usize = 64;
+
+/// Valid frames ending the check early
+const HEALTHY_FRAMES: usize = 3;
+
+/// Largest share of the bytes in valid frames of a garbled line
+const GARBAGE_MAX_FRAMED: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// No byte received
+    Silent,
+    /// Too few bytes to judge
+    Undecided,
+    /// Valid frames received
+    Healthy,
+    /// Bytes received, almost none parsing
+    Garbage,
+}
+
+pub struct LineCheck {
+    scanner: ProtocolScanner,
+    histogram: [usize; 256],
+    bytes: usize,
+    frames: usize,
+    framed_bytes: usize,
+}
+
+impl Default for LineCheck {
+    fn default() -> Self {
+        Self {
+            scanner: ProtocolScanner::default(),
+            histogram: [0; 256],
+            bytes: 0,
+            frames: 0,
+            framed_bytes: 0,
+        }
+    }
+}
+
+impl LineCheck {
+    pub fn push(&mut self, data: &[u8]) {
+        self.bytes += data.len();
+        for byte in data {
+            self.histogram[usize::from(*byte)] += 1;
+        }
+        let (mut frames, mut framed_bytes) = (0, 0);
+        self.scanner.scan(data, |protocol, frame| {
+            if protocol.is_some() {
+                frames += 1;
+                framed_bytes += frame.len();
+            }
+        });
+        self.frames += frames;
+        self.framed_bytes += framed_bytes;
+    }
+
+    /// True once enough valid frames were received to end the check
+    pub fn healthy(&self) -> bool {
+        self.frames >= HEALTHY_FRAMES
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        if self.bytes == 0 {
+            Verdict::Silent
+        } else if self.frames > 0 && self.framed_share() > GARBAGE_MAX_FRAMED {
+            Verdict::Healthy
+        } else if self.bytes < MIN_BYTES {
+            Verdict::Undecided
+        } else {
+            Verdict::Garbage
+        }
+    }
+
+    fn framed_share(&self) -> f64 {
+        self.framed_bytes as f64 / self.bytes.max(1) as f64
+    }
+
+    /// Shannon entropy of the byte values (bits per byte, 8 for random data)
+    fn entropy(&self) -> f64 {
+        let total = self.bytes as f64;
+        self.histogram
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// Evidence of the verdict, one item per line
+    pub fn evidence(&self) -> Vec<String> {
+        let share = |count: usize| 100.0 * count as f64 / self.bytes.max(1) as f64;
+        let ascii = self.histogram[0x20..0x7f].iter().sum::<usize>()
+            + self.histogram[usize::from(b'\r')]
+            + self.histogram[usize::from(b'\n')];
+        vec![
+            format!(
+                "{} bytes received, {} valid UBX/NMEA/RTCM3 frames ({:.1}% of the bytes)",
+                self.bytes,
+                self.frames,
+                share(self.framed_bytes)
+            ),
+            format!(
+                "byte entropy {:.2} bits/byte (8 for random data, about 4.5 for NMEA sentences)",
+                self.entropy()
+            ),
+            format!(
+                "0x00 and 0xFF bytes, typical of framing errors: {:.1}%",
+                share(self.histogram[0x00] + self.histogram[0xff])
+            ),
+            format!("printable ASCII bytes: {:.1}%", share(ascii)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame;
+
+    const GGA: &[u8] =
+        b"$GNGGA,092725.00,4717.11399,N,00833.91590,E,1,08,1.01,499.6,M,48.0,M,,*45\r\n";
+
+    /// Bytes received at `rx_baud` from `data` sent at `tx_baud`, 8N1: the line is sampled
+    /// by a receiver waiting for the start bit, then reading the middle of each data bit
+    fn received(data: &[u8], tx_baud: f64, rx_baud: f64) -> Vec<u8> {
+        let bits = data
+            .iter()
+            .flat_map(|byte| {
+                std::iter::once(false)
+                    .chain((0..8).map(move |bit| byte >> bit & 1 == 1))
+                    .chain(std::iter::once(true))
+            })
+            .collect::<Vec<_>>();
+        let line = |time: f64| bits.get((time * tx_baud) as usize).copied().unwrap_or(true);
+        let end = bits.len() as f64 / tx_baud;
+        let step = 1.0 / (16.0 * rx_baud);
+        let mut received = vec![];
+        let mut time = 0.0;
+        while time < end {
+            if line(time) {
+                time += step;
+                continue;
+            }
+            let byte = (0..8).fold(0_u8, |byte, bit| {
+                let sample = time + (1.5 + f64::from(bit)) / rx_baud;
+                byte | u8::from(line(sample)) << bit
+            });
+            received.push(byte);
+            // middle of the stop bit
+            time += 9.5 / rx_baud;
+        }
+        received
+    }
+
+    fn check(data: &[u8]) -> LineCheck {
+        let mut check = LineCheck::default();
+        for chunk in data.chunks(100) {
+            check.push(chunk);
+        }
+        check
+    }
+
+    fn stream() -> Vec<u8> {
+        let pvt = frame(0x01, 0x07, &[0x55; 92]);
+        (0..10).flat_map(|_| [GGA, &pvt].concat()).collect()
+    }
+
+    #[test]
+    fn right_baud_rate() {
+        let data = received(&stream(), 115200.0, 115200.0);
+        assert_eq!(data, stream());
+        let check = check(&data);
+        assert!(check.healthy());
+        assert_eq!(check.verdict(), Verdict::Healthy);
+    }
+
+    #[test]
+    fn receiver_faster_than_the_line() {
+        for rx_baud in [38400.0, 115200.0, 460800.0] {
+            let data = received(&stream(), 9600.0, rx_baud);
+            assert!(data.len() >= MIN_BYTES);
+            let check = check(&data);
+            assert!(!check.healthy());
+            assert_eq!(check.verdict(), Verdict::Garbage, "received at {}", rx_baud);
+        }
+    }
+
+    #[test]
+    fn receiver_slower_than_the_line() {
+        for rx_baud in [4800.0, 9600.0, 38400.0] {
+            let data = received(&stream(), 115200.0, rx_baud);
+            assert!(data.len() >= MIN_BYTES);
+            assert_eq!(check(&data).verdict(), Verdict::Garbage, "received at {}", rx_baud);
+        }
+    }
+
+    #[test]
+    fn silent_and_undecided() {
+        assert_eq!(LineCheck::default().verdict(), Verdict::Silent);
+        assert_eq!(check(&[0xff; MIN_BYTES - 1]).verdict(), Verdict::Undecided);
+        assert_eq!(check(&[0xff; MIN_BYTES]).verdict(), Verdict::Garbage);
+        // a single frame among little data is enough
+        assert_eq!(check(GGA).verdict(), Verdict::Healthy);
+    }
+
+    #[test]
+    fn evidence() {
+        let check = check(&[[0x00; 50], [0xff; 50]].concat());
+        assert_eq!(
+            check.evidence(),
+            [
+                "100 bytes received, 0 valid UBX/NMEA/RTCM3 frames (0.0% of the bytes)",
+                "byte entropy 1.00 bits/byte (8 for random data, about 4.5 for NMEA sentences)",
+                "0x00 and 0xFF bytes, typical of framing errors: 100.0%",
+                "printable ASCII bytes: 0.0%",
+            ]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Live printing of the navigation solutions being recorded
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use ublox::{GpsFix, NavHpPosLlhRef, NavPvtFlags, NavPvtRef, PacketRef};
+
+use crate::sink::Sink;
+
+/// Vehicle attitude of an epoch, in degrees
+#[derive(Debug, Clone, Copy)]
+struct Attitude {
+    itow: u32,
+    roll: f64,
+    pitch: f64,
+    heading: f64,
+    acc_roll: f64,
+    acc_pitch: f64,
+    acc_heading: f64,
+}
+
+/// Payload offset of NAV-HPPOSLLH `flags`, reserved before protocol 27.11, and its invalidLlh bit
+const HP_FLAGS_OFFSET: usize = 3;
+const HP_INVALID_LLH: u8 = 0x01;
+
+/// High-precision position of an epoch, from NAV-HPPOSLLH
+#[derive(Debug, Clone, Copy)]
+struct HpPosition {
+    itow: u32,
+    lat: f64,
+    lon: f64,
+    height_msl: f64,
+    h_acc: f64,
+    v_acc: f64,
+}
+
+impl HpPosition {
+    /// The position with its high-precision components added, none when flagged invalid
+    fn from_packet(hp: &NavHpPosLlhRef) -> Option<Self> {
+        (hp.as_bytes()[HP_FLAGS_OFFSET] & HP_INVALID_LLH == 0).then(|| Self {
+            itow: hp.itow(),
+            lat: hp.lat_degrees() + hp.lat_hp_degrees(),
+            lon: hp.lon_degrees() + hp.lon_hp_degrees(),
+            // heightHp and the accuracies being scaled to millimeters by the ublox crate
+            height_msl: hp.height_msl() + hp.height_hp_msl() * 1e-3,
+            h_acc: hp.horizontal_accuracy() * 1e-3,
+            v_acc: hp.vertical_accuracy() * 1e-3,
+        })
+    }
+}
+
+/// NAV-PVT solution of an epoch, as output
+#[derive(Debug, Clone)]
+struct Fix {
+    itow: u32,
+    utc: String,
+    fix_type: GpsFix,
+    gnss_fix_ok: bool,
+    num_sv: u8,
+    lat: f64,
+    lon: f64,
+    height_msl: f64,
+    ground_speed: f64,
+    heading_motion: f64,
+    h_acc: f64,
+    v_acc: f64,
+    attitude: Option<Attitude>,
+}
+
+impl Fix {
+    fn new(pvt: &NavPvtRef, attitude: Option<Attitude>) -> Self {
+        Self {
+            itow: pvt.itow(),
+            utc: format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                pvt.year(),
+                pvt.month(),
+                pvt.day(),
+                pvt.hour(),
+                pvt.min(),
+                pvt.sec()
+            ),
+            fix_type: pvt.fix_type(),
+            gnss_fix_ok: pvt.flags().contains(NavPvtFlags::GPS_FIX_OK),
+            num_sv: pvt.num_satellites(),
+            lat: pvt.lat_degrees(),
+            lon: pvt.lon_degrees(),
+            height_msl: pvt.height_msl(),
+            ground_speed: pvt.ground_speed(),
+            heading_motion: pvt.heading_degrees(),
+            h_acc: f64::from(pvt.horiz_accuracy()) * 1e-3,
+            v_acc: f64::from(pvt.vert_accuracy()) * 1e-3,
+            attitude,
+        }
+    }
+
+    fn fold(&mut self, hp: HpPosition) {
+        self.lat = hp.lat;
+        self.lon = hp.lon;
+        self.height_msl = hp.height_msl;
+        self.h_acc = hp.h_acc;
+        self.v_acc = hp.v_acc;
+    }
+}
+
+/// Solutions in output order, with `--high-precision` the NAV-HPPOSLLH position of their
+/// epoch folded in, the solution being held until it follows
+struct Epochs {
+    high_precision: bool,
+    held: Option<Fix>,
+    /// High-precision position received ahead of the solution of its epoch
+    hp: Option<HpPosition>,
+}
+
+impl Epochs {
+    fn new(high_precision: bool) -> Self {
+        Self {
+            high_precision,
+            held: None,
+            hp: None,
+        }
+    }
+
+    /// Solutions ready for output, in order, once `packet` is received
+    fn packet(&mut self, packet: &PacketRef, attitude: Option<Attitude>) -> Vec<Fix> {
+        match packet {
+            PacketRef::NavPvt(pvt) => {
+                let mut fix = Fix::new(pvt, attitude);
+                let mut ready = self.held.take().into_iter().collect::<Vec<_>>();
+                match self.hp.take().filter(|hp| hp.itow == fix.itow) {
+                    Some(hp) => {
+                        fix.fold(hp);
+                        ready.push(fix);
+                    },
+                    None if self.high_precision => self.held = Some(fix),
+                    None => ready.push(fix),
+                }
+                ready
+            },
+            PacketRef::NavHpPosLlh(hp) => {
+                let Some(hp) = HpPosition::from_packet(hp) else {
+                    return Vec::new();
+                };
+                match self.held.take() {
+                    Some(mut fix) if fix.itow == hp.itow => {
+                        fix.fold(hp);
+                        vec![fix]
+                    },
+                    held => {
+                        self.held = held;
+                        self.hp = Some(hp);
+                        Vec::new()
+                    },
+                }
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Prints one JSON object per NAV-PVT solution on stdout, with the NAV-ATT attitude of the
+/// same epoch when present, and the NAV-HPPOSLLH position with `--high-precision`
+pub struct PvtPrinter {
+    /// Last NAV-ATT solution, output ahead of the NAV-PVT solution of its epoch
+    attitude: Option<Attitude>,
+    epochs: Epochs,
+}
+
+impl PvtPrinter {
+    pub fn new(high_precision: bool) -> Self {
+        Self {
+            attitude: None,
+            epochs: Epochs::new(high_precision),
+        }
+    }
+}
+
+impl Sink for PvtPrinter {
+    fn name(&self) -> String {
+        "NAV-PVT printer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(fix) = self.epochs.held.take() {
+            println!("{}", to_json(&fix));
+        }
+        Ok(())
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        let mut attitude = None;
+        match packet {
+            PacketRef::NavAtt(att) => {
+                self.attitude = Some(Attitude {
+                    itow: att.itow(),
+                    roll: att.vehicle_roll(),
+                    pitch: att.vehicle_pitch(),
+                    heading: att.vehicle_heading(),
+                    acc_roll: att.vehicle_roll_accuracy(),
+                    acc_pitch: att.vehicle_pitch_accuracy(),
+                    acc_heading: att.vehicle_heading_accuracy(),
+                })
+            },
+            PacketRef::NavPvt(pvt) => {
+                attitude = self.attitude.take().filter(|att| att.itow == pvt.itow());
+            },
+            _ => {},
+        }
+        for fix in self.epochs.packet(packet, attitude) {
+            println!("{}", to_json(&fix));
+        }
+        Ok(())
+    }
+}
+
+/// Writes one CSV row per NAV-PVT solution, flushed as they come for live use, with the
+/// NAV-HPPOSLLH position with `--high-precision`
+pub struct PvtCsv {
+    path: String,
+    writer: BufWriter<File>,
+    epochs: Epochs,
+}
+
+impl PvtCsv {
+    pub fn create(path: &str, high_precision: bool) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "itow,utc,fix_type,gnss_fix_ok,num_sv,lat,lon,height_msl,ground_speed,heading_motion,h_acc,v_acc"
+        )?;
+        Ok(Self {
+            path: path.to_string(),
+            writer,
+            epochs: Epochs::new(high_precision),
+        })
+    }
+
+    fn write(&mut self, fix: &Fix) -> std::io::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{},{:?},{},{},{:.9},{:.9},{:.3},{:.3},{:.5},{:.3},{:.3}",
+            fix.itow,
+            fix.utc,
+            fix.fix_type,
+            fix.gnss_fix_ok,
+            fix.num_sv,
+            fix.lat,
+            fix.lon,
+            fix.height_msl,
+            fix.ground_speed,
+            fix.heading_motion,
+            fix.h_acc,
+            fix.v_acc,
+        )
+    }
+}
+
+impl Sink for PvtCsv {
+    fn name(&self) -> String {
+        format!("CSV output \"{}\"", self.path)
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(fix) = self.epochs.held.take() {
+            self.write(&fix)?;
+        }
+        self.writer.flush()
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        let ready = self.epochs.packet(packet, None);
+        if ready.is_empty() {
+            return Ok(());
+        }
+        for fix in &ready {
+            self.write(fix)?;
+        }
+        self.writer.flush()
+    }
+}
+
+fn to_json(fix: &Fix) -> String {
+    let mut object = format!(
+        "{{\"itow\":{},\"utc\":\"{}\",\"fix_type\":\"{:?}\",\
+\"gnss_fix_ok\":{},\"num_sv\":{},\"lat\":{:.9},\"lon\":{:.9},\"height_msl\":{:.3},\
+\"ground_speed\":{:.3},\"heading_motion\":{:.5},\"h_acc\":{:.3},\"v_acc\":{:.3}",
+        fix.itow,
+        fix.utc,
+        fix.fix_type,
+        fix.gnss_fix_ok,
+        fix.num_sv,
+        fix.lat,
+        fix.lon,
+        fix.height_msl,
+        fix.ground_speed,
+        fix.heading_motion,
+        fix.h_acc,
+        fix.v_acc,
+    );
+    if let Some(att) = &fix.attitude {
+        object.push_str(&format!(
+            ",\"attitude\":{{\"roll\":{:.5},\"pitch\":{:.5},\"heading\":{:.5},\
+\"acc_roll\":{:.5},\"acc_pitch\":{:.5},\"acc_heading\":{:.5}}}",
+            att.roll, att.pitch, att.heading, att.acc_roll, att.acc_pitch, att.acc_heading
+        ));
+    }
+    object.push('}');
+    object
+}
@@ -1,4 +1,4 @@
-use clap::{value_parser, Arg, Command};
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
 use serialport::{
     DataBits as SerialDataBits, FlowControl as SerialFlowControl, Parity as SerialParity,
     StopBits as SerialStopBits,
@@ -7,22 +7,307 @@ use std::time::Duration;
 use ublox::*;
 
 use std::fs::File;
-use std::io::{Write, BufWriter};
+use std::io::{Read, Write, BufWriter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use flate2::{write::GzEncoder, Compression};
+use ubx_common::logging;
+
+// the error!, warn!, info!, debug! and trace! macros, for every module
+#[macro_use]
+extern crate log;
+
+mod antenna;
+mod assistnow;
+mod base;
+mod condition;
+mod container;
+mod discovery;
+mod esf;
+#[cfg(unix)]
+mod fifo;
+mod fixstop;
+mod geofence;
+mod frame;
+mod gzsync;
+mod inf;
+mod init;
+mod jamming;
+mod latency;
+mod linecheck;
+mod live;
+mod messages;
+mod metadata;
+#[cfg(all(feature = "tokio", unix))]
+mod metrics;
+mod naming;
+mod navcfg;
+mod odometer;
+mod net;
+#[cfg(all(feature = "tokio", unix))]
+mod ntrip;
+mod persist;
+mod portcfg;
+mod powercfg;
+mod profile;
+mod protocol;
+mod reconnect;
+mod reset;
+mod resume;
+mod ring;
+mod rotation;
+#[cfg(all(feature = "tokio", unix))]
+mod runtime;
+mod sats;
+mod session;
+mod settime;
+mod sidecar;
+mod sink;
+mod snapshot;
+mod sos;
+mod source;
+mod split;
+mod timecfg;
+mod timepulse;
+mod timing;
+mod ttff;
+mod valcfg;
+mod verify;
+
+use condition::{Geofence, StartCondition};
+use container::Timestamped;
+use discovery::Filter;
+use frame::FrameScanner;
+use gzsync::SyncedGzip;
+use latency::LatencyMonitor;
+use linecheck::{LineCheck, Verdict};
+use live::{PvtCsv, PvtPrinter};
+use messages::Message;
+use metadata::{Metadata, Receiver};
+use net::{TcpServer, UdpMode, UdpSender};
+use protocol::{Protocol, ProtocolFilter};
+use reconnect::Reconnection;
+use ring::{FrameRing, RingRecorder};
+use rotation::{Align, Rotation};
+use session::{Limits, Session};
+use sidecar::Sidecar;
+use sink::{Sink, Sinks};
+use source::{Endpoint, Port};
+use split::ClassSplit;
+use ttff::TtffMonitor;
+use verify::Verification;
+
+/// ESF-RAW throughput estimate: 100 Hz samples of 3 gyroscope axes,
+/// 3 accelerometer axes and the temperature, 8 bytes each, plus framing
+const ESF_RAW_BYTES_PER_SECOND: u32 = 100 * 7 * 8 + 10 * 12;
+
+/// Output file buffer of the low latency mode
+const LOW_LATENCY_BUFFER: usize = 256;
+
+/// Period of the latency reports
+const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest wait for data of the recording loops, data waking them up right away
+const IDLE_WAIT: Duration = Duration::from_secs(1);
+
+/// Longest wait for data of the pre-trigger loop, which completes captures on time
+const RING_IDLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Duration of the startup check of the line
+const LINE_CHECK_DURATION: Duration = Duration::from_secs(3);
+
+/// Baud rates suggested when the startup check fails, and tried by `--baud auto`
+const COMMON_BAUD_RATES: [u32; 8] = [9600, 38400, 115200, 19200, 57600, 230400, 460800, 921600];
+
+/// Wait for the UBX-MON-VER answer of `list-ports --probe`
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Wait for the acknowledgments and poll responses, unless set by `--ack-timeout`
+const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Wait for a valid frame at each rate tried by `--baud auto`
+const BAUD_DETECTION_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Compression levels of the outputs, unless `--compress-level` is given
+const GZIP_DEFAULT_LEVEL: u32 = 6;
+const ZSTD_DEFAULT_LEVEL: u32 = 3;
+
+/// Highest gzip compression level, zstd going up to 22
+const GZIP_MAX_LEVEL: u32 = 9;
+
+/// Codec of the output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Compression of the output files: codec, from `--compress` or else from the file name, and level
+#[derive(Debug, Clone, Copy)]
+struct OutputCompression {
+    forced: Option<Codec>,
+    level: u32,
+}
+
+impl OutputCompression {
+    /// Compression of the outputs named like `path`, at `level` or else the default level
+    /// of the codec
+    fn new(forced: Option<Codec>, path: &str, level: Option<u32>) -> Result<Self, String> {
+        let mut compression = Self {
+            forced,
+            level: GZIP_DEFAULT_LEVEL,
+        };
+        compression.level = match (compression.codec(path), level) {
+            (Codec::Zstd, None) => ZSTD_DEFAULT_LEVEL,
+            (Codec::Zstd, Some(level)) => level,
+            (_, Some(level)) if level > GZIP_MAX_LEVEL => {
+                return Err(format!(
+                    "gzip compression levels go from 0 to {}, not {}",
+                    GZIP_MAX_LEVEL, level
+                ))
+            },
+            (_, level) => level.unwrap_or(GZIP_DEFAULT_LEVEL),
+        };
+        Ok(compression)
+    }
+
+    /// Codec of the file: gzip when named *.gz, zstd when named *.zst, unless forced
+    fn codec(&self, path: &str) -> Codec {
+        self.forced.unwrap_or(if path.ends_with(".gz") {
+            Codec::Gzip
+        } else if path.ends_with(".zst") {
+            Codec::Zstd
+        } else {
+            Codec::None
+        })
+    }
+}
 
 enum BufferedWriter {
     Plain(BufWriter<File>),
     Gzip(BufWriter<GzEncoder<File>>),
+    Zstd(BufWriter<zstd::Encoder<'static, File>>),
+    SyncedGzip(SyncedGzip),
+    #[cfg(unix)]
+    Fifo(fifo::FifoWriter),
+    Stdout(BufWriter<std::io::StdoutLock<'static>>),
+    GzipStdout(BufWriter<GzEncoder<std::io::StdoutLock<'static>>>),
+    ZstdStdout(BufWriter<zstd::Encoder<'static, std::io::StdoutLock<'static>>>),
 }
 
 impl BufferedWriter {
-    fn new(path: &str) -> Self {
-        let fd = File::create(path)
-            .expect(&format!("failed to create file \"{}\"", path));
-        if path.ends_with(".gz") {
-            Self::Gzip(BufWriter::new(GzEncoder::new(fd, Compression::new(6))))
-        } else {
-            Self::Plain(BufWriter::new(fd))
+    /// Creates the file, or appends to it when resuming a recording
+    fn new(path: &str, compression: OutputCompression, append: bool) -> Self {
+        let codec = compression.codec(path);
+        let fd = create(path, append, (codec == Codec::Gzip).then_some(compression.level));
+        match codec {
+            Codec::Gzip => Self::Gzip(BufWriter::new(GzEncoder::new(
+                fd,
+                Compression::new(compression.level),
+            ))),
+            Codec::Zstd => Self::Zstd(BufWriter::new(
+                zstd::Encoder::new(fd, compression.level as i32)
+                    .unwrap_or_else(|e| exit_on(&format!("failed to compress \"{}\"", path), e)),
+            )),
+            Codec::None => Self::Plain(BufWriter::new(fd)),
+        }
+    }
+
+    /// Standard output, for pipelines, compressed with the codec at `level`
+    fn stdout(codec: Codec, level: u32) -> Self {
+        let stdout = std::io::stdout().lock();
+        match codec {
+            Codec::Gzip => {
+                Self::GzipStdout(BufWriter::new(GzEncoder::new(stdout, Compression::new(level))))
+            },
+            Codec::Zstd => Self::ZstdStdout(BufWriter::new(
+                zstd::Encoder::new(stdout, level as i32)
+                    .unwrap_or_else(|e| exit_on("failed to compress the standard output", e)),
+            )),
+            Codec::None => Self::Stdout(BufWriter::new(stdout)),
+        }
+    }
+
+    /// Uncompressed file of given buffer capacity
+    fn with_capacity(path: &str, capacity: usize, append: bool) -> Self {
+        Self::Plain(BufWriter::with_capacity(capacity, create(path, append, None)))
+    }
+}
+
+/// Creates the output file, or opens it for appending, gzip outputs being compressed at
+/// the given level
+fn create(path: &str, append: bool, gzip: Option<u32>) -> File {
+    let fd = if append {
+        resume::open(path, gzip)
+    } else {
+        File::create(path)
+    };
+    fd.unwrap_or_else(|e| exit_on(&format!("failed to create file \"{}\"", path), e))
+}
+
+impl BufferedWriter {
+    /// Opens the output path, named pipes buffering up to `fifo_buffer` bytes
+    /// while no reader is attached, gzip files getting a sync point every `sync_interval`,
+    /// uncompressed files buffering `buffer` bytes when given, files being appended to
+    /// when `append` is set
+    fn open(
+        path: &str,
+        fifo_buffer: usize,
+        sync_interval: Option<Duration>,
+        buffer: Option<usize>,
+        compression: OutputCompression,
+        append: bool,
+    ) -> Self {
+        #[cfg(unix)]
+        if fifo::is_fifo(path) {
+            return Self::Fifo(fifo::FifoWriter::new(path, fifo_buffer));
+        }
+        let _ = fifo_buffer;
+        let codec = compression.codec(path);
+        match sync_interval {
+            Some(interval) if codec == Codec::Gzip => Self::SyncedGzip(
+                SyncedGzip::create(path, interval, compression.level)
+                    .unwrap_or_else(|e| exit_on(&format!("failed to create file \"{}\"", path), e)),
+            ),
+            _ => match buffer {
+                Some(capacity) if codec == Codec::None => {
+                    Self::with_capacity(path, capacity, append)
+                },
+                _ => Self::new(path, compression, append),
+            },
+        }
+    }
+}
+
+impl BufferedWriter {
+    /// Flushes the output and completes gzip and zstd streams, which the `Write` trait alone
+    /// cannot do. Unlike dropping the writer, failures are reported
+    fn finalize(&mut self) -> std::io::Result<()> {
+        match self {
+            BufferedWriter::Gzip(ref mut writer) => {
+                writer.flush()?;
+                writer.get_mut().try_finish()
+            },
+            BufferedWriter::Zstd(ref mut writer) => {
+                writer.flush()?;
+                writer.get_mut().do_finish()
+            },
+            BufferedWriter::GzipStdout(ref mut writer) => {
+                writer.flush()?;
+                writer.get_mut().try_finish()?;
+                writer.get_mut().get_mut().flush()
+            },
+            BufferedWriter::ZstdStdout(ref mut writer) => {
+                writer.flush()?;
+                writer.get_mut().do_finish()?;
+                writer.get_mut().get_mut().flush()
+            },
+            BufferedWriter::Stdout(ref mut writer) => writer.flush(),
+            BufferedWriter::SyncedGzip(ref mut writer) => writer.finish(),
+            BufferedWriter::Plain(ref mut writer) => writer.flush(),
+            #[cfg(unix)]
+            BufferedWriter::Fifo(ref mut writer) => writer.flush(),
         }
     }
 }
@@ -31,30 +316,77 @@ impl std::io::Write for BufferedWriter {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
         match self {
             BufferedWriter::Gzip(ref mut writer) => writer.write(buf),
+            BufferedWriter::Zstd(ref mut writer) => writer.write(buf),
+            BufferedWriter::SyncedGzip(ref mut writer) => writer.write(buf),
             BufferedWriter::Plain(ref mut writer) => writer.write(buf),
+            BufferedWriter::Stdout(ref mut writer) => writer.write(buf),
+            BufferedWriter::GzipStdout(ref mut writer) => writer.write(buf),
+            BufferedWriter::ZstdStdout(ref mut writer) => writer.write(buf),
+            #[cfg(unix)]
+            BufferedWriter::Fifo(ref mut writer) => writer.write(buf),
         }
     }
     fn flush(&mut self) -> Result<(), std::io::Error> {
         match self {
             BufferedWriter::Gzip(ref mut writer) => writer.flush(),
+            BufferedWriter::Zstd(ref mut writer) => writer.flush(),
+            BufferedWriter::SyncedGzip(ref mut writer) => writer.flush(),
             BufferedWriter::Plain(ref mut writer) => writer.flush(),
+            #[cfg(unix)]
+            BufferedWriter::Fifo(ref mut writer) => writer.flush(),
+            BufferedWriter::Stdout(ref mut writer) => writer.flush(),
+            BufferedWriter::GzipStdout(ref mut writer) => writer.flush(),
+            BufferedWriter::ZstdStdout(ref mut writer) => writer.flush(),
         }
     }
 }
 
 fn main() {
-    let matches = Command::new("ubx-record")
+    let command = Command::new("ubx-record")
         .author(clap::crate_authors!())
         .about("Record UBX files from your U-Blox receiver")
         .arg_required_else_help(true)
+        // list-ports opens no port, the other subcommands check for one
+        .subcommand_negates_reqs(true)
         .next_help_heading("Serial configuration")
         .arg(
             Arg::new("port")
                 .value_name("PORT")
                 .short('p')
                 .long("port")
-                .required(true)
-                .help("Serial port to open"),
+                .required_unless_present_any(["auto-port", "match-serial", "match-product", "list-ports"])
+                .help("Serial port to open, tcp://HOST:PORT to read from a TCP server, \
+like a ser2net or serial-to-WiFi bridge, or udp://ADDRESS:PORT to receive UDP datagrams"),
+        )
+        .arg(
+            Arg::new("auto-port")
+                .long("auto-port")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("port")
+                .help("Open the single u-blox USB receiver (vendor id 0x1546) connected, \
+or the single USB device matching --match-serial and --match-product, which imply --auto-port")
+        )
+        .arg(
+            Arg::new("match-serial")
+                .long("match-serial")
+                .value_name("SERIAL")
+                .conflicts_with("port")
+                .help("Select the USB device whose serial number contains SERIAL")
+        )
+        .arg(
+            Arg::new("match-product")
+                .long("match-product")
+                .value_name("PRODUCT")
+                .conflicts_with("port")
+                .help("Select the USB device whose product string contains PRODUCT (case insensitive)")
+        )
+        .arg(
+            Arg::new("list-ports")
+                .long("list-ports")
+                .action(ArgAction::SetTrue)
+                .exclusive(true)
+                .help("List the serial ports, u-blox receivers first, and exit. \
+Same as the list-ports subcommand")
         )
         .arg(
             Arg::new("baud")
@@ -63,8 +395,9 @@ fn main() {
                 .long("baud")
                 .required(false)
                 .default_value("9600")
-                .value_parser(value_parser!(u32))
-                .help("Baud rate of the port to open"),
+                .value_parser(parse_baud)
+                .help("Baud rate of the port to open, or \"auto\" to find the rate the receiver \
+answers UBX-MON-VER at among the common rates"),
         )
         .arg(
             Arg::new("stop-bits")
@@ -89,6 +422,51 @@ fn main() {
                 .required(false)
                 .value_parser(["even", "odd"]),
         )
+        .arg(
+            Arg::new("flow-control")
+                .long("flow-control")
+                .value_parser(["none", "hardware", "software"])
+                .default_value("none")
+                .help("Flow control of the host port: hardware (RTS/CTS) for adapters wiring it, \
+software (XON/XOFF) drops the 0x11 and 0x13 bytes of the received data. \
+u-blox UARTs do neither")
+        )
+        .arg(
+            Arg::new("low-latency")
+                .long("low-latency")
+                .action(ArgAction::SetTrue)
+                .help("Reduce the latency of the received data: 1 ms read timeout, low latency flag \
+of the serial driver (Linux, like FTDI adapters), small output file buffer. Implies --report-latency")
+        )
+        .arg(
+            Arg::new("report-latency")
+                .long("report-latency")
+                .action(ArgAction::SetTrue)
+                .help("Print the latency of the NAV-PVT solutions every 10 s: host receive time \
+minus epoch time, the host clock being synchronized")
+        )
+        .arg(
+            Arg::new("embed-metadata")
+                .long("embed-metadata")
+                .action(ArgAction::SetTrue)
+                .help("Start the output files with a metadata block: a UBX frame (class 0x7f, \
+id 0x01) holding JSON with the tool version, command line, host name, receiver version \
+(MON-VER) and unique id (SEC-UNIQID), and start time. Other parsers skip it as an unknown message")
+        )
+        .arg(
+            Arg::new("strict-startup")
+                .long("strict-startup")
+                .action(ArgAction::SetTrue)
+                .help("Exit when the startup check of the line finds bytes that do not parse \
+as UBX, NMEA or RTCM3, typical of a wrong baud rate")
+        )
+        .arg(
+            Arg::new("require-firmware")
+                .long("require-firmware")
+                .value_name("SUBSTRING")
+                .help("Poll the receiver version (UBX-MON-VER) first, and exit unless its \
+firmware or software version contains SUBSTRING, like \"HPG 1.32\"")
+        )
         .next_help_heading("Output file")
         .arg(
             Arg::new("output")
@@ -96,20 +474,612 @@ fn main() {
                 .long("output")
                 .required(false)
                 .value_name("FILE")
-                .help("Output file name")
+                .help("Output file name, \"-\" for the standard output, with strftime placeholders (like %Y%m%d-%H%M%S, UTC) \
+and {port}, {serial} (SEC-UNIQID) or {firmware} (MON-VER) tokens. Defaults to \
+output-<start time>.ubx.gz, or output.ubx.gz with --ring-buffer and --rotate-interval, \
+which timestamp their files")
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Overwrite the output file when it exists, like the files of \
+--rotate-interval and --ring-buffer, which are checked as they are named")
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["force", "ring-buffer", "sync-interval"])
+                .help("Append to the output file when it exists, to resume an interrupted \
+recording. Gzip outputs get a new gzip member, an incomplete last member being completed first")
+        )
+        .arg(
+            Arg::new("gzip")
+                .long("gzip")
+                .action(ArgAction::SetTrue)
+                .help("Gzip compress the standard output (-o -). Files are compressed when named *.gz")
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .value_parser(["none", "gzip", "zstd"])
+                .help("Compression of the output, whatever its name. By default, outputs named *.gz \
+are gzip compressed and *.zst zstd compressed")
+        )
+        .arg(
+            Arg::new("compress-level")
+                .long("compress-level")
+                .value_name("LEVEL")
+                .value_parser(value_parser!(u32).range(0..=22))
+                .help("Compression level: gzip from 0 (none) to 9 (smallest), 6 by default, \
+zstd from 1 (fastest) to 22 (smallest), 3 by default. Low levels spare the CPU of small computers")
+        )
+        .arg(
+            Arg::new("fifo")
+                .long("fifo")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("ring-buffer")
+                .help("Create a named pipe at the output path, unless it exists. \
+Existing named pipes are detected without this flag")
+        )
+        .arg(
+            Arg::new("fifo-buffer")
+                .long("fifo-buffer")
+                .value_name("BYTES")
+                .value_parser(value_parser!(usize))
+                .default_value("1048576")
+                .help("Data buffered while no reader is attached to the named pipe, \
+the oldest being dropped beyond")
+        )
+        .arg(
+            Arg::new("sync-interval")
+                .long("sync-interval")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .conflicts_with("ring-buffer")
+                .help("Add a sync point to the gzip output every DURATION (like 10s), at a frame \
+boundary, decompression being able to start at any of them. Their offsets are listed in \
+<output>.sync")
+        )
+        .arg(
+            Arg::new("duration")
+                .long("duration")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .help("Stop recording after DURATION (like 90s, 15m or 1h30m), completing the output")
+        )
+        .arg(
+            Arg::new("stop-after-fix")
+                .long("stop-after-fix")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .help("Stop recording DURATION (like 30, in seconds, or 1m) after the first 3D fix \
+of the NAV-PVT solutions, printing the time to first fix")
+        )
+        .arg(
+            Arg::new("fix-timeout")
+                .long("fix-timeout")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .requires("stop-after-fix")
+                .help("Stop recording with an error when no 3D fix is found within DURATION")
+        )
+        .arg(
+            Arg::new("monitor-antenna")
+                .long("monitor-antenna")
+                .action(ArgAction::SetTrue)
+                .help("Enable UBX-MON-HW and report the changes of the antenna status, warning \
+when the antenna is short or open")
+        )
+        .arg(
+            Arg::new("abort-on-antenna-fault")
+                .long("abort-on-antenna-fault")
+                .action(ArgAction::SetTrue)
+                .requires("monitor-antenna")
+                .help("Stop recording with an error when the antenna is short or open")
+        )
+        .arg(
+            Arg::new("monitor-jamming")
+                .long("monitor-jamming")
+                .action(ArgAction::SetTrue)
+                .help("Enable the interference detector (UBX-CFG-ITFM) and UBX-MON-RF, or the \
+UBX-MON-HW of older firmware, unless passive, and report the changes of the jamming state with \
+the CW jamming indicator and AGC count, warning when it is warning or critical. The changes are \
+events of the --metadata sidecar")
+        )
+        .arg(
+            Arg::new("jamming-bb-threshold")
+                .long("jamming-bb-threshold")
+                .value_name("DB")
+                .value_parser(value_parser!(u8).range(..16))
+                .default_value("3")
+                .help("Broadband jamming detection threshold of --monitor-jamming (dB)")
+        )
+        .arg(
+            Arg::new("jamming-cw-threshold")
+                .long("jamming-cw-threshold")
+                .value_name("DB")
+                .value_parser(value_parser!(u8).range(..32))
+                .default_value("15")
+                .help("Continuous wave jamming detection threshold of --monitor-jamming (dB)")
+        )
+        .arg(
+            Arg::new("monitor-geofence")
+                .long("monitor-geofence")
+                .action(ArgAction::SetTrue)
+                .help("Enable UBX-NAV-GEOFENCE and print the changes of the geofence states: \
+inside, outside or unknown")
+        )
+        .arg(
+            Arg::new("stats-interval")
+                .long("stats-interval")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("10s")
+                .help("Period of the statistics line printed while recording: elapsed time, bytes, \
+throughput, UBX packets per class and checksum failures")
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .help("Only print warnings and errors, without the statistics line")
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .help("Print more: -v adds the messages written to the receiver and their \
+acknowledgments, -vv every frame received")
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Append the status messages to PATH too, with their time and level")
+        )
+        .arg(
+            Arg::new("reconnect")
+                .long("reconnect")
+                .action(ArgAction::SetTrue)
+                .help("Reopen the serial port when it fails, like when the receiver is unplugged, \
+enable the messages again and keep recording into the same outputs")
+        )
+        .arg(
+            Arg::new("reconnect-delay")
+                .long("reconnect-delay")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("1s")
+                .help("Delay of the first reconnection attempt, doubled at every attempt up to a minute")
+        )
+        .arg(
+            Arg::new("reconnect-max")
+                .long("reconnect-max")
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Reconnection attempts before giving up, exiting with an error. \
+Unlimited by default")
+        )
+        .arg(
+            Arg::new("idle-timeout")
+                .long("idle-timeout")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .help("Warn when no data is received for DURATION (like 30s), like when the \
+datagrams of a UDP source stop arriving")
+        )
+        .arg(
+            Arg::new("exit-on-idle")
+                .long("exit-on-idle")
+                .action(ArgAction::SetTrue)
+                .requires("idle-timeout")
+                .help("Stop recording with an error after --idle-timeout without data, \
+instead of warning")
+        )
+        .arg(
+            Arg::new("max-packets")
+                .long("max-packets")
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Stop recording once N UBX packets with a valid checksum were received, \
+right after the last one")
+        )
+        .arg(
+            Arg::new("max-bytes")
+                .long("max-bytes")
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Stop recording once N bytes were received")
+        )
+        .arg(
+            Arg::new("rotate-interval")
+                .long("rotate-interval")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .conflicts_with_all(["ring-buffer", "start-on", "fifo"])
+                .help("Start a new output file every DURATION (like 1h or 15m), named after \
+its start time: \"station.ubx.gz\" becomes \"station-2024-05-03T14.ubx.gz\". Frames are not split \
+across files, each file being complete")
+        )
+        .arg(
+            Arg::new("rotate-align")
+                .long("rotate-align")
+                .value_name("ALIGN")
+                .requires("rotate-interval")
+                .value_parser(Align::parse)
+                .default_value("start")
+                .help("Start the files every interval since the recording started (start), \
+or at the multiples of the interval in UTC (utc), like at :00 every hour")
+        )
+        .arg(
+            Arg::new("metadata")
+                .long("metadata")
+                .action(ArgAction::SetTrue)
+                .help("Describe the session in <output>.meta.json: start and end times, serial \
+settings, receiver version, enabled messages, bytes, packets per class and reconnections. \
+Written at start, on reconnections and at the end")
+        )
+        .arg(
+            Arg::new("timestamps")
+                .long("timestamps")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["ring-buffer", "start-on", "rotate-interval", "split-by-class"])
+                .help("Record every frame with its reception time, monotonic and UTC in \
+nanoseconds, in the timestamped container read by ubx-read, rather than as raw bytes. \
+Invalid data is dropped")
+        )
+        .arg(
+            Arg::new("split-by-class")
+                .long("split-by-class")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["ring-buffer", "start-on", "fifo"])
+                .help("Record every UBX message class into its own output: \"capture.ubx.gz\" \
+becomes \"capture-NAV.ubx.gz\", \"capture-RXM.ubx.gz\"... Unknown classes and other protocols go \
+into \"capture-OTHER.ubx.gz\". Combines with --rotate-interval")
+        )
+        .arg(
+            Arg::new("print-pvt")
+                .long("print-pvt")
+                .action(ArgAction::SetTrue)
+                .help("Print one JSON object per NAV-PVT solution while recording, \
+including the NAV-ATT attitude of the epoch when present")
+        )
+        .next_help_heading("Live outputs")
+        .arg(
+            Arg::new("tcp-listen")
+                .long("tcp-listen")
+                .visible_alias("serve-tcp")
+                .value_name("ADDRESS")
+                .help("Stream the valid UBX, NMEA and RTCM3 frames received to the TCP clients \
+connecting to ADDRESS, like 0.0.0.0:5000, for u-center, RTKLIB str2str or ubx-read")
+        )
+        .arg(
+            Arg::new("udp-send")
+                .long("udp-send")
+                .visible_alias("broadcast-udp")
+                .value_name("ADDRESS")
+                .help("Send the received data as UDP datagrams to ADDRESS, unicast, broadcast \
+or multicast, like 192.168.1.255:5000")
+        )
+        .arg(
+            Arg::new("broadcast-mode")
+                .long("broadcast-mode")
+                .value_name("MODE")
+                .requires("udp-send")
+                .value_parser(["frames", "reads"])
+                .default_value("frames")
+                .help("Content of the UDP datagrams: one valid UBX, NMEA or RTCM3 frame each \
+(frames), or the bytes of one read from the receiver each, as received (reads)")
+        )
+        .arg(
+            Arg::new("broadcast-filter")
+                .long("broadcast-filter")
+                .value_name("CLASSES")
+                .requires("udp-send")
+                .value_delimiter(',')
+                .value_parser(frame::parse_class)
+                .help("Only send the UBX frames of these classes, like RXM,NAV or 0x02, \
+in frames mode")
+        )
+        .arg(
+            Arg::new("csv-out")
+                .long("csv-out")
+                .value_name("FILE")
+                .help("Write one CSV row per NAV-PVT solution to FILE while recording")
+        )
+        .next_help_heading("Messages")
+        .arg(
+            Arg::new("enable-msg")
+                .long("enable-msg")
+                .value_name("MESSAGES")
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| messages::parse(s, true))
+                .help("Comma separated messages to enable instead of UBX-NAV-PVT, named like \
+NAV-PVT or given by class and id like 0x02,0x15, each followed by its rate divisor, 1 by default: \
+NAV-PVT@1,NAV-SAT@5,RXM-RAWX@1,RXM-SFRBX@1")
+        )
+        .arg(
+            Arg::new("disable-msg")
+                .long("disable-msg")
+                .value_name("MESSAGES")
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| messages::parse(s, false))
+                .help("Comma separated messages to disable on all ports, like NMEA-GSV,NMEA-GSA")
+        )
+        .arg(
+            Arg::new("disable-all-default")
+                .long("disable-all-default")
+                .action(ArgAction::SetTrue)
+                .help("Disable the common periodic messages on all ports first: the default NMEA \
+sentences and the NAV solutions, so that only the enabled messages are recorded")
+        )
+        .arg(
+            Arg::new("msg-ports")
+                .long("msg-ports")
+                .value_name("PORTS")
+                .value_delimiter(',')
+                .value_parser(messages::PORTS)
+                .default_value("uart1,uart2,usb")
+                .help("Comma separated ports the --enable-msg messages are output on, among \
+i2c, uart1, uart2, usb and spi, the others not outputting them")
+        )
+        .arg(
+            Arg::new("events-pin")
+                .long("events-pin")
+                .action(ArgAction::SetTrue)
+                .help("Enable UBX-TIM-TM2 time marks of the pulses received on the EXTINT pins")
+        )
+        .arg(
+            Arg::new("rtcm-after-survey")
+                .long("rtcm-after-survey")
+                .action(ArgAction::SetTrue)
+                .help("Enable the RTCM3 base messages on the --msg-ports once the survey-in \
+completes (UBX-NAV-SVIN valid): 1005, 1077, 1087, 1097, 1127 and 1230")
+        )
+        .arg(
+            Arg::new("enable-odo")
+                .long("enable-odo")
+                .action(ArgAction::SetTrue)
+                .help("Enable the UBX-NAV-ODO odometer solutions on the --msg-ports, at each \
+epoch, the odometer being enabled with configure odometer")
+        )
+        .arg(
+            Arg::new("print-odo")
+                .long("print-odo")
+                .action(ArgAction::SetTrue)
+                .help("Print the distances of the UBX-NAV-ODO messages at each epoch, enabling \
+them like --enable-odo unless passive")
+        )
+        .arg(
+            Arg::new("print-sats")
+                .long("print-sats")
+                .action(ArgAction::SetTrue)
+                .help("Print a line per epoch on the satellites: used and tracked, their mean and \
+maximum C/N0, and the satellites of each constellation, from UBX-NAV-SAT, or UBX-NAV-SIG, or \
+the UBX-NAV-SVINFO of older firmware. UBX-NAV-SAT is enabled on the --msg-ports unless passive \
+or one of them is enabled with --enable-msg")
+        )
+        .arg(
+            Arg::new("reset-odometer")
+                .long("reset-odometer")
+                .action(ArgAction::SetTrue)
+                .help("Reset the distance of the odometer (UBX-NAV-RESETODO) before recording, unless \
+passive")
+        )
+        .arg(
+            Arg::new("raw")
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help("Enable the UBX-RXM-RAWX raw measurements, UBX-RXM-SFRBX navigation data \
+and UBX-NAV-PVT at each solution on all serial ports, like for RINEX conversion, the statistics \
+line reporting the raw epochs per second")
+        )
+        .arg(
+            Arg::new("high-precision")
+                .long("high-precision")
+                .action(ArgAction::SetTrue)
+                .help("Enable the UBX-NAV-HPPOSLLH and UBX-NAV-HPPOSECEF high-precision \
+positions of the high precision receivers, like the ZED-F9P, and UBX-NAV-PVT on all serial \
+ports. --print-pvt and --csv-out then output the 0.1 mm positions of UBX-NAV-HPPOSLLH")
+        )
+        .arg(
+            Arg::new("imu-raw")
+                .long("imu-raw")
+                .action(ArgAction::SetTrue)
+                .help("Enable the 100 Hz UBX-ESF-RAW sensor samples of dead reckoning receivers. \
+Requires a high baud rate")
+        )
+        .arg(
+            Arg::new("print-esf-status")
+                .long("print-esf-status")
+                .action(ArgAction::SetTrue)
+                .help("Print the sensor fusion mode and the calibration of each sensor of the \
+UBX-ESF-STATUS messages of dead reckoning receivers at each epoch, as warnings while the sensors \
+are initializing, enabling them on the --msg-ports unless passive")
+        )
+        .arg(
+            Arg::new("inf")
+                .long("inf")
+                .value_name("SEVERITIES")
+                .value_delimiter(',')
+                .value_parser(inf::NAMES)
+                .help("Output the UBX INF text messages of the comma separated severities, among \
+error, warning, notice and debug, on the --msg-ports (UBX-CFG-INF) unless passive, and print \
+their text at the level of their severity, INF-DEBUG being shown with -v")
+        )
+        .arg(
+            Arg::new("print-timing")
+                .long("print-timing")
+                .action(ArgAction::SetTrue)
+                .help("Print each new edge of the UBX-TIM-TM2 time marks: channel, count, time of \
+week and UTC time with nanosecond digits, accuracy, and the quantization error of the last \
+UBX-TIM-TP time pulse. Both are enabled on the --msg-ports unless passive")
+        )
+        .arg(
+            Arg::new("init-packets")
+                .long("init-packets")
+                .value_name("FILE")
+                .help("Send the UBX messages of FILE before configuring the receiver: one hex encoded \
+message per line, either a full frame or its class, id and payload, followed by \"!ack\" \
+to wait for its acknowledgment. Lines starting with # are comments")
+        )
+        .arg(
+            Arg::new("assistnow")
+                .long("assistnow")
+                .value_name("FILE")
+                .help("Send the UBX-MGA messages of the AssistNow Offline or Online FILE before \
+configuring the receiver, for a faster first fix. The receiver is set to acknowledge them \
+(UBX-CFG-NAVX5), each being sent once the previous one is, and the messages accepted and \
+rejected are printed")
+        )
+        .arg(
+            Arg::new("assistnow-online")
+                .long("assistnow-online")
+                .value_name("TOKEN")
+                .conflicts_with("assistnow")
+                .help("Download the AssistNow Online data with the TOKEN of the u-blox account \
+(HTTPS) and send its UBX-MGA messages like --assistnow. When the download fails, the recording \
+starts without them")
+        )
+        .group(ArgGroup::new("assistnow-data").args(["assistnow", "assistnow-online"]))
+        .arg(
+            Arg::new("assistnow-timeout")
+                .long("assistnow-timeout")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("60s")
+                .requires("assistnow-data")
+                .help("Give up the AssistNow upload after DURATION, recording then starting \
+without the messages left")
+        )
+        .arg(
+            Arg::new("set-time")
+                .long("set-time")
+                .action(ArgAction::SetTrue)
+                .help("Send the time of the host clock to the receiver (UBX-MGA-INI-TIME-UTC) \
+before configuring it, for a faster first fix without a backup battery, and print the offset \
+of the host clock at the first solution. Nothing is sent when the host clock is not set")
+        )
+        .arg(
+            Arg::new("set-time-accuracy")
+                .long("set-time-accuracy")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("1s")
+                .requires("set-time")
+                .help("Accuracy of the host clock sent with --set-time")
+        )
+        .arg(
+            Arg::new("protocols")
+                .long("protocols")
+                .value_name("PROTOCOLS")
+                .value_delimiter(',')
+                .required(false)
+                .value_parser(Protocol::parse)
+                .conflicts_with("start-on")
+                .help("Comma separated protocols among ubx, nmea and rtcm: only output these \
+on the receiver port (UBX-CFG-PRT), and only record these. Everything received is recorded by default")
+        )
+        .arg(
+            Arg::new("verify-config")
+                .long("verify-config")
+                .action(ArgAction::SetTrue)
+                .help("Poll the configuration back once written, and print what the receiver \
+actually applied: output protocols of the port, rates of the enabled messages and navigation rate")
+        )
+        .arg(
+            Arg::new("ack-timeout")
+                .long("ack-timeout")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("3s")
+                .help("Longest wait for the receiver to acknowledge a configuration message, or \
+to answer a poll like UBX-MON-VER, before exiting with an error")
+        )
+        .arg(
+            Arg::new("no-configure")
+                .long("no-configure")
+                .action(ArgAction::SetTrue)
+                .help("Passive recording: do not enable UBX-NAV-PVT nor poll UBX-MON-VER, \
+the receiver is only written to by the configuration explicitly requested")
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Check the command without changing anything: the receiver is polled, \
+UBX-MON-VER included, the messages configuring it are printed rather than sent, the outputs \
+are checked to be writable but not created, and nothing is recorded")
+        )
+        .next_help_heading("Receiver reset")
+        .arg(
+            Arg::new("cold-start-on-start")
+                .long("cold-start-on-start")
+                .action(ArgAction::SetTrue)
+                .help("Cold start the receiver (UBX-CFG-RST) before recording, \
+and print the time to first 3D fix")
+        )
+        .next_help_heading("Pre-trigger recording")
+        .arg(
+            Arg::new("ring-buffer")
+                .long("ring-buffer")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .help("Keep the most recent frames in memory (for example \"60s\"), \
+and only write them to a new timestamped file when triggered")
+        )
+        .arg(
+            Arg::new("post-trigger")
+                .long("post-trigger")
+                .value_name("DURATION")
+                .value_parser(parse_duration)
+                .default_value("30s")
+                .help("Duration of live data written after a trigger")
+        )
+        .arg(
+            Arg::new("dump-signal")
+                .long("dump-signal")
+                .value_name("SIGNAL")
+                .value_parser(["USR1", "USR2"])
+                .default_value("USR1")
+                .help("Signal triggering a capture. Pressing Enter triggers a capture too")
+        )
+        .next_help_heading("Start condition")
+        .arg(
+            Arg::new("start-on")
+                .long("start-on")
+                .value_name("CONDITION")
+                .help("Only start writing once the condition is met by a NAV-PVT solution: \
+rtk-float, rtk-fixed, geofence-exit, or a comparison on fix, speed (m/s), hacc, vacc (m) \
+or numsv, like \"speed>5\" or \"fix>=3d\". With --ring-buffer, the buffered lead-up \
+is written first. The UBX frames are recorded into a single output, --rotate-interval, \
+--split-by-class, --timestamps and --protocols not applying")
+        )
+        .arg(
+            Arg::new("geofence")
+                .long("geofence")
+                .value_name("LAT,LON,RADIUS")
+                .value_parser(|s: &str| s.parse::<Geofence>())
+                .help("Circular geofence for geofence-exit, in degrees and meters")
         )
         .subcommand(
             Command::new("configure")
-                .about("Configure settings for specific UART/USB port")
+                .about("Configure settings for specific UART/USB/SPI/I2C port")
                 .arg(
                     Arg::new("port")
                         .long("select")
-                        .required(true)
                         .default_value("usb")
-                        .value_parser(value_parser!(String))
+                        .value_parser(portcfg::PORT_IDS.map(|(name, _)| name))
                         .long_help(
-                            "Apply specific configuration to the selected port. Supported: usb, uart1, uart2.
-Configuration includes: protocol in/out, data-bits, stop-bits, parity, baud-rate",
+                            "Apply specific configuration to the selected port. Supported: usb, uart1, uart2, \
+spi, i2c.
+Configuration includes: protocol in/out, data-bits, stop-bits, parity, baud-rate for the UARTs, \
+the SPI mode and 0xFF count for SPI, the slave address for I2C",
                         ),
                     )
                 .arg(
@@ -117,274 +1087,3099 @@ Configuration includes: protocol in/out, data-bits, stop-bits, parity, baud-rate
                         .value_name("baud")
                         .long("baud")
                         .required(false)
-                        .default_value("9600")
                         .value_parser(value_parser!(u32))
-                        .help("Baud rate to set"),
+                        .help("Baud rate to set, the current one being kept by default"),
                 )
                 .arg(
                     Arg::new("stop-bits")
                         .long("stop-bits")
-                        .help("Number of stop bits to set")
+                        .help("Number of stop bits to set, the current ones being kept by default")
                         .required(false)
-                        .value_parser(["1", "2"])
-                        .default_value("1"),
+                        .value_parser(["1", "2"]),
                 )
                 .arg(
                     Arg::new("data-bits")
                         .long("data-bits")
-                        .help("Number of data bits to set")
+                        .help("Number of data bits to set, the current ones being kept by default")
                         .required(false)
-                        .value_parser(["7", "8"])
-                        .default_value("8"),
+                        .value_parser(["7", "8"]),
                 )
                 .arg(
                     Arg::new("parity")
                         .long("parity")
-                        .help("Parity to set")
+                        .help("Parity to set, the current one being kept by default")
                         .required(false)
-                        .value_parser(["even", "odd"]),
+                        .value_parser(portcfg::PARITIES.map(|(name, _)| name)),
                 )
-        )
-        .get_matches();
-
-    let port = matches
-        .get_one::<String>("port")
-        .expect("Expected required 'port' cli argumnet");
-
-    let baud = matches.get_one::<u32>("baud").cloned().unwrap_or(9600);
-    let stop_bits = match matches.get_one::<String>("stop-bits").map(|s| s.as_str()) {
-        Some("2") => SerialStopBits::Two,
-        _ => SerialStopBits::One,
-    };
-
-    let data_bits = match matches.get_one::<String>("data-bits").map(|s| s.as_str()) {
-        Some("7") => SerialDataBits::Seven,
-        Some("8") => SerialDataBits::Eight,
-        _ => {
-            println!("Number of DataBits supported by uBlox is either 7 or 8");
-            std::process::exit(1);
-        },
-    };
-
-    let parity = match matches.get_one::<String>("parity").map(|s| s.as_str()) {
-        Some("odd") => SerialParity::Even,
-        Some("even") => SerialParity::Odd,
-        _ => SerialParity::None,
-    };
-
-    let builder = serialport::new(port, baud)
-        .stop_bits(stop_bits)
-        .data_bits(data_bits)
-        .timeout(Duration::from_millis(10))
-        .parity(parity)
-        .flow_control(SerialFlowControl::None);
-
-    let port = builder.open().unwrap_or_else(|e| {
-        eprintln!("Failed to open \"{}\". Error: {}", port, e);
-        ::std::process::exit(1);
-    });
-
-    let mut device = Device::new(port);
-
-    let path = match matches.get_one::<String>("output") {
-        Some(output) => output.to_string(),
-        None => "output.ubx.gz".to_string(),
-    };
-
-    let mut buf = [0; 2048];
-    let mut writer = BufferedWriter::new(&path);
+                .arg(
+                    Arg::new("spi-mode")
+                        .long("spi-mode")
+                        .value_name("MODE")
+                        .value_parser(value_parser!(u8).range(..4))
+                        .help("SPI mode to set, 0 to 3 for the clock polarity and phase, with \
+--select spi"),
+                )
+                .arg(
+                    Arg::new("spi-ff-count")
+                        .long("spi-ff-count")
+                        .value_name("N")
+                        .value_parser(value_parser!(u8).range(..=i64::from(portcfg::MAX_FF_COUNT)))
+                        .help("Number of 0xFF bytes received ending an SPI transfer to set, with \
+--select spi"),
+                )
+                .arg(
+                    Arg::new("i2c-address")
+                        .long("i2c-address")
+                        .value_name("ADDRESS")
+                        .value_parser(portcfg::parse_i2c_address)
+                        .help("7 bits slave address of the receiver to set, like 0x42, with \
+--select i2c"),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .value_name("FILE")
+                        .exclusive(true)
+                        .help("Apply the TOML profile FILE: port settings, message rates, \
+navigation settings and saving, printing a pass or fail line per item, and exit"),
+                )
+                .arg(
+                    Arg::new("dump")
+                        .long("dump")
+                        .value_name("FILE")
+                        .exclusive(true)
+                        .help("Write the current configuration to FILE and exit: the known \
+configuration keys in a u-center configuration file on generation 9 receivers, the CFG messages \
+in a legacy dump otherwise, the files of ubx-config dump"),
+                )
+                .arg(
+                    Arg::new("restore")
+                        .long("restore")
+                        .value_name("FILE")
+                        .exclusive(true)
+                        .help("Apply the configuration of the FILE written by --dump or \
+ubx-config dump and exit, printing a pass or fail line per setting, failing on the ones not \
+applied"),
+                )
+                .arg(
+                    Arg::new("show-only")
+                        .long("show-only")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the current settings of the selected port (UBX-CFG-PRT) and \
+exit, writing nothing"),
+                )
+                .arg(
+                    Arg::new("in-proto")
+                        .long("in-proto")
+                        .value_name("PROTOCOLS")
+                        .value_delimiter(',')
+                        .value_parser(Protocol::parse)
+                        .help("Comma separated input protocols to set among ubx, nmea and rtcm3. \
+The current ones are kept by default, the port configuration being polled first"),
+                )
+                .arg(
+                    Arg::new("out-proto")
+                        .long("out-proto")
+                        .value_name("PROTOCOLS")
+                        .value_delimiter(',')
+                        .value_parser(Protocol::parse)
+                        .help("Comma separated output protocols to set among ubx, nmea and rtcm3. \
+The current ones are kept by default, the port configuration being polled first"),
+                )
+                .arg(
+                    Arg::new("meas-rate-ms")
+                        .long("meas-rate-ms")
+                        .value_name("MS")
+                        .value_parser(navcfg::parse_meas_rate)
+                        .help("Measurement period to set (UBX-CFG-RATE), like 100 for 10 Hz, \
+from 25 ms"),
+                )
+                .arg(
+                    Arg::new("nav-rate")
+                        .long("nav-rate")
+                        .value_name("CYCLES")
+                        .value_parser(navcfg::parse_nav_rate)
+                        .help("Measurement cycles per navigation solution to set (UBX-CFG-RATE)"),
+                )
+                .arg(
+                    Arg::new("time-ref")
+                        .long("time-ref")
+                        .value_name("TIME")
+                        .value_parser(navcfg::TIME_REFS)
+                        .help("Time the measurements are aligned on (UBX-CFG-RATE)"),
+                )
+                .arg(
+                    Arg::new("dyn-model")
+                        .long("dyn-model")
+                        .value_name("MODEL")
+                        .value_parser(navcfg::DYN_MODELS.map(|(name, _)| name))
+                        .help("Dynamic platform model to set (UBX-CFG-NAV5), like airborne-4g for \
+drones or stationary for static surveys"),
+                )
+                .arg(
+                    Arg::new("fix-mode")
+                        .long("fix-mode")
+                        .value_name("MODE")
+                        .value_parser(navcfg::FIX_MODES.map(|(name, _)| name))
+                        .help("Position fix mode to set (UBX-CFG-NAV5)"),
+                )
+                .arg(
+                    Arg::new("min-elev")
+                        .long("min-elev")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(i8).range(-90..=90))
+                        .help("Elevation mask to set (UBX-CFG-NAV5): satellites below are not used"),
+                )
+                .arg(
+                    Arg::new("gnss")
+                        .long("gnss")
+                        .value_name("SYSTEMS")
+                        .value_delimiter(',')
+                        .value_parser(navcfg::CONSTELLATIONS.map(|(name, _)| name))
+                        .help("Comma separated constellations to enable (UBX-CFG-GNSS) like \
+gps,galileo,sbas, the others being disabled and the channel allocations kept"),
+                )
+                .arg(
+                    Arg::new("sbas")
+                        .long("sbas")
+                        .value_name("STATE")
+                        .value_parser(["enabled", "disabled"])
+                        .help("Enables or disables SBAS (UBX-CFG-SBAS)"),
+                )
+                .arg(
+                    Arg::new("sbas-mode")
+                        .long("sbas-mode")
+                        .value_name("USES")
+                        .value_delimiter(',')
+                        .value_parser(navcfg::SBAS_MODES.map(|(name, _)| name))
+                        .help("Comma separated uses of SBAS to set among ranging, correction and \
+integrity (UBX-CFG-SBAS), like correction,integrity for timing receivers"),
+                )
+                .arg(
+                    Arg::new("sbas-prns")
+                        .long("sbas-prns")
+                        .value_name("PRNS")
+                        .value_delimiter(',')
+                        .value_parser(navcfg::parse_sbas_prn)
+                        .help("Comma separated PRNs of the SBAS satellites to search (UBX-CFG-SBAS), \
+like 123,136 for EGNOS, from 120 to 158"),
+                )
+                .arg(
+                    Arg::new("tp")
+                        .long("tp")
+                        .value_name("PULSE")
+                        .value_parser(["1", "2"])
+                        .default_value("1")
+                        .help("Time pulse the --tp-* settings are for, TP2 not being on every \
+receiver"),
+                )
+                .arg(
+                    Arg::new("tp-freq")
+                        .long("tp-freq")
+                        .value_name("HZ")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .conflicts_with("tp-period-us")
+                        .help("Time pulse frequency to set (UBX-CFG-TP5)"),
+                )
+                .arg(
+                    Arg::new("tp-period-us")
+                        .long("tp-period-us")
+                        .value_name("US")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("Time pulse period to set (UBX-CFG-TP5), instead of a frequency"),
+                )
+                .arg(
+                    Arg::new("tp-length-us")
+                        .long("tp-length-us")
+                        .value_name("US")
+                        .value_parser(value_parser!(u32))
+                        .conflicts_with("tp-duty")
+                        .help("Time pulse length to set (UBX-CFG-TP5)"),
+                )
+                .arg(
+                    Arg::new("tp-duty")
+                        .long("tp-duty")
+                        .value_name("PERCENT")
+                        .value_parser(timepulse::parse_duty)
+                        .help("Time pulse duty cycle to set (UBX-CFG-TP5), instead of a length"),
+                )
+                .arg(
+                    Arg::new("tp-polarity")
+                        .long("tp-polarity")
+                        .value_name("EDGE")
+                        .value_parser(["rising", "falling"])
+                        .help("Edge of the time pulse at the top of the second (UBX-CFG-TP5)"),
+                )
+                .arg(
+                    Arg::new("tp-align")
+                        .long("tp-align")
+                        .value_name("TIME")
+                        .value_parser(navcfg::TIME_REFS)
+                        .help("Time the pulse is aligned on (UBX-CFG-TP5), like gps or utc"),
+                )
+                .arg(
+                    Arg::new("tp-unlocked")
+                        .long("tp-unlocked")
+                        .value_name("PULSE")
+                        .value_parser(["pulse", "off"])
+                        .help("Pulse while the receiver is not locked on GNSS time, or none until \
+it is (UBX-CFG-TP5)"),
+                )
+                .arg(
+                    Arg::new("power-mode")
+                        .long("power-mode")
+                        .value_name("MODE")
+                        .value_parser(powercfg::POWER_MODES.map(|(name, _)| name))
+                        .help("Power setup to set (UBX-CFG-PMS), the interval mode waking up every \
+--power-interval for --power-ontime"),
+                )
+                .arg(
+                    Arg::new("power-interval")
+                        .long("power-interval")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .required_if_eq("power-mode", "interval")
+                        .help("Period of the interval power mode, or alone the update period of \
+the power save mode (UBX-CFG-PM2), which is then enabled (UBX-CFG-RXM)"),
+                )
+                .arg(
+                    Arg::new("power-ontime")
+                        .long("power-ontime")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .help("Time on in each period of the interval power mode, or alone of the \
+power save mode (UBX-CFG-PM2), in whole seconds"),
+                )
+                .arg(
+                    Arg::new("survey-in")
+                        .long("survey-in")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("fixed")
+                        .help("Make the receiver an RTK base averaging its position \
+(UBX-CFG-TMODE3), its progress being printed from UBX-NAV-SVIN while recording"),
+                )
+                .arg(
+                    Arg::new("min-duration")
+                        .long("min-duration")
+                        .value_name("SECONDS")
+                        .value_parser(value_parser!(u32))
+                        .requires("survey-in")
+                        .help("Shortest survey-in (UBX-CFG-TMODE3)"),
+                )
+                .arg(
+                    Arg::new("accuracy-limit")
+                        .long("accuracy-limit")
+                        .value_name("METERS")
+                        .value_parser(value_parser!(f64))
+                        .requires("survey-in")
+                        .help("Mean position accuracy ending the survey-in (UBX-CFG-TMODE3)"),
+                )
+                .arg(
+                    Arg::new("fixed")
+                        .long("fixed")
+                        .action(ArgAction::SetTrue)
+                        .requires_all(["lat", "lon", "height"])
+                        .help("Make the receiver an RTK base at the known position of --lat, \
+--lon and --height (UBX-CFG-TMODE3)"),
+                )
+                .arg(
+                    Arg::new("lat")
+                        .long("lat")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(base::parse_latitude)
+                        .requires("fixed")
+                        .help("Latitude of the fixed base, to 1e-9 degree"),
+                )
+                .arg(
+                    Arg::new("lon")
+                        .long("lon")
+                        .value_name("DEGREES")
+                        .allow_negative_numbers(true)
+                        .value_parser(base::parse_longitude)
+                        .requires("fixed")
+                        .help("Longitude of the fixed base, to 1e-9 degree"),
+                )
+                .arg(
+                    Arg::new("height")
+                        .long("height")
+                        .value_name("METERS")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f64))
+                        .requires("fixed")
+                        .help("Height of the fixed base above the ellipsoid, to 0.1 mm"),
+                )
+                .arg(
+                    Arg::new("save")
+                        .long("save")
+                        .action(ArgAction::SetTrue)
+                        .help("Save the configuration to BBR and flash once applied (UBX-CFG-CFG), \
+so that it survives a power cycle"),
+                )
+                .args_conflicts_with_subcommands(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Save the port, message, navigation, power and antenna settings \
+to BBR and flash (UBX-CFG-CFG)")
+                )
+                .subcommand(
+                    Command::new("revert")
+                        .about("Reload the saved configuration, undoing the settings not saved")
+                )
+                .subcommand(
+                    Command::new("factory-reset")
+                        .about("Clear the saved configuration and reload the factory defaults")
+                        .arg(
+                            Arg::new("yes")
+                                .long("yes")
+                                .action(ArgAction::SetTrue)
+                                .help("Confirm the factory reset"),
+                        )
+                )
+                .subcommand(
+                    Command::new("valset")
+                        .about("Set configuration keys of generation 9 receivers and later \
+(UBX-CFG-VALSET), like --key CFG-RATE-MEAS=100")
+                        .arg(
+                            Arg::new("key")
+                                .long("key")
+                                .value_name("KEY=VALUE")
+                                .action(ArgAction::Append)
+                                .required(true)
+                                .value_parser(valcfg::parse_assignment)
+                                .help("Key named like CFG-RATE-MEAS or by its hex id like \
+0x30210001, and its value, repeated for several keys, applied together"),
+                        )
+                        .arg(
+                            Arg::new("layer")
+                                .long("layer")
+                                .value_name("LAYERS")
+                                .value_delimiter(',')
+                                .default_value("ram")
+                                .value_parser(valcfg::SET_LAYERS.map(|(name, _)| name))
+                                .help("Layers set, comma separated"),
+                        )
+                )
+                .subcommand(
+                    Command::new("valget")
+                        .about("Read configuration keys of generation 9 receivers and later \
+(UBX-CFG-VALGET)")
+                        .arg(
+                            Arg::new("key")
+                                .long("key")
+                                .value_name("KEY")
+                                .action(ArgAction::Append)
+                                .required(true)
+                                .value_parser(valcfg::parse_key)
+                                .help("Key named like CFG-RATE-MEAS or by its hex id like \
+0x30210001, repeated for several keys"),
+                        )
+                        .arg(
+                            Arg::new("layer")
+                                .long("layer")
+                                .value_name("LAYER")
+                                .default_value("ram")
+                                .value_parser(valcfg::GET_LAYERS.map(|(name, _)| name))
+                                .help("Layer read, the default one being the factory defaults"),
+                        )
+                )
+                .subcommand(
+                    Command::new("antenna")
+                        .about("Configure the active antenna supervisor (UBX-CFG-ANT), the \
+settings not given being kept, or print them without any")
+                        .arg(
+                            Arg::new("supervisor")
+                                .long("supervisor")
+                                .value_name("STATE")
+                                .value_parser(["on", "off"])
+                                .help("Antenna supply voltage control by the supervisor"),
+                        )
+                        .arg(
+                            Arg::new("short-detect")
+                                .long("short-detect")
+                                .value_name("STATE")
+                                .value_parser(["on", "off"])
+                                .help("Short circuit detection"),
+                        )
+                        .arg(
+                            Arg::new("open-detect")
+                                .long("open-detect")
+                                .value_name("STATE")
+                                .value_parser(["on", "off"])
+                                .help("Open circuit detection"),
+                        )
+                )
+                .subcommand(
+                    Command::new("odometer")
+                        .about("Enable the odometer of the receiver (UBX-CFG-ODO), the settings \
+not given being kept")
+                        .arg(
+                            Arg::new("profile")
+                                .long("profile")
+                                .value_name("PROFILE")
+                                .value_parser(odometer::PROFILES.map(|(name, _)| name))
+                                .help("Odometer profile, for the motion of the receiver"),
+                        )
+                        .arg(
+                            Arg::new("enable-low-speed-filter")
+                                .long("enable-low-speed-filter")
+                                .action(ArgAction::SetTrue)
+                                .help("Enable the low-speed course over ground filter, keeping \
+the heading at low speed"),
+                        )
+                )
+                .subcommand(
+                    Command::new("time")
+                        .about("Set the time reference and the UTC standard of the receiver, \
+with UBX-CFG-RATE and UBX-CFG-NAV5 or the configuration keys of generation 9 receivers, the \
+settings not given being kept, or print them without any")
+                        .arg(
+                            Arg::new("reference")
+                                .long("reference")
+                                .value_name("TIME")
+                                .value_parser(navcfg::TIME_REFS)
+                                .help("Time system the measurements are aligned on"),
+                        )
+                        .arg(
+                            Arg::new("utc-standard")
+                                .long("utc-standard")
+                                .value_name("STANDARD")
+                                .value_parser(timecfg::UTC_STANDARDS.map(|(name, _)| name))
+                                .help("UTC standard of the solutions: auto from the \
+constellations used, usno (GPS), su (GLONASS) or ntsc (BeiDou)"),
+                        )
+                )
+                .subcommand(
+                    Command::new("geofence")
+                        .about("Set the geofences of the receiver (UBX-CFG-GEOFENCE), replacing \
+its previous ones")
+                        .arg(
+                            Arg::new("circle")
+                                .long("circle")
+                                .value_name("LAT,LON,RADIUS")
+                                .action(ArgAction::Append)
+                                .required_unless_present("clear")
+                                .value_parser(|s: &str| s.parse::<Geofence>())
+                                .help("Circular geofence, in degrees and meters, repeated for up \
+to 4 fences"),
+                        )
+                        .arg(
+                            Arg::new("confidence")
+                                .long("confidence")
+                                .value_name("LEVEL")
+                                .default_value("2sigma")
+                                .value_parser(geofence::CONFIDENCES.map(|(name, _)| name))
+                                .help("Confidence required for the receiver to tell a position \
+inside or outside the fences"),
+                        )
+                        .arg(
+                            Arg::new("pio-pin")
+                                .long("pio-pin")
+                                .value_name("PIN")
+                                .value_parser(value_parser!(u8).range(..32))
+                                .help("PIO pin driven by the combined fence state, low inside"),
+                        )
+                        .arg(
+                            Arg::new("clear")
+                                .long("clear")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with_all(["circle", "pio-pin"])
+                                .help("Remove all the geofences"),
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("list-ports")
+                .about("List the serial ports, u-blox receivers (USB vendor id 0x1546) first")
+                .arg(
+                    Arg::new("probe")
+                        .long("probe")
+                        .action(ArgAction::SetTrue)
+                        .help("Open each port at --baud, poll UBX-MON-VER and print the firmware \
+and protocol versions of the receivers answering")
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format, json listing the ports as an array of objects")
+                )
+        )
+        .subcommand(
+            Command::new("sos")
+                .about("Save-on-shutdown backup (UBX-UPD-SOS) of the navigation database in flash")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("backup")
+                        .about("Stop GNSS and back the navigation database up, before powering down")
+                )
+                .subcommand(
+                    Command::new("status")
+                        .about("Tell whether a backup was found and restored at the last startup")
+                )
+                .subcommand(Command::new("clear").about("Invalidate the backup"))
+        )
+        .subcommand(
+            Command::new("version")
+                .about("Print the receiver software and hardware versions and the MON-VER \
+extensions, like the protocol version and supported GNSS, and the chip unique id")
+        )
+        .subcommand(
+            Command::new("reset")
+                .about("Reset the receiver (UBX-CFG-RST), like cold starts for TTFF measurements")
+                .arg(
+                    Arg::new("type")
+                        .long("type")
+                        .value_name("START")
+                        .value_parser(reset::TYPES.map(|(name, _)| name))
+                        .default_value("cold")
+                        .help("Navigation data cleared: none for a hot start, the ephemerides for \
+a warm start, everything for a cold start"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_name("MODE")
+                        .value_parser(reset::MODES.map(|(name, _)| name))
+                        .default_value("gnss")
+                        .help("Hardware (watchdog) or software reset, GNSS only reset keeping the \
+USB link, or GNSS stop and start"),
+                )
+                .arg(
+                    Arg::new("wait-ready")
+                        .long("wait-ready")
+                        .action(ArgAction::SetTrue)
+                        .help("Wait for the receiver to answer UBX-MON-VER again, reopening the \
+port it dropped while restarting"),
+                )
+                .arg(
+                    Arg::new("ready-timeout")
+                        .long("ready-timeout")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .default_value("30s")
+                        .help("Longest wait of --wait-ready"),
+                )
+        );
+    #[cfg(all(feature = "tokio", unix))]
+    let command = runtime::args(command);
+    let matches = command.get_matches();
+
+    let level = logging::level(matches.get_flag("quiet"), matches.get_count("verbose"));
+    if let Err(e) = logging::init(level, matches.get_one::<String>("log-file").map(|s| s.as_str())) {
+        eprintln!("Failed to open the log file: {}", e);
+        std::process::exit(1);
+    }
+
+    let mut start_on = matches.get_one::<String>("start-on").map(|condition| {
+        StartCondition::new(condition, matches.get_one::<Geofence>("geofence").copied())
+            .unwrap_or_else(|e| {
+                error!("Invalid start condition: {}", e);
+                std::process::exit(1);
+            })
+    });
+
+    let init_packets = matches.get_one::<String>("init-packets").map(|path| {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            error!("Failed to read \"{}\": {}", path, e);
+            std::process::exit(1);
+        });
+        init::parse(&text).unwrap_or_else(|errors| {
+            for e in errors {
+                error!("{}: {}", path, e);
+            }
+            std::process::exit(1);
+        })
+    });
+
+    let assistnow = matches.get_one::<String>("assistnow").map(|path| {
+        let data = std::fs::read(path).unwrap_or_else(|e| {
+            error!("Failed to read \"{}\": {}", path, e);
+            std::process::exit(1);
+        });
+        assistnow::parse(&data).unwrap_or_else(|e| {
+            error!("{}: {}", path, e);
+            std::process::exit(1);
+        })
+    });
+
+    if matches.get_flag("list-ports") {
+        list_ports(false, "table", None);
+        return;
+    }
+    if let Some(("list-ports", sub_matches)) = matches.subcommand() {
+        list_ports(
+            sub_matches.get_flag("probe"),
+            sub_matches.get_one::<String>("format").unwrap(),
+            matches.get_one::<Option<u32>>("baud").copied().flatten(),
+        );
+        return;
+    }
+    let port_selected = ["port", "auto-port", "match-serial", "match-product"]
+        .iter()
+        .any(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine));
+    if !port_selected {
+        error!("error: --port, or --auto-port to find the receiver, is required");
+        std::process::exit(2);
+    }
+
+    let port = match matches.get_one::<String>("port") {
+        Some(port) => port.to_string(),
+        None => {
+            let filter = Filter {
+                serial: matches.get_one::<String>("match-serial").cloned(),
+                product: matches.get_one::<String>("match-product").cloned(),
+            };
+            let ports = available_ports();
+            let port = discovery::select(&ports, &filter).unwrap_or_else(|e| {
+                error!("Port discovery failed: {}", e);
+                std::process::exit(1);
+            });
+            info!("Using serial port \"{}\"", port);
+            port
+        },
+    };
+    let port = &port;
+
+    let requested_baud = matches.get_one::<Option<u32>>("baud").copied().flatten();
+    let baud = requested_baud.unwrap_or(COMMON_BAUD_RATES[0]);
+    let stop_bits = match matches.get_one::<String>("stop-bits").map(|s| s.as_str()) {
+        Some("2") => SerialStopBits::Two,
+        _ => SerialStopBits::One,
+    };
+
+    let data_bits = match matches.get_one::<String>("data-bits").map(|s| s.as_str()) {
+        Some("7") => SerialDataBits::Seven,
+        Some("8") => SerialDataBits::Eight,
+        _ => {
+            error!("Number of DataBits supported by uBlox is either 7 or 8");
+            std::process::exit(1);
+        },
+    };
+
+    let parity = serial_parity(matches.get_one::<String>("parity").map(|s| s.as_str()));
+
+    let flow_control =
+        serial_flow_control(matches.get_one::<String>("flow-control").map(|s| s.as_str()));
+    if flow_control == SerialFlowControl::Software {
+        warn!(
+            "warning: XON/XOFF flow control takes the 0x11 and 0x13 bytes out of the received \
+data, corrupting binary UBX and RTCM3 frames"
+        );
+    }
+
+    let low_latency = matches.get_flag("low-latency");
+    let builder = serialport::new(port, baud)
+        .stop_bits(stop_bits)
+        .data_bits(data_bits)
+        .timeout(Duration::from_millis(if low_latency { 1 } else { 10 }))
+        .parity(parity)
+        .flow_control(flow_control);
+
+    let endpoint = Endpoint::parse(port, builder.clone(), low_latency);
+    if !endpoint.is_serial() && requested_baud.is_none() {
+        error!("--baud auto needs a serial port, not a {}", endpoint.kind());
+        std::process::exit(1);
+    }
+    let receive_only = endpoint.is_receive_only();
+    if receive_only {
+        // these write to the receiver, and wait for its answers
+        let configuring = matches.subcommand_name().map(|name| format!("The {} subcommand", name)).or_else(|| {
+            [
+                "events-pin",
+                "enable-msg",
+                "disable-msg",
+                "disable-all-default",
+                "imu-raw",
+                "raw",
+                "high-precision",
+                "enable-odo",
+                "reset-odometer",
+                "init-packets",
+                "assistnow",
+                "assistnow-online",
+                "set-time",
+                "protocols",
+                "verify-config",
+                "cold-start-on-start",
+            ]
+            .into_iter()
+            .find(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine))
+            .map(|id| format!("--{}", id))
+        });
+        // the corrections are written to the receiver
+        #[cfg(all(feature = "tokio", unix))]
+        let configuring = configuring
+            .or_else(|| matches.contains_id("ntrip").then(|| "--ntrip".to_string()));
+        if let Some(name) = configuring {
+            error!(
+                "{} configures the receiver, which a {} cannot send to",
+                name,
+                endpoint.kind()
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let mut device = endpoint.open().unwrap_or_else(|e| {
+        error!("Failed to open \"{}\". Error: {}", port, e);
+        ::std::process::exit(1);
+    });
+    let baud = match requested_baud {
+        Some(baud) => baud,
+        None => {
+            info!("Detecting the baud rate ...");
+            match device.detect_baud(&COMMON_BAUD_RATES, BAUD_DETECTION_TIMEOUT) {
+                Ok(Some(baud)) => {
+                    info!("Receiver found at {} baud", baud);
+                    baud
+                },
+                Ok(None) => {
+                    error!(
+                        "No UBX frame received at any of {:?} baud: check the port, \
+and that UBX is enabled on it",
+                        COMMON_BAUD_RATES
+                    );
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    error!("Failed to detect the baud rate: {}", e);
+                    std::process::exit(1);
+                },
+            }
+        },
+    };
+    // reconnections reopen at the detected rate
+    let endpoint = match endpoint {
+        Endpoint::Serial { builder, low_latency } => Endpoint::Serial {
+            builder: builder.baud_rate(baud),
+            low_latency,
+        },
+        endpoint => endpoint,
+    };
+    let dry_run = matches.get_flag("dry-run");
+    device.dry_run = dry_run;
+    let ack_timeout = *matches.get_one::<Duration>("ack-timeout").unwrap();
+    device.ack_timeout = ack_timeout;
+
+    let required = matches.get_one::<String>("require-firmware").map(|substring| {
+        info!("Polling the receiver version ...");
+        let receiver = Receiver::poll(&mut device, ack_timeout);
+        if let Err(e) = receiver.require_firmware(substring) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        receiver
+    });
+    if let Some(("version", _)) = matches.subcommand() {
+        let receiver = required.unwrap_or_else(|| Receiver::poll(&mut device, ack_timeout));
+        for line in receiver.describe() {
+            println!("{}", line);
+        }
+        return;
+    }
+    if let Some(("sos", sub_matches)) = matches.subcommand() {
+        let report = match sub_matches.subcommand() {
+            Some(("backup", _)) => sos::backup(&mut device),
+            Some(("status", _)) => sos::status(&mut device),
+            _ => sos::clear(&mut device),
+        };
+        match report {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(("reset", sub_matches)) = matches.subcommand() {
+        let start = sub_matches.get_one::<String>("type").unwrap();
+        let mode = sub_matches.get_one::<String>("mode").unwrap();
+        info!("Resetting the receiver: {} start, {} reset mode ...", start, mode);
+        if let Err(e) = reset::reset(&mut device, start, mode) {
+            error!("Failed to send UBX-CFG-RST: {}", e);
+            std::process::exit(1);
+        }
+        if sub_matches.get_flag("wait-ready") && !dry_run {
+            let timeout = *sub_matches.get_one::<Duration>("ready-timeout").unwrap();
+            match reset::wait_ready(&endpoint, &mut device, timeout) {
+                Ok(receiver) => info!(
+                    "receiver: firmware {}",
+                    receiver.firmware().unwrap_or_else(|| "unknown".to_string())
+                ),
+                Err(e) => {
+                    error!("{}", e);
+                    std::process::exit(1);
+                },
+            }
+        }
+        return;
+    }
+    let configure = matches.subcommand_matches("configure");
+    if let Some(path) = configure.and_then(|sub_matches| sub_matches.get_one::<String>("dump")) {
+        match snapshot::dump(&mut device, ack_timeout, path) {
+            Ok(written) => info!("{} written to {}", written, path),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(path) = configure.and_then(|sub_matches| sub_matches.get_one::<String>("restore")) {
+        let snapshot = snapshot::Snapshot::load(path).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        info!("Restoring the {} settings of {} ...", snapshot.count(), path);
+        let results = snapshot.restore(&mut device, ack_timeout);
+        let failures = profile::print_summary(&results);
+        if failures > 0 {
+            error!("{} of the {} settings not restored", failures, results.len());
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = configure.and_then(|sub_matches| sub_matches.get_one::<String>("profile")) {
+        let profile = profile::Profile::load(path).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        let results = profile.apply(&mut device, ack_timeout);
+        let failures = profile::print_summary(&results);
+        if failures > 0 {
+            error!("{} of the {} profile items failed", failures, results.len());
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(("valset", valset_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let values = valset_matches
+            .get_many::<(valcfg::Key, Vec<u8>)>("key")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>();
+        let layers = valset_matches
+            .get_many::<String>("layer")
+            .unwrap()
+            .map(|layer| valcfg::SET_LAYERS.iter().find(|(name, _)| name == layer).unwrap().1)
+            .fold(0, |layers, layer| layers | layer);
+        if let Err(e) = valcfg::valset(&mut device, &values, layers) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        if !dry_run {
+            info!("{} configuration keys set", values.len());
+        }
+        return;
+    }
+    if let Some(("valget", valget_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let keys = valget_matches
+            .get_many::<valcfg::Key>("key")
+            .unwrap()
+            .copied()
+            .collect::<Vec<_>>();
+        let layer = valget_matches.get_one::<String>("layer").unwrap();
+        let (_, layer) = valcfg::GET_LAYERS.iter().find(|(name, _)| name == layer).unwrap();
+        match valcfg::valget(&mut device, &keys, *layer) {
+            Ok(values) => {
+                for (key, value) in &values {
+                    println!("{} = {}", key.describe(), valcfg::decode(key.ty, value));
+                }
+                let missing = keys
+                    .iter()
+                    .filter(|key| !values.iter().any(|(read, _)| read == *key))
+                    .map(|key| key.describe())
+                    .collect::<Vec<_>>();
+                if !missing.is_empty() {
+                    error!("Not read, unknown to the firmware: {}", missing.join(", "));
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(("antenna", antenna_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let flags = [
+            ("supervisor", antenna::SUPERVISOR),
+            ("short-detect", antenna::SHORT_DETECT),
+            ("open-detect", antenna::OPEN_DETECT),
+        ]
+        .into_iter()
+        .filter_map(|(id, flag)| Some((flag, antenna_matches.get_one::<String>(id)? == "on")))
+        .collect::<Vec<_>>();
+        let applied = if flags.is_empty() {
+            antenna::poll(&mut device).map(Some)
+        } else {
+            antenna::set(&mut device, &flags)
+        };
+        match applied {
+            Ok(_) if dry_run => {},
+            Ok(Some(ant)) => info!("Antenna supervisor: {}", ant.describe()),
+            Ok(None) => warn!("The antenna settings could not be polled back"),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(("odometer", odometer_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let applied = odometer::set(
+            &mut device,
+            odometer_matches.get_one::<String>("profile").map(String::as_str),
+            odometer_matches.get_flag("enable-low-speed-filter"),
+        );
+        match applied {
+            Ok(_) if dry_run => {},
+            Ok(Some(odo)) => info!("Odometer settings applied: {}", odo.describe()),
+            Ok(None) => warn!("The odometer settings could not be polled back"),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(("time", time_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let receiver = required.unwrap_or_else(|| {
+            info!("Polling the receiver version for its protocol ...");
+            Receiver::poll(&mut device, ack_timeout)
+        });
+        let applied = timecfg::set(
+            &mut device,
+            &receiver,
+            time_matches.get_one::<String>("reference").map(String::as_str),
+            time_matches.get_one::<String>("utc-standard").map(String::as_str),
+        );
+        match applied {
+            Ok(_) if dry_run => {},
+            Ok(Some(time)) => info!("Time system: {}", time.describe()),
+            Ok(None) => warn!("The time system could not be polled back"),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some(("geofence", geofence_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let circles = geofence_matches
+            .get_many::<Geofence>("circle")
+            .map(|circles| circles.copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let confidence = geofence_matches.get_one::<String>("confidence").unwrap();
+        let (_, confidence) = geofence::CONFIDENCES
+            .iter()
+            .find(|(name, _)| name == confidence)
+            .unwrap();
+        let pin = geofence_matches.get_one::<u8>("pio-pin").copied();
+        let applied = geofence::Fences::new(&circles, *confidence, pin)
+            .and_then(|fences| geofence::set(&mut device, &fences));
+        match applied {
+            Ok(_) if dry_run => {},
+            Ok(Some(fences)) => info!("Geofences applied: {}", fences.describe()),
+            Ok(None) => warn!("The geofences could not be polled back"),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if let Some((action, action_matches)) = configure.and_then(ArgMatches::subcommand) {
+        let action = match action {
+            "save" => persist::Action::Save,
+            "revert" => persist::Action::Revert,
+            _ if !action_matches.get_flag("yes") => {
+                error!("factory-reset clears the saved configuration, confirm with --yes");
+                std::process::exit(1);
+            },
+            _ => persist::Action::FactoryReset,
+        };
+        if let Err(e) = persist::apply(&mut device, action) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        if !dry_run {
+            info!("{}", action.outcome());
+        }
+        return;
+    }
+    check_line(&mut device, baud, matches.get_flag("strict-startup"));
+
+    let timestamped_files =
+        matches.contains_id("ring-buffer") || matches.contains_id("rotate-interval");
+    let template = match matches.get_one::<String>("output") {
+        Some(output) => output.as_str(),
+        None if timestamped_files => "output.ubx.gz",
+        None => naming::DEFAULT_TEMPLATE,
+    };
+    let mut receiver = required.or_else(|| {
+        naming::uses_receiver(template).then(|| {
+            info!("Polling the receiver identification for the output name ...");
+            Receiver::poll(&mut device, ack_timeout)
+        })
+    });
+    if dry_run {
+        if receiver.is_none() {
+            info!("Polling the receiver identification ...");
+            receiver = Some(Receiver::poll(&mut device, ack_timeout));
+        }
+        let receiver = receiver.as_ref().unwrap();
+        println!(
+            "receiver: firmware {}, protocol {}",
+            receiver.firmware().as_deref().unwrap_or("unknown"),
+            receiver.protocol().as_deref().unwrap_or("unknown")
+        );
+    }
+    let tokens = naming::Tokens {
+        port: Some(naming::port_name(port)),
+        serial: receiver.as_ref().and_then(Receiver::unique_id),
+        firmware: receiver.as_ref().and_then(Receiver::firmware),
+    };
+    let path = naming::expand(template, chrono::Utc::now(), &tokens).unwrap_or_else(|e| {
+        error!("Invalid output name: {}", e);
+        std::process::exit(1);
+    });
+    let split_by_class = matches.get_flag("split-by-class");
+    let append = matches.get_flag("append");
+    // the recording being resumed already starts with the container magic of --timestamps
+    let resumed = append && std::fs::metadata(&path).is_ok_and(|metadata| metadata.len() > 0);
+    let named = if split_by_class { split::paths(&path) } else { vec![path.clone()] };
+    // the captures of --ring-buffer are named when triggered, and checked then
+    let outputs = match matches.get_one::<Duration>("rotate-interval") {
+        Some(interval) => {
+            let align = *matches.get_one::<Align>("rotate-align").unwrap();
+            named.iter().map(|path| rotation::first_path(path, *interval, align)).collect()
+        },
+        None if timestamped_files => vec![],
+        None => named,
+    };
+    if !matches.get_flag("force") && !append {
+        for output in &outputs {
+            if let Err(e) = check_overwrite(output) {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if dry_run && path != "-" {
+        // the segments and captures are created next to the output name
+        let checked = if outputs.is_empty() { vec![path.clone()] } else { outputs };
+        for output in checked {
+            if let Err(e) = check_writable(&output) {
+                error!("\"{}\" is not writable: {}", output, e);
+                std::process::exit(1);
+            }
+            println!("output: \"{}\" writable", output);
+        }
+    }
+    let forced = matches
+        .get_one::<String>("compress")
+        .map(|codec| match codec.as_str() {
+            "gzip" => Codec::Gzip,
+            "zstd" => Codec::Zstd,
+            _ => Codec::None,
+        })
+        .or(matches.get_flag("gzip").then_some(Codec::Gzip));
+    let compression = OutputCompression::new(
+        forced,
+        &path,
+        matches.get_one::<u32>("compress-level").copied(),
+    )
+    .unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    });
+    if append && compression.codec(&path) == Codec::Zstd {
+        error!("--append resumes uncompressed and gzip outputs, not zstd ones");
+        std::process::exit(1);
+    }
+    if path == "-" {
+        if timestamped_files
+            || split_by_class
+            || matches.get_flag("fifo")
+            || matches.get_flag("metadata")
+            || append
+        {
+            error!("--ring-buffer, --rotate-interval, --split-by-class, --fifo, --metadata \
+and --append write files, not the standard output");
+            std::process::exit(1);
+        }
+        if matches.get_flag("print-pvt") {
+            error!("--print-pvt prints to the standard output, already recorded into");
+            std::process::exit(1);
+        }
+    } else if matches.get_flag("gzip") {
+        error!("--gzip compresses the standard output (-o -), files are compressed when named *.gz");
+        std::process::exit(1);
+    }
+
+    let mut buf = [0; 2048];
+    let mut sinks = Sinks::default();
+    if matches.get_flag("print-pvt") {
+        sinks.add(Box::new(PvtPrinter::new(matches.get_flag("high-precision"))));
+    }
+    // served by a task of the runtime when recording plainly
+    let tcp_task = cfg!(all(feature = "tokio", unix))
+        && !matches.contains_id("start-on")
+        && !matches.contains_id("ring-buffer");
+    if let Some(address) = matches.get_one::<String>("tcp-listen").filter(|_| !tcp_task) {
+        match TcpServer::bind(address) {
+            Ok(server) => sinks.add(Box::new(server)),
+            Err(e) => {
+                error!("Failed to listen on {}: {}", address, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    if let Some(address) = matches.get_one::<String>("udp-send") {
+        let mode = match matches.get_one::<String>("broadcast-mode").unwrap().as_str() {
+            "reads" => UdpMode::Reads,
+            _ => UdpMode::Frames,
+        };
+        let classes = matches
+            .get_many::<u8>("broadcast-filter")
+            .map(|classes| classes.copied().collect::<Vec<_>>());
+        if mode == UdpMode::Reads && classes.is_some() {
+            error!("--broadcast-filter selects frames, not reads (--broadcast-mode reads)");
+            std::process::exit(1);
+        }
+        match UdpSender::connect(address, mode, classes) {
+            Ok(sender) => sinks.add(Box::new(sender)),
+            Err(e) => {
+                error!("Failed to send to {}: {}", address, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    if let Some(csv_path) = matches.get_one::<String>("csv-out").filter(|_| !dry_run) {
+        match PvtCsv::create(csv_path, matches.get_flag("high-precision")) {
+            Ok(csv) => sinks.add(Box::new(csv)),
+            Err(e) => {
+                error!("Failed to create \"{}\": {}", csv_path, e);
+                std::process::exit(1);
+            },
+        }
+    }
+    let ring_buffer = matches.get_one::<Duration>("ring-buffer").copied();
+    let fifo_buffer = *matches.get_one::<usize>("fifo-buffer").unwrap();
+    let sync_interval = matches.get_one::<Duration>("sync-interval").copied();
+    let output_buffer = low_latency.then_some(LOW_LATENCY_BUFFER);
+    if low_latency || matches.get_flag("report-latency") {
+        sinks.add(Box::new(LatencyMonitor::new(LATENCY_REPORT_INTERVAL)));
+    }
+    if sync_interval.is_some() && compression.codec(&path) != Codec::Gzip {
+        error!("--sync-interval applies to gzip outputs, named *.gz or --compress gzip");
+        std::process::exit(1);
+    }
+    if matches.get_flag("fifo") && !dry_run {
+        create_fifo(&path);
+    }
+    let rotate_interval = matches.get_one::<Duration>("rotate-interval").copied();
+    if rotate_interval.is_some_and(|interval| interval < Duration::from_secs(1)) {
+        error!("--rotate-interval is at least 1s");
+        std::process::exit(1);
+    }
+    let open_output = |path: &str| match path {
+        "-" => BufferedWriter::stdout(compression.codec(path), compression.level),
+        _ => BufferedWriter::open(
+            path,
+            fifo_buffer,
+            sync_interval,
+            output_buffer,
+            compression,
+            append,
+        ),
+    };
+    let mut writer = match (ring_buffer, &start_on, rotate_interval) {
+        (None, None, None) if !split_by_class && !dry_run => Some(open_output(&path)),
+        _ => None,
+    };
+
+    if matches.get_flag("set-time") {
+        let accuracy = *matches.get_one::<Duration>("set-time-accuracy").unwrap();
+        match settime::set_time(&mut device, accuracy) {
+            Ok(now) => {
+                info!(
+                    "Time sent to the receiver: {} UTC, {:?} accuracy",
+                    now.format("%Y-%m-%d %H:%M:%S%.3f"),
+                    accuracy
+                );
+                sinks.add(Box::new(settime::ClockCheck::default()));
+            },
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    for packet in init_packets.iter().flatten() {
+        info!(
+            "Sending init packet of line {}: class 0x{:02x} id 0x{:02x} ...",
+            packet.line,
+            packet.class(),
+            packet.id()
+        );
+        device
+            .write_all(&packet.frame)
+            .unwrap_or_else(|e| exit_on("Could not send init packet", e));
+        if packet.ack {
+            match device.wait_for_ack_of(packet.class(), packet.id(), ack_timeout) {
+                Ok(true) => {},
+                Ok(false) => {
+                    error!("Init packet of line {} rejected by the receiver (UBX-ACK-NAK)", packet.line);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    error!("Init packet of line {} not acknowledged: {}", packet.line, e);
+                    std::process::exit(1);
+                },
+            }
+        }
+    }
+
+    let assistnow = assistnow.or_else(|| {
+        let token = matches.get_one::<String>("assistnow-online")?;
+        info!("Downloading the AssistNow Online data ...");
+        let parsed = assistnow::download(token).and_then(|data| assistnow::parse(&data));
+        match parsed {
+            Ok(frames) => Some(frames),
+            Err(e) => {
+                warn!("{}, recording without AssistNow", e);
+                None
+            },
+        }
+    });
+
+    if let Some(frames) = &assistnow {
+        let timeout = *matches.get_one::<Duration>("assistnow-timeout").unwrap();
+        if dry_run {
+            info!("{} AssistNow messages would be sent", frames.len());
+        } else {
+            info!("Sending {} AssistNow messages ...", frames.len());
+            match assistnow::upload(&mut device, frames, timeout, ack_timeout) {
+                Ok(upload) => info!("AssistNow messages: {}", upload.describe()),
+                Err(e) => {
+                    error!("AssistNow upload failed: {}", e);
+                    std::process::exit(1);
+                },
+            }
+        }
+    }
+
+    // UBX-CFG-PRT payload written by the configure subcommand
+    let mut configured_port = None;
+
+    // Parse cli for configuring specific uBlox UART port
+    if let Some(("configure", sub_matches)) = matches.subcommand() {
+        let port_name = sub_matches.get_one::<String>("port").unwrap().as_str();
+        let port_id = portcfg::PORT_IDS
+            .iter()
+            .find(|(name, _)| *name == port_name)
+            .map(|(_, id)| *id);
+
+        let mask = |id: &str| {
+            sub_matches
+                .get_many::<Protocol>(id)
+                .map(|protocols| protocol::proto_mask(&protocols.copied().collect::<Vec<_>>()))
+        };
+        let request = portcfg::Request {
+            baud: sub_matches.get_one::<u32>("cfg-baud").copied(),
+            data_bits: sub_matches
+                .get_one::<String>("data-bits")
+                .map(|bits| bits.parse().unwrap()),
+            parity: sub_matches.get_one::<String>("parity").map(|parity| {
+                portcfg::PARITIES.iter().find(|(name, _)| name == parity).unwrap().1
+            }),
+            two_stop_bits: sub_matches.get_one::<String>("stop-bits").map(|bits| bits == "2"),
+            in_proto: mask("in-proto"),
+            out_proto: mask("out-proto"),
+            spi_mode: sub_matches.get_one::<u8>("spi-mode").copied(),
+            ff_count: sub_matches.get_one::<u8>("spi-ff-count").copied(),
+            i2c_address: sub_matches.get_one::<u8>("i2c-address").copied(),
+        };
+        // before anything is sent
+        if let Err(e) = request.check(port_id.unwrap()) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        // polled first, for the fields not given to be kept
+        let current_port = |device: &mut Device, port_id: u8| {
+            let port = portcfg::poll(device, port_id, ack_timeout)
+                .unwrap_or_else(|e| {
+                    error!("'{}' port: {}", port_name.to_uppercase(), e);
+                    std::process::exit(1);
+                });
+            info!(
+                "Current '{}' port settings: {}",
+                port_name.to_uppercase(),
+                port.describe()
+            );
+            port
+        };
+        if sub_matches.get_flag("show-only") {
+            if let Some(port_id) = port_id {
+                current_port(&mut device, port_id);
+            }
+            return;
+        }
+        let given = |ids: &[&str]| {
+            ids.iter()
+                .any(|id| sub_matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine))
+        };
+        let rate_given = given(&["meas-rate-ms", "nav-rate", "time-ref"]);
+        let nav5_given = given(&["dyn-model", "fix-mode", "min-elev"]);
+        let gnss_given = given(&["gnss"]);
+        let sbas_given = given(&["sbas", "sbas-mode", "sbas-prns"]);
+        let tp_given = given(&[
+            "tp-freq",
+            "tp-period-us",
+            "tp-length-us",
+            "tp-duty",
+            "tp-polarity",
+            "tp-align",
+            "tp-unlocked",
+        ]);
+        let base_given = given(&["survey-in", "fixed"]);
+        let power_given = given(&["power-mode", "power-interval", "power-ontime"]);
+        let other_given = rate_given
+            || nav5_given
+            || gnss_given
+            || sbas_given
+            || tp_given
+            || base_given
+            || power_given;
+        // the port is configured by default, and left as it is when only other settings are given
+        let port_given = given(&[
+            "port",
+            "cfg-baud",
+            "stop-bits",
+            "data-bits",
+            "parity",
+            "in-proto",
+            "out-proto",
+            "spi-mode",
+            "spi-ff-count",
+            "i2c-address",
+        ]);
+
+        // before a possible change of the port settings cuts the link
+        if rate_given {
+            let applied = navcfg::set_rate(
+                &mut device,
+                sub_matches.get_one::<u16>("meas-rate-ms").copied(),
+                sub_matches.get_one::<u16>("nav-rate").copied(),
+                sub_matches.get_one::<String>("time-ref").map(|s| s.as_str()),
+            )
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(rate) => info!("Navigation rate applied: {}", rate.describe()),
+                None => warn!("The navigation rate applied could not be polled back"),
+            }
+        }
+        if nav5_given {
+            let applied = navcfg::set_nav5(
+                &mut device,
+                sub_matches.get_one::<String>("dyn-model").map(|s| s.as_str()),
+                sub_matches.get_one::<String>("fix-mode").map(|s| s.as_str()),
+                sub_matches.get_one::<i8>("min-elev").copied(),
+            )
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(nav5) => info!("Navigation engine settings applied: {}", nav5.describe()),
+                None => warn!("The navigation engine settings applied could not be polled back"),
+            }
+        }
+        if gnss_given {
+            let names = sub_matches
+                .get_many::<String>("gnss")
+                .unwrap_or_default()
+                .cloned()
+                .collect::<Vec<_>>();
+            let applied = navcfg::set_gnss(&mut device, &names).unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(gnss) => {
+                    info!("Constellations applied: {}", gnss.describe());
+                    warn!(
+                        "The receiver may take a few seconds, or a reset (UBX-CFG-RST), \
+to track the new constellations"
+                    );
+                },
+                None => warn!("The constellations applied could not be polled back"),
+            }
+        }
+        if sbas_given {
+            let modes = sub_matches
+                .get_many::<String>("sbas-mode")
+                .map(|modes| modes.cloned().collect::<Vec<_>>());
+            let prns = sub_matches
+                .get_many::<u8>("sbas-prns")
+                .map(|prns| prns.copied().collect::<Vec<_>>());
+            let applied = navcfg::set_sbas(
+                &mut device,
+                sub_matches.get_one::<String>("sbas").map(|state| state == "enabled"),
+                modes.as_deref(),
+                prns.as_deref(),
+            )
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(sbas) => info!("SBAS applied: {}", sbas.describe()),
+                None => warn!("The SBAS settings applied could not be polled back"),
+            }
+        }
+        if tp_given {
+            let request = timepulse::Request {
+                freq_hz: sub_matches.get_one::<u32>("tp-freq").copied(),
+                period_us: sub_matches.get_one::<u32>("tp-period-us").copied(),
+                length_us: sub_matches.get_one::<u32>("tp-length-us").copied(),
+                duty_percent: sub_matches.get_one::<f64>("tp-duty").copied(),
+                rising: sub_matches
+                    .get_one::<String>("tp-polarity")
+                    .map(|edge| edge == "rising"),
+                time_ref: sub_matches.get_one::<String>("tp-align").and_then(|name| {
+                    navcfg::TIME_REFS
+                        .iter()
+                        .position(|known| known == name)
+                        .map(|index| index as u32)
+                }),
+                unlocked_pulse: sub_matches
+                    .get_one::<String>("tp-unlocked")
+                    .map(|pulse| pulse == "pulse"),
+            };
+            let index = u8::from(sub_matches.get_one::<String>("tp").is_some_and(|tp| tp == "2"));
+            let applied = timepulse::set_time_pulse(&mut device, index, &request)
+                .unwrap_or_else(|e| {
+                    error!("{}", e);
+                    std::process::exit(1);
+                });
+            match applied {
+                _ if dry_run => {},
+                Some(time_pulse) => info!("Time pulse applied: {}", time_pulse.describe()),
+                None => warn!("The time pulse settings applied could not be polled back"),
+            }
+        }
+        if power_given {
+            let period = sub_matches.get_one::<Duration>("power-interval").copied();
+            let on_time = sub_matches.get_one::<Duration>("power-ontime").copied();
+            let applied = match sub_matches.get_one::<String>("power-mode") {
+                Some(mode) => powercfg::set_power_mode(&mut device, mode, period, on_time)
+                    .map(|applied| applied.map(|pms| pms.describe())),
+                None => powercfg::set_pm2(&mut device, period, on_time)
+                    .map(|applied| applied.map(|pm2| pm2.describe())),
+            }
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(power) => info!("Power settings applied: {}", power),
+                None => warn!("The power settings applied could not be polled back"),
+            }
+        }
+        if base_given {
+            let mode = if sub_matches.get_flag("fixed") {
+                base::BaseMode::Fixed {
+                    lat: *sub_matches.get_one::<f64>("lat").unwrap(),
+                    lon: *sub_matches.get_one::<f64>("lon").unwrap(),
+                    height: *sub_matches.get_one::<f64>("height").unwrap(),
+                }
+            } else {
+                base::BaseMode::SurveyIn {
+                    min_duration_s: sub_matches.get_one::<u32>("min-duration").copied(),
+                    accuracy_limit_m: sub_matches.get_one::<f64>("accuracy-limit").copied(),
+                }
+            };
+            let applied = base::set_base(&mut device, &mode).unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            match applied {
+                _ if dry_run => {},
+                Some(tmode3) => info!("Base station mode applied: {}", tmode3.describe()),
+                None => warn!("The base station mode applied could not be polled back"),
+            }
+        }
+
+        if let Some(port_id) = port_id.filter(|_| port_given || !other_given) {
+            let mut port = current_port(&mut device, port_id);
+            // the flags, and the protocols not given, are kept
+            port.apply(&request);
+            info!(
+                "Configuring '{}' port: {} ...",
+                port_name.to_uppercase(),
+                port.describe()
+            );
+            device
+                .write_all(&frame::frame(0x06, 0x00, port.payload()))
+                .unwrap_or_else(|e| exit_on("Could not configure UBX-CFG-PRT-UART", e));
+            configured_port = Some(port.payload().to_vec());
+            // without UBX output, the acknowledgment is not sent anymore
+            if OutProtoMask::from_bits_retain(port.out_proto_mask()).contains(OutProtoMask::UBLOX) {
+                expect_ack::<CfgPrtUart>(&mut device, "UBX-CFG-PRT", ack_timeout);
+            }
+        }
+    }
+
+    if receive_only && !matches.get_flag("no-configure") {
+        info!("Receiving only over UDP, the receiver is not configured (--no-configure)");
+    }
+    let passive = matches.get_flag("no-configure") || receive_only;
+    let rtcm_after_survey = matches.get_flag("rtcm-after-survey");
+    if passive && rtcm_after_survey {
+        error!("--rtcm-after-survey configures the receiver, it cannot be passive");
+        std::process::exit(1);
+    }
+
+    // Messages enabled: name, class, id and rates per port
+    let mut enabled: Vec<(String, u8, u8, [u8; 6])> = vec![];
+
+    let mut disabled = Vec::new();
+    if matches.get_flag("disable-all-default") {
+        disabled.extend(messages::DEFAULTS.iter().filter_map(|name| Message::named(name)));
+    }
+    if let Some(lists) = matches.get_many::<Vec<Message>>("disable-msg") {
+        disabled.extend(lists.flatten().cloned());
+    }
+    for message in &disabled {
+        info!("Disable {} message on all ports ...", message.name);
+        // firmware without the message rejects it, it is not output anyway
+        match set_message_rates(&mut device, message, [0; 6]) {
+            Ok(true) => {},
+            Ok(false) => warn!("Disabling {} rejected (UBX-ACK-NAK)", message.name),
+            Err(e) => warn!("Disabling {} failed: {}", message.name, e),
+        }
+    }
+
+    let requested = matches
+        .get_many::<Vec<Message>>("enable-msg")
+        .map(|lists| lists.flatten().cloned().collect::<Vec<_>>());
+    let ports = messages::parse_ports(
+        &matches
+            .get_many::<String>("msg-ports")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    if let Some(requested) = &requested {
+        for message in requested {
+            let rates = message.rates(&ports);
+            info!("Enable {} message, rates {:?} ...", message.name, rates);
+            let applied = match message.name.as_str() {
+                "NAV-SAT" | "NAV-SIG" => enable_satellites(&mut device, message, rates),
+                _ => set_message_rates(&mut device, message, rates)
+                    .map(|acknowledged| acknowledged.then(|| message.clone())),
+            };
+            let applied = match applied {
+                Ok(Some(applied)) => applied,
+                Ok(None) if message.class == 0x10 => {
+                    error!(
+                        "Enabling {} rejected by the receiver (UBX-ACK-NAK): {}",
+                        message.name,
+                        esf::NO_FUSION
+                    );
+                    std::process::exit(1);
+                },
+                Ok(None) => {
+                    error!("Enabling {} rejected by the receiver (UBX-ACK-NAK)", message.name);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    error!("Enabling {} failed: {}", message.name, e);
+                    std::process::exit(1);
+                },
+            };
+            if !enabled.iter().any(|(name, ..)| *name == applied.name) {
+                enabled.push((applied.name, applied.class, applied.id, rates));
+            }
+        }
+    }
+
+    // Enable the NavPvt packet, unless other messages were requested
+    // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
+    // The other positions are for I2C, SPI, etc. Consult your device manual.
+    if !passive && requested.is_none() {
+        info!("Enable UBX-NAV-PVT message on all serial ports: USB, UART1 and UART2 ...");
+        device
+            .write_all(
+                &CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([0, 1, 1, 1, 0, 0])
+                    .into_packet_bytes(),
+            )
+            .unwrap_or_else(|e| exit_on("Could not configure ports for UBX-NAV-PVT", e));
+        expect_ack::<CfgMsgAllPorts>(&mut device, "UBX-NAV-PVT output (UBX-CFG-MSG)", ack_timeout);
+        enabled.push(("NAV-PVT".to_string(), NavPvt::CLASS, NavPvt::ID, messages::SERIAL_PORTS));
+    }
+
+    // the survey-in progress, and its end for --rtcm-after-survey
+    let survey_in = matches
+        .subcommand_matches("configure")
+        .is_some_and(|sub_matches| sub_matches.get_flag("survey-in"));
+    if !passive && (survey_in || rtcm_after_survey) {
+        let message = Message::named("NAV-SVIN").unwrap();
+        let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+        info!("Enable UBX-NAV-SVIN message, rates {:?} ...", rates);
+        match set_message_rates(&mut device, &message, rates) {
+            Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+            Ok(false) => {
+                warn!("Enabling UBX-NAV-SVIN rejected (UBX-ACK-NAK), no survey-in progress")
+            },
+            Err(e) => warn!("Enabling UBX-NAV-SVIN failed: {}, no survey-in progress", e),
+        }
+    }
+
+    if !passive && matches.get_flag("monitor-antenna") {
+        let message = Message::named("MON-HW").unwrap();
+        let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+        info!("Enable UBX-MON-HW message, rates {:?} ...", rates);
+        match set_message_rates(&mut device, &message, rates) {
+            Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+            Ok(false) => {
+                warn!("Enabling UBX-MON-HW rejected (UBX-ACK-NAK), no antenna status")
+            },
+            Err(e) => warn!("Enabling UBX-MON-HW failed: {}, no antenna status", e),
+        }
+    }
+
+    if !passive && matches.get_flag("monitor-jamming") {
+        let bb_threshold = *matches.get_one::<u8>("jamming-bb-threshold").unwrap();
+        let cw_threshold = *matches.get_one::<u8>("jamming-cw-threshold").unwrap();
+        match jamming::set(&mut device, bb_threshold, cw_threshold) {
+            Ok(Some(itfm)) => info!("Interference detector: {}", itfm.describe()),
+            Ok(None) => warn!("The interference detector settings could not be polled back"),
+            Err(e) => warn!("{}, the jamming state may not be reported", e),
+        }
+        for name in ["MON-RF", "MON-HW"] {
+            if enabled.iter().any(|(enabled, ..)| enabled == name) {
+                break;
+            }
+            let message = Message::named(name).unwrap();
+            let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+            info!("Enable UBX-{} message, rates {:?} ...", name, rates);
+            match set_message_rates(&mut device, &message, rates) {
+                Ok(true) => {
+                    enabled.push((message.name.clone(), message.class, message.id, rates));
+                    break;
+                },
+                Ok(false) if name == "MON-RF" => warn!(
+                    "Enabling UBX-MON-RF rejected (UBX-ACK-NAK), enabling the UBX-MON-HW \
+jamming state of older firmware instead"
+                ),
+                Ok(false) => warn!("Enabling UBX-MON-HW rejected (UBX-ACK-NAK), no jamming state"),
+                Err(e) => {
+                    warn!("Enabling UBX-{} failed: {}, no jamming state", name, e);
+                    break;
+                },
+            }
+        }
+    }
+
+    if !passive && matches.get_flag("monitor-geofence") {
+        let message = Message::named("NAV-GEOFENCE").unwrap();
+        let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+        info!("Enable UBX-NAV-GEOFENCE message, rates {:?} ...", rates);
+        match set_message_rates(&mut device, &message, rates) {
+            Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+            Ok(false) => {
+                warn!("Enabling UBX-NAV-GEOFENCE rejected (UBX-ACK-NAK), no geofence states")
+            },
+            Err(e) => warn!("Enabling UBX-NAV-GEOFENCE failed: {}, no geofence states", e),
+        }
+    }
+
+    let print_odo = matches.get_flag("print-odo");
+    if !passive && (print_odo || matches.get_flag("enable-odo")) {
+        let message = Message::named("NAV-ODO").unwrap();
+        let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+        info!("Enable UBX-NAV-ODO message, rates {:?} ...", rates);
+        match set_message_rates(&mut device, &message, rates) {
+            Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+            Ok(false) => warn!("Enabling UBX-NAV-ODO rejected (UBX-ACK-NAK), no odometer output"),
+            Err(e) => warn!("Enabling UBX-NAV-ODO failed: {}, no odometer output", e),
+        }
+    }
+    if print_odo {
+        sinks.add(Box::new(odometer::OdoPrinter));
+    }
+
+    let print_sats = matches.get_flag("print-sats");
+    let satellite_messages = ["NAV-SAT", "NAV-SIG", "NAV-SVINFO"];
+    if !passive
+        && print_sats
+        && !enabled.iter().any(|(name, ..)| satellite_messages.contains(&name.as_str()))
+    {
+        let message = Message {
+            rate: 1,
+            ..Message::named("NAV-SAT").unwrap()
+        };
+        let rates = message.rates(&ports);
+        info!("Enable UBX-NAV-SAT message, rates {:?} ...", rates);
+        match enable_satellites(&mut device, &message, rates) {
+            Ok(Some(applied)) => enabled.push((applied.name, applied.class, applied.id, rates)),
+            Ok(None) => {
+                warn!("Enabling UBX-NAV-SVINFO rejected (UBX-ACK-NAK), no satellites printed")
+            },
+            Err(e) => warn!("Enabling UBX-NAV-SAT failed: {}, no satellites printed", e),
+        }
+    }
+    if print_sats {
+        sinks.add(Box::<sats::SatPrinter>::default());
+    }
+
+    let print_esf_status = matches.get_flag("print-esf-status");
+    if !passive && print_esf_status && !enabled.iter().any(|(name, ..)| name == "ESF-STATUS") {
+        let message = Message::named("ESF-STATUS").unwrap();
+        let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+        info!("Enable UBX-ESF-STATUS message, rates {:?} ...", rates);
+        match set_message_rates(&mut device, &message, rates) {
+            Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+            Ok(false) => warn!(
+                "Enabling UBX-ESF-STATUS rejected (UBX-ACK-NAK): {}, no sensor fusion status",
+                esf::NO_FUSION
+            ),
+            Err(e) => warn!("Enabling UBX-ESF-STATUS failed: {}, no sensor fusion status", e),
+        }
+    }
+    if print_esf_status {
+        sinks.add(Box::<esf::EsfStatusPrinter>::default());
+    }
+    if !passive && matches.get_flag("reset-odometer") {
+        if let Err(e) = odometer::reset(&mut device) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if matches.get_flag("events-pin") {
+        info!("Enable UBX-TIM-TM2 message on all serial ports: USB, UART1 and UART2 ...");
+        device
+            .write_all(
+                &CfgMsgAllPortsBuilder::set_rate_for::<TimTm2>([0, 1, 1, 1, 0, 0])
+                    .into_packet_bytes(),
+            )
+            .unwrap_or_else(|e| exit_on("Could not configure ports for UBX-TIM-TM2", e));
+        expect_ack::<CfgMsgAllPorts>(&mut device, "UBX-TIM-TM2 output (UBX-CFG-MSG)", ack_timeout);
+        enabled.push(("TIM-TM2".to_string(), TimTm2::CLASS, TimTm2::ID, messages::SERIAL_PORTS));
+    }
+
+    if let Some(severities) = matches.get_many::<String>("inf") {
+        if !passive {
+            let mask = inf::mask(&severities.cloned().collect::<Vec<_>>());
+            match inf::set(&mut device, mask, &ports) {
+                Ok(Some(output)) => info!("UBX INF output: {}", output.describe()),
+                Ok(None) => warn!("The UBX INF output could not be polled back"),
+                Err(e) => warn!("{}, no INF messages", e),
+            }
+        }
+        sinks.add(Box::new(inf::InfPrinter));
+    }
+
+    let print_timing = matches.get_flag("print-timing");
+    if !passive && print_timing {
+        for name in ["TIM-TM2", "TIM-TP"] {
+            if enabled.iter().any(|(enabled, ..)| enabled == name) {
+                continue;
+            }
+            let message = Message::named(name).unwrap();
+            let rates = Message { rate: 1, ..message.clone() }.rates(&ports);
+            info!("Enable UBX-{} message, rates {:?} ...", name, rates);
+            match set_message_rates(&mut device, &message, rates) {
+                Ok(true) => enabled.push((message.name.clone(), message.class, message.id, rates)),
+                Ok(false) => warn!("Enabling UBX-{} rejected (UBX-ACK-NAK)", name),
+                Err(e) => warn!("Enabling UBX-{} failed: {}", name, e),
+            }
+        }
+    }
+    if print_timing {
+        sinks.add(Box::<timing::TimingPrinter>::default());
+    }
+
+    if matches.get_flag("raw") {
+        info!(
+            "Enable UBX-RXM-RAWX, UBX-RXM-SFRBX and UBX-NAV-PVT messages on all serial ports: \
+USB, UART1 and UART2 ..."
+        );
+        let raw_messages = [
+            (
+                "RXM-RAWX",
+                RxmRawx::CLASS,
+                RxmRawx::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<RxmRawx>([0, 1, 1, 1, 0, 0]),
+            ),
+            (
+                "RXM-SFRBX",
+                RxmSfrbx::CLASS,
+                RxmSfrbx::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<RxmSfrbx>([0, 1, 1, 1, 0, 0]),
+            ),
+            (
+                "NAV-PVT",
+                NavPvt::CLASS,
+                NavPvt::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([0, 1, 1, 1, 0, 0]),
+            ),
+        ];
+        for (name, class, id, builder) in raw_messages {
+            if enabled.iter().any(|(enabled, ..)| enabled == name) {
+                continue;
+            }
+            device
+                .write_all(&builder.into_packet_bytes())
+                .unwrap_or_else(|e| {
+                    exit_on(&format!("Could not configure ports for UBX-{}", name), e)
+                });
+            match device.wait_for_ack::<CfgMsgAllPorts>(ack_timeout) {
+                Ok(()) => enabled.push((name.to_string(), class, id, messages::SERIAL_PORTS)),
+                // only the timing and high precision receivers output raw measurements
+                Err(AckError::ConfigRejected { .. }) if name == "RXM-RAWX" => warn!(
+                    "UBX-RXM-RAWX rejected by the receiver (UBX-ACK-NAK): raw measurements need \
+a receiver outputting them, like the NEO-M8T or ZED-F9P, no raw epochs recorded"
+                ),
+                Err(e) => {
+                    error!("UBX-{} output (UBX-CFG-MSG) not applied: {}", name, e);
+                    std::process::exit(1);
+                },
+            }
+        }
+    }
+
+    if matches.get_flag("high-precision") {
+        info!(
+            "Enable UBX-NAV-HPPOSLLH, UBX-NAV-HPPOSECEF and UBX-NAV-PVT messages on all serial \
+ports: USB, UART1 and UART2 ..."
+        );
+        let hp_messages = [
+            (
+                "NAV-HPPOSLLH",
+                NavHpPosLlh::CLASS,
+                NavHpPosLlh::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<NavHpPosLlh>([0, 1, 1, 1, 0, 0]),
+            ),
+            (
+                "NAV-HPPOSECEF",
+                NavHpPosEcef::CLASS,
+                NavHpPosEcef::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<NavHpPosEcef>([0, 1, 1, 1, 0, 0]),
+            ),
+            (
+                "NAV-PVT",
+                NavPvt::CLASS,
+                NavPvt::ID,
+                CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([0, 1, 1, 1, 0, 0]),
+            ),
+        ];
+        for (name, class, id, builder) in hp_messages {
+            if enabled.iter().any(|(enabled, ..)| enabled == name) {
+                continue;
+            }
+            device
+                .write_all(&builder.into_packet_bytes())
+                .unwrap_or_else(|e| {
+                    exit_on(&format!("Could not configure ports for UBX-{}", name), e)
+                });
+            match device.wait_for_ack::<CfgMsgAllPorts>(ack_timeout) {
+                Ok(()) => enabled.push((name.to_string(), class, id, messages::SERIAL_PORTS)),
+                // standard precision receivers have no high-precision positions
+                Err(AckError::ConfigRejected { .. }) if name != "NAV-PVT" => warn!(
+                    "UBX-{} rejected by the receiver (UBX-ACK-NAK): high-precision positions \
+need a high precision receiver, like the ZED-F9P or NEO-M8P, the positions of UBX-NAV-PVT being \
+output instead",
+                    name
+                ),
+                Err(e) => {
+                    error!("UBX-{} output (UBX-CFG-MSG) not applied: {}", name, e);
+                    std::process::exit(1);
+                },
+            }
+        }
+    }
+
+    if matches.get_flag("imu-raw") {
+        // 8 bits of data, start and stop bits per byte, keeping half of the link
+        // for the other messages
+        let fits = |baud: u32| baud / 10 >= ESF_RAW_BYTES_PER_SECOND * 2;
+        if !fits(baud) {
+            let advised = [115200, 230400, 460800, 921600]
+                .into_iter()
+                .find(|baud| fits(*baud))
+                .unwrap_or(921600);
+            warn!(
+                "warning: UBX-ESF-RAW needs about {} bytes/s, {} baud only carries {} bytes/s: \
+expect data loss on UART links, use {} baud or more",
+                ESF_RAW_BYTES_PER_SECOND,
+                baud,
+                baud / 10,
+                advised
+            );
+        }
+        info!("Enable UBX-ESF-RAW message on all serial ports: USB, UART1 and UART2 ...");
+        device
+            .write_all(
+                &CfgMsgAllPortsBuilder::set_rate_for::<EsfRaw>([0, 1, 1, 1, 0, 0])
+                    .into_packet_bytes(),
+            )
+            .unwrap_or_else(|e| exit_on("Could not configure ports for UBX-ESF-RAW", e));
+        match device.wait_for_ack::<CfgMsgAllPorts>(ack_timeout) {
+            Ok(()) => {},
+            Err(AckError::ConfigRejected { .. }) => {
+                error!("UBX-ESF-RAW rejected by the receiver (UBX-ACK-NAK): {}", esf::NO_FUSION);
+                std::process::exit(1);
+            },
+            Err(e) => {
+                error!("UBX-ESF-RAW output (UBX-CFG-MSG) not applied: {}", e);
+                std::process::exit(1);
+            },
+        }
+        enabled.push(("ESF-RAW".to_string(), EsfRaw::CLASS, EsfRaw::ID, messages::SERIAL_PORTS));
+    }
+
+    if matches.get_flag("cold-start-on-start") {
+        info!("Enable UBX-NAV-STATUS message on all serial ports: USB, UART1 and UART2 ...");
+        device
+            .write_all(
+                &CfgMsgAllPortsBuilder::set_rate_for::<NavStatus>([0, 1, 1, 1, 0, 0])
+                    .into_packet_bytes(),
+            )
+            .unwrap_or_else(|e| exit_on("Could not configure ports for UBX-NAV-STATUS", e));
+        expect_ack::<CfgMsgAllPorts>(
+            &mut device,
+            "UBX-NAV-STATUS output (UBX-CFG-MSG)",
+            ack_timeout,
+        );
+        enabled.push(("NAV-STATUS".to_string(), NavStatus::CLASS, NavStatus::ID, messages::SERIAL_PORTS));
+
+        // GNSS only reset: the configuration is kept and USB is not re-enumerated
+        info!("Cold starting the receiver ...");
+        device
+            .write_all(
+                &CfgRstBuilder {
+                    nav_bbr_mask: NavBbrPredefinedMask::COLD_START.into(),
+                    reset_mode: ResetMode::ControlledSoftwareResetGpsOnly,
+                    reserved1: 0,
+                }
+                .into_packet_bytes(),
+            )
+            .unwrap_or_else(|e| exit_on("Could not send UBX-CFG-RST", e));
+        sinks.add(Box::new(TtffMonitor::new(std::time::Instant::now())));
+    }
+
+    let protocols = matches
+        .get_many::<Protocol>("protocols")
+        .map(|protocols| protocols.copied().collect::<Vec<_>>());
+    let mut requested_mask = None;
+    if let Some(protocols) = &protocols {
+        if (ring_buffer.is_some() || start_on.is_some()) && !protocols.contains(&Protocol::Ubx) {
+            error!("--ring-buffer and --start-on only record UBX frames, --protocols must include ubx");
+            std::process::exit(1);
+        }
+        if matches.get_flag("verify-config") && !protocols.contains(&Protocol::Ubx) {
+            error!("--verify-config polls UBX messages, --protocols must include ubx");
+            std::process::exit(1);
+        }
+        // poll the configuration of the port we are connected to
+        let mut payload = device
+            .poll_payload(0x06, 0x00, &[], ack_timeout)
+            .unwrap_or_else(|e| {
+                error!("Failed to read the port configuration (UBX-CFG-PRT): {}", e);
+                std::process::exit(1);
+            });
+        let Some((previous, mask)) = protocol::set_out_protocols(&mut payload, protocols) else {
+            error!("Unexpected UBX-CFG-PRT of {} bytes", payload.len());
+            std::process::exit(1);
+        };
+        info!(
+            "Set output protocols of port {}: outProtoMask 0x{:04x} -> 0x{:04x} ...",
+            payload[0], previous, mask
+        );
+        requested_mask = Some(mask);
+        device
+            .write_all(&frame::frame(0x06, 0x00, &payload))
+            .unwrap_or_else(|e| exit_on("Could not configure UBX-CFG-PRT", e));
+        // without UBX output, the acknowledgment is not sent anymore
+        if protocols.contains(&Protocol::Ubx) {
+            expect_ack::<CfgPrtUart>(&mut device, "UBX-CFG-PRT", ack_timeout);
+        }
+    }
+
+    if configure.is_some_and(|sub_matches| sub_matches.get_flag("save")) {
+        persist::apply(&mut device, persist::Action::Save).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        if !dry_run {
+            info!("{}", persist::Action::Save.outcome());
+        }
+    }
 
-    // Parse cli for configuring specific uBlox UART port
-    if let Some(("configure", sub_matches)) = matches.subcommand() {
-        let (port_id, port_name) = match sub_matches.get_one::<String>("port").map(|s| s.as_str()) {
-            Some(x) if x == "usb" => (Some(UartPortId::Usb), x),
-            Some(x) if x == "uart1" => (Some(UartPortId::Uart1), x),
-            Some(x) if x == "uart2" => (Some(UartPortId::Uart2), x),
-            _ => (None, ""),
+    if matches.get_flag("verify-config") && dry_run {
+        info!("The configuration is not verified by a dry run, nothing being applied");
+    } else if matches.get_flag("verify-config") {
+        info!("Verifying the configuration ...");
+        let mut verification = Verification::default();
+        let port = device.poll_payload(0x06, 0x00, &[], ack_timeout).ok();
+        verification.out_protocols(requested_mask, port.as_deref());
+        if let Some(written) = &configured_port {
+            let port = device.poll_payload(0x06, 0x00, &written[..1], ack_timeout).ok();
+            verification.port_settings(written, port.as_deref());
+        }
+        for (name, class, id, requested) in &enabled {
+            let rates = device.poll_payload(0x06, 0x01, &[*class, *id], ack_timeout).ok();
+            verification.message_rates(name, *requested, rates.as_deref());
+        }
+        let rate = device.poll_payload(0x06, 0x08, &[], ack_timeout).ok();
+        verification.rate(rate.as_deref());
+        verification.print();
+        if verification.failures() > 0 {
+            warn!(
+                "warning: {} settings not applied as requested",
+                verification.failures()
+            );
+        }
+    }
+
+    let describe = matches.get_flag("metadata");
+    if receiver.is_none() && (matches.get_flag("embed-metadata") || describe || !passive) {
+        info!("Polling the receiver identification ...");
+        receiver = Some(Receiver::poll(&mut device, ack_timeout));
+    }
+    if let (Some(receiver), false) = (&receiver, dry_run) {
+        for line in receiver.describe() {
+            info!("receiver {}", line);
+        }
+    }
+    let metadata = matches
+        .get_flag("embed-metadata")
+        .then(|| Metadata::new(receiver.as_ref().unwrap()).frame());
+    let timestamps = matches.get_flag("timestamps");
+    if let (Some(writer), Some(block), false) = (&mut writer, &metadata, timestamps) {
+        writer.write_all(block).unwrap_or_else(|e| exit_on("failed to write metadata block", e));
+    }
+
+    // The UBX-MON-VER answer of the identification poll is recorded first
+    if let (Some(frame), false) = (receiver.as_ref().and_then(Receiver::version_frame), passive) {
+        device.replay(&frame);
+    }
+    match device.written() {
+        0 => info!("Nothing written to the receiver"),
+        written => info!("{} messages written to the receiver", written),
+    }
+    if dry_run {
+        info!(
+            "Dry run: {} messages not sent, nothing recorded",
+            device.skipped()
+        );
+        return;
+    }
+
+    // Start streaming
+    info!("uBlox device opened, streaming..");
+    let limits = Limits {
+        packets: matches.get_one::<u64>("max-packets").copied(),
+        bytes: matches.get_one::<u64>("max-bytes").copied(),
+    };
+    let stats_interval = (!matches.get_flag("quiet"))
+        .then(|| *matches.get_one::<Duration>("stats-interval").unwrap());
+    let mut session = Session::new(
+        matches.get_one::<Duration>("duration").copied(),
+        limits,
+        stats_interval,
+    );
+    if let Some(timeout) = matches.get_one::<Duration>("idle-timeout") {
+        session.watch_idle(*timeout, matches.get_flag("exit-on-idle"));
+    }
+    if let Some(after) = matches.get_one::<Duration>("stop-after-fix") {
+        session.stop_after_fix(*after, matches.get_one::<Duration>("fix-timeout").copied());
+    }
+    if matches.get_flag("monitor-antenna") {
+        session.watch_antenna(matches.get_flag("abort-on-antenna-fault"));
+    }
+    if matches.get_flag("monitor-geofence") {
+        session.watch_geofence();
+    }
+    if matches.get_flag("monitor-jamming") {
+        session.watch_jamming();
+    }
+    if matches.get_flag("raw") {
+        session.count_raw_epochs();
+    }
+    if describe {
+        let serial = sidecar::Serial {
+            port: port.to_string(),
+            baud,
+            data_bits: matches.get_one::<String>("data-bits").unwrap().clone(),
+            parity: matches
+                .get_one::<String>("parity")
+                .map_or("none".to_string(), String::clone),
+            stop_bits: matches
+                .get_one::<String>("stop-bits")
+                .map_or("1".to_string(), String::clone),
+            flow_control: matches.get_one::<String>("flow-control").unwrap().clone(),
         };
+        let messages = enabled.clone();
+        session.describe(Sidecar::new(&path, &serial, receiver.as_ref().unwrap(), messages));
+    }
+    let reconnection = Reconnection::new(
+        endpoint,
+        matches
+            .get_flag("reconnect")
+            .then(|| *matches.get_one::<Duration>("reconnect-delay").unwrap()),
+        matches.get_one::<u64>("reconnect-max").map(|max| *max as usize),
+        enabled
+            .iter()
+            .map(|(_, class, id, rates)| (*class, *id, *rates))
+            .collect(),
+    );
+
+    if let Some(condition) = &mut start_on {
+        let mut lead_up = ring_buffer.map(FrameRing::new);
+        info!("waiting for start condition..");
+        let mut scanner = FrameScanner::default();
+        while !session.is_over() {
+            session.tick();
+            enable_rtcm_after_survey(&mut device, &mut session, rtcm_after_survey, &ports);
+            match device.read_when_ready(&mut buf, IDLE_WAIT) {
+                Ok(size) => {
+                    let size = session.feed(&buf[..size]);
+                    scanner.push(&buf[..size]);
+                    sinks.feed(&buf[..size]);
+                },
+                Err(e) => reconnection.recover(&mut device, &e, &mut session),
+            }
+            while let Some(frame) = scanner.next_frame() {
+                if let Some(writer) = &mut writer {
+                    if writer.write_all(&frame).is_err() {
+                        warn!("failed dump into file");
+                    }
+                    continue;
+                }
+                let Some(epoch) = condition.check(&frame) else {
+                    if let Some(ring) = &mut lead_up {
+                        ring.push(std::time::Instant::now(), frame);
+                    }
+                    continue;
+                };
+                info!("{}", epoch);
+                let mut output = open_output(&path);
+                if let Some(block) = &metadata {
+                    output
+                        .write_all(block)
+                        .unwrap_or_else(|e| exit_on("failed to write metadata block", e));
+                }
+                if let Some(ring) = &mut lead_up {
+                    let bytes = ring
+                        .write_to(&mut output)
+                        .unwrap_or_else(|e| exit_on("failed to write lead-up", e));
+                    info!("recording into \"{}\", with {} bytes of lead-up", path, bytes);
+                } else {
+                    info!("recording into \"{}\"", path);
+                }
+                if output.write_all(&frame).is_err() {
+                    warn!("failed dump into file");
+                }
+                writer = Some(output);
+            }
+        }
+        info!("stopping, completing the outputs..");
+        if let Some(writer) = &mut writer {
+            if let Err(e) = writer.finalize() {
+                warn!("failed to complete \"{}\": {}", path, e);
+            }
+        }
+        sinks.finish();
+        device.report();
+        session.summary(writer.is_some().then_some(path.as_str()));
+        if session.failed() {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-        let baud = sub_matches.get_one::<u32>("baud").cloned().unwrap_or(9600);
+    if let Some(window) = ring_buffer {
+        let post_trigger = *matches.get_one::<Duration>("post-trigger").unwrap();
+        let trigger = Arc::new(AtomicBool::new(false));
+        register_trigger_signal(
+            matches.get_one::<String>("dump-signal").unwrap(),
+            trigger.clone(),
+        );
+        let key_trigger = trigger.clone();
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lines() {
+                key_trigger.store(true, Ordering::Relaxed);
+            }
+        });
+        info!(
+            "buffering the last {:?} of data, press Enter or send SIG{} to capture",
+            window,
+            matches.get_one::<String>("dump-signal").unwrap()
+        );
 
-        let stop_bits = match sub_matches
-            .get_one::<String>("stop-bits")
-            .map(|s| s.as_str())
-        {
-            Some("2") => SerialStopBits::Two,
-            _ => SerialStopBits::One,
+        let force = matches.get_flag("force");
+        let mut recorder =
+            RingRecorder::new(window, post_trigger, &path, metadata, compression, force);
+        let mut scanner = FrameScanner::default();
+        while !session.is_over() {
+            session.tick();
+            enable_rtcm_after_survey(&mut device, &mut session, rtcm_after_survey, &ports);
+            match device.read_when_ready(&mut buf, RING_IDLE_WAIT) {
+                Ok(size) => {
+                    let size = session.feed(&buf[..size]);
+                    scanner.push(&buf[..size]);
+                    sinks.feed(&buf[..size]);
+                },
+                Err(e) => reconnection.recover(&mut device, &e, &mut session),
+            }
+            if trigger.swap(false, Ordering::Relaxed) {
+                recorder.trigger().unwrap_or_else(|e| exit_on("failed to start capture", e));
+            }
+            while let Some(frame) = scanner.next_frame() {
+                recorder.push(frame).unwrap_or_else(|e| exit_on("failed to record frame", e));
+            }
+            recorder
+                .update(std::time::Instant::now())
+                .unwrap_or_else(|e| exit_on("failed to complete capture", e));
+        }
+        info!("stopping, completing the outputs..");
+        if let Err(e) = recorder.finish() {
+            warn!("failed to complete the capture: {}", e);
+        }
+        sinks.finish();
+        device.report();
+        session.summary(None);
+        if session.failed() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if split_by_class {
+        let options = rotation::Options {
+            fifo_buffer,
+            sync_interval,
+            buffer: output_buffer,
+            metadata,
+            compression,
+            append,
+            force: matches.get_flag("force"),
+        };
+        let align = *matches.get_one::<Align>("rotate-align").unwrap();
+        let open: split::Opener = Box::new(move |path| -> Box<dyn Sink> {
+            if let Some(interval) = rotate_interval {
+                return Box::new(Rotation::new(path, interval, align, options.clone()));
+            }
+            let mut writer = BufferedWriter::open(
+                path,
+                options.fifo_buffer,
+                options.sync_interval,
+                options.buffer,
+                options.compression,
+                options.append,
+            );
+            if let Some(block) = &options.metadata {
+                writer
+                    .write_all(block)
+                    .unwrap_or_else(|e| exit_on("failed to write metadata block", e));
+            }
+            Box::new(writer)
+        });
+        let split = ClassSplit::new(&path, open);
+        match protocols {
+            Some(protocols) => sinks.add(Box::new(ProtocolFilter::new(split, &protocols))),
+            None => sinks.add(Box::new(split)),
+        }
+    } else if let Some(interval) = rotate_interval {
+        let options = rotation::Options {
+            fifo_buffer,
+            sync_interval,
+            buffer: output_buffer,
+            metadata,
+            compression,
+            append,
+            force: matches.get_flag("force"),
         };
+        let align = *matches.get_one::<Align>("rotate-align").unwrap();
+        let rotation = Rotation::new(&path, interval, align, options);
+        match protocols {
+            Some(protocols) => sinks.add(Box::new(ProtocolFilter::new(rotation, &protocols))),
+            None => sinks.add(Box::new(rotation)),
+        }
+    } else if timestamps {
+        let timestamped = Timestamped::new(writer.unwrap(), metadata.as_deref(), resumed)
+            .unwrap_or_else(|e| exit_on("failed to start the timestamped recording", e));
+        match protocols {
+            Some(protocols) => sinks.add(Box::new(ProtocolFilter::new(timestamped, &protocols))),
+            None => sinks.add(Box::new(timestamped)),
+        }
+    } else {
+        match protocols {
+            Some(protocols) => sinks.add(Box::new(ProtocolFilter::new(writer.unwrap(), &protocols))),
+            None => sinks.add(Box::new(writer.unwrap())),
+        }
+    }
+    #[cfg(all(feature = "tokio", unix))]
+    runtime::record(
+        &mut device,
+        &mut session,
+        &mut sinks,
+        &reconnection,
+        runtime::Tasks::new(&matches),
+        |device, session| enable_rtcm_after_survey(device, session, rtcm_after_survey, &ports),
+    );
+    while !session.is_over() {
+        session.tick();
+        enable_rtcm_after_survey(&mut device, &mut session, rtcm_after_survey, &ports);
+        match device.read_when_ready(&mut buf, IDLE_WAIT) {
+            Ok(size) => {
+                let size = session.feed(&buf[..size]);
+                sinks.feed(&buf[..size]);
+            },
+            Err(e) => reconnection.recover(&mut device, &e, &mut session),
+        }
+    }
+    info!("stopping, completing the outputs..");
+    sinks.finish();
+    device.report();
+    session.summary((rotate_interval.is_none() && !split_by_class).then_some(path.as_str()));
+    if session.failed() {
+        std::process::exit(1);
+    }
+}
 
-        let data_bits = match sub_matches
-            .get_one::<String>("data-bits")
-            .map(|s| s.as_str())
-        {
-            Some("7") => SerialDataBits::Seven,
-            Some("8") => SerialDataBits::Eight,
-            _ => {
-                println!("Number of DataBits supported by uBlox is either 7 or 8");
+/// Parity of the host serial port, none unless given
+fn serial_parity(name: Option<&str>) -> SerialParity {
+    match name {
+        Some("odd") => SerialParity::Odd,
+        Some("even") => SerialParity::Even,
+        _ => SerialParity::None,
+    }
+}
+
+/// Flow control of the host serial port, none unless given
+fn serial_flow_control(name: Option<&str>) -> SerialFlowControl {
+    match name {
+        Some("hardware") => SerialFlowControl::Hardware,
+        Some("software") => SerialFlowControl::Software,
+        _ => SerialFlowControl::None,
+    }
+}
+
+/// Opens the serial port, tuned for low latency when the platform allows
+#[cfg(unix)]
+fn open_port(
+    builder: serialport::SerialPortBuilder,
+    low_latency: bool,
+) -> serialport::Result<Device> {
+    use std::os::unix::io::AsRawFd;
+    let port = builder.open_native()?;
+    let fd = port.as_raw_fd();
+    #[cfg(target_os = "linux")]
+    if low_latency {
+        match latency::set_low_latency(fd) {
+            Ok(()) => info!("Serial driver low latency flag set"),
+            Err(e) => warn!("Serial driver low latency flag skipped: {}", e),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    if low_latency {
+        info!("Serial driver low latency flag skipped: not available on this platform");
+    }
+    let mut device = Device::new(Port::Serial(Box::new(port)));
+    device.fd = Some(fd);
+    Ok(device)
+}
+
+/// Opens the serial port, tuned for low latency when the platform allows
+#[cfg(not(unix))]
+fn open_port(
+    builder: serialport::SerialPortBuilder,
+    low_latency: bool,
+) -> serialport::Result<Device> {
+    if low_latency {
+        info!("Serial driver low latency flag skipped: not available on this platform");
+    }
+    Ok(Device::new(Port::Serial(builder.open()?)))
+}
+
+/// Reads the first seconds of data, warning when they look like a wrong baud rate
+fn check_line(device: &mut Device, baud: u32, strict: bool) {
+    let mut check = LineCheck::default();
+    let mut buf = [0; 1024];
+    let deadline = std::time::Instant::now() + LINE_CHECK_DURATION;
+    while std::time::Instant::now() < deadline && !check.healthy() {
+        match device.read_port(&mut buf) {
+            Ok(nbytes) => check.push(&buf[..nbytes]),
+            Err(e) => {
+                error!("Failed to read the port: {}", e);
                 std::process::exit(1);
             },
-        };
+        }
+    }
+    match check.verdict() {
+        Verdict::Healthy | Verdict::Undecided => {},
+        Verdict::Silent => warn!(
+            "No data received in the first {} s: the receiver may have its outputs disabled, \
+or be connected to another port",
+            LINE_CHECK_DURATION.as_secs()
+        ),
+        Verdict::Garbage => {
+            warn!("************************************************************");
+            warn!("Data received at {} baud does not parse as UBX, NMEA or RTCM3:", baud);
+            for evidence in check.evidence() {
+                warn!("  - {}", evidence);
+            }
+            let others = COMMON_BAUD_RATES
+                .iter()
+                .filter(|rate| **rate != baud)
+                .map(|rate| rate.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            warn!("The baud rate is most likely wrong, try --baud with one of {}", others);
+            warn!("************************************************************");
+            if strict {
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Sets the output rates of a message (UBX-CFG-MSG), false when the receiver rejected them
+fn set_message_rates(device: &mut Device, message: &Message, rates: [u8; 6]) -> std::io::Result<bool> {
+    let mut payload = vec![message.class, message.id];
+    payload.extend_from_slice(&rates);
+    device.write_all(&frame::frame(0x06, 0x01, &payload))?;
+    let timeout = device.ack_timeout();
+    device.wait_for_ack_of(0x06, 0x01, timeout)
+}
+
+/// Enables a satellite message, NAV-SAT or NAV-SIG, falling back to the NAV-SVINFO of the
+/// firmware older than them when rejected, returning the message enabled
+fn enable_satellites(
+    device: &mut Device,
+    message: &Message,
+    rates: [u8; 6],
+) -> std::io::Result<Option<Message>> {
+    if set_message_rates(device, message, rates)? {
+        return Ok(Some(message.clone()));
+    }
+    warn!(
+        "Enabling {} rejected by the receiver (UBX-ACK-NAK), enabling the UBX-NAV-SVINFO \
+satellite information of older firmware instead",
+        message.name
+    );
+    let svinfo = Message {
+        rate: message.rate,
+        ..Message::named("NAV-SVINFO").unwrap()
+    };
+    Ok(set_message_rates(device, &svinfo, rates)?.then_some(svinfo))
+}
 
-        let parity = match sub_matches.get_one::<String>("parity").map(|s| s.as_str()) {
-            Some("odd") => SerialParity::Even,
-            Some("even") => SerialParity::Odd,
-            _ => SerialParity::None,
+/// Enables the RTCM3 base messages on `ports` once the survey-in completed, when `enable` is
+/// set. Not waiting for the acknowledgments, so that no data is lost, they are recorded
+fn enable_rtcm_after_survey(
+    device: &mut Device,
+    session: &mut Session,
+    enable: bool,
+    ports: &[bool; 5],
+) {
+    if !session.survey_completed() || !enable {
+        return;
+    }
+    info!("Survey-in completed, enabling the RTCM3 base messages ...");
+    let mut enabled = Vec::new();
+    for name in base::RTCM_MESSAGES {
+        let message = Message {
+            rate: 1,
+            ..Message::named(name).unwrap()
         };
-        let inproto = InProtoMask::UBLOX;
-        let outproto = OutProtoMask::UBLOX;
+        let mut payload = vec![message.class, message.id];
+        payload.extend_from_slice(&message.rates(ports));
+        if let Err(e) = device.write_all(&frame::frame(0x06, 0x01, &payload)) {
+            warn!("Enabling {} failed: {}", name, e);
+            continue;
+        }
+        enabled.push(name);
+    }
+    session.event(&format!("RTCM3 base messages enabled: {}", enabled.join(", ")));
+}
 
-        if let Some(port_id) = port_id {
-            println!("Configuring '{}' port ...", port_name.to_uppercase());
-            device
-                .write_all(
-                    &CfgPrtUartBuilder {
-                        portid: port_id,
-                        reserved0: 0,
-                        tx_ready: 0,
-                        mode: UartMode::new(
-                            ublox_databits(data_bits),
-                            ublox_parity(parity),
-                            ublox_stopbits(stop_bits),
-                        ),
-                        baud_rate: baud,
-                        in_proto_mask: inproto,
-                        out_proto_mask: outproto,
-                        flags: 0,
-                        reserved5: 0,
-                    }
-                    .into_packet_bytes(),
-                )
-                .expect("Could not configure UBX-CFG-PRT-UART");
-            device
-                .wait_for_ack::<CfgPrtUart>()
-                .expect("Could not acknowledge UBX-CFG-PRT-UART msg");
+/// Fails when the output is an existing file, only overwritten with --force. Named pipes
+/// and the standard output are written to
+fn check_overwrite(path: &str) -> std::io::Result<()> {
+    if path != "-" && std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("\"{}\" exists, use --force to overwrite it", path),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the output can be written, or created in its directory, without creating it
+fn check_writable(path: &str) -> std::io::Result<()> {
+    let path = std::path::Path::new(path);
+    let target = if path.exists() {
+        path
+    } else {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
         }
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let target = std::ffi::CString::new(target.as_os_str().as_bytes())?;
+        // SAFETY: the path is a NUL terminated string
+        if unsafe { libc::access(target.as_ptr(), libc::W_OK) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        if std::fs::metadata(target)?.permissions().readonly() {
+            return Err(std::io::ErrorKind::PermissionDenied.into());
+        }
+        Ok(())
     }
+}
 
-    // Enable the NavPvt packet
-    // By setting 1 in the array below, we enable the NavPvt message for Uart1, Uart2 and USB
-    // The other positions are for I2C, SPI, etc. Consult your device manual.
-    println!("Enable UBX-NAV-PVT message on all serial ports: USB, UART1 and UART2 ...");
-    device
-        .write_all(
-            &CfgMsgAllPortsBuilder::set_rate_for::<NavPvt>([0, 1, 1, 1, 0, 0]).into_packet_bytes(),
-        )
-        .expect("Could not configure ports for UBX-NAV-PVT");
-    device
-        .wait_for_ack::<CfgMsgAllPorts>()
-        .expect("Could not acknowledge UBX-CFG-PRT-UART msg");
+#[cfg(unix)]
+fn create_fifo(path: &str) {
+    fifo::create(path).unwrap_or_else(|e| {
+        error!("Failed to create named pipe \"{}\": {}", path, e);
+        std::process::exit(1);
+    });
+}
 
-    // Send a packet request for the MonVer packet
-    device
-        .write_all(&UbxPacketRequest::request_for::<MonVer>().into_packet_bytes())
-        .expect("Unable to write request/poll for UBX-MON-VER message");
+#[cfg(not(unix))]
+fn create_fifo(_path: &str) {
+    error!("Named pipes are not supported on this platform");
+    std::process::exit(1);
+}
 
-    // Start streaming
-    println!("uBlox device opened, streaming..");
-    
-    loop {
-        if let Ok(size) = device.read_port(&mut buf) {
-            if size > 0 {
-                if writer.write_all(&buf).is_err() {
-                    println!("failed dump into file");
-                }
-            }
+fn available_ports() -> Vec<serialport::SerialPortInfo> {
+    serialport::available_ports().unwrap_or_else(|e| {
+        error!("Failed to list serial ports: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Lists the serial ports, probing them at `baud` (detected when None) when requested
+fn list_ports(probe: bool, format: &str, baud: Option<u32>) {
+    let mut ports = discovery::listing(available_ports());
+    if probe {
+        for port in &mut ports {
+            info!("Probing \"{}\" ...", port.info.port_name);
+            port.probe = Some(probe_port(&port.info.port_name, baud));
+        }
+    }
+    if format == "json" {
+        println!("{}", discovery::json(&ports));
+    } else if ports.is_empty() {
+        println!("no serial port found");
+    } else {
+        println!("{}", discovery::table(&ports));
+    }
+}
+
+/// Polls the version of the receiver on the port
+fn probe_port(name: &str, baud: Option<u32>) -> Result<Receiver, String> {
+    let builder = serialport::new(name, baud.unwrap_or(COMMON_BAUD_RATES[0]))
+        .timeout(Duration::from_millis(10));
+    let mut device = open_port(builder, false).map_err(|e| e.to_string())?;
+    if baud.is_none() {
+        match device.detect_baud(&COMMON_BAUD_RATES, BAUD_DETECTION_TIMEOUT) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Err("no UBX frame at any common baud rate".to_string()),
+            Err(e) => return Err(e.to_string()),
         }
     }
+    device
+        .poll_payload(0x0a, 0x04, &[], PROBE_TIMEOUT)
+        .map(Receiver::from_version)
+        .map_err(|e| e.to_string())
 }
 
-fn ublox_stopbits(s: SerialStopBits) -> StopBits {
-    // Seriaport crate doesn't support the other StopBits option of uBlox
+/// Parses a baud rate, None standing for "auto"
+fn parse_baud(s: &str) -> Result<Option<u32>, String> {
     match s {
-        SerialStopBits::One => StopBits::One,
-        SerialStopBits::Two => StopBits::Two,
+        "auto" => Ok(None),
+        _ => s
+            .parse::<u32>()
+            .map(Some)
+            .map_err(|_| format!("invalid baud rate \"{}\", expecting a number or auto", s)),
     }
 }
 
-fn ublox_databits(d: SerialDataBits) -> DataBits {
-    match d {
-        SerialDataBits::Seven => DataBits::Seven,
-        SerialDataBits::Eight => DataBits::Eight,
-        _ => {
-            println!("uBlox only supports Seven or Eight data bits");
-            DataBits::Eight
-        },
+/// Parses durations like "500ms", "60s", "5m", "1h" or "1h30m", seconds by default
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let mut rest = s;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (value, tail) = match rest.find(|c: char| !c.is_ascii_digit() && c != '.') {
+            Some(i) => rest.split_at(i),
+            None if rest.len() == s.len() => (rest, "s"),
+            None => return Err(format!("missing duration unit at the end of \"{}\"", s)),
+        };
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| format!("invalid duration \"{}\"", s))?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        seconds += match unit {
+            "ms" => value / 1000.0,
+            "s" => value,
+            "m" => value * 60.0,
+            "h" => value * 3600.0,
+            _ => return Err(format!("invalid duration unit in \"{}\", expecting ms, s, m or h", s)),
+        };
+        rest = tail;
+    }
+    if s.is_empty() {
+        return Err("empty duration".to_string());
     }
+    Ok(Duration::from_secs_f64(seconds))
 }
 
-fn ublox_parity(v: SerialParity) -> Parity {
-    match v {
-        SerialParity::Even => Parity::Even,
-        SerialParity::Odd => Parity::Odd,
-        SerialParity::None => Parity::None,
+#[cfg(unix)]
+fn register_trigger_signal(name: &str, trigger: Arc<AtomicBool>) {
+    let signal = match name {
+        "USR2" => signal_hook::consts::SIGUSR2,
+        _ => signal_hook::consts::SIGUSR1,
+    };
+    signal_hook::flag::register(signal, trigger)
+        .unwrap_or_else(|e| exit_on(&format!("failed to register SIG{} handler", name), e));
+}
+
+#[cfg(not(unix))]
+fn register_trigger_signal(name: &str, _trigger: Arc<AtomicBool>) {
+    warn!("SIG{} is not supported on this platform, press Enter to capture", name);
+}
+
+/// Failure of [Device::wait_for_ack]
+#[derive(Debug)]
+enum AckError {
+    /// UBX-ACK-NAK of the message
+    ConfigRejected { class: u8, id: u8 },
+    /// Neither acknowledged nor rejected in time
+    Timeout { class: u8, id: u8, timeout: Duration },
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for AckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ConfigRejected { class, id } => write!(
+                f,
+                "rejected by the receiver (UBX-ACK-NAK of class 0x{:02x} id 0x{:02x}): wrong \
+port, unsupported message or invalid field",
+                class, id
+            ),
+            Self::Timeout { class, id, timeout } => write!(
+                f,
+                "class 0x{:02x} id 0x{:02x} not acknowledged within {:?}: check the baud rate, \
+and that the port outputs UBX",
+                class, id, timeout
+            ),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for AckError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
     }
 }
 
+/// Waits up to `timeout` for the acknowledgment of the `name` configuration message,
+/// exiting on failure
+fn expect_ack<T: UbxPacketMeta>(device: &mut Device, name: &str, timeout: Duration) {
+    if let Err(e) = device.wait_for_ack::<T>(timeout) {
+        error!("{} not applied: {}", name, e);
+        std::process::exit(1);
+    }
+}
+
+/// Prints the error of a failed write to the receiver or the outputs and exits, as no
+/// configuration nor recording can go on without it
+fn exit_on(what: &str, e: impl std::fmt::Display) -> ! {
+    error!("{}: {}", what, e);
+    std::process::exit(1);
+}
+
 struct Device {
-    port: Box<dyn serialport::SerialPort>,
+    port: Port,
     parser: Parser<Vec<u8>>,
+    /// Number of writes to the receiver
+    written: usize,
+    /// Configuration printed rather than written, polls being sent (`--dry-run`)
+    dry_run: bool,
+    /// Messages not written by the dry run
+    skipped: usize,
+    /// Longest wait for an acknowledgment or a poll response (`--ack-timeout`)
+    ack_timeout: Duration,
+    /// Descriptor of the port, polled for data
+    #[cfg(unix)]
+    fd: Option<std::os::unix::io::RawFd>,
+    /// Received messages consumed by polls, read again ahead of the port
+    replayed: Vec<u8>,
 }
 
 impl Device {
-    pub fn new(port: Box<dyn serialport::SerialPort>) -> Device {
+    pub fn new(port: Port) -> Device {
         let parser = Parser::default();
-        Device { port, parser }
+        Device {
+            port,
+            parser,
+            written: 0,
+            dry_run: false,
+            skipped: 0,
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            #[cfg(unix)]
+            fd: None,
+            replayed: vec![],
+        }
+    }
+
+    /// Takes the link of the device reopened after a failure, keeping the settings and
+    /// counters of this one
+    pub fn reconnect(&mut self, reopened: Device) {
+        *self = Device {
+            written: self.written,
+            dry_run: self.dry_run,
+            skipped: self.skipped,
+            ack_timeout: self.ack_timeout,
+            ..reopened
+        };
+    }
+
+    /// Longest wait for an acknowledgment or a poll response
+    pub fn ack_timeout(&self) -> Duration {
+        self.ack_timeout
     }
 
+    /// Writes a message changing the receiver, printed rather than sent by dry runs
     pub fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.write(data, false)
+    }
+
+    /// Writes a poll, which changes nothing and is sent by dry runs too
+    pub fn write_poll(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.write(data, true)
+    }
+
+    fn write(&mut self, data: &[u8], poll: bool) -> std::io::Result<()> {
+        if self.dry_run && !poll {
+            self.skipped += 1;
+            if data.starts_with(&[0xb5, 0x62]) && data.len() >= 8 {
+                println!("would send {}", frame::describe(data));
+            } else {
+                println!("would send {} bytes", data.len());
+            }
+            return Ok(());
+        }
+        self.written += 1;
+        if data.starts_with(&[0xb5, 0x62]) && data.len() >= 8 {
+            debug!(
+                "sending class 0x{:02x} id 0x{:02x}, {} bytes payload",
+                data[2],
+                data[3],
+                data.len() - 8
+            );
+        } else {
+            debug!("sending {} bytes", data.len());
+        }
         self.port.write_all(data)
     }
 
+    /// Number of messages written to the receiver so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Number of messages the dry run did not write
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Prints the statistics of the link, when it has some
+    pub fn report(&self) {
+        if let Some(report) = self.port.report() {
+            info!("{}", report);
+        }
+    }
+
+    /// Reads the port once, up to its 10 ms read timeout, passing the packets parsed to `cb`.
+    /// Reading once lets the callers bound their wait, however chatty the port
     pub fn update<T: FnMut(PacketRef)>(&mut self, mut cb: T) -> std::io::Result<()> {
+        const MAX_PAYLOAD_LEN: usize = 1240;
+        let mut local_buf = [0; MAX_PAYLOAD_LEN];
+        let nbytes = self.read_port(&mut local_buf)?;
+
+        // parser.consume adds the buffer to its internal buffer, and
+        // returns an iterator-like object we can use to process the packets
+        let mut it = self.parser.consume(&local_buf[..nbytes]);
         loop {
-            const MAX_PAYLOAD_LEN: usize = 1240;
-            let mut local_buf = [0; MAX_PAYLOAD_LEN];
-            let nbytes = self.read_port(&mut local_buf)?;
-            if nbytes == 0 {
-                break;
+            match it.next() {
+                Some(Ok(packet)) => {
+                    cb(packet);
+                },
+                Some(Err(_)) => {
+                    // Received a malformed packet, ignore it
+                },
+                None => {
+                    // We've eaten all the packets we have
+                    break;
+                },
             }
+        }
+        Ok(())
+    }
 
-            // parser.consume adds the buffer to its internal buffer, and
-            // returns an iterator-like object we can use to process the packets
-            let mut it = self.parser.consume(&local_buf[..nbytes]);
-            loop {
-                match it.next() {
-                    Some(Ok(packet)) => {
-                        cb(packet);
-                    },
-                    Some(Err(_)) => {
-                        // Received a malformed packet, ignore it
-                    },
-                    None => {
-                        // We've eaten all the packets we have
-                        break;
-                    },
-                }
+    /// Waits up to `timeout` for the acknowledgment of a message, the other packets received
+    /// meanwhile being consumed. A UBX-ACK-NAK is an [AckError::ConfigRejected]
+    pub fn wait_for_ack<T: UbxPacketMeta>(&mut self, timeout: Duration) -> Result<(), AckError> {
+        // nothing was sent to acknowledge
+        if self.dry_run {
+            return Ok(());
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let mut acknowledged = None;
+        while acknowledged.is_none() {
+            if std::time::Instant::now() >= deadline {
+                return Err(AckError::Timeout {
+                    class: T::CLASS,
+                    id: T::ID,
+                    timeout,
+                });
             }
+            self.update(|packet| match packet {
+                PacketRef::AckAck(ack) if ack.class() == T::CLASS && ack.msg_id() == T::ID => {
+                    debug!("UBX-ACK-ACK of class 0x{:02x} id 0x{:02x}", T::CLASS, T::ID);
+                    acknowledged = Some(true);
+                },
+                PacketRef::AckNak(nak) if nak.class() == T::CLASS && nak.msg_id() == T::ID => {
+                    debug!("UBX-ACK-NAK of class 0x{:02x} id 0x{:02x}", T::CLASS, T::ID);
+                    acknowledged = Some(false);
+                },
+                _ => {},
+            })?;
+        }
+        if acknowledged == Some(false) {
+            return Err(AckError::ConfigRejected {
+                class: T::CLASS,
+                id: T::ID,
+            });
         }
         Ok(())
     }
 
-    pub fn wait_for_ack<T: UbxPacketMeta>(&mut self) -> std::io::Result<()> {
-        let mut found_packet = false;
-        while !found_packet {
-            self.update(|packet| {
-                if let PacketRef::AckAck(ack) = packet {
-                    if ack.class() == T::CLASS && ack.msg_id() == T::ID {
-                        found_packet = true;
+    /// Waits for the acknowledgment of a message, false when the receiver rejected it
+    pub fn wait_for_ack_of(&mut self, class: u8, id: u8, timeout: Duration) -> std::io::Result<bool> {
+        if self.dry_run {
+            return Ok(true);
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let mut acknowledged = None;
+        while acknowledged.is_none() {
+            if std::time::Instant::now() >= deadline {
+                warn!(
+                    "no acknowledgment of class 0x{:02x} id 0x{:02x} within {:?}",
+                    class, id, timeout
+                );
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            self.update(|packet| match packet {
+                PacketRef::AckAck(ack) if ack.class() == class && ack.msg_id() == id => {
+                    debug!("UBX-ACK-ACK of class 0x{:02x} id 0x{:02x}", class, id);
+                    acknowledged = Some(true);
+                },
+                PacketRef::AckNak(nak) if nak.class() == class && nak.msg_id() == id => {
+                    debug!("UBX-ACK-NAK of class 0x{:02x} id 0x{:02x}", class, id);
+                    acknowledged = Some(false);
+                },
+                _ => {},
+            })?;
+        }
+        Ok(acknowledged.unwrap_or_default())
+    }
+
+    /// Polls a message, returning the payload of the response, starting like the `request`
+    /// payload. Unlike [Self::update], the raw payload is returned whatever the message content
+    pub fn poll_payload(
+        &mut self,
+        class: u8,
+        id: u8,
+        request: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Vec<u8>> {
+        self.write(&frame::frame(class, id, request), true)?;
+        self.response(class, id, request, timeout)
+    }
+
+    /// Waits up to `timeout` for a message of given class and id whose payload
+    /// starts with `prefix`, telling apart the responses of messages used both
+    /// ways. An UBX-ACK-NAK of the class and id is an error
+    pub fn response(
+        &mut self,
+        class: u8,
+        id: u8,
+        prefix: &[u8],
+        timeout: Duration,
+    ) -> std::io::Result<Vec<u8>> {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut scanner = FrameScanner::default();
+        let mut local_buf = [0; 1024];
+        while std::time::Instant::now() < deadline {
+            let nbytes = self.read_port(&mut local_buf)?;
+            scanner.push(&local_buf[..nbytes]);
+            while let Some(frame) = scanner.next_frame() {
+                let payload = &frame[6..frame.len() - 2];
+                if frame[2] == class && frame[3] == id && payload.len() > prefix.len() {
+                    if payload.starts_with(prefix) {
+                        return Ok(payload.to_vec());
                     }
+                } else if frame[2..4] == [0x05, 0x00] && payload == [class, id] {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "rejected (UBX-ACK-NAK)",
+                    ));
                 }
-            })?;
+            }
         }
-        Ok(())
+        Err(std::io::ErrorKind::TimedOut.into())
+    }
+
+    /// Finds the baud rate of the receiver among `rates`: at each rate, polls UBX-MON-VER
+    /// and waits up to `timeout` for the parser to produce a valid frame, whatever the
+    /// message. The port is left at the rate found, or at its original rate when none
+    /// is, and its read timeout is restored
+    pub fn detect_baud(&mut self, rates: &[u32], timeout: Duration) -> std::io::Result<Option<u32>> {
+        let baud = self.port.serial()?.baud_rate()?;
+        let read_timeout = self.port.serial()?.timeout();
+        let detected = self.try_rates(rates, timeout);
+        self.port.serial()?.set_timeout(read_timeout)?;
+        if !matches!(detected, Ok(Some(_))) {
+            self.port.serial()?.set_baud_rate(baud)?;
+        }
+        detected
+    }
+
+    fn try_rates(&mut self, rates: &[u32], timeout: Duration) -> std::io::Result<Option<u32>> {
+        self.port.serial()?.set_timeout(Duration::from_millis(50))?;
+        let mut buf = [0; 1024];
+        for rate in rates {
+            let port = self.port.serial()?;
+            port.set_baud_rate(*rate)?;
+            // anything received so far was at another rate
+            port.clear(serialport::ClearBuffer::Input)?;
+            self.parser = Parser::default();
+            self.write_poll(&frame::frame(0x0a, 0x04, &[]))?;
+            let deadline = std::time::Instant::now() + timeout;
+            while std::time::Instant::now() < deadline {
+                let nbytes = self.read_port(&mut buf)?;
+                let mut it = self.parser.consume(&buf[..nbytes]);
+                while let Some(packet) = it.next() {
+                    if packet.is_ok() {
+                        return Ok(Some(*rate));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Waits up to `timeout` for data without polling the port, then reads it: the descriptor
+    /// of the port is waited for on Unix. Elsewhere, like on Windows, the read itself waits
+    /// for the data, in the driver, its timeout being raised to `timeout` for the time of
+    /// the read
+    fn read_when_ready(&mut self, output: &mut [u8], timeout: Duration) -> std::io::Result<usize> {
+        if let Some(size) = self.take_replayed(output) {
+            return Ok(size);
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.fd {
+            if !self.port.pending() {
+                let mut pollfd = libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                // SAFETY: polls a single valid descriptor. Signals interrupt the wait (EINTR)
+                let ready =
+                    unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+                if ready == 0 {
+                    return Ok(0);
+                }
+                // a hung up port stays ready, with nothing to read
+                let hung_up = libc::POLLHUP | libc::POLLERR | libc::POLLNVAL;
+                if ready > 0 && pollfd.revents & hung_up != 0 && pollfd.revents & libc::POLLIN == 0
+                {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotConnected,
+                        "device disconnected",
+                    ));
+                }
+            }
+            return self.read_port(output);
+        }
+        // the short timeout of the port kept for the acknowledgments and the polls
+        let read_timeout = self.port.read_timeout()?;
+        self.port.set_read_timeout(Some(timeout))?;
+        let read = self.read_port(output);
+        self.port.set_read_timeout(read_timeout)?;
+        read
+    }
+
+    /// Replayed data, the descriptor not telling about it
+    fn take_replayed(&mut self, output: &mut [u8]) -> Option<usize> {
+        if self.replayed.is_empty() {
+            return None;
+        }
+        let size = self.replayed.len().min(output.len());
+        output[..size].copy_from_slice(&self.replayed[..size]);
+        self.replayed.drain(..size);
+        Some(size)
+    }
+
+    /// Has the next reads return `data` first, a message consumed by a poll being recorded
+    pub fn replay(&mut self, data: &[u8]) {
+        self.replayed.extend_from_slice(data);
     }
 
-    /// Reads the serial port, converting timeouts into "no data received"
+    /// Reads the port, converting timeouts into "no data received"
     fn read_port(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
         match self.port.read(output) {
             Ok(b) => Ok(b),
             Err(e) => {
-                if e.kind() == std::io::ErrorKind::TimedOut {
+                // sockets time out as would block on unix
+                if matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) {
                     Ok(0)
                 } else {
                     Err(e)
@@ -393,3 +4188,189 @@ impl Device {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Memory;
+
+    /// Device receiving `input`, reads failing with `failure` once it is read
+    fn device(input: &[u8], failure: Option<std::io::ErrorKind>) -> Device {
+        Device::new(Port::Memory(Box::new(Memory {
+            input: std::io::Cursor::new(input.to_vec()),
+            failure,
+            output: vec![],
+        })))
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn acknowledged() {
+        let input = [
+            frame::frame(0x01, 0x07, &[0; 92]),
+            // of another message
+            frame::frame(0x05, 0x00, &[0x06, 0x01]),
+            frame::frame(0x05, 0x01, &[0x06, 0x08]),
+        ]
+        .concat();
+        assert!(device(&input, None).wait_for_ack::<CfgRate>(TIMEOUT).is_ok());
+        // returns rather than exiting
+        expect_ack::<CfgRate>(&mut device(&input, None), "UBX-CFG-RATE", TIMEOUT);
+        // nothing sent, nothing to wait for
+        let mut dry_run = device(&[], None);
+        dry_run.dry_run = true;
+        assert!(dry_run.wait_for_ack::<CfgRate>(TIMEOUT).is_ok());
+    }
+
+    #[test]
+    fn replayed_first() {
+        let input = frame::frame(0x01, 0x07, &[0; 92]);
+        let version = frame::frame(0x0a, 0x04, &[0x20; 40]);
+        let mut device = device(&input, None);
+        device.replay(&version);
+        let mut buf = [0; 16];
+        let mut read = vec![];
+        loop {
+            match device.read_when_ready(&mut buf, TIMEOUT).unwrap() {
+                0 => break,
+                size => read.extend_from_slice(&buf[..size]),
+            }
+        }
+        assert_eq!(read, [version, input].concat());
+    }
+
+    #[test]
+    fn read_waits_without_descriptor() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        // rounded by the system
+        let read_timeout = stream.read_timeout().unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            peer.write_all(&[0xb5, 0x62]).unwrap();
+            peer
+        });
+        // without a descriptor to poll, as on Windows: the read waits past its timeout
+        let mut device = Device::new(Port::Tcp(stream));
+        let mut buf = [0; 16];
+        assert_eq!(device.read_when_ready(&mut buf, Duration::from_secs(5)).unwrap(), 2);
+        assert_eq!(device.port.read_timeout().unwrap(), read_timeout);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn config_rejected() {
+        let input = frame::frame(0x05, 0x00, &[0x06, 0x08]);
+        let error = device(&input, None).wait_for_ack::<CfgRate>(TIMEOUT).unwrap_err();
+        assert!(matches!(error, AckError::ConfigRejected { class: 0x06, id: 0x08 }));
+        assert_eq!(
+            error.to_string(),
+            "rejected by the receiver (UBX-ACK-NAK of class 0x06 id 0x08): wrong port, \
+unsupported message or invalid field"
+        );
+    }
+
+    #[test]
+    fn ack_timeout() {
+        let input = frame::frame(0x05, 0x01, &[0x06, 0x01]);
+        let error = device(&input, None).wait_for_ack::<CfgRate>(TIMEOUT).unwrap_err();
+        assert!(matches!(
+            error,
+            AckError::Timeout {
+                class: 0x06,
+                id: 0x08,
+                timeout: TIMEOUT
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "class 0x06 id 0x08 not acknowledged within 50ms: check the baud rate, and that the \
+port outputs UBX"
+        );
+    }
+
+    #[test]
+    fn device_ack_timeout() {
+        let mut short = device(&[], None);
+        assert_eq!(short.ack_timeout(), DEFAULT_ACK_TIMEOUT);
+        short.ack_timeout = TIMEOUT;
+        let message = Message {
+            name: "NAV-PVT".to_string(),
+            class: 0x01,
+            id: 0x07,
+            rate: 1,
+        };
+        // unanswered, the wait being the one of the device
+        let start = std::time::Instant::now();
+        let error = set_message_rates(&mut short, &message, [0, 1, 0, 1, 0, 0]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < DEFAULT_ACK_TIMEOUT);
+        // kept by reconnections, with the counters
+        short.reconnect(device(&[], None));
+        assert_eq!((short.ack_timeout(), short.written()), (TIMEOUT, 1));
+    }
+
+    #[test]
+    fn ack_io_error() {
+        let mut broken = device(&[], Some(std::io::ErrorKind::BrokenPipe));
+        let error = broken.wait_for_ack::<CfgRate>(TIMEOUT).unwrap_err();
+        assert!(matches!(&error, AckError::Io(e) if e.kind() == std::io::ErrorKind::BrokenPipe));
+        // timeouts of the port are no data, not errors
+        let mut silent = device(&[], Some(std::io::ErrorKind::TimedOut));
+        assert!(matches!(
+            silent.wait_for_ack::<CfgRate>(TIMEOUT),
+            Err(AckError::Timeout { .. })
+        ));
+    }
+
+    #[test]
+    fn serial_settings() {
+        assert_eq!(serial_parity(Some("odd")), SerialParity::Odd);
+        assert_eq!(serial_parity(Some("even")), SerialParity::Even);
+        assert_eq!(serial_parity(None), SerialParity::None);
+        assert_eq!(serial_flow_control(Some("hardware")), SerialFlowControl::Hardware);
+        assert_eq!(serial_flow_control(Some("software")), SerialFlowControl::Software);
+        assert_eq!(serial_flow_control(Some("none")), SerialFlowControl::None);
+        assert_eq!(serial_flow_control(None), SerialFlowControl::None);
+    }
+
+    #[test]
+    fn compression_levels() {
+        let level = |forced, path, level| {
+            OutputCompression::new(forced, path, level).map(|compression| compression.level)
+        };
+        // backward compatible default
+        assert_eq!(level(None, "output.ubx.gz", None), Ok(GZIP_DEFAULT_LEVEL));
+        assert_eq!(level(None, "output.ubx.zst", None), Ok(ZSTD_DEFAULT_LEVEL));
+        assert_eq!(level(Some(Codec::Zstd), "output.ubx", Some(19)), Ok(19));
+        assert_eq!(level(None, "output.ubx.gz", Some(1)), Ok(1));
+        assert_eq!(
+            level(Some(Codec::Gzip), "output.ubx.zst", Some(19)),
+            Err("gzip compression levels go from 0 to 9, not 19".to_string())
+        );
+        // --compress none, whatever the name
+        let compression = OutputCompression::new(Some(Codec::None), "out.ubx.zst", None).unwrap();
+        assert_eq!(compression.codec("out.ubx.zst"), Codec::None);
+    }
+
+    #[test]
+    fn zstd_output() {
+        let path = std::env::temp_dir()
+            .join(format!("ubx-record-{}-output.ubx.zst", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let data = frame::frame(0x01, 0x07, &[0x5a; 92]).repeat(50);
+        let compression = OutputCompression::new(None, &path, Some(1)).unwrap();
+        let mut writer = BufferedWriter::new(&path, compression, false);
+        assert!(matches!(writer, BufferedWriter::Zstd(_)));
+        writer.write_all(&data).unwrap();
+        writer.finalize().unwrap();
+        let compressed = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(compressed.len() < data.len());
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), data);
+    }
+}
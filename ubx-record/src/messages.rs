@@ -0,0 +1,205 @@
+//! Messages enabled and disabled on the receiver (`--enable-msg`, `--disable-msg`), named
+//! like NAV-PVT or given by class and id like 0x02,0x15, at a rate divisor of the navigation
+//! solutions, on the ports of `--msg-ports`
+
+/// Known messages: name, class and id
+pub const MESSAGES: [(&str, u8, u8); 67] = [
+    ("NAV-POSECEF", 0x01, 0x01),
+    ("NAV-POSLLH", 0x01, 0x02),
+    ("NAV-STATUS", 0x01, 0x03),
+    ("NAV-DOP", 0x01, 0x04),
+    ("NAV-ATT", 0x01, 0x05),
+    ("NAV-SOL", 0x01, 0x06),
+    ("NAV-PVT", 0x01, 0x07),
+    ("NAV-ODO", 0x01, 0x09),
+    ("NAV-VELECEF", 0x01, 0x11),
+    ("NAV-VELNED", 0x01, 0x12),
+    ("NAV-HPPOSECEF", 0x01, 0x13),
+    ("NAV-HPPOSLLH", 0x01, 0x14),
+    ("NAV-TIMEGPS", 0x01, 0x20),
+    ("NAV-TIMEUTC", 0x01, 0x21),
+    ("NAV-CLOCK", 0x01, 0x22),
+    ("NAV-TIMEGLO", 0x01, 0x23),
+    ("NAV-TIMEBDS", 0x01, 0x24),
+    ("NAV-TIMEGAL", 0x01, 0x25),
+    ("NAV-TIMELS", 0x01, 0x26),
+    ("NAV-SVINFO", 0x01, 0x30),
+    ("NAV-ORB", 0x01, 0x34),
+    ("NAV-SAT", 0x01, 0x35),
+    ("NAV-COV", 0x01, 0x36),
+    ("NAV-GEOFENCE", 0x01, 0x39),
+    ("NAV-SVIN", 0x01, 0x3b),
+    ("NAV-RELPOSNED", 0x01, 0x3c),
+    ("NAV-SIG", 0x01, 0x43),
+    ("NAV-EOE", 0x01, 0x61),
+    ("RXM-SFRBX", 0x02, 0x13),
+    ("RXM-MEASX", 0x02, 0x14),
+    ("RXM-RAWX", 0x02, 0x15),
+    ("RXM-RTCM", 0x02, 0x32),
+    ("RXM-COR", 0x02, 0x34),
+    ("MON-HW", 0x0a, 0x09),
+    ("MON-SPAN", 0x0a, 0x31),
+    ("MON-COMMS", 0x0a, 0x36),
+    ("MON-RF", 0x0a, 0x38),
+    ("TIM-TP", 0x0d, 0x01),
+    ("TIM-TM2", 0x0d, 0x03),
+    ("TIM-SVIN", 0x0d, 0x04),
+    ("ESF-MEAS", 0x10, 0x02),
+    ("ESF-RAW", 0x10, 0x03),
+    ("ESF-STATUS", 0x10, 0x10),
+    ("ESF-ALG", 0x10, 0x14),
+    ("ESF-INS", 0x10, 0x15),
+    ("HNR-PVT", 0x28, 0x00),
+    ("HNR-ATT", 0x28, 0x01),
+    ("HNR-INS", 0x28, 0x02),
+    ("NMEA-GGA", 0xf0, 0x00),
+    ("NMEA-GLL", 0xf0, 0x01),
+    ("NMEA-GSA", 0xf0, 0x02),
+    ("NMEA-GSV", 0xf0, 0x03),
+    ("NMEA-RMC", 0xf0, 0x04),
+    ("NMEA-VTG", 0xf0, 0x05),
+    ("NMEA-GRS", 0xf0, 0x06),
+    ("NMEA-GST", 0xf0, 0x07),
+    ("NMEA-ZDA", 0xf0, 0x08),
+    ("NMEA-GBS", 0xf0, 0x09),
+    ("NMEA-DTM", 0xf0, 0x0a),
+    ("NMEA-GNS", 0xf0, 0x0d),
+    ("NMEA-VLW", 0xf0, 0x0f),
+    ("RTCM3-1005", 0xf5, 0x05),
+    ("RTCM3-1077", 0xf5, 0x4d),
+    ("RTCM3-1087", 0xf5, 0x57),
+    ("RTCM3-1097", 0xf5, 0x61),
+    ("RTCM3-1127", 0xf5, 0x7f),
+    ("RTCM3-1230", 0xf5, 0xe6),
+];
+
+/// Periodic messages disabled by `--disable-all-default`: the NMEA sentences output by
+/// default, and the common navigation solutions
+pub const DEFAULTS: [&str; 15] = [
+    "NMEA-GGA",
+    "NMEA-GLL",
+    "NMEA-GSA",
+    "NMEA-GSV",
+    "NMEA-RMC",
+    "NMEA-VTG",
+    "NMEA-ZDA",
+    "NAV-PVT",
+    "NAV-POSLLH",
+    "NAV-STATUS",
+    "NAV-DOP",
+    "NAV-SOL",
+    "NAV-VELNED",
+    "NAV-TIMEUTC",
+    "NAV-SAT",
+];
+
+/// CFG-MSG ports, in the order of their rates: I2C, UART1, UART2, USB, SPI
+pub const PORTS: [&str; 5] = ["i2c", "uart1", "uart2", "usb", "spi"];
+
+/// Rates of a message enabled on USB, UART1 and UART2, by default
+pub const SERIAL_PORTS: [u8; 6] = [0, 1, 1, 1, 0, 0];
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub name: String,
+    pub class: u8,
+    pub id: u8,
+    /// Output every `rate` navigation solutions, 0 disabling it
+    pub rate: u8,
+}
+
+impl Message {
+    /// Known message of a name, at rate 0
+    pub fn named(name: &str) -> Option<Self> {
+        MESSAGES
+            .iter()
+            .find(|(known, _, _)| known.eq_ignore_ascii_case(name))
+            .map(|(name, class, id)| Self {
+                name: name.to_string(),
+                class: *class,
+                id: *id,
+                rate: 0,
+            })
+    }
+
+    /// CFG-MSG rates of the message on the selected ports
+    pub fn rates(&self, ports: &[bool; 5]) -> [u8; 6] {
+        let mut rates = [0; 6];
+        for (rate, selected) in rates.iter_mut().zip(ports) {
+            if *selected {
+                *rate = self.rate;
+            }
+        }
+        rates
+    }
+}
+
+/// Messages of a comma separated list, each followed by `@rate` when `rated`, 1 by default,
+/// like "NAV-PVT@1,0x02,0x15@5"
+pub fn parse(s: &str, rated: bool) -> Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    let mut tokens = s.split(',').map(str::trim);
+    while let Some(token) = tokens.next() {
+        // the id follows the class
+        let entry = if token.starts_with("0x") {
+            let id = tokens
+                .next()
+                .ok_or_else(|| format!("\"{}\": class without id, expecting like 0x02,0x15", token))?;
+            format!("{},{}", token, id)
+        } else {
+            token.to_string()
+        };
+        let (message, rate) = match entry.split_once('@') {
+            Some((_, _)) if !rated => {
+                return Err(format!("\"{}\": disabled messages take no rate", entry))
+            },
+            Some((message, rate)) => (
+                message.to_string(),
+                rate.parse::<u8>()
+                    .map_err(|e| format!("\"{}\": invalid rate: {}", entry, e))?,
+            ),
+            None => (entry.clone(), u8::from(rated)),
+        };
+        let mut message = match message.split_once(',') {
+            Some((class, id)) => {
+                let hex = |s: &str| {
+                    u8::from_str_radix(s.trim_start_matches("0x"), 16)
+                        .map_err(|e| format!("\"{}\": invalid class or id: {}", entry, e))
+                };
+                let (class, id) = (hex(class)?, hex(id)?);
+                match MESSAGES.iter().find(|(_, c, i)| (*c, *i) == (class, id)) {
+                    Some((name, _, _)) => Message::named(name).unwrap(),
+                    None => Message {
+                        name: format!("0x{:02x},0x{:02x}", class, id),
+                        class,
+                        id,
+                        rate: 0,
+                    },
+                }
+            },
+            None => Message::named(&message).ok_or_else(|| {
+                let known = MESSAGES
+                    .iter()
+                    .map(|(name, _, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "unknown message \"{}\", expecting a class and id like 0x02,0x15 or one of {}",
+                    message, known
+                )
+            })?,
+        };
+        message.rate = rate;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Ports selected by `--msg-ports`, like "usb,uart1"
+pub fn parse_ports(names: &[String]) -> [bool; 5] {
+    let mut ports = [false; 5];
+    for (selected, port) in ports.iter_mut().zip(PORTS) {
+        *selected = names.iter().any(|name| name == port);
+    }
+    ports
+}
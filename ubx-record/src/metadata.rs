@@ -0,0 +1,377 @@
+//! Session metadata block, embedded at the start of the recordings.
+//!
+//! The block is a regular UBX frame of a class u-blox does not use, its payload a JSON
+//! object describing the session: parsers skip it like any unknown message, while
+//! ubx-read displays it (`--info`) and keeps it out of the message statistics.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::frame;
+
+/// UBX class and id of the metadata block, outside the u-blox message classes
+pub const CLASS: u8 = 0x7f;
+pub const ID: u8 = 0x01;
+
+/// Largest JSON payload, below the frame size limits of the UBX scanners
+pub const MAX_JSON_LEN: usize = 4096;
+
+/// UBX-MON-VER: swVersion, then hwVersion, then extensions
+const MON_VER: (u8, u8) = (0x0a, 0x04);
+const SW_VERSION_LEN: usize = 30;
+const HW_VERSION_LEN: usize = 10;
+const EXTENSION_LEN: usize = 30;
+
+/// UBX-SEC-UNIQID: version and reserved bytes, then the chip unique id
+const SEC_UNIQID: (u8, u8) = (0x27, 0x03);
+const UNIQID_OFFSET: usize = 4;
+
+/// Null terminated string of a fixed size field
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub fn json_or_null(value: Option<String>) -> String {
+    value
+        .map(|value| json_string(&value))
+        .unwrap_or_else(|| "null".to_string())
+}
+
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    let mut name = [0_u8; 256];
+    // SAFETY: the buffer is large enough for any host name, truncation being reported
+    let ret = unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len()) };
+    (ret == 0).then(|| field(&name))
+}
+
+#[cfg(not(unix))]
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// Receiver identification, polled once the port is configured
+#[derive(Debug)]
+pub struct Receiver {
+    /// UBX-MON-VER payload
+    version: Option<Vec<u8>>,
+    /// UBX-SEC-UNIQID payload
+    unique_id: Option<Vec<u8>>,
+}
+
+impl Receiver {
+    /// Polls the identification, waiting up to `timeout` for each answer
+    pub fn poll(device: &mut crate::Device, timeout: Duration) -> Self {
+        let (class, id) = MON_VER;
+        let version = device.poll_payload(class, id, &[], timeout);
+        if let Err(e) = &version {
+            warn!(
+                "Receiver version (UBX-MON-VER) unavailable: {}",
+                e
+            );
+        }
+        let (class, id) = SEC_UNIQID;
+        let unique_id = device.poll_payload(class, id, &[], timeout);
+        if let Err(e) = &unique_id {
+            warn!(
+                "Receiver unique id (UBX-SEC-UNIQID) unavailable: {}",
+                e
+            );
+        }
+        Self {
+            version: version.ok(),
+            unique_id: unique_id.ok(),
+        }
+    }
+
+    /// UBX-MON-VER reply of the receiver, recorded ahead of the received data
+    pub fn version_frame(&self) -> Option<Vec<u8>> {
+        let (class, id) = MON_VER;
+        self.version.as_ref().map(|payload| frame::frame(class, id, payload))
+    }
+
+    /// Identification from an UBX-MON-VER payload alone
+    pub fn from_version(payload: Vec<u8>) -> Self {
+        Self {
+            version: Some(payload),
+            unique_id: None,
+        }
+    }
+
+    /// UBX-MON-VER payload, when long enough for the software and hardware versions
+    fn version(&self) -> Option<&[u8]> {
+        self.version
+            .as_deref()
+            .filter(|payload| payload.len() >= SW_VERSION_LEN + HW_VERSION_LEN)
+    }
+
+    /// Software version, like "ROM CORE 3.01 (107888)"
+    pub fn sw_version(&self) -> Option<String> {
+        self.version().map(|payload| field(&payload[..SW_VERSION_LEN]))
+    }
+
+    /// Hardware version, like "00080000"
+    pub fn hw_version(&self) -> Option<String> {
+        self.version()
+            .map(|payload| field(&payload[SW_VERSION_LEN..SW_VERSION_LEN + HW_VERSION_LEN]))
+    }
+
+    /// Extensions, like "PROTVER=18.00" and "GPS;GLO;GAL;BDS" for the supported GNSS
+    pub fn extensions(&self) -> Vec<String> {
+        self.version().map_or(Vec::new(), |payload| {
+            payload[SW_VERSION_LEN + HW_VERSION_LEN..]
+                .chunks_exact(EXTENSION_LEN)
+                .map(field)
+                .collect()
+        })
+    }
+
+    /// Protocol version, like "18.00", from the PROTVER extension
+    pub fn protocol(&self) -> Option<String> {
+        self.extensions().iter().find_map(|extension| {
+            // "PROTVER=18.00", or "PROTVER 14.00" on older firmware
+            extension
+                .strip_prefix("PROTVER")
+                .map(|version| version.trim_start_matches(['=', ' ']).to_string())
+        })
+    }
+
+    /// Major number of the protocol version, like 18 for "18.00"
+    pub fn protocol_major(&self) -> Option<u32> {
+        self.protocol()?.split('.').next()?.trim().parse().ok()
+    }
+
+    /// Chip unique id, in hexadecimal
+    pub fn unique_id(&self) -> Option<String> {
+        self.unique_id
+            .as_ref()
+            .filter(|payload| payload.len() > UNIQID_OFFSET)
+            .map(|payload| {
+                payload[UNIQID_OFFSET..]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+            })
+    }
+
+    /// Firmware version: the FWVER extension, like "HPG 1.13", the software version otherwise
+    pub fn firmware(&self) -> Option<String> {
+        self.extensions()
+            .iter()
+            .find_map(|extension| extension.strip_prefix("FWVER=").map(str::to_string))
+            .or_else(|| self.sw_version())
+    }
+
+    /// Lines printed of the version, like "software version: ROM CORE 3.01 (107888)"
+    pub fn describe(&self) -> Vec<String> {
+        let (Some(sw_version), Some(hw_version)) = (self.sw_version(), self.hw_version()) else {
+            return vec!["version unknown (no UBX-MON-VER answer)".to_string()];
+        };
+        let mut lines = vec![
+            format!("software version: {}", sw_version),
+            format!("hardware version: {}", hw_version),
+        ];
+        lines.extend(
+            self.extensions()
+                .iter()
+                .map(|extension| format!("extension: {}", extension)),
+        );
+        if let Some(unique_id) = self.unique_id() {
+            lines.push(format!("unique id: {}", unique_id));
+        }
+        lines
+    }
+
+    /// Fails unless the firmware or software version contains `substring`, like for
+    /// catching receivers flashed with the wrong image
+    pub fn require_firmware(&self, substring: &str) -> Result<(), String> {
+        let versions = [self.firmware(), self.sw_version()];
+        match &versions[0] {
+            Some(_) if versions.iter().flatten().any(|version| version.contains(substring)) => {
+                Ok(())
+            },
+            Some(firmware) => Err(format!(
+                "Firmware \"{}\" does not match --require-firmware \"{}\"",
+                firmware, substring
+            )),
+            None => Err(format!(
+                "Firmware unknown, the receiver did not answer UBX-MON-VER, required to \
+contain \"{}\"",
+                substring
+            )),
+        }
+    }
+
+    pub fn json(&self) -> String {
+        let version = match (self.sw_version(), self.hw_version()) {
+            (Some(sw_version), Some(hw_version)) => {
+                let extensions = self
+                    .extensions()
+                    .iter()
+                    .map(|extension| json_string(extension))
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{\"sw_version\":{},\"hw_version\":{},\"extensions\":[{}]}}",
+                    json_string(&sw_version),
+                    json_string(&hw_version),
+                    extensions.join(",")
+                )
+            },
+            _ => "null".to_string(),
+        };
+        format!(
+            "{{\"mon_ver\":{},\"unique_id\":{}}}",
+            version,
+            json_or_null(self.unique_id())
+        )
+    }
+}
+
+/// Session metadata, written at the start of every output file
+pub struct Metadata {
+    json: String,
+}
+
+impl Metadata {
+    pub fn new(receiver: &Receiver) -> Self {
+        Self::with_command_line(receiver, std::env::args().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Metadata of the session started with `command_line`, truncated to keep the JSON
+    /// within [MAX_JSON_LEN]
+    fn with_command_line(receiver: &Receiver, mut command_line: String) -> Self {
+        let start_time = Utc::now().to_rfc3339();
+        let render = |command_line: &str| {
+            format!(
+                "{{\"tool\":\"ubx-record\",\"version\":{},\"command_line\":{},\"host\":{},\
+\"receiver\":{},\"start_time\":{}}}",
+                json_string(env!("CARGO_PKG_VERSION")),
+                json_string(command_line),
+                json_or_null(host_name()),
+                receiver.json(),
+                json_string(&start_time),
+            )
+        };
+        let mut json = render(&command_line);
+        if json.len() > MAX_JSON_LEN {
+            // escaping only lengthens the command line: dropping the excess is enough
+            let excess = json.len() - MAX_JSON_LEN + "...".len();
+            let mut keep = command_line.len().saturating_sub(excess);
+            while !command_line.is_char_boundary(keep) {
+                keep -= 1;
+            }
+            command_line.truncate(keep);
+            command_line.push_str("...");
+            json = render(&command_line);
+        }
+        Self { json }
+    }
+
+    /// The metadata block, a UBX frame
+    pub fn frame(&self) -> Vec<u8> {
+        frame::frame(CLASS, ID, self.json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MON-VER payload of a ZED-F9P
+    fn mon_ver() -> Vec<u8> {
+        let mut payload = vec![0; SW_VERSION_LEN + HW_VERSION_LEN];
+        payload[..9].copy_from_slice(b"EXT CORE ");
+        payload[SW_VERSION_LEN..SW_VERSION_LEN + 8].copy_from_slice(b"00190000");
+        for extension in ["FWVER=HPG 1.32", "PROTVER=27.31", "GPS;GLO;GAL;BDS"] {
+            let mut field = [0; EXTENSION_LEN];
+            field[..extension.len()].copy_from_slice(extension.as_bytes());
+            payload.extend_from_slice(&field);
+        }
+        payload
+    }
+
+    fn parse(frame: &[u8]) -> serde_json::Value {
+        let len = usize::from(u16::from_le_bytes([frame[4], frame[5]]));
+        assert_eq!(frame.len(), len + 8);
+        serde_json::from_slice(&frame[6..6 + len]).expect("JSON payload")
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(json_string("a \"b\" \\ \n\t\u{1}é"), "\"a \\\"b\\\" \\\\ \\n\\t\\u0001é\"");
+        assert_eq!(json_or_null(None), "null");
+    }
+
+    #[test]
+    fn receiver_version() {
+        let receiver = Receiver::from_version(mon_ver());
+        assert_eq!(receiver.sw_version().as_deref(), Some("EXT CORE"));
+        assert_eq!(receiver.hw_version().as_deref(), Some("00190000"));
+        assert_eq!(receiver.firmware().as_deref(), Some("HPG 1.32"));
+        assert_eq!(receiver.protocol().as_deref(), Some("27.31"));
+        assert_eq!(receiver.protocol_major(), Some(27));
+        assert!(receiver.require_firmware("HPG").is_ok());
+        assert!(receiver.require_firmware("EXT").is_ok());
+        assert_eq!(
+            receiver.require_firmware("ADR").unwrap_err(),
+            "Firmware \"HPG 1.32\" does not match --require-firmware \"ADR\""
+        );
+        // too short for the versions
+        let unknown = Receiver::from_version(vec![0; 39]);
+        assert_eq!(unknown.describe(), ["version unknown (no UBX-MON-VER answer)"]);
+        assert_eq!(unknown.json(), "{\"mon_ver\":null,\"unique_id\":null}");
+    }
+
+    #[test]
+    fn framing() {
+        let receiver = Receiver::from_version(mon_ver());
+        let command_line = "ubx-record -p /dev/ttyACM0".to_string();
+        let frame = Metadata::with_command_line(&receiver, command_line).frame();
+        assert_eq!(frame[..4], [0xb5, 0x62, CLASS, ID]);
+        let (ck_a, ck_b) = frame::checksum(&frame[2..frame.len() - 2]);
+        assert_eq!(frame[frame.len() - 2..], [ck_a, ck_b]);
+        let json = parse(&frame);
+        assert_eq!(json["tool"], "ubx-record");
+        assert_eq!(json["command_line"], "ubx-record -p /dev/ttyACM0");
+        assert_eq!(json["receiver"]["mon_ver"]["extensions"][1], "PROTVER=27.31");
+        assert!(json["receiver"]["unique_id"].is_null());
+    }
+
+    #[test]
+    fn size_limit() {
+        let receiver = Receiver::from_version(mon_ver());
+        // escaped quotes and multibyte characters, on a character boundary or not
+        for filler in ["a", "\"", "é", "\"é\\"] {
+            let command_line = format!("ubx-record {}", filler.repeat(5000));
+            let frame = Metadata::with_command_line(&receiver, command_line.clone()).frame();
+            assert!(frame.len() <= MAX_JSON_LEN + 8, "{} bytes", frame.len());
+            let json = parse(&frame);
+            let truncated = json["command_line"].as_str().unwrap();
+            let kept = truncated.strip_suffix("...").expect("truncation mark");
+            assert!(command_line.starts_with(kept));
+        }
+        // short enough
+        let frame = Metadata::with_command_line(&receiver, "a".repeat(3000)).frame();
+        assert_eq!(parse(&frame)["command_line"], "a".repeat(3000));
+    }
+}
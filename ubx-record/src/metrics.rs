@@ -0,0 +1,206 @@
+//! Metrics endpoint of the recording (`--metrics ADDRESS`, `tokio` feature): the counters of
+//! the receiver stream, the NTRIP corrections and the TCP clients, served over HTTP in the
+//! Prometheus text format at /metrics by a task of the runtime.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::protocol::Protocol;
+
+/// Longest wait for the request of a client
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request accepted, its headers included
+const MAX_REQUEST_LEN: usize = 8192;
+
+/// Counters shared by the tasks, updated as they go
+pub struct Metrics {
+    start: Instant,
+    /// Bytes received from the receiver
+    pub received: AtomicU64,
+    /// Valid frames received, by protocol: UBX, NMEA, RTCM3
+    pub frames: [AtomicU64; 3],
+    /// Bytes of corrections received from the NTRIP caster, and written to the receiver
+    pub corrections: AtomicU64,
+    pub forwarded: AtomicU64,
+    /// Connections to the NTRIP caster established
+    pub caster_connections: AtomicU64,
+    /// TCP clients connected now, and since the start
+    pub clients: AtomicU64,
+    pub served: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            received: AtomicU64::default(),
+            frames: Default::default(),
+            corrections: AtomicU64::default(),
+            forwarded: AtomicU64::default(),
+            caster_connections: AtomicU64::default(),
+            clients: AtomicU64::default(),
+            served: AtomicU64::default(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Counts a valid frame of the stream
+    pub fn frame(&self, protocol: Protocol) {
+        let index = match protocol {
+            Protocol::Ubx => 0,
+            Protocol::Nmea => 1,
+            Protocol::Rtcm => 2,
+        };
+        self.frames[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counters in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, values: &[(&str, u64)]| {
+            let _ = writeln!(text, "# HELP ubx_record_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE ubx_record_{} {}", name, kind);
+            for (labels, value) in values {
+                let _ = writeln!(text, "ubx_record_{}{} {}", name, labels, value);
+            }
+        };
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Time since the start of the recording",
+            &[("", self.start.elapsed().as_secs())],
+        );
+        metric(
+            "received_bytes_total",
+            "counter",
+            "Bytes received from the receiver",
+            &[("", load(&self.received))],
+        );
+        metric(
+            "frames_total",
+            "counter",
+            "Valid frames received from the receiver",
+            &[
+                ("{protocol=\"ubx\"}", load(&self.frames[0])),
+                ("{protocol=\"nmea\"}", load(&self.frames[1])),
+                ("{protocol=\"rtcm3\"}", load(&self.frames[2])),
+            ],
+        );
+        metric(
+            "ntrip_received_bytes_total",
+            "counter",
+            "Bytes of corrections received from the NTRIP caster",
+            &[("", load(&self.corrections))],
+        );
+        metric(
+            "ntrip_forwarded_bytes_total",
+            "counter",
+            "Bytes of corrections written to the receiver",
+            &[("", load(&self.forwarded))],
+        );
+        metric(
+            "ntrip_connections_total",
+            "counter",
+            "Connections to the NTRIP caster established",
+            &[("", load(&self.caster_connections))],
+        );
+        metric(
+            "tcp_clients",
+            "gauge",
+            "TCP clients connected",
+            &[("", load(&self.clients))],
+        );
+        metric(
+            "tcp_clients_total",
+            "counter",
+            "TCP clients connected since the start",
+            &[("", load(&self.served))],
+        );
+        text
+    }
+}
+
+/// Serves the metrics to the HTTP clients connecting to the listener, each by a task
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream, &metrics).await {
+                        debug!("metrics client {}: {}", peer, e);
+                    }
+                });
+            },
+            Err(e) => {
+                // out of descriptors: the clients wait in the backlog meanwhile
+                warn!("metrics endpoint: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            },
+        }
+    }
+}
+
+/// Reads the request and answers it, the metrics at /metrics, 404 elsewhere
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut chunk = [0; 1024];
+    let read = async {
+        while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+            let size = stream.read(&mut chunk).await?;
+            if size == 0 || request.len() + size > MAX_REQUEST_LEN {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "incomplete request",
+                ));
+            }
+            request.extend_from_slice(&chunk[..size]);
+        }
+        Ok(())
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, read)
+        .await
+        .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into()))?;
+    let line = String::from_utf8_lossy(&request);
+    let mut fields = line.split_whitespace();
+    let response = match (fields.next(), fields.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.render();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        },
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_text() {
+        let metrics = Metrics::default();
+        metrics.received.fetch_add(100, Ordering::Relaxed);
+        metrics.frame(Protocol::Rtcm);
+        metrics.frame(Protocol::Rtcm);
+        let text = metrics.render();
+        assert!(text.contains("# TYPE ubx_record_received_bytes_total counter\n"), "{}", text);
+        assert!(text.contains("\nubx_record_received_bytes_total 100\n"), "{}", text);
+        assert!(text.contains("\nubx_record_frames_total{protocol=\"rtcm3\"} 2\n"), "{}", text);
+        assert!(text.contains("\nubx_record_tcp_clients 0\n"), "{}", text);
+    }
+}
@@ -0,0 +1,182 @@
+//! Output file names: strftime placeholders, like `%Y%m%d-%H%M%S`, expanded at the
+//! start of the recording (UTC), and tokens describing the session, like `{port}`.
+//!
+//! `%%` writes a percent sign, `{{` and `}}` write braces.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+
+/// Default output name of single file recordings, which do not overwrite each other
+pub const DEFAULT_TEMPLATE: &str = "output-%Y%m%dT%H%M%SZ.ubx.gz";
+
+/// Tokens needing the receiver identification, polled when used
+const RECEIVER_TOKENS: [&str; 2] = ["serial", "firmware"];
+
+/// Values of the tokens, None when unknown
+#[derive(Debug, Default)]
+pub struct Tokens {
+    /// Serial port name, like "ttyACM0"
+    pub port: Option<String>,
+    /// Chip unique id (UBX-SEC-UNIQID), in hexadecimal
+    pub serial: Option<String>,
+    /// Firmware version (UBX-MON-VER)
+    pub firmware: Option<String>,
+}
+
+impl Tokens {
+    fn get(&self, name: &str) -> Result<Option<&str>, String> {
+        match name {
+            "port" => Ok(self.port.as_deref()),
+            "serial" => Ok(self.serial.as_deref()),
+            "firmware" => Ok(self.firmware.as_deref()),
+            _ => Err(format!(
+                "unknown token {{{}}}, expecting {{port}}, {{serial}} or {{firmware}}",
+                name
+            )),
+        }
+    }
+}
+
+/// True when the template uses a token of the receiver identification
+pub fn uses_receiver(template: &str) -> bool {
+    RECEIVER_TOKENS
+        .iter()
+        .any(|token| template.contains(&format!("{{{}}}", token)))
+}
+
+/// Last component of the port path, "/dev/ttyACM0" giving "ttyACM0", "COM3" staying as is
+pub fn port_name(port: &str) -> String {
+    port.rsplit(['/', '\\']).next().unwrap_or(port).to_string()
+}
+
+/// Expands the placeholders and tokens of the template. Unknown tokens are errors,
+/// known tokens without value become "unknown". Token values cannot form separators
+pub fn expand(template: &str, time: DateTime<Utc>, tokens: &Tokens) -> Result<String, String> {
+    if StrftimeItems::new(template).any(|item| item == Item::Error) {
+        return Err(format!("invalid % placeholder in \"{}\"", template));
+    }
+    let dated = time.format(template).to_string();
+
+    let mut name = String::with_capacity(dated.len());
+    let mut chars = dated.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let rest = chars.as_str();
+                if let Some(rest) = rest.strip_prefix('{') {
+                    name.push('{');
+                    chars = rest.chars();
+                    continue;
+                }
+                let Some(end) = rest.find('}') else {
+                    return Err(format!("unterminated token in \"{}\"", template));
+                };
+                let value = tokens.get(&rest[..end])?.unwrap_or("unknown");
+                name.extend(value.chars().map(|c| match c {
+                    '/' | '\\' | ':' | ' ' => '_',
+                    c => c,
+                }));
+                chars = rest[end + 1..].chars();
+            },
+            '}' => {
+                if !chars.as_str().starts_with('}') {
+                    return Err(format!(
+                        "unmatched }} in \"{}\", write }}}} instead",
+                        template
+                    ));
+                }
+                chars.next();
+                name.push('}');
+            },
+            c => name.push(c),
+        }
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap()
+    }
+
+    fn tokens() -> Tokens {
+        Tokens {
+            port: Some("ttyACM0".to_string()),
+            serial: Some("e095650f2a".to_string()),
+            firmware: Some("HPG 1.32".to_string()),
+        }
+    }
+
+    #[test]
+    fn placeholders_and_tokens() {
+        assert_eq!(
+            expand("capture-%Y%m%d-%H%M%S-{port}.ubx.gz", time(), &tokens()).unwrap(),
+            "capture-20240309-140507-ttyACM0.ubx.gz"
+        );
+        assert_eq!(
+            expand(DEFAULT_TEMPLATE, time(), &Tokens::default()).unwrap(),
+            "output-20240309T140507Z.ubx.gz"
+        );
+        // values cannot form separators
+        assert_eq!(
+            expand("{firmware}/{serial}", time(), &tokens()).unwrap(),
+            "HPG_1.32/e095650f2a"
+        );
+        let tokens = Tokens {
+            port: Some("COM3:".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(expand("{port}", time(), &tokens).unwrap(), "COM3_");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(expand("100%%-{{port}}", time(), &tokens()).unwrap(), "100%-{port}");
+        assert_eq!(expand("{{{port}}}", time(), &tokens()).unwrap(), "{ttyACM0}");
+        // a token value is not expanded again
+        let tokens = Tokens {
+            port: Some("%Y{port}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(expand("{port}", time(), &tokens).unwrap(), "%Y{port}");
+    }
+
+    #[test]
+    fn missing_tokens() {
+        // known but without value
+        assert_eq!(
+            expand("{port}-{serial}-{firmware}", time(), &Tokens::default()).unwrap(),
+            "unknown-unknown-unknown"
+        );
+        assert_eq!(
+            expand("{host}.ubx", time(), &tokens()),
+            Err("unknown token {host}, expecting {port}, {serial} or {firmware}".to_string())
+        );
+        assert_eq!(
+            expand("{port", time(), &tokens()),
+            Err("unterminated token in \"{port\"".to_string())
+        );
+        assert_eq!(
+            expand("port}", time(), &tokens()),
+            Err("unmatched } in \"port}\", write }} instead".to_string())
+        );
+        assert_eq!(
+            expand("%Q.ubx", time(), &tokens()),
+            Err("invalid % placeholder in \"%Q.ubx\"".to_string())
+        );
+    }
+
+    #[test]
+    fn receiver_tokens() {
+        assert!(uses_receiver("{serial}.ubx"));
+        assert!(uses_receiver("%Y-{firmware}.ubx"));
+        assert!(!uses_receiver("{port}-%H.ubx"));
+        assert_eq!(port_name("/dev/ttyACM0"), "ttyACM0");
+        assert_eq!(port_name("\\\\.\\COM10"), "COM10");
+        assert_eq!(port_name("COM3"), "COM3");
+    }
+}
@@ -0,0 +1,511 @@
+//! Navigation settings of the configure subcommand: measurement and navigation rates
+//! (UBX-CFG-RATE), dynamic model, fix mode and elevation mask (UBX-CFG-NAV5), constellations
+//! (UBX-CFG-GNSS) and SBAS (UBX-CFG-SBAS). The settings not given are kept, and the applied
+//! ones are polled back once acknowledged
+
+use ublox::{
+    AlignmentToReferenceTime, CfgNav5Builder, CfgNav5DynModel, CfgNav5FixMode, CfgNav5Params,
+    CfgRateBuilder,
+};
+
+use crate::frame;
+use crate::Device;
+
+/// Shortest measurement period of the common receivers, 40 Hz on the fastest ones
+const MIN_MEAS_RATE_MS: u16 = 25;
+
+/// Largest navigation rate, in measurement cycles
+const MAX_NAV_RATE: u16 = 127;
+
+/// Time references of CFG-RATE, by name
+pub const TIME_REFS: [&str; 5] = ["utc", "gps", "glonass", "beidou", "galileo"];
+
+/// Dynamic platform models of CFG-NAV5, by name
+pub const DYN_MODELS: [(&str, CfgNav5DynModel); 9] = [
+    ("portable", CfgNav5DynModel::Portable),
+    ("stationary", CfgNav5DynModel::Stationary),
+    ("pedestrian", CfgNav5DynModel::Pedestrian),
+    ("automotive", CfgNav5DynModel::Automotive),
+    ("sea", CfgNav5DynModel::Sea),
+    ("airborne-1g", CfgNav5DynModel::AirborneWithLess1gAcceleration),
+    ("airborne-2g", CfgNav5DynModel::AirborneWithLess2gAcceleration),
+    ("airborne-4g", CfgNav5DynModel::AirborneWith4gAcceleration),
+    ("wrist", CfgNav5DynModel::WristWornWatch),
+];
+
+/// Constellations of CFG-GNSS, by name, with their gnssId
+pub const CONSTELLATIONS: [(&str, u8); 7] = [
+    ("gps", 0),
+    ("sbas", 1),
+    ("galileo", 2),
+    ("beidou", 3),
+    ("imes", 4),
+    ("qzss", 5),
+    ("glonass", 6),
+];
+
+/// Uses of the SBAS corrections in CFG-SBAS, by name, with their usage bit
+pub const SBAS_MODES: [(&str, u8); 3] = [
+    ("ranging", 0x01),
+    ("correction", 0x02),
+    ("integrity", 0x04),
+];
+
+/// PRNs of the SBAS satellites, the first 32 in scanmode1, the others in scanmode2
+const SBAS_PRNS: std::ops::RangeInclusive<u8> = 120..=158;
+
+/// Position fix modes of CFG-NAV5, by name
+pub const FIX_MODES: [(&str, CfgNav5FixMode); 3] = [
+    ("2d", CfgNav5FixMode::Only2D),
+    ("3d", CfgNav5FixMode::Only3D),
+    ("auto", CfgNav5FixMode::Auto2D3D),
+];
+
+pub fn parse_meas_rate(s: &str) -> Result<u16, String> {
+    let rate = s.parse::<u16>().map_err(|e| e.to_string())?;
+    if rate < MIN_MEAS_RATE_MS {
+        return Err(format!(
+            "measurements every {} ms are faster than the common receivers can do, {} ms (40 Hz) at best",
+            rate, MIN_MEAS_RATE_MS
+        ));
+    }
+    Ok(rate)
+}
+
+pub fn parse_nav_rate(s: &str) -> Result<u16, String> {
+    let rate = s.parse::<u16>().map_err(|e| e.to_string())?;
+    if !(1..=MAX_NAV_RATE).contains(&rate) {
+        return Err(format!(
+            "the navigation rate is from 1 to {} measurement cycles",
+            MAX_NAV_RATE
+        ));
+    }
+    Ok(rate)
+}
+
+pub fn parse_sbas_prn(s: &str) -> Result<u8, String> {
+    let prn = s.parse::<u8>().map_err(|e| e.to_string())?;
+    if !SBAS_PRNS.contains(&prn) {
+        return Err(format!(
+            "the SBAS PRNs are from {} to {}",
+            SBAS_PRNS.start(),
+            SBAS_PRNS.end()
+        ));
+    }
+    Ok(prn)
+}
+
+/// Measurement and navigation rates, from a UBX-CFG-RATE payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub meas_rate_ms: u16,
+    pub nav_rate: u16,
+    pub time_ref: u16,
+}
+
+impl Rate {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let field = |offset: usize| {
+            payload
+                .get(offset..offset + 2)
+                .map(|field| u16::from_le_bytes([field[0], field[1]]))
+        };
+        Some(Self {
+            meas_rate_ms: field(0)?,
+            nav_rate: field(2)?,
+            time_ref: field(4)?,
+        })
+    }
+
+    /// Like "measurements every 100 ms, a solution every 1 of them (10.0 Hz), aligned on GPS time"
+    pub fn describe(&self) -> String {
+        let period = f64::from(self.meas_rate_ms) * f64::from(self.nav_rate.max(1));
+        format!(
+            "measurements every {} ms, a solution every {} of them ({:.1} Hz), aligned on {} time",
+            self.meas_rate_ms,
+            self.nav_rate,
+            1000.0 / period,
+            TIME_REFS
+                .get(usize::from(self.time_ref))
+                .map_or("unknown".to_string(), |name| name.to_uppercase())
+        )
+    }
+}
+
+fn alignment(time_ref: u16) -> AlignmentToReferenceTime {
+    match time_ref {
+        1 => AlignmentToReferenceTime::Gps,
+        2 => AlignmentToReferenceTime::Glo,
+        3 => AlignmentToReferenceTime::Bds,
+        4 => AlignmentToReferenceTime::Gal,
+        _ => AlignmentToReferenceTime::Utc,
+    }
+}
+
+/// Sets the rates given, keeping the others, returning the rates polled back
+pub fn set_rate(
+    device: &mut Device,
+    meas_rate_ms: Option<u16>,
+    nav_rate: Option<u16>,
+    time_ref: Option<&str>,
+) -> Result<Option<Rate>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x08, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the navigation rate (UBX-CFG-RATE): {}", e))?;
+    let current = Rate::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-RATE of {} bytes", payload.len()))?;
+    let rate = Rate {
+        meas_rate_ms: meas_rate_ms.unwrap_or(current.meas_rate_ms),
+        nav_rate: nav_rate.unwrap_or(current.nav_rate),
+        time_ref: time_ref
+            .and_then(|name| TIME_REFS.iter().position(|known| *known == name))
+            .map_or(current.time_ref, |index| index as u16),
+    };
+    info!("Setting the navigation rate: {} ...", rate.describe());
+    let packet = CfgRateBuilder {
+        measure_rate_ms: rate.meas_rate_ms,
+        nav_rate: rate.nav_rate,
+        time_ref: alignment(rate.time_ref),
+    }
+    .into_packet_bytes();
+    device
+        .write_all(&packet)
+        .map_err(|e| format!("Failed to write UBX-CFG-RATE: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x08, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the navigation rate (UBX-ACK-NAK of UBX-CFG-RATE): {}",
+                rate.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-RATE not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x08, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Rate::parse(&payload));
+    Ok(applied)
+}
+
+/// Dynamic model, fix mode and elevation mask, from a UBX-CFG-NAV5 payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nav5 {
+    pub dyn_model: u8,
+    pub fix_mode: u8,
+    pub min_elev_degrees: i8,
+}
+
+impl Nav5 {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            dyn_model: *payload.get(2)?,
+            fix_mode: *payload.get(3)?,
+            min_elev_degrees: *payload.get(12)? as i8,
+        })
+    }
+
+    /// Like "airborne-4g dynamic model, auto fix mode, 5° elevation mask"
+    pub fn describe(&self) -> String {
+        let dyn_model = DYN_MODELS
+            .iter()
+            .find(|(_, model)| *model as u8 == self.dyn_model)
+            .map_or(format!("model {}", self.dyn_model), |(name, _)| name.to_string());
+        let fix_mode = FIX_MODES
+            .iter()
+            .find(|(_, mode)| *mode as u8 == self.fix_mode)
+            .map_or(format!("mode {}", self.fix_mode), |(name, _)| name.to_string());
+        format!(
+            "{} dynamic model, {} fix mode, {}° elevation mask",
+            dyn_model, fix_mode, self.min_elev_degrees
+        )
+    }
+}
+
+/// Sets the dynamic model, fix mode and elevation mask given, the mask bits of CFG-NAV5
+/// keeping the others, returning the settings polled back
+pub fn set_nav5(
+    device: &mut Device,
+    dyn_model: Option<&str>,
+    fix_mode: Option<&str>,
+    min_elev_degrees: Option<i8>,
+) -> Result<Option<Nav5>, String> {
+    let mut builder = CfgNav5Builder::default();
+    let mut mask = CfgNav5Params::empty();
+    let mut changes = Vec::new();
+    let dyn_model = dyn_model.and_then(|name| DYN_MODELS.iter().find(|(known, _)| *known == name));
+    if let Some((name, model)) = dyn_model {
+        mask |= CfgNav5Params::DYN;
+        builder.dyn_model = *model;
+        changes.push(format!("{} dynamic model", name));
+    }
+    let fix_mode = fix_mode.and_then(|name| FIX_MODES.iter().find(|(known, _)| *known == name));
+    if let Some((name, mode)) = fix_mode {
+        mask |= CfgNav5Params::POS_FIX_MODE;
+        builder.fix_mode = *mode;
+        changes.push(format!("{} fix mode", name));
+    }
+    if let Some(degrees) = min_elev_degrees {
+        mask |= CfgNav5Params::MIN_EL;
+        builder.min_elev_degrees = degrees;
+        changes.push(format!("{}° elevation mask", degrees));
+    }
+    builder.mask = mask;
+    info!("Setting the navigation engine: {} ...", changes.join(", "));
+    device
+        .write_all(&builder.into_packet_bytes())
+        .map_err(|e| format!("Failed to write UBX-CFG-NAV5: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x24, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the navigation engine settings (UBX-ACK-NAK of UBX-CFG-NAV5): {}",
+                changes.join(", ")
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-NAV5 not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x24, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Nav5::parse(&payload));
+    Ok(applied)
+}
+
+/// Configuration block of a constellation in UBX-CFG-GNSS: gnssId, reserved and maximum
+/// tracking channels, reserved byte and flags, the enable flag in bit 0
+const GNSS_BLOCK_LEN: usize = 8;
+
+/// Header of UBX-CFG-GNSS: msgVer, numTrkChHw, numTrkChUse, numConfigBlocks
+const GNSS_HEADER_LEN: usize = 4;
+
+pub fn constellation_name(gnss_id: u8) -> String {
+    CONSTELLATIONS
+        .iter()
+        .find(|(_, id)| *id == gnss_id)
+        .map_or(format!("gnssId {}", gnss_id), |(name, _)| name.to_string())
+}
+
+/// Constellations of a UBX-CFG-GNSS payload, with their channel allocations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gnss {
+    payload: Vec<u8>,
+}
+
+impl Gnss {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let blocks = usize::from(*payload.get(3)?);
+        if payload.len() < GNSS_HEADER_LEN + blocks * GNSS_BLOCK_LEN {
+            return None;
+        }
+        Some(Self {
+            payload: payload[..GNSS_HEADER_LEN + blocks * GNSS_BLOCK_LEN].to_vec(),
+        })
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = &[u8]> {
+        self.payload[GNSS_HEADER_LEN..].chunks_exact(GNSS_BLOCK_LEN)
+    }
+
+    /// gnssId of the constellations enabled
+    pub fn enabled(&self) -> Vec<u8> {
+        self.blocks()
+            .filter(|block| block[4] & 0x01 != 0)
+            .map(|block| block[0])
+            .collect()
+    }
+
+    /// Enables the constellations given, disabling the others, the channel allocations and
+    /// signals kept. An error for the constellations the receiver has no block of
+    pub fn select(&mut self, gnss_ids: &[u8]) -> Result<(), String> {
+        let missing = gnss_ids
+            .iter()
+            .filter(|id| !self.blocks().any(|block| block[0] == **id))
+            .map(|id| constellation_name(*id))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(format!(
+                "The receiver does not support {} (no UBX-CFG-GNSS block), it has {}",
+                missing.join(", "),
+                self.blocks()
+                    .map(|block| constellation_name(block[0]))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        for block in self.payload[GNSS_HEADER_LEN..].chunks_exact_mut(GNSS_BLOCK_LEN) {
+            if gnss_ids.contains(&block[0]) {
+                block[4] |= 0x01;
+            } else {
+                block[4] &= !0x01;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like "gps, galileo enabled, sbas, glonass disabled"
+    pub fn describe(&self) -> String {
+        let enabled = self.enabled();
+        let (on, off): (Vec<_>, Vec<_>) = self
+            .blocks()
+            .map(|block| block[0])
+            .partition(|id| enabled.contains(id));
+        let names = |ids: Vec<u8>| {
+            ids.into_iter()
+                .map(constellation_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if off.is_empty() {
+            format!("{} enabled", names(on))
+        } else {
+            format!("{} enabled, {} disabled", names(on), names(off))
+        }
+    }
+}
+
+/// Enables the constellations given and disables the others, keeping the channel
+/// allocations of the current UBX-CFG-GNSS, returning the constellations polled back
+pub fn set_gnss(device: &mut Device, names: &[String]) -> Result<Option<Gnss>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x3e, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the constellations (UBX-CFG-GNSS): {}", e))?;
+    let mut gnss = Gnss::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-GNSS of {} bytes", payload.len()))?;
+    let gnss_ids = names
+        .iter()
+        .filter_map(|name| CONSTELLATIONS.iter().find(|(known, _)| known == name))
+        .map(|(_, id)| *id)
+        .collect::<Vec<_>>();
+    gnss.select(&gnss_ids)?;
+    info!("Setting the constellations: {} ...", gnss.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x3e, &gnss.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-GNSS: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x3e, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the constellations (UBX-ACK-NAK of UBX-CFG-GNSS): {}. \
+The combination may exceed the tracking channels or not be supported by the firmware",
+                gnss.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-GNSS not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x3e, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Gnss::parse(&payload));
+    Ok(applied)
+}
+
+/// SBAS settings, from a UBX-CFG-SBAS payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sbas {
+    /// Enable flag in bit 0, test mode in bit 1
+    pub mode: u8,
+    /// Uses of the corrections, as in SBAS_MODES
+    pub usage: u8,
+    /// SBAS satellites tracked at most
+    pub max_sbas: u8,
+    /// PRNs searched, bit 0 being PRN 120, none meaning all of them
+    pub prns: u64,
+}
+
+impl Sbas {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let scanmode1 = u32::from_le_bytes(payload.get(4..8)?.try_into().ok()?);
+        Some(Self {
+            mode: *payload.first()?,
+            usage: *payload.get(1)?,
+            max_sbas: *payload.get(2)?,
+            prns: u64::from(scanmode1) | u64::from(*payload.get(3)?) << 32,
+        })
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = vec![self.mode, self.usage, self.max_sbas, (self.prns >> 32) as u8];
+        payload.extend_from_slice(&(self.prns as u32).to_le_bytes());
+        payload
+    }
+
+    /// Like "enabled, used for correction, integrity, PRNs 123, 136, 3 tracked at most"
+    pub fn describe(&self) -> String {
+        if self.mode & 0x01 == 0 {
+            return "disabled".to_string();
+        }
+        let modes = SBAS_MODES
+            .iter()
+            .filter(|(_, bit)| self.usage & bit != 0)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        let prns = SBAS_PRNS
+            .filter(|prn| self.prns & 1 << (prn - SBAS_PRNS.start()) != 0)
+            .map(|prn| prn.to_string())
+            .collect::<Vec<_>>();
+        let modes = if modes.is_empty() {
+            "nothing".to_string()
+        } else {
+            modes.join(", ")
+        };
+        let prns = if prns.is_empty() {
+            "all PRNs".to_string()
+        } else {
+            format!("PRNs {}", prns.join(", "))
+        };
+        format!(
+            "enabled, used for {}, {}, {} tracked at most",
+            modes, prns, self.max_sbas
+        )
+    }
+}
+
+/// Sets the SBAS settings given, keeping the others, returning the settings polled back
+pub fn set_sbas(
+    device: &mut Device,
+    enabled: Option<bool>,
+    modes: Option<&[String]>,
+    prns: Option<&[u8]>,
+) -> Result<Option<Sbas>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x16, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the SBAS settings (UBX-CFG-SBAS): {}", e))?;
+    let mut sbas = Sbas::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-SBAS of {} bytes", payload.len()))?;
+    match enabled {
+        Some(true) => sbas.mode |= 0x01,
+        Some(false) => sbas.mode &= !0x01,
+        None => {},
+    }
+    if let Some(modes) = modes {
+        sbas.usage = SBAS_MODES
+            .iter()
+            .filter(|(name, _)| modes.iter().any(|mode| mode == name))
+            .fold(0, |usage, (_, bit)| usage | bit);
+    }
+    if let Some(prns) = prns {
+        sbas.prns = prns
+            .iter()
+            .fold(0, |mask, prn| mask | 1 << (prn - SBAS_PRNS.start()));
+    }
+    info!("Setting SBAS: {} ...", sbas.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x16, &sbas.payload()))
+        .map_err(|e| format!("Failed to write UBX-CFG-SBAS: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x16, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the SBAS settings (UBX-ACK-NAK of UBX-CFG-SBAS): {}",
+                sbas.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-SBAS not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x16, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Sbas::parse(&payload));
+    Ok(applied)
+}
@@ -0,0 +1,248 @@
+//! Network outputs: TCP server of the validated frames and UDP sender of the stream
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+
+use crate::protocol::{Protocol, ProtocolScanner};
+use crate::sink::Sink;
+
+/// Data queued per TCP client, slower clients being disconnected
+const CLIENT_MAX_PENDING: usize = 256 * 1024;
+
+/// Largest datagram sent unfragmented over Ethernet: MTU of 1500 bytes minus the IPv4
+/// and UDP headers
+const SAFE_DATAGRAM_LEN: usize = 1472;
+
+struct Client {
+    stream: TcpStream,
+    peer: SocketAddr,
+    pending: VecDeque<u8>,
+    bytes: usize,
+}
+
+impl Client {
+    /// Writes as much pending data as the client accepts
+    fn drain(&mut self) -> std::io::Result<()> {
+        loop {
+            let (data, _) = self.pending.as_slices();
+            if data.is_empty() {
+                return Ok(());
+            }
+            match self.stream.write(data) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(size) => {
+                    self.pending.drain(..size);
+                    self.bytes += size;
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Streams the valid UBX, NMEA and RTCM3 frames received to every connected TCP client,
+/// without blocking. Clients connecting get the frames following, from a frame start
+pub struct TcpServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    scanner: ProtocolScanner,
+    /// Clients connected since the start
+    served: usize,
+}
+
+impl TcpServer {
+    pub fn bind(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!("serving TCP clients on {}", listener.local_addr()?);
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+            scanner: ProtocolScanner::default(),
+            served: 0,
+        })
+    }
+
+    fn accept(&mut self) -> std::io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    stream.set_nonblocking(true)?;
+                    info!("TCP client {} connected", peer);
+                    self.served += 1;
+                    self.clients.push(Client {
+                        stream,
+                        peer,
+                        pending: VecDeque::new(),
+                        bytes: 0,
+                    });
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Sink for TcpServer {
+    fn name(&self) -> String {
+        format!("TCP server {}", self.listener.local_addr().unwrap())
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.accept()?;
+        let mut frames = Vec::new();
+        self.scanner.scan(data, |protocol, frame| {
+            if protocol.is_some() {
+                frames.extend_from_slice(frame);
+            }
+        });
+        // client failures only affect the client
+        self.clients.retain_mut(|client| {
+            client.pending.extend(&frames);
+            if let Err(e) = client.drain() {
+                warn!(
+                    "TCP client {} disconnected after {} bytes: {}",
+                    client.peer, client.bytes, e
+                );
+                return false;
+            }
+            if client.pending.len() > CLIENT_MAX_PENDING {
+                warn!(
+                    "TCP client {} too slow, disconnected after {} bytes",
+                    client.peer, client.bytes
+                );
+                return false;
+            }
+            true
+        });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        for client in &mut self.clients {
+            // the frames still pending are given up
+            let _ = client.drain();
+            info!(
+                "TCP client {} disconnected after {} bytes: end of the recording",
+                client.peer, client.bytes
+            );
+        }
+        info!("{}: {} client(s) served", self.name(), self.served);
+        Ok(())
+    }
+}
+
+/// Content of the datagrams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpMode {
+    /// One valid UBX, NMEA or RTCM3 frame each
+    Frames,
+    /// The bytes of one read each, as received
+    Reads,
+}
+
+/// Sends the received stream as UDP datagrams to a unicast, broadcast or multicast address
+pub struct UdpSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+    mode: UdpMode,
+    scanner: ProtocolScanner,
+    /// UBX classes forwarded, other frames being dropped; all frames when None
+    classes: Option<Vec<u8>>,
+    datagrams: u64,
+    /// Datagrams larger than SAFE_DATAGRAM_LEN, sent regardless
+    oversized: u64,
+}
+
+impl UdpSender {
+    pub fn connect(address: &str, mode: UdpMode, classes: Option<Vec<u8>>) -> std::io::Result<Self> {
+        let target = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("\"{}\" resolves to no address", address),
+            )
+        })?;
+        let local = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        info!("sending UDP datagrams to {}", target);
+        Ok(Self {
+            socket,
+            target,
+            mode,
+            scanner: ProtocolScanner::default(),
+            classes,
+            datagrams: 0,
+            oversized: 0,
+        })
+    }
+
+    fn send(&mut self, datagram: &[u8]) -> std::io::Result<()> {
+        if datagram.len() > SAFE_DATAGRAM_LEN {
+            if self.oversized == 0 {
+                warn!(
+                    "{}: sending a {} bytes datagram, larger than the {} bytes fitting an \
+Ethernet frame: it is fragmented, or dropped by some networks",
+                    self.name(),
+                    datagram.len(),
+                    SAFE_DATAGRAM_LEN
+                );
+            }
+            self.oversized += 1;
+        }
+        self.datagrams += 1;
+        self.socket.send_to(datagram, self.target).map(|_| ())
+    }
+}
+
+impl Sink for UdpSender {
+    fn name(&self) -> String {
+        format!("UDP sender {}", self.target)
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if self.mode == UdpMode::Reads {
+            if data.is_empty() {
+                return Ok(());
+            }
+            return self.send(data);
+        }
+        let mut frames = Vec::new();
+        let classes = &self.classes;
+        self.scanner.scan(data, |protocol, frame| {
+            let forwarded = match (protocol, classes) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(Protocol::Ubx), Some(classes)) => classes.contains(&frame[2]),
+                (Some(_), Some(_)) => false,
+            };
+            if forwarded {
+                frames.push(frame.to_vec());
+            }
+        });
+        for frame in frames {
+            self.send(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        info!(
+            "{}: {} datagrams sent, {} larger than {} bytes",
+            self.name(),
+            self.datagrams,
+            self.oversized,
+            SAFE_DATAGRAM_LEN
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Odometer of the receiver: its settings (UBX-CFG-ODO) for the configure odometer
+//! subcommand, its reset (UBX-NAV-RESETODO, `--reset-odometer`) and the distances of the
+//! UBX-NAV-ODO messages, printed at each epoch with `--print-odo`
+
+use ublox::PacketRef;
+
+use crate::frame;
+use crate::sink::Sink;
+use crate::Device;
+
+/// Odometer profiles of CFG-ODO, by name
+pub const PROFILES: [(&str, u8); 4] = [
+    ("running", 0),
+    ("cycling", 1),
+    ("swimming", 2),
+    ("car", 3),
+];
+
+/// Length of a CFG-ODO payload
+const ODO_LEN: usize = 20;
+
+/// CFG-ODO flags: odometer and low-speed course over ground filter enabled
+const USE_ODO: u8 = 0x01;
+const USE_COG: u8 = 0x02;
+
+/// Profile bits of the odoCfg field
+const PROFILE_MASK: u8 = 0x07;
+
+/// Odometer settings, from a UBX-CFG-ODO payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Odo {
+    payload: [u8; ODO_LEN],
+}
+
+impl Odo {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..ODO_LEN)?.try_into().ok()?,
+        })
+    }
+
+    /// Like "odometer on, car profile, low-speed COG filter off"
+    pub fn describe(&self) -> String {
+        let on = |flag: u8| if self.payload[4] & flag != 0 { "on" } else { "off" };
+        let profile = self.payload[5] & PROFILE_MASK;
+        format!(
+            "odometer {}, {} profile, low-speed COG filter {}",
+            on(USE_ODO),
+            PROFILES
+                .iter()
+                .find(|(_, known)| *known == profile)
+                .map_or("custom", |(name, _)| name),
+            on(USE_COG)
+        )
+    }
+}
+
+/// Enables the odometer, with the `profile` given and the low-speed COG filter when
+/// `low_speed_filter` is set, keeping the other settings, returning the settings polled back
+pub fn set(
+    device: &mut Device,
+    profile: Option<&str>,
+    low_speed_filter: bool,
+) -> Result<Option<Odo>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x1e, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the odometer settings (UBX-CFG-ODO): {}", e))?;
+    let mut odo = Odo::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-ODO of {} bytes", payload.len()))?;
+    odo.payload[4] |= USE_ODO;
+    if low_speed_filter {
+        odo.payload[4] |= USE_COG;
+    }
+    if let Some(name) = profile {
+        let (_, profile) = PROFILES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .ok_or_else(|| format!("unknown odometer profile \"{}\"", name))?;
+        odo.payload[5] = odo.payload[5] & !PROFILE_MASK | profile;
+    }
+    info!("Setting the odometer: {} ...", odo.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x1e, &odo.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-ODO: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x1e, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the odometer settings (UBX-ACK-NAK of UBX-CFG-ODO): {}",
+                odo.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-ODO not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x1e, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Odo::parse(&payload));
+    Ok(applied)
+}
+
+/// Resets the distance of the odometer, the total distance being kept
+pub fn reset(device: &mut Device) -> Result<(), String> {
+    info!("Resetting the odometer ...");
+    device
+        .write_all(&frame::frame(0x01, 0x10, &[]))
+        .map_err(|e| format!("Failed to write UBX-NAV-RESETODO: {}", e))?;
+    match device.wait_for_ack_of(0x01, 0x10, device.ack_timeout()) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(
+            "The receiver rejected the odometer reset (UBX-ACK-NAK of UBX-NAV-RESETODO)".to_string(),
+        ),
+        Err(e) => Err(format!("UBX-NAV-RESETODO not acknowledged: {}", e)),
+    }
+}
+
+/// Prints the distances of the NAV-ODO messages
+pub struct OdoPrinter;
+
+impl Sink for OdoPrinter {
+    fn name(&self) -> String {
+        "odometer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        if let PacketRef::NavOdo(odo) = packet {
+            info!(
+                "odometer: {} m (±{} m) since the reset, {} m in total, iTOW {} ms",
+                odo.distance(),
+                odo.distance_std(),
+                odo.total_distance(),
+                odo.itow()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Persistence of the receiver configuration (UBX-CFG-CFG): the settings applied are lost at
+//! power cycle unless saved to the battery backed RAM and flash, from which they can be
+//! reloaded, and cleared back to the factory defaults
+
+use crate::frame;
+use crate::Device;
+
+/// Configuration sections: port settings, message rates, navigation settings (rates,
+/// engine, constellations, SBAS, time pulse and base station mode), power settings and
+/// antenna supervisor
+const IO_PORT: u32 = 0x01;
+const MSG_CONF: u32 = 0x02;
+const NAV_CONF: u32 = 0x08;
+const RXM_CONF: u32 = 0x10;
+const ANT_CONF: u32 = 0x400;
+const SAVED_SECTIONS: u32 = IO_PORT | MSG_CONF | NAV_CONF | RXM_CONF | ANT_CONF;
+
+/// Every section, for reloading and clearing
+const ALL_SECTIONS: u32 = 0x1f1f;
+
+/// Battery backed RAM and flash
+const DEVICES: u8 = 0x03;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Saves the current configuration
+    Save,
+    /// Reloads the saved configuration
+    Revert,
+    /// Clears the saved configuration and reloads the defaults
+    FactoryReset,
+}
+
+impl Action {
+    fn masks(self) -> (u32, u32, u32) {
+        // clear, save and load masks
+        match self {
+            Self::Save => (0, SAVED_SECTIONS, 0),
+            Self::Revert => (0, 0, ALL_SECTIONS),
+            Self::FactoryReset => (ALL_SECTIONS, 0, ALL_SECTIONS),
+        }
+    }
+
+    /// Like "Configuration saved"
+    pub fn outcome(self) -> &'static str {
+        match self {
+            Self::Save => "Configuration saved",
+            Self::Revert => "Saved configuration reloaded",
+            Self::FactoryReset => "Configuration reset to the factory defaults",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Save => {
+                "saving the port, message, navigation, power and antenna settings to BBR and flash"
+            },
+            Self::Revert => "reloading the saved configuration",
+            Self::FactoryReset => "clearing the saved configuration back to the factory defaults",
+        }
+    }
+}
+
+/// Sends the CFG-CFG of the action, failing unless acknowledged
+pub fn apply(device: &mut Device, action: Action) -> Result<(), String> {
+    let (clear, save, load) = action.masks();
+    let mut payload = Vec::with_capacity(13);
+    payload.extend_from_slice(&clear.to_le_bytes());
+    payload.extend_from_slice(&save.to_le_bytes());
+    payload.extend_from_slice(&load.to_le_bytes());
+    payload.push(DEVICES);
+    info!("Configuration: {} ...", action.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x09, &payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-CFG: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x09, device.ack_timeout()) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!(
+            "The receiver rejected {} (UBX-ACK-NAK of UBX-CFG-CFG)",
+            action.describe()
+        )),
+        Err(e) if action == Action::Save => Err(format!("UBX-CFG-CFG not acknowledged: {}", e)),
+        Err(e) => Err(format!(
+            "UBX-CFG-CFG not acknowledged: {}, the port settings reloaded may differ from the \
+current ones",
+            e
+        )),
+    }
+}
@@ -0,0 +1,446 @@
+//! Port settings of the configure subcommand (UBX-CFG-PRT): the current settings of the
+//! selected port are polled and printed, and only the fields given on the command line are
+//! changed, the baud rate, frame format and protocols not given being kept. The SPI and I2C
+//! (DDC) ports have their own mode fields instead of the frame format: the SPI mode and the
+//! number of 0xFF bytes ending a transfer, and the I2C slave address
+
+use std::time::Duration;
+
+use crate::frame;
+use crate::Device;
+
+/// Ports by name, with their id
+pub const PORT_IDS: [(&str, u8); 5] = [
+    ("i2c", 0),
+    ("uart1", 1),
+    ("uart2", 2),
+    ("usb", 3),
+    ("spi", 4),
+];
+
+/// Length of a CFG-PRT payload
+const PRT_LEN: usize = 20;
+
+const PORT_DDC: u8 = 0;
+const PORT_USB: u8 = 3;
+const PORT_SPI: u8 = 4;
+
+/// SPI fields of the mode: clock polarity and phase, and 0xFF bytes before the transfer ends
+const SPI_MODE_SHIFT: u32 = 1;
+const SPI_MODE_MASK: u32 = 0b11 << SPI_MODE_SHIFT;
+const FF_COUNT_SHIFT: u32 = 8;
+const FF_COUNT_MASK: u32 = 0b11_1111 << FF_COUNT_SHIFT;
+pub const MAX_FF_COUNT: u8 = 63;
+
+/// I2C field of the mode: 7 bits slave address
+const SLAVE_ADDR_SHIFT: u32 = 1;
+const SLAVE_ADDR_MASK: u32 = 0x7f << SLAVE_ADDR_SHIFT;
+/// Addresses out of the reserved ones
+const SLAVE_ADDRS: std::ops::RangeInclusive<u8> = 0x08..=0x77;
+
+/// Frame format fields of the mode: character length, parity and stop bits
+const CHAR_LEN_SHIFT: u32 = 6;
+const CHAR_LEN_MASK: u32 = 0b11 << CHAR_LEN_SHIFT;
+const PARITY_SHIFT: u32 = 9;
+const PARITY_MASK: u32 = 0b111 << PARITY_SHIFT;
+const STOP_BITS_SHIFT: u32 = 12;
+const STOP_BITS_MASK: u32 = 0b11 << STOP_BITS_SHIFT;
+
+/// Parities by name, with their mode bits, "none" being 0b10x
+pub const PARITIES: [(&str, u32); 3] = [("even", 0b000), ("odd", 0b001), ("none", 0b100)];
+
+/// Stop bits by name, with their mode bits
+const STOP_BITS: [(&str, u32); 4] = [("1", 0b00), ("1.5", 0b01), ("2", 0b10), ("0.5", 0b11)];
+
+const PROTO_UBX: u16 = 0x01;
+
+/// inProtoMask and outProtoMask bits, RTCM2 being input only
+const PROTOCOLS: [(&str, u16); 5] = [
+    ("ubx", PROTO_UBX),
+    ("nmea", 0x02),
+    ("rtcm2", 0x04),
+    ("rtcm3", 0x20),
+    ("spartn", 0x40),
+];
+
+/// Parses a 7 bits I2C address, in hexadecimal like 0x42 or in decimal
+pub fn parse_i2c_address(s: &str) -> Result<u8, String> {
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse::<u8>(),
+    }
+    .map_err(|e| e.to_string())?;
+    if !SLAVE_ADDRS.contains(&address) {
+        return Err(format!(
+            "the I2C addresses are from 0x{:02x} to 0x{:02x}, the others being reserved",
+            SLAVE_ADDRS.start(),
+            SLAVE_ADDRS.end()
+        ));
+    }
+    Ok(address)
+}
+
+/// Like "ubx+nmea", "none" or "ubx+0x100" for unknown bits
+fn describe_mask(mask: u16) -> String {
+    let mut names = PROTOCOLS
+        .iter()
+        .filter(|(_, bit)| mask & bit != 0)
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    let unknown = PROTOCOLS.iter().fold(mask, |mask, (_, bit)| mask & !bit);
+    if unknown != 0 {
+        names.push(format!("0x{:x}", unknown));
+    }
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join("+")
+    }
+}
+
+/// Port settings given on the command line or in a profile, the others being kept
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Request {
+    pub baud: Option<u32>,
+    pub data_bits: Option<u8>,
+    pub parity: Option<u32>,
+    pub two_stop_bits: Option<bool>,
+    pub in_proto: Option<u16>,
+    pub out_proto: Option<u16>,
+    /// SPI mode, 0 to 3
+    pub spi_mode: Option<u8>,
+    /// 0xFF bytes ending an SPI transfer
+    pub ff_count: Option<u8>,
+    pub i2c_address: Option<u8>,
+}
+
+impl Request {
+    /// Whether the settings of a UART are given, which the other ports do not have
+    fn uart_given(&self) -> bool {
+        self.baud.is_some()
+            || self.data_bits.is_some()
+            || self.parity.is_some()
+            || self.two_stop_bits.is_some()
+    }
+
+    /// Fails on the settings the port does not have, like the SPI mode of a UART
+    pub fn check(&self, port_id: u8) -> Result<(), String> {
+        let port = PORT_IDS
+            .iter()
+            .find(|(_, id)| *id == port_id)
+            .map_or("the port".to_string(), |(name, _)| name.to_uppercase());
+        if self.uart_given() && matches!(port_id, PORT_DDC | PORT_USB | PORT_SPI) {
+            return Err(format!(
+                "{} has no baud rate or frame format: no baud, stop bits, data bits or parity",
+                port
+            ));
+        }
+        if (self.spi_mode.is_some() || self.ff_count.is_some()) && port_id != PORT_SPI {
+            return Err(format!("--spi-mode and --spi-ff-count apply to SPI, not {}", port));
+        }
+        if self.i2c_address.is_some() && port_id != PORT_DDC {
+            return Err(format!("--i2c-address applies to I2C, not {}", port));
+        }
+        Ok(())
+    }
+}
+
+/// Settings of a port, from a UBX-CFG-PRT payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortConfig {
+    payload: [u8; PRT_LEN],
+}
+
+impl PortConfig {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..PRT_LEN)?.try_into().ok()?,
+        })
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    fn field(&self, offset: usize) -> u32 {
+        let bytes = &self.payload[offset..offset + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn set_field(&mut self, offset: usize, value: u32) {
+        self.payload[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn half_field(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.payload[offset], self.payload[offset + 1]])
+    }
+
+    fn set_half_field(&mut self, offset: usize, value: u16) {
+        self.payload[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn out_proto_mask(&self) -> u16 {
+        self.half_field(14)
+    }
+
+    /// Like "8N1", from the mode bits
+    fn frame_format(&self) -> String {
+        let mode = self.field(4);
+        let data_bits = 5 + ((mode & CHAR_LEN_MASK) >> CHAR_LEN_SHIFT);
+        let parity = match (mode & PARITY_MASK) >> PARITY_SHIFT {
+            0b000 => "E",
+            0b001 => "O",
+            0b100 | 0b101 => "N",
+            _ => "?",
+        };
+        let stop_bits = (mode & STOP_BITS_MASK) >> STOP_BITS_SHIFT;
+        let (stop_bits, _) = STOP_BITS
+            .iter()
+            .find(|(_, bits)| *bits == stop_bits)
+            .unwrap();
+        format!("{}{}{}", data_bits, parity, stop_bits)
+    }
+
+    /// Like "9600 baud, 8N1 (mode 0x000008c0), in ubx+nmea+rtcm3, out ubx+nmea, flags 0x0000"
+    pub fn describe(&self) -> String {
+        let protocols = format!(
+            "in {}, out {}, flags 0x{:04x}",
+            describe_mask(self.half_field(12)),
+            describe_mask(self.out_proto_mask()),
+            self.half_field(16)
+        );
+        let mode = self.field(4);
+        match self.payload[0] {
+            PORT_USB => return protocols,
+            PORT_SPI => {
+                return format!(
+                    "SPI mode {}, {} 0xFF bytes ending a transfer (mode 0x{:08x}), {}",
+                    (mode & SPI_MODE_MASK) >> SPI_MODE_SHIFT,
+                    (mode & FF_COUNT_MASK) >> FF_COUNT_SHIFT,
+                    mode,
+                    protocols
+                )
+            },
+            PORT_DDC => {
+                return format!(
+                    "address 0x{:02x} (mode 0x{:08x}), {}",
+                    (mode & SLAVE_ADDR_MASK) >> SLAVE_ADDR_SHIFT,
+                    mode,
+                    protocols
+                )
+            },
+            _ => {},
+        }
+        format!(
+            "{} baud, {} (mode 0x{:08x}), {}",
+            self.field(8),
+            self.frame_format(),
+            self.field(4),
+            protocols
+        )
+    }
+
+    /// Applies the settings given, keeping the others, the request being checked against the
+    /// port first
+    pub fn apply(&mut self, request: &Request) {
+        let mut mode = self.field(4);
+        match self.payload[0] {
+            PORT_SPI => {
+                if let Some(spi_mode) = request.spi_mode {
+                    mode = mode & !SPI_MODE_MASK | u32::from(spi_mode) << SPI_MODE_SHIFT;
+                }
+                if let Some(count) = request.ff_count {
+                    mode = mode & !FF_COUNT_MASK | u32::from(count) << FF_COUNT_SHIFT;
+                }
+            },
+            PORT_DDC => {
+                if let Some(address) = request.i2c_address {
+                    mode = mode & !SLAVE_ADDR_MASK | u32::from(address) << SLAVE_ADDR_SHIFT;
+                }
+            },
+            // CFG-PRT-USB: tx-ready and protocols, the mode and baud rate fields being reserved
+            PORT_USB => {
+                mode = 0;
+                self.set_field(8, 0);
+            },
+            _ => {
+                if let Some(data_bits) = request.data_bits {
+                    mode = mode & !CHAR_LEN_MASK | u32::from(data_bits - 5) << CHAR_LEN_SHIFT;
+                }
+                if let Some(parity) = request.parity {
+                    mode = mode & !PARITY_MASK | parity << PARITY_SHIFT;
+                }
+                if let Some(two) = request.two_stop_bits {
+                    let bits = if two { 0b10 } else { 0b00 };
+                    mode = mode & !STOP_BITS_MASK | bits << STOP_BITS_SHIFT;
+                }
+                if let Some(baud) = request.baud {
+                    self.set_field(8, baud);
+                }
+            },
+        }
+        self.set_field(4, mode);
+        if let Some(mask) = request.in_proto {
+            self.set_half_field(12, mask);
+        }
+        if let Some(mask) = request.out_proto {
+            self.set_half_field(14, mask);
+        }
+    }
+}
+
+/// Polls the settings of a port, waiting up to `timeout`
+pub fn poll(device: &mut Device, port_id: u8, timeout: Duration) -> Result<PortConfig, String> {
+    let payload = device
+        .poll_payload(0x06, 0x00, &[port_id], timeout)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::Unsupported => {
+                "The receiver has no such port (UBX-ACK-NAK of the UBX-CFG-PRT poll)".to_string()
+            },
+            _ => format!("Failed to read the port configuration (UBX-CFG-PRT): {}", e),
+        })?;
+    PortConfig::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-PRT of {} bytes", payload.len()))
+}
+
+/// Writes the settings of a port, waiting up to `timeout` for the acknowledgment, unless
+/// the port does not output UBX anymore
+pub fn write(device: &mut Device, port: &PortConfig, timeout: Duration) -> Result<(), String> {
+    device
+        .write_all(&frame::frame(0x06, 0x00, port.payload()))
+        .map_err(|e| format!("Failed to write UBX-CFG-PRT: {}", e))?;
+    if port.out_proto_mask() & PROTO_UBX == 0 {
+        return Ok(());
+    }
+    match device.wait_for_ack_of(0x06, 0x00, timeout) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!(
+            "The receiver rejected the port settings (UBX-ACK-NAK of UBX-CFG-PRT): {}",
+            port.describe()
+        )),
+        Err(e) => Err(format!("UBX-CFG-PRT not acknowledged: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{Memory, Port};
+
+    /// UART1 at 9600 baud 8N1, in ubx+nmea+rtcm3, out ubx+nmea
+    fn uart1() -> PortConfig {
+        let mut payload = [0; PRT_LEN];
+        payload[0] = 1;
+        payload[4..8].copy_from_slice(&0x0000_08c0_u32.to_le_bytes());
+        payload[8..12].copy_from_slice(&9600_u32.to_le_bytes());
+        payload[12..14].copy_from_slice(&0x23_u16.to_le_bytes());
+        payload[14..16].copy_from_slice(&0x03_u16.to_le_bytes());
+        PortConfig::parse(&payload).unwrap()
+    }
+
+    #[test]
+    fn masks_given_are_set() {
+        let mut port = uart1();
+        port.apply(&Request {
+            out_proto: Some(PROTO_UBX),
+            ..Default::default()
+        });
+        // the input mask is kept
+        assert_eq!(port.half_field(12), 0x23);
+        assert_eq!(port.out_proto_mask(), 0x01);
+        port.apply(&Request {
+            in_proto: Some(0x02),
+            out_proto: Some(0x22),
+            ..Default::default()
+        });
+        assert_eq!((port.half_field(12), port.out_proto_mask()), (0x02, 0x22));
+        // nothing given, nothing changed
+        let mut port = uart1();
+        port.apply(&Request::default());
+        assert_eq!(port, uart1());
+    }
+
+    #[test]
+    fn mask_descriptions() {
+        assert_eq!(describe_mask(0x23), "ubx+nmea+rtcm3");
+        assert_eq!(describe_mask(0x44), "rtcm2+spartn");
+        assert_eq!(describe_mask(0x101), "ubx+0x100");
+        assert_eq!(describe_mask(0), "none");
+        assert_eq!(
+            uart1().describe(),
+            "9600 baud, 8N1 (mode 0x000008c0), in ubx+nmea+rtcm3, out ubx+nmea, flags 0x0000"
+        );
+    }
+
+    /// Bytes written by [write] to a receiver acknowledging them
+    fn written(port: &PortConfig) -> Vec<u8> {
+        let mut device = Device::new(Port::Memory(Box::new(Memory {
+            input: std::io::Cursor::new(frame::frame(0x05, 0x01, &[0x06, 0x00])),
+            ..Default::default()
+        })));
+        write(&mut device, port, Duration::from_millis(50)).unwrap();
+        match device.port {
+            Port::Memory(memory) => memory.output,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn uart_frame() {
+        let mut port = uart1();
+        let request = Request {
+            baud: Some(115200),
+            data_bits: Some(7),
+            parity: Some(0b000),
+            two_stop_bits: Some(true),
+            out_proto: Some(PROTO_UBX),
+            ..Default::default()
+        };
+        request.check(1).unwrap();
+        port.apply(&request);
+        assert_eq!(
+            port.describe(),
+            "115200 baud, 7E2 (mode 0x00002080), in ubx+nmea+rtcm3, out ubx, flags 0x0000"
+        );
+        assert_eq!(
+            written(&port),
+            [
+                0xb5, 0x62, 0x06, 0x00, 0x14, 0x00, 0x01, 0x00, 0x00, 0x00, 0x80, 0x20, 0x00, 0x00,
+                0x00, 0xc2, 0x01, 0x00, 0x23, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xa2, 0xba,
+            ]
+        );
+    }
+
+    #[test]
+    fn usb_frame() {
+        // USB as polled from a receiver reporting UART fields, tx-ready 0x0011
+        let mut payload = uart1().payload;
+        payload[0] = PORT_USB;
+        payload[2] = 0x11;
+        let mut port = PortConfig::parse(&payload).unwrap();
+        let request = Request {
+            in_proto: Some(0x21),
+            out_proto: Some(PROTO_UBX),
+            ..Default::default()
+        };
+        request.check(PORT_USB).unwrap();
+        port.apply(&request);
+        assert_eq!(port.describe(), "in ubx+rtcm3, out ubx, flags 0x0000");
+        // the mode and baud rate fields are cleared, tx-ready is kept
+        assert_eq!(
+            written(&port),
+            [
+                0xb5, 0x62, 0x06, 0x00, 0x14, 0x00, 0x03, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x50, 0xc4,
+            ]
+        );
+        let baud = Request {
+            baud: Some(9600),
+            ..Default::default()
+        };
+        assert_eq!(
+            baud.check(PORT_USB).unwrap_err(),
+            "USB has no baud rate or frame format: no baud, stop bits, data bits or parity"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Power settings of the configure subcommand, like for trackers logging in bursts: the
+//! power setup (UBX-CFG-PMS), from full power to the interval and aggressive modes, or for
+//! finer control the update period and on time of the power save mode (UBX-CFG-PM2, enabled
+//! with UBX-CFG-RXM). The settings not given are kept, and the applied ones are polled back
+//! once acknowledged
+
+use std::time::Duration;
+
+use crate::frame;
+use crate::Device;
+
+/// Power setups of CFG-PMS, by name, with their powerSetupValue
+pub const POWER_MODES: [(&str, u8); 6] = [
+    ("full", 0x00),
+    ("balanced", 0x01),
+    ("interval", 0x02),
+    ("aggressive-1hz", 0x03),
+    ("aggressive-2hz", 0x04),
+    ("aggressive-4hz", 0x05),
+];
+
+const MODE_FULL: u8 = 0x00;
+const MODE_INTERVAL: u8 = 0x02;
+
+/// Length of a CFG-PMS payload
+const PMS_LEN: usize = 8;
+
+/// Shortest CFG-PM2 payload, of its version 1
+const PM2_LEN: usize = 44;
+
+/// Operation mode of the CFG-PM2 flags: ON/OFF (0) or cyclic tracking (1)
+const PM2_MODE_SHIFT: u32 = 17;
+const PM2_MODE_MASK: u32 = 0b11 << PM2_MODE_SHIFT;
+
+/// CFG-RXM payload selecting the power save mode, its first byte being reserved as 8
+const RXM_POWER_SAVE: [u8; 2] = [0x08, 0x01];
+
+fn warn_raw_output() {
+    warn!(
+        "Power save modes stop or thin the raw measurement output (UBX-RXM-RAWX, UBX-RXM-SFRBX) \
+on some firmware"
+    );
+}
+
+fn whole_seconds(name: &str, duration: Duration) -> Result<u16, String> {
+    if duration.subsec_nanos() != 0 {
+        return Err(format!("{} of {:?} is not whole seconds", name, duration));
+    }
+    u16::try_from(duration.as_secs()).map_err(|_| format!("{} of {:?} is too long", name, duration))
+}
+
+/// Power setup, from a UBX-CFG-PMS payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pms {
+    payload: [u8; PMS_LEN],
+}
+
+impl Pms {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..PMS_LEN)?.try_into().ok()?,
+        })
+    }
+
+    fn mode(&self) -> u8 {
+        self.payload[1]
+    }
+
+    /// Like "interval, on 10 s every 60 s" or "balanced"
+    pub fn describe(&self) -> String {
+        let name = POWER_MODES
+            .iter()
+            .find(|(_, mode)| *mode == self.mode())
+            .map_or_else(
+                || format!("power setup 0x{:02x}", self.mode()),
+                |(name, _)| name.to_string(),
+            );
+        if self.mode() != MODE_INTERVAL {
+            return name;
+        }
+        format!(
+            "{}, on {} s every {} s",
+            name,
+            u16::from_le_bytes([self.payload[4], self.payload[5]]),
+            u16::from_le_bytes([self.payload[2], self.payload[3]])
+        )
+    }
+}
+
+/// Sets the power setup, the interval one with its `period` and `on_time`, returning the
+/// setup polled back
+pub fn set_power_mode(
+    device: &mut Device,
+    mode: &str,
+    period: Option<Duration>,
+    on_time: Option<Duration>,
+) -> Result<Option<Pms>, String> {
+    let (_, mode) = POWER_MODES
+        .iter()
+        .find(|(name, _)| *name == mode)
+        .ok_or_else(|| format!("unknown power mode \"{}\"", mode))?;
+    let (period, on_time) = match (*mode, period) {
+        (MODE_INTERVAL, Some(period)) => (
+            whole_seconds("--power-interval", period)?,
+            whole_seconds("--power-ontime", on_time.unwrap_or_default())?,
+        ),
+        (MODE_INTERVAL, None) => {
+            return Err("The interval power mode needs --power-interval".to_string())
+        },
+        _ if period.is_some() || on_time.is_some() => {
+            return Err(
+                "--power-interval and --power-ontime apply to the interval power mode, or alone \
+(UBX-CFG-PM2)"
+                    .to_string(),
+            )
+        },
+        _ => (0, 0),
+    };
+    if *mode == MODE_INTERVAL && on_time >= period {
+        return Err(format!(
+            "An on time of {} s leaves no sleep in a {} s interval",
+            on_time, period
+        ));
+    }
+    let mut payload = [0; PMS_LEN];
+    payload[1] = *mode;
+    payload[2..4].copy_from_slice(&period.to_le_bytes());
+    payload[4..6].copy_from_slice(&on_time.to_le_bytes());
+    let pms = Pms { payload };
+    if *mode != MODE_FULL {
+        warn_raw_output();
+    }
+    info!("Setting the power mode: {} ...", pms.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x86, &pms.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-PMS: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x86, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the power mode (UBX-ACK-NAK of UBX-CFG-PMS), CFG-PMS \
+needing protocol 18 or later: {}",
+                pms.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-PMS not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x86, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Pms::parse(&payload));
+    Ok(applied)
+}
+
+/// Power save mode settings, from a UBX-CFG-PM2 payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pm2 {
+    payload: Vec<u8>,
+}
+
+impl Pm2 {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        (payload.len() >= PM2_LEN).then(|| Self {
+            payload: payload.to_vec(),
+        })
+    }
+
+    fn field(&self, offset: usize) -> u32 {
+        let bytes = &self.payload[offset..offset + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn set_field(&mut self, offset: usize, value: u32) {
+        self.payload[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Like "ON/OFF operation, an update every 60000 ms, on 10 s, a search every 10000 ms"
+    pub fn describe(&self) -> String {
+        let operation = match (self.field(4) & PM2_MODE_MASK) >> PM2_MODE_SHIFT {
+            0 => "ON/OFF operation",
+            1 => "cyclic tracking",
+            _ => "unknown operation",
+        };
+        format!(
+            "{}, an update every {} ms, on {} s, a search every {} ms",
+            operation,
+            self.field(8),
+            u16::from_le_bytes([self.payload[20], self.payload[21]]),
+            self.field(12)
+        )
+    }
+}
+
+/// Sets the update period and on time of the power save mode given, keeping the other
+/// settings, an on time selecting the ON/OFF operation, then enables the power save mode,
+/// returning the settings polled back
+pub fn set_pm2(
+    device: &mut Device,
+    period: Option<Duration>,
+    on_time: Option<Duration>,
+) -> Result<Option<Pm2>, String> {
+    let payload = device
+        .poll_payload(0x06, 0x3b, &[], device.ack_timeout())
+        .map_err(|e| format!("Failed to read the power save settings (UBX-CFG-PM2): {}", e))?;
+    let mut pm2 = Pm2::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-PM2 of {} bytes", payload.len()))?;
+    if let Some(period) = period {
+        let ms = u32::try_from(period.as_millis())
+            .map_err(|_| format!("--power-interval of {:?} is too long", period))?;
+        pm2.set_field(8, ms);
+    }
+    if let Some(on_time) = on_time {
+        let seconds = whole_seconds("--power-ontime", on_time)?;
+        pm2.payload[20..22].copy_from_slice(&seconds.to_le_bytes());
+        let flags = pm2.field(4) & !PM2_MODE_MASK;
+        pm2.set_field(4, flags);
+    }
+    warn_raw_output();
+    info!("Setting the power save mode: {} ...", pm2.describe());
+    device
+        .write_all(&frame::frame(0x06, 0x3b, &pm2.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-PM2: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x3b, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the power save settings (UBX-ACK-NAK of UBX-CFG-PM2): {}",
+                pm2.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-PM2 not acknowledged: {}", e)),
+    }
+    device
+        .write_all(&frame::frame(0x06, 0x11, &RXM_POWER_SAVE))
+        .map_err(|e| format!("Failed to write UBX-CFG-RXM: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x11, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(
+                "The receiver rejected the power save mode (UBX-ACK-NAK of UBX-CFG-RXM)"
+                    .to_string(),
+            )
+        },
+        Err(e) => return Err(format!("UBX-CFG-RXM not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x3b, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| Pm2::parse(&payload));
+    Ok(applied)
+}
@@ -0,0 +1,456 @@
+//! Configuration profiles (`configure --profile rover.toml`): the settings applied identically
+//! to every receiver of a fleet, described in a TOML file. The profile is validated as a whole
+//! before anything is sent, then applied item by item, each waiting for its acknowledgment,
+//! and a pass or fail line is printed per item:
+//!
+//! ```toml
+//! save = true
+//!
+//! [nav]
+//! meas_rate_ms = 100
+//! dyn_model = "automotive"
+//!
+//! [[message]]
+//! name = "NAV-PVT"
+//! ports = ["usb", "uart1"]
+//!
+//! [[port]]
+//! select = "uart1"
+//! baud = 115200
+//! out_proto = ["ubx"]
+//! ```
+//!
+//! The navigation settings go first, then the messages, then the ports, whose change may
+//! cut the link, and the configuration is saved (UBX-CFG-CFG) last.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::messages::{self, Message};
+use crate::navcfg;
+use crate::persist;
+use crate::portcfg;
+use crate::protocol::{self, Protocol};
+use crate::Device;
+
+/// Ports of the messages without `ports`
+const DEFAULT_MESSAGE_PORTS: [&str; 3] = ["usb", "uart1", "uart2"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Saves the configuration to BBR and flash once applied
+    #[serde(default)]
+    save: bool,
+    nav: Option<Nav>,
+    #[serde(default, rename = "message")]
+    messages: Vec<MessageRate>,
+    #[serde(default, rename = "port")]
+    ports: Vec<Port>,
+}
+
+/// Navigation settings, the ones not given being kept
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Nav {
+    meas_rate_ms: Option<u16>,
+    nav_rate: Option<u16>,
+    time_ref: Option<String>,
+    dyn_model: Option<String>,
+    fix_mode: Option<String>,
+    min_elev: Option<i8>,
+    gnss: Option<Vec<String>>,
+}
+
+/// Output rate of a message, named like NAV-PVT or given like "0x02,0x15"
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MessageRate {
+    name: String,
+    /// Every `rate` navigation solutions, 0 disabling it
+    #[serde(default = "default_rate")]
+    rate: u8,
+    /// Ports among i2c, uart1, uart2, usb and spi
+    ports: Option<Vec<String>>,
+}
+
+fn default_rate() -> u8 {
+    1
+}
+
+/// Settings of a port, the ones not given being kept
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Port {
+    /// usb, uart1 or uart2
+    select: String,
+    baud: Option<u32>,
+    data_bits: Option<u8>,
+    parity: Option<String>,
+    stop_bits: Option<u8>,
+    in_proto: Option<Vec<String>>,
+    out_proto: Option<Vec<String>>,
+}
+
+fn check_name(kind: &str, name: &str, known: &[&str]) -> Result<(), String> {
+    if known.contains(&name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown {} \"{}\", expecting one of {}",
+            kind,
+            name,
+            known.join(", ")
+        ))
+    }
+}
+
+fn proto_mask(names: &[String]) -> Result<u16, String> {
+    let protocols = names
+        .iter()
+        .map(|name| Protocol::parse(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(protocol::proto_mask(&protocols))
+}
+
+impl Nav {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(rate) = self.meas_rate_ms {
+            navcfg::parse_meas_rate(&rate.to_string()).map_err(|e| format!("meas_rate_ms: {}", e))?;
+        }
+        if let Some(rate) = self.nav_rate {
+            navcfg::parse_nav_rate(&rate.to_string()).map_err(|e| format!("nav_rate: {}", e))?;
+        }
+        if let Some(time_ref) = &self.time_ref {
+            check_name("time_ref", time_ref, &navcfg::TIME_REFS)?;
+        }
+        if let Some(model) = &self.dyn_model {
+            check_name("dyn_model", model, &navcfg::DYN_MODELS.map(|(name, _)| name))?;
+        }
+        if let Some(mode) = &self.fix_mode {
+            check_name("fix_mode", mode, &navcfg::FIX_MODES.map(|(name, _)| name))?;
+        }
+        if self.min_elev.is_some_and(|degrees| !(-90..=90).contains(&degrees)) {
+            return Err("min_elev: the elevation mask is from -90 to 90 degrees".to_string());
+        }
+        for name in self.gnss.iter().flatten() {
+            check_name("gnss", name, &navcfg::CONSTELLATIONS.map(|(name, _)| name))?;
+        }
+        Ok(())
+    }
+}
+
+impl MessageRate {
+    fn message(&self) -> Result<Message, String> {
+        let mut parsed = messages::parse(&self.name, false)?;
+        if parsed.len() != 1 {
+            return Err(format!("\"{}\": one message per [[message]]", self.name));
+        }
+        let mut message = parsed.remove(0);
+        message.rate = self.rate;
+        Ok(message)
+    }
+
+    fn ports(&self) -> Result<[bool; 5], String> {
+        let names = match &self.ports {
+            Some(names) => names.clone(),
+            None => DEFAULT_MESSAGE_PORTS.map(str::to_string).to_vec(),
+        };
+        for name in &names {
+            check_name("port", name, &messages::PORTS)?;
+        }
+        Ok(messages::parse_ports(&names))
+    }
+}
+
+impl Port {
+    fn port_id(&self) -> Result<u8, String> {
+        portcfg::PORT_IDS
+            .iter()
+            .find(|(name, _)| *name == self.select)
+            .map(|(_, id)| *id)
+            .ok_or_else(|| {
+                format!(
+                    "unknown port \"{}\", expecting one of {}",
+                    self.select,
+                    portcfg::PORT_IDS.map(|(name, _)| name).join(", ")
+                )
+            })
+    }
+
+    fn request(&self) -> Result<portcfg::Request, String> {
+        if self.data_bits.is_some_and(|bits| !(7..=8).contains(&bits)) {
+            return Err(format!("port {}: data_bits is 7 or 8", self.select));
+        }
+        if self.stop_bits.is_some_and(|bits| !(1..=2).contains(&bits)) {
+            return Err(format!("port {}: stop_bits is 1 or 2", self.select));
+        }
+        let parity = match &self.parity {
+            Some(parity) => Some(
+                portcfg::PARITIES
+                    .iter()
+                    .find(|(name, _)| name == parity)
+                    .map(|(_, bits)| *bits)
+                    .ok_or_else(|| {
+                        format!(
+                            "port {}: unknown parity \"{}\", expecting even, odd or none",
+                            self.select, parity
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+        let mask = |names: &Option<Vec<String>>| {
+            names
+                .as_deref()
+                .map(proto_mask)
+                .transpose()
+                .map_err(|e| format!("port {}: {}", self.select, e))
+        };
+        Ok(portcfg::Request {
+            baud: self.baud,
+            data_bits: self.data_bits,
+            parity,
+            two_stop_bits: self.stop_bits.map(|bits| bits == 2),
+            in_proto: mask(&self.in_proto)?,
+            out_proto: mask(&self.out_proto)?,
+            ..Default::default()
+        })
+    }
+}
+
+impl Profile {
+    /// Reads and validates a profile
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the profile \"{}\": {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid profile \"{}\": {}", path, e))
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let profile: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        if let Some(nav) = &profile.nav {
+            nav.validate()?;
+        }
+        for message in &profile.messages {
+            message.message()?;
+            message.ports()?;
+        }
+        for port in &profile.ports {
+            port.request()?
+                .check(port.port_id()?)
+                .map_err(|e| format!("port {}: {}", port.select, e))?;
+        }
+        Ok(profile)
+    }
+
+    /// Applies the items in order, carrying on after a failure, returning each item with its
+    /// outcome
+    pub fn apply(
+        &self,
+        device: &mut Device,
+        timeout: Duration,
+    ) -> Vec<(String, Result<String, String>)> {
+        let mut results = Vec::new();
+        let nav = self.nav.as_ref();
+        if let Some(nav) = nav.filter(|nav| {
+            nav.meas_rate_ms.is_some() || nav.nav_rate.is_some() || nav.time_ref.is_some()
+        }) {
+            let outcome = navcfg::set_rate(
+                device,
+                nav.meas_rate_ms,
+                nav.nav_rate,
+                nav.time_ref.as_deref(),
+            )
+            .map(|applied| applied.map_or_else(String::new, |rate| rate.describe()));
+            results.push(("UBX-CFG-RATE".to_string(), outcome));
+        }
+        if let Some(nav) = nav.filter(|nav| {
+            nav.dyn_model.is_some() || nav.fix_mode.is_some() || nav.min_elev.is_some()
+        }) {
+            let outcome = navcfg::set_nav5(
+                device,
+                nav.dyn_model.as_deref(),
+                nav.fix_mode.as_deref(),
+                nav.min_elev,
+            )
+            .map(|applied| applied.map_or_else(String::new, |nav5| nav5.describe()));
+            results.push(("UBX-CFG-NAV5".to_string(), outcome));
+        }
+        if let Some(gnss) = nav.and_then(|nav| nav.gnss.as_ref()) {
+            let outcome = navcfg::set_gnss(device, gnss)
+                .map(|applied| applied.map_or_else(String::new, |gnss| gnss.describe()));
+            results.push(("UBX-CFG-GNSS".to_string(), outcome));
+        }
+        for message in &self.messages {
+            // validated when loaded
+            let (rated, ports) = (message.message().unwrap(), message.ports().unwrap());
+            let rates = rated.rates(&ports);
+            info!("Setting {} rates {:?} ...", rated.name, rates);
+            let outcome = match crate::set_message_rates(device, &rated, rates) {
+                Ok(true) => Ok(format!("rates {:?}", rates)),
+                Ok(false) => Err("rejected (UBX-ACK-NAK of UBX-CFG-MSG)".to_string()),
+                Err(e) => Err(format!("UBX-CFG-MSG not acknowledged: {}", e)),
+            };
+            results.push((format!("{} output", rated.name), outcome));
+        }
+        for port in &self.ports {
+            let (port_id, request) = (port.port_id().unwrap(), port.request().unwrap());
+            let name = port.select.to_uppercase();
+            let outcome = portcfg::poll(device, port_id, timeout).and_then(|mut config| {
+                info!("Current '{}' port settings: {}", name, config.describe());
+                config.apply(&request);
+                info!("Configuring '{}' port: {} ...", name, config.describe());
+                portcfg::write(device, &config, timeout).map(|_| config.describe())
+            });
+            results.push((format!("{} port", name), outcome));
+        }
+        if self.save {
+            let outcome = persist::apply(device, persist::Action::Save)
+                .map(|_| persist::Action::Save.outcome().to_string());
+            results.push(("UBX-CFG-CFG".to_string(), outcome));
+        }
+        results
+    }
+}
+
+/// Prints the outcome of each item, returning the number of failures
+pub fn print_summary(results: &[(String, Result<String, String>)]) -> usize {
+    for (item, outcome) in results {
+        match outcome {
+            Ok(applied) => info!("{:<24} pass  {}", item, applied),
+            Err(e) => info!("{:<24} FAIL  {}", item, e),
+        }
+    }
+    results.iter().filter(|(_, outcome)| outcome.is_err()).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame;
+    use crate::source::{Memory, Port};
+
+    type Outcomes = Vec<(String, Result<String, String>)>;
+
+    /// Applies `profile` to a receiver sending `input`, returning the bytes written to it
+    /// and the outcomes
+    fn apply(profile: &Profile, input: &[u8]) -> (Vec<u8>, Outcomes) {
+        let mut device = Device::new(Port::Memory(Box::new(Memory {
+            input: std::io::Cursor::new(input.to_vec()),
+            ..Default::default()
+        })));
+        device.ack_timeout = Duration::from_millis(50);
+        let results = profile.apply(&mut device, Duration::from_millis(50));
+        match device.port {
+            Port::Memory(memory) => (memory.output, results),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn example_profiles() {
+        let rover = Profile::parse(include_str!("../profiles/rover.toml")).unwrap();
+        assert!(rover.save);
+        assert_eq!(rover.nav.as_ref().and_then(|nav| nav.meas_rate_ms), Some(100));
+        assert_eq!(rover.messages.len(), 3);
+        // without ports, on the default ones
+        assert_eq!(rover.messages[2].ports().unwrap(), [false, true, true, true, false]);
+        assert_eq!(rover.ports.len(), 2);
+        let logger = Profile::parse(include_str!("../profiles/raw-logger.toml")).unwrap();
+        assert!(!logger.save);
+        assert_eq!(logger.messages[0].message().unwrap().name, "RXM-RAWX");
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        let error = |text: &str| Profile::parse(text).unwrap_err();
+        assert!(error("saved = true").contains("unknown field `saved`"));
+        assert_eq!(
+            error("[nav]\ndyn_model = \"boat\""),
+            format!(
+                "unknown dyn_model \"boat\", expecting one of {}",
+                navcfg::DYN_MODELS.map(|(name, _)| name).join(", ")
+            )
+        );
+        assert_eq!(
+            error("[nav]\nmin_elev = 91"),
+            "min_elev: the elevation mask is from -90 to 90 degrees"
+        );
+        assert_eq!(
+            error("[[message]]\nname = \"NAV-PVT,NAV-SAT\""),
+            "\"NAV-PVT,NAV-SAT\": one message per [[message]]"
+        );
+        assert!(error("[[message]]\nname = \"NAV-PVT\"\nports = [\"uart3\"]")
+            .starts_with("unknown port \"uart3\""));
+        assert_eq!(
+            error("[[port]]\nselect = \"uart1\"\ndata_bits = 9"),
+            "port uart1: data_bits is 7 or 8"
+        );
+        assert_eq!(
+            error("[[port]]\nselect = \"uart1\"\nparity = \"mark\""),
+            "port uart1: unknown parity \"mark\", expecting even, odd or none"
+        );
+        // USB has no baud rate
+        assert!(error("[[port]]\nselect = \"usb\"\nbaud = 9600")
+            .starts_with("port usb: USB has no baud rate"));
+    }
+
+    #[test]
+    fn message_rates_written() {
+        let profile = Profile::parse(
+            "[[message]]\nname = \"NAV-PVT\"\nrate = 2\nports = [\"usb\", \"uart1\"]\n\n\
+[[message]]\nname = \"NMEA-GGA\"\nrate = 0",
+        )
+        .unwrap();
+        // NMEA-GGA left unacknowledged
+        let (written, results) = apply(&profile, &frame(0x05, 0x01, &[0x06, 0x01]));
+        let expected = [
+            frame(0x06, 0x01, &[0x01, 0x07, 0, 2, 0, 2, 0, 0]),
+            frame(0x06, 0x01, &[0xf0, 0x00, 0, 0, 0, 0, 0, 0]),
+        ]
+        .concat();
+        assert_eq!(written, expected);
+        assert_eq!(
+            results,
+            [
+                ("NAV-PVT output".to_string(), Ok("rates [0, 2, 0, 2, 0, 0]".to_string())),
+                (
+                    "NMEA-GGA output".to_string(),
+                    Err("UBX-CFG-MSG not acknowledged: timed out".to_string())
+                ),
+            ]
+        );
+        assert_eq!(print_summary(&results), 1);
+    }
+
+    #[test]
+    fn port_settings_round_trip() {
+        let profile = Profile::parse(
+            "[[port]]\nselect = \"uart1\"\nbaud = 115200\nout_proto = [\"nmea\"]",
+        )
+        .unwrap();
+        // UART1 at 9600 baud 8N1, in ubx+nmea+rtcm3, out ubx+nmea
+        let mut current = [0; 20];
+        current[0] = 1;
+        current[4..8].copy_from_slice(&0x0000_08c0_u32.to_le_bytes());
+        current[8..12].copy_from_slice(&9600_u32.to_le_bytes());
+        current[12..14].copy_from_slice(&0x23_u16.to_le_bytes());
+        current[14..16].copy_from_slice(&0x03_u16.to_le_bytes());
+        // without UBX output, the new settings are not acknowledged
+        let (written, results) = apply(&profile, &frame(0x06, 0x00, &current));
+
+        let mut applied = current;
+        applied[8..12].copy_from_slice(&115200_u32.to_le_bytes());
+        applied[14..16].copy_from_slice(&0x02_u16.to_le_bytes());
+        let expected = [frame(0x06, 0x00, &[1]), frame(0x06, 0x00, &applied)].concat();
+        assert_eq!(written, expected);
+        // the settings written read back the same
+        let described =
+            "115200 baud, 8N1 (mode 0x000008c0), in ubx+nmea+rtcm3, out nmea, flags 0x0000";
+        assert_eq!(portcfg::PortConfig::parse(&applied).unwrap().describe(), described);
+        assert_eq!(results, [("UART1 port".to_string(), Ok(described.to_string()))]);
+        assert_eq!(print_summary(&results), 0);
+    }
+}
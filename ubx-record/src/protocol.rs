@@ -0,0 +1,408 @@
+//! Protocols of the received stream: selection of the recorded protocols,
+//! and of the protocols the receiver outputs on its port
+
+use ubx_common::frame::{checksum, crc24q};
+
+use crate::sink::Sink;
+
+const UBX_SYNC_CHAR_1: u8 = 0xb5;
+const UBX_SYNC_CHAR_2: u8 = 0x62;
+const NMEA_START: u8 = b'$';
+const RTCM_PREAMBLE: u8 = 0xd3;
+
+/// NMEA sentences are at most 82 characters long, some receivers exceeding it slightly
+const NMEA_MAX_LEN: usize = 128;
+
+/// UBX frames larger than this are considered corrupted
+const UBX_MAX_PAYLOAD_LEN: usize = 8192;
+
+/// outProtoMask bits of UBX-CFG-PRT, the same in inProtoMask
+const OUT_PROTO_UBX: u16 = 0x01;
+const OUT_PROTO_NMEA: u16 = 0x02;
+const OUT_PROTO_RTCM3: u16 = 0x20;
+
+/// Offset of outProtoMask in the UBX-CFG-PRT payload, whatever the port type
+const OUT_PROTO_MASK_OFFSET: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Ubx,
+    Nmea,
+    Rtcm,
+}
+
+impl Protocol {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "ubx" => Ok(Self::Ubx),
+            "nmea" => Ok(Self::Nmea),
+            "rtcm" | "rtcm3" => Ok(Self::Rtcm),
+            _ => Err(format!(
+                "unknown protocol \"{}\", expecting ubx, nmea or rtcm",
+                s
+            )),
+        }
+    }
+
+    fn out_proto_mask(self) -> u16 {
+        match self {
+            Self::Ubx => OUT_PROTO_UBX,
+            Self::Nmea => OUT_PROTO_NMEA,
+            Self::Rtcm => OUT_PROTO_RTCM3,
+        }
+    }
+}
+
+/// inProtoMask or outProtoMask of the selected protocols
+pub fn proto_mask(protocols: &[Protocol]) -> u16 {
+    protocols
+        .iter()
+        .fold(0, |mask, protocol| mask | protocol.out_proto_mask())
+}
+
+/// Sets the output protocols of a UBX-CFG-PRT payload to the selected ones,
+/// returning the previous and new outProtoMask
+pub fn set_out_protocols(payload: &mut [u8], protocols: &[Protocol]) -> Option<(u16, u16)> {
+    let field = payload.get_mut(OUT_PROTO_MASK_OFFSET..OUT_PROTO_MASK_OFFSET + 2)?;
+    let previous = u16::from_le_bytes([field[0], field[1]]);
+    let selected = proto_mask(protocols);
+    // other bits (like SPARTN) are kept
+    let mask = previous & !(OUT_PROTO_UBX | OUT_PROTO_NMEA | OUT_PROTO_RTCM3) | selected;
+    field.copy_from_slice(&mask.to_le_bytes());
+    Some((previous, mask))
+}
+
+/// Splits the received bytes into UBX, NMEA and RTCM3 frames
+#[derive(Default)]
+pub struct ProtocolScanner {
+    buf: Vec<u8>,
+}
+
+enum Found {
+    /// Frame of given length
+    Frame(Protocol, usize),
+    /// Not a frame start
+    Invalid,
+    /// More data needed
+    Incomplete,
+}
+
+impl ProtocolScanner {
+    /// Scans the bytes, calling `f` with each complete frame, and with the bytes
+    /// belonging to no frame (`None`). Incomplete frames are kept for the next call.
+    pub fn scan<F: FnMut(Option<Protocol>, &[u8])>(&mut self, data: &[u8], mut f: F) {
+        self.buf.extend_from_slice(data);
+        let mut start = 0;
+        let mut unknown = 0;
+        while start + unknown < self.buf.len() {
+            match self.frame_at(start + unknown) {
+                Found::Frame(protocol, len) => {
+                    if unknown > 0 {
+                        f(None, &self.buf[start..start + unknown]);
+                        start += unknown;
+                        unknown = 0;
+                    }
+                    f(Some(protocol), &self.buf[start..start + len]);
+                    start += len;
+                },
+                Found::Incomplete => break,
+                Found::Invalid => unknown += 1,
+            }
+        }
+        if unknown > 0 {
+            f(None, &self.buf[start..start + unknown]);
+            start += unknown;
+        }
+        self.buf.drain(..start);
+    }
+
+    /// Number of bytes kept for the next call
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Bytes kept for the next call, like an incomplete frame
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+
+    fn frame_at(&self, start: usize) -> Found {
+        let data = &self.buf[start..];
+        match data[0] {
+            UBX_SYNC_CHAR_1 => ubx_frame(data),
+            NMEA_START => nmea_frame(data),
+            RTCM_PREAMBLE => rtcm_frame(data),
+            _ => Found::Invalid,
+        }
+    }
+}
+
+/// Output file receiving the frames of the selected protocols only,
+/// any other data being dropped
+pub struct ProtocolFilter<W: Sink> {
+    output: W,
+    protocols: Vec<Protocol>,
+    scanner: ProtocolScanner,
+    /// Bytes of other protocols or invalid data
+    dropped: usize,
+}
+
+impl<W: Sink> ProtocolFilter<W> {
+    pub fn new(output: W, protocols: &[Protocol]) -> Self {
+        Self {
+            output,
+            protocols: protocols.to_vec(),
+            scanner: ProtocolScanner::default(),
+            dropped: 0,
+        }
+    }
+}
+
+impl<W: Sink> Sink for ProtocolFilter<W> {
+    fn name(&self) -> String {
+        self.output.name()
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut selected = Vec::new();
+        self.scanner.scan(data, |protocol, frame| {
+            if protocol.is_some_and(|protocol| self.protocols.contains(&protocol)) {
+                selected.extend_from_slice(frame);
+            } else {
+                self.dropped += frame.len();
+            }
+        });
+        self.output.raw(&selected)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        info!(
+            "{}: {} bytes of other protocols or invalid data dropped",
+            self.output.name(),
+            self.dropped
+        );
+        self.output.finish()
+    }
+}
+
+fn ubx_frame(data: &[u8]) -> Found {
+    if data.len() < 2 {
+        return Found::Incomplete;
+    }
+    if data[1] != UBX_SYNC_CHAR_2 {
+        return Found::Invalid;
+    }
+    if data.len() < 6 {
+        return Found::Incomplete;
+    }
+    let len = usize::from(u16::from_le_bytes([data[4], data[5]]));
+    if len > UBX_MAX_PAYLOAD_LEN {
+        return Found::Invalid;
+    }
+    if data.len() < len + 8 {
+        return Found::Incomplete;
+    }
+    if checksum(&data[2..len + 6]) != (data[len + 6], data[len + 7]) {
+        return Found::Invalid;
+    }
+    Found::Frame(Protocol::Ubx, len + 8)
+}
+
+fn nmea_frame(data: &[u8]) -> Found {
+    let end = data
+        .iter()
+        .take(NMEA_MAX_LEN)
+        .position(|byte| *byte == b'\n');
+    let Some(end) = end else {
+        return if data.len() < NMEA_MAX_LEN {
+            Found::Incomplete
+        } else {
+            Found::Invalid
+        };
+    };
+    let Ok(sentence) = std::str::from_utf8(&data[1..end]) else {
+        return Found::Invalid;
+    };
+    let sentence = sentence.trim_end_matches('\r');
+    let (body, checksum) = match sentence.split_once('*') {
+        Some((body, checksum)) => (body, Some(checksum)),
+        None => (sentence, None),
+    };
+    if let Some(checksum) = checksum {
+        let expected = body.bytes().fold(0, |acc, byte| acc ^ byte);
+        if u8::from_str_radix(checksum, 16) != Ok(expected) {
+            return Found::Invalid;
+        }
+    }
+    let address = body.split(',').next().unwrap_or_default();
+    if address.len() < 3 || !address.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Found::Invalid;
+    }
+    Found::Frame(Protocol::Nmea, end + 1)
+}
+
+fn rtcm_frame(data: &[u8]) -> Found {
+    if data.len() < 3 {
+        return Found::Incomplete;
+    }
+    // 6 reserved bits, then a 10 bit length
+    if data[1] & 0xfc != 0 {
+        return Found::Invalid;
+    }
+    let len = usize::from(data[1] & 0x03) << 8 | usize::from(data[2]);
+    if data.len() < len + 6 {
+        return Found::Incomplete;
+    }
+    let crc =
+        u32::from(data[len + 3]) << 16 | u32::from(data[len + 4]) << 8 | u32::from(data[len + 5]);
+    if crc24q(&data[..len + 3]) != crc || len < 2 {
+        return Found::Invalid;
+    }
+    Found::Frame(Protocol::Rtcm, len + 6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame;
+
+    const GGA: &[u8] =
+        b"$GNGGA,092725.00,4717.11399,N,00833.91590,E,1,08,1.01,499.6,M,48.0,M,,*45\r\n";
+
+    /// RTCM3 frame of the payload, with its CRC-24Q
+    fn rtcm(payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![RTCM_PREAMBLE, (payload.len() >> 8) as u8, payload.len() as u8];
+        frame.extend_from_slice(payload);
+        let crc = crc24q(&frame);
+        frame.extend_from_slice(&crc.to_be_bytes()[1..]);
+        frame
+    }
+
+    /// Frames and unknown bytes found by the scanner, data being fed in `chunk` byte pieces
+    fn scan(data: &[u8], chunk: usize) -> Vec<(Option<Protocol>, Vec<u8>)> {
+        let mut scanner = ProtocolScanner::default();
+        let mut found = vec![];
+        for piece in data.chunks(chunk) {
+            scanner.scan(piece, |protocol, frame| found.push((protocol, frame.to_vec())));
+        }
+        assert!(scanner.take().is_empty());
+        found
+    }
+
+    #[derive(Default)]
+    struct Memory(Vec<u8>);
+
+    impl Sink for Memory {
+        fn name(&self) -> String {
+            "memory".to_string()
+        }
+
+        fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn protocol_names() {
+        assert_eq!(Protocol::parse("ubx"), Ok(Protocol::Ubx));
+        assert_eq!(Protocol::parse("rtcm3"), Ok(Protocol::Rtcm));
+        assert_eq!(
+            Protocol::parse("UBX"),
+            Err("unknown protocol \"UBX\", expecting ubx, nmea or rtcm".to_string())
+        );
+        assert_eq!(proto_mask(&[Protocol::Ubx, Protocol::Rtcm]), 0x21);
+        assert_eq!(proto_mask(&[]), 0);
+    }
+
+    /// Mask of a comma list, parsed like --in-proto and --out-proto
+    fn list_mask(list: &str) -> Result<u16, String> {
+        let protocols = list.split(',').map(Protocol::parse).collect::<Result<Vec<_>, _>>()?;
+        Ok(proto_mask(&protocols))
+    }
+
+    #[test]
+    fn mask_combinations() {
+        assert_eq!(list_mask("ubx"), Ok(0x01));
+        assert_eq!(list_mask("nmea"), Ok(0x02));
+        assert_eq!(list_mask("rtcm3"), Ok(0x20));
+        assert_eq!(list_mask("ubx,nmea"), Ok(0x03));
+        assert_eq!(list_mask("ubx,nmea,rtcm3"), Ok(0x23));
+        // order and repetitions do not matter, rtcm being rtcm3
+        assert_eq!(list_mask("rtcm,ubx,rtcm3,ubx"), Ok(0x21));
+        assert_eq!(
+            list_mask("ubx,spartn"),
+            Err("unknown protocol \"spartn\", expecting ubx, nmea or rtcm".to_string())
+        );
+        assert!(list_mask("ubx,").is_err());
+        assert!(list_mask("ubx nmea").is_err());
+    }
+
+    #[test]
+    fn out_protocols() {
+        let mut payload = [0; 20];
+        // UBX, NMEA and SPARTN (0x40)
+        payload[OUT_PROTO_MASK_OFFSET..OUT_PROTO_MASK_OFFSET + 2].copy_from_slice(&[0x43, 0x00]);
+        assert_eq!(
+            set_out_protocols(&mut payload, &[Protocol::Ubx, Protocol::Rtcm]),
+            Some((0x43, 0x61))
+        );
+        assert_eq!(payload[OUT_PROTO_MASK_OFFSET..OUT_PROTO_MASK_OFFSET + 2], [0x61, 0x00]);
+        assert_eq!(set_out_protocols(&mut [0; 15], &[Protocol::Ubx]), None);
+    }
+
+    #[test]
+    fn mixed_stream() {
+        let ubx = frame(0x01, 0x07, &[1, 2, 3, 4]);
+        let rtcm = rtcm(&[0x3e, 0xd0, 0x00, 0x03]);
+        let data = [b"\x00\x01".as_slice(), &ubx, GGA, b"junk", &rtcm].concat();
+        let expected = vec![
+            (None, b"\x00\x01".to_vec()),
+            (Some(Protocol::Ubx), ubx.clone()),
+            (Some(Protocol::Nmea), GGA.to_vec()),
+            (None, b"junk".to_vec()),
+            (Some(Protocol::Rtcm), rtcm.clone()),
+        ];
+        assert_eq!(scan(&data, data.len()), expected);
+        // frames split across reads are kept whole, unknown bytes coming per read
+        let bytewise = scan(&data, 1);
+        let frames = bytewise.iter().filter(|(protocol, _)| protocol.is_some());
+        assert_eq!(frames.cloned().collect::<Vec<_>>(), [&expected[1..3], &expected[4..]].concat());
+        let unknown = bytewise.iter().filter(|(protocol, _)| protocol.is_none());
+        assert_eq!(unknown.map(|(_, bytes)| bytes.len()).sum::<usize>(), 6);
+    }
+
+    #[test]
+    fn corrupted_frames() {
+        let mut ubx = frame(0x01, 0x07, &[1, 2, 3, 4]);
+        ubx[7] ^= 0xff;
+        let mut gga = GGA.to_vec();
+        gga[10] = b'9';
+        let mut rtcm = rtcm(&[0x3e, 0xd0, 0x00, 0x03]);
+        rtcm[4] ^= 0x01;
+        for data in [ubx, gga, rtcm] {
+            let found = scan(&data, data.len());
+            assert!(found.iter().all(|(protocol, _)| protocol.is_none()));
+            assert_eq!(found.iter().map(|(_, bytes)| bytes.len()).sum::<usize>(), data.len());
+        }
+    }
+
+    #[test]
+    fn incomplete_frame_kept() {
+        let ubx = frame(0x01, 0x07, &[1, 2, 3, 4]);
+        let mut scanner = ProtocolScanner::default();
+        scanner.scan(&ubx[..5], |_, _| panic!("incomplete frame"));
+        assert_eq!(scanner.take(), ubx[..5]);
+    }
+
+    #[test]
+    fn filter() {
+        let ubx = frame(0x01, 0x07, &[1, 2, 3, 4]);
+        let rtcm = rtcm(&[0x3e, 0xd0, 0x00, 0x03]);
+        let mut filter = ProtocolFilter::new(Memory::default(), &[Protocol::Ubx, Protocol::Rtcm]);
+        filter.raw(&[GGA, &ubx, b"junk", &rtcm[..4]].concat()).unwrap();
+        filter.raw(&rtcm[4..]).unwrap();
+        assert_eq!(filter.output.0, [ubx, rtcm].concat());
+        assert_eq!(filter.dropped, GGA.len() + 4);
+    }
+}
@@ -0,0 +1,121 @@
+//! Recovery from serial errors, like a receiver disappearing with its USB hub,
+//! or from the loss of the TCP connection.
+//!
+//! With reconnection enabled, the port is reopened on a backoff schedule and the
+//! messages enabled at startup are enabled again, the recording carrying on into
+//! the same outputs. Otherwise, or once the retries are exhausted, the session ends.
+
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::frame;
+use crate::session::Session;
+use crate::source::Endpoint;
+use crate::Device;
+
+/// Longest wait between two attempts
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+pub struct Reconnection {
+    /// Serial port or TCP server, reopened as it was opened at startup
+    endpoint: Endpoint,
+    /// Delay of the first attempt, doubled at every attempt; None when disabled
+    delay: Option<Duration>,
+    /// Attempts per disconnection, unlimited when None
+    max_attempts: Option<usize>,
+    /// Class, id and output rates of the messages to enable again
+    messages: Vec<(u8, u8, [u8; 6])>,
+}
+
+impl Reconnection {
+    pub fn new(
+        endpoint: Endpoint,
+        delay: Option<Duration>,
+        max_attempts: Option<usize>,
+        messages: Vec<(u8, u8, [u8; 6])>,
+    ) -> Self {
+        Self {
+            endpoint,
+            delay,
+            max_attempts,
+            messages,
+        }
+    }
+
+    /// Handles a serial error: reopens and configures the port again, or fails the session
+    pub fn recover(&self, device: &mut Device, error: &std::io::Error, session: &mut Session) {
+        let link = self.endpoint.kind();
+        warn!("{}: {} failure: {}", Utc::now().to_rfc3339(), link, error);
+        session.event(&format!("{} failure: {}", link, error));
+        let Some(mut delay) = self.delay else {
+            session.fail();
+            return;
+        };
+        let mut attempt = 0;
+        while self.max_attempts.map_or(true, |max| attempt < max) {
+            attempt += 1;
+            if !wait(delay, session) {
+                return;
+            }
+            match self.endpoint.open() {
+                Ok(reopened) => {
+                    device.reconnect(reopened);
+                    info!(
+                        "{}: {} reopened, attempt {}",
+                        Utc::now().to_rfc3339(),
+                        link,
+                        attempt
+                    );
+                    self.configure(device);
+                    session.event(&format!("{} reopened, attempt {}", link, attempt));
+                    return;
+                },
+                Err(e) => warn!(
+                    "{}: reconnection attempt {} failed: {}",
+                    Utc::now().to_rfc3339(),
+                    attempt,
+                    e
+                ),
+            }
+            delay = (delay * 2).min(MAX_DELAY);
+        }
+        error!("{}: reconnection attempts exhausted", Utc::now().to_rfc3339());
+        session.event("reconnection attempts exhausted");
+        session.fail();
+    }
+
+    /// Enables the messages again, the receiver having possibly lost its configuration
+    fn configure(&self, device: &mut Device) {
+        for (class, id, rates) in &self.messages {
+            let mut payload = vec![*class, *id];
+            payload.extend_from_slice(rates);
+            let result = device
+                .write_all(&frame::frame(0x06, 0x01, &payload))
+                .and_then(|_| device.wait_for_ack_of(0x06, 0x01, device.ack_timeout()));
+            match result {
+                Ok(true) => {},
+                Ok(false) => warn!(
+                    "Enabling message class 0x{:02x} id 0x{:02x} rejected (UBX-ACK-NAK)",
+                    class, id
+                ),
+                Err(e) => warn!(
+                    "Enabling message class 0x{:02x} id 0x{:02x} failed: {}",
+                    class, id, e
+                ),
+            }
+        }
+    }
+}
+
+/// Waits for the next attempt, false when the session ended meanwhile
+fn wait(delay: Duration, session: &Session) -> bool {
+    let end = Instant::now() + delay;
+    while Instant::now() < end {
+        if session.is_over() {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    !session.is_over()
+}
@@ -0,0 +1,89 @@
+//! Receiver reset subcommand (UBX-CFG-RST): hot, warm or cold start, in one of the reset
+//! modes, like for benchmarking the time to first fix. CFG-RST is not acknowledged, and the
+//! receiver may drop the port while restarting: `--wait-ready` reopens it until the receiver
+//! answers UBX-MON-VER again.
+
+use std::time::{Duration, Instant};
+
+use ublox::{CfgRstBuilder, NavBbrPredefinedMask, ResetMode};
+
+use crate::metadata::Receiver;
+use crate::source::Endpoint;
+use crate::Device;
+
+/// Time between two attempts at reaching the restarted receiver
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Timeout of a UBX-MON-VER poll
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Start types, by name: the navigation data kept by each
+pub const TYPES: [(&str, NavBbrPredefinedMask); 3] = [
+    ("hot", NavBbrPredefinedMask::HOT_START),
+    ("warm", NavBbrPredefinedMask::WARM_START),
+    ("cold", NavBbrPredefinedMask::COLD_START),
+];
+
+/// Reset modes, by name
+pub const MODES: [(&str, ResetMode); 5] = [
+    ("hw", ResetMode::HardwareResetImmediately),
+    ("sw", ResetMode::ControlledSoftwareReset),
+    ("gnss", ResetMode::ControlledSoftwareResetGpsOnly),
+    ("gnss-stop", ResetMode::ControlledGpsStop),
+    ("gnss-start", ResetMode::ControlledGpsStart),
+];
+
+/// Sends the reset, the receiver not acknowledging it
+pub fn reset(device: &mut Device, start: &str, mode: &str) -> std::io::Result<()> {
+    let (_, mask) = TYPES.iter().find(|(name, _)| *name == start).unwrap();
+    let (_, mode) = MODES.iter().find(|(name, _)| *name == mode).unwrap();
+    device.write_all(
+        &CfgRstBuilder {
+            nav_bbr_mask: (*mask).into(),
+            reset_mode: *mode,
+            reserved1: 0,
+        }
+        .into_packet_bytes(),
+    )
+}
+
+/// Waits up to `timeout` for the receiver to answer UBX-MON-VER, reopening the port
+/// while it fails, returning the receiver identification
+pub fn wait_ready(
+    endpoint: &Endpoint,
+    device: &mut Device,
+    timeout: Duration,
+) -> Result<Receiver, String> {
+    let start = Instant::now();
+    // not to be answered by the receiver before it restarts
+    std::thread::sleep(RETRY_DELAY);
+    let mut error = String::new();
+    while start.elapsed() < timeout {
+        match device.poll_payload(0x0a, 0x04, &[], POLL_TIMEOUT) {
+            Ok(version) => {
+                info!(
+                    "Receiver ready {:.1} s after the reset",
+                    start.elapsed().as_secs_f64()
+                );
+                return Ok(Receiver::from_version(version));
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                debug!("No UBX-MON-VER response yet");
+                error = e.to_string();
+            },
+            Err(e) => {
+                debug!("{} failure: {}, reopening it", endpoint.kind(), e);
+                error = e.to_string();
+                std::thread::sleep(RETRY_DELAY);
+                match endpoint.open() {
+                    Ok(reopened) => device.reconnect(reopened),
+                    Err(e) => error = e.to_string(),
+                }
+            },
+        }
+    }
+    Err(format!(
+        "The receiver did not answer UBX-MON-VER within {:?} of the reset: {}",
+        timeout, error
+    ))
+}
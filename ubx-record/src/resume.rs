@@ -0,0 +1,96 @@
+//! Resumption of an interrupted recording (`--append`), like after a restart by systemd.
+//!
+//! The output is opened in append mode rather than truncated. Gzip outputs get a new gzip
+//! member, concatenated members being a valid gzip file. The last member of an interrupted
+//! recording is usually incomplete, its end never written: it is first completed with the
+//! data it holds, so that the members following it can be decompressed.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Reader counting the bytes read
+struct Counted<R: Read> {
+    input: R,
+    read: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.input.read(buf)?;
+        self.read += size as u64;
+        Ok(size)
+    }
+}
+
+/// Offset of the first incomplete or corrupted member, None when all of them are complete
+fn incomplete_member(path: &str) -> std::io::Result<Option<u64>> {
+    let mut input = BufReader::new(Counted {
+        input: File::open(path)?,
+        read: 0,
+    });
+    let mut buf = [0; 8192];
+    loop {
+        if input.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let start = input.get_ref().read - input.buffer().len() as u64;
+        let mut decoder = GzDecoder::new(&mut input);
+        loop {
+            match decoder.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(_) => return Ok(Some(start)),
+            }
+        }
+    }
+}
+
+/// Replaces the data from `start` by a complete member of the data it decompresses to,
+/// returning its size. The member is compressed into `<path>.resume` first
+fn complete_member(path: &str, start: u64, level: u32) -> std::io::Result<u64> {
+    let mut file = File::options().read(true).write(true).open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let temporary = format!("{}.resume", path);
+    let mut decoder = GzDecoder::new(BufReader::new(&file));
+    let mut encoder = GzEncoder::new(File::create(&temporary)?, Compression::new(level));
+    let mut buf = [0; 8192];
+    let mut salvaged = 0;
+    loop {
+        match decoder.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => {
+                encoder.write_all(&buf[..size])?;
+                salvaged += size as u64;
+            },
+        }
+    }
+    encoder.finish()?;
+    file.set_len(start)?;
+    file.seek(SeekFrom::Start(start))?;
+    std::io::copy(&mut File::open(&temporary)?, &mut file)?;
+    std::fs::remove_file(&temporary)?;
+    Ok(salvaged)
+}
+
+/// Opens the output for appending, completing the last member of gzip outputs
+/// compressed at `level` when given
+pub fn open(path: &str, gzip: Option<u32>) -> std::io::Result<File> {
+    let size = std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+    if size > 0 {
+        info!("resuming \"{}\", {} bytes already recorded", path, size);
+    }
+    if let (Some(level), true) = (gzip, size > 0) {
+        if let Some(start) = incomplete_member(path)? {
+            let salvaged = complete_member(path, start, level)?;
+            info!(
+                "\"{}\": incomplete gzip member at offset {} completed, {} bytes of data kept",
+                path, start, salvaged
+            );
+        }
+    }
+    File::options().create(true).append(true).open(path)
+}
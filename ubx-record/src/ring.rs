@@ -0,0 +1,282 @@
+//! Pre-trigger recording: the most recent frames are kept in memory,
+//! and only written to disk around trigger events.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::{BufferedWriter, OutputCompression};
+
+/// Hard limit on buffered data, whatever the window
+const MAX_BUFFERED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Most recent frames, within a time window
+pub struct FrameRing {
+    window: Duration,
+    /// Buffered frames and their reception time, oldest first
+    frames: VecDeque<(Instant, Vec<u8>)>,
+    bytes: usize,
+}
+
+impl FrameRing {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            frames: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Buffers a validated frame received at `now`, evicting older frames
+    pub fn push(&mut self, now: Instant, frame: Vec<u8>) {
+        self.bytes += frame.len();
+        self.frames.push_back((now, frame));
+        // evict whole frames, so the buffer always starts on a frame
+        while let Some((received, frame)) = self.frames.front() {
+            if now.duration_since(*received) <= self.window && self.bytes <= MAX_BUFFERED_BYTES {
+                break;
+            }
+            self.bytes -= frame.len();
+            self.frames.pop_front();
+        }
+    }
+
+    /// Writes the buffered frames, oldest first, and empties the buffer.
+    /// Returns the number of bytes written.
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> std::io::Result<usize> {
+        for (_, frame) in self.frames.drain(..) {
+            writer.write_all(&frame)?;
+        }
+        Ok(std::mem::take(&mut self.bytes))
+    }
+}
+
+pub struct RingRecorder {
+    ring: FrameRing,
+    /// Duration of live data written after a trigger
+    post_trigger: Duration,
+    /// Output path, timestamped for each capture
+    template: String,
+    /// Capture in progress, and when it ends
+    capture: Option<(BufferedWriter, Instant)>,
+    /// Metadata block starting each capture
+    metadata: Option<Vec<u8>>,
+    compression: OutputCompression,
+    /// Existing captures are overwritten (`--force`)
+    force: bool,
+}
+
+impl RingRecorder {
+    pub fn new(
+        window: Duration,
+        post_trigger: Duration,
+        template: &str,
+        metadata: Option<Vec<u8>>,
+        compression: OutputCompression,
+        force: bool,
+    ) -> Self {
+        Self {
+            ring: FrameRing::new(window),
+            post_trigger,
+            template: template.to_string(),
+            capture: None,
+            metadata,
+            compression,
+            force,
+        }
+    }
+
+    /// Records a validated frame, received now
+    pub fn push(&mut self, frame: Vec<u8>) -> std::io::Result<()> {
+        let now = Instant::now();
+        self.update(now)?;
+        if let Some((writer, _)) = &mut self.capture {
+            return writer.write_all(&frame);
+        }
+        self.ring.push(now, frame);
+        Ok(())
+    }
+
+    /// Starts a capture: a new file receives the buffered window, then live
+    /// data until the post-trigger duration elapses. Triggering during a
+    /// capture extends it instead.
+    pub fn trigger(&mut self) -> std::io::Result<()> {
+        let end = Instant::now() + self.post_trigger;
+        if let Some((_, capture_end)) = &mut self.capture {
+            *capture_end = end;
+            info!("trigger: capture extended");
+            return Ok(());
+        }
+        let path = timestamped(&self.template, chrono::Utc::now());
+        if !self.force {
+            crate::check_overwrite(&path)?;
+        }
+        let mut writer = BufferedWriter::new(&path, self.compression, false);
+        if let Some(block) = &self.metadata {
+            writer.write_all(block)?;
+        }
+        let bytes = self.ring.write_to(&mut writer)?;
+        info!(
+            "trigger: capturing {} buffered bytes and {:?} of live data into \"{}\"",
+            bytes, self.post_trigger, path
+        );
+        self.capture = Some((writer, end));
+        Ok(())
+    }
+
+    /// Closes the capture in progress, if any, at the end of the session
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if let Some((mut writer, _)) = self.capture.take() {
+            writer.finalize()?;
+            info!("trigger: capture interrupted, complete up to now");
+        }
+        Ok(())
+    }
+
+    /// Closes the capture in progress once its post-trigger duration elapsed
+    pub fn update(&mut self, now: Instant) -> std::io::Result<()> {
+        if self.capture.as_ref().is_some_and(|(_, end)| now >= *end) {
+            let (mut writer, _) = self.capture.take().unwrap();
+            writer.finalize()?;
+            info!("trigger: capture complete, buffering");
+        }
+        Ok(())
+    }
+}
+
+/// Inserts the timestamp ahead of the extensions of the file name,
+/// "output.ubx.gz" becoming "output-20240501T120000Z.ubx.gz"
+fn timestamped(path: &str, time: chrono::DateTime<chrono::Utc>) -> String {
+    with_time(path, time, "%Y%m%dT%H%M%SZ")
+}
+
+/// Inserts the time, formatted, ahead of the extensions of the file name
+pub fn with_time(path: &str, time: chrono::DateTime<chrono::Utc>, format: &str) -> String {
+    with_suffix(path, &time.format(format).to_string())
+}
+
+/// Inserts "-suffix" ahead of the extensions of the file name
+pub fn with_suffix(path: &str, suffix: &str) -> String {
+    let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let stem_end = path[name_start..]
+        .find('.')
+        .map(|i| name_start + i)
+        .unwrap_or(path.len());
+    format!(
+        "{}-{}{}",
+        &path[..stem_end],
+        suffix,
+        &path[stem_end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn window_eviction() {
+        let start = Instant::now();
+        let mut ring = FrameRing::new(Duration::from_secs(2));
+        ring.push(start, vec![1; 10]);
+        ring.push(start + Duration::from_secs(1), vec![2; 20]);
+        ring.push(start + Duration::from_secs(3), vec![3; 30]);
+        // the first frame is older than the window, the second exactly as old
+        let mut written = vec![];
+        assert_eq!(ring.write_to(&mut written).unwrap(), 50);
+        assert_eq!(written, [vec![2; 20], vec![3; 30]].concat());
+        // emptied
+        written.clear();
+        assert_eq!(ring.write_to(&mut written).unwrap(), 0);
+        assert!(written.is_empty());
+    }
+
+    #[test]
+    fn whole_frames_evicted() {
+        let start = Instant::now();
+        let mut ring = FrameRing::new(Duration::ZERO);
+        ring.push(start, vec![1; 10]);
+        ring.push(start + Duration::from_millis(1), vec![2; 3]);
+        let mut written = vec![];
+        assert_eq!(ring.write_to(&mut written).unwrap(), 3);
+        assert_eq!(written, [2; 3]);
+    }
+
+    /// Capture of a buffered and a live frame, the post-trigger window expiring,
+    /// decompressed
+    fn capture(name: &str, decode: fn(&[u8]) -> Vec<u8>) {
+        let directory =
+            std::env::temp_dir().join(format!("ubx-record-{}-ring-{}", std::process::id(), name));
+        std::fs::create_dir_all(&directory).unwrap();
+        let template = directory.join(name).to_string_lossy().into_owned();
+        let compression = OutputCompression::new(None, &template, None).unwrap();
+        let post_trigger = Duration::from_secs(3600);
+        let window = Duration::from_secs(60);
+        let mut recorder =
+            RingRecorder::new(window, post_trigger, &template, None, compression, false);
+        let buffered = crate::frame::frame(0x01, 0x07, &[1; 92]);
+        let live = crate::frame::frame(0x01, 0x07, &[2; 92]);
+        recorder.push(buffered.clone()).unwrap();
+        recorder.trigger().unwrap();
+        recorder.push(live.clone()).unwrap();
+        recorder.update(Instant::now() + 2 * post_trigger).unwrap();
+        assert!(recorder.capture.is_none());
+
+        let captures: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let compressed = std::fs::read(&captures[0]).unwrap();
+        let _ = std::fs::remove_dir_all(&directory);
+        assert_eq!(captures.len(), 1);
+        assert_eq!(decode(&compressed), [buffered, live].concat());
+    }
+
+    #[test]
+    fn gzip_capture() {
+        capture("capture.ubx.gz", |compressed| {
+            let mut data = vec![];
+            // the gzip trailer is required for reading to the end without error
+            flate2::read::GzDecoder::new(compressed).read_to_end(&mut data).unwrap();
+            data
+        });
+    }
+
+    #[test]
+    fn zstd_capture() {
+        capture("capture.ubx.zst", |compressed| zstd::decode_all(compressed).unwrap());
+    }
+
+    #[test]
+    fn existing_capture_kept() {
+        let directory =
+            std::env::temp_dir().join(format!("ubx-record-{}-ring-existing", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let template = directory.join("capture.ubx").to_string_lossy().into_owned();
+        // the capture of this second, and of the next one
+        let now = chrono::Utc::now();
+        for time in [now, now + chrono::Duration::seconds(1)] {
+            std::fs::write(timestamped(&template, time), b"captured").unwrap();
+        }
+        let compression = OutputCompression::new(None, &template, None).unwrap();
+        let window = Duration::from_secs(60);
+        let mut recorder = RingRecorder::new(window, window, &template, None, compression, false);
+        let error = recorder.trigger().unwrap_err();
+        let _ = std::fs::remove_dir_all(&directory);
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert!(recorder.capture.is_none());
+    }
+
+    #[test]
+    fn timestamped_names() {
+        let time = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(timestamped("output.ubx.gz", time), "output-20240501T120000Z.ubx.gz");
+        assert_eq!(timestamped("logs/run", time), "logs/run-20240501T120000Z");
+        // dots of the directories are not extensions
+        assert_eq!(timestamped("./logs.d/run.ubx", time), "./logs.d/run-20240501T120000Z.ubx");
+        assert_eq!(with_suffix("output.ubx", "2"), "output-2.ubx");
+    }
+}
@@ -0,0 +1,185 @@
+//! Time based rotation of the output file, like one file per UTC hour.
+//!
+//! Every segment is named after its start time and is a complete file of its own:
+//! frames are not split across segments, and the gzip stream of a segment is completed
+//! when the next one starts.
+
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::protocol::ProtocolScanner;
+use crate::ring::with_time;
+use crate::sink::Sink;
+use crate::{BufferedWriter, OutputCompression};
+
+/// Start times of the segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// Every interval since the recording started
+    Start,
+    /// Multiples of the interval since midnight UTC, like :00 for hourly segments
+    Utc,
+}
+
+impl Align {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "start" => Ok(Self::Start),
+            "utc" => Ok(Self::Utc),
+            _ => Err(format!(
+                "unknown alignment \"{}\", expecting start or utc",
+                s
+            )),
+        }
+    }
+}
+
+/// Output parameters of the segments, as for a single output file
+#[derive(Clone)]
+pub struct Options {
+    pub fifo_buffer: usize,
+    pub sync_interval: Option<Duration>,
+    pub buffer: Option<usize>,
+    /// Metadata block starting every segment
+    pub metadata: Option<Vec<u8>>,
+    pub compression: OutputCompression,
+    /// Segments are appended to when they exist, like after a restart
+    pub append: bool,
+    /// Existing segments are overwritten (`--force`)
+    pub force: bool,
+}
+
+pub struct Rotation {
+    template: String,
+    interval: i64,
+    align: Align,
+    options: Options,
+    /// Reference of the segment start times, in seconds since the Unix epoch
+    origin: i64,
+    scanner: ProtocolScanner,
+    /// Segment being written, and when it ends
+    segment: Option<(BufferedWriter, i64)>,
+    segments: usize,
+}
+
+impl Rotation {
+    /// Rotates the output every `interval`, of whole seconds
+    pub fn new(template: &str, interval: Duration, align: Align, options: Options) -> Self {
+        let origin = match align {
+            Align::Start => Utc::now().timestamp(),
+            // the Unix epoch is a UTC midnight
+            Align::Utc => 0,
+        };
+        Self {
+            template: template.to_string(),
+            interval: interval.as_secs().max(1) as i64,
+            align,
+            options,
+            origin,
+            scanner: ProtocolScanner::default(),
+            segment: None,
+            segments: 0,
+        }
+    }
+
+    /// Writes data of a frame, or data belonging to no frame, in the segment of `now`
+    fn write(&mut self, now: i64, data: &[u8]) -> std::io::Result<()> {
+        if self.segment.as_ref().map_or(true, |(_, end)| now >= *end) {
+            self.close()?;
+            let start = now - (now - self.origin).rem_euclid(self.interval);
+            let start_time = Utc.timestamp_opt(start, 0).unwrap();
+            let path = path(&self.template, self.interval, start_time);
+            if !self.options.append && !self.options.force {
+                crate::check_overwrite(&path)?;
+            }
+            let mut writer = BufferedWriter::open(
+                &path,
+                self.options.fifo_buffer,
+                self.options.sync_interval,
+                self.options.buffer,
+                self.options.compression,
+                self.options.append,
+            );
+            if let Some(block) = &self.options.metadata {
+                writer.write_all(block)?;
+            }
+            info!("rotation: recording into \"{}\"", path);
+            self.segment = Some((writer, start + self.interval));
+            self.segments += 1;
+        }
+        match &mut self.segment {
+            Some((writer, _)) => writer.write_all(data),
+            None => Ok(()),
+        }
+    }
+
+    /// Completes the segment being written
+    fn close(&mut self) -> std::io::Result<()> {
+        match self.segment.take() {
+            Some((mut writer, _)) => writer.finalize(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Names segments as precisely as their interval requires, in seconds,
+/// "station.ubx.gz" becoming "station-2024-05-03T14.ubx.gz" when hourly
+fn path(template: &str, interval: i64, start: DateTime<Utc>) -> String {
+    let format = if interval % 3600 == 0 {
+        "%Y-%m-%dT%H"
+    } else if interval % 60 == 0 {
+        "%Y-%m-%dT%H%M"
+    } else {
+        "%Y-%m-%dT%H%M%S"
+    };
+    with_time(template, start, format)
+}
+
+/// Name of the first segment of a rotation starting now, for the existence checks
+pub fn first_path(template: &str, interval: Duration, align: Align) -> String {
+    let now = Utc::now().timestamp();
+    let interval = interval.as_secs().max(1) as i64;
+    let start = match align {
+        Align::Start => now,
+        Align::Utc => now - now.rem_euclid(interval),
+    };
+    path(template, interval, Utc.timestamp_opt(start, 0).unwrap())
+}
+
+impl Sink for Rotation {
+    fn name(&self) -> String {
+        "output file".to_string()
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let now = Utc::now().timestamp();
+        let mut pieces = Vec::new();
+        self.scanner
+            .scan(data, |_, piece| pieces.push(piece.to_vec()));
+        for piece in pieces {
+            self.write(now, &piece)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        // an incomplete frame is kept as it is
+        let pending = self.scanner.take();
+        if !pending.is_empty() {
+            self.write(Utc::now().timestamp(), &pending)?;
+        }
+        self.close()?;
+        info!(
+            "rotation: {} segment(s) of {} s, aligned on {}",
+            self.segments,
+            self.interval,
+            match self.align {
+                Align::Start => "the start",
+                Align::Utc => "UTC",
+            }
+        );
+        Ok(())
+    }
+}
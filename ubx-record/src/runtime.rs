@@ -0,0 +1,555 @@
+//! Recording on a tokio runtime (`tokio` feature, Unix): the receiver read by the recording
+//! loop, the NTRIP corrections (`--ntrip`), the TCP clients (`--tcp-listen`) and the metrics
+//! endpoint (`--metrics`) run as tasks of one single threaded runtime, rather than in the
+//! loop or a thread each. The port of the device keeps its blocking API for the
+//! configuration and the other recordings, its descriptor being polled by the runtime while
+//! the loop runs. The reconnections still block, the tasks waiting for the port to be
+//! reopened.
+
+use std::net::SocketAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use clap::{Arg, ArgMatches, Command};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use ubx_common::transport::AsyncDevice;
+
+use crate::metrics::{self, Metrics};
+use crate::ntrip::{self, Caster};
+use crate::protocol::ProtocolScanner;
+use crate::reconnect::Reconnection;
+use crate::session::Session;
+use crate::sink::Sinks;
+use crate::{Device, IDLE_WAIT};
+
+/// Frames queued per TCP client, slower clients being disconnected
+const CLIENT_MAX_FRAMES: usize = 4096;
+
+/// Corrections queued for the receiver while it is being written or reconnected
+const CORRECTIONS_QUEUE: usize = 64;
+
+/// Options of the tasks
+pub fn args(command: Command) -> Command {
+    command
+        .next_help_heading("Live outputs")
+        .arg(
+            Arg::new("ntrip")
+                .long("ntrip")
+                .value_name("URL")
+                .value_parser(Caster::parse)
+                .conflicts_with_all(["start-on", "ring-buffer"])
+                .help("Forward to the receiver the RTCM3 corrections of an NTRIP caster \
+mountpoint, ntrip://[user[:password]@]host[:port]/MOUNTPOINT, for RTK. The caster is \
+connected again after the failures")
+        )
+        .arg(
+            Arg::new("metrics")
+                .long("metrics")
+                .value_name("ADDRESS")
+                .conflicts_with_all(["start-on", "ring-buffer"])
+                .help("Serve the counters of the recording, bytes and frames received, NTRIP \
+corrections and TCP clients, in the Prometheus text format at http://ADDRESS/metrics, like \
+0.0.0.0:9100")
+        )
+}
+
+/// Tasks of the recording, from the command line
+pub struct Tasks {
+    tcp_listen: Option<String>,
+    ntrip: Option<Caster>,
+    metrics: Option<String>,
+}
+
+impl Tasks {
+    pub fn new(matches: &ArgMatches) -> Self {
+        Self {
+            tcp_listen: matches.get_one::<String>("tcp-listen").cloned(),
+            ntrip: matches.get_one::<Caster>("ntrip").cloned(),
+            metrics: matches.get_one::<String>("metrics").cloned(),
+        }
+    }
+
+    /// Binds the listeners and spawns the tasks, exiting when an address is unavailable
+    async fn spawn(self) -> Running {
+        let metrics = Arc::new(Metrics::default());
+        let mut running = Running::new(metrics.clone());
+        if let Some(address) = self.tcp_listen {
+            let listener = bind(&address).await;
+            info!("serving TCP clients on {}", listener.local_addr().unwrap());
+            running.serve(listener);
+        }
+        if let Some(caster) = self.ntrip {
+            running.forward(caster);
+        }
+        if let Some(address) = self.metrics {
+            let listener = bind(&address).await;
+            info!("serving the metrics on http://{}/metrics", listener.local_addr().unwrap());
+            tokio::spawn(metrics::serve(listener, metrics));
+        }
+        running
+    }
+}
+
+async fn bind(address: &str) -> TcpListener {
+    TcpListener::bind(address).await.unwrap_or_else(|e| {
+        error!("Failed to listen on {}: {}", address, e);
+        std::process::exit(1);
+    })
+}
+
+/// Records on the runtime until the session is over, `tick` being called every second
+/// with the port blocking, like the blocking loop
+pub fn record(
+    device: &mut Device,
+    session: &mut Session,
+    sinks: &mut Sinks,
+    reconnection: &Reconnection,
+    tasks: Tasks,
+    mut tick: impl FnMut(&mut Device, &mut Session),
+) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap_or_else(|e| crate::exit_on("failed to start the runtime", e));
+    runtime.block_on(async {
+        let mut running = tasks.spawn().await;
+        while !session.is_over() {
+            let result = match PortStream::new(device) {
+                Ok(stream) => {
+                    let mut stream = AsyncDevice::new(stream);
+                    run(&mut stream, &mut running, |port, data| {
+                        session.tick();
+                        if data.is_empty() {
+                            port.blocking(|device| tick(device, session));
+                        }
+                        let size = session.feed(data);
+                        sinks.feed(&data[..size]);
+                        (size, !session.is_over())
+                    })
+                    .await
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                reconnection.recover(device, &e, session);
+            }
+        }
+        running.finish();
+    });
+}
+
+/// State of the recording loop: the corrections to forward, the frames to serve
+struct Running {
+    corrections: Option<mpsc::Receiver<Vec<u8>>>,
+    frames: Option<broadcast::Sender<Arc<[u8]>>>,
+    /// Address of the TCP listener
+    listening: Option<SocketAddr>,
+    scanner: ProtocolScanner,
+    metrics: Arc<Metrics>,
+}
+
+impl Running {
+    fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            corrections: None,
+            frames: None,
+            listening: None,
+            scanner: ProtocolScanner::default(),
+            metrics,
+        }
+    }
+
+    /// Serves the frames received to the clients of the listener
+    fn serve(&mut self, listener: TcpListener) {
+        let (frames, _) = broadcast::channel(CLIENT_MAX_FRAMES);
+        self.listening = listener.local_addr().ok();
+        tokio::spawn(serve(listener, frames.clone(), self.metrics.clone()));
+        self.frames = Some(frames);
+    }
+
+    /// Forwards the corrections of the caster to the receiver
+    fn forward(&mut self, caster: Caster) {
+        let (sender, receiver) = mpsc::channel(CORRECTIONS_QUEUE);
+        tokio::spawn(ntrip::receive(caster, sender, self.metrics.clone()));
+        self.corrections = Some(receiver);
+    }
+
+    /// Counts the bytes recorded, serving their valid frames
+    fn recorded(&mut self, data: &[u8]) {
+        self.metrics.received.fetch_add(data.len() as u64, Ordering::Relaxed);
+        let (metrics, frames) = (&self.metrics, &self.frames);
+        self.scanner.scan(data, |protocol, frame| {
+            if let Some(protocol) = protocol {
+                metrics.frame(protocol);
+                if let Some(frames) = frames {
+                    // no client connected
+                    let _ = frames.send(frame.into());
+                }
+            }
+        });
+    }
+
+    /// Next corrections to forward, never when there is no caster
+    async fn corrections(&mut self) -> Option<Vec<u8>> {
+        match &mut self.corrections {
+            Some(corrections) => corrections.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(address) = self.listening {
+            info!(
+                "TCP server {}: {} client(s) served",
+                address,
+                self.metrics.served.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+/// Reads the receiver, feeding the bytes and the ticks to `feed` until it returns false,
+/// with the number of bytes it kept, and writes the corrections to the receiver
+async fn run<T: AsyncRead + AsyncWrite + Unpin>(
+    device: &mut AsyncDevice<T>,
+    running: &mut Running,
+    mut feed: impl FnMut(&mut T, &[u8]) -> (usize, bool),
+) -> std::io::Result<()> {
+    let mut buf = [0; 2048];
+    let mut ticks = tokio::time::interval(IDLE_WAIT);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        // the reads and the receptions are cancel safe
+        let size = tokio::select! {
+            read = device.read(&mut buf) => match read? {
+                0 => return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "end of the stream",
+                )),
+                size => size,
+            },
+            Some(corrections) = running.corrections() => {
+                device.write_all(&corrections).await?;
+                let forwarded = corrections.len() as u64;
+                running.metrics.forwarded.fetch_add(forwarded, Ordering::Relaxed);
+                continue;
+            },
+            _ = ticks.tick() => 0,
+        };
+        let (kept, going_on) = feed(device.get_mut(), &buf[..size]);
+        running.recorded(&buf[..kept]);
+        if !going_on {
+            return Ok(());
+        }
+    }
+}
+
+/// Accepts the TCP clients, each served by a task
+async fn serve(listener: TcpListener, frames: broadcast::Sender<Arc<[u8]>>, metrics: Arc<Metrics>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                info!("TCP client {} connected", peer);
+                tokio::spawn(client(stream, peer, frames.subscribe(), metrics.clone()));
+            },
+            Err(e) => {
+                // out of descriptors: the clients wait in the backlog meanwhile
+                warn!("TCP server: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            },
+        }
+    }
+}
+
+/// Writes the frames received to the client, from the next frame
+async fn client(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    mut frames: broadcast::Receiver<Arc<[u8]>>,
+    metrics: Arc<Metrics>,
+) {
+    metrics.clients.fetch_add(1, Ordering::Relaxed);
+    metrics.served.fetch_add(1, Ordering::Relaxed);
+    let mut bytes = 0;
+    let error = loop {
+        match frames.recv().await {
+            Ok(frame) => match stream.write_all(&frame).await {
+                Ok(()) => bytes += frame.len(),
+                Err(e) => break Some(e),
+            },
+            Err(RecvError::Lagged(_)) => break None,
+            Err(RecvError::Closed) => return,
+        }
+    };
+    metrics.clients.fetch_sub(1, Ordering::Relaxed);
+    match error {
+        Some(e) => warn!("TCP client {} disconnected after {} bytes: {}", peer, bytes, e),
+        None => warn!("TCP client {} too slow, disconnected after {} bytes", peer, bytes),
+    }
+}
+
+/// Descriptor of the port, registered with the runtime, the port keeping it
+struct Descriptor(RawFd);
+
+impl AsRawFd for Descriptor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// Port of the device as an asynchronous stream: its reads and writes return at once, the
+/// runtime polling its descriptor for them to be ready. The blocking mode is restored when
+/// dropped
+struct PortStream<'a> {
+    device: &'a mut Device,
+    fd: AsyncFd<Descriptor>,
+    /// Read timeout of the port, for the acknowledgments and the polls
+    timeout: Option<Duration>,
+}
+
+impl<'a> PortStream<'a> {
+    fn new(device: &'a mut Device) -> std::io::Result<Self> {
+        let fd = device.fd.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Unsupported, "no descriptor to poll")
+        })?;
+        let timeout = device.port.read_timeout()?;
+        device.port.set_nonblocking(true)?;
+        let stream = Self {
+            fd: AsyncFd::new(Descriptor(fd))?,
+            device,
+            timeout,
+        };
+        Ok(stream)
+    }
+
+    /// Runs `f` with the port blocking, for it to send messages and wait for their answers
+    fn blocking<R>(&mut self, f: impl FnOnce(&mut Device) -> R) -> R {
+        let restored = self.restore();
+        let result = f(self.device);
+        if let Err(e) = restored.and_then(|()| self.device.port.set_nonblocking(true)) {
+            warn!("failed to set the port mode: {}", e);
+        }
+        result
+    }
+
+    fn restore(&mut self) -> std::io::Result<()> {
+        self.device.port.set_nonblocking(false)?;
+        self.device.port.set_read_timeout(self.timeout)
+    }
+}
+
+impl Drop for PortStream<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore() {
+            warn!("failed to set the port mode: {}", e);
+        }
+    }
+}
+
+fn would_block(e: &std::io::Error) -> bool {
+    // serial ports time out rather than would block
+    matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+}
+
+impl AsyncRead for PortStream<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        // data the descriptor does not tell about
+        if let Some(size) = this.device.take_replayed(buf.initialize_unfilled()) {
+            buf.advance(size);
+            return Poll::Ready(Ok(()));
+        }
+        if this.device.port.pending() {
+            let size = this.device.read_port(buf.initialize_unfilled())?;
+            buf.advance(size);
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            let mut guard = ready!(this.fd.poll_read_ready(cx))?;
+            match this.device.read_port(buf.initialize_unfilled()) {
+                Ok(0) => guard.clear_ready(),
+                Ok(size) => {
+                    buf.advance(size);
+                    return Poll::Ready(Ok(()));
+                },
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for PortStream<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.fd.poll_write_ready(cx))?;
+            match std::io::Write::write(&mut this.device.port, data) {
+                Err(e) if would_block(&e) => guard.clear_ready(),
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    /// Nothing is buffered, the system sending what is written. The serial ports would
+    /// block until sent
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+    use tokio::io::{AsyncReadExt, DuplexStream};
+
+    /// Longest wait of a step of the test
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    const CORRECTIONS: &[u8] = b"\xd3\x00\x04\x4c\xe0\x00\x80\xed\xed\xd6";
+
+    fn nav_pvt() -> Vec<u8> {
+        crate::frame::frame(0x01, 0x07, &[0; 92])
+    }
+
+    /// Fake receiver: sends a NAV-PVT every 10 ms, and the bytes written to it on `written`
+    async fn receiver(stream: DuplexStream, written: mpsc::Sender<Vec<u8>>) {
+        let (mut input, mut output) = tokio::io::split(stream);
+        tokio::spawn(async move {
+            let mut buf = [0; 256];
+            while let Ok(size @ 1..) = input.read(&mut buf).await {
+                let _ = written.send(buf[..size].to_vec()).await;
+            }
+        });
+        let mut interval = tokio::time::interval(Duration::from_millis(10));
+        loop {
+            interval.tick().await;
+            if output.write_all(&nav_pvt()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Caster of one mountpoint, sending the corrections once to the authenticated clients
+    async fn caster(listener: TcpListener) {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0; 512];
+            while !request.ends_with(b"\r\n\r\n") {
+                let size = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..size]);
+            }
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with("GET /MOUNT HTTP/1.0\r\n"), "{}", request);
+            assert!(request.contains("Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"), "{}", request);
+            stream.write_all(b"ICY 200 OK\r\n").await.unwrap();
+            stream.write_all(CORRECTIONS).await.unwrap();
+            tokio::spawn(async move {
+                // connected until the end of the test
+                let _ = stream.read(&mut buf).await;
+            });
+        }
+    }
+
+    async fn listener() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        (listener, address)
+    }
+
+    async fn scrape(address: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: test\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn tasks_run_concurrently() {
+        let (caster_listener, caster_address) = listener().await;
+        tokio::spawn(caster(caster_listener));
+        let (tcp_listener, tcp_address) = listener().await;
+        let (metrics_listener, metrics_address) = listener().await;
+
+        let metrics = Arc::new(Metrics::default());
+        let mut running = Running::new(metrics.clone());
+        running.serve(tcp_listener);
+        let url = format!("ntrip://user:secret@{}/MOUNT", caster_address);
+        running.forward(Caster::parse(&url).unwrap());
+        tokio::spawn(metrics::serve(metrics_listener, metrics.clone()));
+
+        let (host, fake) = tokio::io::duplex(1024);
+        let (written, mut forwarded) = mpsc::channel(16);
+        tokio::spawn(receiver(fake, written));
+        let stop = Arc::new(AtomicBool::new(false));
+        let fed = Arc::new(AtomicUsize::new(0));
+        let recording = {
+            let (stop, fed) = (stop.clone(), fed.clone());
+            tokio::spawn(async move {
+                let mut device = AsyncDevice::new(host);
+                run(&mut device, &mut running, |_, data| {
+                    fed.fetch_add(data.len(), Ordering::Relaxed);
+                    (data.len(), !stop.load(Ordering::Relaxed))
+                })
+                .await
+            })
+        };
+
+        // the TCP client gets whole frames, from a frame start
+        let mut client = TcpStream::connect(tcp_address).await.unwrap();
+        let mut frame = vec![0; nav_pvt().len()];
+        tokio::time::timeout(TIMEOUT, client.read_exact(&mut frame)).await.unwrap().unwrap();
+        assert_eq!(frame, nav_pvt());
+
+        // the corrections of the caster reach the receiver
+        let mut received = Vec::new();
+        while received.len() < CORRECTIONS.len() {
+            let data = tokio::time::timeout(TIMEOUT, forwarded.recv()).await.unwrap().unwrap();
+            received.extend_from_slice(&data);
+        }
+        assert_eq!(received, CORRECTIONS);
+
+        let expected = format!("\nubx_record_ntrip_forwarded_bytes_total {}\n", CORRECTIONS.len());
+        let response = tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let response = scrape(metrics_address).await;
+                if response.contains(&expected) {
+                    return response;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("\nubx_record_tcp_clients 1\n"), "{}", response);
+        assert!(response.contains("\nubx_record_ntrip_connections_total 1\n"), "{}", response);
+        let frames = metrics.frames[0].load(Ordering::Relaxed);
+        assert!(frames > 0);
+
+        stop.store(true, Ordering::Relaxed);
+        tokio::time::timeout(TIMEOUT, recording).await.unwrap().unwrap().unwrap();
+        assert!(fed.load(Ordering::Relaxed) >= frames as usize * nav_pvt().len());
+    }
+}
@@ -0,0 +1,106 @@
+//! Live summary of the satellites of each epoch (`--print-sats`), from NAV-SAT, or NAV-SIG
+//! when only signals are output, or the NAV-SVINFO of the firmware older than NAV-SAT
+
+use std::collections::BTreeMap;
+
+use ubx_common::satellites::{Epoch, Source, NAV_SAT, NAV_SIG, NAV_SVINFO};
+use ublox::PacketRef;
+
+use crate::navcfg;
+use crate::sink::Sink;
+
+/// Prints one line per epoch: satellites used and tracked, their C/N0, and the satellites of
+/// each constellation
+pub struct SatPrinter {
+    source: Source,
+    /// Malformed messages, reported once and counted
+    malformed: usize,
+}
+
+impl Default for SatPrinter {
+    fn default() -> Self {
+        Self {
+            source: Source::new(&[NAV_SAT, NAV_SIG, NAV_SVINFO]),
+            malformed: 0,
+        }
+    }
+}
+
+/// Like "sats: iTOW 503400 ms, 9 used / 14 tracked, C/N0 mean 38.2 max 47 dBHz, gps 5/7,
+/// galileo 2/4, glonass 2/3", the signals used and tracked being added for NAV-SIG
+fn summary(epoch: &Epoch) -> String {
+    // best signal of each satellite tracked
+    let mut satellites = BTreeMap::<(u8, u8), (u8, bool)>::new();
+    for sat in epoch.satellites.iter().filter(|sat| sat.tracked()) {
+        let entry = satellites.entry((sat.gnss_id, sat.sv_id)).or_default();
+        *entry = (entry.0.max(sat.cno), entry.1 || sat.used);
+    }
+    let used = satellites.values().filter(|(_, used)| *used).count();
+    let mut line = format!(
+        "sats: iTOW {} ms, {} used / {} tracked",
+        epoch.itow,
+        used,
+        satellites.len()
+    );
+    if let Some(max) = satellites.values().map(|(cno, _)| *cno).max() {
+        let mean = satellites.values().map(|(cno, _)| f64::from(*cno)).sum::<f64>()
+            / satellites.len() as f64;
+        line.push_str(&format!(", C/N0 mean {:.1} max {} dBHz", mean, max));
+    }
+    let mut constellations = BTreeMap::<u8, (usize, usize)>::new();
+    for ((gnss_id, _), (_, used)) in &satellites {
+        let entry = constellations.entry(*gnss_id).or_default();
+        *entry = (entry.0 + usize::from(*used), entry.1 + 1);
+    }
+    for (gnss_id, (used, tracked)) in constellations {
+        line.push_str(&format!(
+            ", {} {}/{}",
+            navcfg::constellation_name(gnss_id),
+            used,
+            tracked
+        ));
+    }
+    if epoch.message == NAV_SIG {
+        let tracked = epoch.satellites.iter().filter(|sat| sat.tracked()).count();
+        let used = epoch.satellites.iter().filter(|sat| sat.used).count();
+        line.push_str(&format!(", {} used / {} tracked signals", used, tracked));
+    }
+    line
+}
+
+impl Sink for SatPrinter {
+    fn name(&self) -> String {
+        "satellites printer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        // NAV-SIG and NAV-SVINFO are not decoded by the ublox crate
+        let (message, payload) = match packet {
+            PacketRef::NavSat(sat) => (NAV_SAT, sat.as_bytes()),
+            PacketRef::Unknown(unknown) => ((unknown.class, unknown.msg_id), unknown.payload),
+            _ => return Ok(()),
+        };
+        match self.source.epoch(message, payload) {
+            Ok(Some(epoch)) => info!("{}", summary(&epoch)),
+            Ok(None) => {},
+            Err(e) => {
+                if self.malformed == 0 {
+                    warn!("{}", e);
+                }
+                self.malformed += 1;
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if self.malformed > 1 {
+            warn!("{} malformed satellite messages ignored", self.malformed);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+//! End of the recording session: Ctrl-C (SIGINT), SIGTERM, the end of the
+//! requested duration, the packet and byte limits, the idle timeout, the time
+//! after the first fix or an antenna fault stop the recording loops, so the outputs are completed,
+//! gzip streams included, and the session is summarized.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::antenna::AntennaWatch;
+use crate::base::SurveyWatch;
+use crate::fixstop::FixWatch;
+use crate::frame::{self, FrameScanner};
+use crate::geofence::GeofenceWatch;
+use crate::jamming::JammingWatch;
+use crate::protocol::ProtocolScanner;
+use crate::sidecar::{Sidecar, Statistics};
+
+/// Name of the UBX message class, in hexadecimal when unknown
+fn class_name(class: u8) -> String {
+    frame::class_name(class)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("0x{:02x}", class))
+}
+
+/// Set once the session is to end. A second signal terminates the process
+/// right away, should completing the outputs hang
+fn register_shutdown() -> Arc<AtomicBool> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register_conditional_shutdown(signal, 1, shutdown.clone())
+            .and_then(|_| signal_hook::flag::register(signal, shutdown.clone()))
+            .unwrap_or_else(|e| {
+                crate::exit_on(&format!("failed to register signal {} handler", signal), e)
+            });
+    }
+    shutdown
+}
+
+/// Bounds of the capture, in data received from the receiver
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Limits {
+    /// UBX packets with a valid checksum
+    pub packets: Option<u64>,
+    pub bytes: Option<u64>,
+}
+
+/// Statistics of the session
+pub struct Session {
+    start: Instant,
+    shutdown: Arc<AtomicBool>,
+    /// Duration of the recording, until stopped otherwise
+    duration: Option<Duration>,
+    limits: Limits,
+    limit_reached: bool,
+    /// Ended by a failure, like the loss of the receiver
+    failed: bool,
+    received: u64,
+    scanner: FrameScanner,
+    /// Splits the received bytes into frames of any protocol, to count the others
+    protocols: ProtocolScanner,
+    /// Bytes received outside valid UBX, NMEA or RTCM3 frames
+    discarded: u64,
+    packets: u64,
+    /// Packets per class and id
+    messages: BTreeMap<(u8, u8), u64>,
+    /// Period of the statistics lines, none when quiet
+    stats_interval: Option<Duration>,
+    /// Time and bytes received of the last statistics line
+    last_stats: (Instant, u64),
+    /// Description of the session, written next to the output
+    sidecar: Option<Sidecar>,
+    /// Longest time without data, and whether the session ends then rather than warning
+    idle_timeout: Option<(Duration, bool)>,
+    /// Time of the last data, and whether the wait since was reported
+    last_data: (Instant, bool),
+    /// Ends the session after the first fix
+    fix_watch: Option<FixWatch>,
+    /// Survey-in progress of the NAV-SVIN messages
+    survey: SurveyWatch,
+    /// The survey-in completed since the last call to `survey_completed`
+    survey_completed: bool,
+    /// Antenna status of the MON-HW messages, and whether a fault ends the session
+    antenna: Option<(AntennaWatch, bool)>,
+    /// Fence states of the NAV-GEOFENCE messages
+    geofence: Option<GeofenceWatch>,
+    /// Jamming states of the MON-RF or MON-HW messages
+    jamming: Option<JammingWatch>,
+    /// RXM-RAWX epochs at the last statistics line, when reported
+    raw_epochs: Option<u64>,
+}
+
+impl Session {
+    /// Starts the session, lasting `duration` when given,
+    /// printing statistics every `stats_interval` when given
+    pub fn new(
+        duration: Option<Duration>,
+        limits: Limits,
+        stats_interval: Option<Duration>,
+    ) -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            shutdown: register_shutdown(),
+            duration,
+            limits,
+            limit_reached: false,
+            failed: false,
+            received: 0,
+            scanner: FrameScanner::default(),
+            protocols: ProtocolScanner::default(),
+            discarded: 0,
+            packets: 0,
+            messages: BTreeMap::new(),
+            stats_interval,
+            last_stats: (start, 0),
+            sidecar: None,
+            idle_timeout: None,
+            last_data: (start, false),
+            fix_watch: None,
+            survey: SurveyWatch::default(),
+            survey_completed: false,
+            antenna: None,
+            geofence: None,
+            jamming: None,
+            raw_epochs: None,
+        }
+    }
+
+    /// Ends the session `after` the first 3D fix, failing it without a fix within `timeout`
+    pub fn stop_after_fix(&mut self, after: Duration, timeout: Option<Duration>) {
+        self.fix_watch = Some(FixWatch::new(self.start, after, timeout));
+    }
+
+    /// Reports the changes of the antenna status, a short or open antenna ending the session
+    /// when `abort` is set
+    pub fn watch_antenna(&mut self, abort: bool) {
+        self.antenna = Some((AntennaWatch::default(), abort));
+    }
+
+    /// Prints the changes of the geofence states
+    pub fn watch_geofence(&mut self) {
+        self.geofence = Some(GeofenceWatch::default());
+    }
+
+    /// Reports the changes of the jamming state, in the sidecar too
+    pub fn watch_jamming(&mut self) {
+        self.jamming = Some(JammingWatch::default());
+    }
+
+    /// Adds the RXM-RAWX epochs per second to the statistics line
+    pub fn count_raw_epochs(&mut self) {
+        self.raw_epochs = Some(0);
+    }
+
+    /// Reports waits of `timeout` without data, ending the session on them when `exit` is set
+    pub fn watch_idle(&mut self, timeout: Duration, exit: bool) {
+        self.idle_timeout = Some((timeout, exit));
+    }
+
+    fn check_idle(&mut self) {
+        let (Some((timeout, exit)), (last, false)) = (self.idle_timeout, self.last_data) else {
+            return;
+        };
+        if last.elapsed() < timeout {
+            return;
+        }
+        warn!(
+            "{}: no data received for {:?}",
+            chrono::Utc::now().to_rfc3339(),
+            timeout
+        );
+        self.last_data.1 = true;
+        if exit {
+            self.event("idle timeout");
+            self.fail();
+        }
+    }
+
+    /// Describes the session in the sidecar, written right away
+    pub fn describe(&mut self, sidecar: Sidecar) {
+        info!("describing the session into \"{}\"", sidecar.path());
+        self.sidecar = Some(sidecar);
+        self.write_sidecar(false);
+    }
+
+    /// Records an event of the session in the sidecar
+    pub fn event(&mut self, description: &str) {
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.event(description);
+        }
+        self.write_sidecar(false);
+    }
+
+    fn write_sidecar(&self, end: bool) {
+        let Some(sidecar) = &self.sidecar else {
+            return;
+        };
+        let statistics = Statistics {
+            received: self.received,
+            packets: self.packets,
+            classes: &self.classes(),
+            ttff: self.fix_watch.as_ref().and_then(FixWatch::ttff),
+            survey: self
+                .survey
+                .last()
+                .map(|survey| (survey, self.survey.is_completed())),
+        };
+        if let Err(e) = sidecar.write(&statistics, end) {
+            warn!("failed to write \"{}\": {}", sidecar.path(), e);
+        }
+    }
+
+    /// Packets per class
+    fn classes(&self) -> BTreeMap<u8, u64> {
+        let mut classes = BTreeMap::<u8, u64>::new();
+        for ((class, _), count) in &self.messages {
+            *classes.entry(*class).or_default() += count;
+        }
+        classes
+    }
+
+    /// Prints the statistics line when due: elapsed time, bytes recorded,
+    /// throughput since the last line and on average, packets per class, and the raw epochs
+    /// per second when counted. Reports the idle and fix timeouts first
+    pub fn tick(&mut self) {
+        self.check_idle();
+        if self.fix_watch.as_ref().is_some_and(FixWatch::timed_out) && !self.failed {
+            error!("no 3D fix within the fix timeout");
+            self.event("fix timeout");
+            self.fail();
+        }
+        let Some(interval) = self.stats_interval else {
+            return;
+        };
+        let (last, last_received) = self.last_stats;
+        let now = Instant::now();
+        if now.duration_since(last) < interval {
+            return;
+        }
+        let elapsed = now.duration_since(self.start).as_secs();
+        let rate = |bytes: u64, duration: Duration| bytes as f64 / duration.as_secs_f64().max(1e-3);
+        let classes = self
+            .classes()
+            .into_iter()
+            .map(|(class, count)| format!("{} {}", class_name(class), count))
+            .collect::<Vec<_>>();
+        let epochs = self.messages.get(&(0x02, 0x15)).copied().unwrap_or_default();
+        let raw = match &mut self.raw_epochs {
+            Some(counted) => {
+                let line = format!(
+                    ", {:.1} raw epochs/s",
+                    rate(epochs - *counted, now.duration_since(last))
+                );
+                *counted = epochs;
+                line
+            },
+            None => String::new(),
+        };
+        info!(
+            "stats: {:02}:{:02}:{:02}, {} bytes, {:.0} B/s now, {:.0} B/s average, {}, {} checksum failures{}",
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60,
+            self.received,
+            rate(self.received - last_received, now.duration_since(last)),
+            rate(self.received, now.duration_since(self.start)),
+            if classes.is_empty() {
+                "no UBX packets".to_string()
+            } else {
+                classes.join(" ")
+            },
+            self.scanner.checksum_failures(),
+            raw
+        );
+        self.last_stats = (now, self.received);
+    }
+
+    /// True once after the survey-in completed
+    pub fn survey_completed(&mut self) -> bool {
+        std::mem::take(&mut self.survey_completed)
+    }
+
+    /// Ends the session on a failure
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// True once stopped by a signal, once the duration elapsed, a limit is reached,
+    /// once recorded long enough after the first fix or on a failure, like an antenna fault
+    pub fn is_over(&self) -> bool {
+        self.failed
+            || self.limit_reached
+            || self.fix_watch.as_ref().is_some_and(FixWatch::is_done)
+            || self.shutdown.load(Ordering::Relaxed)
+            || self
+                .duration
+                .is_some_and(|duration| self.start.elapsed() >= duration)
+    }
+
+    /// Accounts for bytes read from the receiver. Returns how many of them are
+    /// to be recorded: all of them, unless a limit is reached within them,
+    /// the packet limit cutting right after the last packet
+    pub fn feed(&mut self, data: &[u8]) -> usize {
+        if !data.is_empty() {
+            if self.last_data.1 {
+                info!(
+                    "{}: data received again after {:.1} s",
+                    chrono::Utc::now().to_rfc3339(),
+                    self.last_data.0.elapsed().as_secs_f64()
+                );
+            }
+            self.last_data = (Instant::now(), false);
+        }
+        let mut kept = data.len();
+        if let Some(max) = self.limits.bytes {
+            let left = max.saturating_sub(self.received);
+            if left <= kept as u64 {
+                kept = left as usize;
+                self.limit_reached = true;
+            }
+        }
+        self.received += kept as u64;
+        self.scanner.push(&data[..kept]);
+        let failures = self.scanner.checksum_failures();
+        while let Some(frame) = self.scanner.next_frame() {
+            trace!(
+                "UBX frame class 0x{:02x} id 0x{:02x}, {} bytes",
+                frame[2],
+                frame[3],
+                frame.len()
+            );
+            if let Some(watch) = &mut self.fix_watch {
+                watch.frame(&frame);
+            }
+            if let Some(survey) = self.survey.frame(&frame) {
+                self.survey_completed = true;
+                self.event(&format!("survey-in completed: {}", survey.describe()));
+            }
+            if let Some(watch) = &mut self.geofence {
+                watch.frame(&frame);
+            }
+            let jamming = self.jamming.as_mut().map(|watch| watch.frame(&frame));
+            for description in jamming.unwrap_or_default() {
+                self.event(&description);
+            }
+            let antenna = self.antenna.as_mut().and_then(|(watch, abort)| {
+                watch.frame(&frame).map(|(description, fault)| (description, fault && *abort))
+            });
+            if let Some((description, abort)) = antenna {
+                self.event(&description);
+                if abort {
+                    error!("antenna fault, stopping the recording");
+                    self.fail();
+                }
+            }
+            self.packets += 1;
+            *self.messages.entry((frame[2], frame[3])).or_default() += 1;
+            if self.limits.packets == Some(self.packets) {
+                // the bytes following the last packet are dropped
+                let dropped = self.scanner.pending();
+                self.received -= dropped as u64;
+                kept -= dropped;
+                self.limit_reached = true;
+                break;
+            }
+        }
+        // bytes outside frames, among the bytes kept
+        let discarded = &mut self.discarded;
+        self.protocols.scan(&data[..kept], |protocol, bytes| {
+            if protocol.is_none() {
+                *discarded += bytes.len() as u64;
+            }
+        });
+        if self.scanner.checksum_failures() > failures {
+            warn!(
+                "UBX checksum failure, {} so far",
+                self.scanner.checksum_failures()
+            );
+        }
+        kept
+    }
+
+    /// Prints the summary, with the size of the completed output file
+    pub fn summary(&self, output: Option<&str>) {
+        info!(
+            "session: {:.1} s, {} bytes received, {} UBX packets",
+            self.start.elapsed().as_secs_f64(),
+            self.received,
+            self.packets
+        );
+        // an incomplete frame at the end counts too
+        let discarded = self.discarded + self.protocols.pending() as u64;
+        if discarded > 0 {
+            info!(
+                "{} bytes outside valid UBX, NMEA or RTCM3 frames (dropped with --protocols)",
+                discarded
+            );
+        }
+        if let Some(ttff) = self.fix_watch.as_ref().and_then(FixWatch::ttff) {
+            info!("time to first fix: {:.1} s", ttff.as_secs_f64());
+        }
+        // named pipes have no size
+        let written = output.and_then(|path| Some((path, std::fs::metadata(path).ok()?)));
+        if let Some((path, metadata)) = written.filter(|(_, metadata)| metadata.is_file()) {
+            info!("\"{}\": {} bytes written", path, metadata.len());
+        }
+        if self.limit_reached {
+            info!("capture limit reached, UBX packets captured:");
+            for ((class, id), count) in &self.messages {
+                info!("  class 0x{:02x} id 0x{:02x}: {}", class, id, count);
+            }
+        }
+        self.write_sidecar(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &[u8] = b"$GNGGA,,,,,,0,00,99.99,,,,,,*56\r\n";
+
+    fn start(limits: Limits) -> Session {
+        Session::new(None, limits, None)
+    }
+
+    #[test]
+    fn short_reads() {
+        let pvt = frame::frame(0x01, 0x07, &[0; 92]);
+        let data = [b"\x00\xff".as_slice(), &pvt, GGA, b"junk", &pvt].concat();
+        let mut session = start(Limits::default());
+        // reads of a few bytes, frames arriving in pieces
+        let kept: usize = data.chunks(5).map(|read| session.feed(read)).sum();
+        assert_eq!(kept, data.len());
+        assert_eq!(session.received, data.len() as u64);
+        assert_eq!(session.packets, 2);
+        assert_eq!(session.discarded, 6);
+    }
+
+    #[test]
+    fn discarded_within_limits() {
+        let pvt = frame::frame(0x01, 0x07, &[0; 92]);
+        let data = [b"junk".as_slice(), &pvt, b"junk", &pvt].concat();
+        let mut session = start(Limits {
+            packets: Some(1),
+            bytes: None,
+        });
+        // the junk following the last packet is not recorded
+        assert_eq!(session.feed(&data), 4 + pvt.len());
+        assert_eq!(session.discarded, 4);
+        // an incomplete frame is cut by the byte limit
+        let mut session = start(Limits {
+            packets: None,
+            bytes: Some(10),
+        });
+        assert_eq!(session.feed(&data), 10);
+        assert_eq!((session.discarded, session.protocols.pending()), (4, 6));
+    }
+}
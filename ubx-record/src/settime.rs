@@ -0,0 +1,95 @@
+//! Time of the host clock sent to the receiver at startup (`--set-time`), like for the
+//! receivers without a backup battery, starting without any notion of time: UBX-MGA-INI-TIME-UTC
+//! with the accuracy of the host clock. The offset of the host clock from the receiver time is
+//! printed at the first solution with a resolved time, as a sanity check.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use ublox::PacketRef;
+
+use crate::frame;
+use crate::sink::Sink;
+use crate::Device;
+
+/// Host clocks before this year are not set, like the ones of the boards without a real time
+/// clock which booted without network
+const MIN_YEAR: i32 = 2020;
+
+/// NAV-PVT `valid`: validDate, validTime and fullyResolved
+const VALID_TIME: u8 = 0x07;
+
+/// MGA-INI-TIME-UTC payload, the time being valid on receipt of the message and the leap
+/// seconds unknown
+fn time_utc(now: DateTime<Utc>, accuracy: Duration) -> Vec<u8> {
+    let mut payload = vec![0x10, 0x00, 0x00, i8::MIN as u8];
+    payload.extend_from_slice(&(now.year() as u16).to_le_bytes());
+    payload.extend_from_slice(&[
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+        0,
+    ]);
+    // a leap second is not sent as such
+    payload.extend_from_slice(&now.nanosecond().min(999_999_999).to_le_bytes());
+    let seconds = u16::try_from(accuracy.as_secs()).unwrap_or(u16::MAX);
+    payload.extend_from_slice(&seconds.to_le_bytes());
+    payload.extend_from_slice(&[0, 0]);
+    payload.extend_from_slice(&accuracy.subsec_nanos().to_le_bytes());
+    payload
+}
+
+/// Sends the time of the host clock, returning it, and fails without sending anything when
+/// the host clock is obviously wrong
+pub fn set_time(device: &mut Device, accuracy: Duration) -> Result<DateTime<Utc>, String> {
+    let now = Utc::now();
+    if now.year() < MIN_YEAR {
+        return Err(format!(
+            "The host clock is not set ({}), the time is not sent to the receiver",
+            now.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    device
+        .write_all(&frame::frame(0x13, 0x40, &time_utc(now, accuracy)))
+        .map_err(|e| format!("Failed to write UBX-MGA-INI-TIME-UTC: {}", e))?;
+    Ok(now)
+}
+
+/// Prints the offset of the host clock from the receiver time at the first NAV-PVT with a
+/// resolved time, the transmission delay included
+#[derive(Default)]
+pub struct ClockCheck {
+    done: bool,
+}
+
+impl Sink for ClockCheck {
+    fn name(&self) -> String {
+        "clock check".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        !self.done
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        let PacketRef::NavPvt(pvt) = packet else {
+            return Ok(());
+        };
+        if pvt.valid() & VALID_TIME != VALID_TIME {
+            return Ok(());
+        }
+        let Ok(epoch) = DateTime::<Utc>::try_from(pvt) else {
+            return Ok(());
+        };
+        let offset = Utc::now() - epoch;
+        info!(
+            "Receiver time {}, the host clock being {:+.3} s off (host - receiver)",
+            epoch.format("%Y-%m-%d %H:%M:%S%.3f"),
+            offset.num_milliseconds() as f64 / 1000.0
+        );
+        self.done = true;
+        Ok(())
+    }
+}
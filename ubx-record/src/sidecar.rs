@@ -0,0 +1,169 @@
+//! Description of the recording session, written next to the output as `<output>.meta.json`.
+//!
+//! The sidecar is written when streaming starts, rewritten on reconnection events, and
+//! completed at the end of the session with the end time and the statistics. It is replaced
+//! atomically, so it always holds a complete JSON object.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::base::Survey;
+use crate::frame;
+use crate::metadata::{json_string, Receiver};
+
+/// Settings of the serial port
+pub struct Serial {
+    pub port: String,
+    pub baud: u32,
+    pub data_bits: String,
+    pub parity: String,
+    pub stop_bits: String,
+    pub flow_control: String,
+}
+
+impl Serial {
+    fn json(&self) -> String {
+        format!(
+            "{{\"port\":{},\"baud\":{},\"data_bits\":{},\"parity\":{},\"stop_bits\":{},\
+\"flow_control\":{}}}",
+            json_string(&self.port),
+            self.baud,
+            self.data_bits,
+            json_string(&self.parity),
+            self.stop_bits,
+            json_string(&self.flow_control)
+        )
+    }
+}
+
+/// Statistics of the session, at the time the sidecar is written
+pub struct Statistics<'a> {
+    pub received: u64,
+    pub packets: u64,
+    /// Packets per class
+    pub classes: &'a BTreeMap<u8, u64>,
+    /// Time to the first 3D fix, when watched for by --stop-after-fix
+    pub ttff: Option<Duration>,
+    /// Last survey-in status of the NAV-SVIN messages, and whether it completed
+    pub survey: Option<(Survey, bool)>,
+}
+
+pub struct Sidecar {
+    path: String,
+    start_time: String,
+    serial: String,
+    receiver: String,
+    /// Messages enabled on the receiver: name, class, id and output rates
+    messages: Vec<(String, u8, u8, [u8; 6])>,
+    /// Time and description of the reconnection events
+    events: Vec<(String, String)>,
+}
+
+impl Sidecar {
+    /// Describes the recording into `output`, its sidecar being `<output>.meta.json`
+    pub fn new(
+        output: &str,
+        serial: &Serial,
+        receiver: &Receiver,
+        messages: Vec<(String, u8, u8, [u8; 6])>,
+    ) -> Self {
+        Self {
+            path: format!("{}.meta.json", output),
+            start_time: Utc::now().to_rfc3339(),
+            serial: serial.json(),
+            receiver: receiver.json(),
+            messages,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Records an event, like the serial port failing or being reopened
+    pub fn event(&mut self, description: &str) {
+        self.events
+            .push((Utc::now().to_rfc3339(), description.to_string()));
+    }
+
+    fn json(&self, statistics: &Statistics, end: bool) -> String {
+        let messages = self
+            .messages
+            .iter()
+            .map(|(name, class, id, rates)| {
+                let rates = rates.iter().map(u8::to_string).collect::<Vec<_>>();
+                format!(
+                    "{{\"name\":{},\"class\":{},\"id\":{},\"rates\":[{}]}}",
+                    json_string(name),
+                    class,
+                    id,
+                    rates.join(",")
+                )
+            })
+            .collect::<Vec<_>>();
+        let classes = statistics
+            .classes
+            .iter()
+            .map(|(class, count)| {
+                let name = frame::class_name(*class)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("0x{:02x}", class));
+                format!("{}:{}", json_string(&name), count)
+            })
+            .collect::<Vec<_>>();
+        let events = self
+            .events
+            .iter()
+            .map(|(time, description)| {
+                format!(
+                    "{{\"time\":{},\"event\":{}}}",
+                    json_string(time),
+                    json_string(description)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"tool\":\"ubx-record\",\"version\":{},\"start_time\":{},\"end_time\":{},\
+\"serial\":{},\"receiver\":{},\"messages\":[{}],\"bytes\":{},\"packets\":{},\"classes\":{{{}}},\
+\"time_to_first_fix_s\":{},\"survey_in\":{},\"events\":[{}]}}\n",
+            json_string(env!("CARGO_PKG_VERSION")),
+            json_string(&self.start_time),
+            if end {
+                json_string(&Utc::now().to_rfc3339())
+            } else {
+                "null".to_string()
+            },
+            self.serial,
+            self.receiver,
+            messages.join(","),
+            statistics.received,
+            statistics.packets,
+            classes.join(","),
+            statistics
+                .ttff
+                .map_or("null".to_string(), |ttff| format!("{:.3}", ttff.as_secs_f64())),
+            statistics
+                .survey
+                .map_or("null".to_string(), |(survey, completed)| format!(
+                    "{{\"duration_s\":{},\"accuracy_m\":{:.4},\"observations\":{},\"valid\":{},\
+\"completed\":{}}}",
+                    survey.duration_s,
+                    survey.accuracy_m(),
+                    survey.observations,
+                    survey.valid,
+                    completed
+                )),
+            events.join(",")
+        )
+    }
+
+    /// Writes the sidecar, with the end time once `end` is set
+    pub fn write(&self, statistics: &Statistics, end: bool) -> std::io::Result<()> {
+        let temporary = format!("{}.tmp", self.path);
+        std::fs::write(&temporary, self.json(statistics, end))?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
@@ -0,0 +1,117 @@
+//! Outputs of a recording session.
+//!
+//! Every output is a sink, receiving the raw bytes read from the receiver
+//! and/or the packets parsed from them. The bytes are parsed once for all sinks.
+//! Sinks are isolated: errors of a sink are reported and counted,
+//! the other sinks being fed regardless.
+
+use std::io::Write;
+
+use ublox::{PacketRef, Parser};
+
+pub trait Sink {
+    /// Name used in reports
+    fn name(&self) -> String;
+
+    /// True when the sink needs the parsed packets
+    fn wants_packets(&self) -> bool {
+        false
+    }
+
+    /// Raw bytes read from the receiver
+    fn raw(&mut self, _data: &[u8]) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Packet parsed from the received bytes
+    fn packet(&mut self, _packet: &PacketRef) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Completes the output at the end of the session
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Sink for crate::BufferedWriter {
+    fn name(&self) -> String {
+        "output file".to_string()
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.write_all(data)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.finalize()
+    }
+}
+
+/// Sink and its statistics
+struct Entry {
+    sink: Box<dyn Sink>,
+    errors: usize,
+}
+
+#[derive(Default)]
+pub struct Sinks {
+    entries: Vec<Entry>,
+    parser: Parser<Vec<u8>>,
+}
+
+impl Sinks {
+    pub fn add(&mut self, sink: Box<dyn Sink>) {
+        self.entries.push(Entry { sink, errors: 0 });
+    }
+
+    /// Feeds the bytes read from the receiver to every sink
+    pub fn feed(&mut self, data: &[u8]) {
+        for entry in &mut self.entries {
+            let result = entry.sink.raw(data);
+            entry.report(result);
+        }
+        if !self.entries.iter().any(|entry| entry.sink.wants_packets()) {
+            return;
+        }
+        let mut it = self.parser.consume(data);
+        while let Some(packet) = it.next() {
+            let Ok(packet) = packet else {
+                continue;
+            };
+            for entry in &mut self.entries {
+                if entry.sink.wants_packets() {
+                    let result = entry.sink.packet(&packet);
+                    entry.report(result);
+                }
+            }
+        }
+    }
+}
+
+impl Sinks {
+    /// Completes every sink, at the end of the session
+    pub fn finish(&mut self) {
+        for entry in &mut self.entries {
+            let result = entry.sink.finish();
+            entry.report(result);
+        }
+    }
+}
+
+impl Entry {
+    fn report(&mut self, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            self.errors += 1;
+            // report the first errors only, the sink failing on every read otherwise
+            if self.errors <= 3 {
+                warn!("{}: {}", self.sink.name(), e);
+            } else if self.errors == 4 {
+                warn!(
+                    "{}: failing repeatedly, errors not reported anymore",
+                    self.sink.name()
+                );
+            }
+        }
+    }
+}
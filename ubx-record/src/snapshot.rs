@@ -0,0 +1,454 @@
+//! Snapshots of the receiver configuration (`configure --dump FILE`), restored with
+//! `configure --restore FILE`, in the formats of ubx-config, shared by ubx_common: the
+//! known configuration keys in RAM polled with UBX-CFG-VALGET are written to a u-center
+//! configuration file, as `ubx-config dump` does. Receivers without VALGET, M8 and older,
+//! get a legacy dump of the CFG messages instead, as `ubx-config dump --legacy` does, the
+//! payloads being kept as polled for the restore to write them back as they were. Both
+//! are read back by `ubx-config diff`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+use ubx_common::legacy::{self, Item, DUMP_HEADER};
+use ubx_common::ucenter;
+use ubx_common::valcfg::{Ack, Link, Response};
+
+use crate::metadata::Receiver;
+use crate::portcfg::{self, PortConfig};
+use crate::valcfg::{self, Key};
+use crate::Device;
+
+const CFG: u8 = 0x06;
+const PRT: u8 = 0x00;
+const NAVX5: u8 = 0x23;
+const NAV5: u8 = 0x24;
+
+/// Parameters applied by a CFG-NAV5, the mask of a polled payload not telling them
+const NAV5_MASK: u16 = 0x05ff;
+/// Parameters applied by a CFG-NAVX5: minimum and maximum satellites, minimum C/N0,
+/// initial 3D fix, week rollover, AssistNow acknowledgements, PPP and AssistNow Autonomous
+const NAVX5_MASK1: u16 = 0x664c;
+/// ADR and signal attenuation compensation
+const NAVX5_MASK2: u32 = 0x00c0;
+
+/// RAM layer of CFG-VALGET and CFG-VALSET
+const LAYER_RAM: (u8, u8) = (0, 0x01);
+
+/// Configuration read from a snapshot
+#[derive(Debug)]
+pub struct Snapshot {
+    /// Comment lines of the header, like "software: ROM SPG 5.10 (7b202e)"
+    header: Vec<String>,
+    content: Content,
+}
+
+#[derive(Debug)]
+enum Content {
+    /// CFG payloads of a legacy dump
+    Messages(Vec<(String, Vec<u8>)>),
+    /// Values of a u-center configuration file, with their VALSET layers
+    Keys(Vec<(Key, Vec<u8>, u8)>),
+}
+
+/// Settings of a CFG payload, like "meas_rate = 100, nav_rate = 1, time_ref = 1"
+fn describe(item: &Item, payload: &[u8]) -> String {
+    item.decode(payload)
+        .iter()
+        .map(|(key, value)| format!("{} = {}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Message of an item, like CFG-PRT for CFG-PRT.UART1
+fn message(item: &Item) -> &str {
+    item.section.split('.').next().unwrap_or_default()
+}
+
+/// Sets the masks of the parameters to apply, returning the bytes they take, which are not
+/// polled back as written
+fn apply_masks(id: u8, payload: &mut [u8]) -> std::ops::Range<usize> {
+    match id {
+        NAV5 if payload.len() >= 2 => {
+            payload[..2].copy_from_slice(&NAV5_MASK.to_le_bytes());
+            0..2
+        },
+        NAVX5 if payload.len() >= 8 => {
+            payload[2..4].copy_from_slice(&NAVX5_MASK1.to_le_bytes());
+            payload[4..8].copy_from_slice(&NAVX5_MASK2.to_le_bytes());
+            2..8
+        },
+        _ => 0..0,
+    }
+}
+
+/// Values of the known keys in RAM, without those unknown to the firmware, none when
+/// CFG-VALGET itself is
+fn poll_keys(device: &mut Device) -> Vec<(Key, Vec<u8>)> {
+    valcfg::valget(device, &valcfg::known_keys(), LAYER_RAM.0).unwrap_or_else(|e| {
+        info!("Configuration keys not dumped, generation 9 receivers and later: {}", e);
+        Vec::new()
+    })
+}
+
+/// Header lines of the dumps, those of ubx-config
+fn header(receiver: &Receiver) -> Vec<String> {
+    let mut header = vec![format!("date: {}", chrono::Utc::now().to_rfc3339())];
+    header.extend(receiver.sw_version().map(|version| format!("software: {}", version)));
+    header.extend(receiver.hw_version().map(|version| format!("hardware: {}", version)));
+    header.extend(
+        receiver
+            .extensions()
+            .into_iter()
+            .map(|extension| format!("extension: {}", extension)),
+    );
+    header
+}
+
+/// Polls the configuration and writes it to `path`: the known keys to a u-center
+/// configuration file when the receiver reads them with VALGET, the CFG messages to a
+/// legacy dump otherwise, skipping those the receiver does not answer. Returns what was
+/// written, like "532 configuration keys".
+pub fn dump(device: &mut Device, timeout: Duration, path: &str) -> Result<String, String> {
+    let header = header(&Receiver::poll(device, timeout));
+    let values = poll_keys(device)
+        .into_iter()
+        .map(|(key, value)| (key.id, value))
+        .collect::<Vec<_>>();
+    let (written, summary) = if values.is_empty() {
+        let items = legacy::items();
+        let mut responses = Vec::with_capacity(items.len());
+        for item in &items {
+            let response = device
+                .poll(item.class, item.id, &item.poll_payload, &item.poll_payload, timeout)
+                .map_err(|e| format!("Failed to poll UBX-{}: {}", message(item), e))?;
+            match &response {
+                Response::Payload(payload) => debug!("{}: {}", item.name, describe(item, payload)),
+                response => info!("{} not dumped: {}", item.name, response),
+            }
+            responses.push(response);
+        }
+        let polled = responses
+            .iter()
+            .filter(|response| matches!(response, Response::Payload(_)))
+            .count();
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create the snapshot \"{}\": {}", path, e))?;
+        (
+            legacy::write_dump(BufWriter::new(file), &header, &items, &responses),
+            format!("{} CFG messages", polled),
+        )
+    } else {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create the snapshot \"{}\": {}", path, e))?;
+        (
+            ucenter::write(BufWriter::new(file), &header, "RAM", &values),
+            format!("{} configuration keys", values.len()),
+        )
+    };
+    written.map_err(|e| format!("Failed to write the snapshot \"{}\": {}", path, e))?;
+    Ok(summary)
+}
+
+impl Snapshot {
+    /// Reads and validates a snapshot
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the snapshot \"{}\": {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid snapshot \"{}\": {}", path, e))
+    }
+
+    /// Legacy dump, or u-center configuration file
+    fn parse(text: &str) -> Result<Self, String> {
+        if text.lines().next() == Some(DUMP_HEADER) {
+            let dump = legacy::parse_dump(text)?;
+            let items = legacy::items();
+            for (name, payload) in &dump.payloads {
+                let item = items
+                    .iter()
+                    .find(|item| item.name == *name)
+                    .ok_or_else(|| format!("{}: unknown CFG message", name))?;
+                if payload.len() <= item.poll_payload.len() {
+                    return Err(format!("{}: truncated payload", name));
+                }
+            }
+            if dump.payloads.is_empty() {
+                return Err("no payloads to restore".to_string());
+            }
+            return Ok(Self {
+                header: dump.header,
+                content: Content::Messages(dump.payloads),
+            });
+        }
+        let (entries, warnings) = ucenter::parse(text)?;
+        for warning in warnings {
+            warn!("{}", warning);
+        }
+        if entries.is_empty() {
+            return Err("neither a legacy dump nor a u-center configuration file".to_string());
+        }
+        let values = entries
+            .into_iter()
+            .map(|entry| {
+                let key = Key::of_id(entry.key)
+                    .ok_or_else(|| format!("line {}: invalid key {}", entry.line, entry.label()))?;
+                Ok((key, entry.value, entry.layers.unwrap_or(LAYER_RAM.1)))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            header: text
+                .lines()
+                .map_while(|line| line.strip_prefix("# "))
+                .map(str::to_string)
+                .collect(),
+            content: Content::Keys(values),
+        })
+    }
+
+    /// Number of CFG messages or configuration keys
+    pub fn count(&self) -> usize {
+        match &self.content {
+            Content::Messages(payloads) => payloads.len(),
+            Content::Keys(values) => values.len(),
+        }
+    }
+
+    /// Firmware of the receiver dumped, from the header
+    fn firmware(&self) -> Option<&str> {
+        let line = |prefix| self.header.iter().find_map(|line| line.strip_prefix(prefix));
+        line("extension: FWVER=").or_else(|| line("software: "))
+    }
+
+    /// Writes the snapshot back and polls the settings back, returning each item with its
+    /// outcome: the settings restored, or why they could not be
+    pub fn restore(
+        &self,
+        device: &mut Device,
+        timeout: Duration,
+    ) -> Vec<(String, Result<String, String>)> {
+        let firmware = Receiver::poll(device, timeout).firmware();
+        if firmware.as_deref() != self.firmware() {
+            warn!(
+                "The snapshot is of firmware {}, the receiver running {}",
+                self.firmware().unwrap_or("unknown"),
+                firmware.as_deref().unwrap_or("unknown")
+            );
+        }
+        self.apply(device, timeout)
+    }
+
+    /// The CFG messages are written the ports last, as their change may cut the link, the
+    /// keys layer by layer
+    fn apply(
+        &self,
+        device: &mut Device,
+        timeout: Duration,
+    ) -> Vec<(String, Result<String, String>)> {
+        match &self.content {
+            Content::Messages(payloads) => {
+                let items = legacy::items();
+                // validated when loaded
+                let mut payloads = payloads
+                    .iter()
+                    .map(|(name, payload)| {
+                        (items.iter().find(|item| item.name == *name).unwrap(), payload)
+                    })
+                    .collect::<Vec<_>>();
+                payloads.sort_by_key(|(item, _)| item.id == PRT);
+                payloads
+                    .into_iter()
+                    .map(|(item, payload)| {
+                        (item.name.clone(), restore_message(device, item, payload, timeout))
+                    })
+                    .collect()
+            },
+            Content::Keys(values) => {
+                let mut layers = vec![];
+                for (_, _, set) in values {
+                    if !layers.contains(set) {
+                        layers.push(*set);
+                    }
+                }
+                let mut results = vec![];
+                for layers in layers {
+                    let values = values
+                        .iter()
+                        .filter(|(_, _, set)| *set == layers)
+                        .map(|(key, value, _)| (*key, value.clone()))
+                        .collect::<Vec<_>>();
+                    results.extend(restore_keys(device, &values, layers));
+                }
+                results
+            },
+        }
+    }
+}
+
+fn restore_message(
+    device: &mut Device,
+    item: &Item,
+    payload: &[u8],
+    timeout: Duration,
+) -> Result<String, String> {
+    let name = format!("UBX-{}", message(item));
+    let mut payload = payload.to_vec();
+    let masks = apply_masks(item.id, &mut payload);
+    let settings = describe(item, &payload);
+    info!("Restoring {}: {} ...", item.name, settings);
+    if item.id == PRT {
+        let port = PortConfig::parse(&payload)
+            .ok_or_else(|| format!("Unexpected {} of {} bytes", name, payload.len()))?;
+        portcfg::write(device, &port, timeout)?;
+    } else {
+        let ack = device
+            .command(CFG, item.id, &payload, timeout)
+            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        match ack {
+            Ack::Acked => {},
+            Ack::Nak => return Err(format!("rejected (UBX-ACK-NAK of {})", name)),
+            Ack::Timeout => return Err(format!("{} not acknowledged: timeout", name)),
+        }
+    }
+    // polled back, for the settings the receiver did not take
+    let polled = device.poll(CFG, item.id, &item.poll_payload, &item.poll_payload, timeout);
+    let current = match polled {
+        Ok(Response::Payload(current)) => current,
+        Ok(response) if item.id == PRT => {
+            return Ok(format!("{}, not polled back, the link may be cut: {}", settings, response))
+        },
+        Ok(response) => return Err(format!("{} not polled back: {}", name, response)),
+        Err(e) => return Err(format!("{} not polled back: {}", name, e)),
+    };
+    let differs = current.len() != payload.len()
+        || (0..payload.len()).any(|i| !masks.contains(&i) && current[i] != payload[i]);
+    if differs {
+        return Err(format!(
+            "differs once restored: {} instead of {}",
+            describe(item, &current),
+            settings
+        ));
+    }
+    Ok(settings)
+}
+
+/// Sets the values in the `layers`, then polls them back from each
+fn restore_keys(
+    device: &mut Device,
+    values: &[(Key, Vec<u8>)],
+    layers: u8,
+) -> Vec<(String, Result<String, String>)> {
+    let set = valcfg::SET_LAYERS
+        .iter()
+        .filter(|(_, bit)| layers & bit != 0)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    let label = format!("UBX-CFG-VALSET {}", set.join("+"));
+    if let Err(e) = valcfg::valset(device, values, layers) {
+        return vec![(label, Err(e))];
+    }
+    let mut results = vec![(label, Ok(format!("{} keys set", values.len())))];
+    let keys = values.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+    for (layer_name, layer) in valcfg::GET_LAYERS.iter().filter(|(name, _)| set.contains(name)) {
+        let current = match valcfg::valget(device, &keys, *layer) {
+            Ok(current) => current,
+            Err(e) => {
+                results.push((format!("UBX-CFG-VALGET {}", layer_name), Err(e)));
+                continue;
+            },
+        };
+        for (key, value) in values {
+            let name = match key.name() {
+                Some(name) => format!("{} {}", name, layer_name),
+                None => format!("0x{:08x} {}", key.id, layer_name),
+            };
+            let decoded = valcfg::decode(key.ty, value);
+            match current.iter().find(|(current, _)| current.id == key.id) {
+                Some((_, current)) if current == value => {},
+                Some((_, current)) => results.push((
+                    name,
+                    Err(format!(
+                        "differs once restored: {} instead of {}",
+                        valcfg::decode(key.ty, current),
+                        decoded
+                    )),
+                )),
+                None => results.push((name, Err(format!("not polled back, {} expected", decoded)))),
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::frame;
+    use crate::source::{Memory, Port};
+
+    type Outcomes = Vec<(String, Result<String, String>)>;
+
+    /// Restores `snapshot` to a receiver sending `input`, returning the bytes written to it
+    /// and the outcomes
+    fn apply(snapshot: &Snapshot, input: &[u8]) -> (Vec<u8>, Outcomes) {
+        let mut device = Device::new(Port::Memory(Box::new(Memory {
+            input: std::io::Cursor::new(input.to_vec()),
+            ..Default::default()
+        })));
+        device.ack_timeout = Duration::from_millis(50);
+        let results = snapshot.apply(&mut device, Duration::from_millis(50));
+        match device.port {
+            Port::Memory(memory) => (memory.output, results),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn legacy_dump_restored() {
+        let items = legacy::items()
+            .into_iter()
+            .filter(|item| item.name == "CFG-NAV5")
+            .collect::<Vec<_>>();
+        let mut nav5 = vec![0; 36];
+        nav5[2] = 4;
+        let header = ["software: ROM SPG 3.01 (107888)".to_string()];
+        let mut dump = vec![];
+        legacy::write_dump(&mut dump, &header, &items, &[Response::Payload(nav5.clone())]).unwrap();
+        let snapshot = Snapshot::parse(std::str::from_utf8(&dump).unwrap()).unwrap();
+        assert_eq!(snapshot.firmware(), Some("ROM SPG 3.01 (107888)"));
+        assert_eq!(snapshot.count(), 1);
+
+        // acknowledged, the memory port then having nothing left for the poll back
+        let (written, results) = apply(&snapshot, &frame(0x05, 0x01, &[0x06, 0x24]));
+        let mut masked = nav5.clone();
+        masked[..2].copy_from_slice(&NAV5_MASK.to_le_bytes());
+        assert_eq!(written, [frame(0x06, 0x24, &masked), frame(0x06, 0x24, &[])].concat());
+        assert_eq!(
+            results,
+            [(
+                "CFG-NAV5".to_string(),
+                Err("UBX-CFG-NAV5 not polled back: timeout".to_string())
+            )]
+        );
+        assert!(describe(&items[0], &masked).starts_with("mask = 0x05ff, dyn_model = 4, "));
+    }
+
+    #[test]
+    fn invalid_snapshots() {
+        let error = |text: &str| Snapshot::parse(text).unwrap_err();
+        assert_eq!(
+            error(&format!("{}\n\n[payloads]\nCFG-FOO = 0100\n", DUMP_HEADER)),
+            "CFG-FOO: unknown CFG message"
+        );
+        assert_eq!(
+            error(&format!("{}\n\n[payloads]\nCFG-TP5.TP1 = 00\n", DUMP_HEADER)),
+            "CFG-TP5.TP1: truncated payload"
+        );
+        assert_eq!(
+            error("# nothing\n"),
+            "neither a legacy dump nor a u-center configuration file"
+        );
+        let snapshot = Snapshot::parse("# software: HPG 1.32\nRAM CFG-RATE-MEAS 0x64\n").unwrap();
+        assert_eq!(snapshot.firmware(), Some("HPG 1.32"));
+        assert_eq!(snapshot.count(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! Save-on-shutdown (UBX-UPD-SOS): backup of the navigation database in flash before
+//! a planned power-down, restored at the next startup for a hot start.
+//!
+//! The commands and the receiver responses are all UPD-SOS messages, told apart
+//! by their first payload byte (`cmd`).
+
+use std::time::Duration;
+
+use ublox::{CfgRstBuilder, NavBbrPredefinedMask, ResetMode};
+
+use crate::Device;
+
+/// UBX-UPD-SOS class and id
+const UPD_SOS: (u8, u8) = (0x09, 0x14);
+
+/// UPD-SOS `cmd` values
+const CMD_CREATE: u8 = 0;
+const CMD_CLEAR: u8 = 1;
+const CMD_BACKUP_ACK: u8 = 2;
+const CMD_RESTORED: u8 = 3;
+
+/// Payload offset of the `response` of the backup acknowledgment and restore status
+const RESPONSE_OFFSET: usize = 4;
+
+/// Time given to the receiver to write the backup to flash
+const BACKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Report of a failed exchange, firmware without save-on-shutdown support
+/// rejecting or ignoring the message
+fn failure(e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::Unsupported => {
+            "UBX-UPD-SOS not supported by the receiver firmware (UBX-ACK-NAK)".to_string()
+        },
+        std::io::ErrorKind::TimedOut => {
+            "no UBX-UPD-SOS response: not supported by the receiver firmware, or busy".to_string()
+        },
+        _ => format!("UBX-UPD-SOS exchange failed: {}", e),
+    }
+}
+
+/// Outcome of the backup restore at the last startup
+pub fn status(device: &mut Device) -> Result<String, String> {
+    let (class, id) = UPD_SOS;
+    // the poll is empty, the response the restore status
+    device
+        .write_poll(&crate::frame::frame(class, id, &[]))
+        .map_err(failure)?;
+    let payload = device
+        .response(class, id, &[CMD_RESTORED], device.ack_timeout())
+        .map_err(failure)?;
+    let status = match payload.get(RESPONSE_OFFSET) {
+        Some(0) => "unknown",
+        Some(1) => "backup found, restoring it failed",
+        Some(2) => "backup found and restored: hot start",
+        Some(3) => "no backup found, not restored",
+        Some(_) => "unexpected response",
+        None => return Err(format!("UBX-UPD-SOS of {} bytes", payload.len())),
+    };
+    Ok(format!("last startup: {}", status))
+}
+
+/// Stops GNSS, then backs the navigation database up in flash. GNSS stays
+/// stopped: the receiver is ready to be powered down
+pub fn backup(device: &mut Device) -> Result<String, String> {
+    // the backup is only consistent once GNSS is stopped (no acknowledgment)
+    device
+        .write_all(
+            &CfgRstBuilder {
+                nav_bbr_mask: NavBbrPredefinedMask::HOT_START.into(),
+                reset_mode: ResetMode::ControlledGpsStop,
+                reserved1: 0,
+            }
+            .into_packet_bytes(),
+        )
+        .map_err(failure)?;
+    let (class, id) = UPD_SOS;
+    device
+        .write_all(&crate::frame::frame(class, id, &[CMD_CREATE, 0, 0, 0]))
+        .map_err(failure)?;
+    let payload = device
+        .response(class, id, &[CMD_BACKUP_ACK], BACKUP_TIMEOUT)
+        .map_err(failure)?;
+    match payload.get(RESPONSE_OFFSET) {
+        Some(1) => Ok(
+            "backup created, GNSS stopped: power the receiver down, it hot starts next time"
+                .to_string(),
+        ),
+        Some(0) => Err("backup not created, the receiver did not acknowledge it".to_string()),
+        _ => Err(format!(
+            "unexpected UBX-UPD-SOS backup response of {} bytes",
+            payload.len()
+        )),
+    }
+}
+
+/// Invalidates the backup in flash
+pub fn clear(device: &mut Device) -> Result<String, String> {
+    let (class, id) = UPD_SOS;
+    device
+        .write_all(&crate::frame::frame(class, id, &[CMD_CLEAR, 0, 0, 0]))
+        .map_err(failure)?;
+    match device.wait_for_ack_of(class, id, device.ack_timeout()) {
+        Ok(true) => Ok("backup cleared".to_string()),
+        Ok(false) => Err(failure(std::io::ErrorKind::Unsupported.into())),
+        Err(e) => Err(failure(e)),
+    }
+}
@@ -0,0 +1,324 @@
+//! Where the receiver is read from: a local serial port, a TCP socket like the ones of
+//! ser2net or serial-to-WiFi bridges (`--port tcp://host:port`), or a UDP socket receiving
+//! the stream broadcast as datagrams (`--port udp://address:port`).
+//!
+//! They are read and written the same way, timeouts meaning "no data", so the
+//! configuration, the recording and the reconnection work alike. The serial settings,
+//! like the baud rate, belong to the bridge over TCP. UDP is one-way: writes fail.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::Duration;
+
+use crate::protocol::ProtocolScanner;
+use crate::Device;
+
+const TCP_SCHEME: &str = "tcp://";
+const UDP_SCHEME: &str = "udp://";
+
+/// Largest UDP payload
+const MAX_DATAGRAM_LEN: usize = 65507;
+
+/// Longest wait for the TCP connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opened link to the receiver
+pub enum Port {
+    Serial(Box<dyn serialport::SerialPort>),
+    Tcp(TcpStream),
+    Udp(Box<Datagrams>),
+    /// In-memory receiver of the tests
+    #[cfg(test)]
+    Memory(Box<Memory>),
+}
+
+/// Receiver of the tests: reads return the input, then nothing like a timeout or the
+/// error given, and the bytes written are kept
+#[cfg(test)]
+#[derive(Default)]
+pub struct Memory {
+    pub input: std::io::Cursor<Vec<u8>>,
+    pub failure: Option<std::io::ErrorKind>,
+    pub output: Vec<u8>,
+}
+
+impl Port {
+    /// Serial port, for its settings. An error over the network
+    pub fn serial(&mut self) -> std::io::Result<&mut dyn serialport::SerialPort> {
+        match self {
+            Self::Serial(port) => Ok(port.as_mut()),
+            Self::Tcp(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "not a serial port, the serial settings belong to the TCP bridge",
+            )),
+            Self::Udp(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "not a serial port, the stream is received over UDP",
+            )),
+            #[cfg(test)]
+            Self::Memory(_) => Err(std::io::ErrorKind::Unsupported.into()),
+        }
+    }
+
+    /// True when data was received but not read yet, the descriptor not telling
+    pub fn pending(&self) -> bool {
+        match self {
+            Self::Udp(datagrams) => datagrams.read < datagrams.datagram.len(),
+            _ => false,
+        }
+    }
+
+    /// How long reads wait for data before timing out, none meaning forever
+    pub fn read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        match self {
+            Self::Serial(port) => Ok(Some(port.timeout())),
+            Self::Tcp(stream) => stream.read_timeout(),
+            Self::Udp(datagrams) => datagrams.socket.read_timeout(),
+            #[cfg(test)]
+            Self::Memory(_) => Ok(None),
+        }
+    }
+
+    /// Has the reads wait up to `timeout` for data: in the driver for a serial port, with
+    /// the COMMTIMEOUTS of Windows returning as soon as a byte is received
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            // serialport has no infinite timeout
+            Self::Serial(port) => Ok(port.set_timeout(timeout.unwrap_or(Duration::MAX))?),
+            Self::Tcp(stream) => stream.set_read_timeout(timeout),
+            Self::Udp(datagrams) => datagrams.socket.set_read_timeout(timeout),
+            #[cfg(test)]
+            Self::Memory(_) => Ok(()),
+        }
+    }
+
+    /// Has the reads and writes return at once when the port is not ready, failing with
+    /// WouldBlock, or TimedOut for a serial port, its descriptor being polled by the runtime
+    /// (`tokio` feature). The read timeout is to be set again when blocking again
+    #[cfg(all(feature = "tokio", unix))]
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            Self::Serial(port) if nonblocking => Ok(port.set_timeout(Duration::ZERO)?),
+            Self::Serial(_) => Ok(()),
+            Self::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            Self::Udp(datagrams) => datagrams.socket.set_nonblocking(nonblocking),
+            #[cfg(test)]
+            Self::Memory(_) => Ok(()),
+        }
+    }
+
+    /// Statistics of the link, for the summary
+    pub fn report(&self) -> Option<String> {
+        match self {
+            Self::Udp(datagrams) => Some(format!(
+                "UDP: {} datagrams received, {} resync events (datagrams lost, cut or reordered)",
+                datagrams.count, datagrams.resyncs
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// UDP socket receiving the stream, read a datagram at a time
+pub struct Datagrams {
+    socket: UdpSocket,
+    /// Datagram being read, and how much of it was
+    datagram: Vec<u8>,
+    read: usize,
+    count: u64,
+    /// Follows the frames across datagrams, to tell the ones not continuing the previous one
+    scanner: ProtocolScanner,
+    resyncs: u64,
+}
+
+impl Datagrams {
+    fn receive(&mut self) -> std::io::Result<()> {
+        self.datagram.resize(MAX_DATAGRAM_LEN, 0);
+        let size = match self.socket.recv(&mut self.datagram) {
+            Ok(size) => size,
+            Err(e) => {
+                self.datagram.clear();
+                self.read = 0;
+                return Err(e);
+            },
+        };
+        self.datagram.truncate(size);
+        self.read = 0;
+        self.count += 1;
+        let resyncs = &mut self.resyncs;
+        // bytes belonging to no frame, or frames cut
+        self.scanner.scan(&self.datagram, |protocol, _| {
+            if protocol.is_none() {
+                *resyncs += 1;
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read == self.datagram.len() {
+            self.receive()?;
+        }
+        let size = buf.len().min(self.datagram.len() - self.read);
+        buf[..size].copy_from_slice(&self.datagram[self.read..self.read + size]);
+        self.read += size;
+        Ok(size)
+    }
+}
+
+impl Read for Port {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Serial(port) => port.read(buf),
+            Self::Tcp(stream) => match stream.read(buf)? {
+                // no data is a timeout, the end of the stream a disconnection
+                0 if !buf.is_empty() => Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    "connection closed by the peer",
+                )),
+                size => Ok(size),
+            },
+            Self::Udp(datagrams) => datagrams.read(buf),
+            #[cfg(test)]
+            Self::Memory(memory) => match (memory.input.read(buf)?, memory.failure) {
+                (0, Some(kind)) => Err(kind.into()),
+                (size, _) => Ok(size),
+            },
+        }
+    }
+}
+
+impl Write for Port {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Serial(port) => port.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Udp(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the UDP input is receive only, nothing can be sent to the receiver",
+            )),
+            #[cfg(test)]
+            Self::Memory(memory) => memory.output.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Serial(port) => port.flush(),
+            Self::Tcp(stream) => stream.flush(),
+            Self::Udp(_) => Ok(()),
+            #[cfg(test)]
+            Self::Memory(_) => Ok(()),
+        }
+    }
+}
+
+/// Link to open, and to reopen as it was opened at startup after a failure
+#[derive(Clone)]
+pub enum Endpoint {
+    Serial {
+        builder: serialport::SerialPortBuilder,
+        low_latency: bool,
+    },
+    /// Address of the TCP server, like "192.168.1.20:2000"
+    Tcp(String),
+    /// Address the UDP socket is bound to, like "0.0.0.0:2101"
+    Udp(String),
+}
+
+impl Endpoint {
+    /// Endpoint of the port name: serial, `tcp://host:port` or `udp://address:port`
+    pub fn parse(port: &str, builder: serialport::SerialPortBuilder, low_latency: bool) -> Self {
+        if let Some(address) = port.strip_prefix(TCP_SCHEME) {
+            Self::Tcp(address.to_string())
+        } else if let Some(address) = port.strip_prefix(UDP_SCHEME) {
+            Self::Udp(address.to_string())
+        } else {
+            Self::Serial {
+                builder,
+                low_latency,
+            }
+        }
+    }
+
+    pub fn is_serial(&self) -> bool {
+        matches!(self, Self::Serial { .. })
+    }
+
+    /// True when nothing can be sent to the receiver
+    pub fn is_receive_only(&self) -> bool {
+        matches!(self, Self::Udp(_))
+    }
+
+    /// Name of the link in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Serial { .. } => "serial port",
+            Self::Tcp(_) => "TCP connection",
+            Self::Udp(_) => "UDP socket",
+        }
+    }
+
+    pub fn open(&self) -> std::io::Result<Device> {
+        match self {
+            Self::Serial {
+                builder,
+                low_latency,
+            } => Ok(crate::open_port(builder.clone(), *low_latency)?),
+            Self::Tcp(address) => connect(address),
+            Self::Udp(address) => bind(address),
+        }
+    }
+}
+
+/// Connects to the TCP server, its first address answering
+fn connect(address: &str) -> std::io::Result<Device> {
+    use std::net::ToSocketAddrs;
+    let mut error = None;
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(Duration::from_millis(10)))?;
+                stream.set_nodelay(true)?;
+                #[cfg(unix)]
+                let fd = std::os::unix::io::AsRawFd::as_raw_fd(&stream);
+                let mut device = Device::new(Port::Tcp(stream));
+                #[cfg(unix)]
+                {
+                    device.fd = Some(fd);
+                }
+                return Ok(device);
+            },
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no address found for \"{}\"", address),
+        )
+    }))
+}
+
+/// Binds the UDP socket receiving the datagrams
+fn bind(address: &str) -> std::io::Result<Device> {
+    let socket = UdpSocket::bind(address)?;
+    socket.set_read_timeout(Some(Duration::from_millis(10)))?;
+    #[cfg(unix)]
+    let fd = std::os::unix::io::AsRawFd::as_raw_fd(&socket);
+    let mut device = Device::new(Port::Udp(Box::new(Datagrams {
+        socket,
+        datagram: Vec::new(),
+        read: 0,
+        count: 0,
+        scanner: ProtocolScanner::default(),
+        resyncs: 0,
+    })));
+    #[cfg(unix)]
+    {
+        device.fd = Some(fd);
+    }
+    Ok(device)
+}
@@ -0,0 +1,102 @@
+//! Recording split into one output per UBX message class, "capture.ubx.gz" becoming
+//! "capture-NAV.ubx.gz", "capture-RXM.ubx.gz"...
+//!
+//! The received bytes are framed and validated first, then every frame is written into the
+//! output of its class, opened with the first frame of the class. Frames of unknown classes,
+//! NMEA and RTCM3 frames and bytes belonging to no frame go into the "-OTHER" output.
+
+use std::collections::BTreeMap;
+
+use crate::frame::{self, CLASSES};
+use crate::protocol::{Protocol, ProtocolScanner};
+use crate::ring::with_suffix;
+use crate::sink::Sink;
+
+/// Name of the catch-all output
+const OTHER: &str = "OTHER";
+
+/// Opens the output of given path
+pub type Opener = Box<dyn FnMut(&str) -> Box<dyn Sink>>;
+
+pub struct ClassSplit {
+    template: String,
+    open: Opener,
+    scanner: ProtocolScanner,
+    /// Outputs opened so far, by class name
+    outputs: BTreeMap<&'static str, Box<dyn Sink>>,
+}
+
+/// Paths of every output the recording may open
+pub fn paths(template: &str) -> Vec<String> {
+    CLASSES
+        .iter()
+        .map(|(_, name)| *name)
+        .chain([OTHER])
+        .map(|name| with_suffix(template, name))
+        .collect()
+}
+
+impl ClassSplit {
+    /// Splits the recording into outputs named after `template`, opened by `open`
+    pub fn new(template: &str, open: Opener) -> Self {
+        Self {
+            template: template.to_string(),
+            open,
+            scanner: ProtocolScanner::default(),
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    fn write(&mut self, class: &'static str, data: &[u8]) -> std::io::Result<()> {
+        if !self.outputs.contains_key(class) {
+            let path = with_suffix(&self.template, class);
+            info!("split: recording {} into \"{}\"", class, path);
+            let output = (self.open)(&path);
+            self.outputs.insert(class, output);
+        }
+        self.outputs.get_mut(class).unwrap().raw(data)
+    }
+}
+
+impl Sink for ClassSplit {
+    fn name(&self) -> String {
+        "output files".to_string()
+    }
+
+    fn raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut pieces = Vec::new();
+        self.scanner.scan(data, |protocol, piece| {
+            let class = match protocol {
+                Some(Protocol::Ubx) => frame::class_name(piece[2]).unwrap_or(OTHER),
+                _ => OTHER,
+            };
+            pieces.push((class, piece.to_vec()));
+        });
+        // every output is written, even when one of them fails
+        let mut result = Ok(());
+        for (class, piece) in pieces {
+            result = result.and(self.write(class, &piece));
+        }
+        result
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        let pending = self.scanner.take();
+        if !pending.is_empty() {
+            self.write(OTHER, &pending)?;
+        }
+        let mut result = Ok(());
+        for (class, output) in &mut self.outputs {
+            if let Err(e) = output.finish() {
+                warn!("split: failed to complete the {} output: {}", class, e);
+                result = Err(e);
+            }
+        }
+        info!(
+            "split: {} output(s), {}",
+            self.outputs.len(),
+            self.outputs.keys().copied().collect::<Vec<_>>().join(" ")
+        );
+        result
+    }
+}
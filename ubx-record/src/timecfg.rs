@@ -0,0 +1,147 @@
+//! Time system of the configure time subcommand: the time reference the measurements are
+//! aligned on and the UTC standard of the solutions. They are set with UBX-CFG-RATE and
+//! UBX-CFG-NAV5 up to protocol 23, and with the CFG-RATE-TIMEREF and CFG-NAVSPG-UTCSTANDARD
+//! keys of the generation 9 receivers, the protocol being read from UBX-MON-VER
+
+use ublox::{CfgNav5Builder, CfgNav5Params, CfgNav5UtcStandard};
+
+use crate::metadata::Receiver;
+use crate::navcfg::{self, TIME_REFS};
+use crate::valcfg;
+use crate::Device;
+
+/// First protocol of the generation 9 receivers, configured with keys
+const GENERATION_9_PROTOCOL: u32 = 27;
+
+/// Offset of utcStandard in a CFG-NAV5 payload
+const UTC_STANDARD_OFFSET: usize = 30;
+
+/// Configuration keys of the time reference and the UTC standard
+const TIME_REF_KEY: &str = "CFG-RATE-TIMEREF";
+const UTC_STANDARD_KEY: &str = "CFG-NAVSPG-UTCSTANDARD";
+
+/// RAM layer of CFG-VALGET and CFG-VALSET
+const LAYER_RAM: (u8, u8) = (0, 0x01);
+
+/// UTC standards selectable in CFG-NAV5 and CFG-NAVSPG-UTCSTANDARD, by name: automatic from
+/// the constellations, USNO (GPS), former Soviet Union (GLONASS) and NTSC China (BeiDou)
+pub const UTC_STANDARDS: [(&str, CfgNav5UtcStandard); 4] = [
+    ("auto", CfgNav5UtcStandard::Automatic),
+    ("usno", CfgNav5UtcStandard::Usno),
+    ("su", CfgNav5UtcStandard::UtcSu),
+    ("ntsc", CfgNav5UtcStandard::UtcChina),
+];
+
+/// Time reference and UTC standard, as CFG-RATE timeRef and CFG-NAV5 utcStandard values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSystem {
+    pub time_ref: u8,
+    pub utc_standard: u8,
+}
+
+impl TimeSystem {
+    /// Like "aligned on GLONASS time, su UTC standard"
+    pub fn describe(&self) -> String {
+        let utc_standard = UTC_STANDARDS
+            .iter()
+            .find(|(_, standard)| *standard as u8 == self.utc_standard)
+            .map_or(format!("standard {}", self.utc_standard), |(name, _)| name.to_string());
+        format!(
+            "aligned on {} time, {} UTC standard",
+            TIME_REFS
+                .get(usize::from(self.time_ref))
+                .map_or("unknown".to_string(), |name| name.to_uppercase()),
+            utc_standard
+        )
+    }
+}
+
+/// Configured with keys, from the protocol of the receiver, the CFG messages being used when
+/// it is unknown
+fn generation_9(receiver: &Receiver) -> bool {
+    match receiver.protocol_major() {
+        Some(major) => major >= GENERATION_9_PROTOCOL,
+        None => {
+            warn!("Receiver protocol unknown, setting the time system with the CFG messages");
+            false
+        },
+    }
+}
+
+fn key(name: &str) -> valcfg::Key {
+    valcfg::parse_key(name).expect("key of the table")
+}
+
+/// Sets the time reference and UTC standard given, keeping the other, returning the time
+/// system polled back, only polled without either
+pub fn set(
+    device: &mut Device,
+    receiver: &Receiver,
+    time_ref: Option<&str>,
+    utc_standard: Option<&str>,
+) -> Result<Option<TimeSystem>, String> {
+    let time_ref = time_ref.and_then(|name| TIME_REFS.iter().position(|known| *known == name));
+    let utc_standard = utc_standard
+        .and_then(|name| UTC_STANDARDS.iter().find(|(known, _)| *known == name));
+    if generation_9(receiver) {
+        let values = [
+            time_ref.map(|index| (key(TIME_REF_KEY), vec![index as u8])),
+            utc_standard.map(|(_, standard)| (key(UTC_STANDARD_KEY), vec![*standard as u8])),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if !values.is_empty() {
+            valcfg::valset(device, &values, LAYER_RAM.1)?;
+        }
+        let keys = [key(TIME_REF_KEY), key(UTC_STANDARD_KEY)];
+        let applied = valcfg::valget(device, &keys, LAYER_RAM.0).ok().and_then(|values| {
+            let value = |name: &str| {
+                values
+                    .iter()
+                    .find(|(key, _)| key.name() == Some(name))
+                    .and_then(|(_, value)| value.first().copied())
+            };
+            Some(TimeSystem {
+                time_ref: value(TIME_REF_KEY)?,
+                utc_standard: value(UTC_STANDARD_KEY)?,
+            })
+        });
+        return Ok(applied);
+    }
+    if let Some(index) = time_ref {
+        navcfg::set_rate(device, None, None, Some(TIME_REFS[index]))?;
+    }
+    if let Some((name, standard)) = utc_standard {
+        let builder = CfgNav5Builder {
+            mask: CfgNav5Params::UTC,
+            utc_standard: *standard,
+            ..CfgNav5Builder::default()
+        };
+        info!("Setting the UTC standard: {} ...", name);
+        device
+            .write_all(&builder.into_packet_bytes())
+            .map_err(|e| format!("Failed to write UBX-CFG-NAV5: {}", e))?;
+        match device.wait_for_ack_of(0x06, 0x24, device.ack_timeout()) {
+            Ok(true) => {},
+            Ok(false) => {
+                return Err(format!(
+                    "The receiver rejected the UTC standard (UBX-ACK-NAK of UBX-CFG-NAV5): {}",
+                    name
+                ))
+            },
+            Err(e) => return Err(format!("UBX-CFG-NAV5 not acknowledged: {}", e)),
+        }
+    }
+    let rate = device
+        .poll_payload(0x06, 0x08, &[], device.ack_timeout())
+        .ok()
+        .and_then(|payload| navcfg::Rate::parse(&payload));
+    let nav5 = device.poll_payload(0x06, 0x24, &[], device.ack_timeout()).ok();
+    Ok(rate.zip(nav5).and_then(|(rate, nav5)| {
+        Some(TimeSystem {
+            time_ref: rate.time_ref as u8,
+            utc_standard: *nav5.get(UTC_STANDARD_OFFSET)?,
+        })
+    }))
+}
@@ -0,0 +1,288 @@
+//! Time pulse settings of the configure subcommand (UBX-CFG-TP5), like for disciplining a
+//! counter on the TIMEPULSE pin: frequency or period, pulse length or duty cycle, polarity,
+//! alignment, and the pulse before the receiver is locked. The settings not given are kept,
+//! impossible combinations are refused before anything is sent
+
+use crate::frame;
+use crate::navcfg::TIME_REFS;
+use crate::Device;
+
+/// Length of a CFG-TP5 payload
+const TP5_LEN: usize = 32;
+
+const ACTIVE: u32 = 0x01;
+const LOCK_GNSS_FREQ: u32 = 0x02;
+const LOCKED_OTHER_SET: u32 = 0x04;
+const IS_FREQ: u32 = 0x08;
+const IS_LENGTH: u32 = 0x10;
+const ALIGN_TO_TOW: u32 = 0x20;
+const POLARITY: u32 = 0x40;
+const GRID_SHIFT: u32 = 7;
+const GRID_MASK: u32 = 0x0f << GRID_SHIFT;
+
+pub fn parse_duty(s: &str) -> Result<f64, String> {
+    let duty = s.parse::<f64>().map_err(|e| e.to_string())?;
+    if !(0.0..=100.0).contains(&duty) {
+        return Err(format!("a duty cycle of {}% is impossible, from 0 to 100%", duty));
+    }
+    Ok(duty)
+}
+
+/// Pulse settings given on the command line, the others being kept
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Request {
+    pub freq_hz: Option<u32>,
+    pub period_us: Option<u32>,
+    pub length_us: Option<u32>,
+    pub duty_percent: Option<f64>,
+    pub rising: Option<bool>,
+    pub time_ref: Option<u32>,
+    /// Pulses while unlocked, or none until locked
+    pub unlocked_pulse: Option<bool>,
+}
+
+/// Settings of a time pulse, from a UBX-CFG-TP5 payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimePulse {
+    payload: [u8; TP5_LEN],
+}
+
+impl TimePulse {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        Some(Self {
+            payload: payload.get(..TP5_LEN)?.try_into().ok()?,
+        })
+    }
+
+    fn field(&self, offset: usize) -> u32 {
+        let bytes = &self.payload[offset..offset + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn set_field(&mut self, offset: usize, value: u32) {
+        self.payload[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn flags(&self) -> u32 {
+        self.field(28)
+    }
+
+    fn set_flag(&mut self, flag: u32, set: bool) {
+        let flags = if set {
+            self.flags() | flag
+        } else {
+            self.flags() & !flag
+        };
+        self.set_field(28, flags);
+    }
+
+    /// TP1 or TP2
+    pub fn name(&self) -> String {
+        format!("TP{}", self.payload[0] + 1)
+    }
+
+    /// Period of the frequency or period field, in microseconds
+    fn period_us(&self, value: u32) -> Option<f64> {
+        match (self.flags() & IS_FREQ != 0, value) {
+            (_, 0) => None,
+            (true, freq) => Some(1e6 / f64::from(freq)),
+            (false, period) => Some(f64::from(period)),
+        }
+    }
+
+    /// Pulse length of the length or ratio field, in microseconds
+    fn length_us(&self, value: u32, period_us: f64) -> f64 {
+        if self.flags() & IS_LENGTH != 0 {
+            f64::from(value)
+        } else {
+            f64::from(value) / 2f64.powi(32) * period_us
+        }
+    }
+
+    fn describe_pulse(&self, freq_period: u32, pulse_len_ratio: u32) -> String {
+        if pulse_len_ratio == 0 {
+            return "no pulse".to_string();
+        }
+        let rate = if self.flags() & IS_FREQ != 0 {
+            format!("{} Hz", freq_period)
+        } else {
+            format!("every {} us", freq_period)
+        };
+        let length = if self.flags() & IS_LENGTH != 0 {
+            format!("{} us long", pulse_len_ratio)
+        } else {
+            format!(
+                "{:.1}% duty",
+                f64::from(pulse_len_ratio) / 2f64.powi(32) * 100.0
+            )
+        };
+        format!("{}, {}", rate, length)
+    }
+
+    /// Like "TP1: 1 Hz, 100000 us long once locked, no pulse until locked, rising edge, aligned on
+    /// GPS time"
+    pub fn describe(&self) -> String {
+        let flags = self.flags();
+        if flags & ACTIVE == 0 {
+            return format!("{}: disabled", self.name());
+        }
+        let mut parts = Vec::new();
+        if flags & LOCKED_OTHER_SET != 0 {
+            parts.push(format!(
+                "{} once locked",
+                self.describe_pulse(self.field(12), self.field(20))
+            ));
+            parts.push(format!(
+                "{} until locked",
+                self.describe_pulse(self.field(8), self.field(16))
+            ));
+        } else {
+            parts.push(self.describe_pulse(self.field(8), self.field(16)));
+        }
+        parts.push(if flags & POLARITY != 0 {
+            "rising edge".to_string()
+        } else {
+            "falling edge".to_string()
+        });
+        if flags & ALIGN_TO_TOW != 0 {
+            let time_ref = ((flags & GRID_MASK) >> GRID_SHIFT) as usize;
+            parts.push(format!(
+                "aligned on {} time",
+                TIME_REFS
+                    .get(time_ref)
+                    .map_or("unknown".to_string(), |name| name.to_uppercase())
+            ));
+        }
+        format!("{}: {}", self.name(), parts.join(", "))
+    }
+
+    /// Raw fields of the payload, printed before it is sent
+    pub fn fields(&self) -> String {
+        format!(
+            "tpIdx {}, freqPeriod {}, freqPeriodLock {}, pulseLenRatio {}, pulseLenRatioLock {}, \
+flags 0x{:08x}",
+            self.payload[0],
+            self.field(8),
+            self.field(12),
+            self.field(16),
+            self.field(20),
+            self.flags()
+        )
+    }
+
+    /// Applies the settings given, keeping the others
+    pub fn apply(&mut self, request: &Request) {
+        let unlocked_pulse = request.unlocked_pulse.unwrap_or(
+            self.flags() & LOCKED_OTHER_SET == 0 || self.field(16) != 0,
+        );
+        self.set_flag(ACTIVE, true);
+        self.set_flag(LOCK_GNSS_FREQ, true);
+        if let Some(freq) = request.freq_hz {
+            self.set_flag(IS_FREQ, true);
+            self.set_field(8, freq);
+            self.set_field(12, freq);
+        }
+        if let Some(period) = request.period_us {
+            self.set_flag(IS_FREQ, false);
+            self.set_field(8, period);
+            self.set_field(12, period);
+        }
+        if let Some(length) = request.length_us {
+            self.set_flag(IS_LENGTH, true);
+            self.set_field(16, length);
+            self.set_field(20, length);
+        }
+        if let Some(duty) = request.duty_percent {
+            self.set_flag(IS_LENGTH, false);
+            let ratio = (duty / 100.0 * 2f64.powi(32)).min(f64::from(u32::MAX)) as u32;
+            self.set_field(16, ratio);
+            self.set_field(20, ratio);
+        }
+        if let Some(rising) = request.rising {
+            self.set_flag(POLARITY, rising);
+        }
+        if let Some(time_ref) = request.time_ref {
+            self.set_flag(ALIGN_TO_TOW, true);
+            self.set_field(28, self.flags() & !GRID_MASK | time_ref << GRID_SHIFT);
+        }
+        if !unlocked_pulse {
+            // the pulse once locked, and none before
+            self.set_flag(LOCKED_OTHER_SET, true);
+            self.set_field(8, self.field(12));
+            self.set_field(16, 0);
+        } else if request.unlocked_pulse.is_some() {
+            // the same pulse locked or not
+            self.set_flag(LOCKED_OTHER_SET, false);
+            self.set_field(8, self.field(12));
+            self.set_field(16, self.field(20));
+        }
+    }
+
+    /// Refuses the impossible pulses: no frequency, or pulses longer than the period
+    pub fn validate(&self) -> Result<(), String> {
+        let pulses = [("", 8, 16), (" once locked", 12, 20)];
+        for (label, freq_period, pulse_len_ratio) in pulses {
+            let period_us = self.period_us(self.field(freq_period)).ok_or_else(|| {
+                format!(
+                    "{}: a zero frequency or period{} is impossible, set --tp-freq or \
+--tp-period-us",
+                    self.name(),
+                    label
+                )
+            })?;
+            let length_us = self.length_us(self.field(pulse_len_ratio), period_us);
+            if length_us > period_us {
+                return Err(format!(
+                    "{}: pulses of {} us{} do not fit in a period of {} us",
+                    self.name(),
+                    length_us,
+                    label,
+                    period_us
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets the time pulse given, TP1 at index 0, keeping the settings not given, returning
+/// the settings polled back
+pub fn set_time_pulse(
+    device: &mut Device,
+    index: u8,
+    request: &Request,
+) -> Result<Option<TimePulse>, String> {
+    let name = format!("TP{}", index + 1);
+    let payload = device
+        .poll_payload(0x06, 0x31, &[index], device.ack_timeout())
+        .map_err(|e| {
+            format!(
+                "Failed to read the {} settings (UBX-CFG-TP5), the receiver may not have it: {}",
+                name, e
+            )
+        })?;
+    let mut time_pulse = TimePulse::parse(&payload)
+        .ok_or_else(|| format!("Unexpected UBX-CFG-TP5 of {} bytes", payload.len()))?;
+    time_pulse.apply(request);
+    time_pulse.validate()?;
+    info!("Setting the time pulse: {} ...", time_pulse.describe());
+    info!("UBX-CFG-TP5 fields: {}", time_pulse.fields());
+    device
+        .write_all(&frame::frame(0x06, 0x31, &time_pulse.payload))
+        .map_err(|e| format!("Failed to write UBX-CFG-TP5: {}", e))?;
+    match device.wait_for_ack_of(0x06, 0x31, device.ack_timeout()) {
+        Ok(true) => {},
+        Ok(false) => {
+            return Err(format!(
+                "The receiver rejected the time pulse (UBX-ACK-NAK of UBX-CFG-TP5): {}",
+                time_pulse.describe()
+            ))
+        },
+        Err(e) => return Err(format!("UBX-CFG-TP5 not acknowledged: {}", e)),
+    }
+    let applied = device
+        .poll_payload(0x06, 0x31, &[index], device.ack_timeout())
+        .ok()
+        .and_then(|payload| TimePulse::parse(&payload));
+    Ok(applied)
+}
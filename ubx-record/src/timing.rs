@@ -0,0 +1,93 @@
+//! Live display of the time marks (`--print-timing`): each new TIM-TM2 edge, in time of
+//! week and UTC, with the quantization error of the last TIM-TP time pulse
+
+use ubx_common::time::{format, WeekTime};
+use ubx_common::timemark::{self, FLAGS_OFFSET, NEW_FALLING_EDGE, NEW_RISING_EDGE, TIME_VALID};
+use ublox::{PacketRef, TimTm2Ref};
+
+use crate::sink::Sink;
+
+/// Prints one line per new edge of the TIM-TM2 messages
+#[derive(Default)]
+pub struct TimingPrinter {
+    /// Quantization error of the last TIM-TP (ps), none when not valid
+    q_err: Option<i32>,
+    /// Last rising edge count, per channel
+    counts: [Option<u16>; 2],
+}
+
+impl TimingPrinter {
+    /// Like "time mark: ch 0 #12 rising, week 2310 tow 345600.123456789 s (gnss), UTC
+    /// 2024-04-10T23:59:42.123456789Z, acc 20 ns, qErr -1234 ps"
+    fn edge(&self, tm: &TimTm2Ref, edge: &str, time: &WeekTime, base: &str) -> String {
+        let mut line = format!(
+            "time mark: ch {} #{} {}, week {} tow {} s ({}), UTC {}, acc {} ns",
+            tm.ch(),
+            tm.count(),
+            edge,
+            time.week,
+            time.tow(),
+            base,
+            format(time.utc()),
+            tm.acc_est()
+        );
+        if let Some(q_err) = self.q_err {
+            line.push_str(&format!(", qErr {} ps", q_err));
+        }
+        line
+    }
+}
+
+impl Sink for TimingPrinter {
+    fn name(&self) -> String {
+        "timing printer".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        true
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        let tm = match packet {
+            PacketRef::TimTp(tp) => {
+                self.q_err = tp.flags().q_err_valid().then(|| tp.q_err());
+                return Ok(());
+            },
+            PacketRef::TimTm2(tm) => tm,
+            _ => return Ok(()),
+        };
+        let flags = tm.as_bytes()[FLAGS_OFFSET];
+        let (base, scale) = timemark::time_base(flags);
+        if flags & TIME_VALID == 0 {
+            warn!("time mark: ch {} #{}, time not valid", tm.ch(), tm.count());
+            return Ok(());
+        }
+        if flags & NEW_RISING_EDGE != 0 {
+            // the counter is incremented on rising edges only
+            if let Some(last) = self.counts.get_mut(usize::from(tm.ch())) {
+                let missed = last.map_or(0, |last| tm.count().wrapping_sub(last).wrapping_sub(1));
+                if missed > 0 {
+                    warn!("time mark: ch {}, {} events missed", tm.ch(), missed);
+                }
+                *last = Some(tm.count());
+            }
+            let rising = WeekTime {
+                scale,
+                week: tm.wn_r(),
+                tow_ms: tm.tow_ms_r(),
+                sub_ms_ns: tm.tow_sub_ms_r(),
+            };
+            info!("{}", self.edge(tm, "rising", &rising, base));
+        }
+        if flags & NEW_FALLING_EDGE != 0 {
+            let falling = WeekTime {
+                scale,
+                week: tm.wn_f(),
+                tow_ms: tm.tow_ms_f(),
+                sub_ms_ns: tm.tow_sub_ms_f(),
+            };
+            info!("{}", self.edge(tm, "falling", &falling, base));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Live time to first fix measurement, following a receiver reset
+
+use std::time::{Duration, Instant};
+
+use ublox::{GpsFix, NavPvtFlags, PacketRef};
+
+use crate::sink::Sink;
+
+/// Margin on the receiver uptime, recognizing the NAV-STATUS of the restarted receiver (ms)
+const UPTIME_MARGIN_MS: u128 = 1000;
+
+/// Measures the time to the first 3D fix, both on the wall clock (since the reset command)
+/// and on the receiver clock (uptime of the fix epoch, from NAV-STATUS)
+pub struct TtffMonitor {
+    reset_at: Instant,
+    /// The receiver has restarted, solutions preceding the reset being ignored
+    restarted: bool,
+    /// Wall clock TTFF and iTOW of the first 3D fix epoch
+    fix: Option<(Duration, u32)>,
+    /// Last NAV-STATUS iTOW and uptime (ms)
+    status: Option<(u32, u32)>,
+    /// Receiver clock TTFF (ms)
+    receiver_ttff: Option<u32>,
+}
+
+impl TtffMonitor {
+    /// Starts measuring, the reset command being sent at `reset_at`
+    pub fn new(reset_at: Instant) -> Self {
+        Self {
+            reset_at,
+            restarted: false,
+            fix: None,
+            status: None,
+            receiver_ttff: None,
+        }
+    }
+}
+
+impl Sink for TtffMonitor {
+    fn name(&self) -> String {
+        "TTFF monitor".to_string()
+    }
+
+    fn wants_packets(&self) -> bool {
+        self.receiver_ttff.is_none()
+    }
+
+    fn packet(&mut self, packet: &PacketRef) -> std::io::Result<()> {
+        match packet {
+            PacketRef::NavStatus(status) => {
+                // uptime shorter than the time since reset: the receiver restarted
+                if u128::from(status.uptime_ms())
+                    <= self.reset_at.elapsed().as_millis() + UPTIME_MARGIN_MS
+                {
+                    self.restarted = true;
+                }
+                self.status = Some((status.itow(), status.uptime_ms()));
+            },
+            PacketRef::NavPvt(pvt) => {
+                let fixed = pvt.flags().contains(NavPvtFlags::GPS_FIX_OK)
+                    && matches!(pvt.fix_type(), GpsFix::Fix3D | GpsFix::GPSPlusDeadReckoning);
+                if !fixed {
+                    self.restarted = true;
+                } else if self.restarted && self.fix.is_none() {
+                    let ttff = self.reset_at.elapsed();
+                    info!(
+                        "TTFF: 3D fix {:.1} s after the reset command (wall clock)",
+                        ttff.as_secs_f64()
+                    );
+                    self.fix = Some((ttff, pvt.itow()));
+                }
+            },
+            _ => {},
+        }
+        // NAV-STATUS of the fix epoch, sent before or after its NAV-PVT
+        if let (Some((_, fix_itow)), Some((itow, uptime_ms))) = (self.fix, self.status) {
+            if itow == fix_itow && self.receiver_ttff.is_none() {
+                info!(
+                    "TTFF: 3D fix {:.3} s after receiver restart (receiver time)",
+                    f64::from(uptime_ms) / 1000.0
+                );
+                self.receiver_ttff = Some(uptime_ms);
+            }
+        }
+        Ok(())
+    }
+}
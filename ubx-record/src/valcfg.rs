@@ -0,0 +1,125 @@
+//! Configuration keys of the generation 9 receivers and later, like the ZED-F9P, set with
+//! UBX-CFG-VALSET and read with UBX-CFG-VALGET (`configure valset`, `configure valget`).
+//!
+//! The key database and the messages are those of ubx-config, shared by ubx_common: keys
+//! are named like CFG-RATE-MEAS or given by their hex id like 0x30210001, typed like
+//! 0x40030009:I4.
+
+use ubx_common::valcfg::{self, Ack, Link, Response, Status};
+
+pub use ubx_common::keys::{decode, known_keys, parse_assignment, parse_key, Key};
+
+use crate::frame;
+use crate::Device;
+
+/// Layers of CFG-VALSET, by name, with their bit
+pub const SET_LAYERS: [(&str, u8); 3] = [("ram", 0x01), ("bbr", 0x02), ("flash", 0x04)];
+
+/// Layers of CFG-VALGET, by name, with their number
+pub const GET_LAYERS: [(&str, u8); 4] = [("ram", 0), ("bbr", 1), ("flash", 2), ("default", 7)];
+
+impl Link for Device {
+    fn command(
+        &mut self,
+        class: u8,
+        id: u8,
+        payload: &[u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<Ack> {
+        self.write_all(&frame::frame(class, id, payload))?;
+        match self.wait_for_ack_of(class, id, timeout) {
+            Ok(true) => Ok(Ack::Acked),
+            Ok(false) => Ok(Ack::Nak),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(Ack::Timeout),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Polls are sent by dry runs too
+    fn poll(
+        &mut self,
+        class: u8,
+        id: u8,
+        request: &[u8],
+        prefix: &[u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<Response> {
+        self.write_poll(&frame::frame(class, id, request))?;
+        match self.response(class, id, prefix, timeout) {
+            Ok(payload) => Ok(Response::Payload(payload)),
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(Response::Nak),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(Response::Timeout),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Sets the values in the `layers`, as a transaction when more than 64, aborted when a
+/// message of it is rejected
+pub fn valset(device: &mut Device, values: &[(Key, Vec<u8>)], layers: u8) -> Result<(), String> {
+    for (key, value) in values {
+        info!("Setting {} = {} ...", key.describe(), decode(key.ty, value));
+    }
+    let ids = values
+        .iter()
+        .map(|(key, value)| (key.id, value.clone()))
+        .collect::<Vec<_>>();
+    let timeout = device.ack_timeout();
+    let statuses = valcfg::valset(device, &ids, layers, timeout)
+        .map_err(|e| format!("Failed to write UBX-CFG-VALSET: {}", e))?;
+    let rejected = values
+        .iter()
+        .zip(&statuses)
+        .filter_map(|((key, _), status)| match status {
+            Status::Rejected(ack) => Some((key, *ack)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let aborted = statuses.iter().filter(|status| **status == Status::Aborted).count();
+    match rejected.first() {
+        None => Ok(()),
+        Some((_, Ack::Nak)) => Err(format!(
+            "The receiver rejected the values (UBX-ACK-NAK of UBX-CFG-VALSET): unknown key, \
+invalid value or layer in {}{}",
+            rejected
+                .iter()
+                .map(|(key, _)| key.describe())
+                .collect::<Vec<_>>()
+                .join(", "),
+            if aborted > 0 {
+                format!(", the transaction of the {} other values aborted", aborted)
+            } else {
+                String::new()
+            }
+        )),
+        Some((_, ack)) => Err(format!("UBX-CFG-VALSET not acknowledged: {}", ack)),
+    }
+}
+
+/// Polls the values of the keys in the `layer`, 64 keys at a time, the keys the receiver
+/// does not know being skipped. Fails when none is read.
+pub fn valget(device: &mut Device, keys: &[Key], layer: u8) -> Result<Vec<(Key, Vec<u8>)>, String> {
+    let ids = keys.iter().map(|key| key.id).collect::<Vec<_>>();
+    let timeout = device.ack_timeout();
+    let values = valcfg::valget(device, &ids, layer, timeout)
+        .map_err(|e| format!("Failed to read the values (UBX-CFG-VALGET): {}", e))?;
+    let mut read = Vec::with_capacity(keys.len());
+    let mut failure = None;
+    for (key, value) in keys.iter().zip(values) {
+        match value {
+            Ok(value) => read.push((*key, value)),
+            Err(response) => {
+                debug!("{} not read (UBX-CFG-VALGET): {}", key.describe(), response);
+                failure.get_or_insert(response);
+            },
+        }
+    }
+    match failure {
+        Some(response) if read.is_empty() => Err(format!(
+            "Failed to read the values (UBX-CFG-VALGET): {}, unknown key or unsupported by the \
+firmware",
+            response
+        )),
+        _ => Ok(read),
+    }
+}
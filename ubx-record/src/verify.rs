@@ -0,0 +1,277 @@
+//! Verification of the receiver configuration, polled back after being written.
+//!
+//! Receivers may acknowledge settings they do not fully apply, like clamped rates:
+//! the polled values are compared to the written ones.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Verified,
+    Mismatched,
+    /// The receiver did not answer the poll
+    Unsupported,
+    /// Read back only, nothing being requested
+    Reported,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Self::Verified => "verified",
+            Self::Mismatched => "MISMATCHED",
+            Self::Unsupported => "unsupported",
+            Self::Reported => "reported",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+struct Check {
+    item: String,
+    requested: String,
+    actual: String,
+    status: Status,
+}
+
+#[derive(Default)]
+pub struct Verification {
+    checks: Vec<Check>,
+}
+
+impl Verification {
+    fn push(&mut self, item: String, requested: String, actual: Option<String>, status: Status) {
+        self.checks.push(Check {
+            item,
+            requested,
+            actual: actual.unwrap_or_else(|| "-".to_string()),
+            status,
+        });
+    }
+
+    /// Output rates per port of a message, from its UBX-CFG-MSG poll response
+    pub fn message_rates(&mut self, name: &str, requested: [u8; 6], response: Option<&[u8]>) {
+        let format = |rates: &[u8]| {
+            rates
+                .iter()
+                .map(|rate| rate.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let rates = response.and_then(|payload| payload.get(2..8));
+        let status = match rates {
+            None => Status::Unsupported,
+            Some(rates) if rates == requested => Status::Verified,
+            Some(_) => Status::Mismatched,
+        };
+        self.push(
+            format!("CFG-MSG {} rates", name),
+            format(&requested),
+            rates.map(format),
+            status,
+        );
+    }
+
+    /// Output protocols of the port we are connected to, from its UBX-CFG-PRT poll response
+    pub fn out_protocols(&mut self, requested: Option<u16>, response: Option<&[u8]>) {
+        let port = response.and_then(|payload| payload.first());
+        let mask = response
+            .and_then(|payload| payload.get(14..16))
+            .map(|mask| u16::from_le_bytes([mask[0], mask[1]]));
+        let status = match (requested, mask) {
+            (_, None) => Status::Unsupported,
+            (None, Some(_)) => Status::Reported,
+            (Some(requested), Some(mask)) if requested == mask => Status::Verified,
+            (Some(_), Some(_)) => Status::Mismatched,
+        };
+        self.push(
+            match port {
+                Some(port) => format!("CFG-PRT port {} outProtoMask", port),
+                None => "CFG-PRT outProtoMask".to_string(),
+            },
+            requested
+                .map(|mask| format!("0x{:04x}", mask))
+                .unwrap_or_else(|| "-".to_string()),
+            mask.map(|mask| format!("0x{:04x}", mask)),
+            status,
+        );
+    }
+
+    /// UART or USB port settings, written as the UBX-CFG-PRT `written` payload
+    pub fn port_settings(&mut self, written: &[u8], response: Option<&[u8]>) {
+        let fields = [
+            ("mode", 4..8),
+            ("baudRate", 8..12),
+            ("inProtoMask", 12..14),
+            ("outProtoMask", 14..16),
+        ];
+        for (name, range) in fields {
+            let value = |payload: &[u8]| {
+                payload.get(range.clone()).map(|bytes| {
+                    bytes
+                        .iter()
+                        .rev()
+                        .fold(0_u32, |v, b| v << 8 | u32::from(*b))
+                })
+            };
+            let requested = value(written);
+            let actual = response.and_then(value);
+            let status = match actual {
+                None => Status::Unsupported,
+                Some(actual) if Some(actual) == requested => Status::Verified,
+                Some(_) => Status::Mismatched,
+            };
+            let format = |v: u32| {
+                if name == "baudRate" {
+                    v.to_string()
+                } else {
+                    format!("0x{:x}", v)
+                }
+            };
+            self.push(
+                format!("CFG-PRT port {} {}", written[0], name),
+                requested.map(format).unwrap_or_default(),
+                actual.map(format),
+                status,
+            );
+        }
+    }
+
+    /// Navigation rate, from the UBX-CFG-RATE poll response
+    pub fn rate(&mut self, response: Option<&[u8]>) {
+        let rate = response.and_then(|payload| {
+            let meas_rate = u16::from_le_bytes([*payload.first()?, *payload.get(1)?]);
+            let nav_rate = u16::from_le_bytes([*payload.get(2)?, *payload.get(3)?]);
+            Some(format!("{} ms, navRate {}", meas_rate, nav_rate))
+        });
+        let status = if rate.is_some() {
+            Status::Reported
+        } else {
+            Status::Unsupported
+        };
+        self.push("CFG-RATE".to_string(), "-".to_string(), rate, status);
+    }
+
+    /// Number of items not verified, among the requested ones
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.status, Status::Mismatched | Status::Unsupported))
+            .count()
+    }
+
+    pub fn print(&self) {
+        info!(
+            "{:<32} {:<14} {:<28} status",
+            "item", "requested", "receiver"
+        );
+        for check in &self.checks {
+            info!(
+                "{:<32} {:<14} {:<28} {}",
+                check.item, check.requested, check.actual, check.status
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Item, requested, actual and status of each check
+    fn checks(verification: &Verification) -> Vec<(&str, &str, &str, Status)> {
+        verification
+            .checks
+            .iter()
+            .map(|check| {
+                (check.item.as_str(), check.requested.as_str(), check.actual.as_str(), check.status)
+            })
+            .collect()
+    }
+
+    /// UBX-CFG-PRT payload of a UART port, 8N1
+    fn uart(baud: u32, in_mask: u16, out_mask: u16) -> Vec<u8> {
+        let mut payload = vec![1, 0, 0, 0, 0xc0, 0x08, 0, 0];
+        payload.extend_from_slice(&baud.to_le_bytes());
+        payload.extend_from_slice(&in_mask.to_le_bytes());
+        payload.extend_from_slice(&out_mask.to_le_bytes());
+        payload.extend_from_slice(&[0; 4]);
+        payload
+    }
+
+    #[test]
+    fn message_rates() {
+        let mut verification = Verification::default();
+        let requested = [0, 1, 0, 1, 0, 0];
+        verification.message_rates("NAV-PVT", requested, Some(&[0x01, 0x07, 0, 1, 0, 1, 0, 0]));
+        // clamped by the receiver
+        verification.message_rates("NAV-SAT", requested, Some(&[0x01, 0x35, 0, 0, 0, 1, 0, 0]));
+        // short response
+        verification.message_rates("NAV-SIG", requested, Some(&[0x01, 0x43, 0]));
+        verification.message_rates("RXM-RAWX", requested, None);
+        assert_eq!(
+            checks(&verification),
+            [
+                ("CFG-MSG NAV-PVT rates", "0 1 0 1 0 0", "0 1 0 1 0 0", Status::Verified),
+                ("CFG-MSG NAV-SAT rates", "0 1 0 1 0 0", "0 0 0 1 0 0", Status::Mismatched),
+                ("CFG-MSG NAV-SIG rates", "0 1 0 1 0 0", "-", Status::Unsupported),
+                ("CFG-MSG RXM-RAWX rates", "0 1 0 1 0 0", "-", Status::Unsupported),
+            ]
+        );
+        assert_eq!(verification.failures(), 3);
+    }
+
+    #[test]
+    fn out_protocols() {
+        let mut verification = Verification::default();
+        verification.out_protocols(Some(0x01), Some(&uart(9600, 0x07, 0x01)));
+        verification.out_protocols(Some(0x01), Some(&uart(9600, 0x07, 0x03)));
+        verification.out_protocols(None, Some(&uart(9600, 0x07, 0x23)));
+        verification.out_protocols(Some(0x01), None);
+        assert_eq!(
+            checks(&verification),
+            [
+                ("CFG-PRT port 1 outProtoMask", "0x0001", "0x0001", Status::Verified),
+                ("CFG-PRT port 1 outProtoMask", "0x0001", "0x0003", Status::Mismatched),
+                ("CFG-PRT port 1 outProtoMask", "-", "0x0023", Status::Reported),
+                ("CFG-PRT outProtoMask", "0x0001", "-", Status::Unsupported),
+            ]
+        );
+        assert_eq!(verification.failures(), 2);
+    }
+
+    #[test]
+    fn port_settings() {
+        let mut verification = Verification::default();
+        let written = uart(115200, 0x01, 0x01);
+        // the receiver kept its baud rate and NMEA input
+        verification.port_settings(&written, Some(&uart(9600, 0x03, 0x01)));
+        assert_eq!(
+            checks(&verification),
+            [
+                ("CFG-PRT port 1 mode", "0x8c0", "0x8c0", Status::Verified),
+                ("CFG-PRT port 1 baudRate", "115200", "9600", Status::Mismatched),
+                ("CFG-PRT port 1 inProtoMask", "0x1", "0x3", Status::Mismatched),
+                ("CFG-PRT port 1 outProtoMask", "0x1", "0x1", Status::Verified),
+            ]
+        );
+        let mut verification = Verification::default();
+        verification.port_settings(&written, None);
+        assert_eq!(verification.failures(), 4);
+    }
+
+    #[test]
+    fn rate_reported() {
+        let mut verification = Verification::default();
+        verification.rate(Some(&[0xc8, 0x00, 0x01, 0x00, 0x01, 0x00]));
+        verification.rate(Some(&[0xc8]));
+        assert_eq!(
+            checks(&verification),
+            [
+                ("CFG-RATE", "-", "200 ms, navRate 1", Status::Reported),
+                ("CFG-RATE", "-", "-", Status::Unsupported),
+            ]
+        );
+        assert_eq!(verification.failures(), 1);
+    }
+}
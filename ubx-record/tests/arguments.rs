@@ -0,0 +1,28 @@
+//! Combinations of arguments rejected before the port is opened
+
+use std::process::Command;
+
+/// Runs the recorder with the arguments, returning its exit code and error output
+fn run(arguments: &[&str]) -> (Option<i32>, String) {
+    let run = Command::new(env!("CARGO_BIN_EXE_ubx-record"))
+        .args(["-p", "tcp://127.0.0.1:9"])
+        .args(arguments)
+        .output()
+        .unwrap();
+    (run.status.code(), String::from_utf8_lossy(&run.stderr).into_owned())
+}
+
+#[test]
+fn start_on_single_output() {
+    // the outputs --start-on does not record into
+    for option in [
+        &["--rotate-interval", "1h"][..],
+        &["--split-by-class"],
+        &["--timestamps"],
+        &["--protocols", "ubx,nmea"],
+    ] {
+        let (code, log) = run(&[&["--start-on", "rtk-fixed"], option].concat());
+        assert_eq!(code, Some(2), "{:?}: {}", option, log);
+        assert!(log.contains("cannot be used with"), "{:?}: {}", option, log);
+    }
+}
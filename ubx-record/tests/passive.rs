@@ -0,0 +1,137 @@
+//! Passive recording (`--no-configure`): the recorder runs against a fake receiver served
+//! over TCP by the test, which streams NAV-PVT frames and counts the bytes written to it
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+mod common;
+use common::frame;
+
+/// Receiver sending a NAV-PVT every 50 ms and acknowledging the CFG messages it receives,
+/// until stopped. Returns the bytes the recorder wrote
+fn fake_receiver(listener: TcpListener, stop: Arc<AtomicBool>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        let mut received = vec![];
+        // frames of the recorder handled so far
+        let mut scanned = 0;
+        let mut itow = 0_u32;
+        let mut buf = [0; 1024];
+        while !stop.load(Ordering::Relaxed) {
+            let mut pvt = [0; 92];
+            pvt[..4].copy_from_slice(&itow.to_le_bytes());
+            itow += 50;
+            if stream.write_all(&frame(0x01, 0x07, &pvt)).is_err() {
+                break;
+            }
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => received.extend_from_slice(&buf[..size]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {},
+                Err(_) => break,
+            }
+            // acknowledge the CFG frames received whole
+            while let [0xb5, 0x62, class, id, len_lo, len_hi, ..] = received[scanned..] {
+                let end = scanned + 8 + usize::from(u16::from_le_bytes([len_lo, len_hi]));
+                if end > received.len() {
+                    break;
+                }
+                if class == 0x06 {
+                    let _ = stream.write_all(&frame(0x05, 0x01, &[class, id]));
+                }
+                scanned = end;
+            }
+        }
+        received
+    })
+}
+
+/// Records for a second, returning whether the recorder succeeded, the bytes it wrote to
+/// the receiver and the size of the recording
+fn record(arguments: &[&str]) -> (bool, Vec<u8>, u64) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let stop = Arc::new(AtomicBool::new(false));
+    let receiver = fake_receiver(listener, stop.clone());
+
+    let output = std::env::temp_dir().join(format!(
+        "ubx-record-passive-{}-{}.ubx",
+        std::process::id(),
+        address.port()
+    ));
+    let status = Command::new(env!("CARGO_BIN_EXE_ubx-record"))
+        .args(["-p", &format!("tcp://{}", address), "--duration", "1s", "--ack-timeout", "1s"])
+        .arg("-o")
+        .arg(&output)
+        .args(arguments)
+        .arg("--quiet")
+        .status()
+        .unwrap();
+    stop.store(true, Ordering::Relaxed);
+    let written = receiver.join().unwrap();
+    let recorded = std::fs::metadata(&output).map_or(0, |metadata| metadata.len());
+    let _ = std::fs::remove_file(&output);
+    (status.success(), written, recorded)
+}
+
+/// Class, id and payload length of the frames written to the receiver
+fn frames(written: &[u8]) -> Vec<(u8, u8, usize)> {
+    let mut frames = vec![];
+    let mut rest = written;
+    while let [0xb5, 0x62, class, id, len_lo, len_hi, ..] = *rest {
+        let len = usize::from(u16::from_le_bytes([len_lo, len_hi]));
+        frames.push((class, id, len));
+        rest = &rest[(8 + len).min(rest.len())..];
+    }
+    assert!(rest.is_empty(), "{} bytes written outside frames", rest.len());
+    frames
+}
+
+#[test]
+fn passive_writes_nothing() {
+    let (success, written, recorded) = record(&["--no-configure"]);
+    assert!(success);
+    assert!(written.is_empty(), "{} bytes written to the receiver", written.len());
+    // the frames were recorded nonetheless
+    assert!(recorded > 0);
+}
+
+#[test]
+fn configuring_writes() {
+    // the same recording, NAV-PVT being enabled on all ports
+    let (success, written, recorded) = record(&[]);
+    assert!(success);
+    assert!(written.starts_with(&[0xb5, 0x62]));
+    assert!(recorded > 0);
+    // the receiver identification is polled once
+    let polls = frames(&written).into_iter().filter(|frame| *frame == (0x0a, 0x04, 0)).count();
+    assert_eq!(polls, 1);
+}
+
+#[test]
+fn passive_keeps_the_odometer() {
+    let (success, written, _) = record(&["--no-configure", "--reset-odometer"]);
+    assert!(success);
+    assert!(written.is_empty(), "{:?} written to the receiver", frames(&written));
+}
+
+#[test]
+fn dry_run_keeps_the_odometer() {
+    let (success, written, _) = record(&["--dry-run", "--reset-odometer"]);
+    assert!(success);
+    // only the polls of the receiver identification (MON-VER, SEC-UNIQID), the
+    // UBX-NAV-RESETODO having no payload either
+    let written = frames(&written);
+    assert!(!written.is_empty());
+    assert!(
+        written.iter().all(|frame| matches!(frame, (0x0a, 0x04, 0) | (0x27, 0x03, 0))),
+        "{:?} written",
+        written
+    );
+}
@@ -0,0 +1,121 @@
+//! Recording of short reads: a fake receiver served over TCP by the test writes its data in
+//! small pieces, and the recording holds exactly the bytes received, no read buffer padding.
+//! The first bytes are read by the line check, before the recording starts. An output that
+//! cannot be created, or the existing segment of a rotation, ends the recorder with an error
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::process::Command;
+use std::time::Duration;
+
+mod common;
+use common::frame;
+
+#[test]
+fn short_reads_recorded_exactly() {
+    let epoch = [
+        b"\x00\xff".as_slice(),
+        &frame(0x01, 0x07, &[0x5a; 92]),
+        b"$GNGGA,,,,,,0,00,99.99,,,,,,*56\r\n",
+        &frame(0x0a, 0x04, &[0x30; 40]),
+    ]
+    .concat();
+    // distinct epochs, a repeated fragment showing in the comparison
+    let data = (0..20_u8)
+        .flat_map(|n| epoch.iter().map(move |byte| byte ^ n))
+        .collect::<Vec<_>>();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let sent = data.clone();
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // reads of a few bytes, a read buffer of 2048 would show stale data otherwise
+        for piece in sent.chunks(7) {
+            stream.write_all(piece).unwrap();
+            stream.flush().unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        // kept open until the recorder leaves
+        let _ = std::io::copy(&mut stream, &mut std::io::sink());
+    });
+
+    let output = std::env::temp_dir().join(format!(
+        "ubx-record-streaming-{}-{}.ubx",
+        std::process::id(),
+        address.port()
+    ));
+    let run = Command::new(env!("CARGO_BIN_EXE_ubx-record"))
+        .args(["-p", &format!("tcp://{}", address), "--duration", "3s", "--no-configure"])
+        .arg("-o")
+        .arg(&output)
+        .output()
+        .unwrap();
+    receiver.join().unwrap();
+    let recorded = std::fs::read(&output).unwrap();
+    let _ = std::fs::remove_file(&output);
+    assert!(run.status.success());
+    assert!(!recorded.is_empty());
+    assert!(data.ends_with(&recorded));
+    // the recording is the size of the data received, as reported by the summary
+    let log = String::from_utf8_lossy(&run.stderr);
+    let summary = format!(", {} bytes received,", recorded.len());
+    assert!(log.contains(&summary), "{}", log);
+}
+
+/// Runs the recorder for a second with the arguments, against a receiver sending a NAV-PVT
+fn run(arguments: &[&std::ffi::OsStr]) -> std::process::Output {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.write_all(&frame(0x01, 0x07, &[0; 92]));
+        let _ = std::io::copy(&mut stream, &mut std::io::sink());
+    });
+    let run = Command::new(env!("CARGO_BIN_EXE_ubx-record"))
+        .args(["-p", &format!("tcp://{}", address), "--duration", "1s", "--no-configure"])
+        .args(arguments)
+        .output()
+        .unwrap();
+    receiver.join().unwrap();
+    run
+}
+
+#[test]
+fn unwritable_output_exits() {
+    let output = std::env::temp_dir()
+        .join(format!("ubx-record-missing-{}", std::process::id()))
+        .join("output.ubx");
+    let run = run(&["-o".as_ref(), output.as_os_str()]);
+    // an error and a failure exit code, not a panic
+    let log = String::from_utf8_lossy(&run.stderr);
+    assert_eq!(run.status.code(), Some(1), "{}", log);
+    assert!(log.contains("failed to create file"), "{}", log);
+    assert!(!log.contains("panicked"), "{}", log);
+}
+
+#[test]
+fn existing_segment_kept() {
+    let directory =
+        std::env::temp_dir().join(format!("ubx-record-segments-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let template = directory.join("station.ubx");
+    // hourly segments aligned on UTC, the one of this hour and of the next one existing
+    let now = chrono::Utc::now();
+    for time in [now, now + chrono::Duration::hours(1)] {
+        let name = format!("station-{}.ubx", time.format("%Y-%m-%dT%H"));
+        std::fs::write(directory.join(name), b"recorded").unwrap();
+    }
+    let arguments = ["--rotate-interval", "1h", "--rotate-align", "utc", "-o"];
+    let arguments: Vec<&std::ffi::OsStr> =
+        arguments.iter().map(|argument| argument.as_ref()).chain([template.as_os_str()]).collect();
+    let run = run(&arguments);
+    let kept = std::fs::read_dir(&directory)
+        .unwrap()
+        .all(|entry| std::fs::read(entry.unwrap().path()).unwrap() == b"recorded");
+    let _ = std::fs::remove_dir_all(&directory);
+    let log = String::from_utf8_lossy(&run.stderr);
+    assert_eq!(run.status.code(), Some(1), "{}", log);
+    assert!(log.contains("exists, use --force to overwrite it"), "{}", log);
+    assert!(kept);
+}